@@ -37,6 +37,7 @@ install -p -m 644 -D vendor/results_template/js/* %{buildroot}%{_datadir}/%{name
 install -p -m 755 -D vendor/*.jar %{buildroot}%{_datadir}/%{name}/vendor/
 install -p -m 644 -D vendor/*.txt %{buildroot}%{_datadir}/%{name}/vendor/
 install -p -m 644 rules.json %{buildroot}%{_sysconfdir}/%{name}/
+install -p -m 644 vulnerabilities.json %{buildroot}%{_sysconfdir}/%{name}/
 install -p -m 644 config.toml %{buildroot}%{_sysconfdir}/%{name}/
 install -p -m 644 config.toml.sample %{buildroot}%{_sysconfdir}/%{name}/
 install -p -m 644 README.md %{buildroot}%{_defaultdocdir}/%{name}/
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use {Config, Result, Error, print_warning, file_exists};
+
+/// Device connected through ADB from which applications are pulled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    serial: Option<String>,
+}
+
+impl Device {
+    /// Creates a device with the given serial, or the only connected device if there is none
+    pub fn new<S: Into<String>>(serial: Option<S>) -> Device {
+        Device { serial: serial.map(|s| s.into()) }
+    }
+
+    /// Creates an `adb` command targeting the device
+    fn command(&self) -> Command {
+        let mut command = Command::new("adb");
+        if let Some(ref serial) = self.serial {
+            command.arg("-s").arg(serial);
+        }
+        command
+    }
+
+    /// Runs the given `adb` arguments on the device, and gets its standard output
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = try!(self.command().args(args).output());
+        if !output.status.success() {
+            print_warning(format!("adb returned an error: {}",
+                                  String::from_utf8_lossy(&output.stderr[..]).trim()),
+                          false);
+            return Err(Error::Unknown);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout[..]).into_owned())
+    }
+
+    /// Lists the third party packages installed in the device
+    pub fn list_packages(&self) -> Result<Vec<String>> {
+        let output = try!(self.run(&["shell", "pm", "list", "packages", "-3"]));
+        Ok(parse_package_list(&output))
+    }
+
+    /// Pulls the APK files of the given package to the downloads folder
+    ///
+    /// The base APK is stored as `{package}.apk`, so that it's analyzed as any downloaded
+    /// application, and each split APK as `{package}-{split}.apk`, so that its resources are
+    /// analyzed with the rest of the expansion files. It returns the number of pulled files.
+    pub fn pull_package(&self, package: &str, config: &Config) -> Result<usize> {
+        let output = try!(self.run(&["shell", "pm", "path", package]));
+        let paths = parse_package_paths(&output);
+        if paths.is_empty() {
+            return Err(Error::AppNotExists);
+        }
+
+        let folder = PathBuf::from(config.get_downloads_folder());
+        if !file_exists(&folder) {
+            try!(fs::create_dir_all(&folder));
+        }
+        for path in &paths {
+            let file_name = match get_split_name(path) {
+                Some(split) => format!("{}-{}.apk", package, split),
+                None => format!("{}.apk", package),
+            };
+            let destination = folder.join(file_name);
+            if config.is_verbose() {
+                println!("Pulling {} to {}.", path, destination.display());
+            }
+            let destination = format!("{}", destination.display());
+            let _ = try!(self.run(&["pull", path.as_str(), destination.as_str()]));
+        }
+        Ok(paths.len())
+    }
+}
+
+/// Parses the output of `pm list packages`, with a `package:{name}` line per package
+fn parse_package_list(output: &str) -> Vec<String> {
+    let mut packages: Vec<_> = output.lines()
+        .filter_map(strip_package_prefix)
+        .collect();
+    packages.sort();
+    packages
+}
+
+/// Parses the output of `pm path`, with a `package:{path}` line per APK of the package
+fn parse_package_paths(output: &str) -> Vec<String> {
+    output.lines()
+        .filter_map(strip_package_prefix)
+        .filter(|p| p.ends_with(".apk"))
+        .collect()
+}
+
+/// Removes the `package:` prefix of a line of the package manager output
+fn strip_package_prefix(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.starts_with("package:") && line.len() > "package:".len() {
+        Some(String::from(&line["package:".len()..]))
+    } else {
+        None
+    }
+}
+
+/// Gets the name of the split of an APK path in the device, if it's not the base APK
+///
+/// Splits are stored as `split_{name}.apk` next to the `base.apk` of the application.
+fn get_split_name(path: &str) -> Option<&str> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if file_name.starts_with("split_") && file_name.len() > "split_.apk".len() {
+        Some(&file_name["split_".len()..file_name.len() - ".apk".len()])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_package_list, parse_package_paths, get_split_name};
+
+    #[test]
+    fn it_parse_package_list() {
+        let output = "package:com.example.notes\r\npackage:com.example.bank\n\nerror line\n";
+        assert_eq!(parse_package_list(output),
+                   vec![String::from("com.example.bank"), String::from("com.example.notes")]);
+        assert!(parse_package_list("").is_empty());
+    }
+
+    #[test]
+    fn it_parse_package_paths() {
+        let output = "package:/data/app/com.example.bank-1/base.apk\n\
+                      package:/data/app/com.example.bank-1/split_config.arm64_v8a.apk\n";
+        let paths = parse_package_paths(output);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(get_split_name(&paths[0]), None);
+        assert_eq!(get_split_name(&paths[1]), Some("config.arm64_v8a"));
+        assert_eq!(get_split_name("/data/app/split_.apk"), None);
+    }
+}
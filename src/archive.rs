@@ -0,0 +1,487 @@
+//! Hardened reader of ZIP archives, such as the `.apk` file of the application and its expansion
+//! files.
+//!
+//! Entries are read from the central directory, as Android does, so that the tricks that
+//! malicious applications use against analysis tools don't stop the analysis: the encryption
+//! flag of the general purpose bits is ignored, and entries with any compression method other
+//! than stored are inflated. Archives with duplicate entries are rejected, as Android does, since
+//! a tool could analyze a different entry than the one Android loads.
+
+use std::{cmp, fmt, fs, io};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::path::{Path, Component};
+
+use flate2::read::DeflateDecoder;
+
+use axml::{read_u16, read_u32};
+use config::ArchiveConfig;
+use {Config, Result, Error};
+
+/// Signatures of the end of central directory record, the central directory headers and the
+/// local file headers
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x02014b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+
+/// Sizes of the fixed parts of the records
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+
+/// Maximum size of the comment of the archive, after the end of central directory record
+const MAX_COMMENT_SIZE: usize = 0xFFFF;
+
+/// Compression method of the entries that are not compressed
+const METHOD_STORED: u16 = 0;
+
+/// Minimum size of an entry to be considered a decompression bomb, so that small and very
+/// repetitive files are not reported
+const MIN_BOMB_SIZE: u64 = 1024 * 1024;
+
+/// Safety limit exceeded by an archive, that stops its extraction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveViolation {
+    TooManyEntries(usize),
+    TooLarge(u64),
+    CompressionRatio(String, u64),
+    UnsafePath(String),
+    DuplicateEntry(String),
+}
+
+impl fmt::Display for ArchiveViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArchiveViolation::TooManyEntries(max) => {
+                write!(f, "the archive has more than {} entries", max)
+            }
+            ArchiveViolation::TooLarge(max) => {
+                write!(f, "the archive contents are bigger than {} MiB", max / 1024 / 1024)
+            }
+            ArchiveViolation::CompressionRatio(ref entry, max) => {
+                write!(f,
+                       "the entry `{}` has a compression ratio higher than {}",
+                       entry,
+                       max)
+            }
+            ArchiveViolation::UnsafePath(ref entry) => {
+                write!(f, "the entry `{}` would be extracted outside its folder", entry)
+            }
+            ArchiveViolation::DuplicateEntry(ref entry) => {
+                write!(f, "the archive has more than one `{}` entry", entry)
+            }
+        }
+    }
+}
+
+/// Result of extracting an archive
+#[derive(Debug, Clone, Default)]
+pub struct Extraction {
+    entries: usize,
+    size: u64,
+    violation: Option<ArchiveViolation>,
+}
+
+impl Extraction {
+    /// Gets the number of extracted entries
+    pub fn get_entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Gets the total size of the extracted entries
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Gets the safety limit that stopped the extraction, if any
+    pub fn get_violation(&self) -> Option<&ArchiveViolation> {
+        self.violation.as_ref()
+    }
+}
+
+/// Entry of the central directory of an archive
+#[derive(Debug, Clone)]
+pub struct Entry {
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    size: u64,
+    offset: u64,
+}
+
+impl Entry {
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Checks if the entry is compressed, which is the case for any compression method other
+    /// than stored, since Android inflates all of them
+    fn is_compressed(&self) -> bool {
+        self.method != METHOD_STORED
+    }
+}
+
+/// ZIP archive, read respecting the archive safety limits
+pub struct Archive<R: Read + Seek> {
+    reader: R,
+    entries: Vec<Entry>,
+    limits: ArchiveConfig,
+}
+
+impl Archive<File> {
+    /// Opens the archive at the given path
+    pub fn open<P: AsRef<Path>>(path: P, limits: &ArchiveConfig) -> Result<Archive<File>> {
+        Archive::new(try!(File::open(path)), limits)
+    }
+}
+
+impl<R: Read + Seek> Archive<R> {
+    /// Reads the central directory of the archive
+    ///
+    /// Archives with more entries than the limit, or with duplicate entries, are rejected.
+    pub fn new(mut reader: R, limits: &ArchiveConfig) -> Result<Archive<R>> {
+        let length = try!(reader.seek(SeekFrom::End(0)));
+        let tail_size = cmp::min(length,
+                                 (END_OF_CENTRAL_DIRECTORY_SIZE + MAX_COMMENT_SIZE) as u64);
+        let _ = try!(reader.seek(SeekFrom::Start(length - tail_size)));
+        let mut tail = Vec::with_capacity(tail_size as usize);
+        let _ = try!((&mut reader).take(tail_size).read_to_end(&mut tail));
+
+        let end = match find_end_of_central_directory(&tail) {
+            Some(end) => end,
+            None => return Err(Error::ParseError),
+        };
+        let count = try!(read_u16(&tail, end + 10)) as usize;
+        let directory_size = try!(read_u32(&tail, end + 12)) as u64;
+        let directory_offset = try!(read_u32(&tail, end + 16)) as u64;
+        let max_entries = limits.get_max_entries();
+        if count > max_entries {
+            return Err(Error::Archive(ArchiveViolation::TooManyEntries(max_entries)));
+        }
+        if directory_offset + directory_size > length {
+            return Err(Error::ParseError);
+        }
+
+        let _ = try!(reader.seek(SeekFrom::Start(directory_offset)));
+        let mut directory = Vec::with_capacity(directory_size as usize);
+        let _ = try!((&mut reader).take(directory_size).read_to_end(&mut directory));
+        let entries = try!(parse_central_directory(&directory, count));
+        if let Some(name) = find_duplicate_entry(&entries) {
+            return Err(Error::Archive(ArchiveViolation::DuplicateEntry(String::from(name))));
+        }
+
+        Ok(Archive {
+            reader: reader,
+            entries: entries,
+            limits: limits.clone(),
+        })
+    }
+
+    /// Gets the entries of the archive, in the order of its central directory
+    pub fn get_entries(&self) -> &[Entry] {
+        self.entries.as_slice()
+    }
+
+    /// Checks the declared sizes and the paths of the entries against the archive safety limits,
+    /// before the archive is extracted by an external tool
+    ///
+    /// The declared sizes are not trusted when the entries are read, so this only detects the
+    /// archives that don't hide their size.
+    pub fn check_limits(&self) -> Option<ArchiveViolation> {
+        let max_ratio = self.limits.get_max_compression_ratio();
+        let mut size = 0u64;
+        for entry in &self.entries {
+            if !is_safe_entry_path(&entry.name) {
+                return Some(ArchiveViolation::UnsafePath(entry.name.clone()));
+            }
+            if entry.is_compressed() &&
+               exceeds_compression_ratio(entry.size, entry.compressed_size, max_ratio) {
+                return Some(ArchiveViolation::CompressionRatio(entry.name.clone(), max_ratio));
+            }
+            size = size.saturating_add(entry.size);
+        }
+        if size > self.limits.get_max_size() {
+            Some(ArchiveViolation::TooLarge(self.limits.get_max_size()))
+        } else {
+            None
+        }
+    }
+
+    /// Reads the entry with the given name
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>> {
+        let entry = match self.entries.iter().find(|e| e.name == name) {
+            Some(e) => e.clone(),
+            None => {
+                return Err(Error::IOError(io::Error::new(io::ErrorKind::NotFound,
+                                                         format!("the entry `{}` does not \
+                                                                  exist in the archive",
+                                                                 name))));
+            }
+        };
+        let max_size = self.limits.get_max_size();
+        self.read_entry(&entry, max_size)
+    }
+
+    /// Reads the data of the entry, stopping if it's bigger than the given size or if it's
+    /// compressed more than the maximum compression ratio
+    fn read_entry(&mut self, entry: &Entry, max_size: u64) -> Result<Vec<u8>> {
+        let _ = try!(self.reader.seek(SeekFrom::Start(entry.offset)));
+        let mut header = [0u8; LOCAL_HEADER_SIZE];
+        try!(self.reader.read_exact(&mut header));
+        if try!(read_u32(&header, 0)) != LOCAL_HEADER_SIGNATURE {
+            return Err(Error::ParseError);
+        }
+        // The data starts after the name and the extra field of the local header, that can
+        // differ from the ones of the central directory
+        let skip = try!(read_u16(&header, 26)) as i64 + try!(read_u16(&header, 28)) as i64;
+        let _ = try!(self.reader.seek(SeekFrom::Current(skip)));
+
+        let mut bytes = Vec::new();
+        if !entry.is_compressed() {
+            // As in Android, the uncompressed size is used for stored entries
+            let _ = try!((&mut self.reader)
+                .take(cmp::min(entry.size, max_size + 1))
+                .read_to_end(&mut bytes));
+        } else {
+            let data = (&mut self.reader).take(entry.compressed_size);
+            let _ = try!(DeflateDecoder::new(data).take(max_size + 1).read_to_end(&mut bytes));
+        }
+
+        if bytes.len() as u64 > max_size {
+            return Err(Error::Archive(ArchiveViolation::TooLarge(self.limits.get_max_size())));
+        }
+        let max_ratio = self.limits.get_max_compression_ratio();
+        if entry.is_compressed() &&
+           exceeds_compression_ratio(bytes.len() as u64, entry.compressed_size, max_ratio) {
+            return Err(Error::Archive(ArchiveViolation::CompressionRatio(entry.name.clone(),
+                                                                         max_ratio)));
+        }
+        Ok(bytes)
+    }
+
+    /// Extracts the archive in the given folder, respecting the archive safety limits
+    ///
+    /// If a limit is exceeded, the extraction stops, and the entries extracted until then are
+    /// kept. The declared sizes of the entries are not trusted, so the limits are also checked
+    /// while reading them.
+    pub fn extract(&mut self, to: &Path) -> Result<Extraction> {
+        let mut extraction: Extraction = Default::default();
+        let max_ratio = self.limits.get_max_compression_ratio();
+        for entry in self.entries.clone() {
+            if !is_safe_entry_path(&entry.name) {
+                extraction.violation = Some(ArchiveViolation::UnsafePath(entry.name));
+                return Ok(extraction);
+            }
+            if entry.is_compressed() &&
+               exceeds_compression_ratio(entry.size, entry.compressed_size, max_ratio) {
+                extraction.violation = Some(ArchiveViolation::CompressionRatio(entry.name,
+                                                                               max_ratio));
+                return Ok(extraction);
+            }
+
+            let path = to.join(&entry.name);
+            if entry.name.ends_with('/') {
+                try!(fs::create_dir_all(&path));
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                try!(fs::create_dir_all(parent));
+            }
+
+            let remaining = self.limits.get_max_size().saturating_sub(extraction.size);
+            let bytes = match self.read_entry(&entry, remaining) {
+                Ok(bytes) => bytes,
+                Err(Error::Archive(violation)) => {
+                    extraction.violation = Some(violation);
+                    return Ok(extraction);
+                }
+                Err(e) => return Err(e),
+            };
+
+            let mut f = try!(File::create(&path));
+            try!(f.write_all(&bytes));
+            extraction.entries += 1;
+            extraction.size += bytes.len() as u64;
+        }
+        Ok(extraction)
+    }
+}
+
+/// Finds the end of central directory record in the tail of the archive, searching backwards
+/// from the end, since the archive comment could contain its signature
+fn find_end_of_central_directory(tail: &[u8]) -> Option<usize> {
+    if tail.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+        return None;
+    }
+    (0..tail.len() - END_OF_CENTRAL_DIRECTORY_SIZE + 1).rev().find(|&i| {
+        read_u32(tail, i).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE) &&
+        read_u16(tail, i + 20)
+            .map(|comment| i + END_OF_CENTRAL_DIRECTORY_SIZE + comment as usize <= tail.len())
+            .unwrap_or(false)
+    })
+}
+
+/// Parses the given number of entries of the central directory
+fn parse_central_directory(directory: &[u8], count: usize) -> Result<Vec<Entry>> {
+    let mut entries = Vec::with_capacity(count);
+    let mut offset = 0;
+    for _ in 0..count {
+        if try!(read_u32(directory, offset)) != CENTRAL_DIRECTORY_SIGNATURE {
+            return Err(Error::ParseError);
+        }
+        let name_start = offset + CENTRAL_DIRECTORY_HEADER_SIZE;
+        let name_end = name_start + try!(read_u16(directory, offset + 28)) as usize;
+        if name_end > directory.len() {
+            return Err(Error::ParseError);
+        }
+        entries.push(Entry {
+            name: String::from_utf8_lossy(&directory[name_start..name_end]).into_owned(),
+            method: try!(read_u16(directory, offset + 10)),
+            compressed_size: try!(read_u32(directory, offset + 20)) as u64,
+            size: try!(read_u32(directory, offset + 24)) as u64,
+            offset: try!(read_u32(directory, offset + 42)) as u64,
+        });
+        offset = name_end + try!(read_u16(directory, offset + 30)) as usize +
+                 try!(read_u16(directory, offset + 32)) as usize;
+    }
+    Ok(entries)
+}
+
+/// Finds the first entry name that appears more than once in the central directory
+fn find_duplicate_entry(entries: &[Entry]) -> Option<&str> {
+    let mut names = BTreeSet::new();
+    entries.iter().map(|e| e.name.as_str()).find(|name| !names.insert(*name))
+}
+
+/// Checks that the archive entry would be extracted inside the destination folder
+fn is_safe_entry_path(name: &str) -> bool {
+    !name.is_empty() && !name.contains('\\') &&
+    Path::new(name).components().all(|c| match c {
+        Component::Normal(_) | Component::CurDir => true,
+        _ => false,
+    })
+}
+
+/// Checks if the entry is compressed more than the maximum compression ratio
+fn exceeds_compression_ratio(size: u64, compressed_size: u64, max_ratio: u64) -> bool {
+    size > compressed_size.saturating_mul(max_ratio) && size > MIN_BOMB_SIZE
+}
+
+/// Reads the file with the given name from the `.apk` file of the application
+pub fn read_apk_file(config: &Config, name: &str) -> Result<Vec<u8>> {
+    let mut apk = try!(Archive::open(format!("{}/{}.apk",
+                                             config.get_downloads_folder(),
+                                             config.get_app_id()),
+                                     config.get_archive_config()));
+    apk.read(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+
+    use axml::tests::{push_u16, push_u32};
+    use config::ArchiveConfig;
+    use Error;
+    use super::{Archive, ArchiveViolation, is_safe_entry_path, exceeds_compression_ratio};
+
+    /// Builds a ZIP archive with the given entries: their names, general purpose flags,
+    /// compression methods, and uncompressed and stored data
+    fn build_archive(entries: &[(&str, u16, u16, &[u8], &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, flags, method, data, stored) in entries {
+            let offset = archive.len() as u32;
+            push_u32(&mut archive, 0x04034b50);
+            push_u16(&mut archive, 20);
+            push_u16(&mut archive, flags);
+            push_u16(&mut archive, method);
+            archive.extend_from_slice(&[0u8; 8]);
+            push_u32(&mut archive, stored.len() as u32);
+            push_u32(&mut archive, data.len() as u32);
+            push_u16(&mut archive, name.len() as u16);
+            push_u16(&mut archive, 0);
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(stored);
+
+            push_u32(&mut directory, 0x02014b50);
+            push_u16(&mut directory, 20);
+            push_u16(&mut directory, 20);
+            push_u16(&mut directory, flags);
+            push_u16(&mut directory, method);
+            directory.extend_from_slice(&[0u8; 8]);
+            push_u32(&mut directory, stored.len() as u32);
+            push_u32(&mut directory, data.len() as u32);
+            push_u16(&mut directory, name.len() as u16);
+            directory.extend_from_slice(&[0u8; 12]);
+            push_u32(&mut directory, offset);
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let directory_offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        push_u32(&mut archive, 0x06054b50);
+        push_u32(&mut archive, 0);
+        push_u16(&mut archive, entries.len() as u16);
+        push_u16(&mut archive, entries.len() as u16);
+        push_u32(&mut archive, directory.len() as u32);
+        push_u32(&mut archive, directory_offset);
+        push_u16(&mut archive, 0);
+        archive
+    }
+
+    #[test]
+    fn it_read_archive() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::Default);
+        encoder.write_all(b"classes").unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        // The encryption flag is ignored, and unknown compression methods are inflated, as
+        // Android does
+        let data = build_archive(&[("AndroidManifest.xml", 0, 0, b"manifest", b"manifest"),
+                                   ("classes.dex", 0x0001, 0x63, b"classes", &deflated),
+                                   ("../../evil.sh", 0, 0, b"", b"")]);
+        let limits: ArchiveConfig = Default::default();
+        let mut archive = Archive::new(Cursor::new(data), &limits).unwrap();
+        assert_eq!(archive.get_entries().len(), 3);
+        assert_eq!(archive.read("AndroidManifest.xml").unwrap(), b"manifest".to_vec());
+        assert_eq!(archive.read("classes.dex").unwrap(), b"classes".to_vec());
+        assert!(archive.read("resources.arsc").is_err());
+        assert_eq!(archive.check_limits(),
+                   Some(ArchiveViolation::UnsafePath(String::from("../../evil.sh"))));
+
+        let data = build_archive(&[("classes.dex", 0, 0, b"first", b"first"),
+                                   ("classes.dex", 0, 0, b"second", b"second")]);
+        match Archive::new(Cursor::new(data), &limits) {
+            Err(Error::Archive(ArchiveViolation::DuplicateEntry(name))) => {
+                assert_eq!(name, "classes.dex")
+            }
+            _ => panic!("the duplicate entry was not detected"),
+        }
+
+        assert!(Archive::new(Cursor::new(b"PK\x03\x04".to_vec()), &limits).is_err());
+    }
+
+    #[test]
+    fn it_is_safe_entry_path() {
+        assert!(is_safe_entry_path("assets/config.json"));
+        assert!(is_safe_entry_path("./assets/"));
+        assert!(!is_safe_entry_path("../config.json"));
+        assert!(!is_safe_entry_path("assets/../../config.json"));
+        assert!(!is_safe_entry_path("/etc/passwd"));
+        assert!(!is_safe_entry_path("assets\\..\\config.json"));
+        assert!(!is_safe_entry_path(""));
+    }
+
+    #[test]
+    fn it_exceeds_compression_ratio() {
+        assert!(!exceeds_compression_ratio(1000, 1, 100));
+        assert!(!exceeds_compression_ratio(10 * 1024 * 1024, 1024 * 1024, 100));
+        assert!(exceeds_compression_ratio(10 * 1024 * 1024, 1024, 100));
+        assert!(exceeds_compression_ratio(10 * 1024 * 1024, 0, 100));
+    }
+}
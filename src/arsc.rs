@@ -0,0 +1,250 @@
+//! Parser of the resource table of Android applications, the `resources.arsc` file, used to
+//! resolve the resource references of binary XML files and the strings of the application
+//! without APKTool.
+
+use std::collections::BTreeMap;
+
+use archive::read_apk_file;
+use axml::{ChunkHeader, StringPool, read_u16, read_u32, RES_STRING_POOL_TYPE, RES_TABLE_TYPE,
+           TYPE_STRING};
+use {Config, Result, Error};
+
+/// Types of the chunks of the resource table
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+
+/// Flags of the type chunks with sparse entries, or with 16 bit entry offsets
+const FLAG_SPARSE: u8 = 0x01;
+const FLAG_OFFSET16: u8 = 0x02;
+
+/// Flag of the complex entries, such as styles, which have a map of values instead of a value
+const FLAG_COMPLEX: u16 = 0x0001;
+
+/// Offset of the missing entries of a type
+const NO_ENTRY: u32 = 0xFFFFFFFF;
+
+/// Resources of the application, with their names and the values of its strings
+#[derive(Debug, Default)]
+pub struct ResourceTable {
+    names: BTreeMap<u32, String>,
+    strings: BTreeMap<String, String>,
+    en_strings: BTreeMap<String, String>,
+}
+
+impl ResourceTable {
+    /// Loads the resource table from the `.apk` file of the application
+    pub fn load(config: &Config) -> Result<ResourceTable> {
+        let data = try!(read_apk_file(config, "resources.arsc"));
+        ResourceTable::parse(&data)
+    }
+
+    /// Parses a `resources.arsc` file
+    pub fn parse(data: &[u8]) -> Result<ResourceTable> {
+        let header = try!(ChunkHeader::read(data, 0));
+        if header.chunk_type != RES_TABLE_TYPE {
+            return Err(Error::ParseError);
+        }
+
+        let mut table = ResourceTable::default();
+        let mut values = StringPool::default();
+        let mut offset = header.header_size;
+        while offset < header.size {
+            let chunk = try!(ChunkHeader::read(data, offset));
+            match chunk.chunk_type {
+                RES_STRING_POOL_TYPE => values = try!(StringPool::parse(data, offset)),
+                RES_TABLE_PACKAGE_TYPE => try!(table.parse_package(data, offset, &values)),
+                _ => {}
+            }
+            offset += chunk.size;
+        }
+        Ok(table)
+    }
+
+    /// Parses the package chunk at the given offset, with the pool of the values of the table
+    fn parse_package(&mut self, data: &[u8], offset: usize, values: &StringPool) -> Result<()> {
+        let header = try!(ChunkHeader::read(data, offset));
+        let package_id = try!(read_u32(data, offset + 8));
+        let type_names = try!(StringPool::parse(data,
+                                                offset +
+                                                try!(read_u32(data, offset + 268)) as usize));
+        let keys = try!(StringPool::parse(data,
+                                          offset + try!(read_u32(data, offset + 276)) as usize));
+
+        let mut chunk_offset = offset + header.header_size;
+        while chunk_offset < offset + header.size {
+            let chunk = try!(ChunkHeader::read(data, chunk_offset));
+            if chunk.chunk_type == RES_TABLE_TYPE_TYPE {
+                try!(self.parse_type(data, chunk_offset, package_id, &type_names, &keys, values));
+            }
+            chunk_offset += chunk.size;
+        }
+        Ok(())
+    }
+
+    /// Parses the type chunk at the given offset, with the entries of a type for a configuration
+    fn parse_type(&mut self,
+                  data: &[u8],
+                  offset: usize,
+                  package_id: u32,
+                  type_names: &StringPool,
+                  keys: &StringPool,
+                  values: &StringPool)
+                  -> Result<()> {
+        let header = try!(ChunkHeader::read(data, offset));
+        if header.header_size < 32 {
+            return Err(Error::ParseError);
+        }
+        let chunk = &data[..offset + header.size];
+        let type_id = chunk[offset + 8];
+        let flags = chunk[offset + 9];
+        let entry_count = try!(read_u32(chunk, offset + 12)) as usize;
+        let entries_start = offset + try!(read_u32(chunk, offset + 16)) as usize;
+        // The language of the configuration, after its size and its mobile country and network
+        // codes
+        let language = [chunk[offset + 28], chunk[offset + 29]];
+        let type_name = match type_names.get((type_id as u32).wrapping_sub(1)) {
+            Some(name) => name,
+            None => return Err(Error::ParseError),
+        };
+
+        if entry_count > header.size / 2 {
+            return Err(Error::ParseError);
+        }
+        for i in 0..entry_count {
+            let (index, entry_offset) = if flags & FLAG_SPARSE != 0 {
+                let position = offset + header.header_size + i * 4;
+                (try!(read_u16(chunk, position)) as u32,
+                 try!(read_u16(chunk, position + 2)) as u32 * 4)
+            } else if flags & FLAG_OFFSET16 != 0 {
+                match try!(read_u16(chunk, offset + header.header_size + i * 2)) {
+                    0xFFFF => continue,
+                    o => (i as u32, o as u32 * 4),
+                }
+            } else {
+                match try!(read_u32(chunk, offset + header.header_size + i * 4)) {
+                    NO_ENTRY => continue,
+                    o => (i as u32, o),
+                }
+            };
+
+            let entry = entries_start + entry_offset as usize;
+            let entry_size = try!(read_u16(chunk, entry)) as usize;
+            let entry_flags = try!(read_u16(chunk, entry + 2));
+            let key = match keys.get(try!(read_u32(chunk, entry + 4))) {
+                Some(key) => key,
+                None => return Err(Error::ParseError),
+            };
+            let id = package_id << 24 | (type_id as u32) << 16 | index;
+            if !self.names.contains_key(&id) {
+                let _ = self.names.insert(id, format!("{}/{}", type_name, key));
+            }
+
+            if type_name == "string" && entry_flags & FLAG_COMPLEX == 0 {
+                let value = entry + entry_size;
+                if try!(read_u16(chunk, value + 2)) >> 8 != TYPE_STRING as u16 {
+                    continue;
+                }
+                let string = match values.get(try!(read_u32(chunk, value + 4))) {
+                    Some(s) => String::from(s),
+                    None => continue,
+                };
+                // The first value of the strings without language is taken as the default one
+                let strings = match &language {
+                    b"\0\0" => &mut self.strings,
+                    b"en" => &mut self.en_strings,
+                    _ => continue,
+                };
+                if !strings.contains_key(key) {
+                    let _ = strings.insert(String::from(key), string);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the name of the resource with the given ID, such as `string/app_name`
+    pub fn get_name(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(|n| n.as_str())
+    }
+
+    /// Gets the value of the string with the given name, in English if it's translated
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        self.en_strings.get(name).or_else(|| self.strings.get(name)).map(|s| s.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axml::tests::{chunk, string_pool, push_u16, push_u32};
+    use axml::format_typed_value;
+    use super::ResourceTable;
+
+    /// Builds a type chunk of the `string` type, with the given language and values
+    fn string_type(language: &[u8; 2], values: &[(u32, u32)]) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.push(1);
+        header.push(0);
+        push_u16(&mut header, 0);
+        push_u32(&mut header, values.len() as u32);
+        // The entries start after the header, the configuration and the entry offsets
+        push_u32(&mut header, (20 + 36 + values.len() * 4) as u32);
+        let mut config = vec![0u8; 36];
+        config[0] = 36;
+        config[8] = language[0];
+        config[9] = language[1];
+        header.extend_from_slice(&config);
+
+        let mut offsets = Vec::new();
+        let mut entries = Vec::new();
+        for &(key, value) in values {
+            push_u32(&mut offsets, entries.len() as u32);
+            push_u16(&mut entries, 8);
+            push_u16(&mut entries, 0);
+            push_u32(&mut entries, key);
+            push_u16(&mut entries, 8);
+            entries.push(0);
+            entries.push(0x03);
+            push_u32(&mut entries, value);
+        }
+        offsets.extend_from_slice(&entries);
+        chunk(0x0201, &header, &offsets)
+    }
+
+    #[test]
+    fn it_parse_resource_table() {
+        let type_names = string_pool(&["string"]);
+        let keys = string_pool(&["app_name", "description"]);
+
+        let mut header = Vec::new();
+        push_u32(&mut header, 0x7f);
+        header.extend_from_slice(&[0u8; 256]);
+        push_u32(&mut header, 284);
+        push_u32(&mut header, 0);
+        push_u32(&mut header, (284 + type_names.len()) as u32);
+        push_u32(&mut header, 0);
+        let mut body = type_names;
+        body.extend(keys);
+        body.extend(string_type(b"\0\0", &[(0, 0), (1, 1)]));
+        body.extend(string_type(b"es", &[(0, 2)]));
+        body.extend(string_type(b"en", &[(1, 3)]));
+        let package = chunk(0x0200, &header, &body);
+
+        let mut table_header = Vec::new();
+        push_u32(&mut table_header, 1);
+        let mut table_body = string_pool(&["SUPER", "Default description", "SUPER ES",
+                                           "English description"]);
+        table_body.extend(package);
+        let table = chunk(0x0002, &table_header, &table_body);
+
+        let table = ResourceTable::parse(&table).unwrap();
+        assert_eq!(table.get_name(0x7f010000), Some("string/app_name"));
+        assert_eq!(table.get_name(0x7f010001), Some("string/description"));
+        assert_eq!(table.get_name(0x7f020000), None);
+        assert_eq!(table.get_string("app_name"), Some("SUPER"));
+        assert_eq!(table.get_string("description"), Some("English description"));
+        assert_eq!(table.get_string("missing"), None);
+        assert_eq!(format_typed_value(0x01, 0x7f010000, Some(&table)), "@string/app_name");
+
+        assert!(ResourceTable::parse(&[2, 0, 12, 0, 255, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,565 @@
+//! Decoder of the binary XML format (AXML) of the files compiled in Android applications, such
+//! as the `AndroidManifest.xml` file.
+//!
+//! The files are decoded to text XML, as APKTool does, so that the manifest can still be analyzed
+//! when APKTool fails or is not installed.
+
+use std::mem;
+use std::collections::BTreeMap;
+
+use arsc::ResourceTable;
+use archive::read_apk_file;
+use {Config, Result, Error};
+
+/// Types of the chunks of binary XML and resource table files
+pub const RES_STRING_POOL_TYPE: u16 = 0x0001;
+pub const RES_TABLE_TYPE: u16 = 0x0002;
+pub const RES_XML_TYPE: u16 = 0x0003;
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_END_NAMESPACE_TYPE: u16 = 0x0101;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_CDATA_TYPE: u16 = 0x0104;
+
+/// Flag of the string pools encoded in UTF-8 instead of UTF-16
+const UTF8_FLAG: u32 = 1 << 8;
+
+/// Index used for missing strings, such as the namespace of attributes without it
+const NO_INDEX: u32 = 0xFFFFFFFF;
+
+/// Types of the typed values of attributes and resources
+pub const TYPE_REFERENCE: u8 = 0x01;
+const TYPE_ATTRIBUTE: u8 = 0x02;
+pub const TYPE_STRING: u8 = 0x03;
+const TYPE_FLOAT: u8 = 0x04;
+const TYPE_DIMENSION: u8 = 0x05;
+const TYPE_FRACTION: u8 = 0x06;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+const TYPE_INT_BOOLEAN: u8 = 0x12;
+const TYPE_FIRST_COLOR_INT: u8 = 0x1c;
+const TYPE_LAST_COLOR_INT: u8 = 0x1f;
+
+/// Indentation of each level of the decoded XML
+const INDENTATION: &'static str = "    ";
+
+/// Namespace of the Android attributes
+const ANDROID_NAMESPACE: &'static str = "http://schemas.android.com/apk/res/android";
+
+/// Units of the dimension and fraction values
+const DIMENSION_UNITS: [&'static str; 6] = ["px", "dip", "sp", "pt", "in", "mm"];
+const FRACTION_UNITS: [&'static str; 2] = ["%", "%p"];
+
+/// Multipliers of the mantissa of complex values, depending on their radix
+const RADIX_MULTIPLIERS: [f32; 4] = [1.0 / 256.0,
+                                     1.0 / 32_768.0,
+                                     1.0 / 8_388_608.0,
+                                     1.0 / 2_147_483_648.0];
+
+/// Protection levels of permissions, and their flags, with their values in the binary XML
+const PROTECTION_LEVELS: [&'static str; 4] = ["normal", "dangerous", "signature",
+                                              "signatureOrSystem"];
+const PROTECTION_FLAGS: [(u32, &'static str); 8] = [(0x10, "privileged"),
+                                                    (0x20, "development"),
+                                                    (0x40, "appop"),
+                                                    (0x80, "pre23"),
+                                                    (0x100, "installer"),
+                                                    (0x200, "verifier"),
+                                                    (0x400, "preinstalled"),
+                                                    (0x800, "setup")];
+
+/// Reads a little endian `u16` at the given offset
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    if offset + 2 > data.len() {
+        return Err(Error::ParseError);
+    }
+    Ok(data[offset] as u16 | (data[offset + 1] as u16) << 8)
+}
+
+/// Reads a little endian `u32` at the given offset
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    if offset + 4 > data.len() {
+        return Err(Error::ParseError);
+    }
+    Ok(data[offset] as u32 | (data[offset + 1] as u32) << 8 | (data[offset + 2] as u32) << 16 |
+       (data[offset + 3] as u32) << 24)
+}
+
+/// Header of a chunk: its type, the size of its header and its total size
+pub struct ChunkHeader {
+    pub chunk_type: u16,
+    pub header_size: usize,
+    pub size: usize,
+}
+
+impl ChunkHeader {
+    /// Reads the header of the chunk at the given offset, checking that it fits in the data
+    pub fn read(data: &[u8], offset: usize) -> Result<ChunkHeader> {
+        let header = ChunkHeader {
+            chunk_type: try!(read_u16(data, offset)),
+            header_size: try!(read_u16(data, offset + 2)) as usize,
+            size: try!(read_u32(data, offset + 4)) as usize,
+        };
+        if header.header_size < 8 || header.size < header.header_size ||
+           offset + header.size > data.len() {
+            return Err(Error::ParseError);
+        }
+        Ok(header)
+    }
+}
+
+/// Pool with the strings of a binary XML or resource table file
+#[derive(Debug, Default)]
+pub struct StringPool {
+    strings: Vec<String>,
+}
+
+impl StringPool {
+    /// Parses the string pool chunk at the given offset
+    pub fn parse(data: &[u8], offset: usize) -> Result<StringPool> {
+        let header = try!(ChunkHeader::read(data, offset));
+        if header.chunk_type != RES_STRING_POOL_TYPE {
+            return Err(Error::ParseError);
+        }
+        let count = try!(read_u32(data, offset + 8)) as usize;
+        let flags = try!(read_u32(data, offset + 16));
+        let strings_start = offset + try!(read_u32(data, offset + 20)) as usize;
+        let chunk = &data[..offset + header.size];
+
+        // The counts are checked against the size of the chunk before allocating anything
+        if count > header.size / 4 {
+            return Err(Error::ParseError);
+        }
+        let mut strings = Vec::with_capacity(count);
+        for i in 0..count {
+            let string_offset = try!(read_u32(chunk, offset + header.header_size + i * 4)) as usize;
+            let string = if flags & UTF8_FLAG != 0 {
+                try!(read_utf8_string(chunk, strings_start + string_offset))
+            } else {
+                try!(read_utf16_string(chunk, strings_start + string_offset))
+            };
+            strings.push(string);
+        }
+        Ok(StringPool { strings: strings })
+    }
+
+    /// Gets the string at the given index, if any
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(|s| s.as_str())
+    }
+}
+
+/// Reads a string of a UTF-8 pool, preceded by its length in UTF-16 characters and in bytes
+fn read_utf8_string(data: &[u8], offset: usize) -> Result<String> {
+    let (_, offset) = try!(read_utf8_length(data, offset));
+    let (length, offset) = try!(read_utf8_length(data, offset));
+    if offset + length > data.len() {
+        return Err(Error::ParseError);
+    }
+    Ok(String::from_utf8_lossy(&data[offset..offset + length]).into_owned())
+}
+
+/// Reads a length of a UTF-8 pool, in one or two bytes, and gets the offset after it
+fn read_utf8_length(data: &[u8], offset: usize) -> Result<(usize, usize)> {
+    match data.get(offset) {
+        Some(&first) if first & 0x80 != 0 => {
+            match data.get(offset + 1) {
+                Some(&second) => Ok((((first & 0x7f) as usize) << 8 | second as usize, offset + 2)),
+                None => Err(Error::ParseError),
+            }
+        }
+        Some(&first) => Ok((first as usize, offset + 1)),
+        None => Err(Error::ParseError),
+    }
+}
+
+/// Reads a string of a UTF-16 pool, preceded by its length in one or two `u16`
+fn read_utf16_string(data: &[u8], offset: usize) -> Result<String> {
+    let first = try!(read_u16(data, offset)) as usize;
+    let (length, offset) = if first & 0x8000 != 0 {
+        ((first & 0x7fff) << 16 | try!(read_u16(data, offset + 2)) as usize, offset + 4)
+    } else {
+        (first, offset + 2)
+    };
+    if offset + length * 2 > data.len() {
+        return Err(Error::ParseError);
+    }
+    let mut units = Vec::with_capacity(length);
+    for i in 0..length {
+        units.push(try!(read_u16(data, offset + i * 2)));
+    }
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Formats a typed value of an attribute or a resource, as APKTool does
+///
+/// References are resolved to their names, such as `@string/app_name`, with the resource table of
+/// the application, if any.
+pub fn format_typed_value(data_type: u8, data: u32, resources: Option<&ResourceTable>) -> String {
+    match data_type {
+        TYPE_REFERENCE | TYPE_ATTRIBUTE => {
+            let prefix = if data_type == TYPE_REFERENCE { "@" } else { "?" };
+            match resources.and_then(|r| r.get_name(data)) {
+                Some(name) => format!("{}{}", prefix, name),
+                None if data == 0 => String::from("@null"),
+                None => format!("{}0x{:08x}", prefix, data),
+            }
+        }
+        TYPE_FLOAT => format!("{}", unsafe { mem::transmute::<u32, f32>(data) }),
+        TYPE_DIMENSION => format_complex(data, &DIMENSION_UNITS),
+        TYPE_FRACTION => format_complex(data, &FRACTION_UNITS),
+        TYPE_INT_DEC => format!("{}", data as i32),
+        TYPE_INT_HEX => format!("0x{:08x}", data),
+        TYPE_INT_BOOLEAN => String::from(if data != 0 { "true" } else { "false" }),
+        t if t >= TYPE_FIRST_COLOR_INT && t <= TYPE_LAST_COLOR_INT => format!("#{:08x}", data),
+        _ => format!("0x{:08x}", data),
+    }
+}
+
+/// Formats a complex value, such as a dimension or a fraction, with its unit
+fn format_complex(data: u32, units: &[&str]) -> String {
+    let value = (data & 0xFFFFFF00) as i32 as f32 * RADIX_MULTIPLIERS[(data >> 4 & 0x3) as usize];
+    match units.get((data & 0xf) as usize) {
+        Some(unit) => format!("{}{}", value, unit),
+        None => format!("{}", value),
+    }
+}
+
+/// Formats the integer value of the Android attributes that APKTool decodes to their names
+fn format_android_attribute(name: &str, data: u32) -> Option<String> {
+    match name {
+        "installLocation" => {
+            match data {
+                0 => Some(String::from("auto")),
+                1 => Some(String::from("internalOnly")),
+                2 => Some(String::from("preferExternal")),
+                _ => None,
+            }
+        }
+        "protectionLevel" => {
+            let mut level = match PROTECTION_LEVELS.get((data & 0xf) as usize) {
+                Some(level) => String::from(*level),
+                None => return None,
+            };
+            for &(flag, flag_name) in PROTECTION_FLAGS.iter() {
+                if data & flag != 0 {
+                    level.push('|');
+                    level.push_str(flag_name);
+                }
+            }
+            Some(level)
+        }
+        _ => None,
+    }
+}
+
+/// Escapes the given text to be used in XML
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Decodes a binary XML file to text XML
+pub fn decode_xml(data: &[u8], resources: Option<&ResourceTable>) -> Result<String> {
+    let header = try!(ChunkHeader::read(data, 0));
+    if header.chunk_type != RES_XML_TYPE {
+        return Err(Error::ParseError);
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"no\"?>");
+    let mut strings = StringPool::default();
+    let mut namespaces: BTreeMap<String, String> = BTreeMap::new();
+    let mut new_namespaces: Vec<(String, String)> = Vec::new();
+    // Elements are written in their own lines, indented, and closed in the same tag if they are
+    // empty, so that the lines of the findings are similar to the ones of APKTool
+    let mut depth = 0;
+    let mut open_tag = false;
+    let mut offset = header.header_size;
+    while offset < header.size {
+        let chunk = try!(ChunkHeader::read(data, offset));
+        let body = offset + chunk.header_size;
+        if open_tag && chunk.chunk_type != RES_XML_END_ELEMENT_TYPE &&
+           chunk.chunk_type != RES_STRING_POOL_TYPE {
+            xml.push('>');
+            open_tag = false;
+        }
+        match chunk.chunk_type {
+            RES_STRING_POOL_TYPE => strings = try!(StringPool::parse(data, offset)),
+            RES_XML_START_NAMESPACE_TYPE => {
+                let prefix = String::from(strings.get(try!(read_u32(data, body))).unwrap_or(""));
+                let uri = String::from(strings.get(try!(read_u32(data, body + 4))).unwrap_or(""));
+                let _ = namespaces.insert(uri.clone(), prefix.clone());
+                new_namespaces.push((prefix, uri));
+            }
+            RES_XML_END_NAMESPACE_TYPE => {}
+            RES_XML_START_ELEMENT_TYPE => {
+                let name = try!(get_qualified_name(data, body, &strings, &namespaces));
+                xml.push('\n');
+                xml.push_str(&INDENTATION.repeat(depth));
+                xml.push('<');
+                xml.push_str(&name);
+                for (prefix, uri) in new_namespaces.drain(..) {
+                    xml.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_xml(&uri)));
+                }
+
+                let attribute_start = try!(read_u16(data, body + 8)) as usize;
+                let attribute_size = try!(read_u16(data, body + 10)) as usize;
+                let attribute_count = try!(read_u16(data, body + 12)) as usize;
+                for i in 0..attribute_count {
+                    let attribute = body + attribute_start + i * attribute_size;
+                    if attribute + 20 > offset + chunk.size {
+                        return Err(Error::ParseError);
+                    }
+                    let name = try!(get_qualified_name(data, attribute, &strings, &namespaces));
+                    let value = try!(get_attribute_value(data, attribute, &strings, resources));
+                    xml.push_str(&format!(" {}=\"{}\"", name, escape_xml(&value)));
+                }
+                depth += 1;
+                open_tag = true;
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                depth = depth.saturating_sub(1);
+                if open_tag {
+                    xml.push_str("/>");
+                    open_tag = false;
+                } else {
+                    let name = try!(get_qualified_name(data, body, &strings, &namespaces));
+                    xml.push('\n');
+                    xml.push_str(&INDENTATION.repeat(depth));
+                    xml.push_str(&format!("</{}>", name));
+                }
+            }
+            RES_XML_CDATA_TYPE => {
+                let text = strings.get(try!(read_u32(data, body))).unwrap_or("");
+                xml.push_str(&escape_xml(text));
+            }
+            // Other chunks, such as the resource map with the IDs of the attributes, are not
+            // needed to decode the file
+            _ => {}
+        }
+        offset += chunk.size;
+    }
+    if open_tag {
+        xml.push_str("/>");
+    }
+    Ok(xml)
+}
+
+/// Gets the name of an element or attribute, with the prefix of its namespace, if any, from its
+/// namespace and name indexes at the given offset
+fn get_qualified_name(data: &[u8],
+                      offset: usize,
+                      strings: &StringPool,
+                      namespaces: &BTreeMap<String, String>)
+                      -> Result<String> {
+    let namespace = try!(read_u32(data, offset));
+    let name = match strings.get(try!(read_u32(data, offset + 4))) {
+        Some(name) => name,
+        None => return Err(Error::ParseError),
+    };
+    if namespace == NO_INDEX {
+        return Ok(String::from(name));
+    }
+    let uri = strings.get(namespace).unwrap_or("");
+    match namespaces.get(uri) {
+        Some(prefix) if !prefix.is_empty() => Ok(format!("{}:{}", prefix, name)),
+        _ if uri == ANDROID_NAMESPACE => Ok(format!("android:{}", name)),
+        _ => Ok(String::from(name)),
+    }
+}
+
+/// Gets the value of the attribute at the given offset
+fn get_attribute_value(data: &[u8],
+                       offset: usize,
+                       strings: &StringPool,
+                       resources: Option<&ResourceTable>)
+                       -> Result<String> {
+    let raw_value = try!(read_u32(data, offset + 8));
+    let data_type = data[offset + 15];
+    let value = try!(read_u32(data, offset + 16));
+    // Android uses the typed value, so the raw value is ignored for anything but strings, since
+    // it can be set to mislead the analysis tools
+    if data_type == TYPE_STRING {
+        let index = if raw_value != NO_INDEX { raw_value } else { value };
+        return Ok(String::from(strings.get(index).unwrap_or("")));
+    }
+
+    let namespace = try!(read_u32(data, offset));
+    if namespace != NO_INDEX && strings.get(namespace) == Some(ANDROID_NAMESPACE) &&
+       (data_type == TYPE_INT_DEC || data_type == TYPE_INT_HEX) {
+        let name = strings.get(try!(read_u32(data, offset + 4))).unwrap_or("");
+        if let Some(value) = format_android_attribute(name, value) {
+            return Ok(value);
+        }
+    }
+    Ok(format_typed_value(data_type, value, resources))
+}
+
+/// Decodes the `AndroidManifest.xml` file of the application from its `.apk` file, resolving
+/// its resource references with its `resources.arsc` file, if any
+pub fn decode_apk_manifest(config: &Config) -> Result<String> {
+    let manifest = try!(read_apk_file(config, "AndroidManifest.xml"));
+    let resources = ResourceTable::load(config).ok();
+    decode_xml(&manifest, resources.as_ref())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{decode_xml, format_typed_value, RES_STRING_POOL_TYPE, RES_XML_TYPE,
+                TYPE_INT_BOOLEAN, TYPE_INT_DEC, TYPE_STRING, TYPE_DIMENSION};
+
+    /// Appends a little endian `u16` to the buffer
+    pub fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+        buffer.push(value as u8);
+        buffer.push((value >> 8) as u8);
+    }
+
+    /// Appends a little endian `u32` to the buffer
+    pub fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+        push_u16(buffer, value as u16);
+        push_u16(buffer, (value >> 16) as u16);
+    }
+
+    /// Builds a chunk with the given type, extra header fields and body
+    pub fn chunk(chunk_type: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = Vec::new();
+        push_u16(&mut chunk, chunk_type);
+        push_u16(&mut chunk, 8 + header.len() as u16);
+        push_u32(&mut chunk, (8 + header.len() + body.len()) as u32);
+        chunk.extend_from_slice(header);
+        chunk.extend_from_slice(body);
+        chunk
+    }
+
+    /// Builds a UTF-16 string pool with the given strings
+    pub fn string_pool(strings: &[&str]) -> Vec<u8> {
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for string in strings {
+            push_u32(&mut offsets, data.len() as u32);
+            let units: Vec<u16> = string.encode_utf16().collect();
+            push_u16(&mut data, units.len() as u16);
+            for unit in units {
+                push_u16(&mut data, unit);
+            }
+            push_u16(&mut data, 0);
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+
+        let mut header = Vec::new();
+        push_u32(&mut header, strings.len() as u32);
+        push_u32(&mut header, 0);
+        push_u32(&mut header, 0);
+        push_u32(&mut header, (28 + offsets.len()) as u32);
+        push_u32(&mut header, 0);
+        offsets.extend_from_slice(&data);
+        chunk(RES_STRING_POOL_TYPE, &header, &offsets)
+    }
+
+    /// Builds a node chunk of a binary XML file, with the given body
+    fn node(chunk_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut header = Vec::new();
+        push_u32(&mut header, 1);
+        push_u32(&mut header, 0xFFFFFFFF);
+        chunk(chunk_type, &header, body)
+    }
+
+    /// Builds an attribute with the given namespace, name, raw value and typed value
+    fn attribute(namespace: u32, name: u32, raw: u32, data_type: u8, data: u32) -> Vec<u8> {
+        let mut attribute = Vec::new();
+        push_u32(&mut attribute, namespace);
+        push_u32(&mut attribute, name);
+        push_u32(&mut attribute, raw);
+        push_u16(&mut attribute, 8);
+        attribute.push(0);
+        attribute.push(data_type);
+        push_u32(&mut attribute, data);
+        attribute
+    }
+
+    #[test]
+    fn it_decode_xml() {
+        let strings = string_pool(&["android",
+                                    "http://schemas.android.com/apk/res/android",
+                                    "manifest",
+                                    "package",
+                                    "com.example.app",
+                                    "versionCode",
+                                    "application",
+                                    "debuggable",
+                                    "installLocation",
+                                    "label",
+                                    "Tom & Jerry"]);
+
+        let mut namespace = Vec::new();
+        push_u32(&mut namespace, 0);
+        push_u32(&mut namespace, 1);
+
+        let mut manifest = Vec::new();
+        push_u32(&mut manifest, 0xFFFFFFFF);
+        push_u32(&mut manifest, 2);
+        push_u16(&mut manifest, 20);
+        push_u16(&mut manifest, 20);
+        push_u16(&mut manifest, 3);
+        push_u16(&mut manifest, 0);
+        push_u16(&mut manifest, 0);
+        push_u16(&mut manifest, 0);
+        manifest.extend(attribute(0xFFFFFFFF, 3, 4, TYPE_STRING, 4));
+        manifest.extend(attribute(1, 5, 0xFFFFFFFF, TYPE_INT_DEC, 12));
+        manifest.extend(attribute(1, 8, 0xFFFFFFFF, TYPE_INT_DEC, 2));
+
+        let mut application = Vec::new();
+        push_u32(&mut application, 0xFFFFFFFF);
+        push_u32(&mut application, 6);
+        push_u16(&mut application, 20);
+        push_u16(&mut application, 20);
+        push_u16(&mut application, 2);
+        push_u16(&mut application, 0);
+        push_u16(&mut application, 0);
+        push_u16(&mut application, 0);
+        application.extend(attribute(1, 7, 0xFFFFFFFF, TYPE_INT_BOOLEAN, 0xFFFFFFFF));
+        application.extend(attribute(1, 9, 10, TYPE_STRING, 10));
+
+        let mut end_application = Vec::new();
+        push_u32(&mut end_application, 0xFFFFFFFF);
+        push_u32(&mut end_application, 6);
+        let mut end_manifest = Vec::new();
+        push_u32(&mut end_manifest, 0xFFFFFFFF);
+        push_u32(&mut end_manifest, 2);
+
+        let mut body = strings;
+        body.extend(node(0x0100, &namespace));
+        body.extend(node(0x0102, &manifest));
+        body.extend(node(0x0102, &application));
+        body.extend(node(0x0103, &end_application));
+        body.extend(node(0x0103, &end_manifest));
+        body.extend(node(0x0101, &namespace));
+        let xml = chunk(RES_XML_TYPE, &[], &body);
+
+        assert_eq!(decode_xml(&xml, None).unwrap(),
+                   "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"no\"?>\n\
+                    <manifest xmlns:android=\"http://schemas.android.com/apk/res/android\" \
+                    package=\"com.example.app\" android:versionCode=\"12\" \
+                    android:installLocation=\"preferExternal\">\n    \
+                    <application android:debuggable=\"true\" \
+                    android:label=\"Tom &amp; Jerry\"/>\n</manifest>");
+
+        assert!(decode_xml(&xml[..xml.len() - 4], None).is_err());
+        assert!(decode_xml(&[0, 0, 0, 0], None).is_err());
+    }
+
+    #[test]
+    fn it_format_typed_value() {
+        assert_eq!(format_typed_value(TYPE_INT_DEC, 0xFFFFFFFF, None), "-1");
+        assert_eq!(format_typed_value(TYPE_INT_BOOLEAN, 0, None), "false");
+        assert_eq!(format_typed_value(0x01, 0x7f0b0001, None), "@0x7f0b0001");
+        assert_eq!(format_typed_value(0x01, 0, None), "@null");
+        assert_eq!(format_typed_value(0x1c, 0xff00ff00, None), "#ff00ff00");
+        // 16dip, with a mantissa of 16 and the radix 23p0
+        assert_eq!(format_typed_value(TYPE_DIMENSION, 16 << 8 | 1, None), "16dip");
+    }
+}
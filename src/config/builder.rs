@@ -0,0 +1,131 @@
+//! Builder of the configuration, so that it can be created without a list of positional
+//! arguments.
+
+use server::is_package_name;
+use {Config, Error, Result};
+
+/// Builder of the configuration
+///
+/// The configuration files are loaded when it's built, in the same order as in the command line
+/// interface, unless they are disabled with `load_files(false)`:
+///
+/// ```ignore
+/// let config = try!(ConfigBuilder::new()
+///     .app_id("com.example.app")
+///     .verbose(true)
+///     .force(true)
+///     .build());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    app_id: String,
+    verbose: bool,
+    quiet: bool,
+    force: bool,
+    bench: bool,
+    skip_files: bool,
+}
+
+impl ConfigBuilder {
+    /// Creates a builder for the default configuration, without an application
+    pub fn new() -> ConfigBuilder {
+        Default::default()
+    }
+
+    /// Sets the package name of the application to analyze
+    pub fn app_id<S: Into<String>>(mut self, app_id: S) -> ConfigBuilder {
+        self.app_id = app_id.into();
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> ConfigBuilder {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn quiet(mut self, quiet: bool) -> ConfigBuilder {
+        self.quiet = quiet;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> ConfigBuilder {
+        self.force = force;
+        self
+    }
+
+    pub fn bench(mut self, bench: bool) -> ConfigBuilder {
+        self.bench = bench;
+        self
+    }
+
+    /// Sets if the configuration files are loaded, or only the default configuration is used
+    pub fn load_files(mut self, load_files: bool) -> ConfigBuilder {
+        self.skip_files = !load_files;
+        self
+    }
+
+    /// Builds the configuration, loading the configuration files
+    ///
+    /// It returns `Error::Config` if the package name is not valid or both `verbose` and `quiet`
+    /// are set, and an error if a configuration file can't be read.
+    pub fn build(&self) -> Result<Config> {
+        if !self.app_id.is_empty() && !is_package_name(&self.app_id) {
+            return Err(Error::Config);
+        }
+        if self.verbose && self.quiet {
+            return Err(Error::Config);
+        }
+
+        let mut config: Config = Default::default();
+        config.set_app_id(&self.app_id);
+        config.set_verbose(self.verbose);
+        config.set_quiet(self.quiet);
+        config.set_force(self.force);
+        config.set_bench(self.bench);
+        if !self.skip_files {
+            try!(config.load_files());
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Error;
+    use super::ConfigBuilder;
+
+    #[test]
+    fn it_build_config() {
+        let config = ConfigBuilder::new()
+            .app_id("com.example.app")
+            .verbose(true)
+            .force(true)
+            .bench(true)
+            .load_files(false)
+            .build()
+            .unwrap();
+        assert_eq!(config.get_app_id(), "com.example.app");
+        assert!(config.is_verbose());
+        assert!(!config.is_quiet());
+        assert!(config.is_force());
+        assert!(config.is_bench());
+        assert_eq!(config.get_loaded_config_files().count(), 0);
+
+        let config = ConfigBuilder::new().quiet(true).load_files(false).build().unwrap();
+        assert_eq!(config.get_app_id(), "");
+        assert!(config.is_quiet());
+        assert!(!config.is_verbose());
+    }
+
+    #[test]
+    fn it_build_invalid_config() {
+        match ConfigBuilder::new().app_id("../com.example.app").build() {
+            Err(Error::Config) => {}
+            _ => panic!("invalid package names must be rejected"),
+        }
+        match ConfigBuilder::new().verbose(true).quiet(true).build() {
+            Err(Error::Config) => {}
+            _ => panic!("verbose and quiet must not be set at the same time"),
+        }
+    }
+}
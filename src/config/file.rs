@@ -0,0 +1,688 @@
+//! Typed structure of the `config.toml` files, deserialized with Serde.
+//!
+//! Every option is optional, so that each file only overrides the options it sets. Options with
+//! an invalid type are reported with their line and left out, so that the rest of the file is
+//! still loaded, and their values are validated when they are applied to the configuration.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use toml::{Decoder, Table, Value};
+
+use print_warning;
+
+/// Options of the root of the configuration files
+const OPTIONS: &'static [&'static str] = &["profile",
+                                           "threads",
+                                           "historical",
+                                           "online_checks",
+                                           "malware_heuristics",
+                                           "trend",
+                                           "report_formats",
+                                           "include_source",
+                                           "decompile",
+                                           "language",
+                                           "gitlab_sast",
+                                           "github_annotations",
+                                           "reproducible",
+                                           "downloads_folder",
+                                           "dist_folder",
+                                           "keep_dist",
+                                           "dist_max_age",
+                                           "workspace_folder",
+                                           "results_folder",
+                                           "versioned_results",
+                                           "apktool_file",
+                                           "dex2jar_folder",
+                                           "jd_cmd_file",
+                                           "results_template",
+                                           "templates_folder",
+                                           "baseline",
+                                           "ignore_file",
+                                           "source_root",
+                                           "rules_json",
+                                           "rules_extra",
+                                           "permissions",
+                                           "manifest_flags",
+                                           "secrets",
+                                           "downloads",
+                                           "certificate",
+                                           "sdk_policy",
+                                           "vulnerability_db",
+                                           "rule_packs",
+                                           "rules",
+                                           "taint",
+                                           "logging",
+                                           "virustotal",
+                                           "notifications",
+                                           "jobs",
+                                           "timeouts",
+                                           "archive",
+                                           "grouping",
+                                           "issues"];
+
+/// Options of each section of the configuration files
+///
+/// The `[profile]` and `[manifest_flags]` sections are not listed, since their keys are names
+/// of profiles and flags.
+const SECTIONS: &'static [(&'static str, &'static [&'static str])] =
+    &[("permissions", &["name", "criticity", "label", "description"]),
+      ("secrets", &["entropy_threshold", "min_length"]),
+      ("downloads", &["concurrency", "rate_limit", "delay", "sources"]),
+      ("certificate", &["expiry_window"]),
+      ("sdk_policy", &["min_sdk", "max_target_lag", "latest_sdk"]),
+      ("vulnerability_db", &["path", "url"]),
+      ("rule_packs", &["url", "public_key"]),
+      ("rules",
+       &["disabled", "include_paths", "exclude_paths", "criticity", "cvss_environment"]),
+      ("taint", &["sources", "sinks", "sanitizers", "inter_procedural"]),
+      ("logging", &["sensitive_names"]),
+      ("virustotal", &["enabled", "api_key", "upload"]),
+      ("notifications", &["slack_webhooks", "webhooks", "min_criticity", "timeout"]),
+      ("jobs", &["timeout", "max_disk", "apk_folder"]),
+      ("timeouts", &["apktool", "dex2jar", "jd_cmd", "analysis"]),
+      ("archive", &["max_entries", "max_size", "max_compression_ratio"]),
+      ("grouping", &["deduplicate", "max_samples"]),
+      ("issues",
+       &["formats",
+         "project",
+         "issue_type",
+         "labels",
+         "min_criticity",
+         "jira_url",
+         "jira_user",
+         "jira_token"])];
+
+/// Options of a configuration file
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigFile {
+    pub threads: Option<i64>,
+    pub historical: Option<bool>,
+    pub online_checks: Option<bool>,
+    pub malware_heuristics: Option<bool>,
+    pub trend: Option<bool>,
+    pub report_formats: Option<Vec<String>>,
+    pub include_source: Option<bool>,
+    pub decompile: Option<bool>,
+    pub language: Option<String>,
+    pub gitlab_sast: Option<bool>,
+    pub github_annotations: Option<bool>,
+    pub reproducible: Option<bool>,
+    pub downloads_folder: Option<String>,
+    pub dist_folder: Option<String>,
+    pub keep_dist: Option<String>,
+    pub dist_max_age: Option<i64>,
+    pub workspace_folder: Option<String>,
+    pub results_folder: Option<String>,
+    pub versioned_results: Option<bool>,
+    pub apktool_file: Option<String>,
+    pub dex2jar_folder: Option<String>,
+    pub jd_cmd_file: Option<String>,
+    pub results_template: Option<String>,
+    pub templates_folder: Option<String>,
+    pub baseline: Option<String>,
+    pub ignore_file: Option<String>,
+    pub source_root: Option<String>,
+    pub rules_json: Option<String>,
+    pub rules_extra: Option<Vec<String>>,
+    pub permissions: Option<Vec<PermissionEntry>>,
+    pub secrets: Option<SecretsSection>,
+    pub downloads: Option<DownloadsSection>,
+    pub certificate: Option<CertificateSection>,
+    pub sdk_policy: Option<SdkPolicySection>,
+    pub vulnerability_db: Option<VulnerabilityDbSection>,
+    pub rule_packs: Option<RulePacksSection>,
+    pub rules: Option<RulesSection>,
+    pub taint: Option<TaintSection>,
+    pub logging: Option<LoggingSection>,
+    pub virustotal: Option<VirusTotalSection>,
+    pub notifications: Option<NotificationsSection>,
+    pub jobs: Option<JobsSection>,
+    pub timeouts: Option<TimeoutsSection>,
+    pub archive: Option<ArchiveSection>,
+    pub grouping: Option<GroupingSection>,
+    pub issues: Option<IssuesSection>,
+}
+
+/// Entry of the `[[permissions]]` array
+#[derive(Debug, Deserialize)]
+pub struct PermissionEntry {
+    pub name: Option<String>,
+    pub criticity: Option<String>,
+    pub label: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SecretsSection {
+    pub entropy_threshold: Option<f64>,
+    pub min_length: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadsSection {
+    pub concurrency: Option<i64>,
+    pub rate_limit: Option<String>,
+    pub delay: Option<i64>,
+    pub sources: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CertificateSection {
+    pub expiry_window: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SdkPolicySection {
+    pub min_sdk: Option<i64>,
+    pub max_target_lag: Option<i64>,
+    pub latest_sdk: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VulnerabilityDbSection {
+    pub path: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RulePacksSection {
+    pub url: Option<String>,
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RulesSection {
+    pub disabled: Option<Vec<String>>,
+    pub include_paths: Option<Vec<String>>,
+    pub exclude_paths: Option<Vec<String>>,
+    pub criticity: Option<BTreeMap<String, String>>,
+    pub cvss_environment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaintSection {
+    pub sources: Option<Vec<String>>,
+    pub sinks: Option<Vec<String>>,
+    pub sanitizers: Option<Vec<String>>,
+    pub inter_procedural: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoggingSection {
+    pub sensitive_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VirusTotalSection {
+    pub enabled: Option<bool>,
+    pub api_key: Option<String>,
+    pub upload: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationsSection {
+    pub slack_webhooks: Option<Vec<String>>,
+    pub webhooks: Option<Vec<String>>,
+    pub min_criticity: Option<String>,
+    pub timeout: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobsSection {
+    pub timeout: Option<i64>,
+    pub max_disk: Option<i64>,
+    pub apk_folder: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeoutsSection {
+    pub apktool: Option<i64>,
+    pub dex2jar: Option<i64>,
+    pub jd_cmd: Option<i64>,
+    pub analysis: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveSection {
+    pub max_entries: Option<i64>,
+    pub max_size: Option<i64>,
+    pub max_compression_ratio: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroupingSection {
+    pub deduplicate: Option<bool>,
+    pub max_samples: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssuesSection {
+    pub formats: Option<Vec<String>>,
+    pub project: Option<String>,
+    pub issue_type: Option<String>,
+    pub labels: Option<Vec<String>>,
+    pub min_criticity: Option<String>,
+    pub jira_url: Option<String>,
+    pub jira_user: Option<String>,
+    pub jira_token: Option<String>,
+}
+
+/// Location of the options of a configuration file, to show it in the warnings about them
+pub struct Source<'a> {
+    /// Name of the file or the profile the options come from
+    name: &'a str,
+    /// Contents of the file, to find the line of each option, if they come from a file
+    text: Option<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    /// Creates the source of the options of a file, with its contents
+    pub fn file(name: &'a str, text: &'a str) -> Source<'a> {
+        Source {
+            name: name,
+            text: Some(text),
+        }
+    }
+
+    /// Creates the source of the options of a profile, without line numbers
+    pub fn profile(name: &'a str) -> Source<'a> {
+        Source {
+            name: name,
+            text: None,
+        }
+    }
+
+    /// Gets the name of the file or the profile the options come from
+    pub fn get_name(&self) -> &str {
+        self.name
+    }
+
+    /// Gets where the option with the given path of keys was set, such as
+    /// `config.toml, line 3`
+    pub fn locate(&self, path: &[&str]) -> String {
+        let line = match (self.text, path.split_last()) {
+            (Some(text), Some((key, section))) => find_line(text, &section.join("."), key),
+            _ => None,
+        };
+        match line {
+            Some(line) => format!("{}, line {}", self.name, line),
+            None => String::from(self.name),
+        }
+    }
+}
+
+/// Finds the line, starting at 1, where the given key of the given section is set, or the
+/// header of its own section if it's a table
+fn find_line(text: &str, section: &str, key: &str) -> Option<usize> {
+    let header = if section.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", section, key)
+    };
+    let mut current = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            current = String::from(line.trim_matches(|c| c == '[' || c == ']').trim());
+            if current == header {
+                return Some(i + 1);
+            }
+        } else if current == section && line.starts_with(key) &&
+                  line[key.len()..].trim_left().starts_with('=') {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Warns about the options of the table that are not configuration options
+pub fn check_unknown_options(table: &Table, source: &Source, verbose: bool) {
+    for (key, value) in table {
+        if !OPTIONS.contains(&key.as_str()) {
+            print_warning(format!("{}: unknown configuration option {}.",
+                                  source.locate(&[key.as_str()]),
+                                  key),
+                          verbose);
+            continue;
+        }
+
+        let options = match SECTIONS.iter().find(|&&(section, _)| section == key.as_str()) {
+            Some(&(_, options)) => options,
+            None => continue,
+        };
+        let tables: Vec<&Table> = match *value {
+            Value::Table(ref t) => vec![t],
+            Value::Array(ref a) => a.iter().filter_map(|v| v.as_table()).collect(),
+            _ => Vec::new(),
+        };
+        for t in tables {
+            for option in t.keys().filter(|o| !options.contains(&o.as_str())) {
+                print_warning(format!("{}: unknown configuration option {} in the [{}] \
+                                       section.",
+                                      source.locate(&[key.as_str(), option.as_str()]),
+                                      option,
+                                      key),
+                              verbose);
+            }
+        }
+    }
+}
+
+/// Deserializes the options of a table of a configuration file
+///
+/// The options with an invalid type are reported and removed from the table, and the rest of
+/// them are deserialized again, so that a single wrong option doesn't discard the whole file.
+pub fn decode_table(mut table: Table, source: &Source, verbose: bool) -> ConfigFile {
+    loop {
+        let error = match ConfigFile::deserialize(&mut Decoder::new(Value::Table(table.clone()))) {
+            Ok(file) => return file,
+            Err(e) => e,
+        };
+        // Fields of arrays of tables, such as `permissions[0].name`, report the whole array
+        let field = error.field.clone().unwrap_or_default();
+        let path: Vec<&str> = field.split('.')
+            .map(|key| key.split('[').next().unwrap_or(key))
+            .filter(|key| !key.is_empty())
+            .collect();
+        print_warning(format!("{}: {}.\nUsing default.", source.locate(&path), error),
+                      verbose);
+        if !remove_option(&mut table, &path) {
+            return ConfigFile::default();
+        }
+    }
+}
+
+/// Removes the option with the given path of keys from the table, or the array that contains
+/// it, returning `false` if it was not found
+fn remove_option(table: &mut Table, path: &[&str]) -> bool {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    if !rest.is_empty() {
+        if let Some(&mut Value::Table(ref mut t)) = table.get_mut(*key) {
+            return remove_option(t, rest);
+        }
+    }
+    table.remove(*key).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::{Parser, Table};
+
+    use super::{Source, decode_table, find_line, remove_option};
+
+    fn parse(toml: &str) -> Table {
+        Parser::new(toml).parse().unwrap()
+    }
+
+    #[test]
+    fn it_decode_table() {
+        let toml = "threads = 4\n\
+                    historical = true\n\
+                    online_checks = true\n\
+                    malware_heuristics = true\n\
+                    trend = true\n\
+                    report_formats = [\"json\", \"md\"]\n\
+                    include_source = false\n\
+                    decompile = false\n\
+                    language = \"es\"\n\
+                    gitlab_sast = true\n\
+                    github_annotations = true\n\
+                    reproducible = true\n\
+                    downloads_folder = \"apks\"\n\
+                    dist_folder = \"decompiled\"\n\
+                    keep_dist = \"never\"\n\
+                    dist_max_age = 7\n\
+                    workspace_folder = \"/var/tmp\"\n\
+                    results_folder = \"reports\"\n\
+                    versioned_results = true\n\
+                    apktool_file = \"apktool.jar\"\n\
+                    dex2jar_folder = \"dex2jar\"\n\
+                    jd_cmd_file = \"jd-cmd.jar\"\n\
+                    results_template = \"template\"\n\
+                    templates_folder = \"templates\"\n\
+                    baseline = \"triage.json\"\n\
+                    ignore_file = \"super.ignore\"\n\
+                    source_root = \"app/src/main\"\n\
+                    rules_json = \"rules.yml\"\n\
+                    rules_extra = [\"company.json\"]\n";
+        let file = decode_table(parse(toml), &Source::file("config.toml", toml), false);
+        assert_eq!(file.threads, Some(4));
+        assert_eq!(file.historical, Some(true));
+        assert_eq!(file.online_checks, Some(true));
+        assert_eq!(file.malware_heuristics, Some(true));
+        assert_eq!(file.trend, Some(true));
+        assert_eq!(file.report_formats,
+                   Some(vec![String::from("json"), String::from("md")]));
+        assert_eq!(file.include_source, Some(false));
+        assert_eq!(file.decompile, Some(false));
+        assert_eq!(file.language, Some(String::from("es")));
+        assert_eq!(file.gitlab_sast, Some(true));
+        assert_eq!(file.github_annotations, Some(true));
+        assert_eq!(file.reproducible, Some(true));
+        assert_eq!(file.downloads_folder, Some(String::from("apks")));
+        assert_eq!(file.dist_folder, Some(String::from("decompiled")));
+        assert_eq!(file.keep_dist, Some(String::from("never")));
+        assert_eq!(file.dist_max_age, Some(7));
+        assert_eq!(file.workspace_folder, Some(String::from("/var/tmp")));
+        assert_eq!(file.results_folder, Some(String::from("reports")));
+        assert_eq!(file.versioned_results, Some(true));
+        assert_eq!(file.apktool_file, Some(String::from("apktool.jar")));
+        assert_eq!(file.dex2jar_folder, Some(String::from("dex2jar")));
+        assert_eq!(file.jd_cmd_file, Some(String::from("jd-cmd.jar")));
+        assert_eq!(file.results_template, Some(String::from("template")));
+        assert_eq!(file.templates_folder, Some(String::from("templates")));
+        assert_eq!(file.baseline, Some(String::from("triage.json")));
+        assert_eq!(file.ignore_file, Some(String::from("super.ignore")));
+        assert_eq!(file.source_root, Some(String::from("app/src/main")));
+        assert_eq!(file.rules_json, Some(String::from("rules.yml")));
+        assert_eq!(file.rules_extra, Some(vec![String::from("company.json")]));
+        assert!(file.permissions.is_none());
+        assert!(file.secrets.is_none());
+    }
+
+    #[test]
+    fn it_decode_sections() {
+        let toml = "[[permissions]]\n\
+                    name = \"unknown\"\n\
+                    criticity = \"low\"\n\
+                    description = \"Unknown permission\"\n\
+                    [secrets]\n\
+                    entropy_threshold = 4\n\
+                    min_length = 12\n\
+                    [downloads]\n\
+                    concurrency = 8\n\
+                    rate_limit = \"2M\"\n\
+                    delay = 500\n\
+                    sources = [\"fdroid\"]\n\
+                    [certificate]\n\
+                    expiry_window = 30\n\
+                    [sdk_policy]\n\
+                    min_sdk = 21\n\
+                    max_target_lag = 1\n\
+                    latest_sdk = 26\n\
+                    [vulnerability_db]\n\
+                    path = \"db.json\"\n\
+                    url = \"https://example.com/db.json\"\n\
+                    [rule_packs]\n\
+                    url = \"https://example.com/rules.json\"\n\
+                    public_key = \"00ff\"\n\
+                    [rules]\n\
+                    disabled = [\"hidden_fields\"]\n\
+                    include_paths = [\"com/example/**\"]\n\
+                    exclude_paths = [\"**/test/**\"]\n\
+                    cvss_environment = \"CR:H\"\n\
+                    [rules.criticity]\n\
+                    math_random_method = \"warning\"\n\
+                    [taint]\n\
+                    sources = [\"readMessage\"]\n\
+                    sinks = [\"runScript\"]\n\
+                    sanitizers = [\"escapeSql\"]\n\
+                    inter_procedural = false\n\
+                    [logging]\n\
+                    sensitive_names = [\"pin\"]\n\
+                    [virustotal]\n\
+                    enabled = true\n\
+                    api_key = \"key\"\n\
+                    upload = false\n\
+                    [notifications]\n\
+                    slack_webhooks = [\"https://hooks.slack.com/services/T0\"]\n\
+                    webhooks = [\"https://example.com/hook\"]\n\
+                    min_criticity = \"high\"\n\
+                    timeout = 5\n\
+                    [jobs]\n\
+                    timeout = 600\n\
+                    max_disk = 1024\n\
+                    [timeouts]\n\
+                    apktool = 60\n\
+                    dex2jar = 120\n\
+                    jd_cmd = 180\n\
+                    analysis = 600\n\
+                    [archive]\n\
+                    max_entries = 500\n\
+                    max_size = 64\n\
+                    max_compression_ratio = 50\n\
+                    [grouping]\n\
+                    deduplicate = false\n\
+                    max_samples = 10\n\
+                    [issues]\n\
+                    formats = [\"jira\", \"csv\"]\n\
+                    project = \"SEC\"\n\
+                    labels = [\"super\"]\n\
+                    min_criticity = \"high\"\n\
+                    jira_url = \"https://example.atlassian.net\"\n";
+        let file = decode_table(parse(toml), &Source::file("config.toml", toml), false);
+
+        let permissions = file.permissions.unwrap();
+        assert_eq!(permissions[0].name, Some(String::from("unknown")));
+        assert_eq!(permissions[0].criticity, Some(String::from("low")));
+        assert_eq!(permissions[0].label, None);
+        assert_eq!(permissions[0].description, Some(String::from("Unknown permission")));
+
+        let secrets = file.secrets.unwrap();
+        assert_eq!(secrets.entropy_threshold, Some(4.0));
+        assert_eq!(secrets.min_length, Some(12));
+
+        let downloads = file.downloads.unwrap();
+        assert_eq!(downloads.concurrency, Some(8));
+        assert_eq!(downloads.rate_limit, Some(String::from("2M")));
+        assert_eq!(downloads.delay, Some(500));
+        assert_eq!(downloads.sources, Some(vec![String::from("fdroid")]));
+
+        assert_eq!(file.certificate.unwrap().expiry_window, Some(30));
+
+        let sdk_policy = file.sdk_policy.unwrap();
+        assert_eq!(sdk_policy.min_sdk, Some(21));
+        assert_eq!(sdk_policy.max_target_lag, Some(1));
+        assert_eq!(sdk_policy.latest_sdk, Some(26));
+
+        let vulnerability_db = file.vulnerability_db.unwrap();
+        assert_eq!(vulnerability_db.path, Some(String::from("db.json")));
+        assert_eq!(vulnerability_db.url, Some(String::from("https://example.com/db.json")));
+
+        let rule_packs = file.rule_packs.unwrap();
+        assert_eq!(rule_packs.url, Some(String::from("https://example.com/rules.json")));
+        assert_eq!(rule_packs.public_key, Some(String::from("00ff")));
+
+        let rules = file.rules.unwrap();
+        assert_eq!(rules.disabled, Some(vec![String::from("hidden_fields")]));
+        assert_eq!(rules.include_paths, Some(vec![String::from("com/example/**")]));
+        assert_eq!(rules.exclude_paths, Some(vec![String::from("**/test/**")]));
+        assert_eq!(rules.cvss_environment, Some(String::from("CR:H")));
+        assert_eq!(rules.criticity.unwrap().get("math_random_method"),
+                   Some(&String::from("warning")));
+
+        let taint = file.taint.unwrap();
+        assert_eq!(taint.sources, Some(vec![String::from("readMessage")]));
+        assert_eq!(taint.sinks, Some(vec![String::from("runScript")]));
+        assert_eq!(taint.sanitizers, Some(vec![String::from("escapeSql")]));
+        assert_eq!(taint.inter_procedural, Some(false));
+
+        assert_eq!(file.logging.unwrap().sensitive_names, Some(vec![String::from("pin")]));
+
+        let virustotal = file.virustotal.unwrap();
+        assert_eq!(virustotal.enabled, Some(true));
+        assert_eq!(virustotal.api_key, Some(String::from("key")));
+        assert_eq!(virustotal.upload, Some(false));
+
+        let notifications = file.notifications.unwrap();
+        assert_eq!(notifications.slack_webhooks,
+                   Some(vec![String::from("https://hooks.slack.com/services/T0")]));
+        assert_eq!(notifications.webhooks, Some(vec![String::from("https://example.com/hook")]));
+        assert_eq!(notifications.min_criticity, Some(String::from("high")));
+        assert_eq!(notifications.timeout, Some(5));
+
+        let jobs = file.jobs.unwrap();
+        assert_eq!(jobs.timeout, Some(600));
+        assert_eq!(jobs.max_disk, Some(1024));
+
+        let timeouts = file.timeouts.unwrap();
+        assert_eq!(timeouts.apktool, Some(60));
+        assert_eq!(timeouts.dex2jar, Some(120));
+        assert_eq!(timeouts.jd_cmd, Some(180));
+        assert_eq!(timeouts.analysis, Some(600));
+
+        let archive = file.archive.unwrap();
+        assert_eq!(archive.max_entries, Some(500));
+        assert_eq!(archive.max_size, Some(64));
+        assert_eq!(archive.max_compression_ratio, Some(50));
+
+        let grouping = file.grouping.unwrap();
+        assert_eq!(grouping.deduplicate, Some(false));
+        assert_eq!(grouping.max_samples, Some(10));
+
+        let issues = file.issues.unwrap();
+        assert_eq!(issues.formats,
+                   Some(vec![String::from("jira"), String::from("csv")]));
+        assert_eq!(issues.project, Some(String::from("SEC")));
+        assert_eq!(issues.issue_type, None);
+        assert_eq!(issues.labels, Some(vec![String::from("super")]));
+        assert_eq!(issues.min_criticity, Some(String::from("high")));
+        assert_eq!(issues.jira_url, Some(String::from("https://example.atlassian.net")));
+    }
+
+    #[test]
+    fn it_decode_invalid_options() {
+        let toml = "threads = \"four\"\n\
+                    trend = true\n\
+                    [secrets]\n\
+                    min_length = \"long\"\n\
+                    entropy_threshold = 5.5\n";
+        let file = decode_table(parse(toml), &Source::file("config.toml", toml), false);
+        assert_eq!(file.threads, None);
+        assert_eq!(file.trend, Some(true));
+        let secrets = file.secrets.unwrap();
+        assert_eq!(secrets.min_length, None);
+        assert_eq!(secrets.entropy_threshold, Some(5.5));
+
+        let mut table = parse(toml);
+        assert!(remove_option(&mut table, &["secrets", "min_length"]));
+        assert!(!remove_option(&mut table, &["secrets", "min_length"]));
+        assert!(remove_option(&mut table, &["threads"]));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn it_locate_options() {
+        let toml = "threads = 2\n\
+                    \n\
+                    [secrets]\n\
+                    min_length = 8\n\
+                    [rules]\n\
+                    disabled = []\n\
+                    [rules.criticity]\n\
+                    math_random_method = \"warning\"\n";
+        assert_eq!(find_line(toml, "", "threads"), Some(1));
+        assert_eq!(find_line(toml, "secrets", "min_length"), Some(4));
+        assert_eq!(find_line(toml, "rules", "criticity"), Some(7));
+        assert_eq!(find_line(toml, "rules.criticity", "math_random_method"), Some(8));
+        assert_eq!(find_line(toml, "", "language"), None);
+
+        let source = Source::file("config.toml", toml);
+        assert_eq!(source.locate(&["secrets", "min_length"]), "config.toml, line 4");
+        assert_eq!(Source::profile("profile quick").locate(&["threads"]), "profile quick");
+    }
+}
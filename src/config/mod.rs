@@ -0,0 +1,3193 @@
+use std::{u8, fs, env};
+use std::path::{Path, PathBuf};
+use std::convert::From;
+use std::str::FromStr;
+use std::io::Read;
+use std::process::exit;
+use std::collections::btree_set::Iter;
+use std::slice::Iter as VecIter;
+use std::collections::{BTreeSet, BTreeMap};
+use std::cmp::{PartialOrd, Ordering};
+
+use colored::Colorize;
+use toml::{Parser, Value, Table};
+use regex::Regex;
+
+use static_analysis::manifest::{Permission, ManifestFlag};
+use static_analysis::code::is_rule_file;
+use static_analysis::cvss::CvssEnvironment;
+use results::{ReportFormat, IssueFormat, LANGUAGES, is_language};
+use tools::check_tools;
+use rule_packs::{get_rules_folder, get_rule_pack_files};
+
+use {Error, Result, Criticity, print_error, print_warning, file_exists};
+
+mod file;
+mod builder;
+
+use self::file::{ConfigFile, PermissionEntry, Source, check_unknown_options, decode_table};
+pub use self::builder::ConfigBuilder;
+
+const MAX_THREADS: i64 = u8::MAX as i64;
+
+/// Ignore file used if it exists in the working directory and no other one is configured
+const DEFAULT_IGNORE_FILE: &'static str = ".superignore";
+
+#[derive(Debug)]
+pub struct Config {
+    app_id: String,
+    verbose: bool,
+    quiet: bool,
+    force: bool,
+    bench: bool,
+    profile_rules: bool,
+    historical: bool,
+    online_checks: bool,
+    malware_heuristics: bool,
+    trend: bool,
+    gitlab_sast: bool,
+    github_annotations: bool,
+    reproducible: bool,
+    show_tool_output: bool,
+    report_formats: Vec<ReportFormat>,
+    include_source: bool,
+    decompile: bool,
+    language: String,
+    mapping_file: Option<String>,
+    baseline: Option<String>,
+    ignore_file: Option<String>,
+    source_root: Option<String>,
+    threads: u8,
+    downloads_folder: String,
+    dist_folder: String,
+    keep_dist: KeepDist,
+    dist_max_age: u64,
+    workspace_folder: Option<String>,
+    results_folder: String,
+    versioned_results: bool,
+    apktool_file: String,
+    dex2jar_folder: String,
+    jd_cmd_file: String,
+    results_template: String,
+    templates_folder: Option<String>,
+    rules_json: String,
+    rules_extra: Vec<String>,
+    unknown_permission: (Criticity, String),
+    permissions: BTreeSet<PermissionConfig>,
+    loaded_files: Vec<String>,
+    secrets: SecretsConfig,
+    downloads: DownloadsConfig,
+    certificate: CertificateConfig,
+    manifest_flags: BTreeMap<ManifestFlag, Option<Criticity>>,
+    sdk_policy: SdkPolicyConfig,
+    vulnerability_db: VulnerabilityDbConfig,
+    rule_packs: RulePacksConfig,
+    rules: RulesConfig,
+    taint: TaintConfig,
+    logging: LoggingConfig,
+    virustotal: VirusTotalConfig,
+    notifications: NotificationsConfig,
+    jobs: JobsConfig,
+    timeouts: TimeoutsConfig,
+    archive: ArchiveConfig,
+    grouping: GroupingConfig,
+    issues: IssuesConfig,
+    profiles: BTreeMap<String, Table>,
+    profile: Option<String>,
+    origins: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Loads the configuration files, each one overriding the options of the previous ones
+    #[cfg(target_family = "unix")]
+    fn load_files(&mut self) -> Result<()> {
+        let verbose = self.verbose;
+        // The installed rule packs are merged before the extra rules of the configuration files
+        if let Some(folder) = get_rules_folder() {
+            self.rules_extra.extend(get_rule_pack_files(folder));
+        }
+
+        if file_exists("/etc/config.toml") {
+            try!(Config::load_from_file(self, "/etc/config.toml", verbose));
+            self.loaded_files.push(String::from("/etc/config.toml"));
+        }
+        if let Some(path) = get_user_config_file() {
+            if file_exists(&path) {
+                try!(Config::load_from_file(self, &path, verbose));
+                self.loaded_files.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if file_exists("./config.toml") {
+            try!(Config::load_from_file(self, "./config.toml", verbose));
+            self.loaded_files.push(String::from("./config.toml"));
+        }
+
+        Ok(())
+    }
+
+    /// Loads the configuration files, each one overriding the options of the previous ones
+    #[cfg(target_family = "windows")]
+    fn load_files(&mut self) -> Result<()> {
+        let verbose = self.verbose;
+        // The installed rule packs are merged before the extra rules of the configuration files
+        if let Some(folder) = get_rules_folder() {
+            self.rules_extra.extend(get_rule_pack_files(folder));
+        }
+
+        if let Some(path) = get_user_config_file() {
+            if file_exists(&path) {
+                try!(Config::load_from_file(self, &path, verbose));
+                self.loaded_files.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if file_exists("config.toml") {
+            try!(Config::load_from_file(self, "config.toml", verbose));
+            self.loaded_files.push(String::from("config.toml"));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the files of the configuration exist, and that Java and the external tools
+    /// can be run and have supported versions
+    pub fn check(&self) -> bool {
+        file_exists(&self.downloads_folder) &&
+        (self.app_id.is_empty() ||
+         file_exists(format!("{}/{}.apk", self.downloads_folder, self.app_id))) &&
+        file_exists(&self.apktool_file) &&
+        (!self.decompile || file_exists(&self.dex2jar_folder) && file_exists(&self.jd_cmd_file)) &&
+        file_exists(&self.results_template) &&
+        file_exists(&self.rules_json) &&
+        self.rules_extra.iter().all(|r| file_exists(r)) &&
+        self.mapping_file.as_ref().map_or(true, |m| file_exists(m)) &&
+        self.templates_folder.as_ref().map_or(true, |t| file_exists(t)) &&
+        self.baseline.as_ref().map_or(true, |b| file_exists(b)) &&
+        self.ignore_file.as_ref().map_or(true, |i| file_exists(i)) &&
+        self.source_root.as_ref().map_or(true, |r| file_exists(r)) &&
+        check_tools(self).is_empty()
+    }
+
+    pub fn get_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !file_exists(&self.downloads_folder) {
+            errors.push(format!("the downloads folder `{}` does not exist",
+                                self.downloads_folder));
+        }
+        // The APK file is only checked when there is an application to analyze
+        if !self.app_id.is_empty() &&
+           !file_exists(format!("{}/{}.apk", self.downloads_folder, self.app_id)) {
+            errors.push(format!("the APK file `{}` does not exist",
+                                format!("{}/{}.apk", self.downloads_folder, self.app_id)));
+        }
+        if !file_exists(&self.apktool_file) {
+            errors.push(format!("the APKTool JAR file `{}` does not exist",
+                                self.apktool_file));
+        }
+        // Dex2Jar and JD-CMD are only needed to decompile the code
+        if self.decompile && !file_exists(&self.dex2jar_folder) {
+            errors.push(format!("the Dex2Jar folder `{}` does not exist",
+                                self.dex2jar_folder));
+        }
+        if self.decompile && !file_exists(&self.jd_cmd_file) {
+            errors.push(format!("the jd-cmd file `{}` does not exist", self.jd_cmd_file));
+        }
+        if !file_exists(&self.results_template) {
+            errors.push(format!("the results template `{}` does not exist",
+                                self.results_template));
+        }
+        if !file_exists(&self.rules_json) {
+            errors.push(format!("the `{}` rule file does not exist", self.rules_json));
+        }
+        for rules_extra in &self.rules_extra {
+            if !file_exists(rules_extra) {
+                errors.push(format!("the `{}` extra rule file does not exist", rules_extra));
+            }
+        }
+        if let Some(ref mapping_file) = self.mapping_file {
+            if !file_exists(mapping_file) {
+                errors.push(format!("the mapping file `{}` does not exist", mapping_file));
+            }
+        }
+        if let Some(ref baseline) = self.baseline {
+            if !file_exists(baseline) {
+                errors.push(format!("the baseline file `{}` does not exist", baseline));
+            }
+        }
+        if let Some(ref ignore_file) = self.ignore_file {
+            if !file_exists(ignore_file) {
+                errors.push(format!("the ignore file `{}` does not exist", ignore_file));
+            }
+        }
+        if let Some(ref source_root) = self.source_root {
+            if !file_exists(source_root) {
+                errors.push(format!("the source root `{}` does not exist", source_root));
+            }
+        }
+        if let Some(ref templates_folder) = self.templates_folder {
+            if !file_exists(templates_folder) {
+                errors.push(format!("the templates folder `{}` does not exist",
+                                    templates_folder));
+            }
+        }
+        errors.extend(check_tools(self));
+        errors
+    }
+
+    pub fn get_loaded_config_files(&self) -> VecIter<String> {
+        self.loaded_files.iter()
+    }
+
+    /// Applies the options of the profile with the given name, from the `[profile.{name}]`
+    /// sections of the configuration files, over the loaded configuration
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.get(name).cloned();
+        match profile {
+            Some(profile) => {
+                let verbose = self.verbose;
+                let source = format!("profile {}", name);
+                Config::load_table(self, profile, &Source::profile(&source), verbose);
+                self.profile = Some(String::from(name));
+                Ok(())
+            }
+            None => Err(Error::Config),
+        }
+    }
+
+    /// Gets the name of the profile applied to the configuration, if any
+    pub fn get_profile(&self) -> Option<&str> {
+        match self.profile {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the names of the profiles defined in the configuration files
+    pub fn get_profiles(&self) -> Vec<&str> {
+        self.profiles.keys().map(|p| p.as_str()).collect()
+    }
+
+    /// Gets where the given option was set: the configuration file or profile that set it last,
+    /// or `default` if it was not set
+    pub fn get_origin(&self, option: &str) -> &str {
+        self.origins.get(option).map_or("default", |o| o.as_str())
+    }
+
+    /// Gets the options of the effective configuration, with their values and their origins
+    ///
+    /// The values of the sections are shown as they are stored, except the ones that can contain
+    /// credentials, such as API keys or webhook URLs, that are hidden.
+    pub fn get_effective_options(&self) -> Vec<(&'static str, String, &str)> {
+        let optional = |o: &Option<String>| match *o {
+            Some(ref s) => format!("{:?}", s),
+            None => String::from("(not set)"),
+        };
+        let hidden = String::from("(hidden, it can contain credentials)");
+        let report_formats: Vec<_> =
+            self.report_formats.iter().map(|f| format!("{:?}", f.as_str())).collect();
+
+        let options = vec![("threads", self.threads.to_string()),
+                           ("downloads_folder", format!("{:?}", self.downloads_folder)),
+                           ("dist_folder", format!("{:?}", self.dist_folder)),
+                           ("keep_dist", format!("{:?}", self.keep_dist.as_str())),
+                           ("dist_max_age", self.dist_max_age.to_string()),
+                           ("workspace_folder", optional(&self.workspace_folder)),
+                           ("results_folder", format!("{:?}", self.results_folder)),
+                           ("versioned_results", self.versioned_results.to_string()),
+                           ("apktool_file", format!("{:?}", self.apktool_file)),
+                           ("jd_cmd_file", format!("{:?}", self.jd_cmd_file)),
+                           ("results_template", format!("{:?}", self.results_template)),
+                           ("templates_folder", optional(&self.templates_folder)),
+                           ("rules_json", format!("{:?}", self.rules_json)),
+                           ("rules_extra", format!("{:?}", self.rules_extra)),
+                           ("baseline", optional(&self.baseline)),
+                           ("ignore_file", optional(&self.ignore_file)),
+                           ("source_root", optional(&self.source_root)),
+                           ("historical", self.historical.to_string()),
+                           ("online_checks", self.online_checks.to_string()),
+                           ("malware_heuristics", self.malware_heuristics.to_string()),
+                           ("trend", self.trend.to_string()),
+                           ("gitlab_sast", self.gitlab_sast.to_string()),
+                           ("github_annotations", self.github_annotations.to_string()),
+                           ("reproducible", self.reproducible.to_string()),
+                           ("report_formats", format!("[{}]", report_formats.join(", "))),
+                           ("include_source", self.include_source.to_string()),
+                           ("decompile", self.decompile.to_string()),
+                           ("language", format!("{:?}", self.language)),
+                           ("permissions",
+                            format!("{} permission rules", self.permissions.len())),
+                           ("secrets", format!("{:?}", self.secrets)),
+                           ("downloads", format!("{:?}", self.downloads)),
+                           ("certificate", format!("{:?}", self.certificate)),
+                           ("manifest_flags", format!("{:?}", self.manifest_flags)),
+                           ("sdk_policy", format!("{:?}", self.sdk_policy)),
+                           ("vulnerability_db", format!("{:?}", self.vulnerability_db)),
+                           ("rule_packs", format!("{:?}", self.rule_packs)),
+                           ("rules", format!("{:?}", self.rules)),
+                           ("taint", format!("{:?}", self.taint)),
+                           ("logging", format!("{:?}", self.logging)),
+                           ("virustotal", hidden.clone()),
+                           ("notifications", hidden.clone()),
+                           ("jobs", format!("{:?}", self.jobs)),
+                           ("timeouts", format!("{:?}", self.timeouts)),
+                           ("archive", format!("{:?}", self.archive)),
+                           ("grouping", format!("{:?}", self.grouping)),
+                           ("issues", hidden)];
+        options.into_iter().map(|(key, value)| (key, value, self.get_origin(key))).collect()
+    }
+
+    pub fn get_app_id(&self) -> &str {
+        self.app_id.as_str()
+    }
+
+    pub fn set_app_id(&mut self, app_id: &str) {
+        self.app_id = String::from(app_id);
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    pub fn is_force(&self) -> bool {
+        self.force
+    }
+
+    pub fn set_force(&mut self, force: bool) {
+        self.force = force;
+    }
+
+    pub fn is_bench(&self) -> bool {
+        self.bench
+    }
+
+    pub fn set_bench(&mut self, bench: bool) {
+        self.bench = bench;
+    }
+
+    /// Checks if the number of files, matches and time of each code rule should be reported
+    pub fn is_profile_rules(&self) -> bool {
+        self.profile_rules
+    }
+
+    pub fn set_profile_rules(&mut self, profile_rules: bool) {
+        self.profile_rules = profile_rules;
+    }
+
+    pub fn is_historical(&self) -> bool {
+        self.historical
+    }
+
+    pub fn set_historical(&mut self, historical: bool) {
+        self.historical = historical;
+    }
+
+    /// Checks if the cloud services referenced by the application should be probed online
+    pub fn is_online_checks(&self) -> bool {
+        self.online_checks
+    }
+
+    pub fn set_online_checks(&mut self, online_checks: bool) {
+        self.online_checks = online_checks;
+    }
+
+    /// Checks if the malware likelihood score should be calculated
+    pub fn is_malware_heuristics(&self) -> bool {
+        self.malware_heuristics
+    }
+
+    pub fn set_malware_heuristics(&mut self, malware_heuristics: bool) {
+        self.malware_heuristics = malware_heuristics;
+    }
+
+    /// Checks if the trend page of the findings across the analyzed versions should be generated
+    pub fn is_trend(&self) -> bool {
+        self.trend
+    }
+
+    pub fn set_trend(&mut self, trend: bool) {
+        self.trend = trend;
+    }
+
+    /// Checks if the report in the GitLab SAST format should be generated
+    pub fn is_gitlab_sast(&self) -> bool {
+        self.gitlab_sast
+    }
+
+    pub fn set_gitlab_sast(&mut self, gitlab_sast: bool) {
+        self.gitlab_sast = gitlab_sast;
+    }
+
+    /// Checks if the findings mapped to the source code should be printed as GitHub Actions
+    /// annotations
+    pub fn is_github_annotations(&self) -> bool {
+        self.github_annotations
+    }
+
+    pub fn set_github_annotations(&mut self, github_annotations: bool) {
+        self.github_annotations = github_annotations;
+    }
+
+    /// Checks if the JSON report should be reproducible, without the absolute paths and the
+    /// timestamps that change between runs
+    pub fn is_reproducible(&self) -> bool {
+        self.reproducible
+    }
+
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+    }
+
+    /// Checks if the output of the external tools should be shown as they run, in verbose mode
+    pub fn is_show_tool_output(&self) -> bool {
+        self.show_tool_output
+    }
+
+    pub fn set_show_tool_output(&mut self, show_tool_output: bool) {
+        self.show_tool_output = show_tool_output;
+    }
+
+    /// Gets the formats of the generated reports
+    pub fn get_report_formats(&self) -> &[ReportFormat] {
+        self.report_formats.as_slice()
+    }
+
+    /// Checks if the report should be generated in the given format
+    pub fn has_report_format(&self, format: ReportFormat) -> bool {
+        self.report_formats.contains(&format)
+    }
+
+    pub fn set_report_formats(&mut self, report_formats: Vec<ReportFormat>) {
+        self.report_formats = report_formats;
+    }
+
+    /// Checks if the decompiled source code should be included in the HTML report
+    pub fn is_include_source(&self) -> bool {
+        self.include_source
+    }
+
+    pub fn set_include_source(&mut self, include_source: bool) {
+        self.include_source = include_source;
+    }
+
+    /// Checks if the code should be decompiled with Dex2Jar and JD-CMD, instead of only being
+    /// scanned from the DEX files
+    pub fn is_decompile(&self) -> bool {
+        self.decompile
+    }
+
+    pub fn set_decompile(&mut self, decompile: bool) {
+        self.decompile = decompile;
+    }
+
+    /// Gets the language of the reports
+    pub fn get_language(&self) -> &str {
+        self.language.as_str()
+    }
+
+    pub fn set_language<S: Into<String>>(&mut self, language: S) {
+        self.language = language.into();
+    }
+
+    /// Gets the ProGuard or R8 mapping file used to de-obfuscate the names in the report
+    pub fn get_mapping_file(&self) -> Option<&str> {
+        match self.mapping_file {
+            Some(ref m) => Some(m.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_mapping_file(&mut self, mapping_file: &str) {
+        self.mapping_file = Some(String::from(mapping_file));
+    }
+
+    /// Gets the triage file exported from a previous HTML report, with the findings to suppress
+    pub fn get_baseline(&self) -> Option<&str> {
+        match self.baseline {
+            Some(ref b) => Some(b.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_baseline(&mut self, baseline: &str) {
+        self.baseline = Some(String::from(baseline));
+    }
+
+    /// Gets the ignore file with the paths, rules and rule and path pairs to leave out of the
+    /// reports
+    ///
+    /// If it's not set, the `.superignore` file of the working directory is used, if it exists.
+    pub fn get_ignore_file(&self) -> Option<&str> {
+        match self.ignore_file {
+            Some(ref i) => Some(i.as_str()),
+            None if file_exists(DEFAULT_IGNORE_FILE) => Some(DEFAULT_IGNORE_FILE),
+            None => None,
+        }
+    }
+
+    /// Gets the root folder of the source code of the application, to map the findings in the
+    /// decompiled files to it
+    pub fn get_source_root(&self) -> Option<&str> {
+        match self.source_root {
+            Some(ref r) => Some(r.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_source_root(&mut self, source_root: &str) {
+        self.source_root = Some(String::from(source_root));
+    }
+
+    pub fn get_threads(&self) -> u8 {
+        self.threads
+    }
+
+    pub fn set_threads(&mut self, threads: u8) {
+        self.threads = threads;
+    }
+
+    pub fn get_downloads_folder(&self) -> &str {
+        self.downloads_folder.as_str()
+    }
+
+    pub fn set_downloads_folder<S: Into<String>>(&mut self, downloads_folder: S) {
+        self.downloads_folder = downloads_folder.into();
+    }
+
+    pub fn get_dist_folder(&self) -> &str {
+        self.dist_folder.as_str()
+    }
+
+    pub fn set_dist_folder<S: Into<String>>(&mut self, dist_folder: S) {
+        self.dist_folder = dist_folder.into();
+    }
+
+    /// Gets the policy to keep the dist folder of the application after its analysis
+    pub fn get_keep_dist(&self) -> KeepDist {
+        self.keep_dist
+    }
+
+    pub fn set_keep_dist(&mut self, keep_dist: KeepDist) {
+        self.keep_dist = keep_dist;
+    }
+
+    /// Gets the number of days after which the folders in the dist folder are removed, or `0` if
+    /// they are never removed
+    pub fn get_dist_max_age(&self) -> u64 {
+        self.dist_max_age
+    }
+
+    pub fn set_dist_max_age(&mut self, dist_max_age: u64) {
+        self.dist_max_age = dist_max_age;
+    }
+
+    /// Gets the folder where the temporary workspaces are created, instead of the temporary
+    /// folder of the system
+    pub fn get_workspace_folder(&self) -> Option<&str> {
+        match self.workspace_folder {
+            Some(ref w) => Some(w.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_workspace_folder(&mut self, workspace_folder: &str) {
+        self.workspace_folder = Some(String::from(workspace_folder));
+    }
+
+    pub fn get_results_folder(&self) -> &str {
+        self.results_folder.as_str()
+    }
+
+    pub fn set_results_folder<S: Into<String>>(&mut self, results_folder: S) {
+        self.results_folder = results_folder.into();
+    }
+
+    /// Checks if each analysis should be saved in its own folder, as
+    /// `{results}/{package}/{version_num}/{timestamp}`, instead of replacing the results of the
+    /// previous analysis
+    pub fn is_versioned_results(&self) -> bool {
+        self.versioned_results
+    }
+
+    pub fn set_versioned_results(&mut self, versioned_results: bool) {
+        self.versioned_results = versioned_results;
+    }
+
+    pub fn get_apktool_file(&self) -> &str {
+        self.apktool_file.as_str()
+    }
+
+    pub fn set_apktool_file<S: Into<String>>(&mut self, apktool_file: S) {
+        self.apktool_file = apktool_file.into();
+    }
+
+    pub fn get_dex2jar_folder(&self) -> &str {
+        self.dex2jar_folder.as_str()
+    }
+
+    pub fn set_dex2jar_folder<S: Into<String>>(&mut self, dex2jar_folder: S) {
+        self.dex2jar_folder = dex2jar_folder.into();
+    }
+
+    pub fn get_jd_cmd_file(&self) -> &str {
+        self.jd_cmd_file.as_str()
+    }
+
+    pub fn set_jd_cmd_file<S: Into<String>>(&mut self, jd_cmd_file: S) {
+        self.jd_cmd_file = jd_cmd_file.into();
+    }
+
+    pub fn get_results_template(&self) -> &str {
+        self.results_template.as_str()
+    }
+
+    pub fn set_results_template<S: Into<String>>(&mut self, results_template: S) {
+        self.results_template = results_template.into();
+    }
+
+    /// Gets the folder with the `.hbs` templates overriding the built-in report templates
+    pub fn get_templates_folder(&self) -> Option<&str> {
+        match self.templates_folder {
+            Some(ref t) => Some(t.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_templates_folder(&mut self, templates_folder: &str) {
+        self.templates_folder = Some(String::from(templates_folder));
+    }
+
+    pub fn get_rules_json(&self) -> &str {
+        self.rules_json.as_str()
+    }
+
+    pub fn set_rules_json<S: Into<String>>(&mut self, rules_json: S) {
+        self.rules_json = rules_json.into();
+    }
+
+    /// Gets the extra rule files or directories, merged in order over the rules of `rules_json`
+    pub fn get_rules_extra(&self) -> &[String] {
+        &self.rules_extra
+    }
+
+    /// Adds an extra rule file or directory, that overrides the rules with the same IDs
+    pub fn add_rules_extra<S: Into<String>>(&mut self, rules_extra: S) {
+        self.rules_extra.push(rules_extra.into());
+    }
+
+    pub fn get_unknown_permission_criticity(&self) -> Criticity {
+        self.unknown_permission.0
+    }
+
+    pub fn get_unknown_permission_description(&self) -> &str {
+        self.unknown_permission.1.as_str()
+    }
+
+    pub fn get_permissions(&self) -> Iter<PermissionConfig> {
+        self.permissions.iter()
+    }
+
+    pub fn get_secrets_config(&self) -> &SecretsConfig {
+        &self.secrets
+    }
+
+    pub fn get_downloads_config(&self) -> &DownloadsConfig {
+        &self.downloads
+    }
+
+    pub fn get_certificate_config(&self) -> &CertificateConfig {
+        &self.certificate
+    }
+
+    pub fn get_sdk_policy(&self) -> &SdkPolicyConfig {
+        &self.sdk_policy
+    }
+
+    pub fn get_vulnerability_db_config(&self) -> &VulnerabilityDbConfig {
+        &self.vulnerability_db
+    }
+
+    pub fn get_rule_packs_config(&self) -> &RulePacksConfig {
+        &self.rule_packs
+    }
+
+    pub fn get_rules_config(&self) -> &RulesConfig {
+        &self.rules
+    }
+
+    pub fn get_taint_config(&self) -> &TaintConfig {
+        &self.taint
+    }
+
+    pub fn get_logging_config(&self) -> &LoggingConfig {
+        &self.logging
+    }
+
+    pub fn get_virustotal_config(&self) -> &VirusTotalConfig {
+        &self.virustotal
+    }
+
+    /// Enables or disables the VirusTotal lookup, keeping the rest of its configuration
+    pub fn set_virustotal(&mut self, enabled: bool) {
+        self.virustotal.enabled = enabled;
+    }
+
+    pub fn get_notifications_config(&self) -> &NotificationsConfig {
+        &self.notifications
+    }
+
+    pub fn get_jobs_config(&self) -> &JobsConfig {
+        &self.jobs
+    }
+
+    pub fn get_timeouts_config(&self) -> &TimeoutsConfig {
+        &self.timeouts
+    }
+
+    pub fn get_archive_config(&self) -> &ArchiveConfig {
+        &self.archive
+    }
+
+    pub fn get_grouping_config(&self) -> &GroupingConfig {
+        &self.grouping
+    }
+
+    /// Gets the configuration of the export of the findings to issue trackers
+    pub fn get_issues_config(&self) -> &IssuesConfig {
+        &self.issues
+    }
+
+    /// Sets the timeout of each external tool, in seconds, or 0 for no timeout
+    pub fn set_tool_timeout(&mut self, timeout: u64) {
+        self.timeouts.apktool = timeout;
+        self.timeouts.dex2jar = timeout;
+        self.timeouts.jd_cmd = timeout;
+    }
+
+    /// Sets the timeout of the whole analysis, in seconds, or 0 for no timeout
+    pub fn set_analysis_timeout(&mut self, timeout: u64) {
+        self.timeouts.analysis = timeout;
+    }
+
+    /// Gets the criticity of the manifest flag findings, or `None` if the check is disabled
+    pub fn get_manifest_flag_criticity(&self, flag: ManifestFlag) -> Option<Criticity> {
+        match self.manifest_flags.get(&flag) {
+            Some(criticity) => *criticity,
+            None => Some(flag.get_default_criticity()),
+        }
+    }
+
+    fn load_from_file<P: AsRef<Path>>(config: &mut Config, path: P, verbose: bool) -> Result<()> {
+        let mut f = try!(fs::File::open(path.as_ref()));
+        let mut text = String::new();
+        try!(f.read_to_string(&mut text));
+
+        let name = path.as_ref().to_string_lossy();
+        let mut parser = Parser::new(text.as_str());
+        let toml = match parser.parse() {
+            Some(t) => t,
+            None => {
+                let errors: Vec<String> = parser.errors
+                    .iter()
+                    .map(|e| {
+                        let (line, col) = parser.to_linecol(e.lo);
+                        format!("line {}, column {}: {}", line + 1, col + 1, e.desc)
+                    })
+                    .collect();
+                print_error(format!("There was an error parsing the {} file:\n{}",
+                                    name,
+                                    errors.join("\n")),
+                            verbose);
+                exit(Error::ParseError.into());
+            }
+        };
+
+        Config::load_table(config, toml, &Source::file(&name, &text), verbose);
+        Ok(())
+    }
+
+    /// Loads the options of a table of a configuration file, that can be the whole file or one
+    /// of its profiles, recording the given source as the origin of each option
+    fn load_table(config: &mut Config, mut toml: Table, source: &Source, verbose: bool) {
+        for key in toml.keys() {
+            let _ = config.origins.insert(key.clone(), String::from(source.get_name()));
+        }
+        check_unknown_options(&toml, source, verbose);
+
+        // The keys of profiles and manifest flags are not known beforehand
+        if let Some(profiles) = toml.remove("profile") {
+            Config::load_profiles(config, profiles, verbose);
+        }
+        if let Some(flags) = toml.remove("manifest_flags") {
+            Config::load_manifest_flags(config, flags, verbose);
+        }
+
+        let file = decode_table(toml, source, verbose);
+        Config::apply_file(config, file, verbose);
+    }
+
+    /// Loads the profiles of the `[profile]` section of a configuration file
+    fn load_profiles(config: &mut Config, profiles: Value, verbose: bool) {
+        match profiles {
+            Value::Table(t) => {
+                for (name, profile) in t {
+                    match profile {
+                        Value::Table(p) => {
+                            // Later files override the options of the profile
+                            config.profiles
+                                .entry(name)
+                                .or_insert_with(Table::new)
+                                .extend(p);
+                        }
+                        _ => {
+                            print_warning(format!("The '{}' profile in config.toml must be a \
+                                                   section.\nIgnoring it.",
+                                                  name),
+                                          verbose)
+                        }
+                    }
+                }
+            }
+            _ => {
+                print_warning("The 'profile' option in config.toml must be a section.\nIgnoring \
+                               it.",
+                              verbose)
+            }
+        }
+    }
+
+    /// Loads the criticities of the `[manifest_flags]` section of a configuration file
+    fn load_manifest_flags(config: &mut Config, flags: Value, verbose: bool) {
+        match flags {
+            Value::Table(t) => {
+                for (key, value) in t {
+                    let flag = match ManifestFlag::from_str(key.as_str()) {
+                        Ok(f) => f,
+                        Err(_) => {
+                            print_warning(format!("Unknown manifest flag {} in the \
+                                                   [manifest_flags] section.",
+                                                  key),
+                                          verbose);
+                            continue;
+                        }
+                    };
+                    match value {
+                        Value::String(ref c) if Criticity::from_str(c).is_ok() => {
+                            let criticity = Criticity::from_str(c).unwrap();
+                            let _ = config.manifest_flags.insert(flag, Some(criticity));
+                        }
+                        Value::Boolean(false) => {
+                            let _ = config.manifest_flags.insert(flag, None);
+                        }
+                        _ => {
+                            print_warning(format!("The '{}' option in the [manifest_flags] \
+                                                   section of config.toml must be a criticity, \
+                                                   or false to disable the check.\nUsing \
+                                                   default.",
+                                                  key),
+                                          verbose)
+                        }
+                    }
+                }
+            }
+            _ => {
+                print_warning("The 'manifest_flags' option in config.toml must be a \
+                               section.\nUsing default.",
+                              verbose)
+            }
+        }
+    }
+
+    /// Applies the options of a deserialized configuration file, checking their values
+    ///
+    /// The lists of the `[downloads]`, `[rules]`, `[taint]`, `[logging]` and `[notifications]`
+    /// sections are appended to the ones of the previous files, and the rest of the options
+    /// override them.
+    fn apply_file(config: &mut Config, file: ConfigFile, verbose: bool) {
+        match file.threads {
+            Some(t @ 1...MAX_THREADS) => config.threads = t as u8,
+            Some(_) => {
+                print_warning(format!("The 'threads' option in config.toml must be an integer \
+                                       between 1 and {}.\nUsing default.",
+                                      MAX_THREADS),
+                              verbose)
+            }
+            None => {}
+        }
+        if let Some(b) = file.historical {
+            config.historical = b;
+        }
+        if let Some(b) = file.online_checks {
+            config.online_checks = b;
+        }
+        if let Some(b) = file.malware_heuristics {
+            config.malware_heuristics = b;
+        }
+        if let Some(b) = file.trend {
+            config.trend = b;
+        }
+        if let Some(formats) = file.report_formats {
+            let mut report_formats = Vec::with_capacity(formats.len());
+            for format in formats {
+                match ReportFormat::from_str(&format) {
+                    Ok(format) => {
+                        if !report_formats.contains(&format) {
+                            report_formats.push(format);
+                        }
+                    }
+                    Err(_) => {
+                        print_warning(format!("The report format {} is not valid. It must be \
+                                               \"html\", \"json\", \"md\" or \"pdf\".",
+                                              format),
+                                      verbose)
+                    }
+                }
+            }
+            config.report_formats = report_formats;
+        }
+        if let Some(b) = file.include_source {
+            config.include_source = b;
+        }
+        if let Some(b) = file.decompile {
+            config.decompile = b;
+        }
+        if let Some(language) = file.language {
+            if is_language(&language) {
+                config.language = language;
+            } else {
+                print_warning(format!("The 'language' option in config.toml must be one of \
+                                       {}.\nUsing default.",
+                                      LANGUAGES.join(", ")),
+                              verbose)
+            }
+        }
+        if let Some(b) = file.gitlab_sast {
+            config.gitlab_sast = b;
+        }
+        if let Some(b) = file.github_annotations {
+            config.github_annotations = b;
+        }
+        if let Some(b) = file.reproducible {
+            config.reproducible = b;
+        }
+        if let Some(folder) = file.downloads_folder {
+            config.downloads_folder = folder;
+        }
+        if let Some(folder) = file.dist_folder {
+            config.dist_folder = folder;
+        }
+        if let Some(keep_dist) = file.keep_dist {
+            match KeepDist::from_str(&keep_dist) {
+                Ok(k) => config.keep_dist = k,
+                Err(_) => {
+                    print_warning("The 'keep_dist' option in config.toml must be `always`, \
+                                   `on_error` or `never`.\nUsing default.",
+                                  verbose)
+                }
+            }
+        }
+        match file.dist_max_age {
+            Some(d) if d >= 0 => config.dist_max_age = d as u64,
+            Some(_) => {
+                print_warning("The 'dist_max_age' option in config.toml must be a positive \
+                               integer or 0.\nUsing default.",
+                              verbose)
+            }
+            None => {}
+        }
+        if let Some(folder) = file.workspace_folder {
+            config.workspace_folder = Some(folder);
+        }
+        if let Some(folder) = file.results_folder {
+            config.results_folder = folder;
+        }
+        if let Some(b) = file.versioned_results {
+            config.versioned_results = b;
+        }
+        if let Some(path) = file.apktool_file {
+            if Path::new(&path).extension().map_or(false, |e| e == "jar") {
+                config.apktool_file = path;
+            } else {
+                print_warning("The APKTool file must be a JAR file.\nUsing default.",
+                              verbose)
+            }
+        }
+        if let Some(folder) = file.dex2jar_folder {
+            config.dex2jar_folder = folder;
+        }
+        if let Some(path) = file.jd_cmd_file {
+            if Path::new(&path).extension().map_or(false, |e| e == "jar") {
+                config.jd_cmd_file = path;
+            } else {
+                print_warning("The JD-CMD file must be a JAR file.\nUsing default.", verbose)
+            }
+        }
+        if let Some(template) = file.results_template {
+            config.results_template = template;
+        }
+        if let Some(folder) = file.templates_folder {
+            config.templates_folder = Some(folder);
+        }
+        if let Some(baseline) = file.baseline {
+            config.baseline = Some(baseline);
+        }
+        if let Some(ignore_file) = file.ignore_file {
+            config.ignore_file = Some(ignore_file);
+        }
+        if let Some(source_root) = file.source_root {
+            config.source_root = Some(source_root);
+        }
+        if let Some(path) = file.rules_json {
+            if Path::new(&path).is_dir() || is_rule_file(&path) {
+                config.rules_json = path;
+            } else {
+                print_warning("The 'rules_json' option in config.toml must be a JSON or YAML \
+                               file, or a directory with rule files.\nUsing default.",
+                              verbose)
+            }
+        }
+        for path in file.rules_extra.unwrap_or_default() {
+            if Path::new(&path).is_dir() || is_rule_file(&path) {
+                config.rules_extra.push(path);
+            } else {
+                print_warning(format!("The extra rule file {} in config.toml must be a JSON or \
+                                       YAML file, or a directory with rule files.",
+                                      path),
+                              verbose)
+            }
+        }
+        if let Some(permissions) = file.permissions {
+            Config::apply_permissions(config, permissions, verbose);
+        }
+        if let Some(secrets) = file.secrets {
+            match secrets.entropy_threshold {
+                Some(f) if f > 0.0 => config.secrets.entropy_threshold = f,
+                Some(_) => {
+                    print_warning("The 'entropy_threshold' option in the [secrets] section of \
+                                   config.toml must be a positive number.\nUsing default.",
+                                  verbose)
+                }
+                None => {}
+            }
+            match secrets.min_length {
+                Some(l) if l > 0 => config.secrets.min_length = l as usize,
+                Some(_) => {
+                    print_warning("The 'min_length' option in the [secrets] section of \
+                                   config.toml must be a positive number.\nUsing default.",
+                                  verbose)
+                }
+                None => {}
+            }
+        }
+        if let Some(downloads) = file.downloads {
+            match downloads.concurrency {
+                Some(c @ 1...MAX_THREADS) => config.downloads.concurrency = c as u8,
+                Some(_) => {
+                    print_warning(format!("The 'concurrency' option in the [downloads] section \
+                                           of config.toml must be an integer between 1 and \
+                                           {}.\nUsing default.",
+                                          MAX_THREADS),
+                                  verbose)
+                }
+                None => {}
+            }
+            if let Some(rate) = downloads.rate_limit {
+                if is_rate(&rate) {
+                    config.downloads.rate_limit = Some(rate);
+                } else {
+                    print_warning("The 'rate_limit' option in the [downloads] section of \
+                                   config.toml must be a rate such as \"500k\" or \"2M\".\nUsing \
+                                   default.",
+                                  verbose)
+                }
+            }
+            match downloads.delay {
+                Some(d) if d >= 0 => config.downloads.delay = d as u64,
+                Some(_) => {
+                    print_warning("The 'delay' option in the [downloads] section of config.toml \
+                                   must be a positive number of milliseconds.\nUsing default.",
+                                  verbose)
+                }
+                None => {}
+            }
+            for source in downloads.sources.unwrap_or_default() {
+                if is_download_source(&source) {
+                    config.downloads.sources.push(source);
+                } else {
+                    print_warning(format!("The download source {} is not valid. It must be \
+                                           \"fdroid\", \"apkpure\" or an HTTP URL containing \
+                                           {{package}}.",
+                                          source),
+                                  verbose)
+                }
+            }
+        }
+        if let Some(certificate) = file.certificate {
+            match certificate.expiry_window {
+                Some(d) if d >= 0 => config.certificate.expiry_window = d as u32,
+                Some(_) => {
+                    print_warning("The 'expiry_window' option in the [certificate] section of \
+                                   config.toml must be a positive number of days.\nUsing \
+                                   default.",
+                                  verbose)
+                }
+                None => {}
+            }
+        }
+        if let Some(sdk_policy) = file.sdk_policy {
+            let sdk_warning = |key: &str| {
+                print_warning(format!("The '{}' option in the [sdk_policy] section of \
+                                       config.toml must be a positive integer.\nUsing default.",
+                                      key),
+                              verbose)
+            };
+            match sdk_policy.min_sdk {
+                Some(s) if s > 0 => config.sdk_policy.min_sdk = Some(s as i32),
+                Some(_) => sdk_warning("min_sdk"),
+                None => {}
+            }
+            match sdk_policy.max_target_lag {
+                Some(l) if l >= 0 => config.sdk_policy.max_target_lag = Some(l as i32),
+                Some(_) => sdk_warning("max_target_lag"),
+                None => {}
+            }
+            match sdk_policy.latest_sdk {
+                Some(s) if s > 0 => config.sdk_policy.latest_sdk = s as i32,
+                Some(_) => sdk_warning("latest_sdk"),
+                None => {}
+            }
+        }
+        if let Some(vulnerability_db) = file.vulnerability_db {
+            if let Some(path) = vulnerability_db.path {
+                if path.ends_with(".json") {
+                    config.vulnerability_db.path = path;
+                } else {
+                    print_warning("The 'path' option in the [vulnerability_db] section of \
+                                   config.toml must be the path of a JSON file.\nUsing default.",
+                                  verbose)
+                }
+            }
+            if let Some(url) = vulnerability_db.url {
+                if url.starts_with("https://") {
+                    config.vulnerability_db.url = url;
+                } else {
+                    print_warning("The 'url' option in the [vulnerability_db] section of \
+                                   config.toml must be an HTTPS URL.\nUsing default.",
+                                  verbose)
+                }
+            }
+        }
+        if let Some(rule_packs) = file.rule_packs {
+            if let Some(url) = rule_packs.url {
+                if url.starts_with("https://") {
+                    config.rule_packs.url = url;
+                } else {
+                    print_warning("The 'url' option in the [rule_packs] section of config.toml \
+                                   must be an HTTPS URL.\nUsing default.",
+                                  verbose)
+                }
+            }
+            if let Some(key) = rule_packs.public_key {
+                if key.len() == 64 && key.chars().all(|c| c.is_digit(16)) {
+                    config.rule_packs.public_key = Some(key.to_lowercase());
+                } else {
+                    print_warning("The 'public_key' option in the [rule_packs] section of \
+                                   config.toml must be an hexadecimal Ed25519 public key.\n\
+                                   Ignoring it.",
+                                  verbose)
+                }
+            }
+        }
+        if let Some(rules) = file.rules {
+            config.rules.disabled.extend(rules.disabled.unwrap_or_default());
+            config.rules.include_paths.extend(rules.include_paths.unwrap_or_default());
+            config.rules.exclude_paths.extend(rules.exclude_paths.unwrap_or_default());
+            for (id, criticity) in rules.criticity.unwrap_or_default() {
+                match Criticity::from_str(&criticity) {
+                    Ok(criticity) => {
+                        let _ = config.rules.criticity.insert(id, criticity);
+                    }
+                    Err(_) => {
+                        print_warning(format!("The criticity of the '{}' rule in the \
+                                               [rules.criticity] section of config.toml must \
+                                               be a criticity.",
+                                              id),
+                                      verbose)
+                    }
+                }
+            }
+            if let Some(environment) = rules.cvss_environment {
+                match CvssEnvironment::from_str(&environment) {
+                    Ok(environment) => config.rules.cvss_environment = environment,
+                    Err(_) => {
+                        print_warning("The 'cvss_environment' option in the [rules] section of \
+                                       config.toml must be CVSS v3.1 environmental metrics, \
+                                       such as \"CR:H/IR:H/MAV:N\".\nUsing default.",
+                                      verbose)
+                    }
+                }
+            }
+        }
+        if let Some(taint) = file.taint {
+            for (key, methods, list) in
+                vec![("sources", taint.sources, &mut config.taint.sources),
+                     ("sinks", taint.sinks, &mut config.taint.sinks),
+                     ("sanitizers", taint.sanitizers, &mut config.taint.sanitizers)] {
+                for method in methods.unwrap_or_default() {
+                    if is_method_name(&method) {
+                        list.push(method);
+                    } else {
+                        print_warning(format!("The '{}' option in the [taint] section of \
+                                               config.toml must be a list of method names.",
+                                              key),
+                                      verbose)
+                    }
+                }
+            }
+            if let Some(b) = taint.inter_procedural {
+                config.taint.inter_procedural = b;
+            }
+        }
+        if let Some(logging) = file.logging {
+            for pattern in logging.sensitive_names.unwrap_or_default() {
+                if is_name_pattern(&pattern) {
+                    config.logging.sensitive_names.push(pattern);
+                } else {
+                    print_warning("The 'sensitive_names' option in the [logging] section of \
+                                   config.toml must be a list of regular expressions.",
+                                  verbose)
+                }
+            }
+        }
+        if let Some(virustotal) = file.virustotal {
+            if let Some(b) = virustotal.enabled {
+                config.virustotal.enabled = b;
+            }
+            if let Some(b) = virustotal.upload {
+                config.virustotal.upload = b;
+            }
+            if let Some(key) = virustotal.api_key {
+                config.virustotal.api_key = if key.trim().is_empty() {
+                    None
+                } else {
+                    Some(String::from(key.trim()))
+                };
+            }
+        }
+        if let Some(notifications) = file.notifications {
+            for (key, urls, list) in
+                vec![("slack_webhooks",
+                      notifications.slack_webhooks,
+                      &mut config.notifications.slack_webhooks),
+                     ("webhooks", notifications.webhooks, &mut config.notifications.webhooks)] {
+                for url in urls.unwrap_or_default() {
+                    if is_http_url(&url) {
+                        list.push(url);
+                    } else {
+                        print_warning(format!("The '{}' option in the [notifications] section \
+                                               of config.toml must be a list of HTTP or HTTPS \
+                                               URLs.",
+                                              key),
+                                      verbose)
+                    }
+                }
+            }
+            if let Some(criticity) = notifications.min_criticity {
+                match Criticity::from_str(&criticity) {
+                    Ok(c) => config.notifications.min_criticity = Some(c),
+                    Err(_) => {
+                        print_warning("The 'min_criticity' option in the [notifications] \
+                                       section of config.toml must be one of \"warning\", \
+                                       \"low\", \"medium\", \"high\" or \"critical\".\nUsing \
+                                       default.",
+                                      verbose)
+                    }
+                }
+            }
+            match notifications.timeout {
+                Some(t) if t > 0 => config.notifications.timeout = t as u32,
+                Some(_) => {
+                    print_warning("The 'timeout' option in the [notifications] section of \
+                                   config.toml must be a positive number of seconds.\nUsing \
+                                   default.",
+                                  verbose)
+                }
+                None => {}
+            }
+        }
+        if let Some(jobs) = file.jobs {
+            match jobs.timeout {
+                Some(t) if t >= 0 => config.jobs.timeout = t as u64,
+                Some(_) => {
+                    print_warning("The 'timeout' option in the [jobs] section of config.toml \
+                                   must be a number of seconds, or 0 for no timeout.\nUsing \
+                                   default.",
+                                  verbose)
+                }
+                None => {}
+            }
+            match jobs.max_disk {
+                Some(m) if m >= 0 => config.jobs.max_disk = m as u64,
+                Some(_) => {
+                    print_warning("The 'max_disk' option in the [jobs] section of config.toml \
+                                   must be a number of megabytes, or 0 for no limit.\nUsing \
+                                   default.",
+                                  verbose)
+                }
+                None => {}
+            }
+            if let Some(folder) = jobs.apk_folder {
+                if Path::new(&folder).is_dir() {
+                    config.jobs.apk_folder = Some(folder);
+                } else {
+                    print_warning(format!("The 'apk_folder' option in the [jobs] section of \
+                                           config.toml must be an existing folder, but {} is \
+                                           not.\nIgnoring it.",
+                                          folder),
+                                  verbose)
+                }
+            }
+        }
+        if let Some(timeouts) = file.timeouts {
+            for (key, value, timeout) in
+                vec![("apktool", timeouts.apktool, &mut config.timeouts.apktool),
+                     ("dex2jar", timeouts.dex2jar, &mut config.timeouts.dex2jar),
+                     ("jd_cmd", timeouts.jd_cmd, &mut config.timeouts.jd_cmd),
+                     ("analysis", timeouts.analysis, &mut config.timeouts.analysis)] {
+                match value {
+                    Some(t) if t >= 0 => *timeout = t as u64,
+                    Some(_) => {
+                        print_warning(format!("The '{}' option in the [timeouts] section of \
+                                               config.toml must be a number of seconds, or 0 \
+                                               for no timeout.\nUsing default.",
+                                              key),
+                                      verbose)
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let Some(archive) = file.archive {
+            let archive_warning = |key: &str| {
+                print_warning(format!("The '{}' option in the [archive] section of config.toml \
+                                       must be a positive integer.\nUsing default.",
+                                      key),
+                              verbose)
+            };
+            match archive.max_entries {
+                Some(i) if i > 0 => config.archive.max_entries = i as usize,
+                Some(_) => archive_warning("max_entries"),
+                None => {}
+            }
+            match archive.max_size {
+                Some(i) if i > 0 => config.archive.max_size = i as u64 * 1024 * 1024,
+                Some(_) => archive_warning("max_size"),
+                None => {}
+            }
+            match archive.max_compression_ratio {
+                Some(i) if i > 0 => config.archive.max_compression_ratio = i as u64,
+                Some(_) => archive_warning("max_compression_ratio"),
+                None => {}
+            }
+        }
+        if let Some(grouping) = file.grouping {
+            if let Some(b) = grouping.deduplicate {
+                config.grouping.deduplicate = b;
+            }
+            match grouping.max_samples {
+                Some(i) if i >= 0 => config.grouping.max_samples = i as usize,
+                Some(_) => {
+                    print_warning("The 'max_samples' option in the [grouping] section of \
+                                   config.toml must be a positive integer, or 0 to show all the \
+                                   findings.\nUsing default.",
+                                  verbose)
+                }
+                None => {}
+            }
+        }
+        if let Some(issues) = file.issues {
+            for format in issues.formats.unwrap_or_default() {
+                match IssueFormat::from_str(&format) {
+                    Ok(f) => {
+                        if !config.issues.formats.contains(&f) {
+                            config.issues.formats.push(f);
+                        }
+                    }
+                    Err(_) => {
+                        print_warning(format!("The issue format {} in the [issues] section of \
+                                               config.toml is not valid. It must be \"jira\", \
+                                               \"json\" or \"csv\".",
+                                              format),
+                                      verbose)
+                    }
+                }
+            }
+            if let Some(project) = issues.project {
+                config.issues.project = project;
+            }
+            if let Some(issue_type) = issues.issue_type {
+                if issue_type.trim().is_empty() {
+                    print_warning("The 'issue_type' option in the [issues] section of \
+                                   config.toml can't be empty.\nUsing default.",
+                                  verbose)
+                } else {
+                    config.issues.issue_type = issue_type;
+                }
+            }
+            if let Some(labels) = issues.labels {
+                config.issues.labels = labels;
+            }
+            if let Some(criticity) = issues.min_criticity {
+                match Criticity::from_str(&criticity) {
+                    Ok(c) => config.issues.min_criticity = c,
+                    Err(_) => {
+                        print_warning("The 'min_criticity' option in the [issues] section of \
+                                       config.toml must be one of \"warning\", \"low\", \
+                                       \"medium\", \"high\" or \"critical\".\nUsing default.",
+                                      verbose)
+                    }
+                }
+            }
+            if let Some(url) = issues.jira_url {
+                if is_http_url(&url) {
+                    config.issues.jira_url = Some(String::from(url.trim_right_matches('/')));
+                } else {
+                    print_warning("The 'jira_url' option in the [issues] section of \
+                                   config.toml must be an HTTP or HTTPS URL.",
+                                  verbose)
+                }
+            }
+            if let Some(user) = issues.jira_user {
+                config.issues.jira_user = Some(user);
+            }
+            if let Some(token) = issues.jira_token {
+                config.issues.jira_token = Some(token);
+            }
+            if config.issues.formats.contains(&IssueFormat::Jira) &&
+               (config.issues.jira_url.is_none() || config.issues.project.is_empty()) {
+                print_warning("The Jira issue format requires the 'jira_url' and 'project' \
+                               options in the [issues] section of config.toml, so no issues \
+                               will be created in Jira.",
+                              verbose);
+                config.issues.formats.retain(|f| *f != IssueFormat::Jira);
+            }
+        }
+    }
+
+    /// Applies the entries of the `[[permissions]]` array of a configuration file
+    fn apply_permissions(config: &mut Config, permissions: Vec<PermissionEntry>, verbose: bool) {
+        let format_warning = format!("The permission configuration format must be the \
+                                      following:\n{}\nUsing default.",
+                                     "[[permissions]]\nname=\"unknown|permission.name\"\n\
+                                      criticity = \"warning|low|medium|high|critical\"\n\
+                                      label = \"Permission label\"\n\
+                                      description = \"Long description to explain the \
+                                      vulnerability\""
+                                         .italic());
+
+        for permission in permissions {
+            let (name, criticity, description) =
+                match (permission.name, permission.criticity, permission.description) {
+                    (Some(n), Some(c), Some(d)) => (n, c, d),
+                    _ => {
+                        print_warning(&format_warning, verbose);
+                        break;
+                    }
+                };
+
+            let criticity = match Criticity::from_str(&criticity) {
+                Ok(c) => c,
+                Err(_) => {
+                    print_warning(format!("Criticity must be one of {}, {}, {}, {} or \
+                                           {}.\nUsing default.",
+                                          "warning".italic(),
+                                          "low".italic(),
+                                          "medium".italic(),
+                                          "high".italic(),
+                                          "critical".italic()),
+                                  verbose);
+                    break;
+                }
+            };
+
+            if name == "unknown" {
+                if permission.label.is_some() {
+                    print_warning(format!("The format for the unknown permissions is the \
+                                           following:\n{}\nUsing default.",
+                                          "[[permissions]]\nname = \"unknown\"\n\
+                                           criticity = \"warning|low|medium|high|criticity\"\n\
+                                           description = \"Long description to explain the \
+                                           vulnerability\""
+                                              .italic()),
+                                  verbose);
+                    break;
+                }
+
+                config.unknown_permission = (criticity, description);
+            } else {
+                let permission_name = match Permission::from_str(name.as_str()) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        print_warning(format!("Unknown permission: {}\nTo set the default \
+                                               vulnerability level for an unknown permission, \
+                                               please, use the {} permission name, under the {} \
+                                               section.",
+                                              name.italic(),
+                                              "unknown".italic(),
+                                              "[[permissions]]".italic()),
+                                      verbose);
+                        break;
+                    }
+                };
+
+                let label = match permission.label {
+                    Some(l) => l,
+                    None => {
+                        print_warning(&format_warning, verbose);
+                        break;
+                    }
+                };
+                config.permissions.insert(PermissionConfig::new(permission_name,
+                                                                criticity,
+                                                                &label,
+                                                                description.as_str()));
+            }
+        }
+    }
+}
+
+impl Default for Config {
+    #[cfg(target_os = "linux")]
+    fn default() -> Config {
+        if file_exists("/usr/share/super") {
+            Config {
+                app_id: String::new(),
+                verbose: false,
+                quiet: false,
+                force: false,
+                bench: false,
+                profile_rules: false,
+                historical: false,
+                online_checks: false,
+                malware_heuristics: false,
+                trend: false,
+                gitlab_sast: false,
+                github_annotations: false,
+                reproducible: false,
+                show_tool_output: false,
+                report_formats: vec![ReportFormat::Html, ReportFormat::Json],
+                include_source: true,
+                decompile: true,
+                language: String::from("en"),
+                mapping_file: None,
+                baseline: None,
+                ignore_file: None,
+                source_root: None,
+                threads: 2,
+                downloads_folder: String::from("downloads"),
+                dist_folder: String::from("dist"),
+                results_folder: String::from("results"),
+                apktool_file: String::from("/usr/share/super/vendor/apktool_2.2.0.jar"),
+                dex2jar_folder: String::from("/usr/share/super/vendor/dex2jar-2.0"),
+                jd_cmd_file: String::from("/usr/share/super/vendor/jd-cmd.jar"),
+                results_template: String::from("/usr/share/super/vendor/results_template"),
+                templates_folder: None,
+                rules_json: if Path::new("/etc/super").exists() {
+                    String::from("/etc/super/rules.json")
+                } else {
+                    String::from("rules.json")
+                },
+                rules_extra: Vec::new(),
+                unknown_permission: (Criticity::Low,
+                                     String::from("Even if the application can create its own \
+                                                   permissions, it's discouraged, since it can \
+                                                   lead to missunderstanding between developers.")),
+                permissions: BTreeSet::new(),
+                loaded_files: Vec::new(),
+                secrets: Default::default(),
+                downloads: Default::default(),
+                certificate: Default::default(),
+                manifest_flags: BTreeMap::new(),
+                sdk_policy: Default::default(),
+                vulnerability_db: Default::default(),
+                rule_packs: Default::default(),
+                rules: Default::default(),
+                taint: Default::default(),
+                logging: Default::default(),
+                virustotal: Default::default(),
+                notifications: Default::default(),
+                jobs: Default::default(),
+                timeouts: Default::default(),
+                archive: Default::default(),
+                grouping: Default::default(),
+                issues: Default::default(),
+                keep_dist: KeepDist::Always,
+                dist_max_age: 0,
+                workspace_folder: None,
+                versioned_results: false,
+                profiles: BTreeMap::new(),
+                profile: None,
+                origins: BTreeMap::new(),
+            }
+        } else {
+            Config {
+                app_id: String::new(),
+                verbose: false,
+                quiet: false,
+                force: false,
+                bench: false,
+                profile_rules: false,
+                historical: false,
+                online_checks: false,
+                malware_heuristics: false,
+                trend: false,
+                gitlab_sast: false,
+                github_annotations: false,
+                reproducible: false,
+                show_tool_output: false,
+                report_formats: vec![ReportFormat::Html, ReportFormat::Json],
+                include_source: true,
+                decompile: true,
+                language: String::from("en"),
+                mapping_file: None,
+                baseline: None,
+                ignore_file: None,
+                source_root: None,
+                threads: 2,
+                downloads_folder: String::from("downloads"),
+                dist_folder: String::from("dist"),
+                results_folder: String::from("results"),
+                apktool_file: String::from("vendor/apktool_2.2.0.jar"),
+                dex2jar_folder: String::from("vendor/dex2jar-2.0"),
+                jd_cmd_file: String::from("vendor/jd-cmd.jar"),
+                results_template: String::from("vendor/results_template"),
+                templates_folder: None,
+                rules_json: if file_exists("/etc/super/rules.json") {
+                    String::from("/etc/super/rules.json")
+                } else {
+                    String::from("rules.json")
+                },
+                rules_extra: Vec::new(),
+                unknown_permission: (Criticity::Low,
+                                     String::from("Even if the application can create its own \
+                                                   permissions, it's discouraged, since it can \
+                                                   lead to missunderstanding between developers.")),
+                permissions: BTreeSet::new(),
+                loaded_files: Vec::new(),
+                secrets: Default::default(),
+                downloads: Default::default(),
+                certificate: Default::default(),
+                manifest_flags: BTreeMap::new(),
+                sdk_policy: Default::default(),
+                vulnerability_db: Default::default(),
+                rule_packs: Default::default(),
+                rules: Default::default(),
+                taint: Default::default(),
+                logging: Default::default(),
+                virustotal: Default::default(),
+                notifications: Default::default(),
+                jobs: Default::default(),
+                timeouts: Default::default(),
+                archive: Default::default(),
+                grouping: Default::default(),
+                issues: Default::default(),
+                keep_dist: KeepDist::Always,
+                dist_max_age: 0,
+                workspace_folder: None,
+                versioned_results: false,
+                profiles: BTreeMap::new(),
+                profile: None,
+                origins: BTreeMap::new(),
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn default() -> Config {
+        if file_exists("/usr/local/super") {
+            Config {
+                app_id: String::new(),
+                verbose: false,
+                quiet: false,
+                force: false,
+                bench: false,
+                profile_rules: false,
+                historical: false,
+                online_checks: false,
+                malware_heuristics: false,
+                trend: false,
+                gitlab_sast: false,
+                github_annotations: false,
+                reproducible: false,
+                show_tool_output: false,
+                report_formats: vec![ReportFormat::Html, ReportFormat::Json],
+                include_source: true,
+                decompile: true,
+                language: String::from("en"),
+                mapping_file: None,
+                baseline: None,
+                ignore_file: None,
+                source_root: None,
+                threads: 2,
+                downloads_folder: String::from("downloads"),
+                dist_folder: String::from("dist"),
+                results_folder: String::from("results"),
+                apktool_file: String::from("/usr/local/super/vendor/apktool_2.2.0.jar"),
+                dex2jar_folder: String::from("/usr/local/super/vendor/dex2jar-2.0"),
+                jd_cmd_file: String::from("/usr/local/super/vendor/jd-cmd.jar"),
+                results_template: String::from("/usr/local/super/vendor/results_template"),
+                templates_folder: None,
+                rules_json: if Path::new("/etc/super").exists() {
+                    String::from("/etc/super/rules.json")
+                } else {
+                    String::from("rules.json")
+                },
+                rules_extra: Vec::new(),
+                unknown_permission: (Criticity::Low,
+                                     String::from("Even if the application can create its own \
+                                                   permissions, it's discouraged, since it can \
+                                                   lead to missunderstanding between developers.")),
+                permissions: BTreeSet::new(),
+                loaded_files: Vec::new(),
+                secrets: Default::default(),
+                downloads: Default::default(),
+                certificate: Default::default(),
+                manifest_flags: BTreeMap::new(),
+                sdk_policy: Default::default(),
+                vulnerability_db: Default::default(),
+                rule_packs: Default::default(),
+                rules: Default::default(),
+                taint: Default::default(),
+                logging: Default::default(),
+                virustotal: Default::default(),
+                notifications: Default::default(),
+                jobs: Default::default(),
+                timeouts: Default::default(),
+                archive: Default::default(),
+                grouping: Default::default(),
+                issues: Default::default(),
+                keep_dist: KeepDist::Always,
+                dist_max_age: 0,
+                workspace_folder: None,
+                versioned_results: false,
+                profiles: BTreeMap::new(),
+                profile: None,
+                origins: BTreeMap::new(),
+            }
+        } else {
+            Config {
+                app_id: String::new(),
+                verbose: false,
+                quiet: false,
+                force: false,
+                bench: false,
+                profile_rules: false,
+                historical: false,
+                online_checks: false,
+                malware_heuristics: false,
+                trend: false,
+                gitlab_sast: false,
+                github_annotations: false,
+                reproducible: false,
+                show_tool_output: false,
+                report_formats: vec![ReportFormat::Html, ReportFormat::Json],
+                include_source: true,
+                decompile: true,
+                language: String::from("en"),
+                mapping_file: None,
+                baseline: None,
+                ignore_file: None,
+                source_root: None,
+                threads: 2,
+                downloads_folder: String::from("downloads"),
+                dist_folder: String::from("dist"),
+                results_folder: String::from("results"),
+                apktool_file: String::from("vendor/apktool_2.2.0.jar"),
+                dex2jar_folder: String::from("vendor/dex2jar-2.0"),
+                jd_cmd_file: String::from("vendor/jd-cmd.jar"),
+                results_template: String::from("vendor/results_template"),
+                templates_folder: None,
+                rules_json: if file_exists("/etc/super/rules.json") {
+                    String::from("/etc/super/rules.json")
+                } else {
+                    String::from("rules.json")
+                },
+                rules_extra: Vec::new(),
+                unknown_permission: (Criticity::Low,
+                                     String::from("Even if the application can create its own \
+                                                   permissions, it's discouraged, since it can \
+                                                   lead to missunderstanding between developers.")),
+                permissions: BTreeSet::new(),
+                loaded_files: Vec::new(),
+                secrets: Default::default(),
+                downloads: Default::default(),
+                certificate: Default::default(),
+                manifest_flags: BTreeMap::new(),
+                sdk_policy: Default::default(),
+                vulnerability_db: Default::default(),
+                rule_packs: Default::default(),
+                rules: Default::default(),
+                taint: Default::default(),
+                logging: Default::default(),
+                virustotal: Default::default(),
+                notifications: Default::default(),
+                jobs: Default::default(),
+                timeouts: Default::default(),
+                archive: Default::default(),
+                grouping: Default::default(),
+                issues: Default::default(),
+                keep_dist: KeepDist::Always,
+                dist_max_age: 0,
+                workspace_folder: None,
+                versioned_results: false,
+                profiles: BTreeMap::new(),
+                profile: None,
+                origins: BTreeMap::new(),
+            }
+        }
+    }
+
+    #[cfg(target_family = "windows")]
+    fn default() -> Config {
+        Config {
+            app_id: String::new(),
+            verbose: false,
+            quiet: false,
+            force: false,
+            bench: false,
+            profile_rules: false,
+            historical: false,
+            online_checks: false,
+            malware_heuristics: false,
+            trend: false,
+            gitlab_sast: false,
+            github_annotations: false,
+            reproducible: false,
+            show_tool_output: false,
+            report_formats: vec![ReportFormat::Html, ReportFormat::Json],
+            include_source: true,
+            decompile: true,
+            language: String::from("en"),
+            mapping_file: None,
+            baseline: None,
+            ignore_file: None,
+            source_root: None,
+            threads: 2,
+            downloads_folder: String::from("downloads"),
+            dist_folder: String::from("dist"),
+            results_folder: String::from("results"),
+            apktool_file: String::from("vendor\\apktool_2.2.0.jar"),
+            dex2jar_folder: String::from("vendor\\dex2jar-2.0"),
+            jd_cmd_file: String::from("vendor\\jd-cmd.jar"),
+            results_template: String::from("vendor\\results_template"),
+            templates_folder: None,
+            rules_json: String::from("rules.json"),
+            rules_extra: Vec::new(),
+            unknown_permission: (Criticity::Low,
+                                 String::from("Even if the application can create its own \
+                                               permissions, it's discouraged, since it can lead \
+                                               to missunderstanding between developers.")),
+            permissions: BTreeSet::new(),
+            loaded_files: Vec::new(),
+            secrets: Default::default(),
+            downloads: Default::default(),
+            certificate: Default::default(),
+            manifest_flags: BTreeMap::new(),
+            sdk_policy: Default::default(),
+            vulnerability_db: Default::default(),
+            rule_packs: Default::default(),
+            rules: Default::default(),
+            taint: Default::default(),
+            logging: Default::default(),
+            virustotal: Default::default(),
+            notifications: Default::default(),
+            jobs: Default::default(),
+            timeouts: Default::default(),
+            archive: Default::default(),
+            grouping: Default::default(),
+            issues: Default::default(),
+            keep_dist: KeepDist::Always,
+            dist_max_age: 0,
+            workspace_folder: None,
+            versioned_results: false,
+            profiles: BTreeMap::new(),
+            profile: None,
+            origins: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Ord, Eq)]
+pub struct PermissionConfig {
+    permission: Permission,
+    criticity: Criticity,
+    label: String,
+    description: String,
+}
+
+impl PartialEq for PermissionConfig {
+    fn eq(&self, other: &PermissionConfig) -> bool {
+        self.permission == other.permission
+    }
+}
+
+impl PartialOrd for PermissionConfig {
+    fn partial_cmp(&self, other: &PermissionConfig) -> Option<Ordering> {
+        if self.permission < other.permission {
+            Some(Ordering::Less)
+        } else if self.permission > other.permission {
+            Some(Ordering::Greater)
+        } else {
+            Some(Ordering::Equal)
+        }
+    }
+}
+
+impl PermissionConfig {
+    fn new(permission: Permission,
+           criticity: Criticity,
+           label: &str,
+           description: &str)
+           -> PermissionConfig {
+        PermissionConfig {
+            permission: permission,
+            criticity: criticity,
+            label: String::from(label),
+            description: String::from(description),
+        }
+    }
+
+    pub fn get_permission(&self) -> Permission {
+        self.permission
+    }
+
+    pub fn get_criticity(&self) -> Criticity {
+        self.criticity
+    }
+
+    pub fn get_label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    pub fn get_description(&self) -> &str {
+        self.description.as_str()
+    }
+}
+
+/// Configuration of the secret detection pass
+#[derive(Debug)]
+pub struct SecretsConfig {
+    entropy_threshold: f64,
+    min_length: usize,
+}
+
+impl SecretsConfig {
+    /// Gets the minimum Shannon entropy, in bits per character, for a string to be a secret
+    pub fn get_entropy_threshold(&self) -> f64 {
+        self.entropy_threshold
+    }
+
+    /// Gets the minimum length of high entropy strings
+    pub fn get_min_length(&self) -> usize {
+        self.min_length
+    }
+}
+
+impl Default for SecretsConfig {
+    fn default() -> SecretsConfig {
+        SecretsConfig {
+            entropy_threshold: 4.5,
+            min_length: 20,
+        }
+    }
+}
+
+/// Configuration of the bulk downloads
+#[derive(Debug)]
+pub struct DownloadsConfig {
+    concurrency: u8,
+    rate_limit: Option<String>,
+    delay: u64,
+    sources: Vec<String>,
+}
+
+impl DownloadsConfig {
+    /// Gets the maximum number of simultaneous downloads
+    pub fn get_concurrency(&self) -> u8 {
+        self.concurrency
+    }
+
+    /// Gets the maximum transfer rate of each download, such as `500k`
+    pub fn get_rate_limit(&self) -> Option<&str> {
+        match self.rate_limit {
+            Some(ref r) => Some(r.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the delay between two downloads of the same worker, in milliseconds
+    pub fn get_delay(&self) -> u64 {
+        self.delay
+    }
+
+    /// Gets the sources from which missing applications are downloaded, in order
+    ///
+    /// Each source is either `fdroid`, `apkpure` or a URL template with a `{package}`
+    /// placeholder. There are no sources by default, so nothing is downloaded automatically.
+    pub fn get_sources(&self) -> &[String] {
+        &self.sources
+    }
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> DownloadsConfig {
+        DownloadsConfig {
+            concurrency: 4,
+            rate_limit: None,
+            delay: 1000,
+            sources: Vec::new(),
+        }
+    }
+}
+
+/// Configuration of the certificate analysis
+#[derive(Debug)]
+pub struct CertificateConfig {
+    expiry_window: u32,
+}
+
+impl CertificateConfig {
+    /// Gets the number of days before the expiration of the certificate from which it's reported
+    pub fn get_expiry_window(&self) -> u32 {
+        self.expiry_window
+    }
+}
+
+impl Default for CertificateConfig {
+    fn default() -> CertificateConfig {
+        CertificateConfig { expiry_window: 90 }
+    }
+}
+
+/// SDK versions allowed for the analyzed applications
+#[derive(Debug)]
+pub struct SdkPolicyConfig {
+    min_sdk: Option<i32>,
+    max_target_lag: Option<i32>,
+    latest_sdk: i32,
+}
+
+impl SdkPolicyConfig {
+    /// Gets the minimum `minSdkVersion` allowed, if the policy sets one
+    pub fn get_min_sdk(&self) -> Option<i32> {
+        self.min_sdk
+    }
+
+    /// Gets the maximum number of API levels the `targetSdkVersion` can be behind the latest
+    /// API level, if the policy sets one
+    pub fn get_max_target_lag(&self) -> Option<i32> {
+        self.max_target_lag
+    }
+
+    /// Gets the latest API level, used to compute the lag of the target SDK
+    pub fn get_latest_sdk(&self) -> i32 {
+        self.latest_sdk
+    }
+}
+
+impl Default for SdkPolicyConfig {
+    fn default() -> SdkPolicyConfig {
+        SdkPolicyConfig {
+            min_sdk: None,
+            max_target_lag: None,
+            latest_sdk: 35,
+        }
+    }
+}
+
+/// Location of the offline vulnerability database of the libraries, and where to update it from
+#[derive(Debug)]
+pub struct VulnerabilityDbConfig {
+    path: String,
+    url: String,
+}
+
+impl VulnerabilityDbConfig {
+    /// Gets the path of the vulnerability database JSON file
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Gets the URL the vulnerability database is downloaded from with `--update-db`
+    pub fn get_url(&self) -> &str {
+        self.url.as_str()
+    }
+}
+
+impl Default for VulnerabilityDbConfig {
+    fn default() -> VulnerabilityDbConfig {
+        VulnerabilityDbConfig {
+            path: if file_exists("/etc/super/vulnerabilities.json") {
+                String::from("/etc/super/vulnerabilities.json")
+            } else {
+                String::from("vulnerabilities.json")
+            },
+            url: String::from("https://raw.githubusercontent.com/SUPERAndroidAnalyzer/super/\
+                               master/vulnerabilities.json"),
+        }
+    }
+}
+
+/// Where the rule packs are updated from with `super rules update`, and the key their signature
+/// is verified with
+#[derive(Debug)]
+pub struct RulePacksConfig {
+    url: String,
+    public_key: Option<String>,
+}
+
+impl RulePacksConfig {
+    /// Gets the URL of the rule pack bundle
+    pub fn get_url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Gets the hexadecimal Ed25519 public key the bundle must be signed with, if any
+    pub fn get_public_key(&self) -> Option<&str> {
+        match self.public_key {
+            Some(ref k) => Some(k.as_str()),
+            None => None,
+        }
+    }
+}
+
+impl Default for RulePacksConfig {
+    fn default() -> RulePacksConfig {
+        RulePacksConfig {
+            url: String::from("https://raw.githubusercontent.com/SUPERAndroidAnalyzer/super/\
+                               master/rules/bundle.json"),
+            public_key: None,
+        }
+    }
+}
+
+/// Rules disabled in the configuration, criticities overriding the ones of the rule files and
+/// paths the rules are checked in
+#[derive(Debug, Default)]
+pub struct RulesConfig {
+    disabled: BTreeSet<String>,
+    criticity: BTreeMap<String, Criticity>,
+    include_paths: Vec<String>,
+    exclude_paths: Vec<String>,
+    cvss_environment: CvssEnvironment,
+}
+
+impl RulesConfig {
+    /// Gets the glob patterns of the only paths checked by the rules, if any
+    pub fn get_include_paths(&self) -> &[String] {
+        self.include_paths.as_slice()
+    }
+
+    /// Gets the glob patterns of the paths no rule is checked in
+    pub fn get_exclude_paths(&self) -> &[String] {
+        self.exclude_paths.as_slice()
+    }
+
+    /// Checks if the rule with the given ID is disabled
+    pub fn is_disabled(&self, id: &str) -> bool {
+        self.disabled.contains(id)
+    }
+
+    /// Gets the criticity set in the configuration for the rule with the given ID, if any
+    pub fn get_criticity(&self, id: &str) -> Option<Criticity> {
+        self.criticity.get(id).cloned()
+    }
+
+    /// Gets the CVSS environmental metrics applied to the vectors of all the rules
+    pub fn get_cvss_environment(&self) -> &CvssEnvironment {
+        &self.cvss_environment
+    }
+
+    /// Gets the IDs of the rules mentioned in the configuration
+    pub fn get_rule_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<_> = self.disabled
+            .iter()
+            .chain(self.criticity.keys())
+            .map(|id| id.as_str())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Sources, sinks and sanitizers added to the built-in ones of the taint analysis, as method
+/// names
+#[derive(Debug)]
+pub struct TaintConfig {
+    sources: Vec<String>,
+    sinks: Vec<String>,
+    sanitizers: Vec<String>,
+    inter_procedural: bool,
+}
+
+impl TaintConfig {
+    /// Gets the methods whose return value is untrusted
+    pub fn get_sources(&self) -> &[String] {
+        self.sources.as_slice()
+    }
+
+    /// Gets the methods that must not receive untrusted data as arguments
+    pub fn get_sinks(&self) -> &[String] {
+        self.sinks.as_slice()
+    }
+
+    /// Gets the methods whose return value is safe, even if their arguments are untrusted
+    pub fn get_sanitizers(&self) -> &[String] {
+        self.sanitizers.as_slice()
+    }
+
+    /// Checks if untrusted data returned by the methods of a class is followed to their callers
+    pub fn is_inter_procedural(&self) -> bool {
+        self.inter_procedural
+    }
+}
+
+impl Default for TaintConfig {
+    fn default() -> TaintConfig {
+        TaintConfig {
+            sources: Vec::new(),
+            sinks: Vec::new(),
+            sanitizers: Vec::new(),
+            inter_procedural: true,
+        }
+    }
+}
+
+/// Patterns added to the built-in ones of the sensitive logging analysis
+#[derive(Debug, Default)]
+pub struct LoggingConfig {
+    sensitive_names: Vec<String>,
+}
+
+impl LoggingConfig {
+    /// Gets the regular expressions matching sensitive variable, field and method names
+    ///
+    /// Names are matched in lowercase snake case, so `userPassword` is `user_password`.
+    pub fn get_sensitive_names(&self) -> &[String] {
+        self.sensitive_names.as_slice()
+    }
+}
+
+/// Settings of the VirusTotal lookup of the application
+///
+/// The lookup is disabled by default, and only the SHA-256 hash of the application is sent,
+/// unless uploading the file is explicitly allowed.
+#[derive(Debug, Default)]
+pub struct VirusTotalConfig {
+    enabled: bool,
+    api_key: Option<String>,
+    upload: bool,
+}
+
+impl VirusTotalConfig {
+    /// Checks if the hash of the application should be looked up in VirusTotal
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Gets the key of the VirusTotal API
+    pub fn get_api_key(&self) -> Option<&str> {
+        self.api_key.as_ref().map(|k| k.as_str())
+    }
+
+    /// Checks if the application file can be uploaded when VirusTotal doesn't know its hash
+    pub fn allows_upload(&self) -> bool {
+        self.upload
+    }
+}
+
+/// Webhooks notified when an analysis completes
+///
+/// Slack webhooks get a message with the summary of the results, and the rest of the webhooks
+/// get the summary as JSON. If a minimum criticity is set, they are only notified when there are
+/// findings of that criticity or higher.
+#[derive(Debug)]
+pub struct NotificationsConfig {
+    slack_webhooks: Vec<String>,
+    webhooks: Vec<String>,
+    min_criticity: Option<Criticity>,
+    timeout: u32,
+}
+
+impl NotificationsConfig {
+    /// Gets the URLs of the Slack compatible incoming webhooks
+    pub fn get_slack_webhooks(&self) -> &[String] {
+        self.slack_webhooks.as_slice()
+    }
+
+    /// Gets the URLs the JSON summary of the results is posted to
+    pub fn get_webhooks(&self) -> &[String] {
+        self.webhooks.as_slice()
+    }
+
+    /// Gets the minimum criticity of the findings that trigger the notifications, if any
+    pub fn get_min_criticity(&self) -> Option<Criticity> {
+        self.min_criticity
+    }
+
+    /// Gets the timeout of each request, in seconds
+    pub fn get_timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    /// Checks if any webhook is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.slack_webhooks.is_empty() || !self.webhooks.is_empty()
+    }
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> NotificationsConfig {
+        NotificationsConfig {
+            slack_webhooks: Vec::new(),
+            webhooks: Vec::new(),
+            min_criticity: None,
+            timeout: 30,
+        }
+    }
+}
+
+/// Limits of the jobs of the queue used by the server
+///
+/// Each job is killed if it runs for longer than the timeout, or if its dist and results folders
+/// grow over the disk limit. Applications can only be submitted by their path in the server if
+/// they are in the APK folder.
+#[derive(Debug)]
+pub struct JobsConfig {
+    timeout: u64,
+    max_disk: u64,
+    apk_folder: Option<String>,
+}
+
+impl JobsConfig {
+    /// Gets the maximum duration of each job, in seconds, or 0 if there is no timeout
+    pub fn get_timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    /// Gets the maximum disk usage of each job, in megabytes, or 0 if there is no limit
+    pub fn get_max_disk(&self) -> u64 {
+        self.max_disk
+    }
+
+    /// Gets the folder with the APK files that can be submitted by their path, if any
+    pub fn get_apk_folder(&self) -> Option<&str> {
+        match self.apk_folder {
+            Some(ref f) => Some(f.as_str()),
+            None => None,
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> JobsConfig {
+        JobsConfig {
+            timeout: 3600,
+            max_disk: 0,
+            apk_folder: None,
+        }
+    }
+}
+
+/// Timeouts of the external tools and of the whole analysis, in seconds
+///
+/// A tool that runs for longer than its timeout is killed with all its child processes, and the
+/// report is generated with the results of the stages that finished. A timeout of 0 disables it.
+#[derive(Debug)]
+pub struct TimeoutsConfig {
+    apktool: u64,
+    dex2jar: u64,
+    jd_cmd: u64,
+    analysis: u64,
+}
+
+impl TimeoutsConfig {
+    /// Gets the timeout of the Apktool decompression
+    pub fn get_apktool(&self) -> u64 {
+        self.apktool
+    }
+
+    /// Gets the timeout of the dex to jar conversion
+    pub fn get_dex2jar(&self) -> u64 {
+        self.dex2jar
+    }
+
+    /// Gets the timeout of the Java decompilation
+    pub fn get_jd_cmd(&self) -> u64 {
+        self.jd_cmd
+    }
+
+    /// Gets the timeout of the whole analysis
+    pub fn get_analysis(&self) -> u64 {
+        self.analysis
+    }
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> TimeoutsConfig {
+        TimeoutsConfig {
+            apktool: 600,
+            dex2jar: 900,
+            jd_cmd: 1800,
+            analysis: 0,
+        }
+    }
+}
+
+/// Safety limits of the extraction of the application package and its expansion files, that
+/// protect the analysis from decompression bombs
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    max_entries: usize,
+    max_size: u64,
+    max_compression_ratio: u64,
+}
+
+impl ArchiveConfig {
+    /// Gets the maximum number of entries of an archive
+    pub fn get_max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// Gets the maximum total uncompressed size of the entries of an archive, in bytes
+    pub fn get_max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Gets the maximum compression ratio of an entry
+    pub fn get_max_compression_ratio(&self) -> u64 {
+        self.max_compression_ratio
+    }
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> ArchiveConfig {
+        ArchiveConfig {
+            max_entries: 10000,
+            max_size: 512 * 1024 * 1024,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+/// Post-processing of the findings before the reports are generated
+#[derive(Debug, Clone)]
+pub struct GroupingConfig {
+    deduplicate: bool,
+    max_samples: usize,
+}
+
+impl GroupingConfig {
+    /// Checks if identical findings in different files, such as duplicated generated classes,
+    /// are reported only once
+    pub fn is_deduplicate(&self) -> bool {
+        self.deduplicate
+    }
+
+    /// Gets the maximum number of findings of each rule listed in the reports, or 0 to list all
+    /// of them
+    pub fn get_max_samples(&self) -> usize {
+        self.max_samples
+    }
+}
+
+impl Default for GroupingConfig {
+    fn default() -> GroupingConfig {
+        GroupingConfig {
+            deduplicate: false,
+            max_samples: 0,
+        }
+    }
+}
+
+/// Export of the findings to issue trackers, with an issue for the findings of each rule
+///
+/// Issues can be created in Jira with its REST API, or written to JSON and CSV files to import
+/// them in other trackers. Only the rules with findings of the minimum criticity or higher get
+/// an issue.
+#[derive(Debug)]
+pub struct IssuesConfig {
+    formats: Vec<IssueFormat>,
+    project: String,
+    issue_type: String,
+    labels: Vec<String>,
+    min_criticity: Criticity,
+    jira_url: Option<String>,
+    jira_user: Option<String>,
+    jira_token: Option<String>,
+}
+
+impl IssuesConfig {
+    /// Gets the formats the issues are exported to
+    pub fn get_formats(&self) -> &[IssueFormat] {
+        self.formats.as_slice()
+    }
+
+    /// Gets the key of the project of the issues
+    pub fn get_project(&self) -> &str {
+        self.project.as_str()
+    }
+
+    /// Gets the type of the issues, such as `Bug`
+    pub fn get_issue_type(&self) -> &str {
+        self.issue_type.as_str()
+    }
+
+    /// Gets the labels added to every issue
+    pub fn get_labels(&self) -> &[String] {
+        self.labels.as_slice()
+    }
+
+    /// Gets the minimum criticity of the findings that get an issue
+    pub fn get_min_criticity(&self) -> Criticity {
+        self.min_criticity
+    }
+
+    /// Gets the base URL of the Jira instance
+    pub fn get_jira_url(&self) -> Option<&str> {
+        self.jira_url.as_ref().map(|u| u.as_str())
+    }
+
+    /// Gets the user and API token used to authenticate with Jira, if both are set
+    pub fn get_jira_credentials(&self) -> Option<(&str, &str)> {
+        match (self.jira_user.as_ref(), self.jira_token.as_ref()) {
+            (Some(user), Some(token)) => Some((user.as_str(), token.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Checks if the issues are exported to any format
+    pub fn is_enabled(&self) -> bool {
+        !self.formats.is_empty()
+    }
+}
+
+impl Default for IssuesConfig {
+    fn default() -> IssuesConfig {
+        IssuesConfig {
+            formats: Vec::new(),
+            project: String::new(),
+            issue_type: String::from("Bug"),
+            labels: vec![String::from("super")],
+            min_criticity: Criticity::Medium,
+            jira_url: None,
+            jira_user: None,
+            jira_token: None,
+        }
+    }
+}
+
+/// Policy to keep the dist folder of an application after its analysis
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeepDist {
+    /// The dist folder is always kept, so that it can be reused in later analyses
+    Always,
+    /// The dist folder is only kept if some stage of the analysis failed, to debug it
+    OnError,
+    /// The dist folder is always removed after the analysis
+    Never,
+}
+
+impl KeepDist {
+    /// Gets the name of the policy, used in the `keep_dist` option of the configuration
+    pub fn as_str(&self) -> &str {
+        match *self {
+            KeepDist::Always => "always",
+            KeepDist::OnError => "on_error",
+            KeepDist::Never => "never",
+        }
+    }
+
+    /// Checks if the dist folder should be kept, depending on whether the analysis failed
+    pub fn should_keep(&self, failed: bool) -> bool {
+        match *self {
+            KeepDist::Always => true,
+            KeepDist::OnError => failed,
+            KeepDist::Never => false,
+        }
+    }
+}
+
+impl FromStr for KeepDist {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KeepDist> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(KeepDist::Always),
+            "on_error" | "on-error" => Ok(KeepDist::OnError),
+            "never" => Ok(KeepDist::Never),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Gets the path of the configuration file of the user, loaded after the system configuration
+/// and before the configuration of the current folder
+#[cfg(target_family = "unix")]
+pub fn get_user_config_file() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".config/super/config.toml"))
+}
+
+/// Gets the path of the configuration file of the user, loaded before the configuration of the
+/// current folder
+#[cfg(target_family = "windows")]
+pub fn get_user_config_file() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("super").join("config.toml"))
+}
+
+/// Checks if the string is an HTTP or HTTPS URL
+fn is_http_url(url: &str) -> bool {
+    (url.starts_with("http://") && url.len() > 7) || (url.starts_with("https://") && url.len() > 8)
+}
+
+/// Checks if the string is a valid regular expression for a name
+fn is_name_pattern(pattern: &str) -> bool {
+    !pattern.is_empty() && Regex::new(pattern).is_ok()
+}
+
+/// Checks if the string is a valid Java method name
+fn is_method_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Checks if the string is a transfer rate, in bytes per second with an optional `k`, `M` or
+/// `G` suffix
+fn is_rate(rate: &str) -> bool {
+    let number = rate.trim_right_matches(|c: char| "kKmMgG".contains(c));
+    rate.len() - number.len() <= 1 && !number.is_empty() &&
+    number.chars().all(|c| c.is_digit(10))
+}
+
+/// Checks if the string is a valid source for the automatic downloads
+fn is_download_source(source: &str) -> bool {
+    source == "fdroid" || source == "apkpure" ||
+    ((source.starts_with("http://") || source.starts_with("https://")) &&
+     source.contains("{package}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {Criticity, file_exists};
+    use static_analysis::manifest::{Permission, ManifestFlag};
+    use results::{ReportFormat, LANGUAGES, is_language};
+    use super::{Config, KeepDist, is_rate, is_download_source};
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn it_config() {
+        let mut config: Config = Default::default();
+
+        assert_eq!(config.get_app_id(), "");
+        assert!(!config.is_verbose());
+        assert!(!config.is_quiet());
+        assert!(!config.is_force());
+        assert!(!config.is_bench());
+        assert!(!config.is_profile_rules());
+        assert!(!config.is_historical());
+        assert!(!config.is_online_checks());
+        assert!(!config.is_malware_heuristics());
+        assert!(!config.is_trend());
+        assert!(!config.is_gitlab_sast());
+        assert!(!config.is_show_tool_output());
+        assert_eq!(config.get_report_formats(),
+                   &[ReportFormat::Html, ReportFormat::Json]);
+        assert!(!config.has_report_format(ReportFormat::Pdf));
+        assert!(config.is_include_source());
+        assert!(config.is_decompile());
+        assert_eq!(config.get_language(), "en");
+        assert_eq!(config.get_mapping_file(), None);
+        assert_eq!(config.get_baseline(), None);
+        assert_eq!(config.get_ignore_file(), None);
+        assert_eq!(config.get_source_root(), None);
+        assert!(!config.is_github_annotations());
+        assert!(!config.is_reproducible());
+        assert_eq!(config.get_templates_folder(), None);
+        assert_eq!(config.get_threads(), 2);
+        assert_eq!(config.get_downloads_folder(), "downloads");
+        assert_eq!(config.get_dist_folder(), "dist");
+        assert_eq!(config.get_keep_dist(), KeepDist::Always);
+        assert_eq!(config.get_dist_max_age(), 0);
+        assert_eq!(config.get_workspace_folder(), None);
+        assert_eq!(config.get_results_folder(), "results");
+        assert!(!config.is_versioned_results());
+        if cfg!(target_os = "linux") && Path::new("/usr/share/super").exists() {
+            assert_eq!(config.get_apktool_file(),
+                       "/usr/share/super/vendor/apktool_2.2.0.jar");
+            assert_eq!(config.get_dex2jar_folder(),
+                       "/usr/share/super/vendor/dex2jar-2.0");
+            assert_eq!(config.get_jd_cmd_file(),
+                       "/usr/share/super/vendor/jd-cmd.jar");
+            assert_eq!(config.get_results_template(),
+                       "/usr/share/super/vendor/results_template");
+        } else if cfg!(target_os = "macos") && Path::new("/usr/local/super").exists() {
+            assert_eq!(config.get_apktool_file(),
+                       "/usr/local/super/vendor/apktool_2.2.0.jar");
+            assert_eq!(config.get_dex2jar_folder(),
+                       "/usr/local/super/vendor/dex2jar-2.0");
+            assert_eq!(config.get_jd_cmd_file(),
+                       "/usr/local/super/vendor/jd-cmd.jar");
+            assert_eq!(config.get_results_template(),
+                       "/usr/local/super/vendor/results_template");
+        } else {
+            assert_eq!(config.get_apktool_file(), "vendor/apktool_2.2.0.jar");
+            assert_eq!(config.get_dex2jar_folder(), "vendor/dex2jar-2.0");
+            assert_eq!(config.get_jd_cmd_file(), "vendor/jd-cmd.jar");
+            assert_eq!(config.get_results_template(), "vendor/results_template");
+        }
+        if cfg!(target_family = "unix") && file_exists("/etc/super/rules.json") {
+            assert_eq!(config.get_rules_json(), "/etc/super/rules.json");
+        } else {
+            assert_eq!(config.get_rules_json(), "rules.json");
+        }
+        assert_eq!(config.get_unknown_permission_criticity(), Criticity::Low);
+        assert_eq!(config.get_unknown_permission_description(),
+                   "Even if the application can create its own permissions, it's discouraged, \
+                    since it can lead to missunderstanding between developers.");
+        assert_eq!(config.get_permissions().next(), None);
+        assert_eq!(config.get_secrets_config().get_entropy_threshold(), 4.5);
+        assert_eq!(config.get_secrets_config().get_min_length(), 20);
+        assert_eq!(config.get_downloads_config().get_concurrency(), 4);
+        assert_eq!(config.get_downloads_config().get_rate_limit(), None);
+        assert_eq!(config.get_downloads_config().get_delay(), 1000);
+        assert!(config.get_downloads_config().get_sources().is_empty());
+        assert_eq!(config.get_certificate_config().get_expiry_window(), 90);
+        assert_eq!(config.get_manifest_flag_criticity(ManifestFlag::Debuggable),
+                   Some(Criticity::Critical));
+        assert_eq!(config.get_manifest_flag_criticity(ManifestFlag::LegacyExternalStorage),
+                   Some(Criticity::Low));
+        assert_eq!(config.get_sdk_policy().get_min_sdk(), None);
+        assert_eq!(config.get_sdk_policy().get_max_target_lag(), None);
+        assert_eq!(config.get_sdk_policy().get_latest_sdk(), 35);
+        if file_exists("/etc/super/vulnerabilities.json") {
+            assert_eq!(config.get_vulnerability_db_config().get_path(),
+                       "/etc/super/vulnerabilities.json");
+        } else {
+            assert_eq!(config.get_vulnerability_db_config().get_path(), "vulnerabilities.json");
+        }
+        assert!(config.get_vulnerability_db_config().get_url().starts_with("https://"));
+        assert!(!config.get_rules_config().is_disabled("sql_injection"));
+        assert_eq!(config.get_rules_config().get_criticity("sql_injection"), None);
+        assert!(config.get_taint_config().get_sources().is_empty());
+        assert!(config.get_taint_config().get_sinks().is_empty());
+        assert!(config.get_taint_config().get_sanitizers().is_empty());
+        assert!(config.get_taint_config().is_inter_procedural());
+        assert!(config.get_logging_config().get_sensitive_names().is_empty());
+        assert!(!config.get_virustotal_config().is_enabled());
+        assert_eq!(config.get_virustotal_config().get_api_key(), None);
+        assert!(!config.get_virustotal_config().allows_upload());
+        assert!(!config.get_notifications_config().is_enabled());
+        assert_eq!(config.get_notifications_config().get_min_criticity(), None);
+        assert_eq!(config.get_notifications_config().get_timeout(), 30);
+        assert_eq!(config.get_jobs_config().get_timeout(), 3600);
+        assert_eq!(config.get_jobs_config().get_max_disk(), 0);
+        assert_eq!(config.get_timeouts_config().get_apktool(), 600);
+        assert_eq!(config.get_timeouts_config().get_dex2jar(), 900);
+        assert_eq!(config.get_timeouts_config().get_jd_cmd(), 1800);
+        assert_eq!(config.get_timeouts_config().get_analysis(), 0);
+
+        if !file_exists(config.get_downloads_folder()) {
+            fs::create_dir(config.get_downloads_folder()).unwrap();
+        }
+        if !file_exists(config.get_dist_folder()) {
+            fs::create_dir(config.get_dist_folder()).unwrap();
+        }
+        if !file_exists(config.get_results_folder()) {
+            fs::create_dir(config.get_results_folder()).unwrap();
+        }
+
+        config.set_app_id("test_app");
+        config.set_verbose(true);
+        config.set_quiet(true);
+        config.set_force(true);
+        config.set_bench(true);
+        config.set_profile_rules(true);
+        config.set_historical(true);
+        config.set_online_checks(true);
+        config.set_malware_heuristics(true);
+        config.set_trend(true);
+        config.set_gitlab_sast(true);
+        config.set_show_tool_output(true);
+        config.set_report_formats(vec![ReportFormat::Json]);
+        config.set_include_source(false);
+        config.set_decompile(false);
+        config.set_language("es");
+        config.set_keep_dist(KeepDist::OnError);
+        config.set_dist_max_age(7);
+        config.set_workspace_folder("/tmp/super");
+        config.set_versioned_results(true);
+
+        assert_eq!(config.get_app_id(), "test_app");
+        assert!(config.is_verbose());
+        assert!(config.is_quiet());
+        assert!(config.is_force());
+        assert!(config.is_bench());
+        assert!(config.is_profile_rules());
+        assert!(config.is_historical());
+        assert!(config.is_online_checks());
+        assert!(config.is_malware_heuristics());
+        assert!(config.is_trend());
+        assert!(config.is_gitlab_sast());
+        assert!(config.is_show_tool_output());
+        assert!(config.has_report_format(ReportFormat::Json));
+        assert!(!config.has_report_format(ReportFormat::Html));
+        assert!(!config.is_include_source());
+        assert!(!config.is_decompile());
+        assert_eq!(config.get_language(), "es");
+        assert_eq!(config.get_keep_dist(), KeepDist::OnError);
+        assert_eq!(config.get_dist_max_age(), 7);
+        assert_eq!(config.get_workspace_folder(), Some("/tmp/super"));
+        assert!(config.is_versioned_results());
+
+        if file_exists(format!("{}/{}.apk",
+                               config.get_downloads_folder(),
+                               config.get_app_id())) {
+            fs::remove_file(format!("{}/{}.apk",
+                                    config.get_downloads_folder(),
+                                    config.get_app_id()))
+                .unwrap();
+        }
+        assert!(!config.check());
+
+        fs::File::create(format!("{}/{}.apk",
+                                 config.get_downloads_folder(),
+                                 config.get_app_id()))
+            .unwrap();
+        assert!(config.check());
+
+        while !file_exists("config.toml.sample") {
+            thread::sleep(Duration::from_millis(50));
+        }
+        let config = ConfigBuilder::new().app_id("test_app").build().unwrap();
+        let mut error_string = String::from("Configuration errors were found:\n");
+        for error in config.get_errors() {
+            error_string.push_str(&error);
+            error_string.push('\n');
+        }
+        error_string.push_str("The configuration was loaded, in order, from the following \
+                               files:\n\t- Default built-in configuration\n");
+        for file in config.get_loaded_config_files() {
+            error_string.push_str(&format!("\t- {}\n", file));
+        }
+        println!("{}", error_string);
+        assert!(config.check());
+
+        fs::remove_file(format!("{}/{}.apk",
+                                config.get_downloads_folder(),
+                                config.get_app_id()))
+            .unwrap();
+    }
+
+    #[test]
+    fn it_is_rate() {
+        assert!(is_rate("500"));
+        assert!(is_rate("500k"));
+        assert!(is_rate("2M"));
+        assert!(!is_rate("2MB"));
+        assert!(!is_rate("k"));
+        assert!(!is_rate("fast"));
+    }
+
+    #[test]
+    fn it_is_download_source() {
+        assert!(is_download_source("fdroid"));
+        assert!(is_download_source("apkpure"));
+        assert!(is_download_source("https://artifacts.example.com/apk/{package}.apk"));
+        assert!(!is_download_source("https://artifacts.example.com/apk/latest.apk"));
+        assert!(!is_download_source("file:///srv/apk/{package}.apk"));
+        assert!(!is_download_source("play"));
+    }
+
+    #[test]
+    fn it_rules_config() {
+        let path = "rules_config_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[rules]\n\
+                      disabled = [\"hidden_fields\", \"ip_disclosure\"]\n\
+                      exclude_paths = [\"com/google/**\", \"**/test/**\"]\n\
+                      [rules.criticity]\n\
+                      sql_injection = \"high\"\n\
+                      url_disclosure = \"unknown\"\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(config.get_rules_config().is_disabled("hidden_fields"));
+        assert!(config.get_rules_config().is_disabled("ip_disclosure"));
+        assert!(!config.get_rules_config().is_disabled("sql_injection"));
+        assert_eq!(config.get_rules_config().get_criticity("sql_injection"),
+                   Some(Criticity::High));
+        assert_eq!(config.get_rules_config().get_criticity("url_disclosure"), None);
+        assert!(config.get_rules_config().get_include_paths().is_empty());
+        assert_eq!(config.get_rules_config().get_exclude_paths(),
+                   &[String::from("com/google/**"), String::from("**/test/**")]);
+        assert_eq!(config.get_rules_config().get_rule_ids(),
+                   vec!["hidden_fields", "ip_disclosure", "sql_injection"]);
+    }
+
+    #[test]
+    fn it_notifications_config() {
+        let path = "notifications_config_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[notifications]\n\
+                      slack_webhooks = [\"https://hooks.slack.com/services/T0/B0/X\"]\n\
+                      webhooks = [\"https://ci.example.com/super\", \"ftp://example.com\"]\n\
+                      min_criticity = \"high\"\n\
+                      timeout = 10\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let notifications = config.get_notifications_config();
+        assert!(notifications.is_enabled());
+        assert_eq!(notifications.get_slack_webhooks(),
+                   &[String::from("https://hooks.slack.com/services/T0/B0/X")]);
+        assert_eq!(notifications.get_webhooks(),
+                   &[String::from("https://ci.example.com/super")]);
+        assert_eq!(notifications.get_min_criticity(), Some(Criticity::High));
+        assert_eq!(notifications.get_timeout(), 10);
+    }
+
+    #[test]
+    fn it_jobs_config() {
+        let path = "jobs_config_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[jobs]\n\
+                      timeout = 0\n\
+                      max_disk = 2048\n\
+                      apk_folder = \"src\"\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_jobs_config().get_timeout(), 0);
+        assert_eq!(config.get_jobs_config().get_max_disk(), 2048);
+        assert_eq!(config.get_jobs_config().get_apk_folder(), Some("src"));
+
+        config.set_dist_folder("dist/jobs/1");
+        config.set_results_folder("results/jobs/1");
+        assert_eq!(config.get_dist_folder(), "dist/jobs/1");
+        assert_eq!(config.get_results_folder(), "results/jobs/1");
+    }
+
+    #[test]
+    fn it_timeouts_config() {
+        let path = "timeouts_config_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[timeouts]\n\
+                      dex2jar = 120\n\
+                      jd_cmd = -5\n\
+                      analysis = 3600\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_timeouts_config().get_apktool(), 600);
+        assert_eq!(config.get_timeouts_config().get_dex2jar(), 120);
+        assert_eq!(config.get_timeouts_config().get_jd_cmd(), 1800);
+        assert_eq!(config.get_timeouts_config().get_analysis(), 3600);
+
+        config.set_tool_timeout(0);
+        config.set_analysis_timeout(60);
+        assert_eq!(config.get_timeouts_config().get_apktool(), 0);
+        assert_eq!(config.get_timeouts_config().get_dex2jar(), 0);
+        assert_eq!(config.get_timeouts_config().get_jd_cmd(), 0);
+        assert_eq!(config.get_timeouts_config().get_analysis(), 60);
+    }
+
+    #[test]
+    fn it_archive_config() {
+        let path = "archive_config_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[archive]\n\
+                      max_entries = 500\n\
+                      max_size = 64\n\
+                      max_compression_ratio = 0\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        assert_eq!(config.get_archive_config().get_max_entries(), 10000);
+        assert!(!config.get_grouping_config().is_deduplicate());
+        assert_eq!(config.get_grouping_config().get_max_samples(), 0);
+        assert!(!config.get_issues_config().is_enabled());
+        assert_eq!(config.get_issues_config().get_issue_type(), "Bug");
+        assert_eq!(config.get_issues_config().get_min_criticity(), Criticity::Medium);
+        assert_eq!(config.get_archive_config().get_max_size(), 512 * 1024 * 1024);
+        assert_eq!(config.get_archive_config().get_max_compression_ratio(), 100);
+
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_archive_config().get_max_entries(), 500);
+        assert_eq!(config.get_archive_config().get_max_size(), 64 * 1024 * 1024);
+        assert_eq!(config.get_archive_config().get_max_compression_ratio(), 100);
+    }
+
+    #[test]
+    fn it_keep_dist() {
+        let path = "keep_dist_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"keep_dist = \"on_error\"\n\
+                      dist_max_age = 30\n\
+                      workspace_folder = \"/var/tmp/super\"\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_keep_dist(), KeepDist::OnError);
+        assert_eq!(config.get_dist_max_age(), 30);
+        assert_eq!(config.get_workspace_folder(), Some("/var/tmp/super"));
+
+        assert!(KeepDist::Always.should_keep(false));
+        assert!(KeepDist::OnError.should_keep(true));
+        assert!(!KeepDist::OnError.should_keep(false));
+        assert!(!KeepDist::Never.should_keep(true));
+        assert_eq!(KeepDist::from_str("NEVER").unwrap(), KeepDist::Never);
+        assert!(KeepDist::from_str("sometimes").is_err());
+    }
+
+    #[test]
+    fn it_invalid_options() {
+        let path = "invalid_options_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"threads = \"four\"\n\
+                      historical = true\n\
+                      unknown_option = 3\n\
+                      [secrets]\n\
+                      min_length = 0\n\
+                      entropy_threshold = 5\n\
+                      [jobs]\n\
+                      timeout = \"forever\"\n\
+                      max_disk = 100\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_threads(), 2);
+        assert!(config.is_historical());
+        assert_eq!(config.get_secrets_config().get_min_length(), 20);
+        assert_eq!(config.get_secrets_config().get_entropy_threshold(), 5.0);
+        assert_eq!(config.get_jobs_config().get_timeout(), 3600);
+        assert_eq!(config.get_jobs_config().get_max_disk(), 100);
+    }
+
+    #[test]
+    fn it_profiles() {
+        let path = "profiles_test.toml";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"online_checks = true\n\
+                      [profile.quick]\n\
+                      report_formats = [\"json\"]\n\
+                      decompile = false\n\
+                      online_checks = false\n\
+                      [profile.quick.rules]\n\
+                      disabled = [\"hidden_fields\"]\n\
+                      [profile.full]\n\
+                      report_formats = [\"html\", \"json\", \"pdf\"]\n")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        Config::load_from_file(&mut config, path, false).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.get_profiles(), vec!["full", "quick"]);
+        assert_eq!(config.get_profile(), None);
+        assert!(config.is_online_checks());
+        assert_eq!(config.get_origin("online_checks"), "profiles_test.toml");
+        assert_eq!(config.get_origin("threads"), "default");
+        assert!(config.apply_profile("thorough").is_err());
+
+        config.apply_profile("quick").unwrap();
+        assert_eq!(config.get_profile(), Some("quick"));
+        assert_eq!(config.get_report_formats(), &[ReportFormat::Json]);
+        assert!(!config.is_decompile());
+        assert!(!config.is_online_checks());
+        assert!(config.get_rules_config().is_disabled("hidden_fields"));
+        assert_eq!(config.get_origin("online_checks"), "profile quick");
+
+        let options = config.get_effective_options();
+        assert!(options.iter()
+            .any(|&(key, ref value, origin)| {
+                key == "decompile" && value == "false" && origin == "profile quick"
+            }));
+        assert!(options.iter().any(|&(key, ref value, _)| {
+            key == "report_formats" && value == "[\"json\"]"
+        }));
+    }
+
+    #[test]
+    fn it_config_sample() {
+        fs::rename("config.toml", "config.toml.bk").unwrap();
+        fs::rename("config.toml.sample", "config.toml").unwrap();
+
+        let config = ConfigBuilder::new().app_id("test_app").build().unwrap();
+        assert_eq!(config.get_threads(), 2);
+        assert_eq!(config.get_downloads_folder(), "downloads");
+        assert_eq!(config.get_dist_folder(), "dist");
+        assert_eq!(config.get_results_folder(), "results");
+        assert_eq!(config.get_apktool_file(),
+                   "/usr/share/super/vendor/apktool_2.2.0.jar");
+        assert_eq!(config.get_dex2jar_folder(),
+                   "/usr/share/super/vendor/dex2jar-2.0");
+        assert_eq!(config.get_jd_cmd_file(),
+                   "/usr/share/super/vendor/jd-cmd.jar");
+        assert_eq!(config.get_results_template(),
+                   "/usr/share/super/vendor/results_template");
+        assert_eq!(config.get_rules_json(), "/etc/super/rules.json");
+        assert_eq!(config.get_unknown_permission_criticity(), Criticity::Low);
+        assert_eq!(config.get_unknown_permission_description(),
+                   "Even if the application can create its own permissions, it's discouraged, \
+                    since it can lead to missunderstanding between developers.");
+
+        let permission = config.get_permissions().next().unwrap();
+        assert_eq!(permission.get_permission(),
+                   Permission::AndroidPermissionInternet);
+        assert_eq!(permission.get_criticity(), Criticity::Warning);
+        assert_eq!(permission.get_label(), "Internet permission");
+        assert_eq!(permission.get_description(),
+                   "Allows the app to create network sockets and use custom network protocols. \
+                    The browser and other applications provide means to send data to the \
+                    internet, so this permission is not required to send data to the internet. \
+                    Check if the permission is actually needed.");
+
+        fs::rename("config.toml", "config.toml.sample").unwrap();
+        fs::rename("config.toml.bk", "config.toml").unwrap();
+    }
+}
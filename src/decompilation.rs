@@ -1,15 +1,129 @@
-use std::fs;
+use std::{cmp, fs, result};
 use std::fs::File;
-use std::time::Instant;
-use std::io::{Read, Write};
-use std::process::{Command, exit};
+use std::time::{Duration, Instant};
+use std::io::Write;
+use std::process::{Command, Output};
 use colored::Colorize;
-use zip::ZipArchive;
+use chrono::Local;
 
-use {Error, Config, print_error, print_warning, file_exists};
-use results::Benchmark;
+use {Error, Result, Config, print_error, print_warning, file_exists, run_command};
+use archive::Archive;
+use results::{Benchmark, IncompleteStage};
 
-pub fn decompress(config: &Config) {
+/// Gets the timeout of a stage, from its configured timeout in seconds and the deadline of the
+/// whole analysis, if any
+fn get_timeout(timeout: u64, deadline: Option<Instant>) -> Option<Duration> {
+    let timeout = if timeout > 0 {
+        Some(Duration::from_secs(timeout))
+    } else {
+        None
+    };
+    match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+            let left = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            Some(timeout.map_or(left, |t| cmp::min(t, left)))
+        }
+        None => timeout,
+    }
+}
+
+/// Runs an external tool of the analysis, and writes its output to a timestamped log file in
+/// the `logs` folder of the results folder
+///
+/// Returns the output of the tool, and the path of the log file relative to the results folder
+/// of the application, if it could be written. The output is also shown as it's generated if
+/// the `--show-tool-output` flag was given in verbose mode.
+fn run_tool(tool: &str,
+            command: &mut Command,
+            timeout: Option<Duration>,
+            config: &Config)
+            -> (Result<Output>, Option<String>) {
+    let echo = config.is_verbose() && config.is_show_tool_output();
+    let command_line = format!("{:?}", command);
+    let output = run_command(command, timeout, echo);
+
+    let log_folder = format!("{}/logs/{}", config.get_results_folder(), config.get_app_id());
+    let log_name = format!("{}-{}.log", Local::now().format("%Y%m%d-%H%M%S"), tool);
+    let mut log = format!("$ {}\n", command_line);
+    match output {
+        Ok(ref output) => {
+            log.push_str(&format!("Exit status: {}\n\n--- stdout ---\n{}\n--- stderr ---\n{}",
+                                  output.status,
+                                  String::from_utf8_lossy(&output.stdout),
+                                  String::from_utf8_lossy(&output.stderr)));
+        }
+        Err(Error::TimedOut(ref stdout, ref stderr)) => {
+            log.push_str(&format!("The command was killed after its timeout of {} seconds.\n\n\
+                                   --- stdout ---\n{}\n--- stderr ---\n{}",
+                                  timeout.map_or(0, |t| t.as_secs()),
+                                  String::from_utf8_lossy(stdout),
+                                  String::from_utf8_lossy(stderr)));
+        }
+        Err(ref e) => log.push_str(&format!("The command could not be executed: {}\n", e)),
+    }
+
+    let written = fs::create_dir_all(&log_folder)
+        .and_then(|_| File::create(format!("{}/{}", log_folder, log_name)))
+        .and_then(|mut f| f.write_all(log.as_bytes()));
+    match written {
+        Ok(()) => (output, Some(format!("../logs/{}/{}", config.get_app_id(), log_name))),
+        Err(e) => {
+            print_warning(format!("The output of {} could not be written to its log file: {}",
+                                  tool,
+                                  e),
+                          config.is_verbose());
+            (output, None)
+        }
+    }
+}
+
+/// Prints the error that made the stage fail, and gets its incomplete stage marker, with the
+/// error output of the tool and its log file, if any
+fn failed(stage: &str,
+          reason: String,
+          stderr: Option<&[u8]>,
+          log: Option<&str>,
+          config: &Config)
+          -> IncompleteStage {
+    print_error(format!("{} The analysis will continue with the stages that can run.", reason),
+                config.is_verbose());
+    let stage = IncompleteStage::new(stage, reason);
+    let stage = match stderr.map(|e| String::from_utf8_lossy(e).trim().to_owned()) {
+        Some(ref details) if !details.is_empty() => stage.with_details(details.as_str()),
+        _ => stage,
+    };
+    match log {
+        Some(log) => stage.with_log(log),
+        None => stage,
+    }
+}
+
+/// Warns that the stage was killed after its timeout, and gets its incomplete stage marker
+fn timed_out(stage: &str,
+             timeout: Option<Duration>,
+             log: Option<&str>,
+             config: &Config)
+             -> IncompleteStage {
+    let stage = IncompleteStage::timed_out(stage,
+                                           timeout.unwrap_or_else(|| Duration::from_secs(0)));
+    print_warning(format!("{}: {} The report will be partial.",
+                          stage.get_stage(),
+                          stage.get_reason()),
+                  config.is_verbose());
+    match log {
+        Some(log) => stage.with_log(log),
+        None => stage,
+    }
+}
+
+pub fn decompress(config: &Config,
+                  deadline: Option<Instant>)
+                  -> result::Result<(), IncompleteStage> {
     let path = format!("{}/{}", config.get_dist_folder(), config.get_app_id());
     if !file_exists(&path) || config.is_force() {
         if file_exists(&path) {
@@ -30,35 +144,66 @@ pub fn decompress(config: &Config) {
             println!("Decompressing the application…");
         }
 
-        let output = Command::new("java")
-            .arg("-jar")
-            .arg(config.get_apktool_file())
-            .arg("d")
-            .arg("-s")
-            .arg("-o")
-            .arg(format!("{}/{}", config.get_dist_folder(), config.get_app_id()))
-            .arg("-f")
-            .arg(format!("{}/{}.apk",
-                         config.get_downloads_folder(),
-                         config.get_app_id()))
-            .output();
+        // APKTool extracts the whole package, so it's only run if the package is within the
+        // archive safety limits
+        let apk = Archive::open(format!("{}/{}.apk",
+                                        config.get_downloads_folder(),
+                                        config.get_app_id()),
+                                config.get_archive_config());
+        let violation = match apk {
+            Ok(apk) => apk.check_limits().map(Error::Archive),
+            Err(e) => Some(e),
+        };
+        if let Some(e) = violation {
+            return Err(failed("ApkTool decompression",
+                              format!("The application package was not decompressed, since it \
+                                       could not be safely extracted: {}",
+                                      e),
+                              None,
+                              None,
+                              config));
+        }
+
+        let timeout = get_timeout(config.get_timeouts_config().get_apktool(), deadline);
+        let (output, log) = run_tool("apktool",
+                                     Command::new("java")
+                                         .arg("-jar")
+                                         .arg(config.get_apktool_file())
+                                         .arg("d")
+                                         .arg("-o")
+                                         .arg(format!("{}/{}",
+                                                      config.get_dist_folder(),
+                                                      config.get_app_id()))
+                                         .arg("-f")
+                                         .arg(format!("{}/{}.apk",
+                                                      config.get_downloads_folder(),
+                                                      config.get_app_id())),
+                                     timeout,
+                                     config);
+        let log = log.as_ref().map(|l| l.as_str());
 
         let output = match output {
             Ok(o) => o,
+            Err(Error::TimedOut(..)) => {
+                return Err(timed_out("ApkTool decompression", timeout, log, config));
+            }
             Err(e) => {
-                print_error(format!("There was an error when executing the decompression \
-                                     command: {}",
-                                    e),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
+                return Err(failed("ApkTool decompression",
+                                  format!("There was an error when executing the decompression \
+                                           command: {}",
+                                          e),
+                                  None,
+                                  log,
+                                  config));
             }
         };
 
         if !output.status.success() {
-            print_error(format!("The decompression command returned an error. More info: {}",
-                                String::from_utf8_lossy(&output.stderr[..])),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
+            return Err(failed("ApkTool decompression",
+                              String::from("The decompression command returned an error."),
+                              Some(&output.stderr[..]),
+                              log,
+                              config));
         }
 
         if config.is_verbose() {
@@ -74,9 +219,13 @@ pub fn decompress(config: &Config) {
         println!("Seems that the application has already been decompressed. There is no need to \
                   do it again.");
     }
+    Ok(())
 }
 
-pub fn extract_dex(config: &Config, benchmarks: &mut Vec<Benchmark>) {
+pub fn extract_dex(config: &Config,
+                   benchmarks: &mut Vec<Benchmark>,
+                   deadline: Option<Instant>)
+                   -> result::Result<(), IncompleteStage> {
     if config.is_force() ||
        !file_exists(format!("{}/{}/classes.jar",
                             config.get_dist_folder(),
@@ -89,70 +238,50 @@ pub fn extract_dex(config: &Config, benchmarks: &mut Vec<Benchmark>) {
 
         let start_time = Instant::now();
 
-        let zip = ZipArchive::new(match File::open(format!("{}/{}.apk",
-                                                           config.get_downloads_folder(),
-                                                           config.get_app_id())) {
-            Ok(f) => f,
+        let dex = Archive::open(format!("{}/{}.apk",
+                                        config.get_downloads_folder(),
+                                        config.get_app_id()),
+                                config.get_archive_config())
+            .and_then(|mut apk| apk.read("classes.dex"));
+        let bytes = match dex {
+            Ok(bytes) => bytes,
             Err(e) => {
-                print_error(format!("There was an error when decompressing the {} file. More \
-                                     info: {}",
-                                    ".apk".italic(),
-                                    e),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
-            }
-        });
-        if zip.is_err() {
-            print_error(format!("There was an error when decompressing the {} file. More info: \
-                                 {}",
-                                ".apk".italic(),
-                                zip.err().unwrap()),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
-        }
-
-        let mut zip = zip.unwrap();
-        let mut dex_file = match zip.by_name("classes.dex") {
-            Ok(f) => f,
-            Err(e) => {
-                print_error(format!("There was an error while finding the classes.dex file \
-                                     inside the {} file. More info: {}",
-                                    ".apk".italic(),
-                                    e),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
+                return Err(failed("Dex extraction",
+                                  format!("There was an error while reading the classes.dex \
+                                           file from the .apk file. More info: {}",
+                                          e),
+                                  None,
+                                  None,
+                                  config));
             }
         };
 
+        // The decompression folder doesn't exist if the Apktool decompression failed
+        let _ = fs::create_dir_all(format!("{}/{}", config.get_dist_folder(), config.get_app_id()));
+
         let mut out_file = match File::create(format!("{}/{}/classes.dex",
                                                       config.get_dist_folder(),
                                                       config.get_app_id())) {
             Ok(f) => f,
             Err(e) => {
-                print_error(format!("There was an error while creating classes.dex file. More \
-                                     info: {}",
-                                    e),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
+                return Err(failed("Dex extraction",
+                                  format!("There was an error while creating classes.dex file. \
+                                           More info: {}",
+                                          e),
+                                  None,
+                                  None,
+                                  config));
             }
         };
 
-        let mut bytes = Vec::with_capacity(dex_file.size() as usize);
-        if let Err(e) = dex_file.read_to_end(&mut bytes) {
-            print_error(format!("There was an error while reading classes.dex file from the {}. \
-                                 More info: {}",
-                                ".apk".italic(),
-                                e),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
-        }
-
         if let Err(e) = out_file.write_all(&bytes[..]) {
-            print_error(format!("There was an error while writting classes.dex file. More info: \
-                                 {}",
-                                e),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
+            return Err(failed("Dex extraction",
+                              format!("There was an error while writting classes.dex file. More \
+                                       info: {}",
+                                      e),
+                              None,
+                              None,
+                              config));
         }
 
         benchmarks.push(Benchmark::new("Dex extraction", start_time.elapsed()));
@@ -172,59 +301,70 @@ pub fn extract_dex(config: &Config, benchmarks: &mut Vec<Benchmark>) {
 
         let dex_jar_time = Instant::now();
         // Converting the dex to jar
-        dex_to_jar(config);
+        let result = dex_to_jar(config, deadline);
 
         benchmarks.push(Benchmark::new("Dex to Jar decompilation", dex_jar_time.elapsed()));
-    } else if config.is_verbose() {
-        println!("Seems that there is already a {} file for the application. There is no need to \
-                  create it again.",
-                 ".jar".italic());
+        result
+    } else {
+        if config.is_verbose() {
+            println!("Seems that there is already a {} file for the application. There is no \
+                      need to create it again.",
+                     ".jar".italic());
+        }
+        Ok(())
     }
 }
 
-fn dex_to_jar(config: &Config) {
-    let output;
-    if cfg!(target_family="windows") {
-        output = Command::new(format!("{}\\d2j-dex2jar.bat", config.get_dex2jar_folder()))
-            .arg(format!("{}\\{}\\classes.dex",
-                         config.get_dist_folder(),
-                         config.get_app_id()))
-            .arg("-o")
-            .arg(format!("{}\\{}\\classes.jar",
-                         config.get_dist_folder(),
-                         config.get_app_id()))
-            .output();
+fn dex_to_jar(config: &Config, deadline: Option<Instant>) -> result::Result<(), IncompleteStage> {
+    let timeout = get_timeout(config.get_timeouts_config().get_dex2jar(), deadline);
+    let (dex2jar, dex_file, jar_file) = if cfg!(target_family="windows") {
+        (format!("{}\\d2j-dex2jar.bat", config.get_dex2jar_folder()),
+         format!("{}\\{}\\classes.dex",
+                 config.get_dist_folder(),
+                 config.get_app_id()),
+         format!("{}\\{}\\classes.jar",
+                 config.get_dist_folder(),
+                 config.get_app_id()))
     } else {
-        output = Command::new(format!("{}/d2j-dex2jar.sh", config.get_dex2jar_folder()))
-            .arg(format!("{}/{}/classes.dex",
-                         config.get_dist_folder(),
-                         config.get_app_id()))
-            .arg("-o")
-            .arg(format!("{}/{}/classes.jar",
-                         config.get_dist_folder(),
-                         config.get_app_id()))
-            .output();
+        (format!("{}/d2j-dex2jar.sh", config.get_dex2jar_folder()),
+         format!("{}/{}/classes.dex",
+                 config.get_dist_folder(),
+                 config.get_app_id()),
+         format!("{}/{}/classes.jar",
+                 config.get_dist_folder(),
+                 config.get_app_id()))
+    };
+    let (output, log) = run_tool("dex2jar",
+                                 Command::new(dex2jar).arg(dex_file).arg("-o").arg(&jar_file),
+                                 timeout,
+                                 config);
+    let log = log.as_ref().map(|l| l.as_str());
+
+    if let Err(Error::TimedOut(..)) = output {
+        // A partial jar file would be taken as a complete one by later analyses
+        if file_exists(&jar_file) {
+            let _ = fs::remove_file(&jar_file);
+        }
+        return Err(timed_out("Dex to Jar decompilation", timeout, log, config));
     }
 
     if output.is_err() {
-        print_error(format!("There was an error when executing the {} to {} conversion \
-                             command: {}",
-                            ".dex".italic(),
-                            ".jar".italic(),
-                            output.err().unwrap()),
-                    config.is_verbose());
-        exit(Error::Unknown.into());
+        return Err(failed("Dex to Jar decompilation",
+                          format!("There was an error when executing the .dex to .jar \
+                                   conversion command: {}",
+                                  output.err().unwrap()),
+                          None,
+                          log,
+                          config));
     }
 
     let output = output.unwrap();
     if !output.status.success() {
-        print_error(format!("The {} to {} conversion command returned an error. More info: \
-                             {}",
-                            ".dex".italic(),
-                            ".jar".italic(),
-                            String::from_utf8_lossy(&output.stderr[..])),
-                    config.is_verbose());
-        exit(Error::Unknown.into());
+        return Err(failed("Dex to Jar decompilation",
+                          String::from("The .dex to .jar conversion command returned an error."),
+                          Some(&output.stderr[..]),
+                          log,
+                          config));
     }
 
     if config.is_verbose() {
@@ -240,36 +380,52 @@ fn dex_to_jar(config: &Config) {
     } else if !config.is_quiet() {
         println!("Jar file generated.");
     }
+    Ok(())
 }
 
-pub fn decompile(config: &Config) {
+pub fn decompile(config: &Config,
+                 deadline: Option<Instant>)
+                 -> result::Result<(), IncompleteStage> {
     let out_path = format!("{}/{}/classes",
                            config.get_dist_folder(),
                            config.get_app_id());
     if config.is_force() || !file_exists(&out_path) {
-        let output = Command::new("java")
-            .arg("-jar")
-            .arg(config.get_jd_cmd_file())
-            .arg(format!("{}/{}/classes.jar",
-                         config.get_dist_folder(),
-                         config.get_app_id()))
-            .arg("-od")
-            .arg(&out_path)
-            .output();
+        let timeout = get_timeout(config.get_timeouts_config().get_jd_cmd(), deadline);
+        let (output, log) = run_tool("jd-cmd",
+                                     Command::new("java")
+                                         .arg("-jar")
+                                         .arg(config.get_jd_cmd_file())
+                                         .arg(format!("{}/{}/classes.jar",
+                                                      config.get_dist_folder(),
+                                                      config.get_app_id()))
+                                         .arg("-od")
+                                         .arg(&out_path),
+                                     timeout,
+                                     config);
+        let log = log.as_ref().map(|l| l.as_str());
 
+        // The classes decompiled before the timeout are kept, and analyzed as a partial result
+        if let Err(Error::TimedOut(..)) = output {
+            return Err(timed_out("Decompilation", timeout, log, config));
+        }
         if output.is_err() {
-            print_error(format!("There was an unknown error decompiling the application: {}",
-                                output.err().unwrap()),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
+            return Err(failed("Decompilation",
+                              format!("There was an unknown error decompiling the application: \
+                                       {}",
+                                      output.err().unwrap()),
+                              None,
+                              log,
+                              config));
         }
 
+        // The classes that could be decompiled are kept, and analyzed as a partial result
         let output = output.unwrap();
         if !output.status.success() {
-            print_error(format!("The decompilation command returned an error. More info: {}",
-                                String::from_utf8_lossy(&output.stderr[..])),
-                        config.is_verbose());
-            exit(Error::Unknown.into());
+            return Err(failed("Decompilation",
+                              String::from("The decompilation command returned an error."),
+                              Some(&output.stderr[..]),
+                              log,
+                              config));
         }
 
         if config.is_verbose() {
@@ -282,4 +438,5 @@ pub fn decompile(config: &Config) {
         println!("Seems that there is already a source folder for the application. There is no \
                   need to decompile it again.");
     }
+    Ok(())
 }
@@ -0,0 +1,290 @@
+//! Parser of the Dalvik executable files of Android applications, the `classes.dex` files.
+//!
+//! It extracts the class names, method references and string constants of the application
+//! directly from its `.apk` file, so that the analyses that only need them can run without
+//! Dex2Jar and JD-CMD.
+
+use archive::read_apk_file;
+use axml::{read_u16, read_u32};
+use {Config, Result, Error};
+
+/// Magic number of the DEX files, followed by their version
+const DEX_MAGIC: &'static [u8] = b"dex\n";
+
+/// Size of the header of the DEX files
+const HEADER_SIZE: usize = 0x70;
+
+/// Sizes of the items of the ID lists
+const STRING_ID_SIZE: usize = 4;
+const TYPE_ID_SIZE: usize = 4;
+const METHOD_ID_SIZE: usize = 8;
+const CLASS_DEF_SIZE: usize = 32;
+
+/// Method referenced by the code of the application, declared in it or in a library
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MethodReference {
+    class: String,
+    name: String,
+}
+
+impl MethodReference {
+    /// Gets the class declaring the method, as a Java name, such as `android.util.Log`
+    pub fn get_class(&self) -> &str {
+        self.class.as_str()
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+/// Contents of a DEX file
+#[derive(Debug, Default)]
+pub struct DexFile {
+    strings: Vec<String>,
+    types: Vec<String>,
+    classes: Vec<String>,
+    methods: Vec<MethodReference>,
+}
+
+impl DexFile {
+    /// Parses a DEX file
+    pub fn parse(data: &[u8]) -> Result<DexFile> {
+        if data.len() < HEADER_SIZE || !data.starts_with(DEX_MAGIC) {
+            return Err(Error::ParseError);
+        }
+
+        let mut dex = DexFile::default();
+        let (count, offset) = try!(get_id_list(data, 56, STRING_ID_SIZE));
+        for i in 0..count {
+            let string_offset = try!(read_u32(data, offset + i * STRING_ID_SIZE)) as usize;
+            dex.strings.push(try!(read_string_data(data, string_offset)));
+        }
+
+        let (count, offset) = try!(get_id_list(data, 64, TYPE_ID_SIZE));
+        for i in 0..count {
+            let descriptor = try!(read_u32(data, offset + i * TYPE_ID_SIZE));
+            let descriptor = String::from(try!(dex.get_string(descriptor)));
+            dex.types.push(descriptor);
+        }
+
+        let (count, offset) = try!(get_id_list(data, 88, METHOD_ID_SIZE));
+        for i in 0..count {
+            let class = try!(read_u16(data, offset + i * METHOD_ID_SIZE)) as u32;
+            let name = try!(read_u32(data, offset + i * METHOD_ID_SIZE + 4));
+            let method = MethodReference {
+                class: get_java_name(try!(dex.get_type(class))),
+                name: String::from(try!(dex.get_string(name))),
+            };
+            dex.methods.push(method);
+        }
+
+        let (count, offset) = try!(get_id_list(data, 96, CLASS_DEF_SIZE));
+        for i in 0..count {
+            let class = try!(read_u32(data, offset + i * CLASS_DEF_SIZE));
+            let class = get_java_name(try!(dex.get_type(class)));
+            dex.classes.push(class);
+        }
+        Ok(dex)
+    }
+
+    fn get_string(&self, index: u32) -> Result<&str> {
+        match self.strings.get(index as usize) {
+            Some(s) => Ok(s.as_str()),
+            None => Err(Error::ParseError),
+        }
+    }
+
+    fn get_type(&self, index: u32) -> Result<&str> {
+        match self.types.get(index as usize) {
+            Some(t) => Ok(t.as_str()),
+            None => Err(Error::ParseError),
+        }
+    }
+
+    /// Gets the strings of the file, including the string constants of the code
+    pub fn get_strings(&self) -> &[String] {
+        self.strings.as_slice()
+    }
+
+    /// Gets the classes defined in the file, as Java names
+    pub fn get_classes(&self) -> &[String] {
+        self.classes.as_slice()
+    }
+
+    /// Gets the methods referenced by the code of the file
+    pub fn get_method_references(&self) -> &[MethodReference] {
+        self.methods.as_slice()
+    }
+
+    /// Checks if the file references a class of the package, given as a path, such as
+    /// `com/google/firebase`
+    pub fn has_package(&self, package: &str) -> bool {
+        let prefix = format!("L{}/", package);
+        self.types.iter().any(|t| t.starts_with(&prefix))
+    }
+
+    /// Checks if the file references the class, given as a path, such as `okhttp3/OkHttpClient`
+    pub fn has_class(&self, class: &str) -> bool {
+        let descriptor = format!("L{};", class);
+        self.types.iter().any(|t| *t == descriptor)
+    }
+}
+
+/// Gets the number of items and the offset of an ID list, from its size and offset fields in
+/// the header, checking that the whole list fits in the file
+fn get_id_list(data: &[u8], header_offset: usize, item_size: usize) -> Result<(usize, usize)> {
+    let count = try!(read_u32(data, header_offset)) as usize;
+    let offset = try!(read_u32(data, header_offset + 4)) as usize;
+    if count > 0 && (count > data.len() / item_size || offset + count * item_size > data.len()) {
+        return Err(Error::ParseError);
+    }
+    Ok((count, offset))
+}
+
+/// Reads an unsigned LEB128 number, and gets the offset after it
+fn read_uleb128(data: &[u8], offset: usize) -> Result<(u32, usize)> {
+    let mut result = 0u32;
+    for i in 0..5 {
+        let byte = match data.get(offset + i) {
+            Some(b) => *b,
+            None => return Err(Error::ParseError),
+        };
+        result |= ((byte & 0x7f) as u32) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok((result, offset + i + 1));
+        }
+    }
+    Err(Error::ParseError)
+}
+
+/// Reads a string data item: its length in UTF-16 units and its MUTF-8 bytes, ending with a zero
+fn read_string_data(data: &[u8], offset: usize) -> Result<String> {
+    let (_, offset) = try!(read_uleb128(data, offset));
+    let end = match data[offset..].iter().position(|b| *b == 0) {
+        Some(end) => offset + end,
+        None => return Err(Error::ParseError),
+    };
+    Ok(decode_mutf8(&data[offset..end]))
+}
+
+/// Decodes a Modified UTF-8 string, in which the characters outside the basic plane are encoded
+/// as two surrogates of three bytes each
+fn decode_mutf8(bytes: &[u8]) -> String {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i] as u16;
+        let (unit, size) = if byte & 0x80 == 0 {
+            (byte, 1)
+        } else if byte & 0xe0 == 0xc0 && i + 1 < bytes.len() {
+            ((byte & 0x1f) << 6 | (bytes[i + 1] & 0x3f) as u16, 2)
+        } else if byte & 0xf0 == 0xe0 && i + 2 < bytes.len() {
+            ((byte & 0x0f) << 12 | ((bytes[i + 1] & 0x3f) as u16) << 6 |
+             (bytes[i + 2] & 0x3f) as u16,
+             3)
+        } else {
+            (0xfffd, 1)
+        };
+        units.push(unit);
+        i += size;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Gets the Java name of a type descriptor, such as `java.lang.String` for `Ljava/lang/String;`
+fn get_java_name(descriptor: &str) -> String {
+    if descriptor.starts_with('L') && descriptor.ends_with(';') {
+        descriptor[1..descriptor.len() - 1].replace('/', ".")
+    } else {
+        String::from(descriptor)
+    }
+}
+
+/// Loads the DEX files of the application from its `.apk` file: `classes.dex`, and
+/// `classes2.dex` and so on for multidex applications
+pub fn load_dex_files(config: &Config) -> Result<Vec<DexFile>> {
+    let mut dex_files = vec![try!(DexFile::parse(&try!(read_apk_file(config, "classes.dex"))))];
+    let mut i = 2;
+    while let Ok(data) = read_apk_file(config, &format!("classes{}.dex", i)) {
+        dex_files.push(try!(DexFile::parse(&data)));
+        i += 1;
+    }
+    Ok(dex_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use axml::tests::{push_u16, push_u32};
+    use super::{DexFile, decode_mutf8, get_java_name};
+
+    #[test]
+    fn it_parse_dex() {
+        let strings = ["<init>", "Landroid/util/Log;", "Lcom/example/Main;", "d",
+                       "https://api.example.com/v1"];
+
+        // Header, with the ID lists right after it, followed by the string data
+        let mut dex = b"dex\n035\0".to_vec();
+        dex.resize(0x70, 0);
+        let strings_offset = 0x70;
+        let types_offset = strings_offset + strings.len() * 4;
+        let methods_offset = types_offset + 2 * 4;
+        let classes_offset = methods_offset + 2 * 8;
+        let data_offset = classes_offset + 32;
+        for &(field, value) in &[(56, strings.len()),
+                                 (60, strings_offset),
+                                 (64, 2),
+                                 (68, types_offset),
+                                 (88, 2),
+                                 (92, methods_offset),
+                                 (96, 1),
+                                 (100, classes_offset)] {
+            let mut bytes = Vec::new();
+            push_u32(&mut bytes, value as u32);
+            dex[field..field + 4].copy_from_slice(&bytes);
+        }
+
+        let mut data = Vec::new();
+        for string in &strings {
+            push_u32(&mut dex, (data_offset + data.len()) as u32);
+            data.push(string.len() as u8);
+            data.extend_from_slice(string.as_bytes());
+            data.push(0);
+        }
+        push_u32(&mut dex, 1);
+        push_u32(&mut dex, 2);
+        for &(class, name) in &[(0u16, 3u32), (1, 0)] {
+            push_u16(&mut dex, class);
+            push_u16(&mut dex, 0);
+            push_u32(&mut dex, name);
+        }
+        push_u32(&mut dex, 1);
+        dex.extend_from_slice(&[0u8; 28]);
+        dex.extend_from_slice(&data);
+
+        let dex = DexFile::parse(&dex).unwrap();
+        assert_eq!(dex.get_strings().len(), 5);
+        assert_eq!(dex.get_strings()[4], "https://api.example.com/v1");
+        assert_eq!(dex.get_classes().to_vec(), vec![String::from("com.example.Main")]);
+        assert_eq!(dex.get_method_references()[0].get_class(), "android.util.Log");
+        assert_eq!(dex.get_method_references()[0].get_name(), "d");
+        assert!(dex.has_package("android/util"));
+        assert!(dex.has_class("com/example/Main"));
+        assert!(!dex.has_package("com/google"));
+
+        assert!(DexFile::parse(b"dex\n035\0").is_err());
+        assert!(DexFile::parse(&[0u8; 0x70]).is_err());
+    }
+
+    #[test]
+    fn it_decode_mutf8() {
+        assert_eq!(decode_mutf8(b"abc"), "abc");
+        assert_eq!(decode_mutf8(&[0xc0, 0x80]), "\u{0}");
+        assert_eq!(decode_mutf8(&[0xc3, 0xb1]), "ñ");
+        // U+1F600, encoded as two surrogates
+        assert_eq!(decode_mutf8(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]), "\u{1F600}");
+
+        assert_eq!(get_java_name("Ljava/lang/String;"), "java.lang.String");
+        assert_eq!(get_java_name("[I"), "[I");
+    }
+}
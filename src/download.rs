@@ -0,0 +1,818 @@
+use std::{fs, io, result};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use chrono::Local;
+use colored::Colorize;
+
+use {Config, Result, Error, print_warning, file_exists};
+
+/// Name of the file in the downloads folder recording the downloaded applications
+const MANIFEST_FILE: &'static str = "manifest.json";
+
+/// F-Droid API giving the suggested version of a package, and repository of its APK files
+const FDROID_API_URL: &'static str = "https://f-droid.org/api/v1/packages";
+const FDROID_REPO_URL: &'static str = "https://f-droid.org/repo";
+
+/// APKPure URL for the latest APK file of a package
+const APKPURE_URL: &'static str = "https://d.apkpure.com/b/APK/{package}?version=latest";
+
+/// Maximum time in seconds for the requests resolving the download URL
+const RESOLVE_TIMEOUT: u32 = 30;
+
+/// Application to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadJob {
+    package: String,
+    url: String,
+}
+
+impl DownloadJob {
+    pub fn new<S: Into<String>>(package: S, url: S) -> DownloadJob {
+        DownloadJob {
+            package: package.into(),
+            url: url.into(),
+        }
+    }
+
+    pub fn get_package(&self) -> &str {
+        self.package.as_str()
+    }
+
+    pub fn get_url(&self) -> &str {
+        self.url.as_str()
+    }
+}
+
+/// Parses a download list
+///
+/// The list has an application per line, either as `<package> <url>` or just as `<url>`, in
+/// which case the package is taken from the file name in the URL. Empty lines and lines
+/// starting with `#` are ignored.
+pub fn parse_download_list(list: &str) -> Result<Vec<DownloadJob>> {
+    let mut jobs = Vec::new();
+    for line in list.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let first = parts.next().unwrap();
+        let job = match (parts.next(), parts.next()) {
+            (None, _) => {
+                match get_package_from_url(first) {
+                    Some(package) => DownloadJob::new(package, String::from(first)),
+                    None => return Err(Error::ParseError),
+                }
+            }
+            (Some(url), None) => DownloadJob::new(first, url),
+            _ => return Err(Error::ParseError),
+        };
+        if !is_valid_package(job.get_package()) {
+            return Err(Error::ParseError);
+        }
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+/// Gets the package of the application from the file name of the URL
+fn get_package_from_url(url: &str) -> Option<String> {
+    let path = url.split(|c| c == '?' || c == '#').next().unwrap();
+    let file_name = path.rsplit('/').next().unwrap();
+    let package = if file_name.ends_with(".apk") {
+        &file_name[..file_name.len() - 4]
+    } else {
+        file_name
+    };
+    if is_valid_package(package) {
+        Some(String::from(package))
+    } else {
+        None
+    }
+}
+
+/// Checks that the package can be safely used as a file name
+fn is_valid_package(package: &str) -> bool {
+    !package.is_empty() && !package.starts_with('.') &&
+    package.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '_' || c == '-')
+}
+
+/// Record of a downloaded application
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadRecord {
+    package: String,
+    url: String,
+    size: u64,
+    sha256: String,
+    date: String,
+}
+
+impl DownloadRecord {
+    pub fn get_package(&self) -> &str {
+        self.package.as_str()
+    }
+
+    pub fn get_url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_sha256(&self) -> &str {
+        self.sha256.as_str()
+    }
+
+    fn from_json(package: &str, value: &Value) -> Result<DownloadRecord> {
+        let record = match value.as_object() {
+            Some(r) => r,
+            None => return Err(Error::ParseError),
+        };
+        let get_str = |key: &str| match record.get(key) {
+            Some(&Value::String(ref s)) => Ok(s.clone()),
+            _ => Err(Error::ParseError),
+        };
+        Ok(DownloadRecord {
+            package: String::from(package),
+            url: try!(get_str("url")),
+            size: match record.get("size") {
+                Some(&Value::U64(s)) => s,
+                _ => return Err(Error::ParseError),
+            },
+            sha256: try!(get_str("sha256")),
+            date: try!(get_str("date")),
+        })
+    }
+}
+
+impl Serialize for DownloadRecord {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("DownloadRecord", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "url", self.url.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "size", self.size));
+        try!(serializer.serialize_struct_elt(&mut state, "sha256", self.sha256.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "date", self.date.as_str()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Manifest of the applications downloaded to the downloads folder
+///
+/// It's stored as a JSON object in the `manifest.json` file of the downloads folder, with the
+/// package of each application as the key.
+#[derive(Debug, Default)]
+pub struct DownloadManifest {
+    records: BTreeMap<String, DownloadRecord>,
+}
+
+impl DownloadManifest {
+    /// Loads the manifest of the given downloads folder, or creates an empty one
+    pub fn load<P: AsRef<Path>>(downloads_folder: P) -> Result<DownloadManifest> {
+        let path = downloads_folder.as_ref().join(MANIFEST_FILE);
+        if !file_exists(&path) {
+            return Ok(Default::default());
+        }
+        let mut f = try!(File::open(path));
+        let mut json = String::new();
+        try!(f.read_to_string(&mut json));
+        DownloadManifest::from_json(json.as_str())
+    }
+
+    fn from_json(json: &str) -> Result<DownloadManifest> {
+        let value: Value = try!(serde_json::from_str(json));
+        let mut manifest: DownloadManifest = Default::default();
+        match value {
+            Value::Object(records) => {
+                for (package, record) in records {
+                    let record = try!(DownloadRecord::from_json(package.as_str(), &record));
+                    manifest.records.insert(package, record);
+                }
+            }
+            _ => return Err(Error::ParseError),
+        }
+        Ok(manifest)
+    }
+
+    fn to_json(&self) -> String {
+        let mut records = BTreeMap::new();
+        for (package, record) in &self.records {
+            records.insert(package.clone(), serde_json::value::to_value(record));
+        }
+        format!("{:?}", Value::Object(records))
+    }
+
+    /// Saves the manifest in the given downloads folder
+    pub fn save<P: AsRef<Path>>(&self, downloads_folder: P) -> Result<()> {
+        let mut f = try!(File::create(downloads_folder.as_ref().join(MANIFEST_FILE)));
+        try!(f.write_all(self.to_json().as_bytes()));
+        Ok(())
+    }
+
+    pub fn get(&self, package: &str) -> Option<&DownloadRecord> {
+        self.records.get(package)
+    }
+
+    pub fn insert(&mut self, record: DownloadRecord) {
+        self.records.insert(record.package.clone(), record);
+    }
+}
+
+/// Source from which missing applications are downloaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadSource {
+    /// The F-Droid main repository, with the suggested version of the package
+    FDroid,
+    /// The latest version in APKPure
+    ApkPure,
+    /// A URL template with a `{package}` placeholder, such as an internal artifact store
+    Template(String),
+}
+
+impl DownloadSource {
+    /// Creates the source from its name in the configuration
+    pub fn from_config(source: &str) -> DownloadSource {
+        match source {
+            "fdroid" => DownloadSource::FDroid,
+            "apkpure" => DownloadSource::ApkPure,
+            template => DownloadSource::Template(String::from(template)),
+        }
+    }
+
+    /// Gets the URL of the APK file of the given package in the source
+    pub fn get_url(&self, package: &str) -> Result<String> {
+        match *self {
+            DownloadSource::FDroid => {
+                let url = format!("{}/{}", FDROID_API_URL, package);
+                match try!(fetch_url(&url, RESOLVE_TIMEOUT)) {
+                    (200, body) => {
+                        match get_fdroid_version_code(&body) {
+                            Some(code) => {
+                                Ok(format!("{}/{}_{}.apk", FDROID_REPO_URL, package, code))
+                            }
+                            None => Err(Error::ParseError),
+                        }
+                    }
+                    _ => Err(Error::AppNotExists),
+                }
+            }
+            DownloadSource::ApkPure => Ok(APKPURE_URL.replace("{package}", package)),
+            DownloadSource::Template(ref template) => Ok(template.replace("{package}", package)),
+        }
+    }
+}
+
+/// Gets the suggested version code of a package from the response of the F-Droid API
+fn get_fdroid_version_code(json: &str) -> Option<u64> {
+    let value: Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return None,
+    };
+    value.as_object()
+        .and_then(|o| o.get("suggestedVersionCode"))
+        .and_then(|c| c.as_u64())
+}
+
+/// Downloads the application of the configuration from the first configured source that has it
+///
+/// It's used when the APK file is not in the downloads folder. The download is recorded in the
+/// downloads manifest, and the URL it was downloaded from is returned.
+pub fn download_from_sources(config: &Config) -> Result<String> {
+    let folder = PathBuf::from(config.get_downloads_folder());
+    if !file_exists(&folder) {
+        try!(fs::create_dir_all(&folder));
+    }
+    let package = config.get_app_id();
+    if !is_valid_package(package) {
+        return Err(Error::AppNotExists);
+    }
+    let rate_limit = config.get_downloads_config().get_rate_limit();
+
+    for source in config.get_downloads_config().get_sources() {
+        let url = match DownloadSource::from_config(source).get_url(package) {
+            Ok(u) => u,
+            Err(e) => {
+                print_warning(format!("{} was not found in the {} source: {}", package, source, e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if config.is_verbose() {
+            println!("Downloading {} from {}.", package, url);
+        }
+
+        match download(&DownloadJob::new(package, url.as_str()), &folder, rate_limit) {
+            Ok(record) => {
+                let mut manifest = try!(DownloadManifest::load(&folder));
+                manifest.insert(record);
+                try!(manifest.save(&folder));
+                return Ok(url);
+            }
+            Err(e) => {
+                print_warning(format!("{} could not be downloaded from {}: {}", package, url, e),
+                              config.is_verbose());
+            }
+        }
+    }
+    Err(Error::AppNotExists)
+}
+
+/// Summary of a bulk download
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadSummary {
+    pub downloaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Downloads all the given applications to the downloads folder
+///
+/// Downloads run concurrently, limited by the `[downloads]` configuration, and partial files
+/// are kept with a `.part` extension so that interrupted downloads can be resumed. Applications
+/// already recorded in the downloads manifest with the same URL and hash are skipped, unless
+/// the download is forced.
+pub fn download_all(jobs: Vec<DownloadJob>, config: &Config) -> Result<DownloadSummary> {
+    let folder = PathBuf::from(config.get_downloads_folder());
+    if !file_exists(&folder) {
+        try!(fs::create_dir_all(&folder));
+    }
+    let manifest = try!(DownloadManifest::load(&folder));
+
+    let mut summary: DownloadSummary = Default::default();
+    let mut pending = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        if !config.is_force() && is_downloaded(&job, &manifest, &folder) {
+            if config.is_verbose() {
+                println!("{} is already downloaded, skipping it.", job.get_package());
+            }
+            summary.skipped += 1;
+        } else {
+            pending.push(job);
+        }
+    }
+    // Jobs are popped from the end of the queue
+    pending.reverse();
+
+    let workers = ::std::cmp::min(config.get_downloads_config().get_concurrency() as usize,
+                                  pending.len());
+    let queue = Arc::new(Mutex::new(pending));
+    let manifest = Arc::new(Mutex::new(manifest));
+    let summary = Arc::new(Mutex::new(summary));
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let queue = queue.clone();
+            let manifest = manifest.clone();
+            let summary = summary.clone();
+            let folder = folder.clone();
+            let rate_limit = config.get_downloads_config().get_rate_limit().map(String::from);
+            let delay = Duration::from_millis(config.get_downloads_config().get_delay());
+            let verbose = config.is_verbose();
+            let quiet = config.is_quiet();
+
+            thread::spawn(move || {
+                loop {
+                    let job = match queue.lock().unwrap().pop() {
+                        Some(j) => j,
+                        None => break,
+                    };
+
+                    match download(&job, &folder, rate_limit.as_ref().map(|r| r.as_str())) {
+                        Ok(record) => {
+                            let mut manifest = manifest.lock().unwrap();
+                            manifest.insert(record);
+                            if let Err(e) = manifest.save(&folder) {
+                                print_warning(format!("The downloads manifest could not be \
+                                                       saved: {}",
+                                                      e),
+                                              verbose);
+                            }
+                            summary.lock().unwrap().downloaded += 1;
+                            if verbose {
+                                println!("{}",
+                                         format!("{} downloaded.", job.get_package()).green());
+                            } else if !quiet {
+                                println!("{} downloaded.", job.get_package());
+                            }
+                        }
+                        Err(e) => {
+                            print_warning(format!("{} could not be downloaded from {}: {}",
+                                                  job.get_package(),
+                                                  job.get_url(),
+                                                  e),
+                                          verbose);
+                            summary.lock().unwrap().failed += 1;
+                        }
+                    }
+
+                    thread::sleep(delay);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        if let Err(e) = handle.join() {
+            print_warning(format!("An error occurred when joining download threads: {:?}", e),
+                          config.is_verbose());
+        }
+    }
+
+    let summary = *summary.lock().unwrap();
+    Ok(summary)
+}
+
+/// Checks if the application was already downloaded from the same URL and was not modified
+fn is_downloaded(job: &DownloadJob, manifest: &DownloadManifest, folder: &Path) -> bool {
+    match manifest.get(job.get_package()) {
+        Some(record) if record.get_url() == job.get_url() => {
+            match get_sha256(folder.join(format!("{}.apk", job.get_package()))) {
+                Ok((_, hash)) => hash == record.get_sha256(),
+                Err(_) => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Downloads the application, resuming a previous partial download if there is one
+fn download(job: &DownloadJob, folder: &Path, rate_limit: Option<&str>) -> Result<DownloadRecord> {
+    let partial = folder.join(format!("{}.apk.part", job.get_package()));
+    let target = folder.join(format!("{}.apk", job.get_package()));
+
+    try!(download_file(job.get_url(), &partial, &target, rate_limit));
+    let (size, sha256) = try!(get_sha256(&target));
+    Ok(DownloadRecord {
+        package: String::from(job.get_package()),
+        url: String::from(job.get_url()),
+        size: size,
+        sha256: sha256,
+        date: Local::now().to_rfc3339(),
+    })
+}
+
+/// Downloads the given URL with `curl` to the partial file, resuming it if it exists, and moves
+/// it to the target file once finished
+///
+/// The URL of the partial file is kept next to it, in a `.url` file, so that a partial file of
+/// another URL is downloaded again instead of being resumed. The size of the downloaded file is
+/// checked against the one given by the server, and the partial file is removed if they differ,
+/// so that a corrupted file never reaches the target.
+pub fn download_file(url: &str,
+                     partial: &Path,
+                     target: &Path,
+                     rate_limit: Option<&str>)
+                     -> Result<()> {
+    let source = get_sibling_file(partial, "url");
+    let headers = get_sibling_file(partial, "headers");
+    if file_exists(partial) {
+        let mut partial_url = String::new();
+        let same_url = File::open(&source)
+            .and_then(|mut f| f.read_to_string(&mut partial_url))
+            .map(|_| partial_url == url)
+            .unwrap_or(false);
+        if !same_url {
+            try!(fs::remove_file(partial));
+        }
+    }
+    try!(File::create(&source).and_then(|mut f| f.write_all(url.as_bytes())));
+
+    let mut command = Command::new("curl");
+    command.arg("--fail")
+        .arg("--location")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--retry")
+        .arg("3")
+        .arg("--continue-at")
+        .arg("-")
+        .arg("--dump-header")
+        .arg(&headers)
+        .arg("--output")
+        .arg(partial);
+    if let Some(rate) = rate_limit {
+        command.arg("--limit-rate").arg(rate);
+    }
+    let output = try!(command.arg(url).output());
+    if !output.status.success() {
+        print_warning(format!("curl returned an error: {}",
+                              String::from_utf8_lossy(&output.stderr[..]).trim()),
+                      false);
+        return Err(Error::Unknown);
+    }
+
+    let mut response = String::new();
+    let expected_size = File::open(&headers)
+        .and_then(|mut f| f.read_to_string(&mut response))
+        .ok()
+        .and_then(|_| get_expected_size(&response));
+    let _ = fs::remove_file(&headers);
+    let _ = fs::remove_file(&source);
+    let size = try!(fs::metadata(partial)).len();
+    if let Some(expected_size) = expected_size {
+        if size != expected_size {
+            try!(fs::remove_file(partial));
+            return Err(Error::IOError(io::Error::new(io::ErrorKind::InvalidData,
+                                                     format!("the downloaded file has {} \
+                                                              bytes, but the server sent {}",
+                                                             size,
+                                                             expected_size))));
+        }
+    }
+
+    try!(fs::rename(partial, target));
+    Ok(())
+}
+
+/// Gets the path of a file next to the given one, with the given extension added to its name
+fn get_sibling_file(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// Gets the full size of the downloaded file from the headers written by `curl`, that contain
+/// the headers of each redirection before the ones of the final response
+///
+/// Resumed downloads get the full size from `Content-Range`, and new ones from
+/// `Content-Length`. `None` is returned if the server didn't send the size.
+fn get_expected_size(headers: &str) -> Option<u64> {
+    let response = headers.split("\r\n\r\n")
+        .flat_map(|r| r.split("\n\n"))
+        .filter(|r| !r.trim().is_empty())
+        .last();
+    let response = match response {
+        Some(r) => r,
+        None => return None,
+    };
+    let mut content_length = None;
+    for line in response.lines() {
+        let (name, value) = match line.find(':') {
+            Some(i) => (line[..i].trim().to_lowercase(), line[i + 1..].trim()),
+            None => continue,
+        };
+        if name == "content-range" {
+            return value.rsplit('/').next().and_then(|total| total.parse().ok());
+        } else if name == "content-length" {
+            content_length = value.parse().ok();
+        }
+    }
+    content_length
+}
+
+/// Requests the given URL, returning the HTTP status code and the body of the response
+pub fn fetch_url(url: &str, timeout: u32) -> Result<(u32, String)> {
+    request_url(url, &[], None, timeout)
+}
+
+/// Requests the given URL with the given headers, returning the HTTP status code and the body
+/// of the response
+///
+/// If a file is given, it's uploaded in a `file` form field. The headers are written to the
+/// standard input of `curl` as its configuration, so that API keys don't show in its command
+/// line.
+pub fn request_url(url: &str,
+                   headers: &[String],
+                   file: Option<&Path>,
+                   timeout: u32)
+                   -> Result<(u32, String)> {
+    let mut command = Command::new("curl");
+    command.arg("--silent")
+        .arg("--show-error")
+        .arg("--max-time")
+        .arg(timeout.to_string())
+        .arg("--write-out")
+        .arg("\n%{http_code}");
+    if let Some(file) = file {
+        command.arg("--form").arg(format!("file=@{}", file.display()));
+    }
+    let config = headers.iter()
+        .map(|h| get_config_line("header", h))
+        .collect::<String>();
+    run_curl(command.arg(url), &config)
+}
+
+/// Runs `curl` with the given configuration, written to its standard input, returning the HTTP
+/// status code and the body of the response
+fn run_curl(command: &mut Command, config: &str) -> Result<(u32, String)> {
+    let mut child = try!(command.arg("--config")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn());
+    if let Some(mut stdin) = child.stdin.take() {
+        try!(stdin.write_all(config.as_bytes()));
+    }
+    let output = try!(child.wait_with_output());
+    get_response(&output)
+}
+
+/// Gets a line of a `curl` configuration setting the option to the given value
+///
+/// The value is quoted, escaping the characters that `curl` unescapes in quoted values.
+fn get_config_line(option: &str, value: &str) -> String {
+    let mut line = format!("{} = \"", option);
+    for c in value.chars() {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '"' => line.push_str("\\\""),
+            '\t' => line.push_str("\\t"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\x0B' => line.push_str("\\v"),
+            c => line.push(c),
+        }
+    }
+    line.push_str("\"\n");
+    line
+}
+
+/// Posts the given JSON body to the URL, returning the HTTP status code and the body of the
+/// response
+///
+/// The body is written to the standard input of `curl`, so that it's not limited by the maximum
+/// length of the command line.
+pub fn post_json(url: &str, body: &str, timeout: u32) -> Result<(u32, String)> {
+    post_json_as(url, body, None, timeout)
+}
+
+/// Posts the given JSON body to the URL with HTTP basic authentication, if credentials are
+/// given, returning the HTTP status code and the body of the response
+///
+/// The credentials are written to the standard input of `curl` with the body, so that they don't
+/// show in its command line.
+pub fn post_json_as(url: &str,
+                    body: &str,
+                    credentials: Option<(&str, &str)>,
+                    timeout: u32)
+                    -> Result<(u32, String)> {
+    let mut command = Command::new("curl");
+    command.arg("--silent")
+        .arg("--show-error")
+        .arg("--max-time")
+        .arg(timeout.to_string())
+        .arg("--write-out")
+        .arg("\n%{http_code}")
+        .arg("--header")
+        .arg("Content-Type: application/json");
+    let mut config = String::new();
+    if let Some((user, password)) = credentials {
+        config.push_str(&get_config_line("user", &format!("{}:{}", user, password)));
+    }
+    config.push_str(&get_config_line("data-binary", body));
+    run_curl(command.arg(url), &config)
+}
+
+/// Gets the HTTP status code and the body of the response from the output of `curl`, written
+/// with `--write-out "\n%{http_code}"`
+fn get_response(output: &Output) -> Result<(u32, String)> {
+    if !output.status.success() {
+        print_warning(format!("curl returned an error: {}",
+                              String::from_utf8_lossy(&output.stderr[..]).trim()),
+                      false);
+        return Err(Error::Unknown);
+    }
+
+    let response = String::from_utf8_lossy(&output.stdout[..]).into_owned();
+    let (body, status) = match response.rfind('\n') {
+        Some(i) => (&response[..i], &response[i + 1..]),
+        None => ("", response.as_str()),
+    };
+    match status.trim().parse() {
+        Ok(s) => Ok((s, String::from(body))),
+        Err(_) => Err(Error::ParseError),
+    }
+}
+
+/// Gets the size and the SHA-256 hash of the given file
+pub fn get_sha256<P: AsRef<Path>>(path: P) -> Result<(u64, String)> {
+    let mut f = try!(File::open(path));
+    let mut sha256 = Sha256::new();
+    let mut buffer = [0u8; 8192];
+    let mut size = 0;
+    loop {
+        let read = try!(f.read(&mut buffer));
+        if read == 0 {
+            break;
+        }
+        sha256.input(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((size, sha256.result_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{DownloadJob, DownloadManifest, DownloadRecord, DownloadSource,
+                parse_download_list, get_package_from_url, get_fdroid_version_code,
+                get_sibling_file, get_expected_size, get_config_line};
+
+    #[test]
+    fn it_parse_download_list() {
+        let list = "# Nightly fetch\n\
+                    com.example.app https://example.com/download?id=42\n\
+                    \n\
+                    https://f-droid.org/repo/org.fdroid.fdroid_1008000.apk\n";
+        let jobs = parse_download_list(list).unwrap();
+        assert_eq!(jobs,
+                   vec![DownloadJob::new("com.example.app", "https://example.com/download?id=42"),
+                        DownloadJob::new("org.fdroid.fdroid_1008000",
+                                         "https://f-droid.org/repo/\
+                                          org.fdroid.fdroid_1008000.apk")]);
+
+        assert!(parse_download_list("com.example.app https://example.com/a.apk extra").is_err());
+        assert!(parse_download_list("../evil https://example.com/a.apk").is_err());
+        assert!(parse_download_list("https://example.com/").is_err());
+    }
+
+    #[test]
+    fn it_get_package_from_url() {
+        assert_eq!(get_package_from_url("https://example.com/apps/com.example.apk?dl=1"),
+                   Some(String::from("com.example")));
+        assert_eq!(get_package_from_url("https://example.com/com.example"),
+                   Some(String::from("com.example")));
+        assert_eq!(get_package_from_url("https://example.com/a%20b.apk"), None);
+    }
+
+    #[test]
+    fn it_download_manifest() {
+        let mut manifest: DownloadManifest = Default::default();
+        manifest.insert(DownloadRecord {
+            package: String::from("com.example"),
+            url: String::from("https://example.com/com.example.apk"),
+            size: 1024,
+            sha256: String::from("e3b0c44298fc1c149afbf4c8996fb924\
+                                  27ae41e4649b934ca495991b7852b855"),
+            date: String::from("2016-12-01T10:00:00+01:00"),
+        });
+
+        let loaded = DownloadManifest::from_json(manifest.to_json().as_str()).unwrap();
+        let record = loaded.get("com.example").unwrap();
+        assert_eq!(record, manifest.get("com.example").unwrap());
+        assert_eq!(record.get_size(), 1024);
+        assert!(loaded.get("com.other").is_none());
+
+        assert!(DownloadManifest::from_json("[]").is_err());
+        assert!(DownloadManifest::from_json("{\"com.example\": {\"url\": 3}}").is_err());
+    }
+
+    #[test]
+    fn it_download_source() {
+        assert_eq!(DownloadSource::from_config("fdroid"), DownloadSource::FDroid);
+        assert_eq!(DownloadSource::from_config("apkpure"), DownloadSource::ApkPure);
+        let source = DownloadSource::from_config("https://store.example.com/{package}/latest.apk");
+        assert_eq!(source.get_url("com.example").unwrap(),
+                   "https://store.example.com/com.example/latest.apk");
+        assert_eq!(DownloadSource::ApkPure.get_url("com.example").unwrap(),
+                   "https://d.apkpure.com/b/APK/com.example?version=latest");
+
+        assert_eq!(get_fdroid_version_code("{\"packageName\": \"org.fdroid.fdroid\", \
+                                            \"suggestedVersionCode\": 1008000, \
+                                            \"packages\": []}"),
+                   Some(1008000));
+        assert_eq!(get_fdroid_version_code("{\"packageName\": \"org.fdroid.fdroid\"}"), None);
+    }
+
+    #[test]
+    fn it_get_expected_size() {
+        assert_eq!(get_sibling_file(Path::new("downloads/com.example.apk.part"), "url"),
+                   Path::new("downloads/com.example.apk.part.url"));
+
+        let redirected = "HTTP/1.1 302 Found\r\nLocation: https://cdn.example.com/a.apk\r\n\
+                          Content-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\
+                          Content-Length: 1024\r\n\r\n";
+        assert_eq!(get_expected_size(redirected), Some(1024));
+        let resumed = "HTTP/1.1 206 Partial Content\r\nContent-Length: 24\r\n\
+                       content-range: bytes 1000-1023/1024\r\n\r\n";
+        assert_eq!(get_expected_size(resumed), Some(1024));
+        assert_eq!(get_expected_size("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"),
+                   None);
+        assert_eq!(get_expected_size(""), None);
+    }
+
+    #[test]
+    fn it_get_config_line() {
+        assert_eq!(get_config_line("header", "x-apikey: 0123abcd"),
+                   "header = \"x-apikey: 0123abcd\"\n");
+        assert_eq!(get_config_line("user", "admin:p\"a\\ss\tword\n"),
+                   "user = \"admin:p\\\"a\\\\ss\\tword\\n\"\n");
+    }
+}
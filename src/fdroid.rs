@@ -0,0 +1,415 @@
+use std::{fs, result};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+
+use {Config, Result, Error, file_exists, csv_escape};
+use download::{DownloadJob, fetch_url};
+use results::{Results, find_report_folder};
+
+/// Names of the aggregate summary files of a repository scan, in the results folder
+const SUMMARY_CSV: &'static str = "fdroid_summary.csv";
+const SUMMARY_JSON: &'static str = "fdroid_summary.json";
+const SUMMARY_HTML: &'static str = "fdroid_summary.html";
+
+/// Maximum time in seconds for the download of the repository index
+const INDEX_TIMEOUT: u32 = 300;
+
+/// Keys of the findings of each criticity in the JSON report
+const CRITICITY_KEYS: [&'static str; 5] = ["warnings", "low", "medium", "high", "critical"];
+
+/// Application published in an F-Droid repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FDroidApp {
+    package: String,
+    version_name: String,
+    version_code: u64,
+    apk_name: String,
+}
+
+impl FDroidApp {
+    pub fn get_package(&self) -> &str {
+        self.package.as_str()
+    }
+
+    pub fn get_version_name(&self) -> &str {
+        self.version_name.as_str()
+    }
+
+    /// Gets the download job of the APK file of the application in the given repository
+    pub fn get_download_job(&self, repo_url: &str) -> DownloadJob {
+        DownloadJob::new(self.package.clone(),
+                         format!("{}/{}", repo_url.trim_right_matches('/'), self.apk_name))
+    }
+}
+
+/// Gets the base URL of the repository of the given index URL
+///
+/// Indexes are published in the repository folder, as in
+/// `https://f-droid.org/repo/index-v1.json`.
+pub fn get_repo_url(index_url: &str) -> &str {
+    if index_url.ends_with(".json") {
+        match index_url.rfind('/') {
+            Some(i) => &index_url[..i],
+            None => index_url,
+        }
+    } else {
+        index_url.trim_right_matches('/')
+    }
+}
+
+/// Downloads and parses the F-Droid repository index of the given URL
+pub fn fetch_index(index_url: &str) -> Result<Vec<FDroidApp>> {
+    match try!(fetch_url(index_url, INDEX_TIMEOUT)) {
+        (200, body) => parse_index(&body),
+        _ => Err(Error::Unknown),
+    }
+}
+
+/// Parses the applications of an F-Droid `index-v1.json` repository index
+///
+/// For each application, the suggested version is selected, or the latest one if the index
+/// doesn't suggest any.
+pub fn parse_index(json: &str) -> Result<Vec<FDroidApp>> {
+    let index: Value = try!(serde_json::from_str(json));
+    let (apps, packages) = match (index.as_object().and_then(|i| i.get("apps")),
+                                  index.as_object().and_then(|i| i.get("packages"))) {
+        (Some(&Value::Array(ref apps)), Some(&Value::Object(ref packages))) => (apps, packages),
+        _ => return Err(Error::ParseError),
+    };
+
+    let mut result = Vec::with_capacity(apps.len());
+    for app in apps {
+        let app = match app.as_object() {
+            Some(a) => a,
+            None => return Err(Error::ParseError),
+        };
+        let package = match app.get("packageName").and_then(|p| p.as_str()) {
+            Some(p) => p,
+            None => return Err(Error::ParseError),
+        };
+        let suggested = match app.get("suggestedVersionCode") {
+            Some(&Value::String(ref c)) => c.parse().ok(),
+            Some(c) => c.as_u64(),
+            None => None,
+        };
+
+        let versions = match packages.get(package).and_then(|v| v.as_array()) {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut selected: Option<FDroidApp> = None;
+        for version in versions {
+            let version = match version.as_object() {
+                Some(v) => v,
+                None => return Err(Error::ParseError),
+            };
+            let (apk_name, version_code) = match (version.get("apkName")
+                                                      .and_then(|n| n.as_str()),
+                                                  version.get("versionCode")
+                                                      .and_then(|c| c.as_u64())) {
+                (Some(n), Some(c)) => (n, c),
+                _ => return Err(Error::ParseError),
+            };
+            let is_better = match (suggested, selected.as_ref()) {
+                (Some(s), _) => s == version_code,
+                (None, Some(current)) => version_code > current.version_code,
+                (None, None) => true,
+            };
+            if is_better {
+                selected = Some(FDroidApp {
+                    package: String::from(package),
+                    version_name: String::from(version.get("versionName")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or("")),
+                    version_code: version_code,
+                    apk_name: String::from(apk_name),
+                });
+            }
+        }
+        if let Some(app) = selected {
+            result.push(app);
+        }
+    }
+    result.sort_by(|a, b| a.package.cmp(&b.package));
+    Ok(result)
+}
+
+/// Summary of the analysis of an application of a repository scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppSummary {
+    package: String,
+    version: String,
+    status: &'static str,
+    /// Number of findings of each criticity, from warnings to critical
+    counts: [usize; 5],
+    label: Option<String>,
+    /// Name of the copy of the launcher icon in the report folder
+    icon: Option<String>,
+    /// Path of the report folder, relative to the results folder
+    report: Option<String>,
+}
+
+impl AppSummary {
+    /// Creates the summary of an application that could not be analyzed
+    pub fn failed(app: &FDroidApp, status: &'static str) -> AppSummary {
+        AppSummary {
+            package: app.package.clone(),
+            version: app.version_name.clone(),
+            status: status,
+            counts: [0; 5],
+            label: None,
+            icon: None,
+            report: None,
+        }
+    }
+
+    /// Creates the summary of an analyzed application from its JSON report
+    pub fn from_results(app: &FDroidApp, config: &Config) -> AppSummary {
+        let folder = find_report_folder(config.get_results_folder(), app.get_package());
+        let mut json = String::new();
+        if File::open(folder.join("results.json"))
+            .and_then(|mut f| f.read_to_string(&mut json))
+            .is_err() {
+            return AppSummary::failed(app, "No report");
+        }
+        match AppSummary::from_json(app, &json) {
+            Some(mut summary) => {
+                summary.report = folder.strip_prefix(config.get_results_folder())
+                    .ok()
+                    .map(|p| p.to_string_lossy().replace('\\', "/"));
+                summary
+            }
+            None => AppSummary::failed(app, "Invalid report"),
+        }
+    }
+
+    fn from_json(app: &FDroidApp, json: &str) -> Option<AppSummary> {
+        let report: Value = match serde_json::from_str(json) {
+            Ok(r) => r,
+            Err(_) => return None,
+        };
+        let report = match report.as_object() {
+            Some(r) => r,
+            None => return None,
+        };
+        let mut counts = [0; 5];
+        for (count, key) in counts.iter_mut().zip(CRITICITY_KEYS.iter()) {
+            *count = match report.get(*key).and_then(|f| f.as_array()) {
+                Some(findings) => findings.len(),
+                None => return None,
+            };
+        }
+        Some(AppSummary {
+            package: app.package.clone(),
+            version: app.version_name.clone(),
+            status: "Analyzed",
+            counts: counts,
+            label: report.get("label").and_then(|l| l.as_str()).map(String::from),
+            icon: report.get("apk")
+                .and_then(|a| a.lookup("icon"))
+                .and_then(|i| i.as_str())
+                .map(String::from),
+            report: None,
+        })
+    }
+
+    pub fn is_analyzed(&self) -> bool {
+        self.status == "Analyzed"
+    }
+}
+
+impl Serialize for AppSummary {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("AppSummary", 11));
+        try!(serializer.serialize_struct_elt(&mut state, "package", self.package.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.version.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "status", self.status));
+        for (key, count) in CRITICITY_KEYS.iter().zip(self.counts.iter()) {
+            try!(serializer.serialize_struct_elt(&mut state, *key, *count));
+        }
+        try!(serializer.serialize_struct_elt(&mut state, "label", &self.label));
+        try!(serializer.serialize_struct_elt(&mut state, "icon", &self.icon));
+        try!(serializer.serialize_struct_elt(&mut state, "report", &self.report));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Generates the CSV summary of a repository scan
+fn get_summary_csv(summaries: &[AppSummary]) -> String {
+    let mut csv = format!("package,version,status,{}\n", CRITICITY_KEYS.join(","));
+    for summary in summaries {
+        csv.push_str(&format!("{},{},{}",
+                              csv_escape(&summary.package),
+                              csv_escape(&summary.version),
+                              summary.status));
+        for count in &summary.counts {
+            csv.push_str(&format!(",{}", count));
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Escapes text for HTML attributes, as well as for element contents
+fn attribute_escape(text: &str) -> String {
+    Results::html_escape(text).replace('"', "&quot;")
+}
+
+/// Generates the HTML index of a repository scan, with the launcher icon and label of each
+/// application and a link to its report
+fn get_summary_html(summaries: &[AppSummary]) -> String {
+    let mut html = String::from("<!DOCTYPE html><html lang=\"en\"><head>\
+                                 <title>F-Droid repository scan</title>\
+                                 <meta charset=\"UTF-8\"></head><body>\
+                                 <h1>F-Droid repository scan</h1><table><thead><tr>\
+                                 <th>Application</th><th>Package</th><th>Version</th>\
+                                 <th>Status</th>");
+    for key in &CRITICITY_KEYS {
+        html.push_str(&format!("<th>{}</th>", key));
+    }
+    html.push_str("</tr></thead><tbody>");
+    for summary in summaries {
+        let label = attribute_escape(summary.label.as_ref().unwrap_or(&summary.package));
+        html.push_str("<tr><td>");
+        if let Some(ref report) = summary.report {
+            if let Some(ref icon) = summary.icon {
+                html.push_str(&format!("<img src=\"{}/{}\" alt=\"{}\" width=\"32\" \
+                                        height=\"32\"> ",
+                                       attribute_escape(report),
+                                       attribute_escape(icon),
+                                       label));
+            }
+            html.push_str(&format!("<a href=\"{}/index.html\">{}</a>",
+                                   attribute_escape(report),
+                                   label));
+        } else {
+            html.push_str(&label);
+        }
+        html.push_str(&format!("</td><td>{}</td><td>{}</td><td>{}</td>",
+                               attribute_escape(&summary.package),
+                               attribute_escape(&summary.version),
+                               summary.status));
+        for count in &summary.counts {
+            html.push_str(&format!("<td>{}</td>", count));
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</tbody></table></body></html>");
+    html
+}
+
+/// Writes the aggregate CSV, JSON and HTML summaries of a repository scan in the results folder
+pub fn write_summaries(summaries: &[AppSummary], config: &Config) -> Result<()> {
+    let folder = Path::new(config.get_results_folder());
+    if !file_exists(folder) {
+        try!(fs::create_dir_all(folder));
+    }
+    let mut f = try!(File::create(folder.join(SUMMARY_CSV)));
+    try!(f.write_all(get_summary_csv(summaries).as_bytes()));
+
+    let mut f = try!(File::create(folder.join(SUMMARY_JSON)));
+    try!(f.write_all(format!("{:?}", serde_json::value::to_value(&summaries)).as_bytes()));
+
+    let mut f = try!(File::create(folder.join(SUMMARY_HTML)));
+    try!(f.write_all(get_summary_html(summaries).as_bytes()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppSummary, FDroidApp, get_repo_url, parse_index, get_summary_csv,
+                get_summary_html};
+
+    #[test]
+    fn it_parse_index() {
+        let json = r#"{"repo": {"name": "F-Droid"},
+            "apps": [{"packageName": "org.example.notes", "suggestedVersionCode": "20"},
+                     {"packageName": "org.example.maps"},
+                     {"packageName": "org.example.removed"}],
+            "packages": {
+                "org.example.notes": [
+                    {"apkName": "org.example.notes_30.apk", "versionCode": 30,
+                     "versionName": "3.0-beta"},
+                    {"apkName": "org.example.notes_20.apk", "versionCode": 20,
+                     "versionName": "2.0"}],
+                "org.example.maps": [
+                    {"apkName": "org.example.maps_7.apk", "versionCode": 7,
+                     "versionName": "1.7"},
+                    {"apkName": "org.example.maps_8.apk", "versionCode": 8,
+                     "versionName": "1.8"}]}}"#;
+        let apps = parse_index(json).unwrap();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].get_package(), "org.example.maps");
+        assert_eq!(apps[0].get_version_name(), "1.8");
+        assert_eq!(apps[1].get_package(), "org.example.notes");
+        assert_eq!(apps[1].get_version_name(), "2.0");
+
+        let job = apps[1].get_download_job(get_repo_url("https://f-droid.org/repo/index-v1.json"));
+        assert_eq!(job.get_package(), "org.example.notes");
+        assert_eq!(job.get_url(), "https://f-droid.org/repo/org.example.notes_20.apk");
+
+        assert!(parse_index("{\"apps\": []}").is_err());
+        assert!(parse_index("not json").is_err());
+    }
+
+    #[test]
+    fn it_get_repo_url() {
+        assert_eq!(get_repo_url("https://f-droid.org/repo/index-v1.json"),
+                   "https://f-droid.org/repo");
+        assert_eq!(get_repo_url("https://example.com/fdroid/repo/"),
+                   "https://example.com/fdroid/repo");
+    }
+
+    #[test]
+    fn it_get_summary_csv() {
+        let app = FDroidApp {
+            package: String::from("org.example.notes"),
+            version_name: String::from("2.0, final"),
+            version_code: 20,
+            apk_name: String::from("org.example.notes_20.apk"),
+        };
+        let report = r#"{"warnings": [{}], "low": [], "medium": [{}, {}], "high": [],
+                         "critical": [{}]}"#;
+        let summary = AppSummary::from_json(&app, report).unwrap();
+        assert!(summary.is_analyzed());
+        let failed = AppSummary::failed(&app, "Download failed");
+        assert!(!failed.is_analyzed());
+        assert!(AppSummary::from_json(&app, "{\"low\": []}").is_none());
+
+        assert_eq!(get_summary_csv(&[summary, failed]),
+                   "package,version,status,warnings,low,medium,high,critical\n\
+                    org.example.notes,\"2.0, final\",Analyzed,1,0,2,0,1\n\
+                    org.example.notes,\"2.0, final\",Download failed,0,0,0,0,0\n");
+    }
+
+    #[test]
+    fn it_get_summary_html() {
+        let app = FDroidApp {
+            package: String::from("org.example.notes"),
+            version_name: String::from("2.0"),
+            version_code: 20,
+            apk_name: String::from("org.example.notes_20.apk"),
+        };
+        let report = r#"{"label": "Notes & Lists", "apk": {"icon": "icon.png"}, "warnings": [],
+                         "low": [], "medium": [], "high": [], "critical": []}"#;
+        let mut summary = AppSummary::from_json(&app, report).unwrap();
+        summary.report = Some(String::from("org.example.notes/latest"));
+        let failed = AppSummary::failed(&app, "Download failed");
+
+        let html = get_summary_html(&[summary, failed]);
+        assert!(html.contains("<img src=\"org.example.notes/latest/icon.png\" \
+                               alt=\"Notes &amp; Lists\""));
+        assert!(html.contains("<a href=\"org.example.notes/latest/index.html\">Notes &amp; \
+                               Lists</a>"));
+        assert!(html.contains("<tr><td>org.example.notes</td><td>org.example.notes</td>\
+                               <td>2.0</td><td>Download failed</td>"));
+    }
+}
@@ -0,0 +1,540 @@
+use std::{env, fs, io, result, thread};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio, ExitStatus};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+use chrono::Local;
+
+use {Config, Result, Error, print_warning, file_exists, kill_process_tree};
+use results::find_report_folder;
+
+/// File of the results folder where the state of the jobs is stored
+const JOBS_FILE: &'static str = "jobs.json";
+
+/// Folder of the dist and results folders where each job gets its own subfolder
+const JOBS_FOLDER: &'static str = "jobs";
+
+/// Interval between the checks of the running analyses, in milliseconds
+const POLL_INTERVAL: u64 = 500;
+
+/// Number of polls between the checks of the disk usage of a job
+const DISK_CHECK_POLLS: u32 = 20;
+
+/// Reason why an application could not be submitted to the queue
+#[derive(Debug)]
+pub enum SubmitError {
+    /// The package is already queued or running
+    Pending(String),
+    /// The application file could not be moved to the downloads folder
+    File(io::Error),
+}
+
+/// Status of a job of the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    fn from_str(status: &str) -> Option<JobStatus> {
+        match status {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            _ => None,
+        }
+    }
+
+    /// Checks if the job is waiting or running
+    pub fn is_pending(&self) -> bool {
+        *self == JobStatus::Queued || *self == JobStatus::Running
+    }
+}
+
+/// Analysis of an application in the job queue
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    id: u64,
+    package: String,
+    status: JobStatus,
+    submitted: String,
+    started: Option<String>,
+    finished: Option<String>,
+    error: Option<String>,
+}
+
+impl Job {
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_package(&self) -> &str {
+        self.package.as_str()
+    }
+
+    pub fn get_status(&self) -> JobStatus {
+        self.status
+    }
+
+    pub fn get_submitted(&self) -> &str {
+        self.submitted.as_str()
+    }
+
+    pub fn get_started(&self) -> Option<&str> {
+        self.started.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn get_finished(&self) -> Option<&str> {
+        self.finished.as_ref().map(|f| f.as_str())
+    }
+
+    /// Gets the reason why the job failed, if it did
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_ref().map(|e| e.as_str())
+    }
+
+    /// Gets the job from its JSON representation in the jobs file
+    fn from_json(json: &Value) -> Result<Job> {
+        let get_string = |key: &str| json.lookup(key).and_then(|v| v.as_str()).map(String::from);
+        let id = json.lookup("id").and_then(|i| i.as_u64());
+        let status = json.lookup("status").and_then(|s| s.as_str()).and_then(JobStatus::from_str);
+        match (id, get_string("package"), status, get_string("submitted")) {
+            (Some(id), Some(package), Some(status), Some(submitted)) => {
+                Ok(Job {
+                    id: id,
+                    package: package,
+                    status: status,
+                    submitted: submitted,
+                    started: get_string("started"),
+                    finished: get_string("finished"),
+                    error: get_string("error"),
+                })
+            }
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+impl Serialize for Job {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Job", 7));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.id));
+        try!(serializer.serialize_struct_elt(&mut state, "package", self.package.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "status", self.status.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "submitted", self.submitted.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "started", &self.started));
+        try!(serializer.serialize_struct_elt(&mut state, "finished", &self.finished));
+        try!(serializer.serialize_struct_elt(&mut state, "error", &self.error));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Queue of analyses, run concurrently by as many workers as configured threads
+///
+/// Each job runs in a new process of the analyzer, with its own dist and results folders, so
+/// that concurrent analyses don't share files and a failing analysis doesn't stop the rest. The
+/// state of the jobs is stored in the `jobs.json` file of the results folder after each change.
+pub struct JobQueue {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    sender: Mutex<Sender<u64>>,
+    uploads: AtomicUsize,
+    analyzer_args: Vec<String>,
+    downloads_folder: PathBuf,
+    dist_folder: PathBuf,
+    results_folder: PathBuf,
+    timeout: u64,
+    max_disk: u64,
+    verbose: bool,
+    quiet: bool,
+}
+
+impl JobQueue {
+    /// Creates the queue and starts its workers
+    ///
+    /// The analyzer of each job gets the given command line arguments, so that the options given
+    /// to the server, such as its profile or tools, apply to its analyses. Finished jobs of
+    /// previous runs are kept, and jobs that were pending when the previous run stopped are
+    /// marked as failed.
+    pub fn start(config: &Config, analyzer_args: Vec<String>) -> Result<Arc<JobQueue>> {
+        let mut jobs = BTreeMap::new();
+        for mut job in try!(load_jobs(config)) {
+            if job.status.is_pending() {
+                job.status = JobStatus::Failed;
+                job.error = Some(String::from("The queue stopped before the job finished."));
+            }
+            let _ = jobs.insert(job.id, job);
+        }
+
+        let (sender, receiver) = channel();
+        let queue = Arc::new(JobQueue {
+            jobs: Mutex::new(jobs),
+            sender: Mutex::new(sender),
+            uploads: AtomicUsize::new(0),
+            analyzer_args: analyzer_args,
+            downloads_folder: PathBuf::from(config.get_downloads_folder()),
+            dist_folder: Path::new(config.get_dist_folder()).join(JOBS_FOLDER),
+            results_folder: PathBuf::from(config.get_results_folder()),
+            timeout: config.get_jobs_config().get_timeout(),
+            max_disk: config.get_jobs_config().get_max_disk() * 1024 * 1024,
+            verbose: config.is_verbose(),
+            quiet: config.is_quiet(),
+        });
+        for folder in &[&queue.downloads_folder, &queue.dist_folder, &queue.results_folder] {
+            if !file_exists(folder) {
+                try!(fs::create_dir_all(folder));
+            }
+        }
+        try!(queue.save(&queue.jobs.lock().unwrap()));
+
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..config.get_threads() {
+            let queue = queue.clone();
+            let receiver = receiver.clone();
+            let _ = thread::spawn(move || queue.run_worker(&receiver));
+        }
+        Ok(queue)
+    }
+
+    /// Gets the folder where the downloaded applications are analyzed from
+    pub fn get_downloads_folder(&self) -> &Path {
+        &self.downloads_folder
+    }
+
+    /// Gets a new temporary file of the downloads folder to store an application of the package
+    ///
+    /// Each call gets a different file, so that concurrent submissions of the same package don't
+    /// write to the same file.
+    pub fn get_temp_file(&self, package: &str) -> PathBuf {
+        let n = self.uploads.fetch_add(1, Ordering::SeqCst);
+        self.downloads_folder.join(format!("{}.apk.{}.part", package, n))
+    }
+
+    /// Checks if an analysis of the package is queued or running
+    ///
+    /// The application file of the package must not be replaced while it is.
+    pub fn is_pending(&self, package: &str) -> bool {
+        self.jobs.lock().unwrap().values().any(|j| j.package == package && j.status.is_pending())
+    }
+
+    /// Queues the analysis of the package, if it's not already queued or running
+    ///
+    /// The given application file is moved to the downloads folder while the queue is locked, so
+    /// that the file of a pending analysis is never replaced. It's removed if the application
+    /// can't be submitted.
+    pub fn submit(&self, package: &str, file: &Path) -> result::Result<Job, SubmitError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.values().any(|j| j.package == package && j.status.is_pending()) {
+            let _ = fs::remove_file(file);
+            return Err(SubmitError::Pending(format!("{} is already being analyzed.", package)));
+        }
+        let target = self.downloads_folder.join(format!("{}.apk", package));
+        if let Err(e) = fs::rename(file, &target) {
+            let _ = fs::remove_file(file);
+            return Err(SubmitError::File(e));
+        }
+        let job = Job {
+            id: jobs.keys().next_back().map_or(1, |id| id + 1),
+            package: String::from(package),
+            status: JobStatus::Queued,
+            submitted: Local::now().to_rfc3339(),
+            started: None,
+            finished: None,
+            error: None,
+        };
+        let _ = jobs.insert(job.id, job.clone());
+        if let Err(e) = self.save(&jobs) {
+            print_warning(format!("The jobs file could not be saved: {}", e), self.verbose);
+        }
+        let _ = self.sender.lock().unwrap().send(job.id);
+        Ok(job)
+    }
+
+    /// Gets a copy of the job with the given ID
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Gets a copy of all the jobs, ordered by ID
+    pub fn get_all(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Gets the folder with the report of the application analyzed by the job
+    pub fn get_results_folder(&self, job: &Job) -> PathBuf {
+        find_report_folder(self.results_folder.join(JOBS_FOLDER).join(job.id.to_string()),
+                           &job.package)
+    }
+
+    /// Updates the status of the job with the given ID, and stores the new state
+    fn set_status(&self, id: u64, status: JobStatus, error: Option<String>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&id) {
+            job.status = status;
+            job.error = error;
+            if status == JobStatus::Running {
+                job.started = Some(Local::now().to_rfc3339());
+            } else if !status.is_pending() {
+                job.finished = Some(Local::now().to_rfc3339());
+            }
+        }
+        if let Err(e) = self.save(&jobs) {
+            print_warning(format!("The jobs file could not be saved: {}", e), self.verbose);
+        }
+    }
+
+    /// Stores the state of the jobs in the jobs file
+    fn save(&self, jobs: &BTreeMap<u64, Job>) -> Result<()> {
+        let json = format!("{:?}",
+                           serde_json::value::to_value(&jobs.values().collect::<Vec<_>>()));
+        let path = self.results_folder.join(JOBS_FILE);
+        let temp = self.results_folder.join(format!("{}.tmp", JOBS_FILE));
+        let mut f = try!(File::create(&temp));
+        try!(f.write_all(json.as_bytes()));
+        try!(fs::rename(temp, path));
+        Ok(())
+    }
+
+    /// Runs the queued jobs, until the queue is dropped
+    fn run_worker(&self, receiver: &Mutex<Receiver<u64>>) {
+        loop {
+            let id = match receiver.lock().unwrap().recv() {
+                Ok(id) => id,
+                Err(_) => break,
+            };
+            let package = match self.get(id) {
+                Some(job) => job.package,
+                None => continue,
+            };
+            self.set_status(id, JobStatus::Running, None);
+            if !self.quiet {
+                println!("Analyzing {} (job {}).", package, id);
+            }
+            match self.run_job(id, &package) {
+                Ok(()) => self.set_status(id, JobStatus::Completed, None),
+                Err(e) => {
+                    print_warning(format!("The analysis of {} failed: {}", package, e),
+                                  self.verbose);
+                    self.set_status(id, JobStatus::Failed, Some(e));
+                }
+            }
+        }
+    }
+
+    /// Analyzes the package with a new process of the analyzer, in the isolated folders of the
+    /// job, killing it if it exceeds the timeout or the disk limit
+    ///
+    /// The error output of the analyzer is stored in the `job.log` file of the job results, and
+    /// the decompiled files are removed once the job finishes.
+    fn run_job(&self, id: u64, package: &str) -> result::Result<(), String> {
+        let dist_folder = self.dist_folder.join(id.to_string());
+        let results_folder = self.results_folder.join(JOBS_FOLDER).join(id.to_string());
+        for folder in &[&dist_folder, &results_folder] {
+            try!(fs::create_dir_all(folder).map_err(|e| format!("{}", e)));
+        }
+
+        let executable = try!(env::current_exe().map_err(|e| format!("{}", e)));
+        let mut child = try!(Command::new(executable)
+            .arg(package)
+            .arg("--force")
+            .arg("--quiet")
+            .arg("--dist")
+            .arg(&dist_folder)
+            .arg("--results")
+            .arg(&results_folder)
+            // Options with multiple values must go after the package, or it would be taken as
+            // one of them
+            .args(&self.analyzer_args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}", e)));
+        // The error output is read in its own thread, so that the analyzer never blocks on it
+        let mut stderr = child.stderr.take().unwrap();
+        let log = thread::spawn(move || {
+            let mut log = String::new();
+            let _ = stderr.read_to_string(&mut log);
+            log
+        });
+        // The analyzer is also waited for in its own thread, since the supported Rust versions
+        // can't check if a child process exited without blocking
+        let pid = child.id();
+        let (sender, receiver) = channel();
+        let _ = thread::spawn(move || sender.send(child.wait()));
+
+        let result = self.wait_for(pid, &receiver, &dist_folder, &results_folder);
+
+        let log = log.join().unwrap_or_else(|_| String::new());
+        if let Err(e) = File::create(results_folder.join("job.log"))
+            .and_then(|mut f| f.write_all(log.as_bytes())) {
+            print_warning(format!("The log of job {} could not be saved: {}", id, e),
+                          self.verbose);
+        }
+        if let Err(e) = fs::remove_dir_all(&dist_folder) {
+            print_warning(format!("The dist folder of job {} could not be removed: {}", id, e),
+                          self.verbose);
+        }
+
+        // The last line of the error output usually explains why the analysis failed
+        result.map_err(|e| match log.lines().rev().find(|l| !l.trim().is_empty()) {
+            Some(line) => format!("{} {}", e, line.trim()),
+            None => e,
+        })
+    }
+
+    /// Waits for the analyzer to exit, killing it and the tools it runs if it exceeds the limits
+    /// of the job
+    fn wait_for(&self,
+                pid: u32,
+                receiver: &Receiver<io::Result<ExitStatus>>,
+                dist_folder: &Path,
+                results_folder: &Path)
+                -> result::Result<(), String> {
+        let start = Instant::now();
+        let mut polls = 0;
+        // The analyzer is waited for after killing it, so that its folders are not removed while
+        // it's still writing to them
+        let kill = || {
+            kill_process_tree(pid);
+            let _ = receiver.recv();
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok(Ok(status)) => {
+                    return if status.success() {
+                        Ok(())
+                    } else {
+                        Err(format!("The analyzer exited with {}.", status))
+                    };
+                }
+                Ok(Err(e)) => return Err(format!("{}", e)),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(String::from("The analyzer could not be waited for."));
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+            if self.timeout > 0 && start.elapsed() > Duration::from_secs(self.timeout) {
+                kill();
+                return Err(format!("The analysis exceeded the timeout of {} seconds.",
+                                   self.timeout));
+            }
+            polls += 1;
+            if self.max_disk > 0 && polls % DISK_CHECK_POLLS == 0 &&
+               get_folder_size(dist_folder) + get_folder_size(results_folder) > self.max_disk {
+                kill();
+                return Err(format!("The analysis exceeded the disk limit of {} MB.",
+                                   self.max_disk / 1024 / 1024));
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL));
+        }
+    }
+}
+
+/// Loads the jobs stored in the jobs file of the results folder
+pub fn load_jobs(config: &Config) -> Result<Vec<Job>> {
+    let path = Path::new(config.get_results_folder()).join(JOBS_FILE);
+    if !file_exists(&path) {
+        return Ok(Vec::new());
+    }
+    let mut json = String::new();
+    let _ = try!(try!(File::open(path)).read_to_string(&mut json));
+    parse_jobs(&json)
+}
+
+/// Parses the jobs of a jobs file
+fn parse_jobs(json: &str) -> Result<Vec<Job>> {
+    let json: Value = try!(serde_json::from_str(json));
+    match json.as_array() {
+        Some(jobs) => jobs.iter().map(Job::from_json).collect(),
+        None => Err(Error::ParseError),
+    }
+}
+
+/// Gets the size of the files in the folder and its subfolders, in bytes
+///
+/// Files that can't be read, such as the ones removed while the folder is walked, are ignored.
+fn get_folder_size(folder: &Path) -> u64 {
+    let entries = match fs::read_dir(folder) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries.filter_map(|e| e.ok())
+        .map(|e| match e.metadata() {
+            Ok(ref m) if m.is_dir() => get_folder_size(&e.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+    use serde_json;
+    use super::{JobStatus, parse_jobs, get_folder_size};
+
+    #[test]
+    fn it_parse_jobs() {
+        let jobs = parse_jobs(r#"[
+            {"id": 1, "package": "com.example", "status": "completed",
+             "submitted": "2017-01-10T10:00:00+01:00", "started": "2017-01-10T10:00:01+01:00",
+             "finished": "2017-01-10T10:05:00+01:00", "error": null},
+            {"id": 2, "package": "com.example.other", "status": "queued",
+             "submitted": "2017-01-10T10:01:00+01:00", "started": null, "finished": null,
+             "error": null}]"#)
+            .unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].get_id(), 1);
+        assert_eq!(jobs[0].get_status(), JobStatus::Completed);
+        assert_eq!(jobs[0].get_finished(), Some("2017-01-10T10:05:00+01:00"));
+        assert_eq!(jobs[1].get_package(), "com.example.other");
+        assert!(jobs[1].get_status().is_pending());
+        assert_eq!(jobs[1].get_started(), None);
+
+        let json = format!("[{:?}]", serde_json::value::to_value(&jobs[0]));
+        assert_eq!(parse_jobs(&json).unwrap(), vec![jobs[0].clone()]);
+
+        assert!(parse_jobs(r#"[{"id": 3, "package": "com.example"}]"#).is_err());
+        assert!(parse_jobs(r#"{"jobs": []}"#).is_err());
+    }
+
+    #[test]
+    fn it_get_folder_size() {
+        let folder = Path::new("folder_size_test");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        File::create(folder.join("a.txt")).unwrap().write_all(&[0; 100]).unwrap();
+        File::create(folder.join("sub/b.txt")).unwrap().write_all(&[0; 50]).unwrap();
+
+        let size = get_folder_size(folder);
+        fs::remove_dir_all(folder).unwrap();
+        assert_eq!(size, 150);
+        assert_eq!(get_folder_size(Path::new("folder_size_test_missing")), 0);
+    }
+}
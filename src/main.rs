@@ -1,9 +1,11 @@
 #[macro_use]
 extern crate clap;
 extern crate colored;
-extern crate zip;
+extern crate flate2;
 extern crate xml;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
 extern crate yaml_rust;
 extern crate chrono;
@@ -11,59 +13,377 @@ extern crate toml;
 extern crate regex;
 extern crate crypto;
 extern crate rustc_serialize;
+extern crate handlebars;
 
 mod decompilation;
 mod static_analysis;
 mod results;
 mod config;
 mod utils;
+mod download;
+mod adb;
+mod fdroid;
+mod server;
+mod jobs;
+mod setup;
+mod tools;
+mod axml;
+mod arsc;
+mod dex;
+mod archive;
+mod workspace;
+mod rule_packs;
 
-use std::{fs, io, fmt, result};
-use std::path::Path;
+use std::{fs, io, fmt, result, u8};
+use std::path::{Path, PathBuf};
 use std::fmt::Display;
 use std::str::FromStr;
 use std::error::Error as StdError;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::process::exit;
 use std::time::{Instant, Duration};
 use std::thread::sleep;
 
 use serde::ser::{Serialize, Serializer};
 use serde_json::error::ErrorCode as JSONErrorCode;
-use clap::{Arg, App, ArgMatches};
+use clap::{Arg, App, ArgMatches, SubCommand, AppSettings};
+use handlebars::{TemplateError, RenderError};
 use colored::Colorize;
 
 use decompilation::*;
 use static_analysis::*;
+use static_analysis::code::validate_rules;
 use results::*;
-pub use config::Config;
+use download::*;
+use adb::*;
+use fdroid::*;
+use server::serve;
+use jobs::load_jobs;
+use setup::{DEFAULT_MIRROR, get_tools_folder, install_tools, write_user_config};
+use archive::ArchiveViolation;
+use workspace::{Workspace, get_package_name, clean_dist_folder, remove_old_artifacts};
+use rule_packs::{get_rules_folder, list_rule_packs, update_rule_pack};
+pub use config::{Config, ConfigBuilder};
 pub use utils::*;
 
 static BANNER: &'static str = include_str!("banner.txt");
+static SAMPLE_CONFIG: &'static str = include_str!("../config.toml.sample");
+
+/// Flags that the server passes to the analyses of its jobs
+const ANALYZER_FLAGS: [&'static str; 10] = ["historical",
+                                            "online-checks",
+                                            "malware-heuristics",
+                                            "virustotal",
+                                            "trend",
+                                            "gitlab-sast",
+                                            "github-annotations",
+                                            "reproducible",
+                                            "no-source",
+                                            "no-decompile"];
+
+/// Options that the server passes to the analyses of its jobs, so that they use its profile,
+/// tools and rules
+const ANALYZER_OPTIONS: [&'static str; 16] = ["profile",
+                                              "format",
+                                              "language",
+                                              "templates",
+                                              "timeout",
+                                              "tool-timeout",
+                                              "downloads",
+                                              "apktool",
+                                              "dex2jar",
+                                              "jd-cmd",
+                                              "rules",
+                                              "rules-extra",
+                                              "template",
+                                              "source-root",
+                                              "mapping",
+                                              "baseline"];
 
 fn main() {
     let matches = get_help_menu();
 
-    let app_id = matches.value_of("package").unwrap();
     let verbose = matches.is_present("verbose");
     let quiet = matches.is_present("quiet");
     let force = matches.is_present("force");
     let bench = matches.is_present("bench");
-    let config = match Config::new(app_id, verbose, quiet, force, bench) {
-        Ok(c) => c,
-        Err(e) => {
-            print_warning(format!("There was an error when reading the config.toml file: {}",
-                                  e),
-                          verbose);
-            let mut c: Config = Default::default();
-            c.set_app_id(app_id);
-            c.set_verbose(verbose);
-            c.set_quiet(quiet);
-            c.set_force(force);
-            c.set_bench(bench);
-            c
+    let profile_rules = matches.is_present("profile-rules");
+    let historical = matches.is_present("historical");
+    let online_checks = matches.is_present("online-checks");
+    let malware_heuristics = matches.is_present("malware-heuristics");
+    let virustotal = matches.is_present("virustotal");
+    let trend = matches.is_present("trend");
+    let gitlab_sast = matches.is_present("gitlab-sast");
+    let github_annotations = matches.is_present("github-annotations");
+    let reproducible = matches.is_present("reproducible");
+    let no_source = matches.is_present("no-source");
+    let no_decompile = matches.is_present("no-decompile");
+    let show_tool_output = matches.is_present("show-tool-output");
+
+    if let Some(sub_matches) = matches.subcommand_matches("migrate-results") {
+        migrate_results_command(sub_matches, verbose, quiet);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("diff") {
+        diff_command(sub_matches, verbose, quiet);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("download") {
+        download_command(sub_matches, verbose, quiet, force);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("fdroid") {
+        fdroid_command(sub_matches, verbose, quiet, force, bench);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("serve") {
+        serve_command(&matches, sub_matches, verbose, quiet);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("setup") {
+        setup_command(sub_matches, verbose, quiet, force);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("config") {
+        config_command(sub_matches, verbose, quiet);
+        return;
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("rules") {
+        rules_command(sub_matches, verbose, quiet);
+        return;
+    }
+
+    if matches.subcommand_matches("jobs").is_some() {
+        jobs_command(matches.value_of("results"), verbose, quiet);
+        return;
+    }
+
+    if matches.is_present("update-db") {
+        update_db_command(verbose, quiet);
+        return;
+    }
+
+    let device = if matches.is_present("device") {
+        Some(Device::new(matches.value_of("device")))
+    } else {
+        None
+    };
+    // The APK file given in the standard input is analyzed in a temporary workspace, that is
+    // created once the configuration is loaded and removed after the analysis
+    let stdin_apk = if matches.is_present("stdin") {
+        let mut apk = Vec::new();
+        if let Err(e) = io::stdin().read_to_end(&mut apk) {
+            print_error(format!("The APK file could not be read from the standard input: {}", e),
+                        verbose);
+            exit(Error::from(e).into());
         }
+        Some(apk)
+    } else {
+        None
     };
+    let stdin_package = match stdin_apk {
+        Some(ref apk) if !matches.is_present("package") => {
+            match get_package_name(apk) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    print_error(format!("The package name could not be read from the manifest \
+                                         of the APK file: {}",
+                                        e),
+                                verbose);
+                    exit(e.into());
+                }
+            }
+        }
+        _ => None,
+    };
+    let app_id = match matches.value_of("package").or(stdin_package.as_ref().map(|p| p.as_str())) {
+        Some(p) => p,
+        None => {
+            // Only the device was given, so its packages are listed
+            if let Some(ref device) = device {
+                list_device_packages(device, verbose, quiet);
+            }
+            return;
+        }
+    };
+    let mut config = load_config(ConfigBuilder::new()
+                                     .app_id(app_id)
+                                     .verbose(verbose)
+                                     .quiet(quiet)
+                                     .force(force)
+                                     .bench(bench),
+                                 verbose);
+    // The options of the profile are applied before the ones given in the command line, so
+    // that the flags override them
+    if let Some(profile) = matches.value_of("profile") {
+        apply_profile(&mut config, profile, verbose);
+    }
+    if profile_rules {
+        config.set_profile_rules(true);
+    }
+    if historical {
+        config.set_historical(true);
+    }
+    if online_checks {
+        config.set_online_checks(true);
+    }
+    if malware_heuristics {
+        config.set_malware_heuristics(true);
+    }
+    if virustotal {
+        config.set_virustotal(true);
+    }
+    if trend {
+        config.set_trend(true);
+    }
+    if gitlab_sast {
+        config.set_gitlab_sast(true);
+    }
+    if github_annotations {
+        config.set_github_annotations(true);
+    }
+    if reproducible {
+        config.set_reproducible(true);
+    }
+    if no_source {
+        config.set_include_source(false);
+    }
+    if no_decompile {
+        config.set_decompile(false);
+    }
+    if show_tool_output {
+        config.set_show_tool_output(true);
+    }
+    if let Some(formats) = matches.values_of("format") {
+        let mut report_formats = Vec::new();
+        for format in formats {
+            match ReportFormat::from_str(format) {
+                Ok(f) => {
+                    if !report_formats.contains(&f) {
+                        report_formats.push(f);
+                    }
+                }
+                Err(_) => {
+                    print_error(format!("The report format `{}` is not valid. It must be html, \
+                                         json, md or pdf.",
+                                        format),
+                                verbose);
+                    exit(Error::Config.into());
+                }
+            }
+        }
+        config.set_report_formats(report_formats);
+    }
+    if let Some(baseline) = matches.value_of("baseline") {
+        config.set_baseline(baseline);
+    }
+    if let Some(source_root) = matches.value_of("source-root") {
+        config.set_source_root(source_root);
+    }
+    if let Some(language) = matches.value_of("language") {
+        config.set_language(language);
+    }
+    if let Some(templates_folder) = matches.value_of("templates") {
+        config.set_templates_folder(templates_folder);
+    }
+    if let Some(mapping_file) = matches.value_of("mapping") {
+        config.set_mapping_file(mapping_file);
+    }
+    if let Some(timeout) = matches.value_of("timeout") {
+        config.set_analysis_timeout(parse_timeout(timeout, verbose));
+    }
+    if let Some(timeout) = matches.value_of("tool-timeout") {
+        config.set_tool_timeout(parse_timeout(timeout, verbose));
+    }
+    if let Some(threads) = matches.value_of("threads") {
+        config.set_threads(parse_threads(threads, verbose));
+    }
+    // The downloads folder is replaced by the one of the workspace when the APK file is read
+    // from the standard input
+    if let Some(downloads_folder) = matches.value_of("downloads") {
+        config.set_downloads_folder(downloads_folder);
+    }
+    if let Some(apktool_file) = matches.value_of("apktool") {
+        config.set_apktool_file(apktool_file);
+    }
+    if let Some(dex2jar_folder) = matches.value_of("dex2jar") {
+        config.set_dex2jar_folder(dex2jar_folder);
+    }
+    if let Some(jd_cmd_file) = matches.value_of("jd-cmd") {
+        config.set_jd_cmd_file(jd_cmd_file);
+    }
+    if let Some(rules_json) = matches.value_of("rules") {
+        config.set_rules_json(rules_json);
+    }
+    if let Some(rules_extra) = matches.values_of("rules-extra") {
+        for path in rules_extra {
+            config.add_rules_extra(path);
+        }
+    }
+    if let Some(results_template) = matches.value_of("template") {
+        config.set_results_template(results_template);
+    }
+    let workspace = match stdin_apk {
+        Some(apk) => {
+            match Workspace::new(&apk, Some(app_id), &config) {
+                Ok(w) => {
+                    w.configure(&mut config);
+                    Some(w)
+                }
+                Err(e) => {
+                    print_error(format!("The temporary workspace could not be created: {}", e),
+                                verbose);
+                    exit(e.into());
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some(dist_folder) = matches.value_of("dist") {
+        config.set_dist_folder(dist_folder);
+    }
+    if let Some(results_folder) = matches.value_of("results") {
+        config.set_results_folder(results_folder);
+    }
+    if let Some(ref device) = device {
+        match device.pull_package(app_id, &config) {
+            Ok(count) => {
+                if !config.is_quiet() {
+                    println!("{} APK files of {} pulled from the device.", count, app_id);
+                }
+            }
+            Err(e) => {
+                print_error(format!("The application could not be pulled from the device: {}",
+                                    e),
+                            verbose);
+                exit(e.into());
+            }
+        }
+    }
+    if !file_exists(format!("{}/{}.apk", config.get_downloads_folder(), app_id)) &&
+       !config.get_downloads_config().get_sources().is_empty() {
+        match download_from_sources(&config) {
+            Ok(url) => {
+                if !config.is_quiet() {
+                    println!("{} downloaded from {}.", app_id, url);
+                }
+            }
+            Err(e) => {
+                print_warning(format!("{} could not be downloaded from any of the configured \
+                                       sources: {}",
+                                      app_id,
+                                      e),
+                              verbose);
+            }
+        }
+    }
 
     if !config.check() {
         let mut error_string = String::from("Configuration errors were found:\n");
@@ -76,7 +396,10 @@ fn main() {
         for file in config.get_loaded_config_files() {
             error_string.push_str(&format!("\t- {}\n", file));
         }
+        error_string.push_str("Missing APKTool, Dex2Jar or JD-CMD files can be installed with \
+                               `super setup`.");
         print_error(error_string, verbose);
+        drop(workspace);
         exit(Error::Config.into());
     }
 
@@ -94,6 +417,20 @@ fn main() {
         sleep(Duration::from_millis(1250));
     }
 
+    let result = analyze(&config);
+    drop(workspace);
+    if let Err(e) = result {
+        print_error(format!("There was an error generating the results report: {}", e),
+                    config.is_verbose());
+        exit(Error::Unknown.into())
+    }
+}
+
+/// Analyzes the application of the configuration and generates its report
+///
+/// The analysis is skipped if the results of the application were already generated, unless
+/// it's forced.
+fn analyze(config: &Config) -> Result<()> {
     let mut benchmarks = if config.is_bench() {
         Vec::with_capacity(4)
     } else {
@@ -101,45 +438,106 @@ fn main() {
     };
 
     let start_time = Instant::now();
+    // Stages that fail or time out are recorded, and the report is generated with the rest
+    let deadline = match config.get_timeouts_config().get_analysis() {
+        0 => None,
+        timeout => Some(start_time + Duration::from_secs(timeout)),
+    };
+    let mut incomplete = Vec::new();
+    // Durations of the stages, recorded in the metadata of the report
+    let mut stages = Vec::new();
+
+    // Dist folders and workspaces left by old analyses are removed before creating new ones
+    remove_old_artifacts(config);
 
     // APKTool app decompression
-    decompress(&config);
+    if let Err(stage) = decompress(config, deadline) {
+        incomplete.push(stage);
+    }
+    stages.push(("Decompression", start_time.elapsed()));
 
     if config.is_bench() {
         benchmarks.push(Benchmark::new("ApkTool decompression", start_time.elapsed()));
     }
 
     // Extracting the classes.dex from the .apk file
-    extract_dex(&config, &mut benchmarks);
-
-    if config.is_verbose() {
-        println!("");
-        println!("Now it's time for the actual decompilation of the source code. We'll translate \
-                  Android JVM bytecode to Java, so that we can check the code afterwards.");
+    if config.is_decompile() {
+        let extract_start = Instant::now();
+        if let Err(stage) = extract_dex(config, &mut benchmarks, deadline) {
+            incomplete.push(stage);
+        }
+        stages.push(("DEX extraction", extract_start.elapsed()));
     }
 
-    let decompile_start = Instant::now();
+    // The jar file is missing if the dex extraction or its conversion failed, but a jar file
+    // generated with errors can still be decompiled
+    if !config.is_decompile() {
+        if config.is_verbose() {
+            println!("");
+            println!("The decompilation is disabled, so the code will only be scanned from the \
+                      DEX files of the application.");
+        }
+    } else if file_exists(format!("{}/{}/classes.jar",
+                                  config.get_dist_folder(),
+                                  config.get_app_id())) {
+        if config.is_verbose() {
+            println!("");
+            println!("Now it's time for the actual decompilation of the source code. We'll \
+                      translate Android JVM bytecode to Java, so that we can check the code \
+                      afterwards.");
+        }
 
-    // Decompiling the app
-    decompile(&config);
+        let decompile_start = Instant::now();
 
-    if config.is_bench() {
-        benchmarks.push(Benchmark::new("Decompilation", decompile_start.elapsed()));
+        // Decompiling the app
+        if let Err(stage) = decompile(config, deadline) {
+            incomplete.push(stage);
+        }
+        stages.push(("Decompilation", decompile_start.elapsed()));
+
+        if config.is_bench() {
+            benchmarks.push(Benchmark::new("Decompilation", decompile_start.elapsed()));
+        }
+    } else {
+        incomplete.push(IncompleteStage::new("Decompilation",
+                                             "Stage skipped, since there is no jar file to \
+                                              decompile."));
     }
 
-    if let Some(mut results) = Results::init(&config) {
+    if let Some(mut results) = Results::init(config) {
         if config.is_bench() {
             while benchmarks.len() > 0 {
                 results.add_benchmark(benchmarks.remove(0));
             }
         }
+        for (stage, duration) in stages {
+            results.add_stage_duration(stage, duration);
+        }
 
-        let static_start = Instant::now();
-        // Static application analysis
-        static_analysis(&config, &mut results);
+        if deadline.map_or(false, |d| Instant::now() >= d) {
+            print_warning(format!("The analysis timed out after {} seconds, so the static \
+                                   analysis was skipped.",
+                                  config.get_timeouts_config().get_analysis()),
+                          config.is_verbose());
+            incomplete.push(IncompleteStage::new("Static analysis",
+                                                 format!("Stage skipped, since the analysis \
+                                                          timed out after {} seconds.",
+                                                         config.get_timeouts_config()
+                                                             .get_analysis())));
+        } else {
+            let static_start = Instant::now();
+            // Static application analysis
+            static_analysis(config, &mut results);
+            results.add_stage_duration("Static analysis", static_start.elapsed());
 
-        if config.is_bench() {
-            results.add_benchmark(Benchmark::new("Total static analysis", static_start.elapsed()));
+            if config.is_bench() {
+                results.add_benchmark(Benchmark::new("Total static analysis",
+                                                     static_start.elapsed()));
+            }
+        }
+        let failed = !incomplete.is_empty();
+        for stage in incomplete {
+            results.add_incomplete_stage(stage);
         }
 
         // TODO dynamic analysis
@@ -150,25 +548,21 @@ fn main() {
 
         let report_start = Instant::now();
 
-        match results.generate_report(&config) {
-            Ok(_) => {
-                if config.is_verbose() {
-                    println!("The results report has been saved. Everything went smoothly, now \
-                              you can check all the results.");
-                    println!("");
-                    println!("I will now analyze myself for vulnerabilities…");
-                    sleep(Duration::from_millis(1500));
-                    println!("Nah, just kidding, I've been developed in {}!",
-                             "Rust".bold().green())
-                } else if !config.is_quiet() {
-                    println!("Report generated.");
-                }
-            }
-            Err(e) => {
-                print_error(format!("There was an error generating the results report: {}", e),
-                            config.is_verbose());
-                exit(Error::Unknown.into())
-            }
+        results.map_to_source(config);
+        results.group_findings(config);
+        let report = results.generate_report(config);
+        clean_dist_folder(config, failed || report.is_err());
+        try!(report);
+        if config.is_verbose() {
+            println!("The results report has been saved. Everything went smoothly, now you can \
+                      check all the results.");
+            println!("");
+            println!("I will now analyze myself for vulnerabilities…");
+            sleep(Duration::from_millis(1500));
+            println!("Nah, just kidding, I've been developed in {}!",
+                     "Rust".bold().green())
+        } else if !config.is_quiet() {
+            println!("Report generated.");
         }
 
         if config.is_bench() {
@@ -183,9 +577,577 @@ fn main() {
                 println!("{}", bench);
             }
         }
+
+        if config.is_profile_rules() {
+            println!("");
+            println!("{}", "Rule profile, slowest rules first:".bold());
+            for profile in results.get_rule_profiles() {
+                println!("{}", profile);
+            }
+        }
     } else if !config.is_quiet() {
         println!("Analysis cancelled.");
     }
+    Ok(())
+}
+
+fn migrate_results_command(matches: &ArgMatches, verbose: bool, quiet: bool) {
+    let path = match matches.value_of("path") {
+        Some(p) => String::from(p),
+        None => {
+            let config = ConfigBuilder::new()
+                .verbose(verbose)
+                .quiet(quiet)
+                .build()
+                .unwrap_or_default();
+            String::from(config.get_results_folder())
+        }
+    };
+
+    if !file_exists(&path) {
+        print_error(format!("The results path `{}` does not exist", path), verbose);
+        exit(Error::Config.into());
+    }
+
+    match migrate_results(&path, verbose) {
+        Ok(count) => {
+            if !quiet {
+                println!("{} result reports migrated to schema version {}.",
+                         count,
+                         SCHEMA_VERSION);
+            }
+        }
+        Err(e) => {
+            print_error(format!("There was an error migrating the results: {}", e),
+                        verbose);
+            exit(Error::Unknown.into());
+        }
+    }
+}
+
+fn diff_command(matches: &ArgMatches, verbose: bool, quiet: bool) {
+    let previous = matches.value_of("previous").unwrap();
+    let current = matches.value_of("current").unwrap();
+    let diff = match diff_results(previous, current) {
+        Ok(d) => d,
+        Err(e) => {
+            print_error(format!("The results `{}` and `{}` could not be compared: {}",
+                                previous,
+                                current,
+                                e),
+                        verbose);
+            exit(Error::ParseError.into());
+        }
+    };
+
+    let output = match matches.value_of("output") {
+        Some(o) => PathBuf::from(o),
+        None => get_report_path(current).with_file_name("diff.json"),
+    };
+    if let Err(e) = fs::File::create(&output).and_then(|mut f| {
+        f.write_all(format!("{:?}", serde_json::value::to_value(&diff)).as_bytes())
+    }) {
+        print_error(format!("The JSON delta could not be saved in `{}`: {}",
+                            output.display(),
+                            e),
+                    verbose);
+        exit(Error::Unknown.into());
+    }
+
+    if !quiet {
+        print!("{}", diff);
+        println!("");
+        println!("The JSON delta was saved in {}.", output.display());
+    }
+}
+
+fn download_command(matches: &ArgMatches, verbose: bool, quiet: bool, force: bool) {
+    let config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet).force(force),
+                             verbose);
+
+    let list_path = matches.value_of("list").unwrap();
+    let mut list = String::new();
+    if let Err(e) = fs::File::open(list_path).and_then(|mut f| f.read_to_string(&mut list)) {
+        print_error(format!("The download list `{}` could not be read: {}", list_path, e),
+                    verbose);
+        exit(Error::Config.into());
+    }
+
+    let jobs = match parse_download_list(&list) {
+        Ok(j) => j,
+        Err(_) => {
+            print_error(format!("The download list `{}` is not valid. Each line must contain \
+                                 a package and a URL, or a URL ending in the package file \
+                                 name.",
+                                list_path),
+                        verbose);
+            exit(Error::ParseError.into());
+        }
+    };
+
+    match download_all(jobs, &config) {
+        Ok(summary) => {
+            if !quiet {
+                println!("{} applications downloaded, {} already downloaded and {} failed.",
+                         summary.downloaded,
+                         summary.skipped,
+                         summary.failed);
+            }
+            if summary.failed > 0 {
+                exit(Error::Unknown.into());
+            }
+        }
+        Err(e) => {
+            print_error(format!("There was an error downloading the applications: {}", e),
+                        verbose);
+            exit(Error::Unknown.into());
+        }
+    }
+}
+
+/// Builds the configuration, using the default configuration if the configuration files can't
+/// be read
+fn load_config(builder: ConfigBuilder, verbose: bool) -> Config {
+    match builder.build() {
+        Ok(c) => c,
+        Err(Error::Config) => {
+            print_error("The package name of the application is not valid.", verbose);
+            exit(Error::Config.into());
+        }
+        Err(e) => {
+            print_warning(format!("There was an error when reading the config.toml file: {}",
+                                  e),
+                          verbose);
+            match builder.load_files(false).build() {
+                Ok(c) => c,
+                Err(e) => exit(e.into()),
+            }
+        }
+    }
+}
+
+/// Parses a timeout option, in seconds, exiting if it's not valid
+fn parse_timeout(timeout: &str, verbose: bool) -> u64 {
+    match timeout.parse() {
+        Ok(t) => t,
+        Err(_) => {
+            print_error(format!("The timeout `{}` is not valid. It must be a number of seconds, \
+                                 or 0 for no timeout.",
+                                timeout),
+                        verbose);
+            exit(Error::Config.into());
+        }
+    }
+}
+
+fn parse_threads(threads: &str, verbose: bool) -> u8 {
+    match threads.parse() {
+        Ok(t) if t > 0 => t,
+        _ => {
+            print_error(format!("The number of threads `{}` is not valid. It must be a number \
+                                 between 1 and {}.",
+                                threads,
+                                u8::MAX),
+                        verbose);
+            exit(Error::Config.into());
+        }
+    }
+}
+
+/// Applies the profile to the configuration, exiting if it's not defined
+fn apply_profile(config: &mut Config, profile: &str, verbose: bool) {
+    if config.apply_profile(profile).is_err() {
+        let profiles = config.get_profiles();
+        print_error(format!("The profile `{}` is not defined in the configuration. {}",
+                            profile,
+                            if profiles.is_empty() {
+                                String::from("There are no profiles defined.")
+                            } else {
+                                format!("The defined profiles are: {}.", profiles.join(", "))
+                            }),
+                    verbose);
+        exit(Error::Config.into());
+    }
+}
+
+/// Gets the arguments for the analyses of the server, with the flags and options given to it
+/// that change how an application is analyzed
+///
+/// The package, dist and results folders of each analysis are set by its job.
+fn get_analyzer_args(matches: &ArgMatches) -> Vec<String> {
+    let mut args = Vec::new();
+    for flag in &ANALYZER_FLAGS {
+        if matches.is_present(flag) {
+            args.push(format!("--{}", flag));
+        }
+    }
+    for option in &ANALYZER_OPTIONS {
+        if let Some(values) = matches.values_of(option) {
+            for value in values {
+                args.push(format!("--{}", option));
+                args.push(String::from(value));
+            }
+        }
+    }
+    args
+}
+
+fn serve_command(matches: &ArgMatches, sub_matches: &ArgMatches, verbose: bool, quiet: bool) {
+    let mut config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet), verbose);
+    // The server gets the same profile as its analyses, and its own folders and workers
+    if let Some(profile) = matches.value_of("profile") {
+        apply_profile(&mut config, profile, verbose);
+    }
+    if let Some(downloads_folder) = matches.value_of("downloads") {
+        config.set_downloads_folder(downloads_folder);
+    }
+    if let Some(dist_folder) = matches.value_of("dist") {
+        config.set_dist_folder(dist_folder);
+    }
+    if let Some(results_folder) = matches.value_of("results") {
+        config.set_results_folder(results_folder);
+    }
+    if let Some(threads) = matches.value_of("threads") {
+        config.set_threads(parse_threads(threads, verbose));
+    }
+
+    let address = sub_matches.value_of("address").unwrap();
+    if let Err(e) = serve(address, &config, get_analyzer_args(matches)) {
+        print_error(format!("The server could not be started on {}: {}", address, e),
+                    verbose);
+        exit(Error::Unknown.into());
+    }
+}
+
+fn setup_command(matches: &ArgMatches, verbose: bool, quiet: bool, force: bool) {
+    let config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet).force(force),
+                             verbose);
+
+    let folder = match matches.value_of("folder") {
+        Some(f) => PathBuf::from(f),
+        None => {
+            match get_tools_folder() {
+                Some(f) => f,
+                None => {
+                    print_error("The data folder of the user could not be found. Use the \
+                                 --folder option to choose where the tools are installed.",
+                                verbose);
+                    exit(Error::Config.into());
+                }
+            }
+        }
+    };
+    let mirror = matches.value_of("mirror").unwrap_or(DEFAULT_MIRROR);
+
+    match install_tools(&folder, mirror, &config) {
+        Ok(count) => {
+            if !quiet {
+                println!("{} tool files downloaded to {}.", count, folder.display());
+            }
+        }
+        Err(e) => {
+            print_error(format!("The tools could not be installed: {}", e), verbose);
+            exit(e.into());
+        }
+    }
+
+    match write_user_config(&folder, &config) {
+        Ok(path) => {
+            if !quiet {
+                println!("The paths of the tools were written to {}.", path.display());
+            }
+        }
+        Err(e) => {
+            print_error(format!("The paths of the tools could not be written to the \
+                                 configuration of the user: {}",
+                                e),
+                        verbose);
+            exit(e.into());
+        }
+    }
+}
+
+fn jobs_command(results_folder: Option<&str>, verbose: bool, quiet: bool) {
+    let mut config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet), verbose);
+    if let Some(results_folder) = results_folder {
+        config.set_results_folder(results_folder);
+    }
+
+    let jobs = match load_jobs(&config) {
+        Ok(j) => j,
+        Err(e) => {
+            print_error(format!("The jobs file could not be loaded: {}", e), verbose);
+            exit(Error::ParseError.into());
+        }
+    };
+    if jobs.is_empty() {
+        println!("There are no jobs.");
+        return;
+    }
+    println!("{:>6}  {:<10} {:<40} {:<26} {}",
+             "ID",
+             "Status",
+             "Package",
+             "Submitted",
+             "Finished");
+    for job in &jobs {
+        println!("{:>6}  {:<10} {:<40} {:<26} {}",
+                 job.get_id(),
+                 job.get_status().as_str(),
+                 job.get_package(),
+                 job.get_submitted(),
+                 job.get_finished().unwrap_or("-"));
+        if let Some(error) = job.get_error() {
+            println!("        {}", error);
+        }
+    }
+}
+
+fn config_command(matches: &ArgMatches, verbose: bool, quiet: bool) {
+    let action = matches.value_of("action").unwrap_or("show");
+    if action == "sample" {
+        print!("{}", SAMPLE_CONFIG);
+        return;
+    }
+
+    let mut config = match ConfigBuilder::new().verbose(verbose).quiet(quiet).build() {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(format!("There was an error when reading the config.toml file: {}", e),
+                        verbose);
+            exit(e.into());
+        }
+    };
+    if let Some(profile) = matches.value_of("profile") {
+        if config.apply_profile(profile).is_err() {
+            print_error(format!("The profile `{}` is not defined in the configuration.",
+                                profile),
+                        verbose);
+            exit(Error::Config.into());
+        }
+    }
+
+    if action == "show" {
+        println!("{}", "Configuration files, in loading order:".bold());
+        println!("\t- Default built-in configuration");
+        for file in config.get_loaded_config_files() {
+            println!("\t- {}", file);
+        }
+        if let Some(profile) = config.get_profile() {
+            println!("\t- Profile {}", profile);
+        }
+        println!("");
+        println!("{}", "Effective configuration:".bold());
+        for (key, value, origin) in config.get_effective_options() {
+            println!("{} = {} {}", key, value, format!("# {}", origin).dimmed());
+        }
+        return;
+    }
+
+    let mut errors = config.get_errors();
+    match validate_rules(&config) {
+        Ok(count) => {
+            if !quiet {
+                println!("{} rules loaded from {}.", count, config.get_rules_json());
+            }
+        }
+        Err(e) => {
+            errors.push(format!("the rules in `{}` are not valid: {}", config.get_rules_json(), e))
+        }
+    }
+    if errors.is_empty() {
+        if !quiet {
+            println!("The configuration is valid.");
+        }
+    } else {
+        let mut error_string = String::from("Configuration errors were found:\n");
+        for error in errors {
+            error_string.push_str(&error);
+            error_string.push('\n');
+        }
+        print_error(error_string, verbose);
+        exit(Error::Config.into());
+    }
+}
+
+fn fdroid_command(matches: &ArgMatches, verbose: bool, quiet: bool, force: bool, bench: bool) {
+    let config = load_config(ConfigBuilder::new()
+                                 .verbose(verbose)
+                                 .quiet(quiet)
+                                 .force(force)
+                                 .bench(bench),
+                             verbose);
+
+    let index_url = matches.value_of("index").unwrap();
+    let mut apps = match fetch_index(index_url) {
+        Ok(a) => a,
+        Err(e) => {
+            print_error(format!("The F-Droid repository index `{}` could not be loaded: {}",
+                                index_url,
+                                e),
+                        verbose);
+            exit(Error::ParseError.into());
+        }
+    };
+    if let Some(packages) = matches.values_of("packages") {
+        let packages: Vec<_> = packages.collect();
+        for package in &packages {
+            if !apps.iter().any(|a| a.get_package() == *package) {
+                print_warning(format!("{} is not in the F-Droid repository.", package),
+                              verbose);
+            }
+        }
+        apps.retain(|a| packages.contains(&a.get_package()));
+    }
+
+    let repo_url = get_repo_url(index_url);
+    let jobs = apps.iter().map(|a| a.get_download_job(repo_url)).collect();
+    if let Err(e) = download_all(jobs, &config) {
+        print_error(format!("There was an error downloading the applications: {}", e),
+                    verbose);
+        exit(Error::Unknown.into());
+    }
+
+    let mut summaries = Vec::with_capacity(apps.len());
+    for app in &apps {
+        if !file_exists(format!("{}/{}.apk", config.get_downloads_folder(), app.get_package())) {
+            summaries.push(AppSummary::failed(app, "Download failed"));
+            continue;
+        }
+        let builder = ConfigBuilder::new()
+            .app_id(app.get_package())
+            .verbose(verbose)
+            .quiet(quiet)
+            .force(force)
+            .bench(bench);
+        let app_config = match builder.build().or_else(|_| builder.load_files(false).build()) {
+            Ok(c) => c,
+            Err(_) => {
+                summaries.push(AppSummary::failed(app, "Configuration error"));
+                continue;
+            }
+        };
+        if !app_config.check() {
+            print_warning(format!("{} could not be analyzed: {}",
+                                  app.get_package(),
+                                  app_config.get_errors().join(", ")),
+                          verbose);
+            summaries.push(AppSummary::failed(app, "Configuration error"));
+            continue;
+        }
+        if !quiet {
+            println!("Analyzing {} {}.", app.get_package(), app.get_version_name());
+        }
+        match analyze(&app_config) {
+            Ok(_) => summaries.push(AppSummary::from_results(app, &app_config)),
+            Err(e) => {
+                print_warning(format!("{} could not be analyzed: {}", app.get_package(), e),
+                              verbose);
+                summaries.push(AppSummary::failed(app, "Analysis failed"));
+            }
+        }
+    }
+
+    if let Err(e) = write_summaries(&summaries, &config) {
+        print_error(format!("There was an error writing the summary of the repository: {}", e),
+                    verbose);
+        exit(Error::Unknown.into());
+    }
+    if !quiet {
+        println!("{} of {} applications of the repository analyzed. The summary was saved in {}.",
+                 summaries.iter().filter(|s| s.is_analyzed()).count(),
+                 summaries.len(),
+                 config.get_results_folder());
+    }
+}
+
+fn list_device_packages(device: &Device, verbose: bool, quiet: bool) {
+    match device.list_packages() {
+        Ok(packages) => {
+            if !quiet {
+                println!("Installed packages in the device:");
+            }
+            for package in packages {
+                println!("{}", package);
+            }
+        }
+        Err(e) => {
+            print_error(format!("The packages of the device could not be listed: {}", e),
+                        verbose);
+            exit(Error::Unknown.into());
+        }
+    }
+}
+
+fn rules_command(matches: &ArgMatches, verbose: bool, quiet: bool) {
+    let config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet), verbose);
+    let folder = match get_rules_folder() {
+        Some(f) => f,
+        None => {
+            print_error("The rules folder of the user could not be found.", verbose);
+            exit(Error::Config.into());
+        }
+    };
+
+    if matches.value_of("action") == Some("update") {
+        match update_rule_pack(&folder, matches.is_present("allow-unsigned"), &config) {
+            Ok(pack) => {
+                if !quiet {
+                    println!("Rule pack {} {} installed with {} rules.",
+                             pack.get_name(),
+                             pack.get_version(),
+                             pack.get_rules());
+                }
+            }
+            Err(e) => {
+                print_error(format!("The rule pack could not be updated from {}: {}",
+                                    config.get_rule_packs_config().get_url(),
+                                    e),
+                            verbose);
+                exit(Error::Unknown.into());
+            }
+        }
+        return;
+    }
+
+    match list_rule_packs(&folder) {
+        Ok(ref packs) if packs.is_empty() => println!("No rule packs are installed."),
+        Ok(packs) => {
+            println!("{}", format!("Rule packs installed in {}:", folder.display()).bold());
+            for pack in packs {
+                println!("\t- {} {}: {} rules, installed on {} from {}",
+                         pack.get_name(),
+                         pack.get_version(),
+                         pack.get_rules(),
+                         pack.get_date(),
+                         pack.get_url());
+            }
+        }
+        Err(e) => {
+            print_error(format!("The index of the rule packs could not be read: {}", e),
+                        verbose);
+            exit(Error::ParseError.into());
+        }
+    }
+}
+
+fn update_db_command(verbose: bool, quiet: bool) {
+    let config = load_config(ConfigBuilder::new().verbose(verbose).quiet(quiet), verbose);
+
+    match update_advisory_database(&config) {
+        Ok(count) => {
+            if !quiet {
+                println!("Vulnerability database updated with {} advisories.", count);
+            }
+        }
+        Err(e) => {
+            print_error(format!("The vulnerability database could not be updated from {}: {}",
+                                config.get_vulnerability_db_config().get_url(),
+                                e),
+                        verbose);
+            exit(Error::Unknown.into());
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -195,6 +1157,10 @@ pub enum Error {
     JSONError(JSONError),
     CodeNotFound,
     Config,
+    Template(String),
+    /// The command was killed after its timeout, with its standard output and error until then
+    TimedOut(Vec<u8>, Vec<u8>),
+    Archive(ArchiveViolation),
     IOError(io::Error),
     Unknown,
 }
@@ -207,6 +1173,9 @@ impl Into<i32> for Error {
             Error::JSONError(_) => 30,
             Error::CodeNotFound => 40,
             Error::Config => 50,
+            Error::Template(_) => 60,
+            Error::TimedOut(..) => 70,
+            Error::Archive(_) => 80,
             Error::IOError(_) => 100,
             Error::Unknown => 1,
         }
@@ -219,6 +1188,18 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<TemplateError> for Error {
+    fn from(err: TemplateError) -> Error {
+        Error::Template(format!("{}", err))
+    }
+}
+
+impl From<RenderError> for Error {
+    fn from(err: RenderError) -> Error {
+        Error::Template(format!("{}", err))
+    }
+}
+
 impl From<serde_json::error::Error> for Error {
     fn from(err: serde_json::error::Error) -> Error {
         match err {
@@ -233,7 +1214,10 @@ impl From<serde_json::error::Error> for Error {
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match *self {
+            Error::Archive(ref v) => write!(f, "{}", v),
+            _ => write!(f, "{}", self.description()),
+        }
     }
 }
 
@@ -245,6 +1229,9 @@ impl StdError for Error {
             Error::JSONError(ref e) => e.description(),
             Error::CodeNotFound => "the code was not found in the file",
             Error::Config => "there was an error in the configuration",
+            Error::Template(ref e) => e.as_str(),
+            Error::TimedOut(..) => "the command did not finish before its timeout",
+            Error::Archive(_) => "the archive exceeds the extraction safety limits",
             Error::IOError(ref e) => e.description(),
             Error::Unknown => "an unknown error occurred",
         }
@@ -322,14 +1309,20 @@ impl FromStr for Criticity {
 }
 
 fn get_help_menu() -> ArgMatches<'static> {
+    get_app().get_matches()
+}
+
+/// Gets the command line interface, with all its arguments and subcommands
+fn get_app() -> App<'static, 'static> {
     App::new("SUPER Android Analyzer")
         .version(crate_version!())
         .author("SUPER Team <contact@superanalyzer.rocks>")
         .about("Audits Android apps for vulnerabilities")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("package")
             .help("The package string of the application to test.")
             .value_name("package")
-            .required(true)
+            .required_unless_one(&["update-db", "device", "stdin"])
             .takes_value(true))
         .arg(Arg::with_name("verbose")
             .short("v")
@@ -342,12 +1335,303 @@ fn get_help_menu() -> ArgMatches<'static> {
         .arg(Arg::with_name("bench")
             .long("bench")
             .help("Show benchmarks for the analysis."))
+        .arg(Arg::with_name("profile-rules")
+            .long("profile-rules")
+            .help("Show the files checked, matches and time of each code rule, and save them in \
+                   the rule_profile.json file of the results."))
+        .arg(Arg::with_name("historical")
+            .long("historical")
+            .help("Evaluate findings against the practices of the time the application \
+                   targets, reporting newer ones as informational warnings."))
+        .arg(Arg::with_name("online-checks")
+            .long("online-checks")
+            .help("Probe the Firebase databases and storage buckets referenced by the \
+                   application to check if they can be read by anyone."))
+        .arg(Arg::with_name("malware-heuristics")
+            .long("malware-heuristics")
+            .help("Combine malware signals, such as dynamic code loading or premium SMS, in a \
+                   malware likelihood score shown at the top of the report."))
+        .arg(Arg::with_name("virustotal")
+            .long("virustotal")
+            .help("Look up the hash of the application in VirusTotal, with the API key of the \
+                   [virustotal] section of the configuration."))
+        .arg(Arg::with_name("trend")
+            .long("trend")
+            .help("Generate a page plotting the findings of each analyzed version of the \
+                   application, from the analysis history of the results folder."))
+        .arg(Arg::with_name("gitlab-sast")
+            .long("gitlab-sast")
+            .help("Generate a gl-sast-report.json report in the GitLab SAST format, to show the \
+                   findings in the GitLab security widgets."))
+        .arg(Arg::with_name("github-annotations")
+            .long("github-annotations")
+            .requires("source-root")
+            .help("Print GitHub Actions annotations for the findings mapped to the source code \
+                   of the application, to show them inline in pull requests."))
+        .arg(Arg::with_name("reproducible")
+            .long("reproducible")
+            .help("Generate a reproducible JSON report, without the generation date and with the \
+                   absolute paths made relative, so that the same analysis gives the same file."))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .value_name("formats")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .help("The formats of the report, separated by commas: html, json, md or pdf. \
+                   Overrides the report_formats option of the configuration."))
+        .arg(Arg::with_name("no-source")
+            .long("no-source")
+            .help("Don't include the decompiled source code in the HTML report, which makes the \
+                   results much smaller."))
+        .arg(Arg::with_name("stdin")
+            .long("stdin")
+            .conflicts_with("device")
+            .help("Reads the APK file from the standard input, and analyzes it in a temporary \
+                   workspace instead of the downloads folder. The package name is read from \
+                   the manifest if it's not given."))
+        .arg(Arg::with_name("no-decompile")
+            .long("no-decompile")
+            .help("Don't decompile the code with Dex2Jar and JD-CMD. The code analyses that \
+                   only need the strings and classes of the application run on the DEX \
+                   files, which is much faster, but the code rules are skipped."))
+        .arg(Arg::with_name("profile")
+            .long("profile")
+            .value_name("name")
+            .takes_value(true)
+            .help("Applies the options of the given profile of the configuration, defined in \
+                   its [profile.{name}] section, such as a quick profile for CI gates."))
+        .arg(Arg::with_name("source-root")
+            .long("source-root")
+            .value_name("dir")
+            .takes_value(true)
+            .help("The source code of the application, such as app/src/main. Findings in \
+                   decompiled classes are mapped to its Java and Kotlin files and lines."))
+        .arg(Arg::with_name("baseline")
+            .long("baseline")
+            .value_name("triage.json")
+            .takes_value(true)
+            .help("A triage file exported from the HTML report. The findings marked in it as \
+                   false positives or accepted are left out of the reports."))
+        .arg(Arg::with_name("language")
+            .long("language")
+            .value_name("language")
+            .takes_value(true)
+            .possible_values(&LANGUAGES)
+            .help("The language of the reports and the rule descriptions. Overrides the \
+                   language option of the configuration."))
+        .arg(Arg::with_name("templates")
+            .long("templates")
+            .value_name("folder")
+            .takes_value(true)
+            .help("A folder with .hbs templates overriding the built-in templates of the HTML \
+                   report, such as header.hbs, footer.hbs or finding.hbs."))
+        .arg(Arg::with_name("mapping")
+            .long("mapping")
+            .value_name("mapping.txt")
+            .takes_value(true)
+            .help("The ProGuard or R8 mapping file of the application, used to de-obfuscate the \
+                   class and method names of the report."))
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("seconds")
+            .takes_value(true)
+            .help("The timeout of the whole analysis, after which the report is generated with \
+                   the stages that finished. Overrides the timeouts configuration."))
+        .arg(Arg::with_name("tool-timeout")
+            .long("tool-timeout")
+            .value_name("seconds")
+            .takes_value(true)
+            .help("The timeout of each external tool, such as apktool, dex2jar or jd-cmd. \
+                   Overrides the timeouts configuration."))
+        .arg(Arg::with_name("show-tool-output")
+            .long("show-tool-output")
+            .requires("verbose")
+            .help("Show the output of apktool, dex2jar and jd-cmd as they run. It's always saved \
+                   in the logs folder of the results folder."))
+        .arg(Arg::with_name("dist")
+            .long("dist")
+            .value_name("folder")
+            .takes_value(true)
+            .help("The folder where the application is decompiled. Overrides the dist_folder \
+                   option of the configuration."))
+        .arg(Arg::with_name("results")
+            .long("results")
+            .value_name("folder")
+            .takes_value(true)
+            .help("The folder where the reports are generated. Overrides the results_folder \
+                   option of the configuration."))
+        .arg(Arg::with_name("downloads")
+            .long("downloads")
+            .value_name("folder")
+            .takes_value(true)
+            .help("The folder with the APK files of the applications. Overrides the \
+                   downloads_folder option of the configuration."))
+        .arg(Arg::with_name("apktool")
+            .long("apktool")
+            .value_name("apktool.jar")
+            .takes_value(true)
+            .help("The APKTool JAR file. Overrides the apktool_file option of the \
+                   configuration."))
+        .arg(Arg::with_name("dex2jar")
+            .long("dex2jar")
+            .value_name("folder")
+            .takes_value(true)
+            .help("The Dex2Jar folder. Overrides the dex2jar_folder option of the \
+                   configuration."))
+        .arg(Arg::with_name("jd-cmd")
+            .long("jd-cmd")
+            .value_name("jd-cmd.jar")
+            .takes_value(true)
+            .help("The JD-CMD JAR file. Overrides the jd_cmd_file option of the \
+                   configuration."))
+        .arg(Arg::with_name("rules")
+            .long("rules")
+            .value_name("rules.json")
+            .takes_value(true)
+            .help("The rule file, or a folder with rule files. Overrides the rules_json option \
+                   of the configuration."))
+        .arg(Arg::with_name("rules-extra")
+            .long("rules-extra")
+            .value_name("rules.json")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("An extra rule file, or a folder with rule files, merged over the configured \
+                   rules. Its rules replace the ones with the same IDs, and later files \
+                   override earlier ones. It can be given several times."))
+        .arg(Arg::with_name("template")
+            .long("template")
+            .value_name("folder")
+            .takes_value(true)
+            .help("The results template folder, with the styles and scripts of the HTML \
+                   report. Overrides the results_template option of the configuration."))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .value_name("threads")
+            .takes_value(true)
+            .help("The number of threads of the analysis. Overrides the threads option of the \
+                   configuration."))
+        .arg(Arg::with_name("device")
+            .long("device")
+            .value_name("serial")
+            .takes_value(true)
+            .min_values(0)
+            .help("Pull the application, including its split APKs, from a device with adb. \
+                   The packages of the device are listed if no package is given."))
+        .arg(Arg::with_name("update-db")
+            .long("update-db")
+            .help("Downloads the latest offline vulnerability database of the libraries."))
         .arg(Arg::with_name("quiet")
             .short("q")
             .long("quiet")
             .conflicts_with("verbose")
             .help("If you'd like a zen auditor that won't talk unless it's 100% necessary."))
-        .get_matches()
+        .subcommand(SubCommand::with_name("migrate-results")
+            .about("Upgrades stored JSON results to the current schema version")
+            .arg(Arg::with_name("path")
+                .help("A results.json file or a results folder. Defaults to the configured \
+                       results folder.")
+                .value_name("path")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("diff")
+            .about("Compares the findings of two analyses, such as two releases of an \
+                    application, showing the new, fixed and persisting ones")
+            .arg(Arg::with_name("previous")
+                .help("The results.json file or results folder of the previous analysis.")
+                .value_name("previous")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("current")
+                .help("The results.json file or results folder of the current analysis.")
+                .value_name("current")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("output")
+                .long("output")
+                .value_name("diff.json")
+                .takes_value(true)
+                .help("Where to save the JSON delta. Defaults to diff.json next to the current \
+                       results.")))
+        .subcommand(SubCommand::with_name("download")
+            .about("Downloads the applications in a list to the downloads folder, resuming \
+                    interrupted downloads")
+            .arg(Arg::with_name("list")
+                .help("A file with an application per line, as a package and a URL, or just \
+                       as a URL ending in the package file name.")
+                .value_name("list")
+                .required(true)
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("fdroid")
+            .about("Downloads and analyzes the applications of an F-Droid repository, with an \
+                    aggregate CSV and JSON summary in the results folder")
+            .arg(Arg::with_name("index")
+                .help("The URL of the index-v1.json index of the repository.")
+                .value_name("index")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("packages")
+                .help("The packages to analyze. All the applications of the repository are \
+                       analyzed if none is given.")
+                .value_name("package")
+                .multiple(true)
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("serve")
+            .about("Starts a REST API to submit applications for analysis and fetch their \
+                    results, running as many analyses at a time as configured threads")
+            .arg(Arg::with_name("address")
+                .long("address")
+                .value_name("address")
+                .default_value("127.0.0.1:8080")
+                .takes_value(true)
+                .help("The address and port to listen on.")))
+        .subcommand(SubCommand::with_name("setup")
+            .alias("install-tools")
+            .about("Downloads the pinned versions of APKTool, Dex2Jar and JD-CMD, verifying \
+                    their checksums, and writes their paths to the configuration of the user")
+            .arg(Arg::with_name("folder")
+                .long("folder")
+                .value_name("folder")
+                .takes_value(true)
+                .help("The folder to install the tools in. By default, the data folder of the \
+                       user."))
+            .arg(Arg::with_name("mirror")
+                .long("mirror")
+                .value_name("url")
+                .takes_value(true)
+                .help("The URL of a mirror of the vendor folder of SUPER to download the tools \
+                       from.")))
+        .subcommand(SubCommand::with_name("config")
+            .about("Shows the effective configuration, with the file each option came from, \
+                    validates it, or prints a commented sample configuration")
+            .arg(Arg::with_name("action")
+                .help("What to do with the configuration.")
+                .value_name("action")
+                .possible_values(&["show", "validate", "sample"])
+                .default_value("show")
+                .takes_value(true))
+            .arg(Arg::with_name("profile")
+                .long("profile")
+                .value_name("name")
+                .takes_value(true)
+                .help("Applies the given profile of the configuration before showing or \
+                       validating it.")))
+        .subcommand(SubCommand::with_name("rules")
+            .about("Updates the rule pack from the configured URL, verifying its checksum and \
+                    signature, or lists the installed rule packs")
+            .arg(Arg::with_name("action")
+                .help("What to do with the rule packs.")
+                .value_name("action")
+                .possible_values(&["update", "list"])
+                .default_value("list")
+                .takes_value(true))
+            .arg(Arg::with_name("allow-unsigned")
+                .long("allow-unsigned")
+                .help("Installs the rule pack even if no public key is configured to verify its \
+                       signature.")))
+        .subcommand(SubCommand::with_name("jobs")
+            .about("Lists the queued, running and finished jobs of the server, from the jobs \
+                    file of the results folder"))
 }
 
 /// Copies the contents of `from` to `to`
@@ -375,6 +1659,7 @@ pub fn copy_folder<P: AsRef<Path>>(from: P, to: P) -> Result<()> {
 mod tests {
     use Criticity;
     use std::str::FromStr;
+    use super::{get_app, get_analyzer_args};
 
     #[test]
     fn it_criticity() {
@@ -424,4 +1709,41 @@ mod tests {
         assert_eq!(format!("{:?}", Criticity::High).as_str(), "High");
         assert_eq!(format!("{:?}", Criticity::Critical).as_str(), "Critical");
     }
+
+    #[test]
+    fn it_get_analyzer_args() {
+        let matches = get_app()
+            .get_matches_from_safe(vec!["super",
+                                        "--github-annotations",
+                                        "--source-root",
+                                        "app/src/main",
+                                        "--mapping",
+                                        "mapping.txt",
+                                        "--baseline",
+                                        "triage.json",
+                                        "--rules-extra",
+                                        "extra.json",
+                                        "serve"])
+            .unwrap();
+        let args = get_analyzer_args(&matches);
+
+        // The arguments of each job, as given by the job queue
+        let mut job_args = vec![String::from("super"),
+                                String::from("com.example"),
+                                String::from("--force"),
+                                String::from("--quiet"),
+                                String::from("--dist"),
+                                String::from("dist/jobs/1"),
+                                String::from("--results"),
+                                String::from("results/jobs/1")];
+        job_args.extend(args);
+        let job_matches = get_app().get_matches_from_safe(job_args).unwrap();
+        assert_eq!(job_matches.value_of("package"), Some("com.example"));
+        assert!(job_matches.is_present("github-annotations"));
+        assert_eq!(job_matches.value_of("source-root"), Some("app/src/main"));
+        assert_eq!(job_matches.value_of("mapping"), Some("mapping.txt"));
+        assert_eq!(job_matches.value_of("baseline"), Some("triage.json"));
+        assert_eq!(job_matches.value_of("rules-extra"), Some("extra.json"));
+        assert_eq!(job_matches.value_of("results"), Some("results/jobs/1"));
+    }
 }
@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::path::Path;
+use std::collections::BTreeSet;
+
+use serde_json;
+use serde_json::value::Value;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use {Error, Result};
+use super::Vulnerability;
+
+/// Triage states of the findings that are suppressed from later reports
+const SUPPRESSED_STATES: [&'static str; 2] = ["false_positive", "accepted"];
+
+/// Gets the triage key of a finding, from its name, file and description
+///
+/// Lines are not part of the key, so that triaged findings stay triaged when the code around
+/// them changes. Identical findings in the same file share their key, and their triage state.
+///
+/// The HTML report now uses the fingerprint of the finding as its key, but triage files exported
+/// from older reports use this one.
+fn get_triage_key(vuln: &Vulnerability) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.input_str(vuln.get_name());
+    sha256.input_str("\u{0}");
+    sha256.input_str(&vuln.get_file()
+        .map(|f| format!("{}", f.display()))
+        .unwrap_or_else(String::new));
+    sha256.input_str("\u{0}");
+    sha256.input_str(vuln.get_description());
+    sha256.result_str()
+}
+
+/// Findings triaged as false positives or accepted risks in the HTML report
+///
+/// The baseline is the triage file exported from the HTML report, and the findings it suppresses
+/// are not added to the results of later analyses.
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    keys: BTreeSet<String>,
+}
+
+impl Baseline {
+    /// Loads the baseline from a triage file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Baseline> {
+        let f = try!(File::open(path));
+        let triage: Value = try!(serde_json::from_reader(f));
+        Baseline::from_json(&triage)
+    }
+
+    /// Gets the baseline of the given triage JSON
+    fn from_json(triage: &Value) -> Result<Baseline> {
+        let findings = match triage.as_object()
+            .and_then(|t| t.get("findings"))
+            .and_then(|f| f.as_array()) {
+            Some(f) => f,
+            None => return Err(Error::ParseError),
+        };
+        let mut baseline = Baseline::default();
+        for finding in findings {
+            let finding = match finding.as_object() {
+                Some(f) => f,
+                None => return Err(Error::ParseError),
+            };
+            let key = finding.get("key").and_then(|k| k.as_str());
+            let state = finding.get("state").and_then(|s| s.as_str());
+            match (key, state) {
+                (Some(key), Some(state)) => {
+                    if SUPPRESSED_STATES.contains(&state) {
+                        let _ = baseline.keys.insert(String::from(key));
+                    }
+                }
+                _ => return Err(Error::ParseError),
+            }
+        }
+        Ok(baseline)
+    }
+
+    /// Returns if the given finding is suppressed by the baseline
+    pub fn contains(&self, vuln: &Vulnerability) -> bool {
+        self.keys.contains(&vuln.get_fingerprint()) || self.keys.contains(&get_triage_key(vuln))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use Criticity;
+    use results::Vulnerability;
+    use super::{Baseline, get_triage_key};
+
+    #[test]
+    fn it_baseline() {
+        let vuln = Vulnerability::new(Criticity::High,
+                                      "Weak algorithm",
+                                      "MD5 is used.",
+                                      Some("classes/com/example/A.java"),
+                                      Some(9),
+                                      Some(9),
+                                      None);
+        let moved = Vulnerability::new(Criticity::High,
+                                       "Weak algorithm",
+                                       "MD5 is used.",
+                                       Some("classes/com/example/A.java"),
+                                       Some(19),
+                                       Some(19),
+                                       None);
+        let other = Vulnerability::new(Criticity::High,
+                                       "Weak algorithm",
+                                       "MD5 is used.",
+                                       Some("classes/com/example/B.java"),
+                                       Some(9),
+                                       Some(9),
+                                       None);
+        assert_eq!(get_triage_key(&vuln), get_triage_key(&moved));
+
+        let triage = serde_json::from_str(&format!(r#"{{"package": "com.example", "findings": [
+            {{"key": "{}", "state": "false_positive"}},
+            {{"key": "{}", "state": "open"}}]}}"#,
+                                                    get_triage_key(&vuln),
+                                                    get_triage_key(&other)))
+            .unwrap();
+        let baseline = Baseline::from_json(&triage).unwrap();
+        assert!(baseline.contains(&moved));
+        assert!(!baseline.contains(&other));
+
+        let triage = serde_json::from_str(&format!(r#"{{"findings": [
+            {{"key": "{}", "state": "accepted"}}]}}"#,
+                                                    other.get_fingerprint()))
+            .unwrap();
+        let baseline = Baseline::from_json(&triage).unwrap();
+        assert!(baseline.contains(&other));
+        assert!(!baseline.contains(&vuln));
+
+        let invalid = serde_json::from_str(r#"{"findings": [{"key": "abc"}]}"#).unwrap();
+        assert!(Baseline::from_json(&invalid).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{fmt, result};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+
+use {Error, Result};
+use super::migration::migrate_report;
+
+/// Keys of the findings of each criticity in the JSON report
+const CRITICITY_KEYS: [&'static str; 5] = ["critical", "high", "medium", "low", "warnings"];
+
+/// Application of a compared report
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AppVersion {
+    package: String,
+    version: String,
+}
+
+impl fmt::Display for AppVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.version.is_empty() {
+            write!(f, "{}", self.package)
+        } else {
+            write!(f, "{} {}", self.package, self.version)
+        }
+    }
+}
+
+impl Serialize for AppVersion {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("AppVersion", 2));
+        try!(serializer.serialize_struct_elt(&mut state, "package", self.package.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.version.as_str()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Differences between the findings of two reports
+///
+/// Findings are matched by their fingerprint, or by their name, file and description if any of
+/// the reports was generated before findings had fingerprints. Lines are not used in any case,
+/// so that findings that only moved in the code are not reported as new.
+#[derive(Debug, Clone)]
+pub struct ReportDiff {
+    previous: AppVersion,
+    current: AppVersion,
+    added: Vec<Value>,
+    removed: Vec<Value>,
+    persisting: Vec<Value>,
+}
+
+impl ReportDiff {
+    /// Compares the findings of the previous and the current report
+    fn new(previous: &BTreeMap<String, Value>, current: &BTreeMap<String, Value>) -> ReportDiff {
+        let fingerprints = has_fingerprints(previous) && has_fingerprints(current);
+        let mut previous_findings = get_findings(previous, fingerprints);
+        let mut diff = ReportDiff {
+            previous: get_app_version(previous),
+            current: get_app_version(current),
+            added: Vec::new(),
+            removed: Vec::new(),
+            persisting: Vec::new(),
+        };
+        for (key, findings) in get_findings(current, fingerprints) {
+            let mut matched = previous_findings.remove(&key).unwrap_or_else(Vec::new);
+            for finding in findings {
+                if matched.pop().is_some() {
+                    diff.persisting.push(finding);
+                } else {
+                    diff.added.push(finding);
+                }
+            }
+            diff.removed.extend(matched);
+        }
+        for (_, findings) in previous_findings {
+            diff.removed.extend(findings);
+        }
+        diff
+    }
+
+    pub fn get_added(&self) -> &[Value] {
+        &self.added
+    }
+
+    pub fn get_removed(&self) -> &[Value] {
+        &self.removed
+    }
+
+    pub fn get_persisting(&self) -> &[Value] {
+        &self.persisting
+    }
+}
+
+impl Serialize for ReportDiff {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("ReportDiff", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "previous", &self.previous));
+        try!(serializer.serialize_struct_elt(&mut state, "current", &self.current));
+        try!(serializer.serialize_struct_elt(&mut state, "added", &self.added));
+        try!(serializer.serialize_struct_elt(&mut state, "removed", &self.removed));
+        try!(serializer.serialize_struct_elt(&mut state, "persisting", &self.persisting));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReportDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f,
+                      "Comparing {} with {}: {} new, {} fixed and {} persisting findings.",
+                      self.previous,
+                      self.current,
+                      self.added.len(),
+                      self.removed.len(),
+                      self.persisting.len()));
+        for &(title, findings) in &[("New findings:", &self.added),
+                                    ("Fixed findings:", &self.removed)] {
+            if findings.is_empty() {
+                continue;
+            }
+            try!(writeln!(f, ""));
+            try!(writeln!(f, "{}", title));
+            for finding in findings.iter() {
+                try!(writeln!(f, "  {}", get_finding_line(finding)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gets a line describing the given finding, with its criticity, name and location
+fn get_finding_line(finding: &Value) -> String {
+    let mut line = format!("[{}] {}",
+                           get_str(finding, "criticity"),
+                           get_str(finding, "name"));
+    if !get_str(finding, "file").is_empty() {
+        line.push_str(&format!(" in {}", get_str(finding, "file")));
+        let start_line = finding.as_object()
+            .and_then(|f| f.get("start_line"))
+            .and_then(|v| v.as_u64());
+        if let Some(start_line) = start_line {
+            line.push_str(&format!(":{}", start_line + 1));
+        }
+    }
+    line
+}
+
+/// Gets the package and version of the application of a report
+fn get_app_version(report: &BTreeMap<String, Value>) -> AppVersion {
+    let get_str = |key: &str| {
+        String::from(report.get(key).and_then(|v| v.as_str()).unwrap_or(""))
+    };
+    AppVersion {
+        package: get_str("package"),
+        version: get_str("version"),
+    }
+}
+
+/// Checks if all the findings of a report have a fingerprint
+fn has_fingerprints(report: &BTreeMap<String, Value>) -> bool {
+    CRITICITY_KEYS.iter()
+        .filter_map(|key| report.get(*key).and_then(|s| s.as_array()))
+        .all(|set| set.iter().all(|finding| !get_str(finding, "fingerprint").is_empty()))
+}
+
+/// Gets the findings of a report, grouped by the key used to match them between reports, which
+/// is their fingerprint if `fingerprints` is set
+fn get_findings(report: &BTreeMap<String, Value>,
+                fingerprints: bool)
+                -> BTreeMap<String, Vec<Value>> {
+    let mut findings = BTreeMap::new();
+    for key in &CRITICITY_KEYS {
+        if let Some(set) = report.get(*key).and_then(|s| s.as_array()) {
+            for finding in set {
+                let key = if fingerprints {
+                    String::from(get_str(finding, "fingerprint"))
+                } else {
+                    get_finding_key(finding)
+                };
+                findings.entry(key)
+                    .or_insert_with(Vec::new)
+                    .push(finding.clone());
+            }
+        }
+    }
+    findings
+}
+
+/// Gets the key of a finding, from its name, file and description
+fn get_finding_key(finding: &Value) -> String {
+    ["name", "file", "description"]
+        .iter()
+        .map(|key| get_str(finding, key))
+        .collect::<Vec<_>>()
+        .join("\u{0}")
+}
+
+/// Gets the string of the given key of a finding, or an empty string if it has no such string
+fn get_str<'a>(finding: &'a Value, key: &str) -> &'a str {
+    finding.as_object()
+        .and_then(|f| f.get(key))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+/// Gets the `results.json` file of the given path, which can be the report itself or the
+/// results folder of an application, in which case the report of its last run is used with the
+/// versioned layout
+pub fn get_report_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_dir() && !path.join("results.json").exists() && path.join("latest").exists() {
+        path.join("latest").join("results.json")
+    } else if path.is_dir() {
+        path.join("results.json")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Loads a JSON report, upgrading it to the current schema version
+fn load_report<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, Value>> {
+    let f = try!(File::open(get_report_path(path)));
+    let report: Value = try!(serde_json::from_reader(f));
+    let mut report = match report {
+        Value::Object(o) => o,
+        _ => return Err(Error::ParseError),
+    };
+    let _ = try!(migrate_report(&mut report));
+    Ok(report)
+}
+
+/// Compares the findings of the previous and the current results
+///
+/// Both paths can be `results.json` files or results folders of an application.
+pub fn diff_results<P: AsRef<Path>>(previous: P, current: P) -> Result<ReportDiff> {
+    let previous = try!(load_report(previous));
+    let current = try!(load_report(current));
+    Ok(ReportDiff::new(&previous, &current))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use serde_json;
+    use serde_json::value::Value;
+    use super::ReportDiff;
+
+    fn get_report(json: &str) -> BTreeMap<String, Value> {
+        match serde_json::from_str(json).unwrap() {
+            Value::Object(o) => o,
+            _ => panic!("the report must be an object"),
+        }
+    }
+
+    #[test]
+    fn it_report_diff() {
+        let previous = get_report(r#"{"package": "com.example", "version": "1.0",
+            "high": [{"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 10, "description": "MD5 is used."},
+                     {"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 20, "description": "MD5 is used."}],
+            "low": [{"criticity": "low", "name": "Log", "file": "B.java", "start_line": 3,
+                     "description": "Logs are written."}]}"#);
+        let current = get_report(r#"{"package": "com.example", "version": "1.1",
+            "critical": [{"criticity": "critical", "name": "Debuggable", "file": null,
+                          "start_line": null, "description": "The app is debuggable."}],
+            "high": [{"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 12, "description": "MD5 is used."}]}"#);
+
+        let diff = ReportDiff::new(&previous, &current);
+        assert_eq!(diff.get_added().len(), 1);
+        assert_eq!(diff.get_removed().len(), 2);
+        assert_eq!(diff.get_persisting().len(), 1);
+
+        let text = format!("{}", diff);
+        assert!(text.starts_with("Comparing com.example 1.0 with com.example 1.1: 1 new, 2 \
+                                   fixed and 1 persisting findings.\n"));
+        assert!(text.contains("New findings:\n  [critical] Debuggable\n"));
+        assert!(text.contains("  [low] Log in B.java:4\n"));
+
+        let json = serde_json::value::to_value(&diff);
+        let json = json.as_object().unwrap();
+        assert_eq!(json.get("added").and_then(|a| a.as_array()).map(|a| a.len()), Some(1));
+        assert_eq!(json.get("previous")
+                       .and_then(|p| p.as_object())
+                       .and_then(|p| p.get("version"))
+                       .and_then(|v| v.as_str()),
+                   Some("1.0"));
+    }
+
+    #[test]
+    fn it_report_diff_fingerprints() {
+        let previous = get_report(r#"{"package": "com.example", "version": "1.0",
+            "high": [{"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 10, "description": "MD5 is used in a().",
+                      "fingerprint": "a1"},
+                     {"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 20, "description": "MD5 is used.", "fingerprint": "b2"}]}"#);
+        let current = get_report(r#"{"package": "com.example", "version": "1.1",
+            "high": [{"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 12, "description": "MD5 is used in method a().",
+                      "fingerprint": "a1"}]}"#);
+        let diff = ReportDiff::new(&previous, &current);
+        assert_eq!(diff.get_added().len(), 0);
+        assert_eq!(diff.get_removed().len(), 1);
+        assert_eq!(diff.get_persisting().len(), 1);
+
+        // Reports without fingerprints are compared by name, file and description
+        let current = get_report(r#"{"package": "com.example", "version": "1.1",
+            "high": [{"criticity": "high", "name": "Weak algorithm", "file": "A.java",
+                      "start_line": 12, "description": "MD5 is used in method a().",
+                      "fingerprint": null}]}"#);
+        let diff = ReportDiff::new(&previous, &current);
+        assert_eq!(diff.get_added().len(), 1);
+        assert_eq!(diff.get_removed().len(), 2);
+    }
+}
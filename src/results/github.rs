@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::Write;
+
+use {Config, Result, Criticity};
+use super::{Results, Vulnerability};
+
+/// Name of the file with the GitHub annotations in the results folder of the application
+const ANNOTATIONS_FILE: &'static str = "github-annotations.txt";
+
+/// Gets the level of the GitHub annotation of the given criticity
+fn get_level(criticity: Criticity) -> &'static str {
+    match criticity {
+        Criticity::Warning => "notice",
+        Criticity::Low | Criticity::Medium => "warning",
+        Criticity::High | Criticity::Critical => "error",
+    }
+}
+
+/// Escapes the message of a workflow command
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command, such as the file or the title
+fn escape_property(property: &str) -> String {
+    escape_data(property).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Gets the workflow command annotating the vulnerability in its source file, if it was mapped
+/// to the source code of the application
+fn get_annotation(vuln: &Vulnerability) -> Option<String> {
+    let file = match vuln.get_source_file() {
+        Some(f) => f,
+        None => return None,
+    };
+    let mut properties = format!("file={}", escape_property(&file.replace('\\', "/")));
+    if let Some(line) = vuln.get_source_line() {
+        properties.push_str(&format!(",line={}", line + 1));
+    }
+    properties.push_str(&format!(",title={}", escape_property(vuln.get_name())));
+    Some(format!("::{} {}::{}",
+                 get_level(vuln.get_criticity()),
+                 properties,
+                 escape_data(vuln.get_description())))
+}
+
+/// Prints the GitHub Actions workflow commands annotating the findings in the source code of
+/// the application, and saves them in the results folder
+///
+/// Findings in files that were not mapped to the source code, such as the ones in libraries,
+/// are not annotated.
+pub fn generate_github_annotations(results: &Results, config: &Config) -> Result<()> {
+    let mut annotations = String::new();
+    let mut skipped = 0;
+    for vuln in results.critical
+        .iter()
+        .chain(results.high.iter())
+        .chain(results.medium.iter())
+        .chain(results.low.iter())
+        .chain(results.warnings.iter()) {
+        match get_annotation(vuln) {
+            Some(annotation) => {
+                println!("{}", annotation);
+                annotations.push_str(&annotation);
+                annotations.push('\n');
+            }
+            None => skipped += 1,
+        }
+    }
+    if config.is_verbose() && skipped > 0 {
+        println!("{} findings could not be mapped to the source code, so they were not \
+                  annotated.",
+                 skipped);
+    }
+
+    let mut f = try!(File::create(results.get_report_folder(config).join(ANNOTATIONS_FILE)));
+    try!(f.write_all(annotations.as_bytes()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use Criticity;
+    use results::Vulnerability;
+    use super::get_annotation;
+
+    #[test]
+    fn it_annotation() {
+        let mut vuln = Vulnerability::new(Criticity::High,
+                                          "Weak hash",
+                                          "MD5 is used: 100% broken,\nuse SHA-256.",
+                                          Some("classes/com/example/Main.java"),
+                                          Some(2),
+                                          Some(2),
+                                          None);
+        assert_eq!(get_annotation(&vuln), None);
+
+        vuln.set_source("app/src/main/java/com/example/Main.java", Some(4));
+        assert_eq!(get_annotation(&vuln),
+                   Some(String::from("::error file=app/src/main/java/com/example/Main.java,\
+                                      line=5,title=Weak hash::MD5 is used: 100%25 \
+                                      broken,%0Ause SHA-256.")));
+    }
+}
@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::Write;
+
+use serde_json::builder::{ObjectBuilder, ArrayBuilder};
+use serde_json::value::Value;
+use chrono::Local;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use {Config, Result, Criticity};
+use super::{Results, Vulnerability};
+
+/// Name of the GitLab SAST report in the results folder of the application
+const REPORT_FILE: &'static str = "gl-sast-report.json";
+
+/// Version of the GitLab security report schema the report follows
+const SCHEMA_VERSION: &'static str = "15.0.7";
+
+/// Format of the scan times in the GitLab security report schema
+const TIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S";
+
+/// Gets the GitLab severity of the given criticity
+fn get_severity(criticity: Criticity) -> &'static str {
+    match criticity {
+        Criticity::Warning => "Info",
+        Criticity::Low => "Low",
+        Criticity::Medium => "Medium",
+        Criticity::High => "High",
+        Criticity::Critical => "Critical",
+    }
+}
+
+/// Gets a stable identifier of the finding, so that GitLab can track it across pipelines
+fn get_finding_id(vuln: &Vulnerability) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.input_str(vuln.get_name());
+    sha256.input_str("\u{0}");
+    sha256.input_str(&vuln.get_file()
+        .map(|f| format!("{}", f.display()))
+        .unwrap_or_else(String::new));
+    sha256.input_str("\u{0}");
+    sha256.input_str(&vuln.get_start_line()
+        .map(|l| l.to_string())
+        .unwrap_or_else(String::new));
+    sha256.input_str("\u{0}");
+    sha256.input_str(vuln.get_description());
+    sha256.result_str()
+}
+
+/// Adds the identifiers of the finding: its rule, and its CWE, MASVS and OWASP mappings
+fn push_identifiers(builder: ArrayBuilder, vuln: &Vulnerability) -> ArrayBuilder {
+    let mut builder = builder.push_object(|b| {
+        b.insert("type", "super_rule")
+            .insert("name", vuln.get_name())
+            .insert("value", vuln.get_name())
+    });
+    let metadata = vuln.get_metadata();
+    for cwe in metadata.get_cwe() {
+        let number = cwe.trim_left_matches("CWE-");
+        builder = builder.push_object(|b| {
+            b.insert("type", "cwe")
+                .insert("name", cwe.as_str())
+                .insert("value", number)
+                .insert("url",
+                        format!("https://cwe.mitre.org/data/definitions/{}.html", number))
+        });
+    }
+    for masvs in metadata.get_masvs() {
+        builder = builder.push_object(|b| {
+            b.insert("type", "masvs")
+                .insert("name", masvs.as_str())
+                .insert("value", masvs.as_str())
+        });
+    }
+    for owasp in metadata.get_owasp_mobile() {
+        builder = builder.push_object(|b| {
+            b.insert("type", "owasp_mobile")
+                .insert("name", format!("OWASP Mobile Top 10 {}", owasp))
+                .insert("value", owasp.as_str())
+        });
+    }
+    builder
+}
+
+/// Gets the GitLab vulnerability object of the given finding
+fn get_vulnerability(vuln: &Vulnerability) -> Value {
+    let metadata = vuln.get_metadata();
+    let mut builder = ObjectBuilder::new()
+        .insert("id", get_finding_id(vuln))
+        .insert("name", vuln.get_name())
+        .insert("description", vuln.get_description())
+        .insert("severity", get_severity(vuln.get_criticity()))
+        .insert_object("location", |b| {
+            let mut b = b;
+            if let Some(file) = vuln.get_file() {
+                b = b.insert("file", format!("{}", file.display()));
+            }
+            if let Some(start_line) = vuln.get_start_line() {
+                b = b.insert("start_line", start_line + 1);
+            }
+            if let Some(end_line) = vuln.get_end_line() {
+                b = b.insert("end_line", end_line + 1);
+            }
+            b
+        })
+        .insert_array("identifiers", |b| push_identifiers(b, vuln));
+    if let Some(remediation) = metadata.get_remediation() {
+        builder = builder.insert("solution", remediation);
+    }
+    if !metadata.get_references().is_empty() {
+        builder = builder.insert_array("links", |b| {
+            metadata.get_references()
+                .iter()
+                .fold(b, |b, url| b.push_object(|o| o.insert("url", url.as_str())))
+        });
+    }
+    builder.build()
+}
+
+/// Gets the GitLab SAST report of the given results
+fn get_report(results: &Results, time: &str) -> Value {
+    let tool = |b: ObjectBuilder| {
+        b.insert("id", "super")
+            .insert("name", "SUPER Android Analyzer")
+            .insert("version", env!("CARGO_PKG_VERSION"))
+            .insert_object("vendor", |v| v.insert("name", "SUPER Team"))
+    };
+    ObjectBuilder::new()
+        .insert("version", SCHEMA_VERSION)
+        .insert_object("scan", |b| {
+            b.insert_object("analyzer", &tool)
+                .insert_object("scanner", &tool)
+                .insert("type", "sast")
+                .insert("start_time", time)
+                .insert("end_time", time)
+                .insert("status", "success")
+        })
+        .insert_array("vulnerabilities", |b| {
+            [&results.critical, &results.high, &results.medium, &results.low, &results.warnings]
+                .iter()
+                .flat_map(|set| set.iter())
+                .fold(b, |b, vuln| b.push(get_vulnerability(vuln)))
+        })
+        .build()
+}
+
+/// Generates the report in the GitLab SAST format, so that the findings are shown in the
+/// security widgets of merge requests and in the security dashboard of GitLab
+pub fn generate_gitlab_report(results: &Results, config: &Config) -> Result<()> {
+    let time = format!("{}", Local::now().format(TIME_FORMAT));
+    let mut f = try!(File::create(results.get_report_folder(config).join(REPORT_FILE)));
+    try!(f.write_all(&format!("{:?}", get_report(results, &time)).into_bytes()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use Criticity;
+    use results::{Vulnerability, RuleMetadata};
+    use super::{get_severity, get_vulnerability, get_finding_id};
+
+    #[test]
+    fn it_get_vulnerability() {
+        let mut vuln = Vulnerability::new(Criticity::High,
+                                          "Weak algorithm",
+                                          "MD5 is used.",
+                                          Some("classes/com/example/A.java"),
+                                          Some(9),
+                                          Some(10),
+                                          None);
+        vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-327")],
+                                            vec![String::from("MASVS-CRYPTO-1")],
+                                            Vec::new(),
+                                            vec![String::from("https://example.com/md5")],
+                                            Some(String::from("Use SHA-256."))));
+        let value = get_vulnerability(&vuln);
+        let object = value.as_object().unwrap();
+        assert_eq!(object.get("id").and_then(|i| i.as_str()),
+                   Some(get_finding_id(&vuln).as_str()));
+        assert_eq!(object.get("severity").and_then(|s| s.as_str()), Some("High"));
+        assert_eq!(object.get("solution").and_then(|s| s.as_str()), Some("Use SHA-256."));
+
+        let location = object.get("location").and_then(|l| l.as_object()).unwrap();
+        assert_eq!(location.get("file").and_then(|f| f.as_str()),
+                   Some("classes/com/example/A.java"));
+        assert_eq!(location.get("start_line").and_then(|l| l.as_u64()), Some(10));
+        assert_eq!(location.get("end_line").and_then(|l| l.as_u64()), Some(11));
+
+        let identifiers = object.get("identifiers").and_then(|i| i.as_array()).unwrap();
+        assert_eq!(identifiers.len(), 3);
+        let cwe = identifiers[1].as_object().unwrap();
+        assert_eq!(cwe.get("value").and_then(|v| v.as_str()), Some("327"));
+        assert_eq!(object.get("links").and_then(|l| l.as_array()).map(|l| l.len()),
+                   Some(1));
+
+        assert_eq!(get_severity(Criticity::Warning), "Info");
+    }
+}
@@ -0,0 +1,205 @@
+use std::result;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::ser::{Serialize, Serializer};
+
+use Criticity;
+use super::Vulnerability;
+
+/// Findings with the same name, found by the same rule, with the number of times it was found
+/// and how many of them are listed in the reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindingGroup {
+    name: String,
+    criticity: Criticity,
+    count: usize,
+    files: BTreeSet<String>,
+    listed: usize,
+}
+
+impl FindingGroup {
+    /// Creates an empty group for the findings with the name and criticity of the given one
+    fn new(vuln: &Vulnerability) -> FindingGroup {
+        FindingGroup {
+            name: String::from(vuln.get_name()),
+            criticity: vuln.get_criticity(),
+            count: 0,
+            files: BTreeSet::new(),
+            listed: 0,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn get_criticity(&self) -> Criticity {
+        self.criticity
+    }
+
+    /// Gets the number of findings of the group
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    /// Gets the number of files with findings of the group
+    pub fn get_files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Gets the number of findings of the group that are listed in the reports
+    pub fn get_listed(&self) -> usize {
+        self.listed
+    }
+
+    /// Gets the number of findings of the group that are not listed in the reports
+    pub fn get_unlisted(&self) -> usize {
+        self.count - self.listed
+    }
+}
+
+impl Serialize for FindingGroup {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("FindingGroup", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "criticity", self.criticity));
+        try!(serializer.serialize_struct_elt(&mut state, "count", self.count));
+        try!(serializer.serialize_struct_elt(&mut state, "files", self.files.len()));
+        try!(serializer.serialize_struct_elt(&mut state, "listed", self.listed));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Removes the findings identical to a previous one but in another file, such as the ones in
+/// duplicated generated classes, and gets the number of removed findings
+///
+/// Findings are identical if they have the same name, description, line and code.
+pub fn deduplicate(set: &mut BTreeSet<Vulnerability>) -> usize {
+    let before = set.len();
+    let unique: BTreeSet<Vulnerability> = {
+        let mut seen = BTreeSet::new();
+        set.iter()
+            .filter(|vuln| {
+                vuln.get_code().is_none() ||
+                seen.insert((vuln.get_name(),
+                             vuln.get_description(),
+                             vuln.get_start_line(),
+                             vuln.get_code()))
+            })
+            .cloned()
+            .collect()
+    };
+    *set = unique;
+    before - set.len()
+}
+
+/// Groups the findings by name, counting the first `max_samples` findings of each group, if it's
+/// not 0, as listed in the reports
+pub fn group_findings(set: &BTreeSet<Vulnerability>, max_samples: usize) -> Vec<FindingGroup> {
+    let mut groups: Vec<FindingGroup> = Vec::new();
+    for vuln in set {
+        let position = groups.iter().position(|g| g.name == vuln.get_name());
+        let i = match position {
+            Some(i) => i,
+            None => {
+                groups.push(FindingGroup::new(vuln));
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[i];
+        group.count += 1;
+        if let Some(file) = vuln.get_file() {
+            let _ = group.files.insert(file.to_string_lossy().into_owned());
+        }
+        if max_samples == 0 || group.listed < max_samples {
+            group.listed += 1;
+        }
+    }
+    groups
+}
+
+/// Gets the findings of the set listed in the reports, with their index in the set, which is
+/// part of their ID
+///
+/// Only the first `max_samples` findings of each name are listed, if it's not 0. The rest are
+/// still part of the JSON report, the counts and the risk score.
+pub fn get_listed_findings(set: &BTreeSet<Vulnerability>,
+                           max_samples: usize)
+                           -> Vec<(usize, &Vulnerability)> {
+    let mut listed: BTreeMap<&str, usize> = BTreeMap::new();
+    set.iter()
+        .enumerate()
+        .filter(|&(_, vuln)| {
+            let count = listed.entry(vuln.get_name()).or_insert(0);
+            *count += 1;
+            max_samples == 0 || *count <= max_samples
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use Criticity;
+    use results::Vulnerability;
+    use super::{deduplicate, group_findings, get_listed_findings};
+
+    fn get_vuln(name: &str, file: &str, line: usize, code: &str) -> Vulnerability {
+        Vulnerability::new(Criticity::Medium,
+                           name,
+                           "Description.",
+                           Some(file),
+                           Some(line),
+                           Some(line),
+                           Some(String::from(code)))
+    }
+
+    #[test]
+    fn it_deduplicate() {
+        let mut set = BTreeSet::new();
+        let _ = set.insert(get_vuln("Weak hash", "classes/a/R$1.java", 3, "md5()"));
+        let _ = set.insert(get_vuln("Weak hash", "classes/b/R$1.java", 3, "md5()"));
+        let _ = set.insert(get_vuln("Weak hash", "classes/c/Main.java", 3, "md5(data)"));
+        let _ = set.insert(get_vuln("Log", "classes/b/R$1.java", 3, "md5()"));
+
+        assert_eq!(deduplicate(&mut set), 1);
+        assert_eq!(set.len(), 3);
+        assert!(set.iter().any(|v| v.get_file().unwrap().to_str() == Some("classes/a/R$1.java")));
+        assert!(!set.iter().any(|v| {
+            v.get_name() == "Weak hash" &&
+            v.get_file().unwrap().to_str() == Some("classes/b/R$1.java")
+        }));
+    }
+
+    #[test]
+    fn it_group_findings() {
+        let mut set = BTreeSet::new();
+        for i in 0..5 {
+            let _ = set.insert(get_vuln("Log", "classes/a/Main.java", i, "Log.d()"));
+        }
+        let _ = set.insert(get_vuln("Log", "classes/b/Main.java", 1, "Log.d()"));
+        let _ = set.insert(get_vuln("Weak hash", "classes/a/Main.java", 9, "md5()"));
+
+        let groups = group_findings(&set, 0);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].count, 6);
+        assert_eq!(groups[0].listed, 6);
+        assert_eq!(get_listed_findings(&set, 0).len(), 7);
+
+        let groups = group_findings(&set, 2);
+        assert_eq!(set.len(), 7);
+        let listed = get_listed_findings(&set, 2);
+        assert_eq!(listed.iter().map(|&(i, _)| i).collect::<Vec<_>>(), vec![0, 1, 5]);
+        assert_eq!(groups[0].name, "Log");
+        assert_eq!(groups[0].count, 6);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].listed, 2);
+        assert_eq!(groups[0].get_unlisted(), 4);
+        assert_eq!(groups[1].name, "Weak hash");
+        assert_eq!(groups[1].get_unlisted(), 0);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{fs, result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+use chrono::Local;
+
+use {Config, Result, Error, file_exists};
+use super::Results;
+
+/// Folder of the results folder where the analysis history of each application is stored
+const HISTORY_FOLDER: &'static str = "history";
+
+/// Keys and labels of the criticities in the history, from warnings to critical
+const CRITICITIES: [(&'static str, &'static str); 5] = [("warnings", "Warnings"),
+                                                          ("low", "Low"),
+                                                          ("medium", "Medium"),
+                                                          ("high", "High"),
+                                                          ("critical", "Critical")];
+
+/// Colors of the criticities in the trend chart
+const COLORS: [&'static str; 5] = ["#888888", "#4a90d9", "#e6b800", "#e67300", "#cc0000"];
+
+/// Size of the trend chart, and margin around the plotted area, in pixels
+const CHART_WIDTH: usize = 800;
+const CHART_HEIGHT: usize = 300;
+const CHART_MARGIN: usize = 40;
+
+/// Summary of an analysis, stored in the history of the application
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    date: String,
+    version: String,
+    version_num: i32,
+    /// Number of findings of each criticity, from warnings to critical
+    counts: [usize; 5],
+    /// Number of findings of each rule, by name
+    rules: BTreeMap<String, usize>,
+}
+
+impl HistoryEntry {
+    /// Creates the history entry of the given results
+    pub fn new(results: &Results) -> HistoryEntry {
+        let sets = [&results.warnings,
+                    &results.low,
+                    &results.medium,
+                    &results.high,
+                    &results.critical];
+        let mut counts = [0; 5];
+        let mut rules = BTreeMap::new();
+        for (count, set) in counts.iter_mut().zip(sets.iter()) {
+            *count = set.len();
+            for vuln in set.iter() {
+                *rules.entry(String::from(vuln.get_name())).or_insert(0) += 1;
+            }
+        }
+        HistoryEntry {
+            date: Local::now().to_rfc3339(),
+            version: results.app_version.clone(),
+            version_num: results.app_version_num,
+            counts: counts,
+            rules: rules,
+        }
+    }
+
+    /// Gets the label of the version of the entry
+    fn get_version_label(&self) -> String {
+        if self.version.is_empty() {
+            format!("#{}", self.version_num)
+        } else {
+            self.version.clone()
+        }
+    }
+
+    fn from_json(json: &str) -> Result<HistoryEntry> {
+        let value: Value = try!(serde_json::from_str(json));
+        let entry = match value.as_object() {
+            Some(e) => e,
+            None => return Err(Error::ParseError),
+        };
+        let get_str = |key: &str| match entry.get(key).and_then(|v| v.as_str()) {
+            Some(s) => Ok(String::from(s)),
+            None => Err(Error::ParseError),
+        };
+        let get_count = |value: Option<&Value>| match value.and_then(|v| v.as_u64()) {
+            Some(c) => Ok(c as usize),
+            None => Err(Error::ParseError),
+        };
+
+        let mut counts = [0; 5];
+        for (count, &(key, _)) in counts.iter_mut().zip(CRITICITIES.iter()) {
+            *count = try!(get_count(entry.get(key)));
+        }
+        let mut rules = BTreeMap::new();
+        match entry.get("rules") {
+            Some(&Value::Object(ref r)) => {
+                for (rule, count) in r {
+                    let _ = rules.insert(rule.clone(), try!(get_count(Some(count))));
+                }
+            }
+            _ => return Err(Error::ParseError),
+        }
+        Ok(HistoryEntry {
+            date: try!(get_str("date")),
+            version: try!(get_str("version")),
+            version_num: match entry.get("version_num").and_then(|v| v.as_i64()) {
+                Some(v) => v as i32,
+                None => return Err(Error::ParseError),
+            },
+            counts: counts,
+            rules: rules,
+        })
+    }
+}
+
+impl Serialize for HistoryEntry {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("HistoryEntry", 9));
+        try!(serializer.serialize_struct_elt(&mut state, "date", self.date.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.version.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version_num", self.version_num));
+        for (&(key, _), count) in CRITICITIES.iter().zip(self.counts.iter()) {
+            try!(serializer.serialize_struct_elt(&mut state, key, *count));
+        }
+        try!(serializer.serialize_struct_elt(&mut state, "rules", &self.rules));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the path of the history file of the application of the configuration
+///
+/// It's stored outside the results folder of the application, since that folder is removed
+/// each time the application is analyzed again.
+fn get_history_path(config: &Config) -> PathBuf {
+    PathBuf::from(config.get_results_folder())
+        .join(HISTORY_FOLDER)
+        .join(format!("{}.jsonl", config.get_app_id()))
+}
+
+/// Appends the given entry to the history of the application, as a JSON line
+pub fn append_history(entry: &HistoryEntry, config: &Config) -> Result<()> {
+    let path = get_history_path(config);
+    if let Some(folder) = path.parent() {
+        if !file_exists(folder) {
+            try!(fs::create_dir_all(folder));
+        }
+    }
+    let mut f = try!(OpenOptions::new().create(true).append(true).open(path));
+    try!(f.write_all(format!("{:?}\n", serde_json::value::to_value(entry)).as_bytes()));
+    Ok(())
+}
+
+/// Loads the history of the application of the configuration, in analysis order
+pub fn load_history(config: &Config) -> Result<Vec<HistoryEntry>> {
+    let path = get_history_path(config);
+    if !file_exists(&path) {
+        return Ok(Vec::new());
+    }
+    let mut history = String::new();
+    let mut f = try!(File::open(path));
+    let _ = try!(f.read_to_string(&mut history));
+    parse_history(&history)
+}
+
+fn parse_history(history: &str) -> Result<Vec<HistoryEntry>> {
+    let mut entries = Vec::new();
+    for line in history.lines().filter(|l| !l.trim().is_empty()) {
+        entries.push(try!(HistoryEntry::from_json(line)));
+    }
+    Ok(entries)
+}
+
+/// Gets the latest analysis of each version, sorted by version number
+fn get_versions(entries: &[HistoryEntry]) -> Vec<&HistoryEntry> {
+    let mut versions: BTreeMap<(i32, &str), &HistoryEntry> = BTreeMap::new();
+    for entry in entries {
+        let _ = versions.insert((entry.version_num, entry.version.as_str()), entry);
+    }
+    versions.into_iter().map(|(_, e)| e).collect()
+}
+
+/// Generates the SVG chart of the findings of each criticity across the given versions
+fn get_trend_chart(versions: &[&HistoryEntry]) -> String {
+    let max = versions.iter()
+        .flat_map(|v| v.counts.iter())
+        .cloned()
+        .max()
+        .unwrap_or(0);
+    let max = if max == 0 { 1 } else { max };
+    let plot_width = CHART_WIDTH - 2 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2 * CHART_MARGIN;
+    let get_x = |i: usize| if versions.len() > 1 {
+        CHART_MARGIN + i * plot_width / (versions.len() - 1)
+    } else {
+        CHART_WIDTH / 2
+    };
+    let get_y = |count: usize| CHART_HEIGHT - CHART_MARGIN - count * plot_height / max;
+
+    let mut svg = format!("<svg class=\"trend\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} \
+                           {1}\" xmlns=\"http://www.w3.org/2000/svg\">",
+                          CHART_WIDTH,
+                          CHART_HEIGHT);
+    svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#000\"/>\
+                           <line x1=\"{0}\" y1=\"{3}\" x2=\"{0}\" y2=\"{1}\" stroke=\"#000\"/>\
+                           <text x=\"{4}\" y=\"{5}\" text-anchor=\"end\">{6}</text>",
+                          CHART_MARGIN,
+                          CHART_HEIGHT - CHART_MARGIN,
+                          CHART_WIDTH - CHART_MARGIN,
+                          CHART_MARGIN,
+                          CHART_MARGIN - 5,
+                          CHART_MARGIN + 5,
+                          max));
+    for (i, version) in versions.iter().enumerate() {
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                              get_x(i),
+                              CHART_HEIGHT - CHART_MARGIN + 20,
+                              Results::html_escape(&version.get_version_label())));
+    }
+    for (c, &(_, label)) in CRITICITIES.iter().enumerate() {
+        let points: Vec<_> = versions.iter()
+            .enumerate()
+            .map(|(i, v)| format!("{},{}", get_x(i), get_y(v.counts[c])))
+            .collect();
+        svg.push_str(&format!("<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" \
+                               points=\"{}\"><title>{}</title></polyline>",
+                              COLORS[c],
+                              points.join(" "),
+                              label));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>",
+                              CHART_MARGIN + c * plot_width / CRITICITIES.len(),
+                              CHART_MARGIN / 2,
+                              COLORS[c],
+                              label));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Generates the trend page of the application, with the findings across its analyzed versions
+///
+/// It's saved as `trend.html` in the report folder of the given results, and includes the
+/// criticity totals and the count of each rule of every version.
+pub fn generate_trend_report(results: &Results, config: &Config) -> Result<()> {
+    let entries = try!(load_history(config));
+    let versions = get_versions(&entries);
+
+    let mut f = try!(File::create(results.get_report_folder(config).join("trend.html")));
+    try!(f.write_all(b"<!DOCTYPE html>"));
+    try!(f.write_all(b"<html lang=\"en\">"));
+    try!(f.write_all(b"<head>"));
+    try!(f.write_all(b"<title>Findings trend</title>"));
+    try!(f.write_all(b"<meta charset=\"UTF-8\">"));
+    try!(f.write_all(b"<link rel=\"stylesheet\" href=\"css/style.css\">"));
+    try!(f.write_all(b"</head>"));
+    try!(f.write_all(b"<body>"));
+    try!(f.write_all(b"<section class=\"report\">"));
+    try!(f.write_all(&format!("<h1 id=\"title\">Findings trend of {}</h1>",
+                              Results::html_escape(config.get_app_id()))
+        .into_bytes()));
+    try!(f.write_all(b"<p><a href=\"index.html\" title=\"Report\">Back to the report</a></p>"));
+    try!(f.write_all(&format!("<p>{} analyses of {} versions recorded.</p>",
+                              entries.len(),
+                              versions.len())
+        .into_bytes()));
+    try!(f.write_all(get_trend_chart(&versions).as_bytes()));
+
+    // Totals of each version
+    try!(f.write_all(b"<h2>Findings by version</h2><table><thead><tr><th>Version</th>\
+                       <th>Analyzed</th>"));
+    for &(_, label) in &CRITICITIES {
+        try!(f.write_all(&format!("<th>{}</th>", label).into_bytes()));
+    }
+    try!(f.write_all(b"</tr></thead><tbody>"));
+    for version in &versions {
+        try!(f.write_all(&format!("<tr><td>{}</td><td>{}</td>",
+                                  Results::html_escape(&version.get_version_label()),
+                                  Results::html_escape(&version.date))
+            .into_bytes()));
+        for count in &version.counts {
+            try!(f.write_all(&format!("<td>{}</td>", count).into_bytes()));
+        }
+        try!(f.write_all(b"</tr>"));
+    }
+    try!(f.write_all(b"</tbody></table>"));
+
+    // Counts of each rule
+    let mut rules: Vec<&str> = versions.iter()
+        .flat_map(|v| v.rules.keys())
+        .map(|r| r.as_str())
+        .collect();
+    rules.sort();
+    rules.dedup();
+    try!(f.write_all(b"<h2>Findings by rule</h2><table><thead><tr><th>Rule</th>"));
+    for version in &versions {
+        try!(f.write_all(&format!("<th>{}</th>",
+                                  Results::html_escape(&version.get_version_label()))
+            .into_bytes()));
+    }
+    try!(f.write_all(b"</tr></thead><tbody>"));
+    for rule in rules {
+        try!(f.write_all(&format!("<tr><td>{}</td>", Results::html_escape(rule)).into_bytes()));
+        for version in &versions {
+            try!(f.write_all(&format!("<td>{}</td>", version.rules.get(rule).unwrap_or(&0))
+                .into_bytes()));
+        }
+        try!(f.write_all(b"</tr>"));
+    }
+    try!(f.write_all(b"</tbody></table>"));
+
+    try!(f.write_all(b"</section>"));
+    try!(f.write_all(b"</body>"));
+    try!(f.write_all(b"</html>"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::{HistoryEntry, parse_history, get_versions, get_trend_chart};
+
+    fn get_entry(version: &str, version_num: i32, high: usize) -> HistoryEntry {
+        let mut rules = BTreeMap::new();
+        let _ = rules.insert(String::from("Weak algorithm"), high);
+        HistoryEntry {
+            date: String::from("2016-12-01T10:00:00+01:00"),
+            version: String::from(version),
+            version_num: version_num,
+            counts: [1, 0, 2, high, 0],
+            rules: rules,
+        }
+    }
+
+    #[test]
+    fn it_history_entry_json() {
+        let entry = get_entry("1.0", 10, 3);
+        let json = format!("{:?}\n\n{:?}\n",
+                           ::serde_json::value::to_value(&entry),
+                           ::serde_json::value::to_value(&get_entry("1.1", 11, 1)));
+        let history = parse_history(&json).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0], entry);
+        assert!(parse_history("{\"date\": \"2016-12-01\"}").is_err());
+    }
+
+    #[test]
+    fn it_get_versions() {
+        let entries = vec![get_entry("1.1", 11, 5),
+                           get_entry("1.0", 10, 3),
+                           get_entry("1.1", 11, 1)];
+        let versions = get_versions(&entries);
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, "1.0");
+        assert_eq!(versions[1].counts[3], 1);
+
+        let chart = get_trend_chart(&versions);
+        assert!(chart.starts_with("<svg"));
+        assert!(chart.contains("<text x=\"760\" y=\"280\" text-anchor=\"middle\">1.1</text>"));
+        assert!(chart.contains("points=\"40,40 760,187\""));
+    }
+}
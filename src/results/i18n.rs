@@ -0,0 +1,127 @@
+use std::result;
+use std::io::Write;
+use std::collections::BTreeMap;
+
+use handlebars::{Handlebars, Helper, HelperDef, RenderContext, RenderError};
+use serde_json;
+use serde_json::value::Value;
+
+use super::Results;
+
+/// Languages of the reports
+pub const LANGUAGES: [&'static str; 2] = ["en", "es"];
+
+/// Language of the strings used when a bundle doesn't have a translation
+const DEFAULT_LANGUAGE: &'static str = "en";
+
+/// Built-in string bundles of each language
+const BUNDLES: [(&'static str, &'static str); 2] = [("en", include_str!("locales/en.json")),
+                                                    ("es", include_str!("locales/es.json"))];
+
+/// Returns if reports can be generated in the given language
+pub fn is_language<S: AsRef<str>>(language: S) -> bool {
+    LANGUAGES.contains(&language.as_ref())
+}
+
+/// Strings of the reports in a language
+#[derive(Debug, Clone)]
+pub struct Locale {
+    strings: BTreeMap<String, String>,
+}
+
+impl Locale {
+    /// Loads the strings of the given language, using the English ones for the strings it
+    /// doesn't translate
+    pub fn new<S: AsRef<str>>(language: S) -> Locale {
+        let mut locale = Locale { strings: BTreeMap::new() };
+        locale.load_bundle(DEFAULT_LANGUAGE);
+        if language.as_ref() != DEFAULT_LANGUAGE {
+            locale.load_bundle(language.as_ref());
+        }
+        locale
+    }
+
+    /// Loads the built-in bundle of the given language, if it exists
+    fn load_bundle(&mut self, language: &str) {
+        let bundle = match BUNDLES.iter().find(|&&(l, _)| l == language) {
+            Some(&(_, bundle)) => bundle,
+            None => return,
+        };
+        let bundle: Value = serde_json::from_str(bundle).unwrap();
+        if let Some(strings) = bundle.as_object() {
+            for (key, string) in strings {
+                if let Some(string) = string.as_str() {
+                    let _ = self.strings.insert(key.clone(), String::from(string));
+                }
+            }
+        }
+    }
+
+    /// Gets the string with the given key, or the key itself if there is no such string
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map_or(key, |s| s.as_str())
+    }
+
+    /// Gets the string with the given key, replacing the `{0}`, `{1}`… placeholders with the
+    /// given arguments
+    pub fn format<S: AsRef<str>>(&self, key: &str, args: &[S]) -> String {
+        args.iter().enumerate().fold(String::from(self.get(key)), |string, (i, arg)| {
+            string.replace(&format!("{{{}}}", i), arg.as_ref())
+        })
+    }
+}
+
+/// Translation helper of the report templates: `{{t "key" arg0 arg1…}}`
+///
+/// The strings of the bundles can contain markup, but the arguments are escaped.
+impl HelperDef for Locale {
+    fn call(&self,
+            h: &Helper,
+            _: &Handlebars,
+            rc: &mut RenderContext)
+            -> result::Result<(), RenderError> {
+        let key = match h.param(0).and_then(|p| p.value().as_str()) {
+            Some(key) => key,
+            None => return Err(RenderError::new("the first parameter of `t` must be a string")),
+        };
+        let args = h.params()
+            .iter()
+            .skip(1)
+            .map(|p| match *p.value() {
+                Value::String(ref s) => Results::html_escape(s),
+                ref v => Results::html_escape(&format!("{:?}", v)),
+            })
+            .collect::<Vec<_>>();
+        try!(rc.writer.write_all(self.format(key, &args).as_bytes()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use serde_json::value::Value;
+    use super::{Locale, BUNDLES, is_language};
+
+    #[test]
+    fn it_locale() {
+        let english = Locale::new("en");
+        assert_eq!(english.get("file"), "File");
+        assert_eq!(english.get("unknown_key"), "unknown_key");
+        assert_eq!(english.format("total_vulnerabilities", &["3"]),
+                   "Total vulnerabilities found: 3");
+
+        let spanish = Locale::new("es");
+        assert_eq!(spanish.get("file"), "Archivo");
+        assert_eq!(spanish.format("markdown_title", &["com.example"]),
+                   "Informe de vulnerabilidades de com.example");
+
+        // Every bundle translates every English string
+        for &(_, bundle) in &BUNDLES {
+            let bundle: Value = serde_json::from_str(bundle).unwrap();
+            assert_eq!(bundle.as_object().unwrap().len(), english.strings.len());
+        }
+        assert!(is_language("es"));
+        assert!(!is_language("xx"));
+    }
+}
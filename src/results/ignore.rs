@@ -0,0 +1,204 @@
+use std::result;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use regex::Regex;
+use serde::ser::{Serialize, Serializer};
+
+use {Error, Result};
+use static_analysis::code::glob_to_regex;
+use super::Vulnerability;
+
+/// Prefix of the entries that ignore the findings of a rule
+const RULE_PREFIX: &'static str = "rule:";
+
+/// Entry of an ignore file, matching the findings of a rule, in a path, or of a rule in a path
+#[derive(Debug)]
+struct IgnoreEntry {
+    pattern: String,
+    rule: Option<Regex>,
+    path: Option<Regex>,
+    ignored: usize,
+}
+
+impl IgnoreEntry {
+    /// Parses a line of the ignore file
+    ///
+    /// Lines starting with `rule:` have a rule ID glob, optionally followed by a path glob, and
+    /// the rest are path globs.
+    fn from_line(line: &str) -> Option<IgnoreEntry> {
+        let (rule, path) = if line.starts_with(RULE_PREFIX) {
+            let mut parts = line[RULE_PREFIX.len()..].split_whitespace();
+            let rule = match parts.next() {
+                Some(r) => r,
+                None => return None,
+            };
+            let path = parts.next();
+            if parts.next().is_some() {
+                return None;
+            }
+            (Some(rule), path)
+        } else {
+            (None, Some(line))
+        };
+
+        Some(IgnoreEntry {
+            pattern: String::from(line),
+            rule: rule.map(glob_to_regex),
+            path: path.map(glob_to_regex),
+            ignored: 0,
+        })
+    }
+
+    /// Checks if the entry matches the vulnerability
+    fn matches(&self, vuln: &Vulnerability) -> bool {
+        let rule_matches = match (self.rule.as_ref(), vuln.get_rule()) {
+            (Some(regex), Some(rule)) => regex.is_match(rule),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let path_matches = match (self.path.as_ref(), vuln.get_file()) {
+            (Some(regex), Some(file)) => regex.is_match(&file.to_string_lossy()),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        rule_matches && path_matches
+    }
+}
+
+impl Serialize for IgnoreEntry {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("IgnoreEntry", 2));
+        try!(serializer.serialize_struct_elt(&mut state, "pattern", self.pattern.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "ignored", self.ignored));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Paths, rules and rule and path pairs whose findings are left out of the reports, such as
+/// vendored SDKs or known false positives
+///
+/// Each line of the file is an entry, and empty lines and lines starting with `#` are skipped:
+///
+/// ```text
+/// # Vendored SDKs
+/// com/google/**
+/// # All the findings of a rule
+/// rule:math_random
+/// # The findings of a rule in some paths
+/// rule:log com/example/debug/**
+/// ```
+#[derive(Debug)]
+pub struct IgnoreFile {
+    path: String,
+    entries: Vec<IgnoreEntry>,
+}
+
+impl IgnoreFile {
+    /// Loads the ignore file in the given path
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<IgnoreFile> {
+        let mut f = try!(File::open(&path));
+        let mut text = String::new();
+        let _ = try!(f.read_to_string(&mut text));
+        IgnoreFile::from_str(&path.as_ref().to_string_lossy(), &text)
+    }
+
+    /// Parses the text of the ignore file in the given path
+    fn from_str(path: &str, text: &str) -> Result<IgnoreFile> {
+        let mut entries = Vec::new();
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match IgnoreEntry::from_line(line) {
+                Some(entry) => entries.push(entry),
+                None => return Err(Error::ParseError),
+            }
+        }
+        Ok(IgnoreFile {
+            path: String::from(path),
+            entries: entries,
+        })
+    }
+
+    /// Checks if the vulnerability is ignored, counting it in the first entry that matches it
+    pub fn ignores(&mut self, vuln: &Vulnerability) -> bool {
+        match self.entries.iter_mut().find(|e| e.matches(vuln)) {
+            Some(entry) => {
+                entry.ignored += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gets the number of findings ignored by all the entries
+    pub fn get_ignored(&self) -> usize {
+        self.entries.iter().map(|e| e.ignored).sum()
+    }
+}
+
+impl Serialize for IgnoreFile {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("IgnoreFile", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "path", self.path.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "ignored", self.get_ignored()));
+        try!(serializer.serialize_struct_elt(&mut state, "entries", &self.entries));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Criticity;
+    use results::Vulnerability;
+    use super::IgnoreFile;
+
+    fn get_vuln(file: &str, rule: Option<&str>) -> Vulnerability {
+        let mut vuln = Vulnerability::new(Criticity::Medium,
+                                          "Finding",
+                                          "Description.",
+                                          Some(file),
+                                          Some(1),
+                                          Some(1),
+                                          None);
+        if let Some(rule) = rule {
+            vuln.set_rule(rule);
+        }
+        vuln
+    }
+
+    #[test]
+    fn it_ignore_file() {
+        let text = "# Vendored SDKs\n\
+                    com/google/**\n\
+                    \n\
+                    rule:math_random\n\
+                    rule:log* classes/com/example/debug/**\n";
+        let mut ignore = IgnoreFile::from_str(".superignore", text).unwrap();
+
+        assert!(ignore.ignores(&get_vuln("classes/com/google/ads/Ad.java", Some("log"))));
+        assert!(ignore.ignores(&get_vuln("smali/com/google/ads/Ad.smali", None)));
+        assert!(ignore.ignores(&get_vuln("classes/com/example/A.java", Some("math_random"))));
+        assert!(ignore.ignores(&get_vuln("classes/com/example/debug/D.java", Some("log_debug"))));
+        assert!(!ignore.ignores(&get_vuln("classes/com/example/A.java", Some("log"))));
+        assert!(!ignore.ignores(&get_vuln("classes/com/example/debug/D.java", None)));
+        assert_eq!(ignore.get_ignored(), 4);
+
+        let entries: Vec<_> = ignore.entries.iter().map(|e| e.ignored).collect();
+        assert_eq!(entries, vec![2, 1, 1]);
+    }
+
+    #[test]
+    fn it_invalid_ignore_file() {
+        assert!(IgnoreFile::from_str(".superignore", "rule:\n").is_err());
+        assert!(IgnoreFile::from_str(".superignore", "rule:log a/** b/**\n").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+
+use serde_json::builder::ObjectBuilder;
+use serde_json::value::Value;
+
+use {Error, Config, Result, Criticity, print_warning, csv_escape};
+use download::post_json_as;
+use super::{Results, Vulnerability};
+use super::grouping::FindingGroup;
+
+/// Maximum time in seconds of each request to the Jira REST API
+const JIRA_TIMEOUT: u32 = 60;
+
+/// Maximum number of issues created in each request to the Jira REST API
+const JIRA_BULK_SIZE: usize = 50;
+
+/// Issue tracker format the issues are exported to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IssueFormat {
+    /// Issues created in Jira with its REST API
+    Jira,
+    /// `issues.json` file, to import the issues with custom scripts
+    Json,
+    /// `issues.csv` file, in the format of the CSV importer of Jira
+    Csv,
+}
+
+impl FromStr for IssueFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IssueFormat> {
+        match s.to_lowercase().as_str() {
+            "jira" => Ok(IssueFormat::Jira),
+            "json" => Ok(IssueFormat::Json),
+            "csv" => Ok(IssueFormat::Csv),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Issue for the findings of a rule
+#[derive(Debug)]
+struct Issue {
+    summary: String,
+    description: String,
+    criticity: Criticity,
+    count: usize,
+    files: usize,
+    locations: Vec<(String, Option<usize>, String)>,
+}
+
+impl Issue {
+    /// Creates the issue of a group of findings, with the findings of the group listed in the
+    /// report as samples
+    fn new(results: &Results, group: &FindingGroup, samples: &[&Vulnerability]) -> Issue {
+        let mut description = match samples.first() {
+            Some(vuln) => format!("{}\n\n", vuln.get_description()),
+            None => String::new(),
+        };
+        description.push_str(&format!("Found {} times in {} files of {} {}.\n",
+                                      group.get_count(),
+                                      group.get_files(),
+                                      results.app_package,
+                                      results.app_version));
+        let locations = samples.iter()
+            .filter_map(|vuln| {
+                vuln.get_file().map(|file| {
+                    (file.to_string_lossy().into_owned(),
+                     vuln.get_start_line().map(|l| l + 1),
+                     vuln.get_fingerprint())
+                })
+            })
+            .collect::<Vec<_>>();
+        if !locations.is_empty() {
+            description.push_str("\nLocations:\n");
+            for &(ref file, line, _) in &locations {
+                match line {
+                    Some(line) => description.push_str(&format!("- {}:{}\n", file, line)),
+                    None => description.push_str(&format!("- {}\n", file)),
+                }
+            }
+        }
+        if let Some(vuln) = samples.first() {
+            let metadata = vuln.get_metadata();
+            if let Some(remediation) = metadata.get_remediation() {
+                description.push_str(&format!("\nRemediation: {}\n", remediation));
+            }
+            if !metadata.get_cwe().is_empty() {
+                description.push_str(&format!("\nCWE: {}\n", metadata.get_cwe().join(", ")));
+            }
+        }
+
+        Issue {
+            summary: format!("[{}] {}", results.app_package, group.get_name()),
+            description: description,
+            criticity: group.get_criticity(),
+            count: group.get_count(),
+            files: group.get_files(),
+            locations: locations,
+        }
+    }
+
+    /// Gets the fields of the issue in the Jira REST API
+    fn get_jira_fields(&self, config: &Config) -> Value {
+        let issues = config.get_issues_config();
+        ObjectBuilder::new()
+            .insert_object("project", |b| b.insert("key", issues.get_project()))
+            .insert("summary", self.summary.as_str())
+            .insert("description", self.description.as_str())
+            .insert_object("issuetype", |b| b.insert("name", issues.get_issue_type()))
+            .insert("labels", issues.get_labels())
+            .build()
+    }
+
+    /// Gets the issue in the generic JSON format
+    fn get_json(&self, config: &Config) -> Value {
+        let issues = config.get_issues_config();
+        ObjectBuilder::new()
+            .insert("project", issues.get_project())
+            .insert("issue_type", issues.get_issue_type())
+            .insert("summary", self.summary.as_str())
+            .insert("description", self.description.as_str())
+            .insert("criticity", self.criticity)
+            .insert("priority", get_priority(self.criticity))
+            .insert("labels", issues.get_labels())
+            .insert("count", self.count)
+            .insert("files", self.files)
+            .insert_array("locations", |b| {
+                self.locations.iter().fold(b, |b, &(ref file, line, ref fingerprint)| {
+                    b.push_object(|o| {
+                        o.insert("file", file.as_str())
+                            .insert("line", line)
+                            .insert("fingerprint", fingerprint.as_str())
+                    })
+                })
+            })
+            .build()
+    }
+
+    /// Gets the issue as a row of the CSV file
+    fn get_csv_row(&self, config: &Config) -> String {
+        let issues = config.get_issues_config();
+        let mut fields = vec![csv_escape(issues.get_project()),
+                              csv_escape(&self.summary),
+                              csv_escape(&self.description),
+                              csv_escape(issues.get_issue_type()),
+                              String::from(get_priority(self.criticity))];
+        fields.extend(issues.get_labels().iter().map(|l| csv_escape(l)));
+        fields.join(",")
+    }
+}
+
+/// Exports the findings of the rules that reach the minimum criticity to the configured issue
+/// tracker formats, with an issue for the findings of each rule
+///
+/// Errors creating the issues in Jira are only printed as warnings, since the reports were
+/// already generated.
+pub fn export_issues(results: &Results, config: &Config) -> Result<()> {
+    let issues = get_issues(results, config);
+    for format in config.get_issues_config().get_formats() {
+        match *format {
+            IssueFormat::Jira => create_jira_issues(&issues, config),
+            IssueFormat::Json => try!(write_json_issues(results, &issues, config)),
+            IssueFormat::Csv => try!(write_csv_issues(results, &issues, config)),
+        }
+    }
+    Ok(())
+}
+
+/// Gets the issues of the groups of findings that reach the minimum criticity
+fn get_issues(results: &Results, config: &Config) -> Vec<Issue> {
+    let min_criticity = config.get_issues_config().get_min_criticity();
+    results.groups
+        .iter()
+        .filter(|group| group.get_criticity() >= min_criticity)
+        .map(|group| {
+            let set = match group.get_criticity() {
+                Criticity::Warning => &results.warnings,
+                Criticity::Low => &results.low,
+                Criticity::Medium => &results.medium,
+                Criticity::High => &results.high,
+                Criticity::Critical => &results.critical,
+            };
+            let samples = set.iter()
+                .filter(|vuln| vuln.get_name() == group.get_name())
+                .take(group.get_listed())
+                .collect::<Vec<_>>();
+            Issue::new(results, group, &samples)
+        })
+        .collect()
+}
+
+/// Gets the name of the Jira priority of a criticity
+fn get_priority(criticity: Criticity) -> &'static str {
+    match criticity {
+        Criticity::Critical => "Highest",
+        Criticity::High => "High",
+        Criticity::Medium => "Medium",
+        Criticity::Low => "Low",
+        Criticity::Warning => "Lowest",
+    }
+}
+
+/// Creates the issues in Jira, in batches, printing a warning for each batch that fails
+fn create_jira_issues(issues: &[Issue], config: &Config) {
+    let issues_config = config.get_issues_config();
+    let url = match issues_config.get_jira_url() {
+        Some(url) => format!("{}/rest/api/2/issue/bulk", url),
+        None => return,
+    };
+    let mut created = 0;
+    for batch in issues.chunks(JIRA_BULK_SIZE) {
+        let body = ObjectBuilder::new()
+            .insert_array("issueUpdates", |b| {
+                batch.iter().fold(b, |b, issue| {
+                    b.push_object(|o| o.insert("fields", issue.get_jira_fields(config)))
+                })
+            })
+            .build();
+        match post_json_as(&url,
+                           &format!("{:?}", body),
+                           issues_config.get_jira_credentials(),
+                           JIRA_TIMEOUT) {
+            Ok((status, _)) if status >= 200 && status < 300 => created += batch.len(),
+            Ok((status, response)) => {
+                print_warning(format!("Jira returned the HTTP status {} when creating the \
+                                       issues: {}",
+                                      status,
+                                      response.trim()),
+                              config.is_verbose())
+            }
+            Err(e) => {
+                print_warning(format!("The issues could not be created in Jira: {}", e),
+                              config.is_verbose())
+            }
+        }
+    }
+    if config.is_verbose() {
+        println!("{} issues were created in Jira.", created);
+    }
+}
+
+/// Writes the issues to the `issues.json` file of the report folder
+fn write_json_issues(results: &Results, issues: &[Issue], config: &Config) -> Result<()> {
+    let json = ObjectBuilder::new()
+        .insert("package", results.app_package.as_str())
+        .insert("version", results.app_version.as_str())
+        .insert_array("issues", |b| {
+            issues.iter().fold(b, |b, issue| b.push(issue.get_json(config)))
+        })
+        .build();
+    let mut f = try!(File::create(results.get_report_folder(config).join("issues.json")));
+    try!(f.write_all(format!("{:?}", json).as_bytes()));
+    Ok(())
+}
+
+/// Writes the issues to the `issues.csv` file of the report folder, with a column for each
+/// label, as the CSV importer of Jira expects
+fn write_csv_issues(results: &Results, issues: &[Issue], config: &Config) -> Result<()> {
+    let mut f = try!(File::create(results.get_report_folder(config).join("issues.csv")));
+    try!(f.write_all(get_csv(issues, config).as_bytes()));
+    Ok(())
+}
+
+/// Gets the CSV file of the issues
+fn get_csv(issues: &[Issue], config: &Config) -> String {
+    let mut csv = String::from("Project,Summary,Description,Issue Type,Priority");
+    for _ in config.get_issues_config().get_labels() {
+        csv.push_str(",Labels");
+    }
+    csv.push('\n');
+    for issue in issues {
+        csv.push_str(&issue.get_csv_row(config));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use {Config, Criticity};
+    use super::{Issue, IssueFormat, get_csv, get_priority};
+
+    #[test]
+    fn it_issue_format() {
+        assert_eq!(IssueFormat::from_str("Jira").unwrap(), IssueFormat::Jira);
+        assert_eq!(IssueFormat::from_str("csv").unwrap(), IssueFormat::Csv);
+        assert!(IssueFormat::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn it_get_csv() {
+        let issue = Issue {
+            summary: String::from("[com.example] Weak hash"),
+            description: String::from("MD5 is used.\n\nFound 2 times in 1 files."),
+            criticity: Criticity::High,
+            count: 2,
+            files: 1,
+            locations: vec![(String::from("A.java"), Some(3), String::from("ab12"))],
+        };
+        let config = Config::default();
+        assert_eq!(get_priority(issue.criticity), "High");
+        assert_eq!(get_csv(&[issue], &config),
+                   "Project,Summary,Description,Issue Type,Priority,Labels\n\
+                    ,[com.example] Weak hash,\"MD5 is used.\n\nFound 2 times in 1 files.\",Bug,\
+                    High,super\n");
+    }
+}
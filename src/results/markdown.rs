@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::Write;
+
+use chrono::Local;
+use rustc_serialize::hex::ToHex;
+
+use {Config, Result, Criticity};
+use super::{Results, Vulnerability};
+use super::i18n::Locale;
+use super::risk::RiskScore;
+
+/// Name of the Markdown report in the results folder of the application
+const REPORT_FILE: &'static str = "report.md";
+
+/// Escapes the text of a Markdown table cell
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Gets the Markdown of the given set of findings
+fn get_findings_markdown(findings: &[(usize, &Vulnerability)],
+                         criticity: Criticity,
+                         locale: &Locale)
+                         -> String {
+    let mut markdown = if criticity == Criticity::Warning {
+        format!("## {}\n\n", locale.get("warnings"))
+    } else {
+        format!("## {}\n\n", locale.get(&format!("{}_vulnerabilities", criticity)))
+    };
+    for &(i, vuln) in findings {
+        markdown.push_str(&format!("### {}: {}\n\n",
+                                   Results::get_vulnerability_id(criticity, i),
+                                   vuln.get_name()));
+        if let Some(file) = vuln.get_file() {
+            markdown.push_str(&format!("- **{}:** `{}`", locale.get("file"), file.display()));
+            match (vuln.get_start_line(), vuln.get_end_line()) {
+                (Some(start), Some(end)) if start != end => {
+                    markdown.push_str(&format!(", {} {}-{}",
+                                               locale.get("lines").to_lowercase(),
+                                               start + 1,
+                                               end + 1))
+                }
+                (Some(start), _) => {
+                    markdown.push_str(&format!(", {} {}",
+                                               locale.get("line").to_lowercase(),
+                                               start + 1))
+                }
+                _ => {}
+            }
+            markdown.push('\n');
+        }
+        if let Some(source_file) = vuln.get_source_file() {
+            markdown.push_str(&format!("- **{}:** `{}`", locale.get("source_file"), source_file));
+            if let Some(line) = vuln.get_source_line() {
+                markdown.push_str(&format!(", {} {}", locale.get("line").to_lowercase(), line + 1));
+            }
+            markdown.push('\n');
+        }
+        let metadata = vuln.get_metadata();
+        if !metadata.get_cwe().is_empty() {
+            markdown.push_str(&format!("- **CWE:** {}\n", metadata.get_cwe().join(", ")));
+        }
+        if !metadata.get_masvs().is_empty() {
+            markdown.push_str(&format!("- **OWASP MASVS:** {}\n", metadata.get_masvs().join(", ")));
+        }
+        if !metadata.get_owasp_mobile().is_empty() {
+            markdown.push_str(&format!("- **OWASP Mobile Top 10:** {}\n",
+                                       metadata.get_owasp_mobile().join(", ")));
+        }
+        if let Some(cvss) = metadata.get_cvss() {
+            markdown.push_str(&format!("- **CVSS:** {}\n", cvss));
+        }
+        if let Some(remediation) = metadata.get_remediation() {
+            markdown.push_str(&format!("- **{}:** {}\n", locale.get("remediation"), remediation));
+        }
+        for reference in metadata.get_references() {
+            markdown.push_str(&format!("- <{}>\n", reference));
+        }
+        markdown.push_str(&format!("\n{}\n\n", vuln.get_description()));
+        if let Some(code) = vuln.get_code() {
+            let lang = vuln.get_file()
+                .and_then(|f| f.extension())
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_else(String::new);
+            markdown.push_str(&format!("```{}\n{}\n```\n\n", lang, code.trim_right()));
+        }
+    }
+    markdown
+}
+
+/// Gets the Markdown summary of the given results
+fn get_markdown(results: &Results, date: &str, locale: &Locale) -> String {
+    let title = if results.app_label.is_empty() {
+        results.app_package.as_str()
+    } else {
+        results.app_label.as_str()
+    };
+    let mut markdown = format!("# {}\n\n{}\n\n",
+                               locale.format("markdown_title", &[title]),
+                               locale.format("markdown_intro", &[date]));
+    if !results.incomplete.is_empty() {
+        markdown.push_str(&format!("> **{}:** {}\n>\n",
+                                   locale.get("incomplete_analysis"),
+                                   locale.get("incomplete_intro")));
+        for stage in &results.incomplete {
+            markdown.push_str(&format!("> - **{}:** {}", stage.get_stage(), stage.get_reason()));
+            if let Some(log) = stage.get_log() {
+                markdown.push_str(&format!(" ([{}]({}))", locale.get("tool_log"), log));
+            }
+            markdown.push('\n');
+        }
+        markdown.push('\n');
+    }
+
+    markdown.push_str(&format!("| {} | |\n|---|---|\n", locale.get("application")));
+    let mut data = vec![(locale.get("package"), results.app_package.clone()),
+                        (locale.get("version"), results.app_version.clone())];
+    if results.app_version_num > 0 {
+        data.push((locale.get("version_number"), results.app_version_num.to_string()));
+    }
+    if results.app_min_sdk > 0 {
+        data.push((locale.get("min_sdk"), results.app_min_sdk.to_string()));
+    }
+    if let Some(target_sdk) = results.app_target_sdk {
+        data.push((locale.get("target_sdk"), target_sdk.to_string()));
+    }
+    if let Some(ref info) = results.apk_info {
+        if let Some(max_sdk) = info.get_max_sdk() {
+            data.push((locale.get("max_sdk"), max_sdk.to_string()));
+        }
+        data.push((locale.get("abis"),
+                   info.get_abis().iter().cloned().collect::<Vec<_>>().join(", ")));
+        data.push((locale.get("locales"),
+                   info.get_locales().iter().cloned().collect::<Vec<_>>().join(", ")));
+    }
+    data.push(("SHA-256", results.app_fingerprint.get_sha256().to_hex()));
+    let risk = RiskScore::new(results);
+    data.push((locale.get("risk_score"),
+               format!("{}/100 ({})",
+                       risk.get_score(),
+                       locale.get(&format!("risk_{}", risk.get_level())))));
+    for (label, value) in data {
+        if !value.is_empty() {
+            markdown.push_str(&format!("| {} | {} |\n", label, escape_cell(&value)));
+        }
+    }
+
+    let sets = [(Criticity::Critical, &results.critical),
+                (Criticity::High, &results.high),
+                (Criticity::Medium, &results.medium),
+                (Criticity::Low, &results.low),
+                (Criticity::Warning, &results.warnings)];
+    markdown.push_str(&format!("\n| {} | {} |\n|---|---|\n",
+                               locale.get("criticity"),
+                               locale.get("findings")));
+    for &(criticity, set) in &sets {
+        let key = if criticity == Criticity::Warning {
+            String::from("warnings")
+        } else {
+            format!("{}", criticity)
+        };
+        markdown.push_str(&format!("| {} | {} |\n", locale.get(&key), set.len()));
+    }
+    markdown.push('\n');
+
+    for &(criticity, set) in &sets {
+        if !set.is_empty() {
+            markdown.push_str(&get_findings_markdown(&results.get_listed_findings(set),
+                                                     criticity,
+                                                     locale));
+        }
+    }
+    markdown
+}
+
+/// Generates a single file Markdown summary of the report, to paste in tickets or pull requests
+pub fn generate_markdown_report(results: &Results, config: &Config) -> Result<()> {
+    let date = Local::now().to_rfc2822();
+    let locale = Locale::new(config.get_language());
+    let mut f = try!(File::create(results.get_report_folder(config).join(REPORT_FILE)));
+    try!(f.write_all(get_markdown(results, &date, &locale).as_bytes()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use Criticity;
+    use results::Vulnerability;
+    use results::i18n::Locale;
+    use super::{escape_cell, get_findings_markdown};
+
+    #[test]
+    fn it_get_findings_markdown() {
+        let mut set = BTreeSet::new();
+        let _ = set.insert(Vulnerability::new(Criticity::High,
+                                              "Weak algorithm",
+                                              "MD5 is used.",
+                                              Some("classes/com/example/A.java"),
+                                              Some(9),
+                                              Some(9),
+                                              Some(String::from("md5(data);\n"))));
+        let findings = set.iter().enumerate().collect::<Vec<_>>();
+        assert_eq!(get_findings_markdown(&findings, Criticity::High, &Locale::new("en")),
+                   "## High criticity vulnerabilities\n\n\
+                    ### H001: Weak algorithm\n\n\
+                    - **File:** `classes/com/example/A.java`, line 10\n\
+                    \nMD5 is used.\n\n\
+                    ```java\nmd5(data);\n```\n\n");
+        assert!(get_findings_markdown(&findings, Criticity::High, &Locale::new("es"))
+            .starts_with("## Vulnerabilidades de criticidad alta\n\n### H001: Weak algorithm\n\n\
+                          - **Archivo:** `classes/com/example/A.java`, línea 10\n"));
+
+        assert_eq!(escape_cell("a|b\nc"), "a\\|b c");
+    }
+}
@@ -0,0 +1,210 @@
+use std::result;
+use std::collections::BTreeSet;
+
+use serde::ser::{Serialize, Serializer};
+
+use Criticity;
+use super::Results;
+
+/// Controls of the OWASP MASVS, with their statement and if they can be verified with a static
+/// analysis of the application
+///
+/// Authentication flows, update enforcement, anti-dynamic analysis and the transparency and user
+/// control of privacy depend on the server side or on the runtime behavior of the application,
+/// so they need a manual or dynamic test, even if a rule sometimes finds issues in them.
+const CONTROLS: [(&'static str, &'static str, bool); 24] =
+    [("MASVS-STORAGE-1", "The app securely stores sensitive data.", true),
+     ("MASVS-STORAGE-2", "The app prevents leakage of sensitive data.", true),
+     ("MASVS-CRYPTO-1",
+      "The app employs current strong cryptography and uses it according to industry best \
+       practices.",
+      true),
+     ("MASVS-CRYPTO-2", "The app performs key management according to industry best practices.",
+      true),
+     ("MASVS-AUTH-1",
+      "The app uses secure authentication and authorization protocols and follows the relevant \
+       best practices.",
+      false),
+     ("MASVS-AUTH-2",
+      "The app performs local authentication securely according to the platform best practices.",
+      true),
+     ("MASVS-AUTH-3", "The app secures sensitive operations with additional authentication.",
+      false),
+     ("MASVS-NETWORK-1",
+      "The app secures all network traffic according to the current best practices.",
+      true),
+     ("MASVS-NETWORK-2",
+      "The app performs identity pinning for all remote endpoints under the developer's control.",
+      true),
+     ("MASVS-PLATFORM-1", "The app uses IPC mechanisms securely.", true),
+     ("MASVS-PLATFORM-2", "The app uses WebViews securely.", true),
+     ("MASVS-PLATFORM-3", "The app uses the user interface securely.", true),
+     ("MASVS-CODE-1", "The app requires an up-to-date platform version.", true),
+     ("MASVS-CODE-2", "The app has a mechanism for enforcing app updates.", false),
+     ("MASVS-CODE-3", "The app only uses software components without known vulnerabilities.",
+      true),
+     ("MASVS-CODE-4", "The app validates and sanitizes all untrusted inputs.", true),
+     ("MASVS-RESILIENCE-1", "The app validates the integrity of the platform.", true),
+     ("MASVS-RESILIENCE-2", "The app implements anti-tampering mechanisms.", true),
+     ("MASVS-RESILIENCE-3", "The app implements anti-static analysis mechanisms.", true),
+     ("MASVS-RESILIENCE-4", "The app implements anti-dynamic analysis techniques.", false),
+     ("MASVS-PRIVACY-1", "The app minimizes access to sensitive data and resources.", true),
+     ("MASVS-PRIVACY-2", "The app prevents identification of the user.", true),
+     ("MASVS-PRIVACY-3", "The app is transparent about data collection and usage.", false),
+     ("MASVS-PRIVACY-4", "The app offers user control over their data.", false)];
+
+/// Base URL of the documentation of each MASVS control
+const CONTROL_URL: &'static str = "https://mas.owasp.org/MASVS/controls/";
+
+/// Compliance status of a MASVS control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlStatus {
+    /// There are findings mapped to the control
+    Failed,
+    /// The control was checked, and there are no findings mapped to it
+    Passed,
+    /// The control can be verified statically, but no enabled rule or analysis checks it
+    NotChecked,
+    /// The control needs a manual or dynamic test
+    NotVerifiable,
+}
+
+impl ControlStatus {
+    /// Gets the key of the status, used in the JSON report and to translate it
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ControlStatus::Failed => "failed",
+            ControlStatus::Passed => "passed",
+            ControlStatus::NotChecked => "not_checked",
+            ControlStatus::NotVerifiable => "not_verifiable",
+        }
+    }
+}
+
+/// Row of the MASVS compliance matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlResult {
+    control: &'static str,
+    description: &'static str,
+    status: ControlStatus,
+    /// Identifiers of the findings mapped to the control
+    findings: Vec<String>,
+}
+
+impl ControlResult {
+    /// Gets the identifier of the control, such as `MASVS-STORAGE-1`
+    pub fn get_control(&self) -> &'static str {
+        self.control
+    }
+
+    /// Gets the statement of the control
+    pub fn get_description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Gets the URL of the documentation of the control
+    pub fn get_url(&self) -> String {
+        format!("{}{}/", CONTROL_URL, self.control)
+    }
+
+    /// Gets the compliance status of the control
+    pub fn get_status(&self) -> ControlStatus {
+        self.status
+    }
+
+    /// Gets the identifiers of the findings mapped to the control
+    pub fn get_findings(&self) -> &[String] {
+        self.findings.as_slice()
+    }
+}
+
+impl Serialize for ControlResult {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("ControlResult", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "control", self.control));
+        try!(serializer.serialize_struct_elt(&mut state, "description", self.description));
+        try!(serializer.serialize_struct_elt(&mut state, "status", self.status.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "findings", &self.findings));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the MASVS compliance matrix of the results
+///
+/// Controls are checked by the enabled rules and the analyses that map findings to them, and
+/// any control with findings has failed, even if it can't be fully verified statically.
+/// Warnings are informative, so they don't make a control fail.
+pub fn get_masvs_matrix(results: &Results) -> Vec<ControlResult> {
+    let sets = [(Criticity::Critical, &results.critical),
+                (Criticity::High, &results.high),
+                (Criticity::Medium, &results.medium),
+                (Criticity::Low, &results.low)];
+    CONTROLS.iter()
+        .map(|&(control, description, verifiable)| {
+            let findings = sets.iter()
+                .flat_map(|&(criticity, set)| {
+                    set.iter()
+                        .enumerate()
+                        .filter(|&(_, vuln)| {
+                            vuln.get_metadata().get_masvs().iter().any(|m| m == control)
+                        })
+                        .map(move |(i, _)| Results::get_vulnerability_id(criticity, i))
+                })
+                .collect::<Vec<_>>();
+            ControlResult {
+                control: control,
+                description: description,
+                status: get_status(&results.checked_controls, control, verifiable, &findings),
+                findings: findings,
+            }
+        })
+        .collect()
+}
+
+/// Gets the status of a control, from the checked controls and its findings
+fn get_status(checked: &BTreeSet<String>,
+              control: &str,
+              verifiable: bool,
+              findings: &[String])
+              -> ControlStatus {
+    if !findings.is_empty() {
+        ControlStatus::Failed
+    } else if checked.contains(control) {
+        ControlStatus::Passed
+    } else if verifiable {
+        ControlStatus::NotChecked
+    } else {
+        ControlStatus::NotVerifiable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use super::{ControlStatus, CONTROLS, get_status};
+
+    #[test]
+    fn it_control_status() {
+        let mut checked = BTreeSet::new();
+        let _ = checked.insert(String::from("MASVS-STORAGE-1"));
+        let findings = vec![String::from("H001")];
+
+        assert_eq!(get_status(&checked, "MASVS-STORAGE-1", true, &findings),
+                   ControlStatus::Failed);
+        assert_eq!(get_status(&checked, "MASVS-STORAGE-1", true, &[]),
+                   ControlStatus::Passed);
+        assert_eq!(get_status(&checked, "MASVS-CODE-1", true, &[]),
+                   ControlStatus::NotChecked);
+        assert_eq!(get_status(&checked, "MASVS-AUTH-1", false, &[]),
+                   ControlStatus::NotVerifiable);
+        assert_eq!(get_status(&checked, "MASVS-AUTH-1", false, &findings),
+                   ControlStatus::Failed);
+
+        // Every control is listed once
+        let controls = CONTROLS.iter().map(|&(c, _, _)| c).collect::<BTreeSet<_>>();
+        assert_eq!(controls.len(), CONTROLS.len());
+    }
+}
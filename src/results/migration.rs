@@ -0,0 +1,425 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use serde_json;
+use serde_json::value::Value;
+use colored::Colorize;
+
+use {Error, Result, print_warning};
+use super::SCHEMA_VERSION;
+
+/// Migration from one schema version to the next one.
+///
+/// The migration at index `n` upgrades a report from version `n` to version `n + 1`.
+type Migration = fn(&mut BTreeMap<String, Value>) -> Result<()>;
+
+/// Depth of the report folders in the versioned layout of the results folder:
+/// `{package}/{version_num}/{timestamp}`
+const MAX_REPORT_DEPTH: usize = 3;
+
+const MIGRATIONS: [Migration; 7] = [migrate_v0_to_v1,
+                                    migrate_v1_to_v2,
+                                    migrate_v2_to_v3,
+                                    migrate_v3_to_v4,
+                                    migrate_v4_to_v5,
+                                    migrate_v5_to_v6,
+                                    migrate_v6_to_v7];
+
+/// Gets the schema version of a JSON report
+///
+/// Reports generated before the schema was versioned do not have the `schema_version` key, and
+/// are considered to be version 0.
+pub fn get_schema_version(report: &BTreeMap<String, Value>) -> Result<u64> {
+    match report.get("schema_version") {
+        Some(&Value::U64(v)) => Ok(v),
+        Some(&Value::I64(v)) if v >= 0 => Ok(v as u64),
+        None => Ok(0),
+        _ => Err(Error::ParseError),
+    }
+}
+
+/// Upgrades the given report to the current schema version
+///
+/// Returns the version the report had before the migration.
+pub fn migrate_report(report: &mut BTreeMap<String, Value>) -> Result<u64> {
+    let original_version = try!(get_schema_version(report));
+    if original_version > SCHEMA_VERSION as u64 {
+        return Err(Error::ParseError);
+    }
+
+    for migration in &MIGRATIONS[original_version as usize..] {
+        try!(migration(report));
+    }
+
+    Ok(original_version)
+}
+
+/// Migrates every `results.json` file found in the given path
+///
+/// The path can either be a `results.json` file or a results folder, in which case all the
+/// application result folders inside it will be migrated, including the folders of each run in
+/// the versioned layout. Returns the number of migrated reports.
+pub fn migrate_results<P: AsRef<Path>>(path: P, verbose: bool) -> Result<usize> {
+    let path = path.as_ref();
+    if path.is_file() {
+        return Ok(if try!(migrate_file(path, verbose)) { 1 } else { 0 });
+    }
+
+    let mut reports = Vec::new();
+    try!(find_reports(path, 0, &mut reports));
+    reports.sort();
+
+    let mut count = 0;
+    for report in reports {
+        match migrate_file(&report, verbose) {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(e) => {
+                print_warning(format!("The results in {} could not be migrated: {}",
+                                      report.display(),
+                                      e),
+                              verbose)
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Finds the `results.json` files in the given folder and in its subfolders, down to the run
+/// folders of the versioned layout
+///
+/// Symbolic links, such as the `latest` link of each application, are not followed, so that
+/// each report is only found once.
+fn find_reports(folder: &Path, depth: usize, reports: &mut Vec<PathBuf>) -> Result<()> {
+    let report = folder.join("results.json");
+    if report.is_file() {
+        reports.push(report);
+    }
+    if depth == MAX_REPORT_DEPTH {
+        return Ok(());
+    }
+    for entry in try!(fs::read_dir(folder)) {
+        let entry = try!(entry);
+        if try!(entry.file_type()).is_dir() {
+            try!(find_reports(&entry.path(), depth + 1, reports));
+        }
+    }
+    Ok(())
+}
+
+fn migrate_file<P: AsRef<Path>>(path: P, verbose: bool) -> Result<bool> {
+    let f = try!(File::open(path.as_ref()));
+    let report: Value = try!(serde_json::from_reader(f));
+    let mut report = match report {
+        Value::Object(o) => o,
+        _ => return Err(Error::ParseError),
+    };
+
+    let version = try!(migrate_report(&mut report));
+    if version == SCHEMA_VERSION as u64 {
+        if verbose {
+            println!("The results in {} are already in the latest schema version.",
+                     path.as_ref().display());
+        }
+        return Ok(false);
+    }
+
+    let mut f = try!(File::create(path.as_ref()));
+    try!(f.write_all(&format!("{:?}", Value::Object(report)).into_bytes()));
+
+    if verbose {
+        println!("{}",
+                 format!("The results in {} were migrated from schema version {} to version {}.",
+                         path.as_ref().display(),
+                         version,
+                         SCHEMA_VERSION)
+                     .green());
+    }
+    Ok(true)
+}
+
+/// Version 1 added the schema version, the version number and the SDK versions to the report.
+fn migrate_v0_to_v1(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(1));
+    if !report.contains_key("version_num") {
+        report.insert(String::from("version_num"), Value::Null);
+    }
+    if !report.contains_key("min_sdk") {
+        report.insert(String::from("min_sdk"), Value::Null);
+    }
+    if !report.contains_key("target_sdk") {
+        report.insert(String::from("target_sdk"), Value::Null);
+    }
+    Ok(())
+}
+
+/// Version 2 added IDs and cross references to the vulnerabilities, along with the permissions
+/// they are related to.
+fn migrate_v1_to_v2(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(2));
+    for &(key, prefix) in &[("critical", 'C'),
+                            ("high", 'H'),
+                            ("medium", 'M'),
+                            ("low", 'L'),
+                            ("warnings", 'W')] {
+        if let Some(&mut Value::Array(ref mut vulns)) = report.get_mut(key) {
+            for (i, vuln) in vulns.iter_mut().enumerate() {
+                match *vuln {
+                    Value::Object(ref mut vuln) => {
+                        vuln.insert(String::from("id"),
+                                    Value::String(format!("{}{:03}", prefix, i + 1)));
+                        vuln.insert(String::from("references"), Value::Array(Vec::new()));
+                        vuln.insert(String::from("permission"), Value::Null);
+                        vuln.insert(String::from("required_permissions"),
+                                    Value::Array(Vec::new()));
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 3 added the classification and remediation guidance of the rule that found each
+/// vulnerability.
+fn migrate_v2_to_v3(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(3));
+    for key in &["critical", "high", "medium", "low", "warnings"] {
+        if let Some(&mut Value::Array(ref mut vulns)) = report.get_mut(*key) {
+            for vuln in vulns.iter_mut() {
+                match *vuln {
+                    Value::Object(ref mut vuln) => {
+                        let mut metadata = BTreeMap::new();
+                        for list in &["cwe", "masvs", "owasp_mobile", "references"] {
+                            metadata.insert(String::from(*list), Value::Array(Vec::new()));
+                        }
+                        metadata.insert(String::from("remediation"), Value::Null);
+                        vuln.insert(String::from("metadata"), Value::Object(metadata));
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 4 added the CVSS vector and score of the rule that found each vulnerability.
+fn migrate_v3_to_v4(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(4));
+    for key in &["critical", "high", "medium", "low", "warnings"] {
+        if let Some(&mut Value::Array(ref mut vulns)) = report.get_mut(*key) {
+            for vuln in vulns.iter_mut() {
+                match *vuln {
+                    Value::Object(ref mut vuln) => {
+                        match vuln.get_mut("metadata") {
+                            Some(&mut Value::Object(ref mut metadata)) => {
+                                metadata.insert(String::from("cvss"), Value::Null);
+                            }
+                            _ => return Err(Error::ParseError),
+                        }
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 5 added the fingerprint of each vulnerability, that can't be computed for older
+/// reports, since they don't have the code of the vulnerabilities.
+fn migrate_v4_to_v5(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(5));
+    for key in &["critical", "high", "medium", "low", "warnings"] {
+        if let Some(&mut Value::Array(ref mut vulns)) = report.get_mut(*key) {
+            for vuln in vulns.iter_mut() {
+                match *vuln {
+                    Value::Object(ref mut vuln) => {
+                        vuln.insert(String::from("fingerprint"), Value::Null);
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 6 added the location of each vulnerability in the source code of the application,
+/// when it's given.
+fn migrate_v5_to_v6(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(6));
+    for key in &["critical", "high", "medium", "low", "warnings"] {
+        if let Some(&mut Value::Array(ref mut vulns)) = report.get_mut(*key) {
+            for vuln in vulns.iter_mut() {
+                match *vuln {
+                    Value::Object(ref mut vuln) => {
+                        vuln.insert(String::from("source_file"), Value::Null);
+                        vuln.insert(String::from("source_line"), Value::Null);
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 7 added the provenance metadata, the APK metadata, the incomplete stages, the risk
+/// score, the MASVS matrix, the methodology and the groups of findings to the report.
+///
+/// The provenance, the APK metadata and the risk score can't be computed for older reports, so
+/// they are left empty. The keys of the report sections, such as `secrets` or `endpoints`, are
+/// only present when their analysis ran, so there is nothing to add for them.
+fn migrate_v6_to_v7(report: &mut BTreeMap<String, Value>) -> Result<()> {
+    report.insert(String::from("schema_version"), Value::U64(7));
+    for key in &["metadata", "apk", "risk"] {
+        if !report.contains_key(*key) {
+            report.insert(String::from(*key), Value::Null);
+        }
+    }
+    for key in &["incomplete", "masvs", "groups"] {
+        if !report.contains_key(*key) {
+            report.insert(String::from(*key), Value::Array(Vec::new()));
+        }
+    }
+    let methodology = report.entry(String::from("methodology"))
+        .or_insert_with(|| Value::Object(BTreeMap::new()));
+    match *methodology {
+        Value::Object(ref mut methodology) => {
+            for &(key, ref default) in &[("suppressed", Value::U64(0)),
+                                         ("ignore_file", Value::Null),
+                                         ("duplicates", Value::U64(0))] {
+                if !methodology.contains_key(key) {
+                    methodology.insert(String::from(key), default.clone());
+                }
+            }
+        }
+        _ => return Err(Error::ParseError),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::Path;
+    use std::collections::BTreeMap;
+    use serde_json;
+    use serde_json::value::Value;
+
+    use super::{get_schema_version, migrate_report, migrate_results};
+    use results::SCHEMA_VERSION;
+
+    #[test]
+    fn it_migrate_v0() {
+        let mut report = BTreeMap::new();
+        report.insert(String::from("package"), Value::String(String::from("com.example")));
+        report.insert(String::from("min_sdk"), Value::U64(15));
+
+        assert_eq!(get_schema_version(&report).unwrap(), 0);
+        assert_eq!(migrate_report(&mut report).unwrap(), 0);
+        assert_eq!(get_schema_version(&report).unwrap(), SCHEMA_VERSION as u64);
+        assert_eq!(report.get("min_sdk"), Some(&Value::U64(15)));
+        assert_eq!(report.get("target_sdk"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn it_migrate_v1() {
+        let mut vuln = BTreeMap::new();
+        vuln.insert(String::from("name"), Value::String(String::from("Allows Backup")));
+        let mut report = BTreeMap::new();
+        report.insert(String::from("schema_version"), Value::U64(1));
+        report.insert(String::from("medium"),
+                      Value::Array(vec![Value::Object(vuln.clone()), Value::Object(vuln)]));
+
+        assert_eq!(migrate_report(&mut report).unwrap(), 1);
+        let vulns = report.get("medium").unwrap().as_array().unwrap();
+        assert_eq!(vulns[1].as_object().unwrap().get("id"),
+                   Some(&Value::String(String::from("M002"))));
+        assert_eq!(vulns[0].as_object().unwrap().get("references"),
+                   Some(&Value::Array(Vec::new())));
+    }
+
+    #[test]
+    fn it_migrate_v2() {
+        let mut vuln = BTreeMap::new();
+        vuln.insert(String::from("name"), Value::String(String::from("SQL injection")));
+        let mut report = BTreeMap::new();
+        report.insert(String::from("schema_version"), Value::U64(2));
+        report.insert(String::from("critical"), Value::Array(vec![Value::Object(vuln)]));
+
+        assert_eq!(migrate_report(&mut report).unwrap(), 2);
+        let vulns = report.get("critical").unwrap().as_array().unwrap();
+        let metadata = vulns[0].as_object().unwrap().get("metadata").unwrap();
+        assert_eq!(metadata.as_object().unwrap().get("cwe"),
+                   Some(&Value::Array(Vec::new())));
+        assert_eq!(metadata.as_object().unwrap().get("remediation"), Some(&Value::Null));
+        assert_eq!(metadata.as_object().unwrap().get("cvss"), Some(&Value::Null));
+        assert_eq!(vulns[0].as_object().unwrap().get("fingerprint"), Some(&Value::Null));
+        assert_eq!(vulns[0].as_object().unwrap().get("source_file"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn it_migrate_v6() {
+        let mut methodology = BTreeMap::new();
+        methodology.insert(String::from("suppressed"), Value::U64(3));
+        let mut report = BTreeMap::new();
+        report.insert(String::from("schema_version"), Value::U64(6));
+        report.insert(String::from("incomplete"), Value::Array(vec![Value::Null]));
+        report.insert(String::from("methodology"), Value::Object(methodology));
+
+        assert_eq!(migrate_report(&mut report).unwrap(), 6);
+        assert_eq!(report.get("metadata"), Some(&Value::Null));
+        assert_eq!(report.get("risk"), Some(&Value::Null));
+        assert_eq!(report.get("incomplete"), Some(&Value::Array(vec![Value::Null])));
+        assert_eq!(report.get("groups"), Some(&Value::Array(Vec::new())));
+        let methodology = report.get("methodology").unwrap().as_object().unwrap();
+        assert_eq!(methodology.get("suppressed"), Some(&Value::U64(3)));
+        assert_eq!(methodology.get("duplicates"), Some(&Value::U64(0)));
+    }
+
+    #[test]
+    fn it_migrate_newer() {
+        let mut report = BTreeMap::new();
+        report.insert(String::from("schema_version"),
+                      Value::U64(SCHEMA_VERSION as u64 + 1));
+
+        assert!(migrate_report(&mut report).is_err());
+    }
+
+    #[test]
+    fn it_migrate_versioned_results() {
+        let results = Path::new("migration_test");
+        let run = results.join("com.example.app/3/20170102-101010");
+        let legacy = results.join("com.example.legacy");
+        for folder in &[&run, &legacy] {
+            fs::create_dir_all(folder).unwrap();
+            File::create(folder.join("results.json"))
+                .unwrap()
+                .write_all(b"{\"package\": \"com.example\", \"min_sdk\": 15}")
+                .unwrap();
+        }
+        // The link to the last run must not make its report be migrated twice
+        #[cfg(target_family = "unix")]
+        ::std::os::unix::fs::symlink("3/20170102-101010", results.join("com.example.app/latest"))
+            .unwrap();
+
+        let migrated = migrate_results(results, false);
+        let mut json = String::new();
+        let _ = File::open(run.join("results.json")).unwrap().read_to_string(&mut json).unwrap();
+        fs::remove_dir_all(results).unwrap();
+
+        assert_eq!(migrated.unwrap(), 2);
+        let report: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(get_schema_version(report.as_object().unwrap()).unwrap(),
+                   SCHEMA_VERSION as u64);
+    }
+}
@@ -1,21 +1,142 @@
-use std::fs;
+use std::{fs, mem};
 use std::fs::File;
 use std::io::{Read, Write};
-use std::collections::BTreeSet;
-use std::path::Path;
+use std::collections::{BTreeSet, BTreeMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::borrow::Borrow;
 use std::slice::Iter;
+use std::collections::btree_set::Iter as SetIter;
+use std::time::Duration;
 
-use serde_json::builder::ObjectBuilder;
-use chrono::{Local, Datelike};
-use rustc_serialize::hex::ToHex;
+use serde_json::builder::{ObjectBuilder, ArrayBuilder};
+use serde_json::value::{Value, to_value};
+use chrono::Local;
 
 mod utils;
-
-pub use self::utils::{Benchmark, Vulnerability};
+mod migration;
+mod section;
+mod history;
+mod diff;
+mod gitlab;
+mod markdown;
+mod pdf;
+mod template;
+mod i18n;
+mod baseline;
+mod ignore;
+mod grouping;
+mod issues;
+mod github;
+mod source_map;
+mod risk;
+mod masvs;
+mod notifications;
+mod runs;
+mod reproducible;
+mod provenance;
+
+pub use self::utils::{Benchmark, Vulnerability, RuleMetadata, CvssScore, RuleProfile,
+                      IncompleteStage};
+pub use self::section::ReportSection;
+pub use self::migration::migrate_results;
+pub use self::diff::{ReportDiff, diff_results, get_report_path};
+pub use self::runs::find_report_folder;
+pub use self::i18n::{LANGUAGES, is_language};
+pub use self::issues::IssueFormat;
 use self::utils::FingerPrint;
+use self::history::{HistoryEntry, append_history, generate_trend_report};
+use self::gitlab::generate_gitlab_report;
+use self::github::generate_github_annotations;
+use self::source_map::SourceMap;
+use self::markdown::generate_markdown_report;
+use self::pdf::generate_pdf_report;
+use self::template::{render_html_report, SourceRenderer};
+use self::baseline::Baseline;
+use self::ignore::IgnoreFile;
+use self::grouping::{FindingGroup, deduplicate, group_findings, get_listed_findings};
+use self::risk::RiskScore;
+use self::masvs::get_masvs_matrix;
+use self::notifications::send_notifications;
+use self::issues::export_issues;
+use self::reproducible::make_reproducible;
+use self::provenance::Provenance;
+use self::runs::{Run, add_run, TIMESTAMP_FORMAT};
 
 use {Error, Config, Result, Criticity, print_error, print_warning, file_exists, copy_folder};
+use static_analysis::code::is_smali_folder;
+use static_analysis::obfuscation::Mapping;
+use static_analysis::apk_info::ApkInfo;
+
+/// Version of the machine readable results format
+///
+/// It must be increased, and a migration added, every time the existing keys of the JSON report
+/// change. New top level sections added by new analyses are optional, and consumers must ignore
+/// the keys they don't know, so they don't require a new version.
+pub const SCHEMA_VERSION: u32 = 7;
+
+/// Keys of the JSON report and anchors of the HTML report that can't be used by report sections
+const RESERVED_KEYS: [&'static str; 22] = ["schema_version",
+                                           "metadata",
+                                           "apk",
+                                           "label",
+                                           "description",
+                                           "package",
+                                           "version",
+                                           "version_num",
+                                           "min_sdk",
+                                           "target_sdk",
+                                           "fingerprint",
+                                           "incomplete",
+                                           "risk",
+                                           "masvs",
+                                           "methodology",
+                                           "groups",
+                                           "warnings",
+                                           "low",
+                                           "medium",
+                                           "high",
+                                           "critical",
+                                           "title"];
+
+/// Format of the generated reports
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ReportFormat {
+    /// HTML report, with the source code of the application
+    Html,
+    /// Machine readable JSON report
+    Json,
+    /// Single file Markdown summary, to paste in tickets or pull requests
+    Markdown,
+    /// PDF export of a printable version of the report
+    Pdf,
+}
+
+impl ReportFormat {
+    /// Gets the name of the format, used in the `report_formats` option of the configuration
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ReportFormat> {
+        match s.to_lowercase().as_str() {
+            "html" => Ok(ReportFormat::Html),
+            "json" => Ok(ReportFormat::Json),
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "pdf" => Ok(ReportFormat::Pdf),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
 
 pub struct Results {
     app_package: String,
@@ -26,19 +147,34 @@ pub struct Results {
     app_min_sdk: i32,
     app_target_sdk: Option<i32>,
     app_fingerprint: FingerPrint,
+    apk_info: Option<ApkInfo>,
     warnings: BTreeSet<Vulnerability>,
     low: BTreeSet<Vulnerability>,
     medium: BTreeSet<Vulnerability>,
     high: BTreeSet<Vulnerability>,
     critical: BTreeSet<Vulnerability>,
+    sections: BTreeSet<ReportSection>,
     benchmarks: Vec<Benchmark>,
+    rule_profiles: Vec<RuleProfile>,
+    mapping: Option<Mapping>,
+    baseline: Option<Baseline>,
+    suppressed: usize,
+    ignore: Option<IgnoreFile>,
+    duplicates: usize,
+    groups: Vec<FindingGroup>,
+    max_samples: usize,
+    checked_controls: BTreeSet<String>,
+    incomplete: Vec<IncompleteStage>,
+    provenance: Provenance,
+    timestamp: String,
 }
 
 impl Results {
     pub fn init(config: &Config) -> Option<Results> {
         let path = format!("{}/{}", config.get_results_folder(), config.get_app_id());
-        if !fs::metadata(&path).is_ok() || config.is_force() {
-            if fs::metadata(&path).is_ok() {
+        // Each analysis gets its own folder with the versioned layout, so nothing is overwritten
+        if config.is_versioned_results() || !fs::metadata(&path).is_ok() || config.is_force() {
+            if !config.is_versioned_results() && fs::metadata(&path).is_ok() {
                 if let Err(e) = fs::remove_dir_all(&path) {
                     print_error(format!("An unknown error occurred when trying to delete the \
                                          results folder: {}",
@@ -58,6 +194,39 @@ impl Results {
                     return None;
                 }
             };
+            let baseline = match config.get_baseline() {
+                Some(path) => {
+                    match Baseline::load(path) {
+                        Ok(b) => Some(b),
+                        Err(e) => {
+                            print_warning(format!("The baseline {} could not be loaded, so no \
+                                                   findings will be suppressed: {}",
+                                                  path,
+                                                  e),
+                                          config.is_verbose());
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            let ignore = match config.get_ignore_file() {
+                Some(path) => {
+                    match IgnoreFile::load(path) {
+                        Ok(i) => Some(i),
+                        Err(e) => {
+                            print_warning(format!("The ignore file {} could not be loaded, so no \
+                                                   findings will be ignored: {}",
+                                                  path,
+                                                  e),
+                                          config.is_verbose());
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            let provenance = Provenance::new(config, &fingerprint);
             if config.is_verbose() {
                 println!("The results struct has been created. All the vulnerabilitis will now \
                           be recorded and when the analysis ends, they will be written to result \
@@ -74,16 +243,30 @@ impl Results {
                 app_min_sdk: 0,
                 app_target_sdk: None,
                 app_fingerprint: fingerprint,
+                apk_info: None,
                 warnings: BTreeSet::new(),
                 low: BTreeSet::new(),
                 medium: BTreeSet::new(),
                 high: BTreeSet::new(),
                 critical: BTreeSet::new(),
+                sections: BTreeSet::new(),
                 benchmarks: if config.is_bench() {
                     Vec::with_capacity(10)
                 } else {
                     Vec::with_capacity(0)
                 },
+                rule_profiles: Vec::new(),
+                mapping: None,
+                baseline: baseline,
+                suppressed: 0,
+                ignore: ignore,
+                duplicates: 0,
+                groups: Vec::new(),
+                max_samples: 0,
+                checked_controls: BTreeSet::new(),
+                incomplete: Vec::new(),
+                provenance: provenance,
+                timestamp: format!("{}", Local::now().format(TIMESTAMP_FORMAT)),
             })
         } else {
             if config.is_verbose() {
@@ -122,7 +305,38 @@ impl Results {
         self.app_target_sdk = Some(sdk);
     }
 
-    pub fn add_vulnerability(&mut self, vuln: Vulnerability) {
+    /// Sets the structured metadata of the application package, shown in the header of the
+    /// report
+    pub fn set_apk_info(&mut self, info: ApkInfo) {
+        self.apk_info = Some(info);
+    }
+
+    /// Gets the API level the application targets, or its minimum API level if the target is
+    /// not specified
+    pub fn get_app_target_sdk(&self) -> Option<i32> {
+        match self.app_target_sdk {
+            Some(sdk) => Some(sdk),
+            None if self.app_min_sdk > 0 => Some(self.app_min_sdk),
+            None => None,
+        }
+    }
+
+    /// Sets the mapping used to de-obfuscate the findings and the source code of the report
+    pub fn set_mapping(&mut self, mapping: Mapping) {
+        self.mapping = Some(mapping);
+    }
+
+    pub fn add_vulnerability(&mut self, mut vuln: Vulnerability) {
+        if let Some(ref mapping) = self.mapping {
+            vuln.deobfuscate(mapping);
+        }
+        if self.baseline.as_ref().map_or(false, |b| b.contains(&vuln)) {
+            self.suppressed += 1;
+            return;
+        }
+        if self.ignore.as_mut().map_or(false, |i| i.ignores(&vuln)) {
+            return;
+        }
         match vuln.get_criticity() {
             Criticity::Warning => {
                 self.warnings.insert(vuln);
@@ -142,6 +356,53 @@ impl Results {
         }
     }
 
+    /// Records that a MASVS control was checked by the analysis, for the compliance matrix
+    pub fn add_checked_control<S: Into<String>>(&mut self, control: S) {
+        let _ = self.checked_controls.insert(control.into());
+    }
+
+    /// Marks the analysis as partial, since the given stage didn't finish
+    pub fn add_incomplete_stage(&mut self, stage: IncompleteStage) {
+        self.incomplete.push(stage);
+    }
+
+    /// Records the duration of a stage of the analysis in the metadata of the report
+    pub fn add_stage_duration(&mut self, stage: &str, duration: Duration) {
+        self.provenance.add_duration(stage, duration);
+    }
+
+    /// Gets the stages of the analysis that didn't finish
+    pub fn get_incomplete_stages(&self) -> &[IncompleteStage] {
+        self.incomplete.as_slice()
+    }
+
+    /// Adds a section to the report
+    ///
+    /// Returns `false` if the section could not be added because its key is already used by the
+    /// report or by another section.
+    pub fn add_section(&mut self, section: ReportSection) -> bool {
+        if RESERVED_KEYS.contains(&section.get_key()) ||
+           self.sections.iter().any(|s| s.get_key() == section.get_key()) {
+            return false;
+        }
+        self.sections.insert(section)
+    }
+
+    pub fn get_sections(&self) -> SetIter<ReportSection> {
+        self.sections.iter()
+    }
+
+    /// Gets the folder where the report of the analysis is generated: the results folder of the
+    /// application, or the folder of this run inside it with the versioned layout
+    pub fn get_report_folder(&self, config: &Config) -> PathBuf {
+        let folder = Path::new(config.get_results_folder()).join(config.get_app_id());
+        if config.is_versioned_results() {
+            folder.join(self.app_version_num.to_string()).join(&self.timestamp)
+        } else {
+            folder
+        }
+    }
+
     pub fn add_benchmark(&mut self, bench: Benchmark) {
         self.benchmarks.push(bench);
     }
@@ -150,8 +411,79 @@ impl Results {
         self.benchmarks.iter()
     }
 
+    /// Sets the profiles of the code rules, sorted by the time spent on them
+    pub fn set_rule_profiles(&mut self, mut profiles: Vec<RuleProfile>) {
+        profiles.sort_by(|a, b| b.get_duration().cmp(&a.get_duration()));
+        self.rule_profiles = profiles;
+    }
+
+    pub fn get_rule_profiles(&self) -> Iter<RuleProfile> {
+        self.rule_profiles.iter()
+    }
+
+    /// Maps the findings in decompiled files to the files and lines of the source code of the
+    /// application, if its source root is configured
+    pub fn map_to_source(&mut self, config: &Config) {
+        let root = match config.get_source_root() {
+            Some(r) => r,
+            None => return,
+        };
+        let source_map = match SourceMap::load(root) {
+            Ok(m) => m,
+            Err(e) => {
+                print_warning(format!("The source code in {} could not be loaded, so the \
+                                       findings will not be mapped to it: {}",
+                                      root,
+                                      e),
+                              config.is_verbose());
+                return;
+            }
+        };
+        let mut mapped = 0;
+        for set in vec![&mut self.critical,
+                        &mut self.high,
+                        &mut self.medium,
+                        &mut self.low,
+                        &mut self.warnings] {
+            let vulns = mem::replace(set, BTreeSet::new());
+            for mut vuln in vulns {
+                if let Some((file, line)) = source_map.map(&vuln) {
+                    vuln.set_source(&file.to_string_lossy(), line);
+                    mapped += 1;
+                }
+                let _ = set.insert(vuln);
+            }
+        }
+        if config.is_verbose() {
+            println!("{} findings were mapped to the source code in {}.", mapped, root);
+        }
+    }
+
+    /// Removes the duplicated findings, if enabled, and groups the rest by rule, counting the
+    /// configured number of samples of each group as listed in the reports
+    pub fn group_findings(&mut self, config: &Config) {
+        let grouping = config.get_grouping_config();
+        let mut groups = Vec::new();
+        for set in vec![&mut self.critical,
+                        &mut self.high,
+                        &mut self.medium,
+                        &mut self.low,
+                        &mut self.warnings] {
+            if grouping.is_deduplicate() {
+                self.duplicates += deduplicate(set);
+            }
+            groups.extend(group_findings(set, grouping.get_max_samples()));
+        }
+        self.max_samples = grouping.get_max_samples();
+        if config.is_verbose() && self.duplicates > 0 {
+            println!("{} duplicated findings were removed from the results.",
+                     self.duplicates);
+        }
+        self.groups = groups;
+    }
+
     pub fn generate_report(&self, config: &Config) -> Result<()> {
-        let path = format!("{}/{}", config.get_results_folder(), config.get_app_id());
+        let path = self.get_report_folder(config);
         if !file_exists(&path) || config.is_force() {
             if file_exists(&path) {
                 if let Err(e) = fs::remove_dir_all(&path) {
@@ -170,18 +502,100 @@ impl Results {
                 println!("Results folder created. Time to create the reports.");
             }
 
-            try!(self.generate_json_report(config));
+            if let Some(ref info) = self.apk_info {
+                if let (Some(icon), Some(file)) = (info.get_icon(), info.get_icon_file()) {
+                    if let Err(e) = fs::copy(icon, path.join(file)) {
+                        print_warning(format!("The launcher icon could not be copied to the \
+                                               report folder: {}",
+                                              e),
+                                      config.is_verbose());
+                    }
+                }
+            }
 
-            if config.is_verbose() {
-                println!("JSON report generated.");
-                println!("");
+            if config.has_report_format(ReportFormat::Json) {
+                try!(self.generate_json_report(config));
+
+                if config.is_verbose() {
+                    println!("JSON report generated.");
+                    println!("");
+                }
             }
 
-            try!(self.generate_html_report(config));
+            if config.is_profile_rules() {
+                try!(self.generate_rule_profile(config));
+            }
 
-            if config.is_verbose() {
-                println!("HTML report generated.");
+            if config.is_gitlab_sast() {
+                try!(generate_gitlab_report(self, config));
+                if config.is_verbose() {
+                    println!("GitLab SAST report generated.");
+                    println!("");
+                }
+            }
+
+            if config.is_github_annotations() {
+                try!(generate_github_annotations(self, config));
+                if config.is_verbose() {
+                    println!("GitHub annotations generated.");
+                    println!("");
+                }
+            }
+
+            if config.get_issues_config().is_enabled() {
+                try!(export_issues(self, config));
+                if config.is_verbose() {
+                    println!("Issues exported.");
+                    println!("");
+                }
             }
+
+            if config.has_report_format(ReportFormat::Html) {
+                try!(self.generate_html_report(config));
+
+                if config.is_verbose() {
+                    println!("HTML report generated.");
+                }
+            }
+
+            if config.has_report_format(ReportFormat::Markdown) {
+                try!(generate_markdown_report(self, config));
+
+                if config.is_verbose() {
+                    println!("Markdown report generated.");
+                }
+            }
+
+            if config.has_report_format(ReportFormat::Pdf) {
+                try!(generate_pdf_report(self, config));
+
+                if config.is_verbose() {
+                    println!("PDF report generated.");
+                }
+            }
+
+            if let Err(e) = append_history(&HistoryEntry::new(self), config) {
+                print_warning(format!("The analysis could not be added to the history of the \
+                                       application: {}",
+                                      e),
+                              config.is_verbose());
+            }
+            if config.is_versioned_results() {
+                if let Err(e) = add_run(&Run::new(self), config) {
+                    print_warning(format!("The analysis could not be added to the index of the \
+                                           runs of the application: {}",
+                                          e),
+                                  config.is_verbose());
+                }
+            }
+            if config.is_trend() {
+                try!(generate_trend_report(self, config));
+                if config.is_verbose() {
+                    println!("Trend report generated.");
+                }
+            }
+
+            send_notifications(self, config);
         }
 
         Ok(())
@@ -191,348 +605,186 @@ impl Results {
         if config.is_verbose() {
             println!("Starting JSON report generation. First we create the file.")
         }
-        let mut f = try!(File::create(format!("{}/{}/results.json",
-                                              config.get_results_folder(),
-                                              config.get_app_id())));
+        let mut f = try!(File::create(self.get_report_folder(config).join("results.json")));
         if config.is_verbose() {
             println!("The report file has been created. Now it's time to fill it.")
         }
 
-        let report = ObjectBuilder::new()
+        let references = self.get_cross_references();
+        let mut report = ObjectBuilder::new()
+            .insert("schema_version", SCHEMA_VERSION)
+            .insert("metadata", &self.provenance)
             .insert("label", self.app_label.as_str())
             .insert("description", self.app_description.as_str())
             .insert("package", self.app_package.as_str())
             .insert("version", self.app_version.as_str())
+            .insert("version_num", self.app_version_num)
+            .insert("min_sdk", self.app_min_sdk)
+            .insert("target_sdk", self.app_target_sdk)
             .insert("fingerprint", &self.app_fingerprint)
+            .insert("apk", &self.apk_info)
+            .insert("incomplete", &self.incomplete)
+            .insert("risk", RiskScore::new(self))
+            .insert("masvs", get_masvs_matrix(self))
+            .insert_object("methodology", |b| {
+                b.insert("suppressed", self.suppressed)
+                    .insert("ignore_file", &self.ignore)
+                    .insert("duplicates", self.duplicates)
+            })
+            .insert("groups", &self.groups)
             .insert_array("warnings", |builder| {
-                let mut builder = builder;
-                for warn in &self.warnings {
-                    builder = builder.push(warn);
-                }
-                builder
+                self.push_json_vuln_set(builder, &self.warnings, Criticity::Warning, &references)
             })
             .insert_array("low", |builder| {
-                let mut builder = builder;
-                for vuln in &self.low {
-                    builder = builder.push(vuln);
-                }
-                builder
+                self.push_json_vuln_set(builder, &self.low, Criticity::Low, &references)
             })
             .insert_array("medium", |builder| {
-                let mut builder = builder;
-                for vuln in &self.medium {
-                    builder = builder.push(vuln);
-                }
-                builder
+                self.push_json_vuln_set(builder, &self.medium, Criticity::Medium, &references)
             })
             .insert_array("high", |builder| {
-                let mut builder = builder;
-                for vuln in &self.high {
-                    builder = builder.push(vuln);
-                }
-                builder
+                self.push_json_vuln_set(builder, &self.high, Criticity::High, &references)
             })
             .insert_array("critical", |builder| {
-                let mut builder = builder;
-                for vuln in &self.critical {
-                    builder = builder.push(vuln);
-                }
-                builder
+                self.push_json_vuln_set(builder, &self.critical, Criticity::Critical, &references)
             })
             .build();
 
+        if let Value::Object(ref mut report) = report {
+            for section in &self.sections {
+                report.insert(String::from(section.get_key()), section.get_data().clone());
+            }
+        }
+        if config.is_reproducible() {
+            make_reproducible(&mut report, config);
+        }
+
         try!(f.write_all(&format!("{:?}", report).into_bytes()));
 
         Ok(())
     }
 
-    fn generate_html_report(&self, config: &Config) -> Result<()> {
+    /// Writes the profiles of the code rules to the `rule_profile.json` file of the report
+    fn generate_rule_profile(&self, config: &Config) -> Result<()> {
+        let mut f = try!(File::create(self.get_report_folder(config).join("rule_profile.json")));
+        try!(f.write_all(&format!("{:?}", to_value(&self.rule_profiles)).into_bytes()));
         if config.is_verbose() {
-            println!("Starting HTML report generation. First we create the file.")
+            println!("Rule profile generated.");
         }
-        let mut f = try!(File::create(format!("{}/{}/index.html",
-                                              config.get_results_folder(),
-                                              config.get_app_id())));
-        if config.is_verbose() {
-            println!("The report file has been created. Now it's time to fill it.")
-        }
-
-        let now = Local::now();
+        Ok(())
+    }
 
-        // Header
-        try!(f.write_all(b"<!DOCTYPE html>"));
-        try!(f.write_all(b"<html lang=\"en\">"));
-        try!(f.write_all(b"<head>"));
-        try!(f.write_all(b"<title>Vulnerability report</title>"));
-        try!(f.write_all(b"<meta charset=\"UTF-8\">"));
-        try!(f.write_all(b"<link rel=\"stylesheet\" href=\"css/style.css\">"));
-        try!(f.write_all(b"<link rel=\"stylesheet\" href=\"css/androidstudio.css\">"));
-        try!(f.write_all(b"</head>"));
-        try!(f.write_all(b"<body>"));
-        try!(f.write_all(b"<section class=\"report\">"));
-        try!(f.write_all(b"<a href=\"http://superanalyzer.rocks\" \
-                                title=\"S.U.P.E.R. Android Analyzer\">\
-                            <img src=\"img/logo.png\" alt=\"S.U.P.E.R. Android Analyzer\"></a>"));
-        try!(f.write_all(b"<h1 id=\"title\">S.U.P.E.R. Android Analyzer \
-                            Report</h1>"));
-        try!(f.write_all(&format!("<p>This is the vulnerability report for the android \
-                                   application <em>{}</em>. Report generated on {}.</p>",
-                                  self.app_package,
-                                  now.to_rfc2822())
-            .into_bytes()));
-
-        // Application data
-        try!(f.write_all(b"<h2>Application data:</h2>"));
-        try!(f.write_all(b"<ul>"));
-        if !self.app_label.is_empty() {
-            try!(f.write_all(&format!("<li><strong>Label:</strong> {}</li>",
-                                      self.app_label.as_str())
-                .into_bytes()));
-        }
-        if !self.app_description.is_empty() {
-            try!(f.write_all(&format!("<li><strong>Description:</strong> {}</li>",
-                                      self.app_description.as_str())
-                .into_bytes()));
-        }
-        if !self.app_package.is_empty() {
-            try!(f.write_all(&format!("<li><strong>Package:</strong> {}</li>",
-                                      self.app_package.as_str())
-                .into_bytes()));
-        }
-        if !self.app_version.is_empty() {
-            try!(f.write_all(&format!("<li><strong>Version:</strong> {}</li>",
-                                      self.app_version.as_str())
-                .into_bytes()));
-        }
-        if self.app_version_num > 0 {
-            try!(f.write_all(&format!("<li><strong>Version number:</strong> {}</li>",
-                                      self.app_version_num)
-                .into_bytes()));
-        }
-        if self.app_min_sdk > 0 {
-            try!(f.write_all(&format!("<li><strong>Minimum SDK version:</strong> {}</li>",
-                                      self.app_min_sdk)
-                .into_bytes()));
-        }
-        if self.app_target_sdk.is_some() {
-            try!(f.write_all(&format!("<li><strong>Target SDK:</strong> {}</li>",
-                                      self.app_target_sdk.unwrap())
-                .into_bytes()));
-        }
-        try!(f.write_all(b"<li><strong>Fingerprints:</strong><ul>"));
-        try!(f.write_all(&format!("<li>MD5: {}</li>", self.app_fingerprint.get_md5().to_hex())
-            .into_bytes()));
-        try!(f.write_all(&format!("<li>SHA-1: {}</li>",
-                                  self.app_fingerprint.get_sha1().to_hex())
-            .into_bytes()));
-        try!(f.write_all(&format!("<li>SHA-256: {}</li>",
-                                  self.app_fingerprint.get_sha256().to_hex())
-            .into_bytes()));
-        try!(f.write_all(b"</ul></li>"));
-
-        try!(f.write_all(b"<li><a href=\"src/index.html\" \
-                        title=\"Source code\">Check source code</a></li>"));
-        try!(f.write_all(b"</ul>"));
-
-        // Vulnerability count
-        let total_vuln = self.low.len() + self.medium.len() + self.high.len() + self.critical.len();
-        try!(f.write_all(&format!("<h3>Total vulnerabilities found: {}</h3>", total_vuln)
-            .into_bytes()));
-        try!(f.write_all(b"<ul>"));
-        if self.critical.len() == 0 {
-            try!(f.write_all(b"<li>Critical: 0</li>"));
-        } else {
-            try!(f.write_all(&format!("<li>Critical: <span class=\"critical\">{}</span> <a \
-                                       href=\"#critical\" title=\"Critical\">⇒</a></li>",
-                                      self.critical.len())
-                .into_bytes()));
-        }
-        if self.high.len() == 0 {
-            try!(f.write_all(b"<li>High: 0</li>"));
-        } else {
-            try!(f.write_all(&format!("<li>High: <span class=\"high\">{}</span> <a \
-                                       href=\"#high\" title=\"High\">⇒</a></li>",
-                                      self.high.len())
-                .into_bytes()));
-        }
-        if self.medium.len() == 0 {
-            try!(f.write_all(b"<li>Medium: 0</li>"));
-        } else {
-            try!(f.write_all(&format!("<li>Medium: <span class=\"medium\">{}</span> <a \
-                                       href=\"#medium\" title=\"Medium\">⇒</a></li>",
-                                      self.medium.len())
-                .into_bytes()));
-        }
-        if self.low.len() == 0 {
-            try!(f.write_all(b"<li>Low: 0</li>"));
-        } else {
-            try!(f.write_all(&format!("<li>Low: <span class=\"low\">{}</span> <a href=\"#low\" \
-                                       title=\"Low\">⇒</a></li>",
-                                      self.low.len())
-                .into_bytes()));
-        }
-        if self.warnings.len() == 0 {
-            try!(f.write_all(b"<li>Warnings: 0</li>"));
-        } else {
-            try!(f.write_all(&format!("<li>Warnings: <span class=\"warnings\">{}</span> <a \
-                                       href=\"#warnings\" title=\"Warnings\">⇒</a></li>",
-                                      self.warnings.len())
-                .into_bytes()));
+    fn push_json_vuln_set(&self,
+                          builder: ArrayBuilder,
+                          set: &BTreeSet<Vulnerability>,
+                          criticity: Criticity,
+                          references: &BTreeMap<String, Vec<String>>)
+                          -> ArrayBuilder {
+        let mut builder = builder;
+        for (i, vuln) in set.iter().enumerate() {
+            let id = Results::get_vulnerability_id(criticity, i);
+            let mut value = to_value(vuln);
+            if let Value::Object(ref mut object) = value {
+                object.insert(String::from("references"),
+                              Value::Array(match references.get(&id) {
+                                  Some(r) => r.iter().map(|r| Value::String(r.clone())).collect(),
+                                  None => Vec::new(),
+                              }));
+                object.insert(String::from("id"), Value::String(id));
+            }
+            builder = builder.push(value);
         }
-        try!(f.write_all(b"</ul>"));
+        builder
+    }
 
-        try!(f.write_all(b"<h2>Vulnerabilities:</h2>"));
+    /// Gets the identifier of the vulnerability in the given position of its criticity set
+    fn get_vulnerability_id(criticity: Criticity, index: usize) -> String {
+        format!("{}{:03}",
+                format!("{:?}", criticity).chars().nth(0).unwrap(),
+                index + 1)
+    }
 
-        if self.critical.len() > 0 {
-            try!(self.print_html_vuln_set(&mut f, &self.critical, Criticity::Critical))
-        }
+    /// Gets the findings of the set listed in the HTML, Markdown and PDF reports, with their
+    /// index in the set
+    fn get_listed_findings<'r>(&self,
+                               set: &'r BTreeSet<Vulnerability>)
+                               -> Vec<(usize, &'r Vulnerability)> {
+        get_listed_findings(set, self.max_samples)
+    }
 
-        if self.high.len() > 0 {
-            try!(self.print_html_vuln_set(&mut f, &self.high, Criticity::High))
+    /// Links permission findings with the code findings relying on those permissions
+    ///
+    /// Returns, for each vulnerability ID, the IDs of its related vulnerabilities.
+    fn get_cross_references(&self) -> BTreeMap<String, Vec<String>> {
+        let sets = [(Criticity::Critical, &self.critical),
+                    (Criticity::High, &self.high),
+                    (Criticity::Medium, &self.medium),
+                    (Criticity::Low, &self.low),
+                    (Criticity::Warning, &self.warnings)];
+
+        let mut permission_ids = BTreeMap::new();
+        let mut code_ids = Vec::new();
+        for &(criticity, set) in sets.iter() {
+            for (i, vuln) in set.iter().enumerate() {
+                let id = Results::get_vulnerability_id(criticity, i);
+                if let Some(permission) = vuln.get_permission() {
+                    permission_ids.insert(permission, id);
+                } else if !vuln.get_required_permissions().is_empty() {
+                    code_ids.push((id, vuln.get_required_permissions()));
+                }
+            }
         }
 
-        if self.medium.len() > 0 {
-            try!(self.print_html_vuln_set(&mut f, &self.medium, Criticity::Medium))
+        let mut references: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (id, permissions) in code_ids {
+            for permission in permissions {
+                if let Some(permission_id) = permission_ids.get(permission) {
+                    references.entry(id.clone())
+                        .or_insert_with(Vec::new)
+                        .push(permission_id.clone());
+                    references.entry(permission_id.clone())
+                        .or_insert_with(Vec::new)
+                        .push(id.clone());
+                }
+            }
         }
+        references
+    }
 
-        if self.low.len() > 0 {
-            try!(self.print_html_vuln_set(&mut f, &self.low, Criticity::Low))
+    fn generate_html_report(&self, config: &Config) -> Result<()> {
+        if config.is_verbose() {
+            println!("Starting HTML report generation. First we render the report templates.")
         }
-
-        if self.warnings.len() > 0 {
-            try!(self.print_html_vuln_set(&mut f, &self.warnings, Criticity::Warning))
+        let report = try!(render_html_report(self, config));
+        let mut f = try!(File::create(self.get_report_folder(config).join("index.html")));
+        try!(f.write_all(report.as_bytes()));
+        if config.is_verbose() {
+            println!("The report file has been created. Now it's time to copy the assets.")
         }
-        try!(f.write_all(b"</section>"));
-
-        // Footer
-        try!(f.write_all(b"<footer>"));
-        try!(f.write_all(&format!("<p>Copyright © {} - S.U.P.E.R. Android Analyzer</p>",
-                                  if now.year() > 2016 {
-                                      format!("2016 - {}", now.year())
-                                  } else {
-                                      format!("{}", now.year())
-                                  })
-            .into_bytes()));
-        try!(f.write_all(b"</footer>"));
-        try!(f.write_all(b"<script src=\"js/highlight.pack.js\"></script>"));
-        try!(f.write_all(b"<script>hljs.initHighlightingOnLoad();</script>"));
-        try!(f.write_all(b"<script src=\"js/jquery-3.1.0.slim.min.js\"></script>"));
-        try!(f.write_all(b"<script>$('.vulnerability h4 a.collapse').click(function(event) {\
-            event.preventDefault();\
-            $(this).parents('section.vulnerability').find('ul div').hide('slow');\
-            $(this).hide('fast');\
-            $(this).prev('a').show('fast');\
-        });\
-        $('.vulnerability h4 a.show').click(function(event) {\
-            event.preventDefault();\
-            $(this).parents('section.vulnerability').find('ul div').show('slow');\
-            $(this).hide('fast');\
-            $(this).next('a').show('fast');\
-        });</script>"));
-        try!(f.write_all(b"</body>"));
-        try!(f.write_all(b"</html>"));
 
         // Copying JS and CSS files
-        try!(copy_folder(config.get_results_template(),
-                         &format!("{}/{}", config.get_results_folder(), config.get_app_id())));
+        try!(copy_folder(Path::new(config.get_results_template()),
+                         &self.get_report_folder(config)));
 
-        try!(self.generate_code_html_files(config));
+        if config.is_include_source() {
+            try!(self.generate_code_html_files(config));
+        }
 
         Ok(())
     }
 
-    fn print_html_vuln_set(&self,
-                           f: &mut File,
-                           set: &BTreeSet<Vulnerability>,
-                           criticity: Criticity)
-                           -> Result<()> {
-        let criticity_str = format!("{:?}", criticity);
-        if criticity == Criticity::Warning {
-            try!(f.write_all(&String::from("<h3 id=\"warnings\">Warnings: <a href=\"#title\" \
-                                            title=\"Top\">⇮</a></h3>")
-                .into_bytes()));
-
-        } else {
-            try!(f.write_all(&format!("<h3 id=\"{}\">{} criticity vulnerabilities: <a \
-                                       href=\"#title\" title=\"Top\">⇮</a></h3>",
-                                      criticity_str.to_lowercase(),
-                                      criticity_str)
-                .into_bytes()));
-        }
-
-        for (i, vuln) in set.iter().enumerate() {
-            try!(f.write_all(b"<section class=\"vulnerability\">"));
-            try!(f.write_all(&format!("<h4>{}{:03}: <a href=\"#\" title=\"Display \
-                                       vulnerability\" class=\"show\">+</a><a href=\"#\" \
-                                       style=\"display: none\" class=\"collapse\" \
-                                       title=\"Collapse vulnerability\">-</a></h4>",
-                                      criticity_str.chars().nth(0).unwrap(),
-                                      i + 1)
-                .into_bytes()));
-            try!(f.write_all(b"<ul>"));
-            try!(f.write_all(&format!("<li><strong>Label:</strong> {}</li>", vuln.get_name())
-                .into_bytes()));
-            try!(f.write_all(b"<div style=\"display: none\">"));
-            try!(f.write_all(&format!("<li><strong>Description:</strong> {}</li>",
-                                      vuln.get_description())
-                .into_bytes()));
-            if let Some(file) = vuln.get_file() {
-                try!(f.write_all(&format!("<li><strong>File:</strong> <a \
-                                           href=\"src/{0}.html\">{0}</a></li>",
-                                          file.display())
-                    .into_bytes()));
-            }
-            if let Some(code) = vuln.get_code() {
-                if vuln.get_start_line().unwrap() != vuln.get_end_line().unwrap() {
-                    try!(f.write_all(&format!("<li><strong>Lines:</strong> {}-{}</li>",
-                                              vuln.get_start_line().unwrap() + 1,
-                                              vuln.get_end_line().unwrap() + 1)
-                        .into_bytes()));
-                } else {
-                    try!(f.write_all(&format!("<li><strong>Line:</strong> {}</li>",
-                                              vuln.get_start_line().unwrap() + 1)
-                        .into_bytes()));
-                }
-
-                let start_line = if vuln.get_start_line().unwrap() < 5 {
-                    0
-                } else {
-                    vuln.get_start_line().unwrap() - 4
-                };
-
-                let mut lines = String::new();
-                for (i, _line) in code.lines().enumerate() {
-                    if i + start_line >= vuln.get_start_line().unwrap() &&
-                       i + start_line <= vuln.get_end_line().unwrap() {
-                        lines.push_str(format!("-&gt;<em>{}</em><br>", i + start_line+1).as_str());
-                    } else {
-                        lines.push_str(format!("{}<br>", i + start_line + 1).as_str());
-                    }
-                }
-                let lang = vuln.get_file().unwrap().extension().unwrap().to_string_lossy();
-                try!(f.write_all(&format!("<li><p><strong>Affected code:</strong></p><div><div \
-                                           class=\"line_numbers\">{}</div><div \
-                                           class=\"code\"><pre><code \
-                                           class=\"{}\">{}</code></pre></div></li>",
-                                          lines,
-                                          lang,
-                                          Results::html_escape(code))
-                    .into_bytes()));
-            }
-            try!(f.write_all(b"</div>"));
-            try!(f.write_all(b"</ul>"));
-            try!(f.write_all(b"</section>"));
-        }
-        Ok(())
+    pub fn html_yes_no(value: bool) -> &'static str {
+        if value { "Yes" } else { "No" }
     }
 
     fn generate_code_html_files(&self, config: &Config) -> Result<()> {
-        try!(self.generate_code_html_folder("", config));
+        let renderer = try!(SourceRenderer::new(self, config));
+        try!(self.generate_code_html_folder("", config, &renderer));
+        try!(self.generate_finding_html_files(config, &renderer));
         let menu = try!(self.generate_html_src_menu("", config));
 
-        let mut f = try!(fs::File::create(format!("{}/{}/src/index.html",
-                                                  config.get_results_folder(),
-                                                  config.get_app_id())));
+        let mut f = try!(fs::File::create(self.get_report_folder(config)
+            .join("src")
+            .join("index.html")));
 
         try!(f.write_all(b"<!DOCTYPE html>"));
         try!(f.write_all(b"<html lang=\"en\">"));
@@ -558,10 +810,40 @@ impl Results {
         Ok(())
     }
 
-    fn generate_code_html_folder<P: AsRef<Path>>(&self, path: P, config: &Config) -> Result<usize> {
+    /// Generates the HTML pages for the files with vulnerabilities that were not rendered yet
+    ///
+    /// Smali folders and the Android and Google libraries are not fully rendered, since they are
+    /// huge, but the files with findings need their page so that the report can link to them.
+    fn generate_finding_html_files(&self,
+                                   config: &Config,
+                                   renderer: &SourceRenderer)
+                                   -> Result<()> {
+        for file in renderer.get_finding_files() {
+            if file_exists(self.get_report_folder(config)
+                .join("src")
+                .join(format!("{}.html", file.display()))) ||
+               !file_exists(&format!("{}/{}/{}",
+                                     config.get_dist_folder(),
+                                     config.get_app_id(),
+                                     file.display())) {
+                continue;
+            }
+            if let Some(parent) = file.parent() {
+                try!(fs::create_dir_all(self.get_report_folder(config).join("src").join(parent)));
+            }
+            try!(self.generate_code_html_for(file, config, renderer));
+        }
+        Ok(())
+    }
+
+    fn generate_code_html_folder<P: AsRef<Path>>(&self,
+                                                 path: P,
+                                                 config: &Config,
+                                                 renderer: &SourceRenderer)
+                                                 -> Result<usize> {
         if path.as_ref() == Path::new("classes/android") ||
            path.as_ref() == Path::new("classes/com/google/android/gms") ||
-           path.as_ref() == Path::new("smali") {
+           is_smali_folder(path.as_ref()) {
             return Ok(0);
         }
         let dir_iter = try!(fs::read_dir(&format!("{}/{}/{}",
@@ -569,10 +851,7 @@ impl Results {
                                                   config.get_app_id(),
                                                   path.as_ref().display())));
 
-        try!(fs::create_dir_all(&format!("{}/{}/src/{}",
-                                         config.get_results_folder(),
-                                         config.get_app_id(),
-                                         path.as_ref().display())));
+        try!(fs::create_dir_all(self.get_report_folder(config).join("src").join(path.as_ref())));
         let mut count = 0;
 
         for f in dir_iter {
@@ -595,7 +874,8 @@ impl Results {
                         let prefix =
                             format!("{}/{}/", config.get_dist_folder(), config.get_app_id());
                         try!(self.generate_code_html_for(f.path().strip_prefix(&prefix).unwrap(),
-                                                         config));
+                                                         config,
+                                                         renderer));
                         count += 1;
                     }
                 }
@@ -608,7 +888,8 @@ impl Results {
                             let f_count = try!(self.generate_code_html_folder(f.path()
                                                                .strip_prefix(&prefix)
                                                                .unwrap(),
-                                                           config));
+                                                           config,
+                                                           renderer));
                             if f_count > 0 {
                                 count += 1;
                             }
@@ -618,10 +899,7 @@ impl Results {
             }
         }
         if count == 0 {
-            try!(fs::remove_dir(&format!("{}/{}/src/{}",
-                                         config.get_results_folder(),
-                                         config.get_app_id(),
-                                         path.as_ref().display())));
+            try!(fs::remove_dir(self.get_report_folder(config).join("src").join(path.as_ref())));
         }
 
         Ok(count)
@@ -631,10 +909,9 @@ impl Results {
                                               dir_path: P,
                                               config: &Config)
                                               -> Result<String> {
-        let iter = try!(fs::read_dir(&format!("{}/{}/src/{}",
-                                              config.get_results_folder(),
-                                              config.get_app_id(),
-                                              dir_path.as_ref().display())));
+        let iter = try!(fs::read_dir(self.get_report_folder(config)
+            .join("src")
+            .join(dir_path.as_ref())));
         let mut menu = String::new();
         menu.push_str("<ul>");
         for entry in iter {
@@ -670,9 +947,7 @@ impl Results {
                             Some(n) => String::from(n.to_string_lossy().borrow() as &str),
                             None => String::new(),
                         };
-                        let prefix = format!("{}/{}/src/",
-                                             config.get_results_folder(),
-                                             config.get_app_id());
+                        let prefix = self.get_report_folder(config).join("src");
                         let submenu =
                             match self.generate_html_src_menu(path.strip_prefix(&prefix).unwrap(),
                                                         config) {
@@ -711,60 +986,31 @@ impl Results {
         Ok(menu)
     }
 
-    fn generate_code_html_for<P: AsRef<Path>>(&self, path: P, config: &Config) -> Result<()> {
+    fn generate_code_html_for<P: AsRef<Path>>(&self,
+                                              path: P,
+                                              config: &Config,
+                                              renderer: &SourceRenderer)
+                                              -> Result<()> {
         let mut f_in = try!(File::open(format!("{}/{}/{}",
                                                config.get_dist_folder(),
                                                config.get_app_id(),
                                                path.as_ref().display())));
-        let mut f_out = try!(File::create(format!("{}/{}/src/{}.html",
-                                                  config.get_results_folder(),
-                                                  config.get_app_id(),
-                                                  path.as_ref().display())));
+        let mut f_out = try!(File::create(self.get_report_folder(config)
+            .join("src")
+            .join(format!("{}.html", path.as_ref().display()))));
 
         let mut code = String::new();
         try!(f_in.read_to_string(&mut code));
-        let code = Results::html_escape(code.as_str());
-
-        let mut back_path = String::new();
-        for _ in 0..path.as_ref().components().count() {
-            back_path.push_str("../");
-        }
-
-        let mut line_numbers = String::new();
-        for i in 0..code.lines().count() {
-            line_numbers.push_str(format!("{}<br>", i + 1).as_str());
-        }
-
-        try!(f_out.write_all(b"<!DOCTYPE html>"));
-        try!(f_out.write_all(b"<html lang=\"en\">"));
-        try!(f_out.write_all(b"<head>"));
-        try!(f_out.write_all(&format!("<title>Source - {}</title>", path.as_ref().display())
-            .into_bytes()));
-        try!(f_out.write_all(b"<meta charset=\"UTF-8\">"));
-        try!(f_out.write_all(&format!("<link rel=\"stylesheet\" href=\"{}css/style.css\">",
-                                      back_path)
-            .into_bytes()));
-        try!(f_out.write_all(&format!("<link rel=\"stylesheet\" href=\"{}css/androidstudio.css\">",
-                                back_path)
-                .into_bytes()));
-        try!(f_out.write_all(b"</head>"));
-        try!(f_out.write_all(b"<body>"));
-        try!(f_out.write_all(&format!("<div><div class=\"line_numbers\">{}</div>", line_numbers)
-            .into_bytes()));
-        try!(f_out.write_all(b"<div class=\"code\"><pre><code>"));
-        try!(f_out.write_all(&code.into_bytes()));
-        try!(f_out.write_all(b"</code></pre></div></div>"));
-        try!(f_out.write_all(&format!("<script src=\"{}js/highlight.pack.js\"></script>",
-                                      back_path)
-            .into_bytes()));
-        try!(f_out.write_all(b"<script>hljs.initHighlightingOnLoad();</script>"));
-        try!(f_out.write_all(b"</body>"));
-        try!(f_out.write_all(b"</html>"));
+        if let Some(ref mapping) = self.mapping {
+            code = mapping.deobfuscate(&code);
+        }
+        let page = try!(renderer.render(path.as_ref(), &code));
+        try!(f_out.write_all(page.as_bytes()));
 
         Ok(())
     }
 
-    fn html_escape(code: &str) -> String {
+    pub fn html_escape(code: &str) -> String {
         let mut res = String::new();
         for c in code.chars() {
             match c {
@@ -777,3 +1023,51 @@ impl Results {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+
+    use {Config, Criticity};
+    use static_analysis::manifest::Permission;
+    use super::{Results, Vulnerability};
+
+    fn finding(criticity: Criticity, name: &str, file: &str) -> Vulnerability {
+        Vulnerability::new(criticity, name, "Description.", Some(file), None, None, None)
+    }
+
+    #[test]
+    fn it_get_cross_references() {
+        let mut config: Config = Default::default();
+        config.set_app_id("com.example.references");
+        config.set_downloads_folder("cross_references_test");
+        config.set_results_folder("cross_references_test");
+        fs::create_dir_all("cross_references_test").unwrap();
+        File::create("cross_references_test/com.example.references.apk")
+            .unwrap()
+            .write_all(b"PK")
+            .unwrap();
+        let results = Results::init(&config);
+        fs::remove_dir_all("cross_references_test").unwrap();
+        let mut results = results.unwrap();
+
+        let mut permission = finding(Criticity::Medium, "SMS permission", "AndroidManifest.xml");
+        permission.set_permission(Permission::AndroidPermissionSendSms);
+        results.add_vulnerability(permission);
+        let mut sms = finding(Criticity::High, "SMS sending", "classes/com/example/Sms.java");
+        sms.set_required_permissions(vec![Permission::AndroidPermissionSendSms]);
+        results.add_vulnerability(sms);
+        let mut camera = finding(Criticity::Low, "Camera usage", "classes/com/example/Cam.java");
+        camera.set_required_permissions(vec![Permission::AndroidPermissionCamera]);
+        results.add_vulnerability(camera);
+
+        let references = results.get_cross_references();
+        assert_eq!(references.get("H001"), Some(&vec![String::from("M001")]));
+        assert_eq!(references.get("M001"), Some(&vec![String::from("H001")]));
+        // The code finding without a permission finding has no references
+        assert_eq!(references.get("L001"), None);
+        assert_eq!(references.len(), 2);
+    }
+}
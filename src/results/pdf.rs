@@ -0,0 +1,57 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::process::Command;
+
+use {Config, Result, print_warning};
+use super::Results;
+use super::template::render_printable_report;
+
+/// Command converting the printable report to PDF
+const PDF_CONVERTER: &'static str = "wkhtmltopdf";
+
+/// Generates the PDF export of the report
+///
+/// The printable version of the report, rendered with the `print` template, is converted with
+/// `wkhtmltopdf`. If it's not installed, the printable `report.print.html` file is kept, so that
+/// it can be printed to PDF from a browser.
+pub fn generate_pdf_report(results: &Results, config: &Config) -> Result<()> {
+    let folder = results.get_report_folder(config);
+    let printable = folder.join("report.print.html");
+    let report = try!(render_printable_report(results, config));
+    {
+        let mut f = try!(File::create(&printable));
+        try!(f.write_all(report.as_bytes()));
+    }
+
+    let output = Command::new(PDF_CONVERTER)
+        .arg("--quiet")
+        .arg("--encoding")
+        .arg("utf-8")
+        .arg(&printable)
+        .arg(folder.join("report.pdf"))
+        .output();
+    match output {
+        Ok(ref o) if o.status.success() => {
+            try!(fs::remove_file(&printable));
+        }
+        Ok(o) => {
+            print_warning(format!("{} returned an error: {}. The printable report was kept in \
+                                   {}.",
+                                  PDF_CONVERTER,
+                                  String::from_utf8_lossy(&o.stderr[..]).trim(),
+                                  printable.display()),
+                          config.is_verbose());
+        }
+        Err(e) => {
+            print_warning(format!("{} could not be run, so the report was not converted to \
+                                   PDF: {}. The printable report was kept in {}, and it can be \
+                                   printed to PDF from a browser.",
+                                  PDF_CONVERTER,
+                                  e,
+                                  printable.display()),
+                          config.is_verbose());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use std::result;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::ser::{Serialize, Serializer};
+use chrono::Local;
+use rustc_serialize::hex::ToHex;
+
+use {Config, print_warning};
+use rule_packs::{RulePack, get_loaded_rule_packs};
+use static_analysis::code::get_rules_hash;
+use tools::get_tool_versions;
+use super::utils::{Benchmark, FingerPrint};
+
+/// Gets the duration in seconds, with the fractional part
+pub fn get_seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000f64
+}
+
+/// Gets the display name of a tool, by its key in the tool versions
+pub fn get_tool_name(tool: &str) -> &str {
+    match tool {
+        "java" => "Java",
+        "apktool" => "APKTool",
+        "dex2jar" => "Dex2Jar",
+        "jd_cmd" => "JD-CMD",
+        _ => tool,
+    }
+}
+
+/// What was run to generate a report: the versions of SUPER, of the rules and of the external
+/// tools, the configuration profile, the analyzed APK and the duration of each stage
+pub struct Provenance {
+    date: String,
+    rules_sha256: Option<String>,
+    rule_packs: Vec<RulePack>,
+    tools: BTreeMap<String, String>,
+    profile: Option<String>,
+    apk_sha256: String,
+    durations: Vec<Benchmark>,
+}
+
+impl Provenance {
+    /// Records the provenance of the analysis of the APK with the given fingerprint
+    ///
+    /// The external tools are run to get their versions, so it should only be created once per
+    /// analysis.
+    pub fn new(config: &Config, fingerprint: &FingerPrint) -> Provenance {
+        let rules_sha256 = match get_rules_hash(config) {
+            Ok(h) => Some(h),
+            Err(e) => {
+                print_warning(format!("The rule files could not be hashed for the report: {}", e),
+                              config.is_verbose());
+                None
+            }
+        };
+        Provenance {
+            date: Local::now().to_rfc3339(),
+            rules_sha256: rules_sha256,
+            rule_packs: get_loaded_rule_packs(config),
+            tools: get_tool_versions(config),
+            profile: config.get_profile().map(String::from),
+            apk_sha256: fingerprint.get_sha256().to_hex(),
+            durations: Vec::new(),
+        }
+    }
+
+    /// Gets the version of SUPER that runs the analysis
+    pub fn get_super_version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    /// Gets the date the analysis started, in RFC 3339 format
+    pub fn get_date(&self) -> &str {
+        self.date.as_str()
+    }
+
+    /// Gets the SHA-256 hash of the loaded rule files, if they could be read
+    pub fn get_rules_sha256(&self) -> Option<&str> {
+        match self.rules_sha256 {
+            Some(ref h) => Some(h.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the installed rule packs whose rules were loaded
+    pub fn get_rule_packs(&self) -> &[RulePack] {
+        &self.rule_packs
+    }
+
+    /// Gets the detected versions of Java and the external tools, by their names
+    pub fn get_tools(&self) -> &BTreeMap<String, String> {
+        &self.tools
+    }
+
+    /// Gets the name of the configuration profile of the analysis, if any
+    pub fn get_profile(&self) -> Option<&str> {
+        match self.profile {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the SHA-256 hash of the analyzed APK, in hexadecimal
+    pub fn get_apk_sha256(&self) -> &str {
+        self.apk_sha256.as_str()
+    }
+
+    /// Gets the stages of the analysis with their durations, in the order they ran
+    pub fn get_durations(&self) -> &[Benchmark] {
+        &self.durations
+    }
+
+    /// Records the duration of a stage of the analysis
+    pub fn add_duration(&mut self, stage: &str, duration: Duration) {
+        self.durations.push(Benchmark::new(stage, duration));
+    }
+}
+
+/// Rule pack in the provenance, with only its name, version and checksum
+struct RulePackVersion<'p>(&'p RulePack);
+
+impl<'p> Serialize for RulePackVersion<'p> {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("RulePackVersion", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.0.get_name()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.0.get_version()));
+        try!(serializer.serialize_struct_elt(&mut state, "sha256", self.0.get_sha256()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Stage of the analysis in the provenance, with its duration in seconds
+struct StageDuration<'b>(&'b Benchmark);
+
+impl<'b> Serialize for StageDuration<'b> {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("StageDuration", 2));
+        try!(serializer.serialize_struct_elt(&mut state, "stage", self.0.get_label()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "seconds",
+                                             get_seconds(self.0.get_duration())));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+impl Serialize for Provenance {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let rule_packs: Vec<_> = self.rule_packs.iter().map(RulePackVersion).collect();
+        let durations: Vec<_> = self.durations.iter().map(StageDuration).collect();
+        let mut state = try!(serializer.serialize_struct("Provenance", 8));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "super_version",
+                                             self.get_super_version()));
+        try!(serializer.serialize_struct_elt(&mut state, "date", self.date.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "rules_sha256", &self.rules_sha256));
+        try!(serializer.serialize_struct_elt(&mut state, "rule_packs", rule_packs));
+        try!(serializer.serialize_struct_elt(&mut state, "tools", &self.tools));
+        try!(serializer.serialize_struct_elt(&mut state, "profile", &self.profile));
+        try!(serializer.serialize_struct_elt(&mut state, "apk_sha256", self.apk_sha256.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "durations", durations));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use serde_json::value::to_value;
+
+    use super::{Provenance, get_seconds};
+
+    #[test]
+    fn it_get_seconds() {
+        assert_eq!(get_seconds(Duration::new(2, 500_000_000)), 2.5);
+        assert_eq!(get_seconds(Duration::from_millis(250)), 0.25);
+    }
+
+    #[test]
+    fn it_serialize_provenance() {
+        let mut tools = BTreeMap::new();
+        let _ = tools.insert(String::from("apktool"), String::from("2.2.0"));
+        let mut provenance = Provenance {
+            date: String::from("2026-10-15T10:00:00+02:00"),
+            rules_sha256: Some(String::from("abc")),
+            rule_packs: Vec::new(),
+            tools: tools,
+            profile: Some(String::from("ci")),
+            apk_sha256: String::from("def"),
+            durations: Vec::new(),
+        };
+        provenance.add_duration("Decompilation", Duration::from_millis(1500));
+
+        let value = to_value(&provenance);
+        assert_eq!(value.lookup("super_version").and_then(|v| v.as_str()),
+                   Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(value.lookup("rules_sha256").and_then(|v| v.as_str()), Some("abc"));
+        assert_eq!(value.lookup("tools.apktool").and_then(|v| v.as_str()), Some("2.2.0"));
+        assert_eq!(value.lookup("profile").and_then(|v| v.as_str()), Some("ci"));
+        assert_eq!(value.lookup("apk_sha256").and_then(|v| v.as_str()), Some("def"));
+        let durations = value.lookup("durations").and_then(|v| v.as_array()).unwrap();
+        assert_eq!(durations[0].lookup("stage").and_then(|v| v.as_str()),
+                   Some("Decompilation"));
+        assert_eq!(durations[0].lookup("seconds").and_then(|v| v.as_f64()), Some(1.5));
+    }
+}
@@ -0,0 +1,134 @@
+use std::env;
+use std::cmp::Ordering;
+use std::path::Path;
+
+use serde_json::value::Value;
+
+use Config;
+
+/// Gets the folders whose absolute paths are removed from the reproducible reports, longest
+/// first, so that the most specific one is removed from each path
+fn get_volatile_folders(config: &Config) -> Vec<String> {
+    let mut folders = vec![Path::new(config.get_dist_folder())
+                               .join(config.get_app_id())
+                               .to_string_lossy()
+                               .into_owned(),
+                           String::from(config.get_dist_folder()),
+                           String::from(config.get_results_folder()),
+                           String::from(config.get_downloads_folder())];
+    if let Some(root) = config.get_source_root() {
+        folders.push(String::from(root));
+    }
+    if let Some(workspace) = config.get_workspace_folder() {
+        folders.push(String::from(workspace));
+    }
+    if let Ok(current) = env::current_dir() {
+        folders.push(current.to_string_lossy().into_owned());
+    }
+
+    let mut folders: Vec<String> = folders.into_iter()
+        .filter(|f| Path::new(f).is_absolute())
+        .map(|f| String::from(f.trim_right_matches(|c: char| c == '/' || c == '\\')))
+        .filter(|f| !f.is_empty())
+        .collect();
+    folders.sort_by(|a, b| {
+        match b.len().cmp(&a.len()) {
+            Ordering::Equal => a.cmp(b),
+            o => o,
+        }
+    });
+    folders.dedup();
+    folders
+}
+
+/// Makes the path in the given string relative, if it's inside any of the given folders
+fn strip_folders(path: &str, folders: &[String]) -> Option<String> {
+    for folder in folders {
+        if path.starts_with(folder.as_str()) {
+            let rest = &path[folder.len()..];
+            if rest.starts_with('/') || rest.starts_with('\\') {
+                return Some(String::from(&rest[1..]));
+            }
+        }
+    }
+    None
+}
+
+/// Makes all the paths in the strings of the given JSON value relative to the given folders
+fn strip_value_paths(value: &mut Value, folders: &[String]) {
+    match *value {
+        Value::String(ref mut s) => {
+            if let Some(relative) = strip_folders(s, folders) {
+                *s = relative;
+            }
+        }
+        Value::Array(ref mut values) => {
+            for v in values.iter_mut() {
+                strip_value_paths(v, folders);
+            }
+        }
+        Value::Object(ref mut map) => {
+            for (_, v) in map.iter_mut() {
+                strip_value_paths(v, folders);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes the values of the JSON report that change between runs of the same analysis, so
+/// that analyzing the same application with the same rules generates the same report
+///
+/// The date and the stage durations of the analysis and the paths of the tool logs, whose names
+/// have the time of the run, are removed, and the absolute paths of the configured folders and
+/// of the working directory are made relative to them.
+pub fn make_reproducible(report: &mut Value, config: &Config) {
+    if let Value::Object(ref mut map) = *report {
+        if let Some(&mut Value::Object(ref mut metadata)) = map.get_mut("metadata") {
+            let _ = metadata.remove("date");
+            let _ = metadata.remove("durations");
+        }
+        if let Some(&mut Value::Array(ref mut stages)) = map.get_mut("incomplete") {
+            for stage in stages.iter_mut() {
+                if let Value::Object(ref mut stage) = *stage {
+                    let _ = stage.remove("log");
+                }
+            }
+        }
+    }
+    strip_value_paths(report, &get_volatile_folders(config));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::builder::ObjectBuilder;
+
+    use super::{strip_folders, strip_value_paths};
+
+    #[test]
+    fn it_strip_folders() {
+        let folders = vec![String::from("/home/user/super/dist/com.example"),
+                           String::from("/home/user/super")];
+        assert_eq!(strip_folders("/home/user/super/dist/com.example/classes/Main.java",
+                                 &folders),
+                   Some(String::from("classes/Main.java")));
+        assert_eq!(strip_folders("/home/user/super/super.ignore", &folders),
+                   Some(String::from("super.ignore")));
+        assert_eq!(strip_folders("/home/user/superuser/super.ignore", &folders), None);
+        assert_eq!(strip_folders("classes/Main.java", &folders), None);
+    }
+
+    #[test]
+    fn it_strip_value_paths() {
+        let mut value = ObjectBuilder::new()
+            .insert("source_file", "/src/app/src/main/java/Main.kt")
+            .insert_array("files", |b| b.push("/src/app/build.gradle").push("README.md"))
+            .build();
+        strip_value_paths(&mut value, &[String::from("/src/app")]);
+        assert_eq!(value,
+                   ObjectBuilder::new()
+                       .insert("source_file", "src/main/java/Main.kt")
+                       .insert_array("files", |b| b.push("build.gradle").push("README.md"))
+                       .build());
+    }
+}
@@ -0,0 +1,144 @@
+use std::result;
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, Serializer};
+
+use static_analysis::certificate::CERTIFICATE_FINDINGS;
+use super::{Results, Vulnerability};
+
+/// Risk points of a finding of each criticity, from warnings to critical
+const CRITICITY_POINTS: [f64; 5] = [0.0, 1.0, 2.0, 5.0, 10.0];
+
+/// Multipliers of the points of the permission and certificate findings
+///
+/// Dangerous permissions are a risk only if they are misused, while certificate issues put every
+/// update of the application at risk.
+const PERMISSION_FACTOR: f64 = 0.5;
+const CERTIFICATE_FACTOR: f64 = 2.0;
+
+/// Points at which the risk score reaches 63, as the score approaches 100 asymptotically
+const SCORE_SCALE: f64 = 40.0;
+
+/// Minimum scores of each risk level, from the highest level
+const LEVELS: [(u8, &'static str); 4] = [(75, "critical"),
+                                         (50, "high"),
+                                         (25, "medium"),
+                                         (1, "low")];
+
+/// Overall risk score of an application, from 0 to 100
+///
+/// Each finding adds points depending on its criticity, weighted for permission and certificate
+/// findings, and the score grows with the points, approaching 100 for the riskiest applications.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskScore {
+    code: f64,
+    permissions: f64,
+    certificate: f64,
+}
+
+impl RiskScore {
+    /// Computes the risk score of the given results
+    pub fn new(results: &Results) -> RiskScore {
+        let sets = [&results.warnings,
+                    &results.low,
+                    &results.medium,
+                    &results.high,
+                    &results.critical];
+        let mut score = RiskScore {
+            code: 0.0,
+            permissions: 0.0,
+            certificate: 0.0,
+        };
+        for (&points, set) in CRITICITY_POINTS.iter().zip(sets.iter()) {
+            for vuln in set.iter() {
+                score.add(vuln, points);
+            }
+        }
+        score
+    }
+
+    /// Adds the points of a finding to its category
+    fn add(&mut self, vuln: &Vulnerability, points: f64) {
+        if vuln.get_permission().is_some() {
+            self.permissions += points * PERMISSION_FACTOR;
+        } else if CERTIFICATE_FINDINGS.contains(&vuln.get_name()) {
+            self.certificate += points * CERTIFICATE_FACTOR;
+        } else {
+            self.code += points;
+        }
+    }
+
+    /// Gets the total risk points of the findings
+    pub fn get_points(&self) -> f64 {
+        self.code + self.permissions + self.certificate
+    }
+
+    /// Gets the risk points of the code, permission and certificate findings, by key
+    pub fn get_components(&self) -> [(&'static str, f64); 3] {
+        [("code", self.code), ("permissions", self.permissions), ("certificate", self.certificate)]
+    }
+
+    /// Gets the score, from 0 to 100
+    pub fn get_score(&self) -> u8 {
+        (100.0 * (1.0 - (-self.get_points() / SCORE_SCALE).exp())).round().min(100.0) as u8
+    }
+
+    /// Gets the key of the risk level of the score, from `none` to `critical`
+    pub fn get_level(&self) -> &'static str {
+        let score = self.get_score();
+        LEVELS.iter().find(|&&(min, _)| score >= min).map_or("none", |&(_, level)| level)
+    }
+}
+
+impl Serialize for RiskScore {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("RiskScore", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "score", self.get_score()));
+        try!(serializer.serialize_struct_elt(&mut state, "level", self.get_level()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "points",
+                                             self.get_components()
+                                                 .iter()
+                                                 .cloned()
+                                                 .collect::<BTreeMap<_, _>>()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Criticity;
+    use results::Vulnerability;
+    use super::RiskScore;
+
+    fn finding(criticity: Criticity, name: &str) -> Vulnerability {
+        Vulnerability::new(criticity, name, "Description.", None as Option<&str>, None, None, None)
+    }
+
+    #[test]
+    fn it_risk_score() {
+        let mut score = RiskScore {
+            code: 0.0,
+            permissions: 0.0,
+            certificate: 0.0,
+        };
+        assert_eq!(score.get_score(), 0);
+        assert_eq!(score.get_level(), "none");
+
+        score.add(&finding(Criticity::High, "Weak algorithm"), 5.0);
+        score.add(&finding(Criticity::Critical, "Android Debug Certificate"), 10.0);
+        assert_eq!(score.get_components(),
+                   [("code", 5.0), ("permissions", 0.0), ("certificate", 20.0)]);
+        assert_eq!(score.get_score(), 46);
+        assert_eq!(score.get_level(), "medium");
+
+        for _ in 0..20 {
+            score.add(&finding(Criticity::Critical, "SQL injection"), 10.0);
+        }
+        assert_eq!(score.get_score(), 100);
+        assert_eq!(score.get_level(), "critical");
+    }
+}
@@ -0,0 +1,198 @@
+//! Versioned layout of the results folder, with a folder for each run of the analysis of each
+//! version of an application: `{results}/{package}/{version_num}/{timestamp}`.
+//!
+//! The folder of each application has an `index.json` file listing its runs, and a `latest`
+//! symbolic link to the folder of its last run.
+
+use std::{fs, result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::{Value, to_value};
+use chrono::Local;
+
+use {Config, Result, Error, file_exists};
+use super::Results;
+
+/// Index of the runs of an application, in its results folder
+const INDEX_FILE: &'static str = "index.json";
+
+/// Symbolic link to the folder of the last run of an application
+const LATEST_LINK: &'static str = "latest";
+
+/// Format of the timestamps naming the folders of the runs
+pub const TIMESTAMP_FORMAT: &'static str = "%Y%m%d-%H%M%S";
+
+/// Run of the analysis of an application, listed in its index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    date: String,
+    version: String,
+    version_num: i32,
+    /// Folder of the run, relative to the results folder of the application
+    folder: String,
+    incomplete: bool,
+    /// Number of findings of each criticity, from warnings to critical
+    counts: [usize; 5],
+}
+
+impl Run {
+    /// Creates the run of the given results
+    pub fn new(results: &Results) -> Run {
+        Run {
+            date: Local::now().to_rfc3339(),
+            version: results.app_version.clone(),
+            version_num: results.app_version_num,
+            folder: format!("{}/{}", results.app_version_num, results.timestamp),
+            incomplete: !results.incomplete.is_empty(),
+            counts: [results.warnings.len(),
+                     results.low.len(),
+                     results.medium.len(),
+                     results.high.len(),
+                     results.critical.len()],
+        }
+    }
+}
+
+impl Serialize for Run {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Run", 10));
+        try!(serializer.serialize_struct_elt(&mut state, "date", self.date.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.version.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version_num", self.version_num));
+        try!(serializer.serialize_struct_elt(&mut state, "folder", self.folder.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "incomplete", self.incomplete));
+        for (key, count) in ["warnings", "low", "medium", "high", "critical"]
+            .iter()
+            .zip(self.counts.iter()) {
+            try!(serializer.serialize_struct_elt(&mut state, *key, *count));
+        }
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Adds the run to the index of the application of the configuration, and points the `latest`
+/// link of the application to it
+pub fn add_run(run: &Run, config: &Config) -> Result<()> {
+    let folder = Path::new(config.get_results_folder()).join(config.get_app_id());
+    try!(append_to_index(&folder, run));
+    update_latest_link(&folder, &run.folder)
+}
+
+/// Appends the run to the `index.json` file of the given results folder of an application
+fn append_to_index(folder: &Path, run: &Run) -> Result<()> {
+    let path = folder.join(INDEX_FILE);
+    let mut runs = if file_exists(&path) {
+        let f = try!(File::open(&path));
+        let index: Value = try!(serde_json::from_reader(f));
+        match index {
+            Value::Array(runs) => runs,
+            _ => return Err(Error::ParseError),
+        }
+    } else {
+        Vec::new()
+    };
+    runs.push(to_value(run));
+
+    let mut f = try!(File::create(path));
+    try!(f.write_all(format!("{:?}", Value::Array(runs)).as_bytes()));
+    Ok(())
+}
+
+/// Points the `latest` link of the given results folder of an application to the given run
+/// folder, relative to it
+#[cfg(target_family = "unix")]
+fn update_latest_link(folder: &Path, run_folder: &str) -> Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let link = folder.join(LATEST_LINK);
+    if fs::symlink_metadata(&link).is_ok() {
+        try!(fs::remove_file(&link));
+    }
+    try!(symlink(run_folder, link));
+    Ok(())
+}
+
+/// Points the `latest` link of the given results folder of an application to the given run
+/// folder, relative to it
+///
+/// Creating symbolic links may need extra privileges in Windows.
+#[cfg(target_family = "windows")]
+fn update_latest_link(folder: &Path, run_folder: &str) -> Result<()> {
+    use std::os::windows::fs::symlink_dir;
+
+    let link = folder.join(LATEST_LINK);
+    if fs::symlink_metadata(&link).is_ok() {
+        try!(fs::remove_dir(&link));
+    }
+    try!(symlink_dir(run_folder, link));
+    Ok(())
+}
+
+/// Finds the folder with the report of the given application in the given results folder: the
+/// folder of its last run with the versioned layout, or its results folder otherwise
+pub fn find_report_folder<P: AsRef<Path>>(results_folder: P, package: &str) -> PathBuf {
+    let folder = results_folder.as_ref().join(package);
+    let latest = folder.join(LATEST_LINK);
+    if latest.exists() { latest } else { folder }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    use serde_json;
+    use serde_json::value::Value;
+
+    use super::{Run, append_to_index, update_latest_link, find_report_folder};
+
+    #[test]
+    fn it_add_run() {
+        let results = Path::new("runs_test");
+        let folder = results.join("com.example.app");
+        fs::create_dir_all(folder.join("3/20170102-101010")).unwrap();
+        fs::create_dir_all(folder.join("4/20170103-101010")).unwrap();
+        assert_eq!(find_report_folder(results, "com.example.app"), folder);
+
+        for &(version_num, timestamp) in &[(3, "20170102-101010"), (4, "20170103-101010")] {
+            let run = Run {
+                date: String::from("2017-01-02T10:10:10+01:00"),
+                version: format!("1.{}", version_num),
+                version_num: version_num,
+                folder: format!("{}/{}", version_num, timestamp),
+                incomplete: false,
+                counts: [1, 0, 2, 0, 0],
+            };
+            append_to_index(&folder, &run).unwrap();
+            update_latest_link(&folder, &run.folder).unwrap();
+        }
+
+        let mut index = String::new();
+        let _ = File::open(folder.join("index.json"))
+            .unwrap()
+            .read_to_string(&mut index)
+            .unwrap();
+        let index: Value = serde_json::from_str(&index).unwrap();
+        let runs = index.as_array().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].lookup("folder").and_then(|f| f.as_str()),
+                   Some("4/20170103-101010"));
+        assert_eq!(runs[1].lookup("medium").and_then(|m| m.as_u64()), Some(2));
+
+        assert_eq!(find_report_folder(results, "com.example.app"),
+                   folder.join("latest"));
+        assert_eq!(fs::read_link(folder.join("latest")).unwrap(),
+                   Path::new("4/20170103-101010"));
+
+        fs::remove_dir_all(results).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+
+use serde::ser::Serialize;
+use serde_json::value::{Value, to_value};
+
+use super::Results;
+
+/// Section of the report contributed by an analyzer
+///
+/// A section has structured data, written to the JSON report under the section key, and an HTML
+/// block, rendered in the HTML report after the vulnerabilities. Sections are written in
+/// ascending order, and sections with the same order are sorted by their key.
+#[derive(Debug, Clone)]
+pub struct ReportSection {
+    key: String,
+    title: String,
+    order: u32,
+    data: Value,
+    html: String,
+}
+
+impl ReportSection {
+    /// Creates a new, empty, report section
+    pub fn new<S: Into<String>>(key: S, title: S, order: u32) -> ReportSection {
+        ReportSection {
+            key: key.into(),
+            title: title.into(),
+            order: order,
+            data: Value::Null,
+            html: String::new(),
+        }
+    }
+
+    /// Gets the key of the section, used as the JSON key and as the HTML anchor
+    pub fn get_key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    /// Gets the title of the section in the HTML report
+    pub fn get_title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    /// Gets the order of the section in the report
+    pub fn get_order(&self) -> u32 {
+        self.order
+    }
+
+    /// Gets the structured data of the section
+    pub fn get_data(&self) -> &Value {
+        &self.data
+    }
+
+    /// Sets the structured data of the section
+    pub fn set_data<T: Serialize>(&mut self, data: &T) {
+        self.data = to_value(data);
+    }
+
+    /// Gets the HTML block of the section
+    pub fn get_html(&self) -> &str {
+        self.html.as_str()
+    }
+
+    /// Sets the HTML block of the section
+    pub fn set_html<S: Into<String>>(&mut self, html: S) {
+        self.html = html.into();
+    }
+
+    /// Sets the HTML block of the section to a table with the given headers and rows
+    ///
+    /// Headers are escaped, but cells are written as they are, so that they can contain markup.
+    /// If there are no rows, the HTML block will be empty, and the section will not be shown in
+    /// the HTML report.
+    pub fn set_html_table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        if rows.is_empty() {
+            self.html = String::new();
+            return;
+        }
+
+        let mut html = format!("<table class=\"{}\"><tr>", self.key);
+        for header in headers {
+            html.push_str(&format!("<th>{}</th>", Results::html_escape(header)));
+        }
+        html.push_str("</tr>");
+        for row in rows {
+            html.push_str("<tr>");
+            for cell in row {
+                html.push_str(&format!("<td>{}</td>", cell));
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</table>");
+        self.html = html;
+    }
+}
+
+impl PartialEq for ReportSection {
+    fn eq(&self, other: &ReportSection) -> bool {
+        self.order == other.order && self.key == other.key
+    }
+}
+
+impl Eq for ReportSection {}
+
+impl PartialOrd for ReportSection {
+    fn partial_cmp(&self, other: &ReportSection) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ReportSection {
+    fn cmp(&self, other: &ReportSection) -> Ordering {
+        match self.order.cmp(&other.order) {
+            Ordering::Equal => self.key.cmp(&other.key),
+            o => o,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::value::Value;
+    use super::ReportSection;
+
+    #[test]
+    fn it_report_section() {
+        let mut section = ReportSection::new("trackers", "Trackers", 10);
+        assert_eq!(section.get_data(), &Value::Null);
+        assert!(section.get_html().is_empty());
+
+        section.set_data(&vec!["analytics"]);
+        assert_eq!(section.get_data(),
+                   &Value::Array(vec![Value::String(String::from("analytics"))]));
+
+        section.set_html_table(&["Name", "<Type>"], &[]);
+        assert!(section.get_html().is_empty());
+
+        section.set_html_table(&["Name", "<Type>"],
+                               &[vec![String::from("analytics"), String::from("<b>SDK</b>")]]);
+        assert_eq!(section.get_html(),
+                   "<table class=\"trackers\"><tr><th>Name</th><th>&lt;Type&gt;</th></tr>\
+                    <tr><td>analytics</td><td><b>SDK</b></td></tr></table>");
+    }
+
+    #[test]
+    fn it_report_section_order() {
+        let mut sections = vec![ReportSection::new("b", "B", 20),
+                                ReportSection::new("c", "C", 10),
+                                ReportSection::new("a", "A", 20)];
+        sections.sort();
+
+        let keys: Vec<_> = sections.iter().map(|s| s.get_key()).collect();
+        assert_eq!(keys, vec!["c", "a", "b"]);
+    }
+}
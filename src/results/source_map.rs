@@ -0,0 +1,168 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use Result;
+use static_analysis::code::is_smali_folder;
+use super::Vulnerability;
+
+/// Extensions of the source files the decompiled classes are mapped to
+const SOURCE_EXTENSIONS: [&'static str; 2] = ["java", "kt"];
+
+/// Gets the path of the class of a decompiled Java or smali file, without the extension and the
+/// inner class, such as `com/example/Main` for `classes/com/example/Main$1.java`
+fn get_class_path(file: &Path) -> Option<String> {
+    let mut components = file.components();
+    let folder = match components.next() {
+        Some(c) => c.as_os_str().to_string_lossy().into_owned(),
+        None => return None,
+    };
+    if folder != "classes" && !is_smali_folder(&folder) {
+        return None;
+    }
+    let class = components.as_path().with_extension("").to_string_lossy().replace('\\', "/");
+    match class.find('$') {
+        Some(i) => Some(String::from(&class[..i])),
+        None if class.is_empty() => None,
+        None => Some(class),
+    }
+}
+
+/// Finds the line of the source file with the first line of code of the vulnerability, ignoring
+/// whitespace, since the decompiled code has different line numbers than the original one
+fn find_line(source: &str, vuln: &Vulnerability) -> Option<usize> {
+    let normalize = |line: &str| line.split_whitespace().collect::<Vec<_>>().join(" ");
+    let first_line = match vuln.get_code_lines()
+        .and_then(|lines| lines.into_iter().map(&normalize).find(|l| !l.is_empty())) {
+        Some(l) => l,
+        None => return None,
+    };
+    source.lines().position(|l| normalize(l) == first_line)
+}
+
+/// Source code of the application, to map the findings in the decompiled files to it
+pub struct SourceMap {
+    root: PathBuf,
+    /// Paths of the Java and Kotlin files, relative to the root and without extension, with
+    /// their extension
+    files: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    /// Loads the source files in the given root folder
+    pub fn load<P: AsRef<Path>>(root: P) -> Result<SourceMap> {
+        let mut map = SourceMap {
+            root: root.as_ref().to_path_buf(),
+            files: Vec::new(),
+        };
+        let root = map.root.clone();
+        try!(map.add_files(&root));
+        Ok(map)
+    }
+
+    /// Adds the source files of the folder and its subfolders
+    fn add_files(&mut self, folder: &Path) -> Result<()> {
+        for entry in try!(fs::read_dir(folder)) {
+            let path = try!(entry).path();
+            if path.is_dir() {
+                try!(self.add_files(&path));
+                continue;
+            }
+            let extension = match path.extension().and_then(|e| e.to_str()) {
+                Some(e) if SOURCE_EXTENSIONS.contains(&e) => String::from(e),
+                _ => continue,
+            };
+            if let Ok(relative) = path.with_extension("").strip_prefix(&self.root) {
+                self.files.push((relative.to_string_lossy().replace('\\', "/"), extension));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the source file of a file of the analysis, relative to the root folder
+    ///
+    /// Decompiled classes are mapped to the source file of the same class, in any source set,
+    /// and the rest of the files, such as the manifest, to the same path in the root folder.
+    fn find(&self, file: &Path) -> Option<PathBuf> {
+        match get_class_path(file) {
+            Some(class) => {
+                let suffix = format!("/{}", class);
+                self.files
+                    .iter()
+                    .find(|&&(ref path, _)| *path == class || path.ends_with(&suffix))
+                    .map(|&(ref path, ref extension)| {
+                        PathBuf::from(format!("{}.{}", path, extension))
+                    })
+            }
+            None if self.root.join(file).is_file() => Some(file.to_path_buf()),
+            None => None,
+        }
+    }
+
+    /// Gets the source file of the vulnerability, including the root folder, and the line of
+    /// its code in it, if it's found
+    pub fn map(&self, vuln: &Vulnerability) -> Option<(PathBuf, Option<usize>)> {
+        let file = match vuln.get_file().and_then(|f| self.find(f)) {
+            Some(f) => self.root.join(f),
+            None => return None,
+        };
+        let mut source = String::new();
+        let read = File::open(&file).and_then(|mut f| f.read_to_string(&mut source));
+        let line = match read {
+            Ok(_) => find_line(&source, vuln),
+            Err(_) => None,
+        };
+        Some((file, line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use Criticity;
+    use results::Vulnerability;
+    use super::{SourceMap, get_class_path, find_line};
+
+    #[test]
+    fn it_get_class_path() {
+        assert_eq!(get_class_path(Path::new("classes/com/example/Main$1.java")),
+                   Some(String::from("com/example/Main")));
+        assert_eq!(get_class_path(Path::new("smali_classes2/com/example/Util.smali")),
+                   Some(String::from("com/example/Util")));
+        assert_eq!(get_class_path(Path::new("AndroidManifest.xml")), None);
+    }
+
+    #[test]
+    fn it_source_map() {
+        let map = SourceMap {
+            root: PathBuf::from("app/src"),
+            files: vec![(String::from("main/java/com/example/Main"), String::from("kt")),
+                        (String::from("debug/java/com/example/debug/Main"),
+                         String::from("java"))],
+        };
+        assert_eq!(map.find(Path::new("classes/com/example/Main$Companion.java")),
+                   Some(PathBuf::from("main/java/com/example/Main.kt")));
+        assert_eq!(map.find(Path::new("classes/org/example/Main.java")), None);
+    }
+
+    #[test]
+    fn it_find_line() {
+        let vuln = Vulnerability::new(Criticity::High,
+                                      "Weak hash",
+                                      "MD5 is used.",
+                                      Some("classes/com/example/Main.java"),
+                                      Some(2),
+                                      Some(2),
+                                      Some(String::from("class Main {\n  void a() {\n    \
+                                                         md5(data);\n  }\n}\n")));
+        let source = "package com.example\n\nclass Main {\n    fun a() {\n        \
+                      md5(data)\n    }\n}\n";
+        assert_eq!(find_line(source, &vuln), None);
+
+        let source = "package com.example;\n\nclass Main {\n    void a() {\n        \
+                      md5(data);\n    }\n}\n";
+        assert_eq!(find_line(source, &vuln), Some(4));
+    }
+}
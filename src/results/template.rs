@@ -0,0 +1,577 @@
+use std::{fs, result};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::collections::{BTreeSet, BTreeMap};
+
+use handlebars::{Handlebars, Helper, RenderContext, RenderError};
+use serde_json::builder::{ObjectBuilder, ArrayBuilder};
+use serde_json::value::Value;
+use chrono::{Local, Datelike};
+use rustc_serialize::hex::ToHex;
+
+use {Config, Result, Criticity};
+use static_analysis::java::get_class_name;
+use super::{Results, Vulnerability, SCHEMA_VERSION};
+use super::i18n::Locale;
+use super::risk::RiskScore;
+use super::masvs::get_masvs_matrix;
+use super::provenance::{get_seconds, get_tool_name};
+
+/// Extension of the templates in the templates folder
+const TEMPLATE_EXTENSION: &'static str = "hbs";
+
+/// Built-in templates of the HTML reports
+///
+/// `report` and `print` are the full reports, built on the `layout` template, `source` is the
+/// page of each file of the source code viewer, and the rest are the partials they include. Any
+/// of them can be overridden with a file with the same name and the `.hbs` extension in the
+/// templates folder.
+const TEMPLATES: [(&'static str, &'static str); 17] =
+    [("layout", include_str!("templates/layout.hbs")),
+     ("report", include_str!("templates/report.hbs")),
+     ("print", include_str!("templates/print.hbs")),
+     ("header", include_str!("templates/header.hbs")),
+     ("incomplete", include_str!("templates/incomplete.hbs")),
+     ("executive_summary", include_str!("templates/executive_summary.hbs")),
+     ("app_data", include_str!("templates/app_data.hbs")),
+     ("summary", include_str!("templates/summary.hbs")),
+     ("filters", include_str!("templates/filters.hbs")),
+     ("findings", include_str!("templates/findings.hbs")),
+     ("finding", include_str!("templates/finding.hbs")),
+     ("masvs", include_str!("templates/masvs.hbs")),
+     ("metadata", include_str!("templates/metadata.hbs")),
+     ("related_links", include_str!("templates/related_links.hbs")),
+     ("footer", include_str!("templates/footer.hbs")),
+     ("scripts", include_str!("templates/scripts.hbs")),
+     ("source", include_str!("templates/source.hbs"))];
+
+/// Helper joining an array of strings, with the given separator or a comma
+///
+/// For example, `{{join masvs ", "}}`.
+fn join_helper(h: &Helper,
+               _: &Handlebars,
+               rc: &mut RenderContext)
+               -> result::Result<(), RenderError> {
+    let list = match h.param(0).and_then(|p| p.value().as_array()) {
+        Some(list) => list,
+        None => return Err(RenderError::new("the first parameter of `join` must be an array")),
+    };
+    let separator = h.param(1).and_then(|p| p.value().as_str()).unwrap_or(", ");
+    let joined = list.iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>()
+        .join(separator);
+    try!(rc.writer.write_all(Results::html_escape(&joined).as_bytes()));
+    Ok(())
+}
+
+/// Loads the templates of the reports
+///
+/// The built-in templates are registered first, and then every `.hbs` file of the templates
+/// folder, if configured. This way, a template can override just the header or the finding card
+/// of the report, or add new partials to be included from other overridden templates. The `t`
+/// helper translates the strings of the templates to the language of the given locale.
+fn get_templates(config: &Config, locale: &Locale) -> Result<Handlebars> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_helper("join", Box::new(join_helper));
+    handlebars.register_helper("t", Box::new(locale.clone()));
+    for &(name, template) in &TEMPLATES {
+        try!(handlebars.register_template_string(name, String::from(template)));
+    }
+
+    if let Some(folder) = config.get_templates_folder() {
+        for entry in try!(fs::read_dir(folder)) {
+            let path = try!(entry).path();
+            if path.extension().map_or(true, |e| e != TEMPLATE_EXTENSION) {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let mut template = String::new();
+            let _ = try!(try!(File::open(&path)).read_to_string(&mut template));
+            try!(handlebars.register_template_string(&name, template));
+            if config.is_verbose() {
+                println!("Loaded the `{}` report template from {}.", name, path.display());
+            }
+        }
+    }
+    Ok(handlebars)
+}
+
+/// Gets the numbers of the lines of the code snippet of a finding, marking the affected ones
+fn push_line_numbers(builder: ArrayBuilder,
+                     code: &str,
+                     start_line: usize,
+                     end_line: usize)
+                     -> ArrayBuilder {
+    let first_line = if start_line < 5 { 0 } else { start_line - 4 };
+    code.lines().enumerate().fold(builder, |b, (i, _)| {
+        let line = first_line + i;
+        b.push_object(|o| {
+            o.insert("number", line + 1)
+                .insert("affected", line >= start_line && line <= end_line)
+        })
+    })
+}
+
+/// Gets the class of the highlighter for the code of the given file, from its extension
+fn get_code_language(file: &Path) -> String {
+    match file.extension().map(|e| e.to_string_lossy()) {
+        // There is no smali support in the bundled highlighter
+        Some(ref e) if e == "smali" => String::from("nohighlight"),
+        Some(e) => e.into_owned(),
+        None => String::new(),
+    }
+}
+
+/// Gets the data of a finding, rendered with the `finding` template
+fn get_finding_data(results: &Results,
+                    vuln: &Vulnerability,
+                    id: &str,
+                    references: &BTreeMap<String, Vec<String>>,
+                    with_source: bool)
+                    -> Value {
+    let metadata = vuln.get_metadata();
+    let mut builder = ObjectBuilder::new()
+        .insert("id", id)
+        .insert("key", vuln.get_fingerprint())
+        .insert("criticity", get_criticity_key(vuln.get_criticity()))
+        .insert("name", vuln.get_name())
+        .insert("description", vuln.get_description())
+        .insert_array("cwe", |b| {
+            metadata.get_cwe().iter().fold(b, |b, cwe| {
+                b.push_object(|o| {
+                    o.insert("name", cwe.as_str())
+                        .insert("number", cwe.trim_left_matches("CWE-"))
+                })
+            })
+        })
+        .insert("masvs", metadata.get_masvs())
+        .insert("owasp_mobile", metadata.get_owasp_mobile())
+        .insert("references", metadata.get_references());
+    if let Some(remediation) = metadata.get_remediation() {
+        builder = builder.insert("remediation", remediation);
+    }
+    if let Some(cvss) = metadata.get_cvss() {
+        builder = builder.insert("cvss", cvss);
+    }
+    if let Some(related) = references.get(id) {
+        builder = builder.insert("related", related)
+            .insert("permission", vuln.get_permission().is_some())
+            .insert_array("required_permissions", |b| {
+                vuln.get_required_permissions()
+                    .iter()
+                    .fold(b, |b, p| b.push(p.as_str()))
+            });
+    }
+    if let Some(file) = vuln.get_file() {
+        builder = builder.insert("file", format!("{}", file.display()))
+            .insert("source_link", with_source);
+        let original_class = match (results.mapping.as_ref(), get_class_name(file)) {
+            (Some(mapping), Some(class)) => mapping.get_original_class(&class),
+            _ => None,
+        };
+        if let Some(original_class) = original_class {
+            builder = builder.insert("original_class", original_class);
+        }
+    }
+    if let Some(source_file) = vuln.get_source_file() {
+        builder = builder.insert("source_file", source_file)
+            .insert("source_line", vuln.get_source_line().map(|l| l + 1));
+    }
+    if let (Some(code), Some(start_line), Some(end_line)) =
+           (vuln.get_code(), vuln.get_start_line(), vuln.get_end_line()) {
+        let lang = vuln.get_file().map_or_else(String::new, get_code_language);
+        builder = builder.insert("code", code)
+            .insert("start_line", start_line + 1)
+            .insert("end_line", end_line + 1)
+            .insert("multiline", start_line != end_line)
+            .insert("lang", lang)
+            .insert_array("line_numbers",
+                          |b| push_line_numbers(b, code, start_line, end_line));
+    }
+    builder.build()
+}
+
+/// Gets the data of the findings of the given criticity, rendered in the `findings` template
+fn get_set_data(results: &Results,
+                set: &BTreeSet<Vulnerability>,
+                criticity: Criticity,
+                references: &BTreeMap<String, Vec<String>>,
+                with_source: bool,
+                locale: &Locale)
+                -> Value {
+    let key = get_criticity_key(criticity);
+    let title = if criticity == Criticity::Warning {
+        String::from(locale.get(key))
+    } else {
+        String::from(locale.get(&format!("{}_vulnerabilities", key)))
+    };
+    ObjectBuilder::new()
+        .insert("key", key)
+        .insert("title", title)
+        .insert_array("findings", |b| {
+            results.get_listed_findings(set).into_iter().fold(b, |b, (i, vuln)| {
+                let id = Results::get_vulnerability_id(criticity, i);
+                b.push(get_finding_data(results, vuln, &id, references, with_source))
+            })
+        })
+        .build()
+}
+
+/// Gets the key of the given criticity, used as the anchor of its findings and to translate it
+fn get_criticity_key(criticity: Criticity) -> &'static str {
+    match criticity {
+        Criticity::Warning => "warnings",
+        Criticity::Low => "low",
+        Criticity::Medium => "medium",
+        Criticity::High => "high",
+        Criticity::Critical => "critical",
+    }
+}
+
+/// Gets the given value as a rounded percentage of the total, for the bars of the charts
+fn get_percent(value: f64, total: f64) -> u64 {
+    if total > 0.0 {
+        (value * 100.0 / total).round() as u64
+    } else {
+        0
+    }
+}
+
+/// Gets the data the report templates are rendered with
+///
+/// The printable report doesn't link to the source code or to the trend page.
+fn get_report_data(results: &Results, config: &Config, locale: &Locale, printable: bool) -> Value {
+    let now = Local::now();
+    let with_source = !printable && config.is_include_source();
+    let sets = [(Criticity::Critical, &results.critical),
+                (Criticity::High, &results.high),
+                (Criticity::Medium, &results.medium),
+                (Criticity::Low, &results.low),
+                (Criticity::Warning, &results.warnings)];
+    let references = results.get_cross_references();
+    let risk = RiskScore::new(results);
+    let max_count = sets.iter().map(|&(_, set)| set.len()).max().unwrap_or(0);
+
+    ObjectBuilder::new()
+        .insert("schema_version", SCHEMA_VERSION)
+        .insert("language", config.get_language())
+        .insert("date", now.to_rfc2822())
+        .insert("copyright",
+                if now.year() > 2016 {
+                    format!("2016 - {}", now.year())
+                } else {
+                    format!("{}", now.year())
+                })
+        .insert("include_source", with_source)
+        .insert("suppressed", results.suppressed)
+        .insert("ignored", results.ignore.as_ref().map_or(0, |i| i.get_ignored()))
+        .insert("duplicates", results.duplicates)
+        .insert("unlisted",
+                results.groups.iter().map(|g| g.get_unlisted()).sum::<usize>())
+        .insert("incomplete", &results.incomplete)
+        .insert("trend", !printable && config.is_trend())
+        .insert_object("app", |b| {
+            let mut b = b.insert("label", results.app_label.as_str())
+                .insert("description", results.app_description.as_str())
+                .insert("package", results.app_package.as_str())
+                .insert("version", results.app_version.as_str())
+                .insert("version_num", results.app_version_num)
+                .insert("min_sdk", results.app_min_sdk)
+                .insert_object("fingerprint", |f| {
+                    f.insert("md5", results.app_fingerprint.get_md5().to_hex())
+                        .insert("sha1", results.app_fingerprint.get_sha1().to_hex())
+                        .insert("sha256", results.app_fingerprint.get_sha256().to_hex())
+                });
+            if let Some(target_sdk) = results.app_target_sdk {
+                b = b.insert("target_sdk", target_sdk);
+            }
+            if let Some(ref info) = results.apk_info {
+                b = b.insert("icon", info.get_icon_file())
+                    .insert("max_sdk", info.get_max_sdk())
+                    .insert("abis", join(info.get_abis()))
+                    .insert("locales", join(info.get_locales()))
+                    .insert("densities", join(info.get_densities()))
+                    .insert_array("features", |b| {
+                        info.get_features().iter().fold(b, |b, feature| {
+                            b.push_object(|o| {
+                                o.insert("name", feature.get_name())
+                                    .insert("required", feature.is_required())
+                            })
+                        })
+                    })
+                    .insert_array("components", |b| {
+                        info.get_components()
+                            .iter()
+                            .filter(|&(_, count)| *count > 0)
+                            .fold(b, |b, (key, count)| {
+                                b.push_object(|o| {
+                                    o.insert("title", locale.get(key)).insert("count", *count)
+                                })
+                            })
+                    });
+            }
+            b
+        })
+        .insert("total",
+                results.low.len() + results.medium.len() + results.high.len() +
+                results.critical.len())
+        .insert_array("counts", |b| {
+            sets.iter().fold(b, |b, &(criticity, set)| {
+                let key = get_criticity_key(criticity);
+                b.push_object(|o| {
+                    o.insert("key", key)
+                        .insert("title", locale.get(key))
+                        .insert("count", set.len())
+                        .insert("percent", get_percent(set.len() as f64, max_count as f64))
+                })
+            })
+        })
+        .insert_object("risk", |b| {
+            b.insert("score", risk.get_score())
+                .insert("level", risk.get_level())
+                .insert("level_title", locale.get(&format!("risk_{}", risk.get_level())))
+                .insert_array("components", |b| {
+                    risk.get_components().iter().fold(b, |b, &(key, points)| {
+                        b.push_object(|o| {
+                            o.insert("key", key)
+                                .insert("title", locale.get(&format!("{}_findings", key)))
+                                .insert("points", points)
+                                .insert("percent", get_percent(points, risk.get_points()))
+                        })
+                    })
+                })
+        })
+        .insert_array("masvs", |b| {
+            get_masvs_matrix(results).iter().fold(b, |b, control| {
+                b.push_object(|o| {
+                    o.insert("control", control.get_control())
+                        .insert("description", control.get_description())
+                        .insert("url", control.get_url())
+                        .insert("status", control.get_status().as_str())
+                        .insert("status_title",
+                                locale.get(&format!("masvs_{}", control.get_status().as_str())))
+                        .insert("findings", control.get_findings())
+                })
+            })
+        })
+        .insert_object("metadata", |b| {
+            let provenance = &results.provenance;
+            b.insert("super_version", provenance.get_super_version())
+                .insert("date", provenance.get_date())
+                .insert("profile", provenance.get_profile())
+                .insert("apk_sha256", provenance.get_apk_sha256())
+                .insert("rules_sha256", provenance.get_rules_sha256())
+                .insert_array("rule_packs", |b| {
+                    provenance.get_rule_packs().iter().fold(b, |b, pack| {
+                        b.push_object(|o| {
+                            o.insert("name", pack.get_name())
+                                .insert("version", pack.get_version())
+                                .insert("sha256", pack.get_sha256())
+                        })
+                    })
+                })
+                .insert_array("tools", |b| {
+                    provenance.get_tools().iter().fold(b, |b, (tool, version)| {
+                        b.push_object(|o| {
+                            o.insert("name", get_tool_name(tool))
+                                .insert("version", version.as_str())
+                        })
+                    })
+                })
+                .insert_array("durations", |b| {
+                    provenance.get_durations().iter().fold(b, |b, stage| {
+                        b.push_object(|o| {
+                            o.insert("stage", stage.get_label())
+                                .insert("seconds",
+                                        format!("{:.2}", get_seconds(stage.get_duration())))
+                        })
+                    })
+                })
+        })
+        .insert("rules",
+                sets.iter()
+                    .flat_map(|&(_, set)| set.iter().map(|v| v.get_name()))
+                    .collect::<BTreeSet<_>>())
+        .insert_array("sets", |b| {
+            sets.iter()
+                .filter(|&&(_, set)| !set.is_empty())
+                .fold(b, |b, &(criticity, set)| {
+                    b.push(get_set_data(results, set, criticity, &references, with_source, locale))
+                })
+        })
+        .insert_array("sections", |b| {
+            results.sections
+                .iter()
+                .filter(|s| !s.get_html().is_empty())
+                .fold(b, |b, section| {
+                    b.push_object(|o| {
+                        o.insert("key", section.get_key())
+                            .insert("title", section.get_title())
+                            .insert("html", section.get_html())
+                    })
+                })
+        })
+        .build()
+}
+
+/// Joins the values of a set with commas, to show them in a single line
+fn join(values: &BTreeSet<String>) -> String {
+    values.iter().map(|v| v.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Renders the HTML report, with the `report` template
+pub fn render_html_report(results: &Results, config: &Config) -> Result<String> {
+    let locale = Locale::new(config.get_language());
+    let templates = try!(get_templates(config, &locale));
+    Ok(try!(templates.render("report", &get_report_data(results, config, &locale, false))))
+}
+
+/// Renders the printable report used for the PDF export, with the `print` template
+pub fn render_printable_report(results: &Results, config: &Config) -> Result<String> {
+    let locale = Locale::new(config.get_language());
+    let templates = try!(get_templates(config, &locale));
+    Ok(try!(templates.render("print", &get_report_data(results, config, &locale, true))))
+}
+
+/// Renderer of the pages of the source code viewer of the HTML report
+///
+/// Each page shows the highlighted code of a file with anchored line numbers, so that findings
+/// can link to their lines, and the lines of the findings in the file link back to them.
+pub struct SourceRenderer<'r> {
+    templates: Handlebars,
+    language: String,
+    findings: BTreeMap<&'r Path, Vec<(String, &'r Vulnerability)>>,
+}
+
+impl<'r> SourceRenderer<'r> {
+    /// Creates the renderer, loading the templates and indexing the findings by file
+    pub fn new(results: &'r Results, config: &Config) -> Result<SourceRenderer<'r>> {
+        let locale = Locale::new(config.get_language());
+        let templates = try!(get_templates(config, &locale));
+        let sets = [(Criticity::Critical, &results.critical),
+                    (Criticity::High, &results.high),
+                    (Criticity::Medium, &results.medium),
+                    (Criticity::Low, &results.low),
+                    (Criticity::Warning, &results.warnings)];
+        let mut findings = BTreeMap::new();
+        for &(criticity, set) in &sets {
+            for (i, vuln) in results.get_listed_findings(set) {
+                if let Some(file) = vuln.get_file() {
+                    findings.entry(file)
+                        .or_insert_with(Vec::new)
+                        .push((Results::get_vulnerability_id(criticity, i), vuln));
+                }
+            }
+        }
+        Ok(SourceRenderer {
+            templates: templates,
+            language: String::from(config.get_language()),
+            findings: findings,
+        })
+    }
+
+    /// Gets the files with findings, which need their page even if they are not part of the
+    /// rendered source tree
+    pub fn get_finding_files(&self) -> Vec<&'r Path> {
+        self.findings.keys().cloned().collect()
+    }
+
+    /// Renders the page of the given file, with the `source` template
+    pub fn render(&self, path: &Path, code: &str) -> Result<String> {
+        let findings = self.findings.get(path).map_or(&[][..], |f| f.as_slice());
+        let back_path = path.components().map(|_| "../").collect::<String>();
+        let data = ObjectBuilder::new()
+            .insert("language", self.language.as_str())
+            .insert("path", format!("{}", path.display()))
+            .insert("back_path", back_path)
+            .insert("lang", get_code_language(path))
+            .insert("code", code)
+            .insert_array("lines", |b| push_source_lines(b, code, findings))
+            .build();
+        Ok(try!(self.templates.render("source", &data)))
+    }
+}
+
+/// Gets the line numbers of a source code page, linking the lines of each finding to it
+///
+/// When several findings affect a line, it links to the first one, and the names of all of them
+/// are shown in its title.
+fn push_source_lines(builder: ArrayBuilder,
+                     code: &str,
+                     findings: &[(String, &Vulnerability)])
+                     -> ArrayBuilder {
+    code.lines().enumerate().fold(builder, |b, (line, _)| {
+        let affecting = findings.iter()
+            .filter(|&&(_, vuln)| match (vuln.get_start_line(), vuln.get_end_line()) {
+                (Some(start), Some(end)) => line >= start && line <= end,
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        b.push_object(|o| {
+            let o = o.insert("number", line + 1);
+            match affecting.first() {
+                Some(&&(ref id, _)) => {
+                    o.insert("finding", id.as_str())
+                        .insert("title",
+                                affecting.iter()
+                                    .map(|&&(ref id, vuln)| format!("{}: {}", id, vuln.get_name()))
+                                    .collect::<Vec<_>>()
+                                    .join("\n"))
+                }
+                None => o,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::builder::ArrayBuilder;
+    use Criticity;
+    use results::Vulnerability;
+    use super::{push_line_numbers, push_source_lines};
+
+    #[test]
+    fn it_push_line_numbers() {
+        let lines = push_line_numbers(ArrayBuilder::new(), "a\nb\nc\nd\ne\nf\n", 5, 6).build();
+        let lines = lines.as_array().unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0].as_object().and_then(|l| l.get("number")).and_then(|n| n.as_u64()),
+                   Some(2));
+        let affected = lines.iter()
+            .filter(|l| {
+                l.as_object()
+                    .and_then(|l| l.get("affected"))
+                    .and_then(|a| a.as_bool())
+                    .unwrap_or(false)
+            })
+            .count();
+        assert_eq!(affected, 2);
+    }
+
+    #[test]
+    fn it_push_source_lines() {
+        let vuln = Vulnerability::new(Criticity::High,
+                                      "Weak algorithm",
+                                      "MD5 is used.",
+                                      Some("classes/com/example/A.java"),
+                                      Some(1),
+                                      Some(2),
+                                      Some(String::from("b\nc")));
+        let findings = vec![(String::from("H001"), &vuln)];
+        let lines = push_source_lines(ArrayBuilder::new(), "a\nb\nc\nd\n", &findings).build();
+        let lines = lines.as_array().unwrap();
+        assert_eq!(lines.len(), 4);
+        let linked = lines.iter()
+            .filter_map(|l| l.as_object().and_then(|l| l.get("finding")).and_then(|f| f.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(linked, vec!["H001", "H001"]);
+        assert_eq!(lines[1].as_object().and_then(|l| l.get("title")).and_then(|t| t.as_str()),
+                   Some("H001: Weak algorithm"));
+    }
+}
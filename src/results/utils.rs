@@ -1,6 +1,7 @@
 use std::{fmt, result};
 use std::fs::File;
 use std::io::Read;
+use std::cmp;
 use std::cmp::Ordering;
 use std::path::Path;
 use std::time::Duration;
@@ -13,6 +14,139 @@ use crypto::sha2::Sha256;
 use rustc_serialize::hex::ToHex;
 
 use {Config, Result, Criticity};
+use static_analysis::manifest::Permission;
+use static_analysis::obfuscation::Mapping;
+
+/// CVSS v3.1 vector of the rule that found a vulnerability, with its score in the configured
+/// environment
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CvssScore {
+    vector: String,
+    /// Score in tenths, so that scores can be compared for equality
+    score: u8,
+}
+
+impl CvssScore {
+    /// Creates a new CVSS score, from 0.0 to 10.0
+    pub fn new(vector: &str, score: f64) -> CvssScore {
+        CvssScore {
+            vector: String::from(vector),
+            score: (score * 10f64).round() as u8,
+        }
+    }
+
+    pub fn get_vector(&self) -> &str {
+        self.vector.as_str()
+    }
+
+    pub fn get_score(&self) -> f64 {
+        self.score as f64 / 10f64
+    }
+}
+
+impl fmt::Display for CvssScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "{:.1} ({})", self.get_score(), self.vector)
+    }
+}
+
+impl Serialize for CvssScore {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("CvssScore", 2));
+        try!(serializer.serialize_struct_elt(&mut state, "vector", self.vector.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "score", self.get_score()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Classification and remediation guidance of the rule that found a vulnerability
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RuleMetadata {
+    cwe: Vec<String>,
+    masvs: Vec<String>,
+    owasp_mobile: Vec<String>,
+    references: Vec<String>,
+    remediation: Option<String>,
+    cvss: Option<CvssScore>,
+}
+
+impl RuleMetadata {
+    /// Creates new rule metadata
+    pub fn new(cwe: Vec<String>,
+               masvs: Vec<String>,
+               owasp_mobile: Vec<String>,
+               references: Vec<String>,
+               remediation: Option<String>)
+               -> RuleMetadata {
+        RuleMetadata {
+            cwe: cwe,
+            masvs: masvs,
+            owasp_mobile: owasp_mobile,
+            references: references,
+            remediation: remediation,
+            cvss: None,
+        }
+    }
+
+    /// Gets the CWE identifiers of the weakness, such as `CWE-89`
+    pub fn get_cwe(&self) -> &[String] {
+        self.cwe.as_slice()
+    }
+
+    /// Gets the OWASP MASVS controls the weakness is related to, such as `MASVS-CODE-4`
+    pub fn get_masvs(&self) -> &[String] {
+        self.masvs.as_slice()
+    }
+
+    /// Gets the OWASP Mobile Top 10 categories of the weakness, such as `M4`
+    pub fn get_owasp_mobile(&self) -> &[String] {
+        self.owasp_mobile.as_slice()
+    }
+
+    /// Gets the URLs with more information about the weakness
+    pub fn get_references(&self) -> &[String] {
+        self.references.as_slice()
+    }
+
+    /// Gets the guidance to fix the weakness
+    pub fn get_remediation(&self) -> Option<&str> {
+        self.remediation.as_ref().map(|r| r.as_str())
+    }
+
+    /// Gets the CVSS vector of the rule and its score
+    pub fn get_cvss(&self) -> Option<&CvssScore> {
+        self.cvss.as_ref()
+    }
+
+    pub fn set_cvss(&mut self, cvss: CvssScore) {
+        self.cvss = Some(cvss);
+    }
+
+    /// Returns if there is no metadata at all
+    pub fn is_empty(&self) -> bool {
+        self.cwe.is_empty() && self.masvs.is_empty() && self.owasp_mobile.is_empty() &&
+        self.references.is_empty() && self.remediation.is_none() && self.cvss.is_none()
+    }
+}
+
+impl Serialize for RuleMetadata {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("RuleMetadata", 6));
+        try!(serializer.serialize_struct_elt(&mut state, "cwe", &self.cwe));
+        try!(serializer.serialize_struct_elt(&mut state, "masvs", &self.masvs));
+        try!(serializer.serialize_struct_elt(&mut state, "owasp_mobile", &self.owasp_mobile));
+        try!(serializer.serialize_struct_elt(&mut state, "references", &self.references));
+        try!(serializer.serialize_struct_elt(&mut state, "remediation", &self.remediation));
+        try!(serializer.serialize_struct_elt(&mut state, "cvss", &self.cvss));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
 
 /// Structure to store information about a vulnerability
 #[derive(Debug, Clone, PartialEq, Eq, Ord)]
@@ -24,6 +158,12 @@ pub struct Vulnerability {
     start_line: Option<usize>,
     end_line: Option<usize>,
     code: Option<String>,
+    permission: Option<Permission>,
+    required_permissions: Vec<Permission>,
+    metadata: RuleMetadata,
+    rule: Option<String>,
+    source_file: Option<String>,
+    source_line: Option<usize>,
 }
 
 impl Vulnerability {
@@ -50,6 +190,12 @@ impl Vulnerability {
                 Some(s) => Some(String::from(s.as_ref() as &str)),
                 None => None,
             },
+            permission: None,
+            required_permissions: Vec::new(),
+            metadata: Default::default(),
+            rule: None,
+            source_file: None,
+            source_line: None,
         }
     }
 
@@ -93,19 +239,160 @@ impl Vulnerability {
     pub fn get_end_line(&self) -> Option<usize> {
         self.end_line
     }
+
+    /// Gets the permission this vulnerability is about, if it is a permission finding
+    pub fn get_permission(&self) -> Option<Permission> {
+        self.permission
+    }
+
+    /// Sets the permission this vulnerability is about
+    pub fn set_permission(&mut self, permission: Permission) {
+        self.permission = Some(permission);
+    }
+
+    /// Gets the permissions the vulnerable code relies on
+    pub fn get_required_permissions(&self) -> &[Permission] {
+        self.required_permissions.as_slice()
+    }
+
+    /// Sets the permissions the vulnerable code relies on
+    pub fn set_required_permissions(&mut self, permissions: Vec<Permission>) {
+        self.required_permissions = permissions;
+    }
+
+    /// Gets the classification and remediation guidance of the rule that found the
+    /// vulnerability
+    pub fn get_metadata(&self) -> &RuleMetadata {
+        &self.metadata
+    }
+
+    /// Sets the classification and remediation guidance of the vulnerability
+    pub fn set_metadata(&mut self, metadata: RuleMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// Gets the ID of the code rule that found the vulnerability, if it was found by one
+    pub fn get_rule(&self) -> Option<&str> {
+        match self.rule {
+            Some(ref r) => Some(r.as_str()),
+            None => None,
+        }
+    }
+
+    pub fn set_rule(&mut self, rule: &str) {
+        self.rule = Some(String::from(rule));
+    }
+
+    /// Gets the file of the source code of the application the vulnerability was mapped to, if
+    /// any
+    pub fn get_source_file(&self) -> Option<&str> {
+        match self.source_file {
+            Some(ref f) => Some(f.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the line of the code of the vulnerability in its source file, if it was found
+    pub fn get_source_line(&self) -> Option<usize> {
+        self.source_line
+    }
+
+    /// Sets the location of the vulnerability in the source code of the application
+    pub fn set_source(&mut self, file: &str, line: Option<usize>) {
+        self.source_file = Some(String::from(file));
+        self.source_line = line;
+    }
+
+    /// Gets the fingerprint of the vulnerability, that identifies it between analyses of
+    /// different versions of the application
+    ///
+    /// It's computed from the rule of the vulnerability, or its name if it was not found by a
+    /// rule, its path with `/` separators and the code of its lines, ignoring whitespace, so it
+    /// doesn't change when code is added or removed around it. Vulnerabilities without code use
+    /// their description instead.
+    pub fn get_fingerprint(&self) -> String {
+        let mut sha256 = Sha256::new();
+        sha256.input_str(self.get_rule().unwrap_or(&self.name));
+        sha256.input_str("\u{0}");
+        sha256.input_str(&self.file.as_ref().map_or(String::new(), |f| f.replace('\\', "/")));
+        sha256.input_str("\u{0}");
+        match self.get_code_lines() {
+            Some(lines) => {
+                let lines = lines.iter()
+                    .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .collect::<Vec<_>>();
+                sha256.input_str(&lines.join("\n"));
+            }
+            None => sha256.input_str(&self.description),
+        }
+        sha256.result_str()
+    }
+
+    /// Gets the lines of code of the vulnerability, without the lines of context around them
+    pub fn get_code_lines(&self) -> Option<Vec<&str>> {
+        match (self.code.as_ref(), self.start_line, self.end_line) {
+            (Some(code), Some(start_line), Some(end_line)) if end_line >= start_line => {
+                // The code has up to 4 lines of context before the lines of the vulnerability
+                Some(code.lines()
+                    .skip(cmp::min(start_line, 4))
+                    .take(end_line - start_line + 1)
+                    .collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces the obfuscated names in the name, description and code of the vulnerability with
+    /// the original ones of the mapping
+    pub fn deobfuscate(&mut self, mapping: &Mapping) {
+        self.name = mapping.deobfuscate(&self.name);
+        self.description = mapping.deobfuscate(&self.description);
+        self.code = self.code.as_ref().map(|c| mapping.deobfuscate(c));
+    }
+
+    /// Evaluates the vulnerability against the practices of the time the application targets
+    ///
+    /// If the application targets an API level older than `since_sdk`, the API level from which
+    /// the practice is considered an issue, the vulnerability is downgraded to a warning and
+    /// its description explains that it's only reported for information.
+    pub fn adjust_for_era(&mut self, app_sdk: i32, since_sdk: i32) {
+        if app_sdk < since_sdk {
+            self.criticity = Criticity::Warning;
+            self.description = format!("{} Note: this was common practice for applications \
+                                        targeting API level {}, and it's only considered an \
+                                        issue since API level {}, so it's reported for \
+                                        information only.",
+                                       self.description,
+                                       app_sdk,
+                                       since_sdk);
+        }
+    }
 }
 
 impl Serialize for Vulnerability {
     fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
         where S: Serializer
     {
-        let mut state = try!(serializer.serialize_struct("Vulnerability", 7));
+        let mut state = try!(serializer.serialize_struct("Vulnerability", 13));
         try!(serializer.serialize_struct_elt(&mut state, "criticity", self.criticity));
         try!(serializer.serialize_struct_elt(&mut state, "name", self.name.as_str()));
         try!(serializer.serialize_struct_elt(&mut state, "description", self.description.as_str()));
         try!(serializer.serialize_struct_elt(&mut state, "file", &self.file));
         try!(serializer.serialize_struct_elt(&mut state, "start_line", self.start_line));
         try!(serializer.serialize_struct_elt(&mut state, "end_line", self.end_line));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "permission",
+                                             self.permission.as_ref().map(|p| p.as_str())));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "required_permissions",
+                                             self.required_permissions
+                                                 .iter()
+                                                 .map(|p| p.as_str())
+                                                 .collect::<Vec<_>>()));
+        try!(serializer.serialize_struct_elt(&mut state, "metadata", &self.metadata));
+        try!(serializer.serialize_struct_elt(&mut state, "fingerprint", self.get_fingerprint()));
+        try!(serializer.serialize_struct_elt(&mut state, "source_file", &self.source_file));
+        try!(serializer.serialize_struct_elt(&mut state, "source_line", self.source_line));
         try!(serializer.serialize_struct_end(state));
         Ok(())
     }
@@ -222,6 +509,14 @@ impl Benchmark {
             duration: duration,
         }
     }
+
+    pub fn get_label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        self.duration
+    }
 }
 
 impl fmt::Display for Benchmark {
@@ -233,3 +528,223 @@ impl fmt::Display for Benchmark {
                self.duration.subsec_nanos())
     }
 }
+
+/// Number of files checked, matches and time spent by a code rule, to find the rules that
+/// dominate the analysis time or generate noise
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleProfile {
+    id: String,
+    files: usize,
+    matches: usize,
+    duration: Duration,
+}
+
+impl RuleProfile {
+    /// Creates an empty profile for the rule with the given ID
+    pub fn new(id: &str) -> RuleProfile {
+        RuleProfile {
+            id: String::from(id),
+            files: 0,
+            matches: 0,
+            duration: Duration::new(0, 0),
+        }
+    }
+
+    /// Records a file checked by the rule, with its number of matches and the time spent on it
+    pub fn add_file(&mut self, matches: usize, duration: Duration) {
+        self.files += 1;
+        self.matches += matches;
+        self.duration += duration;
+    }
+
+    /// Adds the counts of another profile of the same rule, such as the one of another thread
+    pub fn merge(&mut self, other: &RuleProfile) {
+        self.files += other.files;
+        self.matches += other.matches;
+        self.duration += other.duration;
+    }
+
+    pub fn get_id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn get_files(&self) -> usize {
+        self.files
+    }
+
+    pub fn get_matches(&self) -> usize {
+        self.matches
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl fmt::Display for RuleProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f,
+               "{}: {} files, {} matches, {}.{:03}s",
+               self.id,
+               self.files,
+               self.matches,
+               self.duration.as_secs(),
+               self.duration.subsec_nanos() / 1_000_000)
+    }
+}
+
+impl Serialize for RuleProfile {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let seconds = self.duration.as_secs() as f64 +
+                      self.duration.subsec_nanos() as f64 / 1_000_000_000f64;
+        let mut state = try!(serializer.serialize_struct("RuleProfile", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.id.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "files", self.files));
+        try!(serializer.serialize_struct_elt(&mut state, "matches", self.matches));
+        try!(serializer.serialize_struct_elt(&mut state, "seconds", seconds));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Stage of the analysis that didn't finish, so that the report is marked as partial
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncompleteStage {
+    stage: String,
+    reason: String,
+    details: Option<String>,
+    log: Option<String>,
+}
+
+impl IncompleteStage {
+    /// Creates a new incomplete stage, with the reason why it didn't finish
+    pub fn new<S: Into<String>>(stage: &str, reason: S) -> IncompleteStage {
+        IncompleteStage {
+            stage: String::from(stage),
+            reason: reason.into(),
+            details: None,
+            log: None,
+        }
+    }
+
+    /// Adds details about the failure, such as the error output of the tool that failed
+    pub fn with_details(mut self, details: &str) -> IncompleteStage {
+        self.details = Some(String::from(details));
+        self
+    }
+
+    /// Adds the path of the log file with the output of the tool that failed, relative to the
+    /// results folder of the application
+    pub fn with_log(mut self, log: &str) -> IncompleteStage {
+        self.log = Some(String::from(log));
+        self
+    }
+
+    /// Creates the incomplete stage of a command that was killed after the given timeout
+    pub fn timed_out(stage: &str, timeout: Duration) -> IncompleteStage {
+        IncompleteStage::new(stage,
+                             format!("Stage timed out after {} seconds.", timeout.as_secs()))
+    }
+
+    pub fn get_stage(&self) -> &str {
+        self.stage.as_str()
+    }
+
+    pub fn get_reason(&self) -> &str {
+        self.reason.as_str()
+    }
+
+    pub fn get_details(&self) -> Option<&str> {
+        self.details.as_ref().map(|d| d.as_str())
+    }
+
+    pub fn get_log(&self) -> Option<&str> {
+        self.log.as_ref().map(|l| l.as_str())
+    }
+}
+
+impl Serialize for IncompleteStage {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("IncompleteStage", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "stage", self.stage.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "reason", self.reason.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "details", &self.details));
+        try!(serializer.serialize_struct_elt(&mut state, "log", &self.log));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use Criticity;
+    use super::{Vulnerability, RuleProfile};
+
+    #[test]
+    fn it_adjust_for_era() {
+        let mut vuln = Vulnerability::new(Criticity::High,
+                                          "World readable permissions",
+                                          "Description.",
+                                          Some("MainActivity.java"),
+                                          Some(1),
+                                          Some(1),
+                                          None);
+        vuln.adjust_for_era(17, 17);
+        assert_eq!(vuln.get_criticity(), Criticity::High);
+        assert_eq!(vuln.get_description(), "Description.");
+
+        vuln.adjust_for_era(10, 17);
+        assert_eq!(vuln.get_criticity(), Criticity::Warning);
+        assert!(vuln.get_description().starts_with("Description. Note:"));
+        assert!(vuln.get_description().contains("API level 17"));
+    }
+
+    #[test]
+    fn it_fingerprint() {
+        let get_vuln = |file: &str, line: usize, code: &str| {
+            let mut vuln = Vulnerability::new(Criticity::Medium,
+                                              "Weak hash",
+                                              "Description.",
+                                              Some(file),
+                                              Some(line),
+                                              Some(line),
+                                              Some(String::from(code)));
+            vuln.set_rule("weak_hash");
+            vuln
+        };
+        let code = "class A {\n    void a() {\n        md5(data);\n    }\n}\n";
+        let moved = "class A {\n    void b() {}\n\n    void a() {\n        md5(data);\n\
+                     }\n}\n";
+        let fingerprint = get_vuln("classes/A.java", 2, code).get_fingerprint();
+
+        assert_eq!(fingerprint, get_vuln("classes/A.java", 4, moved).get_fingerprint());
+        assert_eq!(fingerprint,
+                   get_vuln("classes\\A.java", 2, "class A {\n void a() {\n \tmd5(data);  ")
+                       .get_fingerprint());
+        assert!(fingerprint != get_vuln("classes/B.java", 2, code).get_fingerprint());
+        assert!(fingerprint != get_vuln("classes/A.java", 1, code).get_fingerprint());
+    }
+
+    #[test]
+    fn it_rule_profile() {
+        let mut profile = RuleProfile::new("math_random");
+        profile.add_file(2, Duration::from_millis(1500));
+        profile.add_file(0, Duration::from_millis(20));
+
+        let mut other = RuleProfile::new("math_random");
+        other.add_file(1, Duration::from_millis(5));
+        profile.merge(&other);
+
+        assert_eq!(profile.get_id(), "math_random");
+        assert_eq!(profile.get_files(), 3);
+        assert_eq!(profile.get_matches(), 3);
+        assert_eq!(profile.get_duration(), Duration::from_millis(1525));
+        assert_eq!(format!("{}", profile), "math_random: 3 files, 3 matches, 1.525s");
+    }
+}
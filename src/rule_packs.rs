@@ -0,0 +1,410 @@
+//! Rule packs, installed in the rules folder of the user with `super rules update`, so that the
+//! rules can be kept up to date without reinstalling SUPER.
+//!
+//! A rule pack bundle is a JSON object with the `name` and the `version` of the pack and its
+//! `rules`. Its SHA-256 checksum is downloaded from `{url}.sha256` and, if a public key is
+//! configured, its Ed25519 signature from `{url}.sig`. Bundles without a signature are only
+//! installed if allowed explicitly. The installed packs are listed in the `index.json` file of
+//! the rules folder, their rules are stored in its `packs` folder, and they are merged over the
+//! configured rules, as extra rule files.
+
+use std::{env, fs, result};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::{Value, to_value};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use crypto::ed25519;
+use rustc_serialize::hex::FromHex;
+use chrono::Local;
+
+use download::{download_file, fetch_url};
+use static_analysis::code::validate_rule_file;
+use {Config, Result, Error, print_error, print_warning, file_exists};
+
+/// Index of the installed rule packs, in the rules folder
+const INDEX_FILE: &'static str = "index.json";
+
+/// Folder of the rules folder with the rule file of each pack, so that the name of a pack never
+/// collides with the index or the downloaded bundle
+const PACKS_FOLDER: &'static str = "packs";
+
+/// Timeout to download the checksum and the signature of the bundle, in seconds
+const FETCH_TIMEOUT: u32 = 30;
+
+/// Rule pack installed in the rules folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulePack {
+    name: String,
+    version: String,
+    url: String,
+    sha256: String,
+    date: String,
+    rules: usize,
+}
+
+impl RulePack {
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn get_version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    /// Gets the SHA-256 checksum of the installed bundle of the pack
+    pub fn get_sha256(&self) -> &str {
+        self.sha256.as_str()
+    }
+
+    /// Gets the URL the pack was downloaded from
+    pub fn get_url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Gets the date the pack was installed, in RFC 3339 format
+    pub fn get_date(&self) -> &str {
+        self.date.as_str()
+    }
+
+    /// Gets the number of rules of the pack
+    pub fn get_rules(&self) -> usize {
+        self.rules
+    }
+
+    /// Loads a rule pack from its entry in the index
+    fn from_json(json: &Value) -> Result<RulePack> {
+        let get_string = |key: &str| json.lookup(key).and_then(|v| v.as_str()).map(String::from);
+        let rules = json.lookup("rules").and_then(|r| r.as_u64());
+        match (get_string("name"),
+               get_string("version"),
+               get_string("url"),
+               get_string("sha256"),
+               get_string("date"),
+               rules) {
+            (Some(name), Some(version), Some(url), Some(sha256), Some(date), Some(rules)) => {
+                Ok(RulePack {
+                    name: name,
+                    version: version,
+                    url: url,
+                    sha256: sha256,
+                    date: date,
+                    rules: rules as usize,
+                })
+            }
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+impl Serialize for RulePack {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("RulePack", 6));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.version.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "url", self.url.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "sha256", self.sha256.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "date", self.date.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "rules", self.rules));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the folder of the user where the rule packs are installed
+#[cfg(target_family = "unix")]
+pub fn get_rules_folder() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".local/share/super/rules"))
+}
+
+/// Gets the folder of the user where the rule packs are installed
+#[cfg(target_family = "windows")]
+pub fn get_rules_folder() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("super").join("rules"))
+}
+
+/// Lists the rule packs installed in the given rules folder
+pub fn list_rule_packs<P: AsRef<Path>>(folder: P) -> Result<Vec<RulePack>> {
+    let path = folder.as_ref().join(INDEX_FILE);
+    if !file_exists(&path) {
+        return Ok(Vec::new());
+    }
+
+    let f = try!(File::open(&path));
+    let index: Value = try!(serde_json::from_reader(f));
+    match index {
+        Value::Array(packs) => packs.iter().map(RulePack::from_json).collect(),
+        _ => Err(Error::ParseError),
+    }
+}
+
+/// Gets the path of the rule file of the pack with the given name
+fn get_pack_file(folder: &Path, name: &str) -> PathBuf {
+    folder.join(PACKS_FOLDER).join(format!("{}.json", name))
+}
+
+/// Gets the rule files of the packs installed in the given rules folder, in installation order
+pub fn get_rule_pack_files<P: AsRef<Path>>(folder: P) -> Vec<String> {
+    let folder = folder.as_ref();
+    list_rule_packs(folder)
+        .unwrap_or_default()
+        .iter()
+        .map(|pack| get_pack_file(folder, &pack.name))
+        .filter(|path| file_exists(path))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Gets the installed rule packs whose rules are loaded with the given configuration
+pub fn get_loaded_rule_packs(config: &Config) -> Vec<RulePack> {
+    let folder = match get_rules_folder() {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+    list_rule_packs(&folder)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|pack| {
+            let file = get_pack_file(&folder, &pack.name).to_string_lossy().into_owned();
+            config.get_rules_extra().contains(&file)
+        })
+        .collect()
+}
+
+/// Downloads the rule pack bundle of the configuration, verifies it and installs it in the given
+/// rules folder, replacing the previous version of the pack
+///
+/// If no public key is configured, the bundle is only installed if `allow_unsigned` is set.
+pub fn update_rule_pack<P: AsRef<Path>>(folder: P,
+                                        allow_unsigned: bool,
+                                        config: &Config)
+                                        -> Result<RulePack> {
+    let folder = folder.as_ref();
+    let url = config.get_rule_packs_config().get_url();
+    try!(fs::create_dir_all(folder));
+
+    let partial = folder.join("bundle.json.part");
+    let downloaded = folder.join("bundle.json.new");
+    // A previous partial download may be from an older version of the bundle
+    if file_exists(&partial) {
+        try!(fs::remove_file(&partial));
+    }
+    try!(download_file(url, &partial, &downloaded, None));
+
+    let mut bundle = Vec::new();
+    let read = File::open(&downloaded).and_then(|mut f| f.read_to_end(&mut bundle));
+    let _ = fs::remove_file(&downloaded);
+    let _ = try!(read);
+
+    try!(verify_bundle(&bundle, url, allow_unsigned, config));
+    install_bundle(&bundle, url, folder, config)
+}
+
+/// Verifies the checksum of the bundle and, if a public key is configured, its signature
+///
+/// Without a public key the bundle is rejected, unless unsigned bundles are allowed.
+fn verify_bundle(bundle: &[u8], url: &str, allow_unsigned: bool, config: &Config) -> Result<()> {
+    let (status, checksum) = try!(fetch_url(&format!("{}.sha256", url), FETCH_TIMEOUT));
+    // The checksum can be followed by the file name, as written by `sha256sum`
+    let checksum = checksum.split_whitespace().next().unwrap_or("").to_lowercase();
+    if status != 200 || checksum.is_empty() {
+        print_error(format!("The checksum of the rule pack could not be downloaded from {}.sha256.",
+                            url),
+                    config.is_verbose());
+        return Err(Error::Unknown);
+    }
+    let hash = get_sha256(bundle);
+    if hash != checksum {
+        print_error(format!("The checksum of the rule pack does not match. Expected {}, but got \
+                             {}.",
+                            checksum,
+                            hash),
+                    config.is_verbose());
+        return Err(Error::Unknown);
+    }
+
+    match config.get_rule_packs_config().get_public_key() {
+        Some(key) => {
+            let (status, signature) = try!(fetch_url(&format!("{}.sig", url), FETCH_TIMEOUT));
+            if status != 200 || !verify_signature(bundle, key, signature.trim()) {
+                print_error("The signature of the rule pack is not valid for the configured \
+                             public key.",
+                            config.is_verbose());
+                return Err(Error::Unknown);
+            }
+        }
+        None if allow_unsigned => {
+            print_warning("No public key is configured in the [rule_packs] section, so only the \
+                           checksum of the rule pack was verified.",
+                          config.is_verbose())
+        }
+        None => {
+            print_error("No public key is configured in the [rule_packs] section, so the \
+                         signature of the rule pack can't be verified. Configure the public key \
+                         of the pack, or use --allow-unsigned to install it anyway.",
+                        config.is_verbose());
+            return Err(Error::Config);
+        }
+    }
+    Ok(())
+}
+
+/// Gets the hexadecimal SHA-256 hash of the given bytes
+fn get_sha256(bytes: &[u8]) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.input(bytes);
+    sha256.result_str()
+}
+
+/// Checks the hexadecimal Ed25519 signature of the message with the hexadecimal public key
+fn verify_signature(message: &[u8], public_key: &str, signature: &str) -> bool {
+    match (public_key.from_hex(), signature.from_hex()) {
+        (Ok(ref key), Ok(ref signature)) if key.len() == 32 && signature.len() == 64 => {
+            ed25519::verify(message, key, signature)
+        }
+        _ => false,
+    }
+}
+
+/// Installs the rules of the verified bundle in the rules folder, and adds the pack to the index
+fn install_bundle(bundle: &[u8], url: &str, folder: &Path, config: &Config) -> Result<RulePack> {
+    let bundle: Value = try!(serde_json::from_slice(bundle));
+    let name = match bundle.lookup("name").and_then(|n| n.as_str()) {
+        Some(n) if is_pack_name(n) => n,
+        _ => {
+            print_error("The rule pack must have a name made of letters, numbers, `-` and `_`.",
+                        config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+    let version = match bundle.lookup("version").and_then(|v| v.as_str()) {
+        Some(v) => v,
+        None => {
+            print_error("The rule pack must have a version.", config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+    let rules = match bundle.lookup("rules") {
+        Some(&Value::Array(ref rules)) => rules,
+        _ => {
+            print_error("The rules of the rule pack must be an array.", config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    // The rules are validated before replacing the installed version of the pack
+    let rules_json = format!("{:?}", Value::Array(rules.clone()));
+    let pack_file = get_pack_file(folder, name);
+    try!(fs::create_dir_all(folder.join(PACKS_FOLDER)));
+    let new_file = pack_file.with_extension("json.new");
+    let mut f = try!(File::create(&new_file));
+    try!(f.write_all(rules_json.as_bytes()));
+    let count = match validate_rule_file(&new_file, config) {
+        Ok(c) => c,
+        Err(e) => {
+            let _ = fs::remove_file(&new_file);
+            return Err(e);
+        }
+    };
+    try!(fs::rename(&new_file, &pack_file));
+
+    let pack = RulePack {
+        name: String::from(name),
+        version: String::from(version),
+        url: String::from(url),
+        sha256: get_sha256(rules_json.as_bytes()),
+        date: Local::now().to_rfc3339(),
+        rules: count,
+    };
+    try!(add_to_index(folder, &pack));
+    Ok(pack)
+}
+
+/// Adds the pack to the index of the rules folder, replacing its previous version
+fn add_to_index(folder: &Path, pack: &RulePack) -> Result<()> {
+    let mut packs = try!(list_rule_packs(folder));
+    match packs.iter().position(|p| p.name == pack.name) {
+        Some(i) => packs[i] = pack.clone(),
+        None => packs.push(pack.clone()),
+    }
+
+    let index = Value::Array(packs.iter().map(to_value).collect());
+    let mut f = try!(File::create(folder.join(INDEX_FILE)));
+    try!(f.write_all(format!("{:?}", index).as_bytes()));
+    Ok(())
+}
+
+/// Checks if the name of a rule pack can be used as a file name
+fn is_pack_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crypto::ed25519;
+    use rustc_serialize::hex::ToHex;
+
+    use super::{RulePack, add_to_index, list_rule_packs, get_rule_pack_files, get_sha256,
+                verify_signature, is_pack_name};
+
+    #[test]
+    fn it_rule_pack_index() {
+        let folder = "rule_packs_test";
+        fs::create_dir_all(folder).unwrap();
+        assert!(list_rule_packs(folder).unwrap().is_empty());
+
+        let mut pack = RulePack {
+            name: String::from("company"),
+            version: String::from("1.0.0"),
+            url: String::from("https://example.com/rules/bundle.json"),
+            sha256: get_sha256(b"[]"),
+            date: String::from("2017-03-01T10:10:10+01:00"),
+            rules: 0,
+        };
+        add_to_index(folder.as_ref(), &pack).unwrap();
+        pack.version = String::from("1.1.0");
+        pack.rules = 3;
+        add_to_index(folder.as_ref(), &pack).unwrap();
+
+        let packs = list_rule_packs(folder).unwrap();
+        assert_eq!(packs, vec![pack]);
+        assert_eq!(packs[0].get_version(), "1.1.0");
+        assert_eq!(packs[0].get_rules(), 3);
+
+        // Packs whose rule file is missing are skipped
+        assert!(get_rule_pack_files(folder).is_empty());
+        fs::create_dir_all("rule_packs_test/packs").unwrap();
+        fs::File::create("rule_packs_test/packs/company.json").unwrap();
+        assert_eq!(get_rule_pack_files(folder), vec!["rule_packs_test/packs/company.json"]);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn it_verify_signature() {
+        let (secret, public) = ed25519::keypair(&[7u8; 32]);
+        let bundle = b"{\"name\": \"company\", \"version\": \"1.0.0\", \"rules\": []}";
+        let signature = ed25519::signature(bundle, &secret);
+
+        assert!(verify_signature(bundle, &public.to_hex(), &signature.to_hex()));
+        assert!(!verify_signature(b"{}", &public.to_hex(), &signature.to_hex()));
+        assert!(!verify_signature(bundle, &public.to_hex(), "00ff"));
+        assert!(!verify_signature(bundle, "not a key", &signature.to_hex()));
+    }
+
+    #[test]
+    fn it_is_pack_name() {
+        assert!(is_pack_name("company-rules_2"));
+        assert!(!is_pack_name(""));
+        assert!(!is_pack_name("../rules"));
+        assert!(!is_pack_name("company rules"));
+    }
+}
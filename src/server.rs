@@ -0,0 +1,552 @@
+use std::{fs, thread};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf, Component};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::ser::Serialize;
+use serde_json;
+use serde_json::builder::ObjectBuilder;
+use serde_json::value::Value;
+
+use {Config, Result, Error, print_warning};
+use jobs::{Job, JobQueue, JobStatus, SubmitError};
+
+/// Maximum size of the JSON bodies of the requests
+const MAX_JSON_SIZE: u64 = 64 * 1024;
+
+/// Maximum size of the uploaded APK files
+const MAX_UPLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Maximum length of the request line and of each header line, in bytes
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+
+/// Maximum number of headers of a request
+const MAX_HEADERS: usize = 100;
+
+/// Time a connection can be idle before it's closed, in seconds
+const CONNECTION_TIMEOUT: u64 = 30;
+
+/// Maximum number of connections handled at the same time
+const MAX_CONNECTIONS: usize = 64;
+
+/// State shared by the connections of the server
+struct Server {
+    queue: Arc<JobQueue>,
+    apk_folder: Option<PathBuf>,
+    connections: AtomicUsize,
+    verbose: bool,
+}
+
+/// Connection being handled by the server, counted until it's dropped
+struct Connection<'s> {
+    server: &'s Server,
+}
+
+impl<'s> Drop for Connection<'s> {
+    fn drop(&mut self) {
+        let _ = self.server.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Gets the JSON representation of a job, with the links to its results
+fn get_job_value(job: &Job) -> Value {
+    let mut value = serde_json::value::to_value(job);
+    if let Value::Object(ref mut object) = value {
+        let _ = object.insert(String::from("results"),
+                              Value::String(format!("/analyses/{}/results.json", job.get_id())));
+        let _ = object.insert(String::from("report"),
+                              Value::String(format!("/analyses/{}/report/index.html",
+                                                    job.get_id())));
+    }
+    value
+}
+
+/// HTTP request received by the server
+///
+/// The body is not part of the request, since uploads are streamed to the downloads folder.
+#[derive(Debug, Clone, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    query: BTreeMap<String, String>,
+    /// Headers, with lowercase names
+    headers: BTreeMap<String, String>,
+}
+
+impl Request {
+    /// Gets the value of the given header, by its lowercase name
+    fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|h| h.as_str())
+    }
+
+    /// Gets the length of the body of the request
+    fn get_content_length(&self) -> u64 {
+        self.get_header("content-length").and_then(|l| l.trim().parse().ok()).unwrap_or(0)
+    }
+}
+
+/// HTTP response of the server
+#[derive(Debug, Clone, PartialEq)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a JSON response
+    fn json<T: Serialize>(status: u16, body: &T) -> Response {
+        Response {
+            status: status,
+            content_type: "application/json",
+            body: format!("{:?}", serde_json::value::to_value(body)).into_bytes(),
+        }
+    }
+
+    /// Creates a JSON error response with the given message
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status,
+                       &ObjectBuilder::new().insert("error", message).build())
+    }
+
+    /// Writes the response to the stream, closing the connection after it
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        try!(write!(writer,
+                    "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: \
+                     close\r\n\r\n",
+                    self.status,
+                    get_reason(self.status),
+                    self.content_type,
+                    self.body.len()));
+        try!(writer.write_all(&self.body));
+        try!(writer.flush());
+        Ok(())
+    }
+}
+
+/// Gets the reason phrase of the HTTP status codes used by the server
+fn get_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Starts the server on the given address, with a job queue that analyzes the submitted
+/// applications, passing the given arguments to the analyzer of each job
+///
+/// The server runs until it's killed.
+pub fn serve(address: &str, config: &Config, analyzer_args: Vec<String>) -> Result<()> {
+    let listener = try!(TcpListener::bind(address));
+    let server = Arc::new(Server {
+        queue: try!(JobQueue::start(config, analyzer_args)),
+        apk_folder: match config.get_jobs_config().get_apk_folder() {
+            Some(folder) => Some(try!(fs::canonicalize(folder))),
+            None => None,
+        },
+        connections: AtomicUsize::new(0),
+        verbose: config.is_verbose(),
+    });
+
+    if !config.is_quiet() {
+        println!("Listening on {} with {} workers.", address, config.get_threads());
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                let timeout = Some(Duration::from_secs(CONNECTION_TIMEOUT));
+                if stream.set_read_timeout(timeout).is_err() ||
+                   stream.set_write_timeout(timeout).is_err() {
+                    continue;
+                }
+                if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    let _ = server.connections.fetch_sub(1, Ordering::SeqCst);
+                    let _ = Response::error(503, "The server is handling too many connections.")
+                        .write_to(&mut stream);
+                    continue;
+                }
+                let server = server.clone();
+                let _ = thread::spawn(move || {
+                    let _connection = Connection { server: &server };
+                    handle_connection(stream, &server)
+                });
+            }
+            Err(e) => {
+                print_warning(format!("A connection could not be accepted: {}", e),
+                              config.is_verbose())
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles a connection, answering its request
+fn handle_connection(stream: TcpStream, server: &Server) {
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    });
+    let response = match read_request(&mut reader) {
+        Ok(request) => route(&request, &mut reader, server),
+        Err(_) => Response::error(400, "The request could not be parsed."),
+    };
+    let mut stream = stream;
+    if let Err(e) = response.write_to(&mut stream) {
+        print_warning(format!("The response could not be sent: {}", e),
+                      server.verbose);
+    }
+}
+
+/// Reads a line of the request line or the headers, failing if it's longer than the maximum
+/// length or the connection is closed before its end
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<()> {
+    line.clear();
+    let _ = try!(reader.by_ref().take(MAX_LINE_LENGTH).read_line(line));
+    if line.ends_with('\n') {
+        Ok(())
+    } else {
+        Err(Error::ParseError)
+    }
+}
+
+/// Reads the request line and the headers of a request
+fn read_request<R: BufRead>(reader: &mut R) -> Result<Request> {
+    let mut line = String::new();
+    try!(read_line(reader, &mut line));
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (String::from(method), String::from(target)),
+        _ => return Err(Error::ParseError),
+    };
+    let (path, query) = match target.find('?') {
+        Some(i) => (String::from(&target[..i]), parse_query(&target[i + 1..])),
+        None => (target, BTreeMap::new()),
+    };
+
+    let mut headers = BTreeMap::new();
+    loop {
+        try!(read_line(reader, &mut line));
+        let header = line.trim_right();
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(Error::ParseError);
+        }
+        match header.find(':') {
+            Some(i) => {
+                let _ = headers.insert(header[..i].trim().to_lowercase(),
+                                       String::from(header[i + 1..].trim()));
+            }
+            None => return Err(Error::ParseError),
+        }
+    }
+    Ok(Request {
+        method: method,
+        path: path,
+        query: query,
+        headers: headers,
+    })
+}
+
+/// Parses the query string of a URL
+///
+/// Values are not percent-decoded, since package names don't need it.
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.find('=') {
+            Some(i) => (String::from(&p[..i]), String::from(&p[i + 1..])),
+            None => (String::from(p), String::new()),
+        })
+        .collect()
+}
+
+/// Answers the request with the endpoint of its path
+///
+/// - `POST /analyses`: submits an analysis, uploading the APK file with the `package` query
+///   parameter, or sending `{"path": "…"}` with the path of an APK file in the APK folder of the
+///   server, if it has one.
+/// - `GET /analyses`: lists the submitted analyses.
+/// - `GET /analyses/{id}`: gets the status of an analysis.
+/// - `GET /analyses/{id}/results.json`: gets the JSON report of a completed analysis.
+/// - `GET /analyses/{id}/report/{file}`: gets a file of the HTML report of a completed analysis.
+fn route<R: Read>(request: &Request, body: &mut R, server: &Server) -> Response {
+    let segments = request.path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    if segments.first() != Some(&"analyses") {
+        return Response::error(404, "The endpoint does not exist.");
+    }
+    match (request.method.as_str(), segments.len()) {
+        ("POST", 1) => submit_analysis(request, body, server),
+        ("GET", 1) => {
+            Response::json(200,
+                           &server.queue
+                               .get_all()
+                               .iter()
+                               .map(get_job_value)
+                               .collect::<Vec<_>>())
+        }
+        ("GET", 2) => {
+            match segments[1].parse().ok().and_then(|id| server.queue.get(id)) {
+                Some(job) => Response::json(200, &get_job_value(&job)),
+                None => Response::error(404, "The analysis does not exist."),
+            }
+        }
+        ("GET", 3) if segments[2] == "results.json" => {
+            get_result_file(segments[1], "results.json", server)
+        }
+        ("GET", _) if segments.len() > 3 && segments[2] == "report" => {
+            get_result_file(segments[1], &segments[3..].join("/"), server)
+        }
+        (_, 1) | (_, 2) => Response::error(405, "The method is not allowed."),
+        _ => Response::error(404, "The endpoint does not exist."),
+    }
+}
+
+/// Submits an analysis, storing its APK file in the downloads folder
+///
+/// The APK file is first stored in a temporary file, which the queue moves to the downloads
+/// folder if the package is not being analyzed.
+fn submit_analysis<R: Read>(request: &Request, body: &mut R, server: &Server) -> Response {
+    let length = request.get_content_length();
+    let is_json = request.get_header("content-type")
+        .map_or(false, |t| t.starts_with("application/json"));
+    let (package, file) = if is_json {
+        let apk_folder = match server.apk_folder {
+            Some(ref f) => f,
+            None => {
+                return Response::error(403,
+                                       "The server only accepts uploaded APK files, since it \
+                                        has no APK folder.")
+            }
+        };
+        if length > MAX_JSON_SIZE {
+            return Response::error(413, "The request is too big.");
+        }
+        let mut json = String::new();
+        if body.take(length).read_to_string(&mut json).is_err() {
+            return Response::error(400, "The request could not be read.");
+        }
+        let json: Value = match serde_json::from_str(&json) {
+            Ok(j) => j,
+            Err(_) => return Response::error(400, "The request is not valid JSON."),
+        };
+        let path = match json.lookup("path").and_then(|p| p.as_str()) {
+            Some(p) => PathBuf::from(p),
+            None => return Response::error(400, "The path of the APK file is missing."),
+        };
+        let package = match json.lookup("package").and_then(|p| p.as_str()) {
+            Some(p) => String::from(p),
+            None => {
+                path.file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(String::new)
+            }
+        };
+        if !is_package_name(&package) {
+            return Response::error(400, "The package name is not valid.");
+        }
+        let path = match get_apk_path(apk_folder, &path) {
+            Some(p) => p,
+            None => return Response::error(400, "The APK file does not exist in the APK folder."),
+        };
+        if server.queue.is_pending(&package) {
+            return Response::error(409, &format!("{} is already being analyzed.", package));
+        }
+        let file = server.queue.get_temp_file(&package);
+        if let Err(e) = fs::copy(&path, &file) {
+            let _ = fs::remove_file(&file);
+            return Response::error(500, &format!("The APK file could not be copied: {}", e));
+        }
+        (package, file)
+    } else {
+        let package = match request.query.get("package") {
+            Some(p) if is_package_name(p) => p.clone(),
+            _ => return Response::error(400, "The package query parameter is not valid."),
+        };
+        if server.queue.is_pending(&package) {
+            return Response::error(409, &format!("{} is already being analyzed.", package));
+        }
+        if length == 0 {
+            return Response::error(400, "The APK file is missing.");
+        } else if length > MAX_UPLOAD_SIZE {
+            return Response::error(413, "The APK file is too big.");
+        }
+        let file = server.queue.get_temp_file(&package);
+        if let Err(e) = save_upload(body, length, &file) {
+            let _ = fs::remove_file(&file);
+            return Response::error(500, &format!("The APK file could not be saved: {}", e));
+        }
+        (package, file)
+    };
+
+    match server.queue.submit(&package, &file) {
+        Ok(job) => Response::json(202, &get_job_value(&job)),
+        Err(SubmitError::Pending(e)) => Response::error(409, &e),
+        Err(SubmitError::File(e)) => {
+            Response::error(500, &format!("The APK file could not be stored: {}", e))
+        }
+    }
+}
+
+/// Saves the uploaded file, checking that the upload is complete
+fn save_upload<R: Read>(body: &mut R, length: u64, file: &Path) -> Result<()> {
+    let mut f = try!(File::create(file));
+    let copied = try!(::std::io::copy(&mut body.take(length), &mut f));
+    if copied < length {
+        return Err(Error::ParseError);
+    }
+    Ok(())
+}
+
+/// Gets a file of the results of a completed analysis
+fn get_result_file(id: &str, file: &str, server: &Server) -> Response {
+    let job = match id.parse().ok().and_then(|id| server.queue.get(id)) {
+        Some(a) => a,
+        None => return Response::error(404, "The analysis does not exist."),
+    };
+    if job.get_status() != JobStatus::Completed {
+        return Response::error(409, "The analysis is not completed.");
+    }
+    if !is_relative_path(file) {
+        return Response::error(400, "The file path is not valid.");
+    }
+    let path = server.queue.get_results_folder(&job).join(file);
+    let mut body = Vec::new();
+    match File::open(&path).and_then(|mut f| f.read_to_end(&mut body)) {
+        Ok(_) => {
+            Response {
+                status: 200,
+                content_type: get_content_type(&path),
+                body: body,
+            }
+        }
+        Err(_) => Response::error(404, "The file does not exist."),
+    }
+}
+
+/// Checks if the string is a valid package name, that can be used as a file name
+pub fn is_package_name(package: &str) -> bool {
+    !package.is_empty() && !package.starts_with('.') &&
+    package.chars().all(|c| match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '.' | '_' => true,
+        _ => false,
+    })
+}
+
+/// Gets the canonical path of an APK file, if it's a file inside the canonical APK folder
+///
+/// Relative paths are relative to the APK folder. Symbolic links are followed before checking
+/// that the file is inside it.
+fn get_apk_path(apk_folder: &Path, path: &Path) -> Option<PathBuf> {
+    match fs::canonicalize(apk_folder.join(path)) {
+        Ok(ref p) if p.starts_with(apk_folder) && p.is_file() => Some(p.clone()),
+        _ => None,
+    }
+}
+
+/// Checks if the path is relative and stays inside its base folder
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|c| match c {
+        Component::Normal(_) => true,
+        _ => false,
+    })
+}
+
+/// Gets the content type of a file of the results, from its extension
+fn get_content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "application/javascript",
+        Some("json") => "application/json",
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::Path;
+    use super::{MAX_LINE_LENGTH, MAX_HEADERS, read_request, is_package_name, is_relative_path,
+                get_apk_path};
+
+    #[test]
+    fn it_read_request() {
+        let mut request = Cursor::new(&b"POST /analyses?package=com.example&force HTTP/1.1\r\n\
+                                         Host: localhost:8080\r\n\
+                                         Content-Type: application/vnd.android.package-archive\r\n\
+                                         Content-Length: 1024\r\n\
+                                         \r\n\
+                                         PK"[..]);
+        let request = read_request(&mut request).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/analyses");
+        assert_eq!(request.query.get("package").map(|p| p.as_str()),
+                   Some("com.example"));
+        assert_eq!(request.query.get("force").map(|p| p.as_str()), Some(""));
+        assert_eq!(request.get_header("content-type"),
+                   Some("application/vnd.android.package-archive"));
+        assert_eq!(request.get_content_length(), 1024);
+
+        let mut truncated = Cursor::new(&b"GET /analyses HTTP/1.1\r\nHost: localhost"[..]);
+        assert!(read_request(&mut truncated).is_err());
+
+        let mut long_line = format!("GET /analyses HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+                                    "a".repeat(MAX_LINE_LENGTH as usize));
+        assert!(read_request(&mut Cursor::new(long_line.as_bytes())).is_err());
+        long_line = format!("GET /analyses HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(64));
+        assert!(read_request(&mut Cursor::new(long_line.as_bytes())).is_ok());
+
+        let mut many_headers = String::from("GET /analyses HTTP/1.1\r\n");
+        for i in 0..MAX_HEADERS + 1 {
+            many_headers.push_str(&format!("X-Header-{}: {}\r\n", i, i));
+        }
+        many_headers.push_str("\r\n");
+        assert!(read_request(&mut Cursor::new(many_headers.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn it_validates_paths() {
+        assert!(is_package_name("com.example.app_2"));
+        assert!(!is_package_name(""));
+        assert!(!is_package_name(".."));
+        assert!(!is_package_name("com/example"));
+
+        assert!(is_relative_path("index.html"));
+        assert!(is_relative_path("src/classes/com/example/A.java.html"));
+        assert!(!is_relative_path("../config.toml"));
+        assert!(!is_relative_path("/etc/passwd"));
+        assert!(!is_relative_path("src/../../config.toml"));
+
+        let apk_folder = fs::canonicalize("src").unwrap();
+        assert_eq!(get_apk_path(&apk_folder, Path::new("main.rs")),
+                   Some(apk_folder.join("main.rs")));
+        assert!(get_apk_path(&apk_folder, Path::new("static_analysis")).is_none());
+        assert!(get_apk_path(&apk_folder, Path::new("../Cargo.toml")).is_none());
+        assert!(get_apk_path(&apk_folder, Path::new("/etc/passwd")).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+use std::{env, fs};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use download::{download_file, get_sha256};
+use config::get_user_config_file;
+use {Config, Result, Error, print_error, print_warning};
+
+/// URL of the vendor folder of the SUPER repository, where the pinned versions of the tools are
+/// downloaded from by default
+pub const DEFAULT_MIRROR: &'static str = "https://raw.githubusercontent.com/\
+                                          SUPERAndroidAnalyzer/super/master/vendor";
+
+/// Files of the pinned versions of APKTool, Dex2Jar and JD-CMD, relative to the vendor folder,
+/// with their SHA-256 checksums
+const TOOL_FILES: &'static [(&'static str, &'static str)] =
+    &[("apktool_2.2.0.jar", "33529b641fb8985388920813d5b4e1cd75ea3d6cc0322d8489cf78a8516b5836"),
+      ("apktool.LICENSE.txt", "7a6e078a25f2efbf6d23dac32b0f3a09ab92cc2303b18d3dc08c367f6ab7e6ca"),
+      ("jd-cmd.jar", "163f49d0c39b17e4a46e762c945ac8df4b6835cd31173fc8c87693237be3cd8b"),
+      ("jd-cmd.LICENSE.txt", "589ed823e9a84c56feb95ac58e7cf384626b9cbf4fda2a907bc36e103de1bad2"),
+      ("dex2jar-2.0/LICENSE.txt",
+       "f2ffa5942b316849be283deaeb2912e2e5b5b17e91012bede8210210ff3e91e7"),
+      ("dex2jar-2.0/d2j-dex2jar.sh",
+       "c93b2ff3af12aa067884b62fed0f2f894206c6f60dd64babf7c7b2e60165d0e6"),
+      ("dex2jar-2.0/d2j-dex2jar.bat",
+       "d4e3f83817c434a0e14eaa9178a3ce6f3b52f6e6200ed228d6f7f56be67a2842"),
+      ("dex2jar-2.0/d2j_invoke.sh",
+       "a1030ac24a2a462e0ca6206cf03251952d3c4c7c3834ccbe44d31e5200e1d3a9"),
+      ("dex2jar-2.0/d2j_invoke.bat",
+       "8bed3a6403c2830f032803aacbb5c980817e385b9931a3aed54963bf37e08eab"),
+      ("dex2jar-2.0/lib/antlr-runtime-3.5.jar",
+       "7ef52a4e25ea2472a0ae62ae1d5ccaa7ef23be188289ad225fcb8a452a1b738d"),
+      ("dex2jar-2.0/lib/asm-debug-all-4.1.jar",
+       "c0f582e1eb589315a62939197116b24412c5f4386c5b78aee7b017a4532312ba"),
+      ("dex2jar-2.0/lib/d2j-base-cmd-2.0.jar",
+       "4be63198f70d83f0d0022d03219dc8ab36812b3a65de14ec6a152dcaeca2abcb"),
+      ("dex2jar-2.0/lib/d2j-jasmin-2.0.jar",
+       "2d42a4a97f0c7ceefa79839bbcd93f45cb5a962d5a2b866d3ffc67f8f8273bc8"),
+      ("dex2jar-2.0/lib/d2j-smali-2.0.jar",
+       "24a3f36705076447a020590ae1d22443de0f9c2ff2ac83a32df14af4cd5127ff"),
+      ("dex2jar-2.0/lib/dex-ir-2.0.jar",
+       "81efed5a016730cd02b725134101ff69aeb843c26584ed97878cc13a42a1db52"),
+      ("dex2jar-2.0/lib/dex-reader-2.0.jar",
+       "3def566afaa95edecbbd7cc74f3cfad4073be3dc0efcded8f163a1052b81bda6"),
+      ("dex2jar-2.0/lib/dex-reader-api-2.0.jar",
+       "3a5e21b43aeb70075f8eccc25817fa88e8f0d078205a1f63564fbf02ea5d5274"),
+      ("dex2jar-2.0/lib/dex-tools-2.0.jar",
+       "f45f5a729f4e79922f86c13693ce3af7de505ffb2dfa821a2630a70f6c7db9a4"),
+      ("dex2jar-2.0/lib/dex-translator-2.0.jar",
+       "3dd01af96cf4fda7559001470952f11e98030d5c14dbf18570731640c4468b18"),
+      ("dex2jar-2.0/lib/dex-writer-2.0.jar",
+       "a0ec9cb39ea71b2c98c53f5892aad2ab0a4a943039ea1307443748a40acf2006"),
+      ("dex2jar-2.0/lib/dx-1.7.jar",
+       "923302e666d76e126e4cdf7129318532b258b1a66e435b92ccc29e91a60357bf")];
+
+/// Gets the folder of the user where the tools are installed
+#[cfg(target_family = "unix")]
+pub fn get_tools_folder() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".local/share/super/vendor"))
+}
+
+/// Gets the folder of the user where the tools are installed
+#[cfg(target_family = "windows")]
+pub fn get_tools_folder() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("super").join("vendor"))
+}
+
+/// Downloads the pinned versions of the tools to the given folder, verifying their checksums
+///
+/// Files that were already downloaded are only downloaded again if their checksum doesn't match,
+/// or if the installation is forced. Returns the number of downloaded files.
+pub fn install_tools<P: AsRef<Path>>(folder: P, mirror: &str, config: &Config) -> Result<usize> {
+    let folder = folder.as_ref();
+    let mirror = mirror.trim_right_matches('/');
+    let mut downloaded = 0;
+    for &(file, checksum) in TOOL_FILES {
+        let target = folder.join(file);
+        if !config.is_force() && is_installed(&target, checksum) {
+            if config.is_verbose() {
+                println!("{} is already installed.", file);
+            }
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+        if config.is_verbose() {
+            println!("Downloading {}…", file);
+        }
+        let partial = folder.join(format!("{}.part", file));
+        try!(download_file(&format!("{}/{}", mirror, file), &partial, &target, None));
+
+        let (_, hash) = try!(get_sha256(&target));
+        if hash != checksum {
+            let _ = fs::remove_file(&target);
+            print_error(format!("The checksum of {} does not match the pinned version. \
+                                 Expected {}, but got {}.",
+                                file,
+                                checksum,
+                                hash),
+                        config.is_verbose());
+            return Err(Error::Unknown);
+        }
+        if file.ends_with(".sh") {
+            try!(set_executable(&target));
+        }
+        downloaded += 1;
+    }
+    Ok(downloaded)
+}
+
+/// Checks if the file exists and matches the given checksum
+fn is_installed(path: &Path, checksum: &str) -> bool {
+    match get_sha256(path) {
+        Ok((_, hash)) => hash == checksum,
+        Err(_) => false,
+    }
+}
+
+/// Allows the execution of the downloaded script
+#[cfg(target_family = "unix")]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = try!(fs::metadata(path)).permissions();
+    permissions.set_mode(0o755);
+    try!(fs::set_permissions(path, permissions));
+    Ok(())
+}
+
+/// Allows the execution of the downloaded script
+#[cfg(target_family = "windows")]
+fn set_executable(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Writes the paths of the tools installed in the given folder to the configuration file of the
+/// user, keeping the rest of its options, and returns the path of the configuration file
+pub fn write_user_config<P: AsRef<Path>>(folder: P, config: &Config) -> Result<PathBuf> {
+    let folder = folder.as_ref();
+    let path = match get_user_config_file() {
+        Some(p) => p,
+        None => {
+            print_warning("The configuration folder of the user could not be found.",
+                          config.is_verbose());
+            return Err(Error::Config);
+        }
+    };
+
+    let mut contents = String::new();
+    if path.exists() {
+        let mut f = try!(File::open(&path));
+        let _ = try!(f.read_to_string(&mut contents));
+    } else if let Some(parent) = path.parent() {
+        try!(fs::create_dir_all(parent));
+    }
+
+    let options = [("apktool_file", folder.join("apktool_2.2.0.jar")),
+                   ("dex2jar_folder", folder.join("dex2jar-2.0")),
+                   ("jd_cmd_file", folder.join("jd-cmd.jar"))];
+    let options: Vec<(&str, String)> = options.iter()
+        .map(|&(key, ref value)| (key, value.to_string_lossy().into_owned()))
+        .collect();
+
+    let mut f = try!(File::create(&path));
+    try!(f.write_all(update_config_options(&contents, &options).as_bytes()));
+    Ok(path)
+}
+
+/// Sets the given top level string options in the contents of a TOML configuration file
+///
+/// Existing options are replaced in place, so that comments and the rest of the options are
+/// kept, and new ones are added before the first section.
+fn update_config_options(contents: &str, options: &[(&str, String)]) -> String {
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let first_section = lines.iter()
+        .position(|l| l.trim_left().starts_with('['))
+        .unwrap_or(lines.len());
+
+    let mut new_lines = Vec::new();
+    for &(key, ref value) in options {
+        let line = format!("{} = \"{}\"",
+                           key,
+                           value.replace('\\', "\\\\").replace('"', "\\\""));
+        let existing = lines[..first_section].iter().position(|l| {
+            let mut parts = l.splitn(2, '=');
+            parts.next().map_or(false, |k| k.trim() == key) && parts.next().is_some()
+        });
+        match existing {
+            Some(i) => lines[i] = line,
+            None => new_lines.push(line),
+        }
+    }
+
+    if !new_lines.is_empty() && first_section < lines.len() {
+        new_lines.push(String::new());
+    }
+    for (i, line) in new_lines.into_iter().enumerate() {
+        lines.insert(first_section + i, line);
+    }
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::update_config_options;
+
+    #[test]
+    fn it_update_config_options() {
+        let options = vec![("apktool_file", String::from("/home/user/apktool.jar")),
+                           ("jd_cmd_file", String::from("C:\\super\\jd-cmd.jar"))];
+
+        assert_eq!(update_config_options("", &options),
+                   "apktool_file = \"/home/user/apktool.jar\"\n\
+                    jd_cmd_file = \"C:\\\\super\\\\jd-cmd.jar\"\n");
+
+        let contents = "# Comment\nthreads = 4\napktool_file = \"old.jar\"\n\n[jobs]\n\
+                        timeout = 60\n";
+        assert_eq!(update_config_options(contents, &options),
+                   "# Comment\nthreads = 4\napktool_file = \"/home/user/apktool.jar\"\n\n\
+                    jd_cmd_file = \"C:\\\\super\\\\jd-cmd.jar\"\n\n[jobs]\ntimeout = 60\n");
+    }
+}
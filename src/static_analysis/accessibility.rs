@@ -0,0 +1,452 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use xml::reader::{EventReader, XmlEvent};
+use colored::Colorize;
+
+use {Config, Result, Error, Criticity, print_warning, print_vulnerability, get_code,
+     PARSER_CONFIG};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, Component, ComponentKind, Permission};
+use super::network_security::get_config_file;
+use super::read_text_file;
+
+/// Order of the accessibility and device administration section in the report, after the
+/// components
+const SECTION_ORDER: u32 = 56;
+
+/// Permission that the system holds to bind to accessibility services
+const BIND_ACCESSIBILITY_SERVICE: &'static str = "android.permission.BIND_ACCESSIBILITY_SERVICE";
+
+/// Permission that the system holds to send broadcasts to device administrators
+const BIND_DEVICE_ADMIN: &'static str = "android.permission.BIND_DEVICE_ADMIN";
+
+/// Meta-data with the configuration of an accessibility service
+const ACCESSIBILITY_META_DATA: &'static str = "android.accessibilityservice";
+
+/// Meta-data with the policies of a device administrator
+const DEVICE_ADMIN_META_DATA: &'static str = "android.app.device_admin";
+
+/// Accessibility event types that report the text typed by the user or the content of the
+/// windows of other applications
+const SENSITIVE_EVENT_TYPES: [&'static str; 4] = ["typeAllMask",
+                                                  "typeViewTextChanged",
+                                                  "typeWindowContentChanged",
+                                                  "typeViewFocused"];
+
+/// Device administration policies that can lock the user out of the device or destroy data
+const DANGEROUS_POLICIES: [&'static str; 6] = ["wipe-data",
+                                               "reset-password",
+                                               "force-lock",
+                                               "limit-password",
+                                               "expire-password",
+                                               "disable-keyguard-features"];
+
+/// Code patterns of accessibility services that act on other applications, with what they do
+const ACCESSIBILITY_PATTERNS: [(&'static str, &'static str); 7] =
+    [("performGlobalAction", "performs global actions, such as going back or home"),
+     ("dispatchGesture", "dispatches gestures on the screen"),
+     ("ACTION_CLICK", "clicks on the elements of other applications"),
+     ("ACTION_SET_TEXT", "fills the text fields of other applications"),
+     ("TYPE_ACCESSIBILITY_OVERLAY", "draws overlays over other applications"),
+     ("TYPE_APPLICATION_OVERLAY", "draws overlays over other applications"),
+     (".getText()", "reads the text of the events or of the windows")];
+
+/// Code patterns of device administrators that lock the user out of the device or destroy data,
+/// with what they do
+const DEVICE_ADMIN_PATTERNS: [(&'static str, &'static str); 5] =
+    [("wipeData", "wipes the data of the device"),
+     ("resetPassword", "changes the password of the device"),
+     ("lockNow", "locks the device"),
+     ("setCameraDisabled", "disables the camera"),
+     ("onDisableRequested", "shows a message when the user tries to disable it")];
+
+/// Configuration of an accessibility service, from its XML resource
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AccessibilityConfig {
+    event_types: Vec<String>,
+    can_retrieve_window_content: bool,
+    can_perform_gestures: bool,
+    can_filter_key_events: bool,
+    /// Packages whose events the service receives, or all of them if empty
+    package_names: Vec<String>,
+}
+
+impl AccessibilityConfig {
+    /// Gets the event types that report sensitive data
+    fn get_sensitive_event_types(&self) -> Vec<&str> {
+        self.event_types
+            .iter()
+            .map(|t| t.as_str())
+            .filter(|t| SENSITIVE_EVENT_TYPES.contains(t))
+            .collect()
+    }
+
+    /// Checks if the service can read the content of the windows of every application
+    fn is_broad(&self) -> bool {
+        self.can_retrieve_window_content && self.package_names.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        let mut capabilities = Vec::new();
+        if !self.event_types.is_empty() {
+            capabilities.push(format!("events: {}", self.event_types.join(", ")));
+        }
+        if self.can_retrieve_window_content {
+            capabilities.push(String::from("retrieves the window content"));
+        }
+        if self.can_perform_gestures {
+            capabilities.push(String::from("performs gestures"));
+        }
+        if self.can_filter_key_events {
+            capabilities.push(String::from("filters key events"));
+        }
+        capabilities.push(if self.package_names.is_empty() {
+            String::from("all applications")
+        } else {
+            format!("packages: {}", self.package_names.join(", "))
+        });
+        capabilities.join("; ")
+    }
+}
+
+/// Parses the `<accessibility-service>` configuration of an accessibility service
+fn parse_accessibility_config(code: &str) -> Result<AccessibilityConfig> {
+    let parser = EventReader::new_with_config(code.as_bytes(), PARSER_CONFIG);
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                if name.local_name != "accessibility-service" {
+                    continue;
+                }
+                let mut config: AccessibilityConfig = Default::default();
+                for attr in attributes {
+                    let list = || -> Vec<String> {
+                        attr.value
+                            .split(|c| c == '|' || c == ',')
+                            .map(|v| String::from(v.trim()))
+                            .filter(|v| !v.is_empty())
+                            .collect()
+                    };
+                    match attr.name.local_name.as_str() {
+                        "accessibilityEventTypes" => config.event_types = list(),
+                        "packageNames" => config.package_names = list(),
+                        "canRetrieveWindowContent" => {
+                            config.can_retrieve_window_content = attr.value == "true"
+                        }
+                        "canPerformGestures" => config.can_perform_gestures = attr.value == "true",
+                        "canRequestFilterKeyEvents" => {
+                            config.can_filter_key_events = attr.value == "true"
+                        }
+                        _ => {}
+                    }
+                }
+                return Ok(config);
+            }
+            Ok(_) => {}
+            Err(_) => return Err(Error::ParseError),
+        }
+    }
+    Err(Error::ParseError)
+}
+
+/// Parses the policies used by a device administrator, from its `<device-admin>` resource
+fn parse_device_admin_policies(code: &str) -> Result<Vec<String>> {
+    let parser = EventReader::new_with_config(code.as_bytes(), PARSER_CONFIG);
+    let mut in_policies = false;
+    let mut policies = Vec::new();
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, .. }) => {
+                if name.local_name == "uses-policies" {
+                    in_policies = true;
+                } else if in_policies {
+                    policies.push(name.local_name);
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                if name.local_name == "uses-policies" {
+                    in_policies = false;
+                }
+            }
+            Ok(_) => {}
+            Err(_) => return Err(Error::ParseError),
+        }
+    }
+    Ok(policies)
+}
+
+/// Finds the abuse patterns of the given list in the code, without repeating descriptions
+fn find_patterns(code: &str, patterns: &[(&'static str, &'static str)]) -> Vec<&'static str> {
+    let mut found = Vec::new();
+    for &(marker, description) in patterns {
+        if code.contains(marker) && !found.contains(&description) {
+            found.push(description);
+        }
+    }
+    found
+}
+
+/// Reads the XML resource referenced by a meta-data element of a component
+fn read_meta_data_file(component: &Component,
+                       meta_data: &str,
+                       config: &Config)
+                       -> Option<(String, String)> {
+    let file = match component.get_meta_data(meta_data).and_then(get_config_file) {
+        Some(f) => f,
+        None => return None,
+    };
+    let path = format!("{}/{}/{}", config.get_dist_folder(), config.get_app_id(), file);
+    match read_text_file(Path::new(&path)) {
+        Ok(Some(code)) => Some((file, code)),
+        Ok(None) => None,
+        Err(e) => {
+            print_warning(format!("There was an error reading the file {}: {}", path, e),
+                          config.is_verbose());
+            None
+        }
+    }
+}
+
+/// Reads the decompiled code of the class of a component, if it was decompiled
+pub fn read_component_code(component: &Component, config: &Config) -> Option<(PathBuf, String)> {
+    let relative = PathBuf::from(format!("classes/{}.java",
+                                         component.get_name().replace('.', "/")));
+    let path = PathBuf::from(format!("{}/{}", config.get_dist_folder(), config.get_app_id()))
+        .join(&relative);
+    match read_text_file(&path) {
+        Ok(Some(code)) => Some((relative, code)),
+        _ => None,
+    }
+}
+
+/// Looks for accessibility services and device administrators, the capabilities that banking
+/// trojans abuse to read the screen, act on other applications and lock the user out of the
+/// device
+///
+/// Their configuration resources are parsed, and the code of their classes is searched for
+/// patterns such as performing global actions, drawing overlays or wiping the device. The
+/// criticity grows with the capabilities they configure and use.
+pub fn accessibility_analysis(manifest: Option<&Manifest>,
+                              config: &Config,
+                              results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Looking for accessibility services and device administrators.");
+    }
+    let overlays = manifest.get_permission_checklist()
+        .needs_permission(Permission::AndroidPermissionSystemAlertWindow);
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for component in manifest.get_components() {
+        let accessibility = component.get_kind() == ComponentKind::Service &&
+                            component.get_permission() == Some(BIND_ACCESSIBILITY_SERVICE);
+        let device_admin = component.get_kind() == ComponentKind::Receiver &&
+                           component.get_permission() == Some(BIND_DEVICE_ADMIN);
+        if !component.is_enabled() || !(accessibility || device_admin) {
+            continue;
+        }
+
+        let code = read_component_code(component, config);
+        let (kind, criticity, configuration, patterns, description) = if accessibility {
+            let service_config = read_meta_data_file(component, ACCESSIBILITY_META_DATA, config)
+                .and_then(|(file, code)| match parse_accessibility_config(&code) {
+                    Ok(c) => Some(c),
+                    Err(_) => {
+                        print_warning(format!("The accessibility service configuration {} \
+                                               could not be parsed.",
+                                              file),
+                                      config.is_verbose());
+                        None
+                    }
+                });
+            let patterns = code.as_ref()
+                .map_or(Vec::new(), |&(_, ref c)| find_patterns(c, &ACCESSIBILITY_PATTERNS));
+            let broad = service_config.as_ref().map_or(false, |c| {
+                c.is_broad() && !c.get_sensitive_event_types().is_empty()
+            });
+            let criticity = if broad && (!patterns.is_empty() || overlays) {
+                Criticity::High
+            } else {
+                Criticity::Medium
+            };
+            let configuration = service_config.as_ref()
+                .map_or(String::new(), |c| c.describe());
+            let mut description = format!("The application declares the accessibility service \
+                                           {}, that can observe and act on the user \
+                                           interface of other applications. Accessibility \
+                                           services are the main capability of banking trojans, \
+                                           used to read credentials and to perform actions on \
+                                           behalf of the user.",
+                                          component.get_name());
+            if broad {
+                description.push_str(" It retrieves the window content of all applications, \
+                                      with events that report the typed text or the screen \
+                                      content.");
+            }
+            if !patterns.is_empty() {
+                description.push_str(&format!(" Its code {}.", patterns.join(", ")));
+            }
+            if overlays {
+                description.push_str(" The application can also draw over other \
+                                      applications with the SYSTEM_ALERT_WINDOW permission, \
+                                      allowing overlay and clickjacking attacks.");
+            }
+            ("Accessibility service", criticity, configuration, patterns, description)
+        } else {
+            let policies = read_meta_data_file(component, DEVICE_ADMIN_META_DATA, config)
+                .and_then(|(file, code)| match parse_device_admin_policies(&code) {
+                    Ok(p) => Some(p),
+                    Err(_) => {
+                        print_warning(format!("The device administrator policies {} could not \
+                                               be parsed.",
+                                              file),
+                                      config.is_verbose());
+                        None
+                    }
+                })
+                .unwrap_or_else(Vec::new);
+            let dangerous: Vec<_> = policies.iter()
+                .map(|p| p.as_str())
+                .filter(|p| DANGEROUS_POLICIES.contains(p))
+                .collect();
+            let patterns = code.as_ref()
+                .map_or(Vec::new(), |&(_, ref c)| find_patterns(c, &DEVICE_ADMIN_PATTERNS));
+            let criticity = if !patterns.is_empty() && !dangerous.is_empty() {
+                Criticity::High
+            } else if !dangerous.is_empty() {
+                Criticity::Medium
+            } else {
+                Criticity::Low
+            };
+            let mut description = format!("The application declares the device administrator \
+                                           {}. Device administrators can't be uninstalled \
+                                           while they are active, and are used by malware to \
+                                           lock the user out of the device or to wipe it.",
+                                          component.get_name());
+            if !dangerous.is_empty() {
+                description.push_str(&format!(" It uses the {} policies.", dangerous.join(", ")));
+            }
+            if !patterns.is_empty() {
+                description.push_str(&format!(" Its code {}.", patterns.join(", ")));
+            }
+            ("Device administrator", criticity, policies.join(", "), patterns, description)
+        };
+
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), criticity);
+        }
+        let line = component.get_line();
+        let mut vuln = Vulnerability::new(criticity,
+                                          String::from(kind),
+                                          description,
+                                          Some("AndroidManifest.xml"),
+                                          line,
+                                          line,
+                                          line.map(|l| get_code(manifest.get_code(), l, l)));
+        vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-284")],
+                                            vec![String::from("MASVS-PLATFORM-1")],
+                                            vec![String::from("M8")],
+                                            Vec::new(),
+                                            Some(String::from("Only use these capabilities \
+                                                               for their intended purpose, \
+                                                               restricted to the events, \
+                                                               packages and policies the \
+                                                               feature needs."))));
+        results.add_vulnerability(vuln);
+
+        rows.push(vec![String::from(kind),
+                       Results::html_escape(component.get_name()),
+                       Results::html_escape(&configuration),
+                       patterns.iter()
+                           .map(|p| Results::html_escape(p))
+                           .collect::<Vec<_>>()
+                           .join("<br>")]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("kind", String::from(kind));
+        let _ = entry.insert("component", String::from(component.get_name()));
+        let _ = entry.insert("configuration", configuration);
+        let _ = entry.insert("patterns", patterns.join(", "));
+        data.push(entry);
+    }
+
+    if !rows.is_empty() {
+        let mut section = ReportSection::new("accessibility",
+                                             "Accessibility and device administration",
+                                             SECTION_ORDER);
+        section.set_data(&data);
+        section.set_html_table(&["Kind", "Component", "Configuration", "Code patterns"], &rows);
+        results.add_section(section);
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}",
+                 "The accessibility services and device administrators were analyzed correctly!"
+                     .green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Accessibility services and device administrators analyzed.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ACCESSIBILITY_PATTERNS, parse_accessibility_config, parse_device_admin_policies,
+                find_patterns};
+
+    #[test]
+    fn it_parse_accessibility_config() {
+        let code = r#"<?xml version="1.0" encoding="utf-8"?>
+<accessibility-service xmlns:android="http://schemas.android.com/apk/res/android"
+    android:accessibilityEventTypes="typeWindowStateChanged|typeViewTextChanged"
+    android:canRetrieveWindowContent="true"
+    android:canPerformGestures="true" />"#;
+        let config = parse_accessibility_config(code).unwrap();
+        assert_eq!(config.event_types,
+                   vec![String::from("typeWindowStateChanged"),
+                        String::from("typeViewTextChanged")]);
+        assert_eq!(config.get_sensitive_event_types(), vec!["typeViewTextChanged"]);
+        assert!(config.can_perform_gestures);
+        assert!(!config.can_filter_key_events);
+        assert!(config.is_broad());
+
+        let code = r#"<accessibility-service
+    xmlns:android="http://schemas.android.com/apk/res/android"
+    android:canRetrieveWindowContent="true"
+    android:packageNames="com.example.reader" />"#;
+        let config = parse_accessibility_config(code).unwrap();
+        assert!(!config.is_broad());
+        assert_eq!(config.describe(),
+                   "retrieves the window content; packages: com.example.reader");
+        assert!(parse_accessibility_config("<device-admin />").is_err());
+    }
+
+    #[test]
+    fn it_parse_device_admin_policies() {
+        let code = r#"<device-admin xmlns:android="http://schemas.android.com/apk/res/android">
+    <uses-policies>
+        <force-lock />
+        <wipe-data />
+    </uses-policies>
+</device-admin>"#;
+        assert_eq!(parse_device_admin_policies(code).unwrap(),
+                   vec![String::from("force-lock"), String::from("wipe-data")]);
+    }
+
+    #[test]
+    fn it_find_patterns() {
+        let code = "AccessibilityNodeInfo node = getRootInActiveWindow();\n\
+                    node.performAction(AccessibilityNodeInfo.ACTION_CLICK);\n\
+                    params.type = WindowManager.LayoutParams.TYPE_ACCESSIBILITY_OVERLAY;\n\
+                    performGlobalAction(1);";
+        assert_eq!(find_patterns(code, &ACCESSIBILITY_PATTERNS),
+                   vec!["performs global actions, such as going back or home",
+                        "clicks on the elements of other applications",
+                        "draws overlays over other applications"]);
+    }
+}
@@ -0,0 +1,421 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::cmp::Ordering;
+
+use serde_json;
+use serde_json::value::Value;
+use colored::Colorize;
+
+use {Config, Criticity, Result, Error, print_warning, print_vulnerability, file_exists};
+use results::{Results, Vulnerability};
+use download::download_file;
+use super::dependencies::Dependency;
+
+/// Range of affected versions of an advisory, with the first affected version and the version
+/// fixing it, if known
+#[derive(Debug, Clone, PartialEq)]
+pub struct AffectedRange {
+    introduced: Option<String>,
+    fixed: Option<String>,
+}
+
+impl AffectedRange {
+    /// Checks if the given version is in the range
+    pub fn contains(&self, version: &str) -> bool {
+        let after_introduced = match self.introduced {
+            Some(ref introduced) => compare_versions(version, introduced) != Ordering::Less,
+            None => true,
+        };
+        let before_fixed = match self.fixed {
+            Some(ref fixed) => compare_versions(version, fixed) == Ordering::Less,
+            None => true,
+        };
+        after_introduced && before_fixed
+    }
+
+    pub fn get_fixed(&self) -> Option<&str> {
+        match self.fixed {
+            Some(ref f) => Some(f.as_str()),
+            None => None,
+        }
+    }
+
+    fn from_json(value: &Value) -> Result<AffectedRange> {
+        let range = match value.as_object() {
+            Some(r) => r,
+            None => return Err(Error::ParseError),
+        };
+        let get_version = |key: &str| match range.get(key) {
+            Some(&Value::String(ref v)) => Ok(Some(v.clone())),
+            None => Ok(None),
+            _ => Err(Error::ParseError),
+        };
+        Ok(AffectedRange {
+            introduced: try!(get_version("introduced")),
+            fixed: try!(get_version("fixed")),
+        })
+    }
+}
+
+/// Known vulnerability of a library
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advisory {
+    id: String,
+    library: String,
+    summary: String,
+    cvss: f64,
+    affected: Vec<AffectedRange>,
+}
+
+impl Advisory {
+    /// Gets the identifier of the advisory, such as a CVE or an OSV identifier
+    pub fn get_id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Gets the name of the affected library, as reported in the dependencies section
+    pub fn get_library(&self) -> &str {
+        self.library.as_str()
+    }
+
+    pub fn get_summary(&self) -> &str {
+        self.summary.as_str()
+    }
+
+    /// Gets the CVSS base score of the vulnerability
+    pub fn get_cvss(&self) -> f64 {
+        self.cvss
+    }
+
+    /// Gets the criticity of the vulnerability, from its CVSS base score
+    pub fn get_criticity(&self) -> Criticity {
+        get_cvss_criticity(self.cvss)
+    }
+
+    /// Gets the affected range containing the given version, if any
+    pub fn get_affected_range(&self, version: &str) -> Option<&AffectedRange> {
+        self.affected.iter().find(|r| r.contains(version))
+    }
+
+    fn from_json(value: &Value) -> Result<Advisory> {
+        let advisory = match value.as_object() {
+            Some(a) => a,
+            None => return Err(Error::ParseError),
+        };
+        let get_str = |key: &str| match advisory.get(key) {
+            Some(&Value::String(ref s)) => Ok(s.clone()),
+            _ => Err(Error::ParseError),
+        };
+        let cvss = match advisory.get("cvss").and_then(|c| c.as_f64()) {
+            Some(c) if c >= 0.0 && c <= 10.0 => c,
+            _ => return Err(Error::ParseError),
+        };
+        let affected = match advisory.get("affected").and_then(|a| a.as_array()) {
+            Some(ranges) => {
+                let mut affected = Vec::with_capacity(ranges.len());
+                for range in ranges {
+                    affected.push(try!(AffectedRange::from_json(range)));
+                }
+                affected
+            }
+            None => return Err(Error::ParseError),
+        };
+        Ok(Advisory {
+            id: try!(get_str("id")),
+            library: try!(get_str("library")),
+            summary: try!(get_str("summary")),
+            cvss: cvss,
+            affected: affected,
+        })
+    }
+}
+
+/// Offline database of known vulnerabilities of the libraries
+///
+/// It's stored as a JSON object with the date of the last update in `updated` and the list of
+/// advisories in `advisories`.
+#[derive(Debug)]
+pub struct AdvisoryDatabase {
+    updated: String,
+    advisories: Vec<Advisory>,
+}
+
+impl AdvisoryDatabase {
+    /// Loads the database from the given JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AdvisoryDatabase> {
+        let mut f = try!(File::open(path));
+        let mut json = String::new();
+        try!(f.read_to_string(&mut json));
+        AdvisoryDatabase::from_json(json.as_str())
+    }
+
+    fn from_json(json: &str) -> Result<AdvisoryDatabase> {
+        let value: Value = try!(serde_json::from_str(json));
+        let database = match value.as_object() {
+            Some(d) => d,
+            None => return Err(Error::ParseError),
+        };
+        let updated = match database.get("updated") {
+            Some(&Value::String(ref u)) => u.clone(),
+            _ => return Err(Error::ParseError),
+        };
+        let advisories = match database.get("advisories").and_then(|a| a.as_array()) {
+            Some(list) => {
+                let mut advisories = Vec::with_capacity(list.len());
+                for advisory in list {
+                    advisories.push(try!(Advisory::from_json(advisory)));
+                }
+                advisories
+            }
+            None => return Err(Error::ParseError),
+        };
+        Ok(AdvisoryDatabase {
+            updated: updated,
+            advisories: advisories,
+        })
+    }
+
+    /// Gets the date of the last update of the database
+    pub fn get_updated(&self) -> &str {
+        self.updated.as_str()
+    }
+
+    pub fn len(&self) -> usize {
+        self.advisories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.advisories.is_empty()
+    }
+
+    /// Gets the advisories affecting the given version of the library
+    pub fn get_advisories(&self, library: &str, version: &str) -> Vec<&Advisory> {
+        self.advisories
+            .iter()
+            .filter(|a| a.library == library && a.get_affected_range(version).is_some())
+            .collect()
+    }
+}
+
+/// Gets the criticity for a CVSS base score, following the CVSS v3 severity ratings
+pub fn get_cvss_criticity(cvss: f64) -> Criticity {
+    if cvss >= 9.0 {
+        Criticity::Critical
+    } else if cvss >= 7.0 {
+        Criticity::High
+    } else if cvss >= 4.0 {
+        Criticity::Medium
+    } else if cvss > 0.0 {
+        Criticity::Low
+    } else {
+        Criticity::Warning
+    }
+}
+
+/// Gets the numeric components of a version, ignoring any suffix, so that `3.12.1-beta` is
+/// `[3, 12, 1]`
+fn get_version_numbers(version: &str) -> Vec<u64> {
+    let mut numbers = Vec::new();
+    for part in version.split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_digit(10)).collect();
+        if digits.is_empty() {
+            break;
+        }
+        numbers.push(digits.parse().unwrap_or(u64::max_value()));
+        if digits.len() < part.len() {
+            break;
+        }
+    }
+    numbers
+}
+
+/// Compares two versions by their numeric components, so that `3.9` is lower than `3.10` and
+/// `3.1` equals `3.1.0`
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = get_version_numbers(a);
+    let b = get_version_numbers(b);
+    for i in 0..::std::cmp::max(a.len(), b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Loads the offline vulnerability database, if it exists and it's valid
+pub fn load_advisory_database(config: &Config) -> Option<AdvisoryDatabase> {
+    let path = config.get_vulnerability_db_config().get_path();
+    if !file_exists(path) {
+        if config.is_verbose() {
+            println!("The vulnerability database `{}` does not exist, so the libraries will not \
+                      be checked for known vulnerabilities. You can download it with \
+                      `super --update-db`.",
+                     path);
+        }
+        return None;
+    }
+    match AdvisoryDatabase::load(path) {
+        Ok(d) => Some(d),
+        Err(e) => {
+            print_warning(format!("The vulnerability database `{}` could not be loaded. Try \
+                                   updating it with `super --update-db`. More info: {}",
+                                  path,
+                                  e),
+                          config.is_verbose());
+            None
+        }
+    }
+}
+
+/// Adds a vulnerability for each advisory of the database affecting the given version of the
+/// library, and gets the number of advisories found
+pub fn add_advisory_findings(database: &AdvisoryDatabase,
+                             library: &str,
+                             version: &str,
+                             config: &Config,
+                             results: &mut Results)
+                             -> usize {
+    let advisories = database.get_advisories(library, version);
+    for advisory in &advisories {
+        let fixed = advisory.get_affected_range(version).and_then(|r| r.get_fixed());
+        let description = format!("{} {} is affected by {} (CVSS {:.1}): {}{}",
+                                  library,
+                                  version,
+                                  advisory.get_id(),
+                                  advisory.get_cvss(),
+                                  advisory.get_summary(),
+                                  match fixed {
+                                      Some(f) => format!(" It's fixed in version {}.", f),
+                                      None => String::from(" There is no fixed version."),
+                                  });
+        let criticity = advisory.get_criticity();
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), criticity);
+        }
+        let vuln = Vulnerability::new(criticity,
+                                      format!("{} in {}", advisory.get_id(), library),
+                                      description,
+                                      None as Option<&str>,
+                                      None,
+                                      None,
+                                      None);
+        results.add_vulnerability(vuln);
+    }
+    advisories.len()
+}
+
+/// Looks for the known vulnerabilities of the detected libraries in the offline vulnerability
+/// database
+pub fn advisory_analysis(dependencies: &[Dependency], config: &Config, results: &mut Results) {
+    let database = match load_advisory_database(config) {
+        Some(d) => d,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Checking the libraries against the vulnerability database, updated on {}.",
+                 database.get_updated());
+    }
+
+    for dependency in dependencies {
+        if let Some(version) = dependency.get_version() {
+            let _ = add_advisory_findings(&database,
+                                          dependency.get_name(),
+                                          version,
+                                          config,
+                                          results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}",
+                 "The libraries were checked for known vulnerabilities correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Known vulnerabilities checked.");
+    }
+}
+
+/// Downloads the vulnerability database from the configured URL, replacing the current one
+/// only if the new one is valid, and returns the number of advisories in it
+pub fn update_advisory_database(config: &Config) -> Result<usize> {
+    let db_config = config.get_vulnerability_db_config();
+    let path = PathBuf::from(db_config.get_path());
+    let partial = PathBuf::from(format!("{}.part", db_config.get_path()));
+    let downloaded = PathBuf::from(format!("{}.new", db_config.get_path()));
+
+    // A previous partial download may be from an older version of the database
+    if file_exists(&partial) {
+        try!(fs::remove_file(&partial));
+    }
+    try!(download_file(db_config.get_url(), &partial, &downloaded, None));
+    let database = match AdvisoryDatabase::load(&downloaded) {
+        Ok(d) => d,
+        Err(e) => {
+            try!(fs::remove_file(&downloaded));
+            return Err(e);
+        }
+    };
+    try!(fs::rename(&downloaded, &path));
+    Ok(database.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use Criticity;
+    use super::{AdvisoryDatabase, compare_versions, get_cvss_criticity};
+
+    #[test]
+    fn it_compare_versions() {
+        assert_eq!(compare_versions("3.9", "3.10"), Ordering::Less);
+        assert_eq!(compare_versions("3.1", "3.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("4.0.2", "4.0.1"), Ordering::Greater);
+        assert_eq!(compare_versions("2.8.9-SNAPSHOT", "2.8.9"), Ordering::Equal);
+        assert_eq!(compare_versions("v2", "1.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn it_cvss_criticity() {
+        assert_eq!(get_cvss_criticity(9.8), Criticity::Critical);
+        assert_eq!(get_cvss_criticity(7.5), Criticity::High);
+        assert_eq!(get_cvss_criticity(5.9), Criticity::Medium);
+        assert_eq!(get_cvss_criticity(3.1), Criticity::Low);
+        assert_eq!(get_cvss_criticity(0.0), Criticity::Warning);
+    }
+
+    #[test]
+    fn it_advisory_database() {
+        let database = AdvisoryDatabase::from_json("{\"updated\": \"2026-10-01\", \
+                                                    \"advisories\": [{\
+                                                        \"id\": \"CVE-2015-1835\", \
+                                                        \"library\": \"Apache Cordova\", \
+                                                        \"summary\": \"Settings injection.\", \
+                                                        \"cvss\": 5, \
+                                                        \"affected\": [\
+                                                            {\"fixed\": \"3.7.2\"}, \
+                                                            {\"introduced\": \"4.0.0\", \
+                                                             \"fixed\": \"4.0.2\"}]}]}")
+            .unwrap();
+        assert_eq!(database.get_updated(), "2026-10-01");
+        assert_eq!(database.len(), 1);
+
+        let advisories = database.get_advisories("Apache Cordova", "4.0.1");
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].get_criticity(), Criticity::Medium);
+        assert_eq!(advisories[0].get_affected_range("4.0.1").unwrap().get_fixed(),
+                   Some("4.0.2"));
+        assert_eq!(database.get_advisories("Apache Cordova", "3.6.0").len(), 1);
+        assert!(database.get_advisories("Apache Cordova", "3.7.2").is_empty());
+        assert!(database.get_advisories("Apache Cordova", "4.0.2").is_empty());
+        assert!(database.get_advisories("OkHttp", "4.0.1").is_empty());
+
+        assert!(AdvisoryDatabase::from_json("[]").is_err());
+        assert!(AdvisoryDatabase::from_json("{\"updated\": \"2026-10-01\", \"advisories\": \
+                                             [{\"id\": \"CVE-2015-1835\"}]}")
+            .is_err());
+    }
+}
@@ -0,0 +1,398 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::result;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeSet, BTreeMap};
+
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use Config;
+use results::Results;
+use super::manifest::{Manifest, Feature, ComponentKind};
+use super::native::NativeLibrary;
+
+/// Screen density qualifiers of the drawable and mipmap resource folders
+const DENSITIES: [&'static str; 9] = ["ldpi", "mdpi", "tvdpi", "hdpi", "xhdpi", "xxhdpi",
+                                      "xxxhdpi", "nodpi", "anydpi"];
+
+/// Image extensions of the launcher icons, in order of preference
+const ICON_EXTENSIONS: [&'static str; 3] = ["png", "webp", "jpg"];
+
+/// Name of the copy of the launcher icon in the report folder, without the extension
+const ICON_FILE: &'static str = "icon";
+
+/// Maximum number of adaptive icon layers followed to find the image of the launcher icon
+const MAX_ICON_DEPTH: usize = 2;
+
+/// Keys of the component counts, by the kind of the components
+fn get_component_key(kind: ComponentKind) -> &'static str {
+    match kind {
+        ComponentKind::Activity => "activities",
+        ComponentKind::ActivityAlias => "activity_aliases",
+        ComponentKind::Service => "services",
+        ComponentKind::Receiver => "receivers",
+        ComponentKind::Provider => "providers",
+    }
+}
+
+/// Gets the locale of a `values` resource folder, such as `pt-BR` for `values-pt-rBR` or
+/// `sr-Latn` for `values-b+sr+Latn`, if it has a language qualifier
+pub fn get_locale(folder: &str) -> Option<String> {
+    let mut qualifiers = folder.split('-');
+    if qualifiers.next() != Some("values") {
+        return None;
+    }
+    // The mobile country and network codes go before the language
+    let mut qualifiers = qualifiers.skip_while(|q| q.starts_with("mcc") || q.starts_with("mnc"));
+    let language = match qualifiers.next() {
+        Some(q) if q.starts_with("b+") => return Some(q[2..].replace('+', "-")),
+        // `car` is the UI mode of car docks, not a language
+        Some(q) if (q.len() == 2 || q.len() == 3) && q != "car" &&
+                   q.chars().all(|c| c >= 'a' && c <= 'z') => q,
+        _ => return None,
+    };
+    match qualifiers.next() {
+        Some(r) if r.len() == 3 && r.starts_with('r') => Some(format!("{}-{}", language, &r[1..])),
+        _ => Some(String::from(language)),
+    }
+}
+
+/// Gets the screen density of a drawable or mipmap resource folder, such as `xhdpi` for
+/// `mipmap-xhdpi-v4`, if it has a density qualifier
+fn get_density(folder: &str) -> Option<&str> {
+    let mut qualifiers = folder.split('-');
+    match qualifiers.next() {
+        Some("drawable") | Some("mipmap") => qualifiers.find(|q| DENSITIES.iter().any(|d| d == q)),
+        _ => None,
+    }
+}
+
+/// Ranks the screen density of a resource folder, higher for denser screens, so that the
+/// sharpest version of the launcher icon is used
+fn get_density_rank(folder: &str) -> usize {
+    match get_density(folder).and_then(|d| DENSITIES.iter().position(|r| *r == d)) {
+        // `nodpi` and `anydpi` are the last ones, and they are not densities as such
+        Some(i) if i < 7 => i + 1,
+        _ => 0,
+    }
+}
+
+/// Gets the drawable of the foreground layer of an adaptive icon, such as
+/// `@mipmap/ic_launcher_foreground`
+fn get_foreground_drawable(xml: &str) -> Option<&str> {
+    let foreground = match xml.find("<foreground") {
+        Some(i) => &xml[i..],
+        None => return None,
+    };
+    let foreground = match foreground.find('>') {
+        Some(end) => &foreground[..end],
+        None => foreground,
+    };
+    foreground.find("android:drawable=\"").and_then(|i| {
+        let value = &foreground[i + 18..];
+        value.find('"').map(|end| &value[..end])
+    })
+}
+
+/// Finds the image of a drawable or mipmap resource, such as `@mipmap/ic_launcher`, in the
+/// resource folder of the densest screens it's provided for
+///
+/// Adaptive icons are XML files, so the image of their foreground layer is used instead.
+fn find_icon(res: &Path, resource: &str, depth: usize) -> Option<PathBuf> {
+    let (kind, name) = match resource.find('/') {
+        Some(i) if resource.starts_with('@') => (&resource[1..i], &resource[i + 1..]),
+        _ => return None,
+    };
+    if kind != "drawable" && kind != "mipmap" {
+        return None;
+    }
+
+    let mut folders: Vec<(usize, PathBuf)> = match fs::read_dir(res) {
+        Ok(entries) => {
+            entries.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .and_then(|n| n.split('-').next())
+                        .map_or(false, |n| n == kind)
+                })
+                .map(|p| {
+                    let rank = get_density_rank(&p.file_name().unwrap().to_string_lossy());
+                    (rank, p)
+                })
+                .collect()
+        }
+        Err(_) => return None,
+    };
+    // Densest folders first, and sorted by name for the same density
+    folders.sort_by(|a, b| {
+        match b.0.cmp(&a.0) {
+            Ordering::Equal => a.1.cmp(&b.1),
+            o => o,
+        }
+    });
+
+    for &(_, ref folder) in &folders {
+        for extension in &ICON_EXTENSIONS {
+            let image = folder.join(format!("{}.{}", name, extension));
+            if image.is_file() {
+                return Some(image);
+            }
+        }
+    }
+    if depth >= MAX_ICON_DEPTH {
+        return None;
+    }
+    for &(_, ref folder) in &folders {
+        let mut xml = String::new();
+        if File::open(folder.join(format!("{}.xml", name)))
+            .and_then(|mut f| f.read_to_string(&mut xml))
+            .is_ok() {
+            if let Some(icon) = get_foreground_drawable(&xml)
+                .and_then(|drawable| find_icon(res, drawable, depth + 1)) {
+                return Some(icon);
+            }
+        }
+    }
+    None
+}
+
+/// Structured metadata of the application package, for the header of the report
+pub struct ApkInfo {
+    version_name: String,
+    version_code: i32,
+    min_sdk: i32,
+    target_sdk: Option<i32>,
+    max_sdk: Option<i32>,
+    features: Vec<Feature>,
+    abis: BTreeSet<String>,
+    locales: BTreeSet<String>,
+    densities: BTreeSet<String>,
+    components: BTreeMap<&'static str, usize>,
+    icon: Option<PathBuf>,
+}
+
+impl ApkInfo {
+    /// Gets the image of the launcher icon in the decompressed application, if it was found
+    pub fn get_icon(&self) -> Option<&Path> {
+        match self.icon {
+            Some(ref i) => Some(i.as_path()),
+            None => None,
+        }
+    }
+
+    /// Gets the name of the copy of the launcher icon in the report folder, if it was found
+    pub fn get_icon_file(&self) -> Option<String> {
+        self.icon.as_ref().map(|i| match i.extension() {
+            Some(e) => format!("{}.{}", ICON_FILE, e.to_string_lossy()),
+            None => String::from(ICON_FILE),
+        })
+    }
+
+    pub fn get_max_sdk(&self) -> Option<i32> {
+        self.max_sdk
+    }
+
+    /// Gets the features declared with `<uses-feature>`
+    pub fn get_features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    /// Gets the ABIs of the native libraries of the application
+    pub fn get_abis(&self) -> &BTreeSet<String> {
+        &self.abis
+    }
+
+    /// Gets the locales the application has resources for, besides the default one
+    pub fn get_locales(&self) -> &BTreeSet<String> {
+        &self.locales
+    }
+
+    /// Gets the screen densities the application has drawables or icons for
+    pub fn get_densities(&self) -> &BTreeSet<String> {
+        &self.densities
+    }
+
+    /// Gets the number of components of each kind, by the keys of their kinds
+    pub fn get_components(&self) -> &BTreeMap<&'static str, usize> {
+        &self.components
+    }
+}
+
+/// Feature in the APK metadata, with its name and if it's required
+struct FeatureInfo<'f>(&'f Feature);
+
+impl<'f> Serialize for FeatureInfo<'f> {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Feature", 2));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.0.get_name()));
+        try!(serializer.serialize_struct_elt(&mut state, "required", self.0.is_required()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+impl Serialize for ApkInfo {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let features: Vec<_> = self.features.iter().map(FeatureInfo).collect();
+        let mut state = try!(serializer.serialize_struct("ApkInfo", 11));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "version_name",
+                                             self.version_name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version_code", self.version_code));
+        try!(serializer.serialize_struct_elt(&mut state, "min_sdk", self.min_sdk));
+        try!(serializer.serialize_struct_elt(&mut state, "target_sdk", self.target_sdk));
+        try!(serializer.serialize_struct_elt(&mut state, "max_sdk", self.max_sdk));
+        try!(serializer.serialize_struct_elt(&mut state, "features", features));
+        try!(serializer.serialize_struct_elt(&mut state, "abis", &self.abis));
+        try!(serializer.serialize_struct_elt(&mut state, "locales", &self.locales));
+        try!(serializer.serialize_struct_elt(&mut state, "densities", &self.densities));
+        try!(serializer.serialize_struct_elt(&mut state, "components", &self.components));
+        try!(serializer.serialize_struct_elt(&mut state, "icon", self.get_icon_file()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the locales and the screen densities of the resource folders of the application
+fn get_resource_configs(res: &Path) -> (BTreeSet<String>, BTreeSet<String>) {
+    let mut locales = BTreeSet::new();
+    let mut densities = BTreeSet::new();
+    if let Ok(entries) = fs::read_dir(res) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(locale) = get_locale(&name) {
+                let _ = locales.insert(locale);
+            } else if let Some(density) = get_density(&name) {
+                let _ = densities.insert(String::from(density));
+            }
+        }
+    }
+    (locales, densities)
+}
+
+/// Extracts the version, the SDK levels, the features, the ABIs, the locales, the screen
+/// densities and the number of components of the application, for the header of the report
+pub fn apk_info_analysis(manifest: Option<&Manifest>,
+                         libraries: &[NativeLibrary],
+                         config: &Config,
+                         results: &mut Results) {
+    let res = Path::new(config.get_dist_folder()).join(config.get_app_id()).join("res");
+    let (locales, densities) = get_resource_configs(&res);
+    let mut info = ApkInfo {
+        version_name: String::new(),
+        version_code: 0,
+        min_sdk: 0,
+        target_sdk: None,
+        max_sdk: None,
+        features: Vec::new(),
+        abis: libraries.iter().map(|l| String::from(l.get_abi())).collect(),
+        locales: locales,
+        densities: densities,
+        components: BTreeMap::new(),
+        icon: None,
+    };
+    for kind in &[ComponentKind::Activity,
+                  ComponentKind::ActivityAlias,
+                  ComponentKind::Service,
+                  ComponentKind::Receiver,
+                  ComponentKind::Provider] {
+        let _ = info.components.insert(get_component_key(*kind), 0);
+    }
+
+    if let Some(manifest) = manifest {
+        info.version_name = String::from(manifest.get_version_str());
+        info.version_code = manifest.get_version_number();
+        info.min_sdk = manifest.get_min_sdk();
+        info.target_sdk = manifest.get_target_sdk();
+        info.max_sdk = manifest.get_max_sdk();
+        info.features = manifest.get_features().to_vec();
+        info.icon = manifest.get_icon().and_then(|icon| find_icon(&res, icon, 0));
+        for component in manifest.get_components() {
+            *info.components.entry(get_component_key(component.get_kind())).or_insert(0) += 1;
+        }
+    }
+
+    results.set_apk_info(info);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The APK metadata was extracted correctly!".green());
+    } else if !config.is_quiet() {
+        println!("APK metadata extracted.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::{get_locale, get_density, get_foreground_drawable, find_icon};
+
+    #[test]
+    fn it_get_locale() {
+        assert_eq!(get_locale("values-es"), Some(String::from("es")));
+        assert_eq!(get_locale("values-pt-rBR"), Some(String::from("pt-BR")));
+        assert_eq!(get_locale("values-b+sr+Latn"), Some(String::from("sr-Latn")));
+        assert_eq!(get_locale("values-mcc310-en-v21"), Some(String::from("en")));
+        assert_eq!(get_locale("values-fil"), Some(String::from("fil")));
+        assert_eq!(get_locale("values"), None);
+        assert_eq!(get_locale("values-v21"), None);
+        assert_eq!(get_locale("values-land"), None);
+        assert_eq!(get_locale("values-car"), None);
+        assert_eq!(get_locale("drawable-es"), None);
+    }
+
+    #[test]
+    fn it_get_density() {
+        assert_eq!(get_density("mipmap-xxhdpi-v4"), Some("xxhdpi"));
+        assert_eq!(get_density("drawable-land-hdpi"), Some("hdpi"));
+        assert_eq!(get_density("drawable"), None);
+        assert_eq!(get_density("values-hdpi"), None);
+    }
+
+    #[test]
+    fn it_get_foreground_drawable() {
+        let xml = "<adaptive-icon xmlns:android=\"http://schemas.android.com/apk/res/android\">\n\
+                   <background android:drawable=\"@color/ic_launcher_background\"/>\n\
+                   <foreground android:drawable=\"@mipmap/ic_launcher_foreground\"/>\n\
+                   </adaptive-icon>";
+        assert_eq!(get_foreground_drawable(xml), Some("@mipmap/ic_launcher_foreground"));
+        assert_eq!(get_foreground_drawable("<selector/>"), None);
+    }
+
+    #[test]
+    fn it_find_icon() {
+        let res = Path::new("apk_info_icon_test");
+        fs::create_dir_all(res.join("mipmap-hdpi")).unwrap();
+        fs::create_dir_all(res.join("mipmap-xxhdpi")).unwrap();
+        fs::create_dir_all(res.join("mipmap-anydpi-v26")).unwrap();
+        let _ = File::create(res.join("mipmap-hdpi/ic_launcher.png")).unwrap();
+        let _ = File::create(res.join("mipmap-xxhdpi/ic_launcher.png")).unwrap();
+        let _ = File::create(res.join("mipmap-xxhdpi/ic_round_foreground.webp")).unwrap();
+        let mut f = File::create(res.join("mipmap-anydpi-v26/ic_round.xml")).unwrap();
+        f.write_all(b"<adaptive-icon><foreground \
+                      android:drawable=\"@mipmap/ic_round_foreground\"/></adaptive-icon>")
+            .unwrap();
+
+        assert_eq!(find_icon(res, "@mipmap/ic_launcher", 0),
+                   Some(res.join("mipmap-xxhdpi/ic_launcher.png")));
+        assert_eq!(find_icon(res, "@mipmap/ic_round", 0),
+                   Some(res.join("mipmap-xxhdpi/ic_round_foreground.webp")));
+        assert_eq!(find_icon(res, "@drawable/missing", 0), None);
+        assert_eq!(find_icon(res, "@android:drawable/sym_def_app_icon", 0), None);
+
+        fs::remove_dir_all(res).unwrap();
+    }
+}
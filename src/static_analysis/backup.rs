@@ -0,0 +1,389 @@
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use xml::reader::{EventReader, XmlEvent};
+use colored::Colorize;
+
+use {Config, Result, Error, Criticity, print_warning, print_vulnerability, get_code,
+     PARSER_CONFIG};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, get_line};
+use super::network_security::get_config_file;
+use super::storage::SensitiveFile;
+use super::read_text_file;
+
+/// Order of the backups section in the report
+const SECTION_ORDER: u32 = 86;
+
+/// API level from which the data extraction rules replace the full backup content rules
+const DATA_EXTRACTION_RULES_SDK: i32 = 31;
+
+/// Backup of the application data, with its own set of rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupKind {
+    /// Cloud backups up to Android 11, configured by the full backup content rules
+    LegacyCloud,
+    /// Cloud backups from Android 12, configured by the `<cloud-backup>` data extraction rules
+    Cloud,
+    /// Device to device transfers from Android 12, configured by the `<device-transfer>` data
+    /// extraction rules
+    DeviceTransfer,
+}
+
+impl BackupKind {
+    fn get_label(&self) -> &'static str {
+        match *self {
+            BackupKind::LegacyCloud => "Cloud backups up to Android 11",
+            BackupKind::Cloud => "Cloud backups from Android 12",
+            BackupKind::DeviceTransfer => "Device transfers",
+        }
+    }
+}
+
+/// `<include>` or `<exclude>` element of the backup rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BackupRule {
+    kind: BackupKind,
+    include: bool,
+    domain: String,
+    path: String,
+}
+
+impl BackupRule {
+    /// Checks if the rule applies to the file with the given domain and name
+    ///
+    /// Rules of the `root` domain apply to the shared preferences and databases in their
+    /// folders, and rules without path, or with `.`, apply to the whole domain.
+    fn matches(&self, domain: &str, name: &str) -> bool {
+        let target = if self.domain == domain {
+            String::from(name)
+        } else if self.domain == "root" {
+            let folder = match domain {
+                "sharedpref" => "shared_prefs",
+                "database" => "databases",
+                _ => "files",
+            };
+            format!("{}/{}", folder, name)
+        } else {
+            return false;
+        };
+        let path = self.path.trim_matches('/');
+        path.is_empty() || path == "." || target == path ||
+        target.starts_with(format!("{}/", path).as_str())
+    }
+}
+
+/// Parses the full backup content or the data extraction rules XML
+fn parse_backup_rules(code: &str) -> Result<Vec<BackupRule>> {
+    let parser = EventReader::new_with_config(code.as_bytes(), PARSER_CONFIG);
+    let mut rules = Vec::new();
+    let mut kind = None;
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let attribute = |key: &str| {
+                    attributes.iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "full-backup-content" => kind = Some(BackupKind::LegacyCloud),
+                    "cloud-backup" => kind = Some(BackupKind::Cloud),
+                    "device-transfer" => kind = Some(BackupKind::DeviceTransfer),
+                    element @ "include" | element @ "exclude" => {
+                        let kind = match kind {
+                            Some(k) => k,
+                            None => return Err(Error::ParseError),
+                        };
+                        rules.push(BackupRule {
+                            kind: kind,
+                            include: element == "include",
+                            domain: match attribute("domain") {
+                                Some(d) => d,
+                                None => return Err(Error::ParseError),
+                            },
+                            path: attribute("path").unwrap_or_else(|| String::from(".")),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            Ok(_) => {}
+            Err(_) => return Err(Error::ParseError),
+        }
+    }
+    Ok(rules)
+}
+
+/// Checks if a file is in the given backup according to the rules
+///
+/// Without `<include>` rules all the files are backed up, and with them only the included ones.
+/// Excluded files are never backed up.
+fn is_backed_up(rules: &[BackupRule], kind: BackupKind, domain: &str, name: &str) -> bool {
+    let rules: Vec<_> = rules.iter().filter(|r| r.kind == kind).collect();
+    let included = !rules.iter().any(|r| r.include) ||
+                   rules.iter().any(|r| r.include && r.matches(domain, name));
+    included && !rules.iter().any(|r| !r.include && r.matches(domain, name))
+}
+
+/// Rules of the backups of the application, loaded from the files referenced in the manifest
+struct BackupRules {
+    /// Backups that can run, with the file with their rules, if any
+    kinds: Vec<(BackupKind, Option<String>)>,
+    rules: Vec<BackupRule>,
+}
+
+impl BackupRules {
+    /// Loads the rules of the cloud backups that can run for the application
+    ///
+    /// Applications targeting Android 12 or later use the data extraction rules in Android 12
+    /// devices, and the full backup content rules in older devices, if their minimum SDK allows
+    /// them. Rules that can't be read or parsed are reported and ignored, as if all the data
+    /// was backed up.
+    fn load(manifest: &Manifest, config: &Config) -> BackupRules {
+        let mut backup_rules = BackupRules {
+            kinds: Vec::new(),
+            rules: Vec::new(),
+        };
+        if !manifest.allows_backup() {
+            return backup_rules;
+        }
+        let target_sdk = manifest.get_target_sdk().unwrap_or(manifest.get_min_sdk());
+
+        if manifest.get_min_sdk() < DATA_EXTRACTION_RULES_SDK ||
+           target_sdk < DATA_EXTRACTION_RULES_SDK {
+            match manifest.get_full_backup_content() {
+                Some("false") => {}
+                Some(resource) if resource != "true" => {
+                    let file = get_config_file(resource);
+                    backup_rules.load_file(BackupKind::LegacyCloud, file, resource, config);
+                }
+                _ => backup_rules.kinds.push((BackupKind::LegacyCloud, None)),
+            }
+        }
+        if target_sdk >= DATA_EXTRACTION_RULES_SDK {
+            match manifest.get_data_extraction_rules() {
+                Some(resource) => {
+                    let file = get_config_file(resource);
+                    backup_rules.load_file(BackupKind::Cloud, file, resource, config);
+                }
+                None => backup_rules.kinds.push((BackupKind::Cloud, None)),
+            }
+        }
+        backup_rules
+    }
+
+    /// Loads the rules of the given backup from a rules file
+    fn load_file(&mut self,
+                 kind: BackupKind,
+                 file: Option<String>,
+                 resource: &str,
+                 config: &Config) {
+        let file = match file {
+            Some(f) => f,
+            None => {
+                print_warning(format!("The backup rules resource {} could not be resolved.",
+                                      resource),
+                              config.is_verbose());
+                self.kinds.push((kind, None));
+                return;
+            }
+        };
+        let path = format!("{}/{}/{}", config.get_dist_folder(), config.get_app_id(), file);
+        let rules = match read_text_file(Path::new(&path)) {
+            Ok(Some(code)) => parse_backup_rules(&code),
+            Ok(None) => Err(Error::ParseError),
+            Err(e) => Err(e),
+        };
+        match rules {
+            Ok(rules) => {
+                // Rules files can have both sections, but only the one of this backup applies
+                self.rules.extend(rules.into_iter().filter(|r| r.kind == kind));
+                self.kinds.push((kind, Some(file)));
+            }
+            Err(e) => {
+                print_warning(format!("The backup rules in {} could not be loaded, so all the \
+                                       data is considered backed up: {}",
+                                      file,
+                                      e),
+                              config.is_verbose());
+                self.kinds.push((kind, None));
+            }
+        }
+    }
+
+    /// Gets the backups that include the file, with their rules file
+    fn get_backups(&self, file: &SensitiveFile) -> Vec<(BackupKind, Option<&str>)> {
+        self.kinds
+            .iter()
+            .filter(|&&(kind, _)| {
+                is_backed_up(&self.rules, kind, file.get_domain(), file.get_name())
+            })
+            .map(|&(kind, ref rules_file)| (kind, rules_file.as_ref().map(String::as_str)))
+            .collect()
+    }
+}
+
+/// Checks if the private files that store sensitive data, found by the data storage analysis,
+/// are copied to the cloud backups of the application
+///
+/// The `allowBackup` flag itself is checked by the manifest analysis, so this analysis only
+/// reports the sensitive files that the backup rules don't exclude.
+pub fn backup_analysis(manifest: Option<&Manifest>,
+                       sensitive_files: &[SensitiveFile],
+                       config: &Config,
+                       results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Checking the backup rules of the application.");
+    }
+
+    let backup_rules = BackupRules::load(manifest, config);
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for file in sensitive_files {
+        let backups = backup_rules.get_backups(file);
+        let labels: Vec<_> = backups.iter().map(|&(kind, _)| kind.get_label()).collect();
+        if !backups.is_empty() {
+            add_finding(file, &backups, manifest, config, results);
+        }
+
+        let path = format!("{}/{}", file.get_domain(), file.get_name());
+        rows.push(vec![Results::html_escape(&path),
+                       String::from(file.get_term()),
+                       Results::html_escape(file.get_location()),
+                       if labels.is_empty() {
+                           String::from("None")
+                       } else {
+                           labels.join("<br>")
+                       }]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("file", path);
+        let _ = entry.insert("term", String::from(file.get_term()));
+        let _ = entry.insert("location", String::from(file.get_location()));
+        let _ = entry.insert("backups", labels.join(", "));
+        data.push(entry);
+    }
+
+    let mut section = ReportSection::new("backups", "Backups", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["File", "Sensitive data", "Used in", "Backed up in"], &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The backup rules were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Backup rules analyzed.");
+    }
+}
+
+/// Adds a finding for a sensitive file included in the cloud backups
+///
+/// The finding is located in the rules file that includes it, or in the `<application>` element
+/// of the manifest if there are no rules.
+fn add_finding(file: &SensitiveFile,
+               backups: &[(BackupKind, Option<&str>)],
+               manifest: &Manifest,
+               config: &Config,
+               results: &mut Results) {
+    let criticity = Criticity::Medium;
+    let labels: Vec<_> = backups.iter().map(|&(kind, _)| kind.get_label()).collect();
+    let description = format!("The {} {} file, used in {}, stores data that looks sensitive \
+                               (\"{}\"), and the backup rules of the application don't exclude \
+                               it from the following backups: {}. Backups are stored outside \
+                               the device, so the data can be restored in another device or \
+                               accessed if the backup account is compromised. Exclude the \
+                               file in the backup rules, or disable backups with \
+                               android:allowBackup=\"false\".",
+                              file.get_domain(),
+                              file.get_name(),
+                              file.get_location(),
+                              file.get_term(),
+                              labels.join(", "));
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let rules_file = backups.iter().filter_map(|&(_, f)| f).next();
+    let (location, line, code) = match rules_file {
+        Some(f) => (String::from(f), None, None),
+        None => {
+            let line = get_line(manifest.get_code(), "<application").ok();
+            (String::from("AndroidManifest.xml"),
+             line,
+             line.map(|l| get_code(manifest.get_code(), l, l)))
+        }
+    };
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from("Sensitive data in backups"),
+                                      description,
+                                      Some(location),
+                                      line,
+                                      line,
+                                      code);
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-530")],
+                                        vec![String::from("MASVS-STORAGE-2")],
+                                        vec![String::from("M9")],
+                                        Vec::new(),
+                                        Some(String::from("Exclude the files with sensitive \
+                                                           data in the fullBackupContent and \
+                                                           dataExtractionRules rules."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackupKind, BackupRule, parse_backup_rules, is_backed_up};
+
+    #[test]
+    fn it_parse_backup_rules() {
+        let code = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <data-extraction-rules>\n\
+                    <cloud-backup disableIfNoEncryptionCapabilities=\"true\">\n\
+                    <exclude domain=\"sharedpref\" path=\"session.xml\"/>\n\
+                    </cloud-backup>\n\
+                    <device-transfer>\n\
+                    <include domain=\"root\" path=\".\"/>\n\
+                    </device-transfer>\n\
+                    </data-extraction-rules>";
+        assert_eq!(parse_backup_rules(code).unwrap(),
+                   vec![BackupRule {
+                            kind: BackupKind::Cloud,
+                            include: false,
+                            domain: String::from("sharedpref"),
+                            path: String::from("session.xml"),
+                        },
+                        BackupRule {
+                            kind: BackupKind::DeviceTransfer,
+                            include: true,
+                            domain: String::from("root"),
+                            path: String::from("."),
+                        }]);
+        assert!(parse_backup_rules("<full-backup-content><include path=\"a\"/>\
+                                    </full-backup-content>")
+            .is_err());
+        assert!(parse_backup_rules("<include domain=\"file\" path=\"a\"/>").is_err());
+    }
+
+    #[test]
+    fn it_is_backed_up() {
+        let code = "<full-backup-content>\n\
+                    <include domain=\"sharedpref\" path=\".\"/>\n\
+                    <include domain=\"root\" path=\"databases/\"/>\n\
+                    <exclude domain=\"sharedpref\" path=\"session.xml\"/>\n\
+                    </full-backup-content>";
+        let rules = parse_backup_rules(code).unwrap();
+        let kind = BackupKind::LegacyCloud;
+        assert!(!is_backed_up(&rules, kind, "sharedpref", "session.xml"));
+        assert!(is_backed_up(&rules, kind, "sharedpref", "api_keys.xml"));
+        assert!(is_backed_up(&rules, kind, "database", "accounts.db"));
+        assert!(!is_backed_up(&rules, kind, "file", "token.txt"));
+        assert!(is_backed_up(&rules, BackupKind::Cloud, "file", "token.txt"));
+        assert!(is_backed_up(&[], kind, "sharedpref", "session.xml"));
+    }
+}
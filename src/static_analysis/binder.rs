@@ -0,0 +1,400 @@
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, Component, ComponentKind};
+use super::intents::is_unprotected;
+use super::java::{get_java_files, get_class_name};
+use super::read_text_file;
+
+/// Order of the Binder interfaces section in the report, after the components
+const SECTION_ORDER: u32 = 52;
+
+/// Name of the interface of the services that receive messages through a `Messenger`
+const MESSENGER: &'static str = "Messenger";
+
+/// Calls that check the identity or the permissions of the caller of a Binder method
+const CALLER_CHECKS: [&'static str; 7] = ["checkCallingPermission",
+                                          "checkCallingOrSelfPermission",
+                                          "enforceCallingPermission",
+                                          "enforceCallingOrSelfPermission",
+                                          "getCallingUid",
+                                          "getCallingPid",
+                                          "checkSignatures"];
+
+/// Method of a Binder interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BinderMethod {
+    name: String,
+    parameters: String,
+}
+
+impl BinderMethod {
+    /// Checks if the method receives data from the caller
+    fn accepts_input(&self) -> bool {
+        !self.parameters.trim().is_empty()
+    }
+
+    fn get_signature(&self) -> String {
+        format!("{}({})", self.name, self.parameters.trim())
+    }
+}
+
+/// Regular expressions to find the AIDL interfaces and their implementations
+struct Scanner {
+    interface: Regex,
+    method: Regex,
+    stub_class: Regex,
+    stub_reference: Regex,
+}
+
+impl Scanner {
+    fn new() -> Scanner {
+        Scanner {
+            interface: Regex::new(r"\binterface\s+(\w+)\s+extends\s+(?:android\.os\.)?IInterface\b")
+                .unwrap(),
+            method: Regex::new(concat!(r"\b(\w+)\s*\(([^()]*)\)\s*",
+                                       r"throws\s+(?:android\.os\.)?RemoteException\s*;"))
+                .unwrap(),
+            stub_class: Regex::new(r"\bclass\s+(\w+)\s+extends\s+(?:[\w.]+\.)?(\w+)\.Stub\b")
+                .unwrap(),
+            stub_reference: Regex::new(r"\b(\w+)\.Stub\b(\s*\.\s*asInterface)?").unwrap(),
+        }
+    }
+
+    /// Gets the name of the AIDL interface declared in the code, if any
+    fn find_interface<'c>(&self, code: &'c str) -> Option<&'c str> {
+        self.interface.captures(code).and_then(|caps| caps.at(1))
+    }
+
+    /// Finds the methods declared by an AIDL interface
+    ///
+    /// The declarations end with a semicolon, unlike the implementations of the proxy class
+    /// generated in the same file.
+    fn find_methods(&self, code: &str) -> Vec<BinderMethod> {
+        let mut methods: Vec<BinderMethod> = Vec::new();
+        for caps in self.method.captures_iter(code) {
+            let method = BinderMethod {
+                name: String::from(caps.at(1).unwrap()),
+                parameters: String::from(caps.at(2).unwrap()),
+            };
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+        methods
+    }
+
+    /// Finds the classes implementing the stub of an AIDL interface, with the name of the
+    /// interface
+    fn find_stub_classes<'c>(&self, code: &'c str) -> Vec<(&'c str, &'c str)> {
+        self.stub_class
+            .captures_iter(code)
+            .map(|caps| (caps.at(1).unwrap(), caps.at(2).unwrap()))
+            .collect()
+    }
+
+    /// Finds the interfaces whose stubs are implemented or returned in the code, without the
+    /// ones only used as clients with `asInterface()`
+    fn find_stub_references<'c>(&self, code: &'c str) -> BTreeSet<&'c str> {
+        self.stub_reference
+            .captures_iter(code)
+            .filter(|caps| caps.at(2).is_none())
+            .map(|caps| caps.at(1).unwrap())
+            .collect()
+    }
+}
+
+/// Checks if the code checks the identity or the permissions of the caller
+fn has_caller_checks(code: &str) -> bool {
+    CALLER_CHECKS.iter().any(|check| code.contains(check))
+}
+
+/// Binder interface exposed by an exported service
+struct ExposedInterface<'s> {
+    service: &'s Component,
+    protection: Option<&'s str>,
+    unprotected: bool,
+    interface: String,
+    methods: Vec<BinderMethod>,
+    caller_checks: bool,
+}
+
+/// Enumerates the Binder interfaces of the exported services: the AIDL interfaces whose stubs
+/// they return, and `Messenger` handlers, with the methods that receive data from the callers
+///
+/// Services that other applications can bind to without a permission, exposing methods with
+/// parameters and without checking the caller in the code, are reported.
+pub fn binder_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    let services: Vec<_> = manifest.get_components()
+        .iter()
+        .filter(|c| c.get_kind() == ComponentKind::Service && c.is_exported() && c.is_enabled())
+        .collect();
+    if services.is_empty() {
+        return;
+    }
+    if config.is_verbose() {
+        println!("Enumerating the Binder interfaces of the exported services.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the Binder \
+                                   interface analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    // AIDL interfaces by name, with their methods, and the classes implementing their stubs,
+    // with the interface and whether they check the caller
+    let scanner = Scanner::new();
+    let mut interfaces: BTreeMap<String, Vec<BinderMethod>> = BTreeMap::new();
+    let mut stub_classes: BTreeMap<String, (String, bool)> = BTreeMap::new();
+    let mut service_code: BTreeMap<String, (PathBuf, String)> = BTreeMap::new();
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if code.contains("IInterface") {
+            if let Some(interface) = scanner.find_interface(&code) {
+                let _ = interfaces.insert(String::from(interface), scanner.find_methods(&code));
+            }
+        }
+        if code.contains(".Stub") {
+            for (stub_class, interface) in scanner.find_stub_classes(&code) {
+                let _ = stub_classes.insert(String::from(stub_class),
+                                            (String::from(interface), has_caller_checks(&code)));
+            }
+        }
+        if services.iter().any(|s| s.get_name() == class) {
+            let _ = service_code.insert(class, (relative, code));
+        }
+    }
+
+    let mut exposed = Vec::new();
+    for service in &services {
+        let protection = manifest.get_component_protection(service);
+        let unprotected = is_unprotected(manifest, service);
+        let code = match service_code.get(service.get_name()) {
+            Some(&(_, ref code)) => code.as_str(),
+            None => "",
+        };
+
+        // Stubs implemented or returned by the service itself, and classes implementing stubs
+        // that the service uses
+        let mut found: BTreeMap<String, bool> = scanner.find_stub_references(code)
+            .into_iter()
+            .map(|interface| (String::from(interface), has_caller_checks(code)))
+            .collect();
+        for (stub_class, &(ref interface, checks)) in &stub_classes {
+            if code.contains(stub_class.as_str()) {
+                let entry = found.entry(interface.clone()).or_insert(false);
+                *entry = *entry || checks || has_caller_checks(code);
+            }
+        }
+        if code.contains("new Messenger(") {
+            let _ = found.insert(String::from(MESSENGER), has_caller_checks(code));
+        }
+
+        for (interface, caller_checks) in found {
+            let methods = if interface == MESSENGER {
+                vec![BinderMethod {
+                         name: String::from("handleMessage"),
+                         parameters: String::from("Message msg"),
+                     }]
+            } else {
+                match interfaces.get(&interface) {
+                    Some(m) => m.clone(),
+                    // Stubs of the platform or of libraries without code
+                    None => continue,
+                }
+            };
+            exposed.push(ExposedInterface {
+                service: service,
+                protection: protection,
+                unprotected: unprotected,
+                interface: interface,
+                methods: methods,
+                caller_checks: caller_checks,
+            });
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for interface in &exposed {
+        let inputs: Vec<_> = interface.methods
+            .iter()
+            .filter(|m| m.accepts_input())
+            .map(|m| m.get_signature())
+            .collect();
+        if interface.unprotected && !interface.caller_checks && !inputs.is_empty() {
+            let file = service_code.get(interface.service.get_name()).map(|&(ref f, _)| f);
+            add_finding(interface, &inputs, file, manifest, config, results);
+        }
+
+        rows.push(vec![Results::html_escape(interface.service.get_name()),
+                       Results::html_escape(interface.protection.unwrap_or("")),
+                       Results::html_escape(&interface.interface),
+                       inputs.iter()
+                           .map(|m| format!("<code>{}</code>", Results::html_escape(m)))
+                           .collect::<Vec<_>>()
+                           .join("<br>"),
+                       String::from(Results::html_yes_no(interface.caller_checks))]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("service", String::from(interface.service.get_name()));
+        let _ = entry.insert("permission",
+                             String::from(interface.protection.unwrap_or("")));
+        let _ = entry.insert("interface", interface.interface.clone());
+        let _ = entry.insert("methods", inputs.join(", "));
+        let _ = entry.insert("caller_checks", interface.caller_checks.to_string());
+        data.push(entry);
+    }
+
+    let mut section = ReportSection::new("binder", "Binder interfaces", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["Service", "Permission", "Interface", "Methods with input",
+                             "Caller checks"],
+                           &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The Binder interfaces were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Binder interfaces analyzed.");
+    }
+}
+
+/// Adds a finding for a Binder interface that any application can call with its own data
+fn add_finding(interface: &ExposedInterface,
+               inputs: &[String],
+               file: Option<&PathBuf>,
+               manifest: &Manifest,
+               config: &Config,
+               results: &mut Results) {
+    let criticity = Criticity::Medium;
+    let description = format!("The exported service {} can be bound by any application, \
+                               since it's not protected by a signature permission, and \
+                               exposes the {} interface, whose methods receive data from the \
+                               caller: {}. The service does not \
+                               check the identity or the permissions of the caller, so any \
+                               application can call them. Protect the service with a \
+                               signature permission, or check the caller with \
+                               checkCallingPermission() or getCallingUid() in each method.",
+                              interface.service.get_name(),
+                              interface.interface,
+                              inputs.join(", "));
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    // Without the code of the service, the finding is located in its declaration
+    let (location, line, code) = match file {
+        Some(f) => (f.clone(), None, None),
+        None => {
+            let line = interface.service.get_line();
+            (PathBuf::from("AndroidManifest.xml"),
+             line,
+             line.map(|l| get_code(manifest.get_code(), l, l)))
+        }
+    };
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from("Unprotected Binder interface"),
+                                      description,
+                                      Some(location.as_path() as &Path),
+                                      line,
+                                      line,
+                                      code);
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-926")],
+                                        vec![String::from("MASVS-PLATFORM-1")],
+                                        vec![String::from("M1")],
+                                        Vec::new(),
+                                        Some(String::from("Protect the service with a \
+                                                           signature permission, or check the \
+                                                           caller in each Binder method."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scanner, BinderMethod, has_caller_checks};
+
+    #[test]
+    fn it_find_interface_methods() {
+        let scanner = Scanner::new();
+        let code = "public interface IAccountService extends IInterface {\n\
+                    public static abstract class Stub extends Binder implements IAccountService {\n\
+                    private static class Proxy implements IAccountService {\n\
+                    public String getToken(String account) throws RemoteException {\n\
+                    return null;\n\
+                    }\n\
+                    }\n\
+                    }\n\
+                    String getToken(String paramString) throws RemoteException;\n\
+                    int getVersion() throws RemoteException;\n\
+                    }";
+        assert_eq!(scanner.find_interface(code), Some("IAccountService"));
+        let methods = scanner.find_methods(code);
+        assert_eq!(methods,
+                   vec![BinderMethod {
+                            name: String::from("getToken"),
+                            parameters: String::from("String paramString"),
+                        },
+                        BinderMethod {
+                            name: String::from("getVersion"),
+                            parameters: String::new(),
+                        }]);
+        assert!(methods[0].accepts_input());
+        assert!(!methods[1].accepts_input());
+        assert_eq!(methods[0].get_signature(), "getToken(String paramString)");
+        assert_eq!(scanner.find_interface("public interface Callback {}"), None);
+    }
+
+    #[test]
+    fn it_find_stubs() {
+        let scanner = Scanner::new();
+        let code = "class AccountBinder extends com.example.IAccountService.Stub {\n\
+                    }\n\
+                    public IBinder onBind(Intent intent) {\n\
+                    return new ISyncService.Stub() {};\n\
+                    }\n\
+                    void connect(IBinder binder) {\n\
+                    IRemote remote = IRemote.Stub.asInterface(binder);\n\
+                    }";
+        assert_eq!(scanner.find_stub_classes(code),
+                   vec![("AccountBinder", "IAccountService")]);
+        let references: Vec<_> = scanner.find_stub_references(code).into_iter().collect();
+        assert_eq!(references, vec!["IAccountService", "ISyncService"]);
+        assert!(!has_caller_checks(code));
+        assert!(has_caller_checks("if (Binder.getCallingUid() != Process.myUid()) return;"));
+    }
+}
@@ -1,32 +1,144 @@
 extern crate colored;
 
 use std::fs;
-use std::process::{Command, exit};
+use std::process::Command;
 
 use colored::Colorize;
-use chrono::{Local, Datelike};
-
-use {Error, Config, Criticity, Result, print_error, print_vulnerability, print_warning};
-use results::{Results, Vulnerability};
-
-fn parse_month(month_str: &str) -> u32 {
-    let month_number = match month_str {
-        "Jan" => 1,
-        "Feb" => 2,
-        "Mar" => 3,
-        "Apr" => 4,
-        "May" => 5,
-        "Jun" => 6,
-        "Jul" => 7,
-        "Aug" => 8,
-        "Sep" => 9,
-        "Oct" => 10,
-        "Nov" => 11,
-        "Dec" => 12,
-        _ => 0,
-    };
-
-    month_number
+use chrono::{Local, NaiveDate, NaiveDateTime};
+
+use {Config, Criticity, Result, print_error, print_vulnerability, print_warning};
+use results::{Results, Vulnerability, IncompleteStage};
+
+/// Minimum size of RSA and DSA keys
+const MIN_KEY_SIZE: u32 = 2048;
+
+/// Minimum size of elliptic curve keys
+const MIN_EC_KEY_SIZE: u32 = 224;
+
+/// Elliptic curves that are considered weak, regardless of their size
+const WEAK_CURVES: [&'static str; 6] = ["secp112r1", "secp128r1", "secp160k1", "secp160r1",
+                                        "secp160r2", "prime192v1"];
+
+/// Names of the findings of the certificate analysis
+pub const CERTIFICATE_FINDINGS: [&'static str; 5] = ["Android Debug Certificate",
+                                                     "Weak signing key",
+                                                     "Weak certificate signature",
+                                                     "Expired certificate",
+                                                     "Certificate about to expire"];
+
+/// Signing certificate of the application, as printed by `openssl pkcs7 -print_certs -text`
+#[derive(Debug, Default)]
+struct Certificate {
+    issuer: String,
+    subject: String,
+    not_after: Option<NaiveDate>,
+    signature_algorithm: String,
+    key_algorithm: String,
+    key_size: Option<u32>,
+    curve: Option<String>,
+}
+
+impl Certificate {
+    /// Parses the text output of openssl for the first certificate
+    fn parse(text: &str) -> Certificate {
+        let mut certificate: Certificate = Default::default();
+        for line in text.lines().map(|l| l.trim()) {
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+            match key {
+                "Issuer" if certificate.issuer.is_empty() => {
+                    certificate.issuer = String::from(value)
+                }
+                "Subject" if certificate.subject.is_empty() => {
+                    certificate.subject = String::from(value)
+                }
+                "Not After" if certificate.not_after.is_none() => {
+                    certificate.not_after = parse_date(value)
+                }
+                "Signature Algorithm" if certificate.signature_algorithm.is_empty() => {
+                    certificate.signature_algorithm = String::from(value)
+                }
+                "Public Key Algorithm" if certificate.key_algorithm.is_empty() => {
+                    certificate.key_algorithm = String::from(value)
+                }
+                "Public-Key" | "RSA Public-Key" | "DSA Public-Key"
+                    if certificate.key_size.is_none() => {
+                    certificate.key_size = value.trim_left_matches('(')
+                        .split(' ')
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                }
+                "ASN1 OID" if certificate.curve.is_none() => {
+                    certificate.curve = Some(String::from(value))
+                }
+                _ => {}
+            }
+        }
+        certificate
+    }
+
+    /// Checks if the certificate is the one generated by the Android SDK for debug builds
+    fn is_debug(&self) -> bool {
+        self.issuer.contains("Android Debug") || self.subject.contains("CN=Android Debug")
+    }
+
+    /// Checks if the key of the certificate is an elliptic curve key
+    fn is_ec(&self) -> bool {
+        self.key_algorithm == "id-ecPublicKey"
+    }
+
+    /// Gets the reason why the key of the certificate is weak, if it is
+    fn get_weak_key(&self) -> Option<String> {
+        if self.is_ec() {
+            match (self.curve.as_ref(), self.key_size) {
+                (Some(curve), _) if WEAK_CURVES.contains(&curve.as_str()) => {
+                    Some(format!("the weak {} elliptic curve", curve))
+                }
+                (curve, Some(size)) if size < MIN_EC_KEY_SIZE => {
+                    Some(format!("a {} bit elliptic curve key{}",
+                                 size,
+                                 match curve {
+                                     Some(c) => format!(" ({})", c),
+                                     None => String::new(),
+                                 }))
+                }
+                _ => None,
+            }
+        } else {
+            match self.key_size {
+                Some(size) if size < MIN_KEY_SIZE => {
+                    Some(format!("a {} bit {} key",
+                                 size,
+                                 if self.key_algorithm.starts_with("dsa") {
+                                     "DSA"
+                                 } else {
+                                     "RSA"
+                                 }))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Gets the weak digest used for the signature of the certificate, if any
+    fn get_weak_digest(&self) -> Option<&'static str> {
+        let algorithm = self.signature_algorithm.to_lowercase();
+        if algorithm.contains("md5") {
+            Some("MD5")
+        } else if algorithm.contains("sha1") {
+            Some("SHA-1")
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses the dates printed by openssl, such as `Jan  1 00:00:00 2045 GMT`
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    let date = date.split_whitespace().collect::<Vec<_>>().join(" ");
+    NaiveDateTime::parse_from_str(&date, "%b %d %H:%M:%S %Y GMT").ok().map(|d| d.date())
 }
 
 pub fn certificate_analysis(config: &Config, results: &mut Results) -> Result<()> {
@@ -63,7 +175,8 @@ pub fn certificate_analysis(config: &Config, results: &mut Results) -> Result<()
         match f.path().extension() {
             None => {}
             Some(e) => {
-                if e.to_string_lossy() == "RSA" || e.to_string_lossy() == "DSA" {
+                if e.to_string_lossy() == "RSA" || e.to_string_lossy() == "DSA" ||
+                   e.to_string_lossy() == "EC" {
                     is_cert = true;
                 }
             }
@@ -81,20 +194,29 @@ pub fn certificate_analysis(config: &Config, results: &mut Results) -> Result<()
                 .arg("-text")
                 .output();
 
-            if output.is_err() {
-                print_error(format!("There was an error when executing the openssl command to \
-                                     check the certificate: {}",
-                                    output.err().unwrap()),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
-            }
-
-            let output = output.unwrap();
+            // The rest of the certificates, and of the analysis, can still be checked
+            let output = match output {
+                Ok(o) => o,
+                Err(e) => {
+                    let reason = format!("There was an error when executing the openssl command \
+                                          to check the certificate {}: {}",
+                                         path_file,
+                                         e);
+                    print_error(reason.as_str(), config.is_verbose());
+                    results.add_incomplete_stage(IncompleteStage::new("Certificate analysis",
+                                                                      reason));
+                    continue;
+                }
+            };
             if !output.status.success() {
-                print_error(format!("The openssl command returned an error. More info: {}",
-                                    String::from_utf8_lossy(&output.stderr[..])),
-                            config.is_verbose());
-                exit(Error::Unknown.into());
+                let reason = format!("The openssl command returned an error for the certificate \
+                                      {}.",
+                                     path_file);
+                print_error(reason.as_str(), config.is_verbose());
+                let details = String::from_utf8_lossy(&output.stderr[..]).into_owned();
+                results.add_incomplete_stage(IncompleteStage::new("Certificate analysis", reason)
+                    .with_details(details.trim()));
+                continue;
             };
 
             let cmd = output.stdout;
@@ -105,80 +227,8 @@ pub fn certificate_analysis(config: &Config, results: &mut Results) -> Result<()
                 println!("{}", String::from_utf8_lossy(&cmd));
             }
 
-            let mut issuer = String::new();
-            let mut subject = String::new();
-            let mut after = String::new();
-            for line in String::from_utf8_lossy(&cmd).lines() {
-                if line.contains("Issuer:") {
-                    issuer = String::from(line.clone());
-                }
-                if line.contains("Subject:") {
-                    subject = String::from(line.clone());
-                }
-                if line.contains("Not After :") {
-                    after = String::from(line.clone());
-                }
-            }
-
-            let mut issuer = issuer.split(": ");
-            let mut subject = subject.split(": ");
-            let mut after = after.split(": ");
-
-            if issuer.nth(1).unwrap().contains("Android Debug") {
-                let criticity = Criticity::Critical;
-                let description = "The application is signed with the Android Debug Certificate. \
-                                   This certificate should never be used for publishing an app.";
-
-                let vuln = Vulnerability::new(criticity,
-                                              "Android Debug Certificate",
-                                              description,
-                                              None as Option<&str>,
-                                              None,
-                                              None,
-                                              None);
-                results.add_vulnerability(vuln);
-
-                if config.is_verbose() {
-                    print_vulnerability(description, criticity);
-                }
-            }
-            if issuer.nth(1) == subject.nth(1) {
-                // TODO: This means it is self signed. Should we do something?
-            }
-
-            let now = Local::now();
-            let year = now.year();
-            let month = now.month();
-            let day = now.day();
-
-            let after = after.nth(1).unwrap();
-            let cert_year = after[16..20].parse::<i32>().unwrap();
-            let cert_month = parse_month(&after[0..3]);
-            let cert_day = match after[4..6].parse::<u32>() { //if day<10 parse 1 number
-                Ok(n) => n,
-                Err(_) => after[5..6].parse::<u32>().unwrap(),
-            };
-
-            if year > cert_year || (year == cert_year && month > cert_month) ||
-               (year == cert_year && month == cert_month && day > cert_day) {
-                let criticity = Criticity::High;
-                let description = "The certificate of the application has expired. You should not \
-                                   use applications with expired certificates since the app is \
-                                   not secure anymore.";
-
-                let vuln = Vulnerability::new(criticity,
-                                              "Expired certificate",
-                                              description,
-                                              None as Option<&str>,
-                                              None,
-                                              None,
-                                              None);
-                results.add_vulnerability(vuln);
-
-                if config.is_verbose() {
-                    print_vulnerability(description, criticity);
-                }
-            }
+            let certificate = Certificate::parse(&String::from_utf8_lossy(&cmd));
+            check_certificate(&certificate, config, results);
         }
     }
 
@@ -191,3 +241,161 @@ pub fn certificate_analysis(config: &Config, results: &mut Results) -> Result<()
     }
     Ok(())
 }
+
+/// Checks the signing certificate for debug keys, weak keys and digests, and its expiration
+fn check_certificate(certificate: &Certificate, config: &Config, results: &mut Results) {
+    if certificate.is_debug() {
+        add_finding(Criticity::Critical,
+                    "Android Debug Certificate",
+                    String::from("The application is signed with the Android Debug \
+                                  Certificate. This certificate should never be used for \
+                                  publishing an app."),
+                    config,
+                    results);
+    }
+
+    if let Some(key) = certificate.get_weak_key() {
+        add_finding(Criticity::High,
+                    "Weak signing key",
+                    format!("The application is signed with {}. The key could be broken, and \
+                             an attacker could sign updates of the application with it. Use RSA \
+                             keys of at least {} bits, or elliptic curve keys of at least {} \
+                             bits.",
+                            key,
+                            MIN_KEY_SIZE,
+                            MIN_EC_KEY_SIZE),
+                    config,
+                    results);
+    }
+
+    if let Some(digest) = certificate.get_weak_digest() {
+        add_finding(if digest == "MD5" {
+                        Criticity::High
+                    } else {
+                        Criticity::Medium
+                    },
+                    "Weak certificate signature",
+                    format!("The certificate of the application is signed with {} ({}). The \
+                             digest is vulnerable to collisions, so a certificate with the same \
+                             signature could be forged.",
+                            digest,
+                            certificate.signature_algorithm),
+                    config,
+                    results);
+    }
+
+    if let Some(not_after) = certificate.not_after {
+        let today = Local::today().naive_local();
+        let window = config.get_certificate_config().get_expiry_window();
+        let days_left = (not_after - today).num_days();
+        if days_left < 0 {
+            add_finding(Criticity::High,
+                        "Expired certificate",
+                        String::from("The certificate of the application has expired. You \
+                                      should not use applications with expired certificates \
+                                      since the app is not secure anymore."),
+                        config,
+                        results);
+        } else if days_left < window as i64 {
+            add_finding(Criticity::Medium,
+                        "Certificate about to expire",
+                        format!("The certificate of the application expires on {}, in {} \
+                                 days. Once it expires, the application can't be updated \
+                                 with the same key.",
+                                not_after,
+                                days_left),
+                        config,
+                        results);
+        }
+    }
+}
+
+/// Adds a finding for the signing certificate
+fn add_finding(criticity: Criticity,
+               name: &str,
+               description: String,
+               config: &Config,
+               results: &mut Results) {
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(name),
+                                  description,
+                                  None as Option<&str>,
+                                  None,
+                                  None,
+                                  None);
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::{Certificate, parse_date};
+
+    const DEBUG_CERTIFICATE: &'static str = "Certificate:
+    Data:
+        Version: 1 (0x0)
+        Serial Number: 1 (0x1)
+    Signature Algorithm: sha1WithRSAEncryption
+        Issuer: C=US, O=Android, CN=Android Debug
+        Validity
+            Not Before: Dec 31 08:35:04 2013 GMT
+            Not After : Dec 24 08:35:04 2043 GMT
+        Subject: C=US, O=Android, CN=Android Debug
+        Subject Public Key Info:
+            Public Key Algorithm: rsaEncryption
+                Public-Key: (1024 bit)
+                Modulus:
+                    00:c4:2e:ae:39:8b:8f:ae:e4:bb:84:9a:03:4f:95:
+                Exponent: 65537 (0x10001)
+    Signature Algorithm: sha1WithRSAEncryption
+         3a:4d:03:78:9a:2e:bb:2c:4d:0b:ef:42:20:bc:5b:4a:ad:7c:
+";
+
+    const EC_CERTIFICATE: &'static str = "Certificate:
+    Data:
+    Signature Algorithm: ecdsa-with-SHA256
+        Issuer: CN=Example
+        Validity
+            Not Before: Mar  1 10:00:00 2017 GMT
+            Not After : Mar  1 10:00:00 2042 GMT
+        Subject: CN=Example
+        Subject Public Key Info:
+            Public Key Algorithm: id-ecPublicKey
+                Public-Key: (192 bit)
+                ASN1 OID: prime192v1
+";
+
+    #[test]
+    fn it_parse_certificate() {
+        let certificate = Certificate::parse(DEBUG_CERTIFICATE);
+        assert_eq!(certificate.issuer, "C=US, O=Android, CN=Android Debug");
+        assert_eq!(certificate.not_after, Some(NaiveDate::from_ymd(2043, 12, 24)));
+        assert_eq!(certificate.signature_algorithm, "sha1WithRSAEncryption");
+        assert_eq!(certificate.key_algorithm, "rsaEncryption");
+        assert_eq!(certificate.key_size, Some(1024));
+        assert!(certificate.is_debug());
+        assert_eq!(certificate.get_weak_key(), Some(String::from("a 1024 bit RSA key")));
+        assert_eq!(certificate.get_weak_digest(), Some("SHA-1"));
+
+        let certificate = Certificate::parse(EC_CERTIFICATE);
+        assert!(!certificate.is_debug());
+        assert!(certificate.is_ec());
+        assert_eq!(certificate.curve, Some(String::from("prime192v1")));
+        assert_eq!(certificate.get_weak_key(),
+                   Some(String::from("the weak prime192v1 elliptic curve")));
+        assert_eq!(certificate.get_weak_digest(), None);
+    }
+
+    #[test]
+    fn it_parse_date() {
+        assert_eq!(parse_date("Mar  1 10:00:00 2042 GMT"),
+                   Some(NaiveDate::from_ymd(2042, 3, 1)));
+        assert_eq!(parse_date("Dec 24 08:35:04 2043 GMT"),
+                   Some(NaiveDate::from_ymd(2043, 12, 24)));
+        assert_eq!(parse_date("never"), None);
+    }
+}
@@ -0,0 +1,396 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use download::fetch_url;
+use super::code::get_line_for;
+use super::{add_files_to_vec, read_text_file};
+
+/// Order of the cloud services section in the report
+const SECTION_ORDER: u32 = 220;
+
+/// Maximum time in seconds for each of the online checks
+const PROBE_TIMEOUT: u32 = 10;
+
+/// Names of the string resources generated from the `google-services.json` file
+const FIREBASE_METADATA: [&'static str; 7] = ["google_app_id",
+                                              "gcm_defaultSenderId",
+                                              "project_id",
+                                              "google_api_key",
+                                              "google_crash_reporting_api_key",
+                                              "firebase_database_url",
+                                              "google_storage_bucket"];
+
+/// Cloud service referenced by the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CloudService {
+    /// Firebase realtime database
+    FirebaseDatabase,
+    /// Cloud Firestore project
+    Firestore,
+    /// Firebase or Google Cloud storage bucket
+    FirebaseStorage,
+    /// Amazon S3 bucket
+    S3Bucket,
+}
+
+impl CloudService {
+    /// Gets the label of the service shown in the report
+    fn get_label(&self) -> &'static str {
+        match *self {
+            CloudService::FirebaseDatabase => "Firebase database",
+            CloudService::Firestore => "Firestore project",
+            CloudService::FirebaseStorage => "Firebase storage bucket",
+            CloudService::S3Bucket => "S3 bucket",
+        }
+    }
+
+    /// Gets the name of the vulnerability reported when the resource can be read by anyone
+    fn get_open_label(&self) -> &'static str {
+        match *self {
+            CloudService::FirebaseDatabase => "Firebase database readable by anyone",
+            CloudService::Firestore => "Firestore database readable by anyone",
+            CloudService::FirebaseStorage => "Firebase storage bucket listable by anyone",
+            CloudService::S3Bucket => "S3 bucket listable by anyone",
+        }
+    }
+
+    /// Gets the URL requested without credentials to check if the resource is open, if the
+    /// service can be checked
+    fn get_probe_url(&self, name: &str) -> Option<String> {
+        match *self {
+            CloudService::FirebaseDatabase => Some(format!("{}/.json?shallow=true", name)),
+            CloudService::Firestore => None,
+            CloudService::FirebaseStorage => {
+                Some(format!("https://firebasestorage.googleapis.com/v0/b/{}/o?maxResults=1",
+                             name))
+            }
+            CloudService::S3Bucket => {
+                Some(format!("https://{}.s3.amazonaws.com/?max-keys=1", name))
+            }
+        }
+    }
+
+    /// Gets the exposure of the resource from the response to the probe request
+    fn get_exposure(&self, status: u32, body: &str) -> Exposure {
+        match status {
+            200 if *self == CloudService::S3Bucket && !body.contains("<ListBucketResult") => {
+                Exposure::Unknown
+            }
+            200 => Exposure::Open,
+            401 | 403 => Exposure::Protected,
+            _ => Exposure::Unknown,
+        }
+    }
+}
+
+/// Result of the online check of a cloud resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exposure {
+    /// The resource was not checked
+    NotChecked,
+    /// The resource can be read without authentication
+    Open,
+    /// The resource requires authentication
+    Protected,
+    /// The resource does not exist or the response could not be interpreted
+    Unknown,
+}
+
+impl Exposure {
+    /// Gets the label of the exposure shown in the report
+    fn get_label(&self) -> &'static str {
+        match *self {
+            Exposure::NotChecked => "Not checked",
+            Exposure::Open => "Readable by anyone",
+            Exposure::Protected => "Protected",
+            Exposure::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Reference to a cloud resource found in the application
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CloudReference {
+    service: CloudService,
+    name: String,
+    line: usize,
+    code: String,
+}
+
+/// Gets the patterns of the cloud resources, capturing the name of the resource
+fn get_patterns() -> Vec<(CloudService, Regex)> {
+    vec![(CloudService::FirebaseDatabase,
+          Regex::new(concat!(r"(https://[a-z0-9][a-z0-9-]*\.",
+                             r"(?:firebaseio\.com|[a-z0-9-]+\.firebasedatabase\.app))"))
+              .unwrap()),
+         (CloudService::Firestore,
+          Regex::new(r"firestore\.googleapis\.com/v1(?:beta1)?/projects/([a-z0-9][a-z0-9-]*)")
+              .unwrap()),
+         (CloudService::FirebaseStorage,
+          Regex::new(concat!(r#"(?:gs://|firebasestorage\.googleapis\.com/v0/b/|[>"'])"#,
+                             r"([a-z0-9][a-z0-9._-]*\.(?:appspot\.com|firebasestorage\.app))"))
+              .unwrap()),
+         (CloudService::S3Bucket,
+          Regex::new(r"([a-z0-9][a-z0-9.-]{1,61}[a-z0-9])\.s3(?:[.-][a-z0-9-]+)?\.amazonaws\.com")
+              .unwrap()),
+         (CloudService::S3Bucket,
+          Regex::new(r"s3://([a-z0-9][a-z0-9.-]{1,61}[a-z0-9])").unwrap())]
+}
+
+/// Finds the references to cloud resources in the given code
+fn find_cloud_references(code: &str,
+                         patterns: &[(CloudService, Regex)])
+                         -> Vec<CloudReference> {
+    let mut references = Vec::new();
+    for &(service, ref regex) in patterns {
+        for caps in regex.captures_iter(code) {
+            let (start, _) = caps.pos(1).unwrap();
+            let line = get_line_for(start, code);
+            references.push(CloudReference {
+                service: service,
+                name: String::from(caps.at(1).unwrap()),
+                line: line,
+                code: get_code(code, line, line),
+            });
+        }
+    }
+    references.sort_by_key(|r| r.line);
+    references
+}
+
+/// Finds the Firebase configuration generated from the `google-services.json` file in the given
+/// string resources
+fn find_firebase_metadata(code: &str) -> Vec<(String, String)> {
+    let regex = Regex::new(r#"<string name="([A-Za-z_]+)"[^>]*>([^<]*)</string>"#).unwrap();
+    regex.captures_iter(code)
+        .filter(|caps| FIREBASE_METADATA.contains(&caps.at(1).unwrap()))
+        .map(|caps| {
+            (String::from(caps.at(1).unwrap()), String::from(caps.at(2).unwrap().trim()))
+        })
+        .filter(|&(_, ref value)| !value.is_empty())
+        .collect()
+}
+
+/// Finds the Firebase and cloud storage resources referenced by the application and, if online
+/// checks are enabled, checks if they can be read without authentication
+pub fn cloud_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for the cloud services used by the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let mut files = Vec::new();
+    if let Err(e) = add_files_to_vec(&dist_folder, &dist_folder, &mut files) {
+        print_warning(format!("An error occurred when reading files for the cloud service \
+                               analysis, the results might be incomplete. Error: {}",
+                              e),
+                      config.is_verbose());
+    }
+
+    let patterns = get_patterns();
+    let mut references: BTreeMap<(CloudService, String), (PathBuf, CloudReference)> =
+        BTreeMap::new();
+    let mut metadata = BTreeMap::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        if relative.starts_with("res/values") {
+            for (name, value) in find_firebase_metadata(&code) {
+                let _ = metadata.entry(name).or_insert(value);
+            }
+        }
+        for reference in find_cloud_references(&code, &patterns) {
+            let key = (reference.service, reference.name.clone());
+            let _ = references.entry(key).or_insert((relative.clone(), reference));
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for (_, (path, reference)) in references {
+        let exposure = if config.is_online_checks() {
+            check_exposure(&reference, config)
+        } else {
+            Exposure::NotChecked
+        };
+        if exposure == Exposure::Open {
+            add_open_finding(&reference, &path, config, results);
+        }
+
+        let location = format!("{}:{}", path.display(), reference.line + 1);
+        rows.push(vec![String::from(reference.service.get_label()),
+                       format!("<code>{}</code>", Results::html_escape(&reference.name)),
+                       Results::html_escape(&location),
+                       String::from(exposure.get_label())]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("service", String::from(reference.service.get_label()));
+        let _ = entry.insert("resource", reference.name);
+        let _ = entry.insert("location", location);
+        let _ = entry.insert("exposure", String::from(exposure.get_label()));
+        data.push(entry);
+    }
+    for (name, value) in metadata {
+        rows.push(vec![String::from("Firebase configuration"),
+                       format!("<code>{}</code>", Results::html_escape(&name)),
+                       String::from("res/values"),
+                       Results::html_escape(&value)]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("service", String::from("Firebase configuration"));
+        let _ = entry.insert("resource", name);
+        let _ = entry.insert("value", value);
+        data.push(entry);
+    }
+
+    if !data.is_empty() {
+        let mut section = ReportSection::new("cloud", "Cloud services", SECTION_ORDER);
+        section.set_data(&data);
+        section.set_html_table(&["Service", "Resource", "Location", "Status"], &rows);
+        results.add_section(section);
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The cloud services were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Cloud services analyzed.");
+    }
+}
+
+/// Requests the resource without credentials to check if it can be read by anyone
+fn check_exposure(reference: &CloudReference, config: &Config) -> Exposure {
+    let url = match reference.service.get_probe_url(&reference.name) {
+        Some(u) => u,
+        None => return Exposure::NotChecked,
+    };
+    if config.is_verbose() {
+        println!("Checking if {} can be read by anyone.", url);
+    }
+    match fetch_url(&url, PROBE_TIMEOUT) {
+        Ok((status, body)) => reference.service.get_exposure(status, &body),
+        Err(_) => {
+            print_warning(format!("The {} {} could not be checked.",
+                                  reference.service.get_label(),
+                                  reference.name),
+                          config.is_verbose());
+            Exposure::Unknown
+        }
+    }
+}
+
+/// Adds the finding for a cloud resource that can be read without authentication
+fn add_open_finding(reference: &CloudReference,
+                    path: &Path,
+                    config: &Config,
+                    results: &mut Results) {
+    let description = format!("The {} {} referenced in {} can be read without any \
+                               authentication. Anyone extracting it from the application can \
+                               access all the data it stores. Access rules should be restricted \
+                               to authenticated users and to the data each of them owns.",
+                              reference.service.get_label(),
+                              reference.name,
+                              path.display());
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Critical);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Critical,
+                                      String::from(reference.service.get_open_label()),
+                                      description,
+                                      Some(path),
+                                      Some(reference.line),
+                                      Some(reference.line),
+                                      Some(reference.code.clone()));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-284")],
+                                        vec![String::from("MASVS-STORAGE-2")],
+                                        vec![String::from("M8")],
+                                        Vec::new(),
+                                        Some(String::from("Restrict the security rules of \
+                                                           the database or bucket so that \
+                                                           only authenticated users can read \
+                                                           their own data."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CloudService, Exposure, get_patterns, find_cloud_references,
+                find_firebase_metadata};
+
+    #[test]
+    fn it_find_cloud_references() {
+        let code = "<resources>\n\
+                    <string name=\"firebase_database_url\">https://example-app.firebaseio.com\
+                    </string>\n\
+                    <string name=\"google_storage_bucket\">example-app.appspot.com</string>\n\
+                    <string name=\"app_url\">https://example-app.appspot.com</string>\n\
+                    </resources>\n\
+                    String DB = \"https://other-default-rtdb.europe-west1.firebasedatabase.app\";\n\
+                    String IMAGES = \"https://media.s3.eu-west-1.amazonaws.com/img.png\";\n\
+                    String BACKUP = \"s3://example-backups/daily\";\n\
+                    String DOCS = \"https://firestore.googleapis.com/v1/projects/example-app/\";\n";
+        let references: Vec<_> = find_cloud_references(code, &get_patterns())
+            .into_iter()
+            .map(|r| (r.service, r.name, r.line))
+            .collect();
+        assert_eq!(references,
+                   vec![(CloudService::FirebaseDatabase,
+                         String::from("https://example-app.firebaseio.com"),
+                         1),
+                        (CloudService::FirebaseStorage, String::from("example-app.appspot.com"), 2),
+                        (CloudService::FirebaseDatabase,
+                         String::from("https://other-default-rtdb.europe-west1.\
+                                       firebasedatabase.app"),
+                         5),
+                        (CloudService::S3Bucket, String::from("media"), 6),
+                        (CloudService::S3Bucket, String::from("example-backups"), 7),
+                        (CloudService::Firestore, String::from("example-app"), 8)]);
+    }
+
+    #[test]
+    fn it_find_firebase_metadata() {
+        let code = "<resources>\n\
+                    <string name=\"app_name\">Example</string>\n\
+                    <string name=\"gcm_defaultSenderId\" translatable=\"false\">1234</string>\n\
+                    <string name=\"project_id\">example-app</string>\n\
+                    <string name=\"google_api_key\"></string>\n\
+                    </resources>";
+        assert_eq!(find_firebase_metadata(code),
+                   vec![(String::from("gcm_defaultSenderId"), String::from("1234")),
+                        (String::from("project_id"), String::from("example-app"))]);
+    }
+
+    #[test]
+    fn it_get_exposure() {
+        let database = CloudService::FirebaseDatabase;
+        assert_eq!(database.get_probe_url("https://example.firebaseio.com").unwrap(),
+                   "https://example.firebaseio.com/.json?shallow=true");
+        assert_eq!(database.get_exposure(200, "{\"users\":true}"), Exposure::Open);
+        assert_eq!(database.get_exposure(401, "{\"error\":\"Permission denied\"}"),
+                   Exposure::Protected);
+        assert_eq!(database.get_exposure(404, ""), Exposure::Unknown);
+
+        let bucket = CloudService::S3Bucket;
+        assert_eq!(bucket.get_exposure(200, "<ListBucketResult xmlns=\"\">"), Exposure::Open);
+        assert_eq!(bucket.get_exposure(200, "<html></html>"), Exposure::Unknown);
+        assert_eq!(bucket.get_exposure(403, "<Error>AccessDenied</Error>"), Exposure::Protected);
+        assert!(CloudService::Firestore.get_probe_url("example").is_none());
+    }
+}
@@ -8,20 +8,28 @@ use std::thread;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use std::slice::Iter;
+use std::collections::BTreeMap;
 
 use serde_json;
 use serde_json::value::Value;
-use regex::Regex;
+use regex::{Regex, quote};
+use yaml_rust::yaml::{Yaml, YamlLoader};
 use colored::Colorize;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
 
 use {Config, Result, Error, Criticity, print_warning, print_error, print_vulnerability, get_code};
-use results::{Results, Vulnerability, Benchmark};
+use results::{Results, Vulnerability, Benchmark, RuleMetadata, RuleProfile, CvssScore};
 use super::manifest::{Permission, Manifest};
+use super::java::{Token, tokenize};
+use super::structural::{CallPattern, ArgumentPattern};
+use super::cvss::{CvssVector, get_criticity};
+use super::kotlin::{add_kotlin_files, is_intrinsics_check};
 
 pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut Results) {
     let code_start = Instant::now();
     let rules = match load_rules(config) {
-        Ok(r) => r,
+        Ok(r) => apply_rules_config(r, config),
         Err(e) => {
             print_error(format!("An error occurred when loading code analysis rules. Error: {}",
                                 e),
@@ -30,17 +38,32 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
         }
     };
 
+    for rule in &rules {
+        for control in rule.get_metadata().get_masvs() {
+            results.add_checked_control(control.as_str());
+        }
+    }
     if config.is_bench() {
         results.add_benchmark(Benchmark::new("Rule loading", code_start.elapsed()));
     }
 
+    let filter = PathFilter::new(config.get_rules_config().get_include_paths(),
+                                 config.get_rules_config().get_exclude_paths());
     let mut files: Vec<DirEntry> = Vec::new();
-    if let Err(e) = add_files_to_vec("", &mut files, config) {
+    if let Err(e) = add_files_to_vec("", &mut files, &filter, config) {
         print_warning(format!("An error occurred when reading files for analysis, the results \
                                might be incomplete. Error: {}",
                               e),
                       config.is_verbose());
     }
+    if let Some(source_root) = config.get_source_root() {
+        if let Err(e) = add_kotlin_files(source_root, &mut files, &filter) {
+            print_warning(format!("An error occurred when reading the Kotlin files of the source \
+                                   root, they will not be analyzed. Error: {}",
+                                  e),
+                          config.is_verbose());
+        }
+    }
     let total_files = files.len();
 
     let rules = Arc::new(rules);
@@ -48,7 +71,14 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
     let found_vulns: Arc<Mutex<Vec<Vulnerability>>> = Arc::new(Mutex::new(Vec::new()));
     let files = Arc::new(Mutex::new(files));
     let verbose = config.is_verbose();
+    let profile_rules = config.is_profile_rules();
     let dist_folder = Arc::new(format!("{}/{}", config.get_dist_folder(), config.get_app_id()));
+    let source_root = Arc::new(config.get_source_root().map(PathBuf::from));
+    let era_sdk = if config.is_historical() {
+        results.get_app_target_sdk()
+    } else {
+        None
+    };
 
     if config.is_verbose() {
         println!("Starting analysis of the code with {} threads. {} files to go!",
@@ -64,8 +94,14 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
             let thread_rules = rules.clone();
             let thread_vulns = found_vulns.clone();
             let thread_dist_folder = dist_folder.clone();
+            let thread_source_root = source_root.clone();
 
             thread::spawn(move || {
+                let mut profiles = if profile_rules {
+                    Some(thread_rules.iter().map(|r| RuleProfile::new(r.get_id())).collect())
+                } else {
+                    None
+                };
                 loop {
                     let f = {
                         let mut files = thread_files.lock().unwrap();
@@ -76,9 +112,12 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
                             if let Err(e) =
                                    analyze_file(f.path(),
                                                 PathBuf::from(thread_dist_folder.as_str()),
+                                                (*thread_source_root).as_ref(),
                                                 &thread_rules,
                                                 &thread_manifest,
                                                 &thread_vulns,
+                                                era_sdk,
+                                                profiles.as_mut(),
                                                 verbose) {
                                 print_warning(format!("Error analyzing file {}. The analysis \
                                                        will continue, though. Error: {}",
@@ -90,6 +129,7 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
                         None => break,
                     }
                 }
+                profiles
             })
         })
         .collect();
@@ -114,19 +154,40 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
         }
     }
 
+    let mut profiles: Vec<RuleProfile> =
+        rules.iter().map(|r| RuleProfile::new(r.get_id())).collect();
     for t in handles {
-        if let Err(e) = t.join() {
-            print_warning(format!("An error occurred when joining analysis thrads: Error: {:?}",
-                                  e),
-                          config.is_verbose());
+        match t.join() {
+            Ok(Some(thread_profiles)) => {
+                for (profile, thread_profile) in profiles.iter_mut().zip(thread_profiles.iter()) {
+                    profile.merge(thread_profile);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                print_warning(format!("An error occurred when joining analysis thrads: Error: \
+                                       {:?}",
+                                      e),
+                              config.is_verbose());
+            }
         }
     }
+    if profile_rules {
+        results.set_rule_profiles(profiles);
+    }
 
     if config.is_bench() {
         results.add_benchmark(Benchmark::new("File analysis", analysis_start.elapsed()));
     }
 
-    for vuln in Arc::try_unwrap(found_vulns).unwrap().into_inner().unwrap() {
+    // Threads find the vulnerabilities in any order, so they are sorted to always keep the same
+    // one of the findings that are considered duplicates in the results
+    let mut found_vulns = Arc::try_unwrap(found_vulns).unwrap().into_inner().unwrap();
+    found_vulns.sort_by(|a, b| {
+        (a.get_file(), a.get_start_line(), a.get_name(), a.get_end_line(), a.get_code())
+            .cmp(&(b.get_file(), b.get_start_line(), b.get_name(), b.get_end_line(), b.get_code()))
+    });
+    for vuln in found_vulns {
         results.add_vulnerability(vuln);
     }
 
@@ -144,16 +205,39 @@ pub fn code_analysis(manifest: Option<Manifest>, config: &Config, results: &mut
 
 fn analyze_file<P: AsRef<Path>>(path: P,
                                 dist_folder: P,
+                                source_root: Option<&PathBuf>,
                                 rules: &Vec<Rule>,
                                 manifest: &Option<Manifest>,
                                 results: &Mutex<Vec<Vulnerability>>,
+                                era_sdk: Option<i32>,
+                                mut profiles: Option<&mut Vec<RuleProfile>>,
                                 verbose: bool)
                                 -> Result<()> {
     let mut f = try!(File::open(&path));
     let mut code = String::new();
     try!(f.read_to_string(&mut code));
 
-    'check: for rule in rules {
+    let target = match path.as_ref().extension() {
+        Some(e) if e == "smali" => RuleTarget::Smali,
+        Some(e) if e == "kt" => RuleTarget::Kotlin,
+        _ => RuleTarget::Java,
+    };
+
+    // Kotlin source files are relative to the source root instead of the distribution folder
+    let file = match source_root {
+        Some(root) if !path.as_ref().starts_with(&dist_folder) => {
+            path.as_ref().strip_prefix(root).unwrap()
+        }
+        _ => path.as_ref().strip_prefix(&dist_folder).unwrap(),
+    };
+    let relative_path = file.to_string_lossy();
+
+    let mut tokens = None;
+    'check: for (i, rule) in rules.iter().enumerate() {
+        if rule.get_target() != target || !rule.get_path_filter().matches(&relative_path) {
+            continue 'check;
+        }
+
         if manifest.is_some() && rule.get_max_sdk().is_some() {
             if rule.get_max_sdk().unwrap() < manifest.as_ref().unwrap().get_min_sdk() {
                 continue 'check;
@@ -170,91 +254,142 @@ fn analyze_file<P: AsRef<Path>>(path: P,
             }
         }
 
-        'rule: for (s, e) in rule.get_regex().find_iter(code.as_str()) {
+        match profiles {
+            Some(ref mut profiles) => {
+                let start = Instant::now();
+                let matches = check_rule(rule, file, &code, &mut tokens, era_sdk, results, verbose);
+                profiles[i].add_file(matches, start.elapsed());
+            }
+            None => {
+                let _ = check_rule(rule, file, &code, &mut tokens, era_sdk, results, verbose);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the rule in the code of a file, adding the vulnerabilities found, and gets the number
+/// of matches
+///
+/// The tokens of the code are only created the first time a structural rule needs them.
+fn check_rule<'a>(rule: &Rule,
+                  file: &Path,
+                  code: &'a str,
+                  tokens: &mut Option<Vec<Token<'a>>>,
+                  era_sdk: Option<i32>,
+                  results: &Mutex<Vec<Vulnerability>>,
+                  verbose: bool)
+                  -> usize {
+    let mut matches = 0;
+    if let Some(call) = rule.get_call() {
+        // The regular expression of structural rules is a quick check of the method name
+        if !rule.get_regex().is_match(code) {
+            return matches;
+        }
+        if tokens.is_none() {
+            *tokens = Some(tokenize(code));
+        }
+        'call: for (s, e) in call.find_calls(tokens.as_ref().unwrap()) {
             for white in rule.get_whitelist() {
                 if white.is_match(&code[s..e]) {
-                    continue 'rule;
+                    continue 'call;
                 }
             }
-            match rule.get_forward_check() {
-                None => {
-                    let start_line = get_line_for(s, code.as_str());
-                    let end_line = get_line_for(e, code.as_str());
-                    let mut results = results.lock().unwrap();
-                    results.push(Vulnerability::new(rule.get_criticity(),
-                                                    rule.get_label(),
-                                                    rule.get_description(),
-                                                    Some(path.as_ref()
-                                                        .strip_prefix(&dist_folder)
-                                                        .unwrap()),
-                                                    Some(start_line),
-                                                    Some(end_line),
-                                                    Some(get_code(code.as_str(),
-                                                                  start_line,
-                                                                  end_line))));
-
-                    if verbose {
-                        print_vulnerability(rule.get_description(), rule.get_criticity());
-                    }
-                }
-                Some(check) => {
-                    let caps = rule.get_regex().captures(&code[s..e]).unwrap();
+            if is_intrinsics_check(code, s) {
+                continue 'call;
+            }
+            add_rule_vulnerability(rule, file, code, (s, e), era_sdk, results, verbose);
+            matches += 1;
+        }
+        return matches;
+    }
 
-                    let fcheck1 = caps.name("fc1");
-                    let fcheck2 = caps.name("fc2");
-                    let mut r = check.clone();
+    'rule: for (s, e) in rule.get_regex().find_iter(code) {
+        for white in rule.get_whitelist() {
+            if white.is_match(&code[s..e]) {
+                continue 'rule;
+            }
+        }
+        // Null checks of decompiled Kotlin code quote the source of the checked expressions
+        if rule.get_target() == RuleTarget::Java && is_intrinsics_check(code, s) {
+            continue 'rule;
+        }
+        match rule.get_forward_check() {
+            None => {
+                add_rule_vulnerability(rule, file, code, (s, e), era_sdk, results, verbose);
+                matches += 1;
+            }
+            Some(check) => {
+                let caps = rule.get_regex().captures(&code[s..e]).unwrap();
 
-                    if let Some(fc1) = fcheck1 {
-                        r = r.replace("{fc1}", fc1);
-                    }
+                let fcheck1 = caps.name("fc1");
+                let fcheck2 = caps.name("fc2");
+                let mut r = check.clone();
 
-                    if let Some(fc2) = fcheck2 {
-                        r = r.replace("{fc2}", fc2);
-                    }
+                if let Some(fc1) = fcheck1 {
+                    r = r.replace("{fc1}", fc1);
+                }
 
-                    let regex = match Regex::new(r.as_str()) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            print_warning(format!("There was an error creating the \
-                                                   forward_check '{}'. The rule will be \
-                                                   skipped. {}",
-                                                  r,
-                                                  e),
-                                          verbose);
-                            break 'rule;
-                        }
-                    };
+                if let Some(fc2) = fcheck2 {
+                    r = r.replace("{fc2}", fc2);
+                }
 
-                    for (s, e) in regex.find_iter(code.as_str()) {
-                        let start_line = get_line_for(s, code.as_str());
-                        let end_line = get_line_for(e, code.as_str());
-                        let mut results = results.lock().unwrap();
-                        results.push(Vulnerability::new(rule.get_criticity(),
-                                                        rule.get_label(),
-                                                        rule.get_description(),
-                                                        Some(path.as_ref()
-                                                            .strip_prefix(&dist_folder)
-                                                            .unwrap()),
-                                                        Some(start_line),
-                                                        Some(end_line),
-                                                        Some(get_code(code.as_str(),
-                                                                      start_line,
-                                                                      end_line))));
-
-                        if verbose {
-                            print_vulnerability(rule.get_description(), rule.get_criticity());
-                        }
+                let regex = match Regex::new(r.as_str()) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        print_warning(format!("There was an error creating the forward_check \
+                                               '{}'. The rule will be skipped. {}",
+                                              r,
+                                              e),
+                                      verbose);
+                        break 'rule;
                     }
+                };
+
+                for (s, e) in regex.find_iter(code) {
+                    add_rule_vulnerability(rule, file, code, (s, e), era_sdk, results, verbose);
+                    matches += 1;
                 }
             }
-
         }
     }
+    matches
+}
 
-    Ok(())
+/// Adds the vulnerability found by a rule between the given indexes of the code
+fn add_rule_vulnerability(rule: &Rule,
+                          file: &Path,
+                          code: &str,
+                          (s, e): (usize, usize),
+                          era_sdk: Option<i32>,
+                          results: &Mutex<Vec<Vulnerability>>,
+                          verbose: bool) {
+    let start_line = get_line_for(s, code);
+    let end_line = get_line_for(e, code);
+    let mut vuln = Vulnerability::new(rule.get_criticity(),
+                                      rule.get_label(),
+                                      rule.get_description(),
+                                      Some(file),
+                                      Some(start_line),
+                                      Some(end_line),
+                                      Some(get_code(code, start_line, end_line)));
+    vuln.set_required_permissions(rule.get_permissions().cloned().collect());
+    vuln.set_metadata(rule.get_metadata().clone());
+    vuln.set_rule(rule.get_id());
+    if let (Some(app_sdk), Some(since_sdk)) = (era_sdk, rule.get_since_sdk()) {
+        vuln.adjust_for_era(app_sdk, since_sdk);
+    }
+
+    if verbose {
+        print_vulnerability(vuln.get_description(), vuln.get_criticity());
+    }
+
+    let mut results = results.lock().unwrap();
+    results.push(vuln);
 }
 
-fn get_line_for(index: usize, text: &str) -> usize {
+pub fn get_line_for(index: usize, text: &str) -> usize {
     let mut line = 0;
     for (i, c) in text.char_indices() {
         if i == index {
@@ -269,13 +404,21 @@ fn get_line_for(index: usize, text: &str) -> usize {
 
 fn add_files_to_vec<P: AsRef<Path>>(path: P,
                                     vec: &mut Vec<DirEntry>,
+                                    filter: &PathFilter,
                                     config: &Config)
                                     -> Result<()> {
     if path.as_ref() == Path::new("classes/android") ||
        path.as_ref() == Path::new("classes/com/google/android/gms") ||
-       path.as_ref() == Path::new("smali") {
+       filter.excludes_folder(&path.as_ref().to_string_lossy()) {
         return Ok(());
     }
+    if is_smali_folder(path.as_ref()) {
+        let real_path = format!("{}/{}/{}",
+                                config.get_dist_folder(),
+                                config.get_app_id(),
+                                path.as_ref().display());
+        return add_smali_files_to_vec(real_path, vec, filter, config);
+    }
     let real_path = format!("{}/{}/{}",
                             config.get_dist_folder(),
                             config.get_app_id(),
@@ -301,11 +444,13 @@ fn add_files_to_vec<P: AsRef<Path>>(path: P,
                                                              config.get_app_id()))
                                       .unwrap(),
                                   vec,
+                                  filter,
                                   config));
         } else if f_ext.is_some() {
             let filename = f_path.file_name().unwrap().to_string_lossy();
+            let relative = path.as_ref().join(f_path.file_name().unwrap());
             if filename != "AndroidManifest.xml" && filename != "R.java" &&
-               !filename.starts_with("R$") {
+               !filename.starts_with("R$") && filter.matches(&relative.to_string_lossy()) {
                 match f_ext.unwrap().to_string_lossy().borrow() {
                     "xml" | "java" => vec.push(f),
                     _ => {}
@@ -316,22 +461,183 @@ fn add_files_to_vec<P: AsRef<Path>>(path: P,
     Ok(())
 }
 
+/// Returns if the given path, relative to the distribution folder, is an apktool smali folder
+///
+/// Apps with multiple dex files have one `smali_classesN` folder for each extra dex file.
+pub fn is_smali_folder<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().to_str() {
+        Some(p) => p == "smali" || p.starts_with("smali_classes"),
+        None => false,
+    }
+}
+
+/// Returns if the given path, relative to the distribution folder, is the smali code of the
+/// Android or Google Play services libraries, that is not analyzed
+///
+/// Only the packages at the root of each smali folder are skipped, so that application packages
+/// that happen to be named `android` are still analyzed.
+fn is_library_smali_folder<P: AsRef<Path>>(path: P) -> bool {
+    let mut components = path.as_ref().components();
+    match components.next() {
+        Some(c) if is_smali_folder(c.as_os_str()) => {
+            let package = components.as_path();
+            package == Path::new("android") || package == Path::new("com/google/android/gms")
+        }
+        _ => false,
+    }
+}
+
+fn add_smali_files_to_vec<P: AsRef<Path>>(path: P,
+                                          vec: &mut Vec<DirEntry>,
+                                          filter: &PathFilter,
+                                          config: &Config)
+                                          -> Result<()> {
+    let dist_folder = format!("{}/{}", config.get_dist_folder(), config.get_app_id());
+    let relative = match path.as_ref().strip_prefix(&dist_folder) {
+        Ok(r) => r.to_string_lossy().into_owned(),
+        Err(_) => path.as_ref().to_string_lossy().into_owned(),
+    };
+    if is_library_smali_folder(&relative) || filter.excludes_folder(&relative) {
+        return Ok(());
+    }
+    for f in try!(fs::read_dir(path.as_ref())) {
+        let f = match f {
+            Ok(f) => f,
+            Err(e) => {
+                print_warning(format!("There was an error reading the directory {}: {}",
+                                      path.as_ref().display(),
+                                      e),
+                              config.is_verbose());
+                return Err(Error::from(e));
+            }
+        };
+        let f_type = try!(f.file_type());
+        if f_type.is_dir() {
+            try!(add_smali_files_to_vec(f.path(), vec, filter, config));
+        } else if f.path().extension().is_some() && f.path().extension().unwrap() == "smali" &&
+                  filter.matches(&format!("{}/{}",
+                                          relative,
+                                          f.file_name().to_string_lossy())) {
+            vec.push(f);
+        }
+    }
+    Ok(())
+}
+
+/// Converts a glob pattern to a regular expression matching paths relative to the distribution
+/// folder
+///
+/// `*` matches any character but `/`, `?` matches a single one and `**` matches any number of
+/// folders. Patterns starting with `/` are anchored to the distribution folder, while the rest
+/// can match from any folder, so that `com/google/**` matches the code of that package both in
+/// `classes` and in the smali folders.
+pub fn glob_to_regex(glob: &str) -> Regex {
+    let mut regex = String::from(if glob.starts_with('/') { "^" } else { "^(?:.*/)?" });
+    let mut chars = glob.trim_left_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                let _ = chars.next();
+                if chars.peek() == Some(&'/') {
+                    let _ = chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&quote(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+/// Filter of the paths a rule is checked against, as glob patterns relative to the distribution
+/// folder
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl PathFilter {
+    /// Creates a filter from the include and exclude glob patterns
+    ///
+    /// If there are no include patterns, all the paths that are not excluded are matched.
+    pub fn new<S: AsRef<str>>(include: &[S], exclude: &[S]) -> PathFilter {
+        PathFilter {
+            include: include.iter().map(|g| glob_to_regex(g.as_ref())).collect(),
+            exclude: exclude.iter().map(|g| glob_to_regex(g.as_ref())).collect(),
+        }
+    }
+
+    /// Checks if the file in the given path passes the filter
+    pub fn matches(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|r| r.is_match(path))) &&
+        !self.exclude.iter().any(|r| r.is_match(path))
+    }
+
+    /// Checks if all the files inside the given folder are excluded, so that it can be skipped
+    pub fn excludes_folder(&self, path: &str) -> bool {
+        !path.is_empty() && self.exclude.iter().any(|r| r.is_match(&format!("{}/", path)))
+    }
+}
+
+/// Code representation a rule is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleTarget {
+    /// Decompiled Java code and XML resources
+    Java,
+    /// Smali disassembly generated by apktool
+    Smali,
+    /// Kotlin source files of the source root, if it's set
+    Kotlin,
+}
+
+impl FromStr for RuleTarget {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<RuleTarget> {
+        match s {
+            "java" => Ok(RuleTarget::Java),
+            "smali" => Ok(RuleTarget::Smali),
+            "kotlin" => Ok(RuleTarget::Kotlin),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
 struct Rule {
+    id: String,
     regex: Regex,
+    target: RuleTarget,
     permissions: Vec<Permission>,
     forward_check: Option<String>,
     max_sdk: Option<i32>,
+    since_sdk: Option<i32>,
     whitelist: Vec<Regex>,
     label: String,
     description: String,
     criticity: Criticity,
+    metadata: RuleMetadata,
+    path_filter: PathFilter,
+    call: Option<CallPattern>,
 }
 
 impl Rule {
+    pub fn get_id(&self) -> &str {
+        self.id.as_str()
+    }
+
     pub fn get_regex(&self) -> &Regex {
         &self.regex
     }
 
+    pub fn get_target(&self) -> RuleTarget {
+        self.target
+    }
+
     pub fn get_permissions(&self) -> Iter<Permission> {
         self.permissions.iter()
     }
@@ -344,6 +650,10 @@ impl Rule {
         self.max_sdk
     }
 
+    pub fn get_since_sdk(&self) -> Option<i32> {
+        self.since_sdk
+    }
+
     pub fn get_label(&self) -> &str {
         self.label.as_str()
     }
@@ -359,251 +669,862 @@ impl Rule {
     pub fn get_whitelist(&self) -> Iter<Regex> {
         self.whitelist.iter()
     }
+
+    pub fn get_metadata(&self) -> &RuleMetadata {
+        &self.metadata
+    }
+
+    pub fn get_path_filter(&self) -> &PathFilter {
+        &self.path_filter
+    }
+
+    pub fn get_call(&self) -> Option<&CallPattern> {
+        self.call.as_ref()
+    }
+}
+
+/// Gets the list of strings of the given rule attribute, that can either be a string or an
+/// array of strings
+fn get_string_list(rule: &BTreeMap<String, Value>, key: &str) -> Result<Vec<String>> {
+    match rule.get(key) {
+        Some(&Value::String(ref s)) => Ok(vec![s.clone()]),
+        Some(&Value::Array(ref v)) => {
+            let mut list = Vec::with_capacity(v.len());
+            for s in v {
+                match *s {
+                    Value::String(ref s) => list.push(s.clone()),
+                    _ => return Err(Error::ParseError),
+                }
+            }
+            Ok(list)
+        }
+        Some(_) => Err(Error::ParseError),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Gets the CWE identifiers of a rule, normalized to the `CWE-<number>` form
+///
+/// They can be given as numbers, or as strings with or without the `CWE-` prefix.
+fn get_cwe_list(rule: &BTreeMap<String, Value>) -> Result<Vec<String>> {
+    let values = match rule.get("cwe") {
+        Some(&Value::Array(ref v)) => v.clone(),
+        Some(v) => vec![v.clone()],
+        None => Vec::new(),
+    };
+    let mut list = Vec::with_capacity(values.len());
+    for value in values {
+        let number = match value {
+            Value::U64(n) => n,
+            Value::String(ref s) => {
+                let s = s.trim();
+                let s = if s.to_uppercase().starts_with("CWE-") {
+                    &s[4..]
+                } else {
+                    s
+                };
+                match s.parse() {
+                    Ok(n) => n,
+                    Err(_) => return Err(Error::ParseError),
+                }
+            }
+            _ => return Err(Error::ParseError),
+        };
+        list.push(format!("CWE-{}", number));
+    }
+    Ok(list)
+}
+
+/// Loads the structural call pattern of a rule
+fn get_call_pattern(call: &BTreeMap<String, Value>) -> Result<CallPattern> {
+    let method = match call.get("method") {
+        Some(&Value::String(ref m)) => m.clone(),
+        _ => return Err(Error::ParseError),
+    };
+    let receiver = match call.get("receiver") {
+        Some(&Value::String(ref r)) => Some(r.clone()),
+        None => None,
+        _ => return Err(Error::ParseError),
+    };
+    let arguments = match call.get("arguments") {
+        Some(&Value::Array(ref a)) => {
+            let mut patterns = Vec::with_capacity(a.len());
+            for pattern in a {
+                match *pattern {
+                    Value::String(ref p) => patterns.push(try!(ArgumentPattern::from_str(p))),
+                    _ => return Err(Error::ParseError),
+                }
+            }
+            Some(patterns)
+        }
+        None => None,
+        _ => return Err(Error::ParseError),
+    };
+    if call.len() > 3 {
+        return Err(Error::ParseError);
+    }
+    CallPattern::new(method, receiver, arguments)
+}
+
+/// Loads the classification and remediation guidance of a rule
+fn get_rule_metadata(rule: &BTreeMap<String, Value>) -> Result<RuleMetadata> {
+    let remediation = match rule.get("remediation") {
+        Some(&Value::String(ref r)) => Some(r.clone()),
+        Some(_) => return Err(Error::ParseError),
+        None => None,
+    };
+    Ok(RuleMetadata::new(try!(get_cwe_list(rule)),
+                         try!(get_string_list(rule, "masvs")),
+                         try!(get_string_list(rule, "owasp_mobile")),
+                         try!(get_string_list(rule, "references")),
+                         remediation))
+}
+
+/// Gets the strings of the rule translated to the given language, from its `translations`
+/// attribute
+///
+/// Rules without a translation to the language use their own label, description and
+/// remediation.
+fn get_rule_translation<'r>(rule: &'r BTreeMap<String, Value>,
+                            language: &str)
+                            -> Result<Option<&'r BTreeMap<String, Value>>> {
+    let translation = match rule.get("translations") {
+        Some(&Value::Object(ref translations)) => translations.get(language),
+        Some(_) => return Err(Error::ParseError),
+        None => None,
+    };
+    match translation {
+        Some(&Value::Object(ref translation)) => {
+            if translation.iter().all(|(k, v)| {
+                v.as_str().is_some() &&
+                (k == "label" || k == "description" || k == "remediation")
+            }) {
+                Ok(Some(translation))
+            } else {
+                Err(Error::ParseError)
+            }
+        }
+        Some(_) => Err(Error::ParseError),
+        None => Ok(None),
+    }
+}
+
+/// Gets the rule files in the given path, that can either be a rule file or a directory
+///
+/// Directories are traversed recursively, and their JSON and YAML files are returned sorted by
+/// path, so that rules are always loaded in the same order.
+fn get_rule_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for f in try!(fs::read_dir(path)) {
+        let f_path = try!(f).path();
+        if f_path.file_name().map_or(false, |n| n.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if f_path.is_dir() {
+            files.extend(try!(get_rule_files(&f_path)));
+        } else if is_rule_file(&f_path) {
+            files.push(f_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns if the given path is a JSON or YAML rule file, by its extension
+pub fn is_rule_file<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().extension() {
+        Some(e) => e == "json" || e == "yml" || e == "yaml",
+        None => false,
+    }
+}
+
+/// Converts a YAML document to the JSON value rules are loaded from
+fn yaml_to_json(yaml: &Yaml) -> Result<Value> {
+    match *yaml {
+        Yaml::String(ref s) => Ok(Value::String(s.clone())),
+        Yaml::Integer(i) if i >= 0 => Ok(Value::U64(i as u64)),
+        Yaml::Integer(i) => Ok(Value::I64(i)),
+        Yaml::Real(ref r) => {
+            match r.parse() {
+                Ok(r) => Ok(Value::F64(r)),
+                Err(_) => Err(Error::ParseError),
+            }
+        }
+        Yaml::Boolean(b) => Ok(Value::Bool(b)),
+        Yaml::Array(ref a) => {
+            let mut array = Vec::with_capacity(a.len());
+            for value in a {
+                array.push(try!(yaml_to_json(value)));
+            }
+            Ok(Value::Array(array))
+        }
+        Yaml::Hash(ref h) => {
+            let mut object = BTreeMap::new();
+            for (key, value) in h {
+                match *key {
+                    Yaml::String(ref key) => {
+                        let _ = object.insert(key.clone(), try!(yaml_to_json(value)));
+                    }
+                    _ => return Err(Error::ParseError),
+                }
+            }
+            Ok(Value::Object(object))
+        }
+        Yaml::Null => Ok(Value::Null),
+        _ => Err(Error::ParseError),
+    }
+}
+
+/// Reads the rules of a JSON or YAML rule file
+fn read_rule_file<P: AsRef<Path>>(path: P) -> Result<Value> {
+    let path = path.as_ref();
+    let mut f = try!(File::open(path));
+    match path.extension() {
+        Some(e) if e == "yml" || e == "yaml" => {
+            let mut code = String::new();
+            try!(f.read_to_string(&mut code));
+            let mut documents = match YamlLoader::load_from_str(&code) {
+                Ok(d) => d,
+                Err(_) => return Err(Error::ParseError),
+            };
+            match documents.pop() {
+                Some(ref document) if documents.is_empty() => yaml_to_json(document),
+                _ => Err(Error::ParseError),
+            }
+        }
+        _ => Ok(try!(serde_json::from_reader(f))),
+    }
+}
+
+/// Gets an identifier for a rule without an explicit one, derived from its label
+///
+/// If the identifier is already taken, a numeric suffix is added to it.
+fn get_free_rule_id(label: &str, ids: &BTreeMap<String, PathBuf>) -> String {
+    let mut base = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_alphanumeric() {
+            base.extend(c.to_lowercase());
+        } else if !base.is_empty() && !base.ends_with('_') {
+            base.push('_');
+        }
+    }
+    let base = String::from(base.trim_right_matches('_'));
+
+    let mut id = base.clone();
+    let mut suffix = 2;
+    while ids.contains_key(&id) {
+        id = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    id
 }
 
+/// Loads the rules in the configured rule file or directory, and merges the extra rule files
+/// over them
+///
+/// All the JSON and YAML files of a rule directory are merged, and rule IDs must be unique
+/// across all of them. The rules of the extra rule files replace the rules with the same IDs,
+/// so later files override earlier ones, and the rest of them are added.
 fn load_rules(config: &Config) -> Result<Vec<Rule>> {
-    let f = try!(File::open(config.get_rules_json()));
-    let rules_json: Value = try!(serde_json::from_reader(f));
+    let files = try!(get_rule_files(config.get_rules_json()));
+    if files.is_empty() {
+        print_warning(format!("No rule files were found in {}.", config.get_rules_json()),
+                      config.is_verbose());
+        return Err(Error::Config);
+    }
 
     let mut rules = Vec::new();
-    let rules_json = match rules_json.as_array() {
+    let mut ids = BTreeMap::new();
+    for file in files {
+        rules.extend(try!(load_rule_file(&file, &mut ids, config)));
+    }
+
+    for extra in config.get_rules_extra() {
+        let files = try!(get_rule_files(extra));
+        if files.is_empty() {
+            print_warning(format!("No rule files were found in {}.", extra),
+                          config.is_verbose());
+        }
+        for file in files {
+            // IDs only need to be unique inside each extra file
+            for rule in try!(load_rule_file(&file, &mut BTreeMap::new(), config)) {
+                let position = rules.iter().position(|r| r.get_id() == rule.get_id());
+                match position {
+                    Some(i) => rules[i] = rule,
+                    None => rules.push(rule),
+                }
+            }
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Gets the SHA-256 hash of the rule files that are loaded with the given configuration, to
+/// identify the exact rules a report was generated with
+///
+/// The files are hashed in the order they are loaded, and only their contents are hashed, so
+/// the hash does not depend on the folder the rules are installed in.
+pub fn get_rules_hash(config: &Config) -> Result<String> {
+    let mut sha256 = Sha256::new();
+    let mut files = try!(get_rule_files(config.get_rules_json()));
+    for extra in config.get_rules_extra() {
+        files.extend(try!(get_rule_files(extra)));
+    }
+    for file in files {
+        let mut contents = Vec::new();
+        try!(try!(File::open(&file)).read_to_end(&mut contents));
+        sha256.input(&contents);
+        sha256.input(&[0]);
+    }
+    Ok(sha256.result_str())
+}
+
+/// Loads the rules of a rule file, adding their IDs to the given set of loaded rule IDs
+fn load_rule_file(file: &Path,
+                  ids: &mut BTreeMap<String, PathBuf>,
+                  config: &Config)
+                  -> Result<Vec<Rule>> {
+    let rules_value = match read_rule_file(file) {
+        Ok(r) => r,
+        Err(e) => {
+            print_warning(format!("The rule file {} could not be parsed.", file.display()),
+                          config.is_verbose());
+            return Err(e);
+        }
+    };
+    let rules_value = match rules_value.as_array() {
         Some(a) => a,
         None => {
-            print_warning("Rules must be a JSON array.", config.is_verbose());
+            print_warning(format!("Rules must be an array, and the rules in {} are not.",
+                                  file.display()),
+                          config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let mut rules = Vec::with_capacity(rules_value.len());
+    for rule in rules_value {
+        let rule = try!(load_rule(rule, ids, config));
+        let _ = ids.insert(rule.get_id().to_owned(), file.to_path_buf());
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+/// Validates the configured rule files, loading them as the code analysis does, and gets the
+/// number of rules enabled in the configuration
+pub fn validate_rules(config: &Config) -> Result<usize> {
+    let rules = try!(load_rules(config));
+    Ok(apply_rules_config(rules, config).len())
+}
+
+/// Validates a single rule file, such as the rules of a rule pack, and gets its number of rules
+pub fn validate_rule_file<P: AsRef<Path>>(file: P, config: &Config) -> Result<usize> {
+    let mut ids = BTreeMap::new();
+    load_rule_file(file.as_ref(), &mut ids, config).map(|rules| rules.len())
+}
+
+/// Removes the rules disabled in the `[rules]` section of the configuration, and overrides the
+/// criticity of the rules set in it
+fn apply_rules_config(rules: Vec<Rule>, config: &Config) -> Vec<Rule> {
+    let rules_config = config.get_rules_config();
+    for id in rules_config.get_rule_ids() {
+        if !rules.iter().any(|r| r.get_id() == id) {
+            print_warning(format!("The rule {} in the [rules] section of config.toml does not \
+                                   exist.",
+                                  id),
+                          config.is_verbose());
+        }
+    }
+
+    rules.into_iter()
+        .filter(|r| !rules_config.is_disabled(r.get_id()))
+        .map(|mut r| {
+            let criticity = rules_config.get_criticity(r.get_id());
+            if let Some(criticity) = criticity {
+                r.criticity = criticity;
+            }
+            r
+        })
+        .collect()
+}
+
+/// Loads a rule, checking that its ID is not in the given set of loaded rule IDs
+fn load_rule(rule: &Value, ids: &BTreeMap<String, PathBuf>, config: &Config) -> Result<Rule> {
+    let format_warning =
+        format!("Rules must be objects with the following structure:\n{}\nAn optional {} \
+                 attribute can be added: an array of regular expressions that if matched, \
+                 the found match will be discarded. You can also include an optional {} \
+                 attribute: an array of the permissions needed for this rule to be checked. \
+                 And finally, an optional {} attribute can be added where you can specify a \
+                 second regular expression to check if the one in the {} attribute matches. \
+                 You can add one or two capture groups with name from the match to this \
+                 check, with names {} and {}. To use them you have to include {} or {} in \
+                 the forward check. The optional {} attribute selects the code the rule \
+                 is checked against, {} (default) or {}. The optional {} attribute is the \
+                 API level from which the rule is considered an issue, for historical \
+                 analyses. The optional {}, {}, {}, {} and {} attributes map the rule \
+                 to standards: CWE identifiers, OWASP MASVS controls and OWASP Mobile Top \
+                 10 categories, along with reference URLs and remediation guidance. The \
+                 optional {} attribute is the unique identifier of the rule, derived from its \
+                 label if missing. The optional {} and {} attributes are lists of glob \
+                 patterns of the paths the rule is checked in or skipped for, such as \
+                 \"com/google/**\". Instead of the {} attribute, Java rules can have a {} \
+                 attribute, to match calls to a method by the structure of the code. The \
+                 optional {} attribute has the {}, {} and {} of the rule in other languages, \
+                 by language code. The optional {} attribute is a CVSS v3.1 vector, whose \
+                 score sets the criticity of the rule, so it can be used instead of {}.",
+                "{\n\t\"label\": \"Label for the rule\",\n\t\"description\": \"Long \
+                 description for this rule\"\n\t\"criticity\": \
+                 \"warning|low|medium|high|critical\"\n\t\"regex\": \
+                 \"regex_to_find_vulnerability\"\n}"
+                    .italic(),
+                "whitelist".italic(),
+                "permissions".italic(),
+                "forward_check".italic(),
+                "regex".italic(),
+                "fc1".italic(),
+                "fc2".italic(),
+                "{fc1}".italic(),
+                "{fc2}".italic(),
+                "target".italic(),
+                "java".italic(),
+                "smali".italic(),
+                "since_sdk".italic(),
+                "cwe".italic(),
+                "masvs".italic(),
+                "owasp_mobile".italic(),
+                "references".italic(),
+                "remediation".italic(),
+                "id".italic(),
+                "include_paths".italic(),
+                "exclude_paths".italic(),
+                "regex".italic(),
+                "call".italic(),
+                "translations".italic(),
+                "label".italic(),
+                "description".italic(),
+                "remediation".italic(),
+                "cvss".italic(),
+                "criticity".italic());
+    let rule = match rule.as_object() {
+        Some(o) => o,
+        None => {
+            print_warning(format_warning, config.is_verbose());
             return Err(Error::ParseError);
         }
     };
 
-    for rule in rules_json {
-        let format_warning =
-            format!("Rules must be objects with the following structure:\n{}\nAn optional {} \
-                     attribute can be added: an array of regular expressions that if matched, \
-                     the found match will be discarded. You can also include an optional {} \
-                     attribute: an array of the permissions needed for this rule to be checked. \
-                     And finally, an optional {} attribute can be added where you can specify a \
-                     second regular expression to check if the one in the {} attribute matches. \
-                     You can add one or two capture groups with name from the match to this \
-                     check, with names {} and {}. To use them you have to include {} or {} in \
-                     the forward check.",
-                    "{\n\t\"label\": \"Label for the rule\",\n\t\"description\": \"Long \
-                     description for this rule\"\n\t\"criticity\": \
-                     \"warning|low|medium|high|critical\"\n\t\"regex\": \
-                     \"regex_to_find_vulnerability\"\n}"
-                        .italic(),
-                    "whitelist".italic(),
-                    "permissions".italic(),
-                    "forward_check".italic(),
-                    "regex".italic(),
-                    "fc1".italic(),
-                    "fc2".italic(),
-                    "{fc1}".italic(),
-                    "{fc2}".italic());
-        let rule = match rule.as_object() {
-            Some(o) => o,
-            None => {
-                print_warning(format_warning, config.is_verbose());
+    if rule.len() < 4 || rule.len() > 21 {
+        print_warning(format_warning, config.is_verbose());
+        return Err(Error::ParseError);
+    }
+
+    let id = match rule.get("id") {
+        Some(&Value::String(ref id)) => {
+            if let Some(file) = ids.get(id) {
+                print_warning(format!("The rule ID {} is duplicated, it was already defined in \
+                                       {}.",
+                                      id.italic(),
+                                      file.display()),
+                              config.is_verbose());
+                return Err(Error::ParseError);
+            }
+            id.clone()
+        }
+        None => {
+            let label = match rule.get("label") {
+                Some(&Value::String(ref l)) => l.as_str(),
+                _ => "",
+            };
+            get_free_rule_id(label, ids)
+        }
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let call = match rule.get("call") {
+        Some(&Value::Object(ref c)) => {
+            match get_call_pattern(c) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    print_warning(format!("The {} attribute must be an object with the name of \
+                                           the called {}, and optionally the {} expression \
+                                           and a list of {} patterns: {}, {}, {} or {} (the \
+                                           rest of the arguments, only at the end).",
+                                          "call".italic(),
+                                          "method".italic(),
+                                          "receiver".italic(),
+                                          "arguments".italic(),
+                                          "any".italic(),
+                                          "constant".italic(),
+                                          "non_constant".italic(),
+                                          "...".italic()),
+                                  config.is_verbose());
+                    return Err(e);
+                }
+            }
+        }
+        None => None,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let regex = match (rule.get("regex"), call.as_ref()) {
+        (None, Some(call)) => {
+            Regex::new(&format!(r"\b{}\s*\(", quote(call.get_method()))).unwrap()
+        }
+        (Some(&Value::String(ref r)), _) => {
+            match Regex::new(r) {
+                Ok(r) => r,
+                Err(e) => {
+                    print_warning(format!("An error occurred when compiling the regular \
+                                           expresion: {}",
+                                          e),
+                                  config.is_verbose());
+                    return Err(Error::ParseError);
+                }
+            }
+        }
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let target = match rule.get("target") {
+        Some(&Value::String(ref t)) => {
+            match RuleTarget::from_str(t) {
+                Ok(t) => t,
+                Err(e) => {
+                    print_warning(format!("The rule target must be {}, {} or {}.",
+                                          "java".italic(),
+                                          "smali".italic(),
+                                          "kotlin".italic()),
+                                  config.is_verbose());
+                    return Err(e);
+                }
+            }
+        }
+        None => RuleTarget::Java,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    if call.is_some() && (target != RuleTarget::Java || rule.contains_key("forward_check")) {
+        print_warning(format!("Rules with a {} pattern can only target Java code, and can't \
+                               have a {}.",
+                              "call".italic(),
+                              "forward_check".italic()),
+                      config.is_verbose());
+        return Err(Error::ParseError);
+    }
+
+    let max_sdk = match rule.get("max_sdk") {
+        Some(&Value::U64(sdk)) => Some(sdk as i32),
+        None => None,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let since_sdk = match rule.get("since_sdk") {
+        Some(&Value::U64(sdk)) => Some(sdk as i32),
+        None => None,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+
+    let permissions = match rule.get("permissions") {
+        Some(&Value::Array(ref v)) => {
+            let mut list = Vec::with_capacity(v.len());
+            for p in v {
+                list.push(match p {
+                    &Value::String(ref p) => {
+                        match Permission::from_str(p) {
+                            Ok(p) => p,
+                            Err(_) => {
+                                print_warning(format!("the permission {} is unknown",
+                                                      p.italic()),
+                                              config.is_verbose());
+                                return Err(Error::ParseError);
+                            }
+                        }
+                    }
+                    _ => {
+                        print_warning(format_warning, config.is_verbose());
+                        return Err(Error::ParseError);
+                    }
+                });
+            }
+            list
+        }
+        Some(_) => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+        None => Vec::with_capacity(0),
+    };
+
+    let forward_check = match rule.get("forward_check") {
+        Some(&Value::String(ref s)) => {
+            let capture_names = regex.capture_names();
+            for cap in capture_names {
+                match cap {
+                    Some("fc1") => {
+                        if !s.contains("{fc1}") {
+                            print_warning("You must provide the '{fc1}' string where you \
+                                           want the 'fc1' capture to be inserted in the \
+                                           forward check.",
+                                          config.is_verbose());
+                            return Err(Error::ParseError);
+                        }
+                    }
+                    Some("fc2") => {
+                        if !s.contains("{fc2}") {
+                            print_warning("You must provide the '{fc2}' string where you \
+                                           want the 'fc2' capture to be inserted in the \
+                                           forward check.",
+                                          config.is_verbose());
+                            return Err(Error::ParseError);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let mut capture_names = regex.capture_names();
+            if capture_names.find(|c| c.is_some() && c.unwrap() == "fc2").is_some() &&
+               capture_names.find(|c| c.is_some() && c.unwrap() == "fc1").is_none() {
+                print_warning("You must have a capture group named fc1 to use the capture \
+                               fc2.",
+                              config.is_verbose());
                 return Err(Error::ParseError);
             }
-        };
 
-        if rule.len() < 4 || rule.len() > 8 {
+            Some(s.clone())
+        }
+        None => None,
+        _ => {
             print_warning(format_warning, config.is_verbose());
             return Err(Error::ParseError);
         }
+    };
 
-        let regex = match rule.get("regex") {
-            Some(&Value::String(ref r)) => {
-                match Regex::new(r) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        print_warning(format!("An error occurred when compiling the regular \
-                                               expresion: {}",
-                                              e),
-                                      config.is_verbose());
-                        return Err(Error::ParseError);
-                    }
-                }
-            }
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-        };
+    let label = match rule.get("label") {
+        Some(&Value::String(ref l)) => l,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
 
-        let max_sdk = match rule.get("max_sdk") {
-            Some(&Value::U64(sdk)) => Some(sdk as i32),
-            None => None,
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-        };
+    let description = match rule.get("description") {
+        Some(&Value::String(ref d)) => d,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
 
-        let permissions = match rule.get("permissions") {
-            Some(&Value::Array(ref v)) => {
-                let mut list = Vec::with_capacity(v.len());
-                for p in v {
-                    list.push(match p {
-                        &Value::String(ref p) => {
-                            match Permission::from_str(p) {
-                                Ok(p) => p,
-                                Err(_) => {
-                                    print_warning(format!("the permission {} is unknown",
-                                                          p.italic()),
-                                                  config.is_verbose());
-                                    return Err(Error::ParseError);
-                                }
-                            }
-                        }
-                        _ => {
-                            print_warning(format_warning, config.is_verbose());
-                            return Err(Error::ParseError);
-                        }
-                    });
+    let cvss = match rule.get("cvss") {
+        Some(&Value::String(ref v)) => {
+            match CvssVector::from_str(v) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    print_warning(format!("The {} attribute of the rule {} must be a CVSS v3.1 \
+                                           vector, such as {}.",
+                                          "cvss".italic(),
+                                          label.italic(),
+                                          "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"
+                                              .italic()),
+                                  config.is_verbose());
+                    return Err(e);
                 }
-                list
             }
-            Some(_) => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
+        }
+        None => None,
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
+    let score = cvss.as_ref()
+        .map(|c| c.get_score(config.get_rules_config().get_cvss_environment()));
+
+    let criticity = match rule.get("criticity") {
+        // The criticity is derived from the CVSS score, if the rule has a vector
+        None if score.is_some() => get_criticity(score.unwrap()),
+        Some(&Value::String(ref c)) => {
+            match Criticity::from_str(c) {
+                Ok(c) => c,
+                Err(e) => {
+                    print_warning(format!("Criticity must be  one of {}, {}, {}, {} or {}.",
+                                          "warning".italic(),
+                                          "low".italic(),
+                                          "medium".italic(),
+                                          "high".italic(),
+                                          "critical".italic()),
+                                  config.is_verbose());
+                    return Err(e);
+                }
             }
-            None => Vec::with_capacity(0),
-        };
+        }
+        _ => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+    };
 
-        let forward_check = match rule.get("forward_check") {
-            Some(&Value::String(ref s)) => {
-                let capture_names = regex.capture_names();
-                for cap in capture_names {
-                    match cap {
-                        Some("fc1") => {
-                            if !s.contains("{fc1}") {
-                                print_warning("You must provide the '{fc1}' string where you \
-                                               want the 'fc1' capture to be inserted in the \
-                                               forward check.",
-                                              config.is_verbose());
-                                return Err(Error::ParseError);
-                            }
-                        }
-                        Some("fc2") => {
-                            if !s.contains("{fc2}") {
-                                print_warning("You must provide the '{fc2}' string where you \
-                                               want the 'fc2' capture to be inserted in the \
-                                               forward check.",
+    let whitelist = match rule.get("whitelist") {
+        Some(&Value::Array(ref v)) => {
+            let mut list = Vec::with_capacity(v.len());
+            for r in v {
+                list.push(match r {
+                    &Value::String(ref r) => {
+                        match Regex::new(r) {
+                            Ok(r) => r,
+                            Err(e) => {
+                                print_warning(format!("An error occurred when compiling the \
+                                                       regular expresion: {}",
+                                                      e),
                                               config.is_verbose());
                                 return Err(Error::ParseError);
                             }
                         }
-                        _ => {}
                     }
-                }
-
-                let mut capture_names = regex.capture_names();
-                if capture_names.find(|c| c.is_some() && c.unwrap() == "fc2").is_some() &&
-                   capture_names.find(|c| c.is_some() && c.unwrap() == "fc1").is_none() {
-                    print_warning("You must have a capture group named fc1 to use the capture \
-                                   fc2.",
-                                  config.is_verbose());
-                    return Err(Error::ParseError);
-                }
-
-                Some(s.clone())
-            }
-            None => None,
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-        };
-
-        let label = match rule.get("label") {
-            Some(&Value::String(ref l)) => l,
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
+                    _ => {
+                        print_warning(format_warning, config.is_verbose());
+                        return Err(Error::ParseError);
+                    }
+                });
             }
-        };
+            list
+        }
+        Some(_) => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(Error::ParseError);
+        }
+        None => Vec::with_capacity(0),
+    };
 
-        let description = match rule.get("description") {
-            Some(&Value::String(ref d)) => d,
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-        };
+    let translation = match get_rule_translation(rule, config.get_language()) {
+        Ok(t) => t,
+        Err(e) => {
+            print_warning(format!("The {} attribute of the rule {} must be an object with the \
+                                   translated {}, {} and {} strings of each language.",
+                                  "translations".italic(),
+                                  label.italic(),
+                                  "label".italic(),
+                                  "description".italic(),
+                                  "remediation".italic()),
+                          config.is_verbose());
+            return Err(e);
+        }
+    };
+    let translated = |key: &str| translation.and_then(|t| t.get(key)).and_then(|v| v.as_str());
 
-        let criticity = match rule.get("criticity") {
-            Some(&Value::String(ref c)) => {
-                match Criticity::from_str(c) {
-                    Ok(c) => c,
-                    Err(e) => {
-                        print_warning(format!("Criticity must be  one of {}, {}, {}, {} or {}.",
-                                              "warning".italic(),
-                                              "low".italic(),
-                                              "medium".italic(),
-                                              "high".italic(),
-                                              "critical".italic()),
-                                      config.is_verbose());
-                        return Err(e);
-                    }
-                }
-            }
-            _ => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-        };
+    let metadata = match get_rule_metadata(rule) {
+        Ok(m) => m,
+        Err(e) => {
+            print_warning(format!("The metadata of the rule {} is invalid. The {} \
+                                   attribute must be a CWE identifier or a list of them, \
+                                   {}, {} and {} must be strings or lists of strings, and \
+                                   {} must be a string.",
+                                  label.italic(),
+                                  "cwe".italic(),
+                                  "masvs".italic(),
+                                  "owasp_mobile".italic(),
+                                  "references".italic(),
+                                  "remediation".italic()),
+                          config.is_verbose());
+            return Err(e);
+        }
+    };
 
-        let whitelist = match rule.get("whitelist") {
-            Some(&Value::Array(ref v)) => {
-                let mut list = Vec::with_capacity(v.len());
-                for r in v {
-                    list.push(match r {
-                        &Value::String(ref r) => {
-                            match Regex::new(r) {
-                                Ok(r) => r,
-                                Err(e) => {
-                                    print_warning(format!("An error occurred when compiling the \
-                                                           regular expresion: {}",
-                                                          e),
-                                                  config.is_verbose());
-                                    return Err(Error::ParseError);
-                                }
-                            }
-                        }
-                        _ => {
-                            print_warning(format_warning, config.is_verbose());
-                            return Err(Error::ParseError);
-                        }
-                    });
-                }
-                list
-            }
-            Some(_) => {
-                print_warning(format_warning, config.is_verbose());
-                return Err(Error::ParseError);
-            }
-            None => Vec::with_capacity(0),
-        };
+    let mut metadata = match translated("remediation") {
+        Some(remediation) => {
+            RuleMetadata::new(metadata.get_cwe().to_vec(),
+                              metadata.get_masvs().to_vec(),
+                              metadata.get_owasp_mobile().to_vec(),
+                              metadata.get_references().to_vec(),
+                              Some(String::from(remediation)))
+        }
+        None => metadata,
+    };
+    let criticity = match (cvss, score) {
+        (Some(cvss), Some(score)) => {
+            metadata.set_cvss(CvssScore::new(cvss.get_vector(), score));
+            get_criticity(score)
+        }
+        _ => criticity,
+    };
 
-        rules.push(Rule {
-            regex: regex,
-            permissions: permissions,
-            forward_check: forward_check,
-            max_sdk: max_sdk,
-            label: label.clone(),
-            description: description.clone(),
-            criticity: criticity,
-            whitelist: whitelist,
-        })
-    }
+    let include_paths = match get_string_list(rule, "include_paths") {
+        Ok(p) => p,
+        Err(e) => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(e);
+        }
+    };
+    let exclude_paths = match get_string_list(rule, "exclude_paths") {
+        Ok(p) => p,
+        Err(e) => {
+            print_warning(format_warning, config.is_verbose());
+            return Err(e);
+        }
+    };
 
-    Ok(rules)
+    Ok(Rule {
+        id: id,
+        regex: regex,
+        target: target,
+        permissions: permissions,
+        forward_check: forward_check,
+        max_sdk: max_sdk,
+        since_sdk: since_sdk,
+        label: String::from(translated("label").unwrap_or(label.as_str())),
+        description: String::from(translated("description").unwrap_or(description.as_str())),
+        criticity: criticity,
+        whitelist: whitelist,
+        metadata: metadata,
+        path_filter: PathFilter::new(&include_paths, &exclude_paths),
+        call: call,
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Write;
+
     use regex::Regex;
-    use super::{Rule, load_rules};
+    use std::path::PathBuf;
+
+    use serde_json;
+    use serde_json::value::Value;
+    use yaml_rust::yaml::YamlLoader;
+    use {Config, Criticity};
+    use super::{Rule, RuleTarget, PathFilter, load_rules, load_rule, get_rule_metadata,
+                get_free_rule_id, yaml_to_json, is_rule_file, get_call_pattern,
+                get_rule_translation, add_files_to_vec};
+    use super::super::java::tokenize;
 
     fn check_match(text: &str, rule: &Rule) -> bool {
         if rule.get_regex().is_match(text) {
@@ -862,43 +1783,11 @@ mod tests {
         }
     }
 
-    #[test]
-    fn it_weak_algs() {
-        let config = Default::default();
-        let rules = load_rules(&config).unwrap();
-        let rule = rules.get(8).unwrap();
-
-        let should_match = &["DESKeySpec",
-                             "getInstance(MD5)",
-                             "getInstance(\"MD5\")",
-                             "getInstance(SHA-1)",
-                             "getInstance(\"SHA-1\")",
-                             "getInstance(\"MD4\")",
-                             "getInstance(\"RC2\")",
-                             "getInstance(\"md4\")",
-                             "getInstance(\"rc2\")",
-                             "getInstance(\"rc4\")",
-                             "getInstance(\"RC4\")",
-                             "getInstance(\"AES/ECB\")",
-                             "getInstance(\"RSA/ECB/nopadding\")",
-                             "getInstance(\"rsa/ECB/nopadding\")"];
-
-        let should_not_match = &["", "", "", ""];
-
-        for m in should_match {
-            assert!(check_match(m, rule));
-        }
-
-        for m in should_not_match {
-            assert!(!check_match(m, rule));
-        }
-    }
-
     #[test]
     fn it_sleep_method() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(9).unwrap();
+        let rule = rules.get(8).unwrap();
 
         let should_match = &["Thread.sleep(Usertime+Variable+Variable);",
                              "Thread.sleep(Usertime+13+123+1+24);",
@@ -925,7 +1814,8 @@ mod tests {
     fn it_world_readable_permissions() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(10).unwrap();
+        let rule = rules.get(9).unwrap();
+        assert_eq!(rule.get_since_sdk(), Some(17));
 
         let should_match = &["MODE_WORLD_READABLE",
                              "openFileOutput(\"file.txt  \", 1) ",
@@ -949,7 +1839,7 @@ mod tests {
     fn it_world_writable_permissions() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(11).unwrap();
+        let rule = rules.get(10).unwrap();
 
         let should_match = &["MODE_WORLD_WRITABLE",
                              "openFileOutput(\"file.txt  \", 2) ",
@@ -973,7 +1863,7 @@ mod tests {
     fn it_external_storage_write_read() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(12).unwrap();
+        let rule = rules.get(11).unwrap();
 
         let should_match = &[".getExternalStorage", ".getExternalFilesDir()"];
 
@@ -992,7 +1882,7 @@ mod tests {
     fn it_temp_file() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(13).unwrap();
+        let rule = rules.get(12).unwrap();
 
         let should_match = &[".createTempFile()", ".createTempFile()"];
 
@@ -1011,7 +1901,7 @@ mod tests {
     fn it_webview_xss() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(14).unwrap();
+        let rule = rules.get(13).unwrap();
 
         let should_match = &["setJavaScriptEnabled(true)    .addJavascriptInterface()"];
 
@@ -1030,7 +1920,7 @@ mod tests {
     fn it_webview_ssl_errors() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(15).unwrap();
+        let rule = rules.get(14).unwrap();
 
         let should_match = &["onReceivedSslError(WebView view, SslErrorHandler handler, SslError \
                               error)             .proceed();"];
@@ -1050,7 +1940,7 @@ mod tests {
     fn it_sql_injection() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(16).unwrap();
+        let rule = rules.get(15).unwrap();
 
         let should_match = &["android.database.sqlite   .execSQL(\"INSERT INTO myuser VALUES \
                               ('\" + paramView.getText().toString() + \"', '\" + \
@@ -1077,7 +1967,7 @@ mod tests {
     fn it_ssl_accepting_all_certificates() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(17).unwrap();
+        let rule = rules.get(16).unwrap();
 
         let should_match = &["javax.net.ssl   TrustAllSSLSocket-Factory",
                              "javax.net.ssl   AllTrustSSLSocketFactory",
@@ -1102,7 +1992,7 @@ mod tests {
     fn it_sms_mms_sending() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(18).unwrap();
+        let rule = rules.get(17).unwrap();
 
         let should_match =
             &["telephony.SmsManager     sendMultipartTextMessage(String destinationAddress, \
@@ -1135,7 +2025,7 @@ mod tests {
     fn it_superuser_privileges() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(19).unwrap();
+        let rule = rules.get(18).unwrap();
 
         let should_match = &["com.noshufou.android.su",
                              "com.thirdparty.superuser",
@@ -1158,7 +2048,7 @@ mod tests {
     fn it_superuser_device_detection() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(20).unwrap();
+        let rule = rules.get(19).unwrap();
 
         let should_match = &[".contains(\"test-keys\")",
                              "/system/app/Superuser.apk",
@@ -1183,7 +2073,7 @@ mod tests {
     fn it_base_station_location() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(21).unwrap();
+        let rule = rules.get(20).unwrap();
 
         let should_match = &["telephony.TelephonyManager    getCellLocation"];
 
@@ -1202,7 +2092,7 @@ mod tests {
     fn it_get_device_id() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(22).unwrap();
+        let rule = rules.get(21).unwrap();
 
         let should_match = &["telephony.TelephonyManager      getDeviceId()"];
 
@@ -1221,7 +2111,7 @@ mod tests {
     fn it_get_sim_serial() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(23).unwrap();
+        let rule = rules.get(22).unwrap();
 
         let should_match = &["telephony.TelephonyManager      getSimSerialNumber()"];
 
@@ -1240,7 +2130,7 @@ mod tests {
     fn it_gps_location() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(24).unwrap();
+        let rule = rules.get(23).unwrap();
 
         let should_match = &["android.location   getLastKnownLocation()",
                              "android.location   requestLocationUpdates()",
@@ -1266,7 +2156,7 @@ mod tests {
     fn it_base64_encode() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(25).unwrap();
+        let rule = rules.get(24).unwrap();
 
         let should_match = &["android.util.Base64 .encodeToString()",
                              "android.util.Base64    .encode()"];
@@ -1286,7 +2176,7 @@ mod tests {
     fn it_base64_decoding() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(26).unwrap();
+        let rule = rules.get(25).unwrap();
 
         let should_match = &["android.util.Base64   .decode()"];
 
@@ -1305,7 +2195,7 @@ mod tests {
     fn it_infinite_loop() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(27).unwrap();
+        let rule = rules.get(26).unwrap();
 
         let should_match = &["while(true)"];
 
@@ -1324,7 +2214,7 @@ mod tests {
     fn it_email_disclosure() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(28).unwrap();
+        let rule = rules.get(27).unwrap();
 
         let should_match = &["super@super.es",
                              "android_analizer@dem.co.uk",
@@ -1346,7 +2236,7 @@ mod tests {
     fn it_hardcoded_certificate() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(29).unwrap();
+        let rule = rules.get(28).unwrap();
 
         let should_match = &["\"key.key              ",
                              "\"cert.cert\"",
@@ -1373,7 +2263,7 @@ mod tests {
     fn it_get_sim_operator() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(30).unwrap();
+        let rule = rules.get(29).unwrap();
 
         let should_match = &["telephony.TelephonyManager      getSimOperator()"];
 
@@ -1392,7 +2282,7 @@ mod tests {
     fn it_get_sim_operatorname() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(31).unwrap();
+        let rule = rules.get(30).unwrap();
 
         let should_match = &["telephony.TelephonyManager      getSimOperatorName()"];
 
@@ -1411,7 +2301,7 @@ mod tests {
     fn it_obfuscation() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(32).unwrap();
+        let rule = rules.get(31).unwrap();
 
         let should_match = &["android.utils.AESObfuscator getObfuscator();",
                              "android.utils.AESObfuscator   obfuscation.getObfuscator();",
@@ -1437,7 +2327,7 @@ mod tests {
     fn it_command_exec() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(33).unwrap();
+        let rule = rules.get(32).unwrap();
 
         let should_match = &["Runtime.getRuntime().exec(\"command\", options);",
                              "getRuntime().exec(\"ls -la\", options);",
@@ -1462,7 +2352,7 @@ mod tests {
     fn it_ssl_getinsecure_method() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(34).unwrap();
+        let rule = rules.get(33).unwrap();
 
         let should_match = &[" javax.net.ssl.SSLSocketFactory                 \
                               SSLSocketFactory.getInsecure()"];
@@ -1485,7 +2375,7 @@ mod tests {
     fn it_finally_with_return() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(35).unwrap();
+        let rule = rules.get(34).unwrap();
 
         let should_match = &["finally {                      return;",
                              "finally {                      return;}"];
@@ -1506,7 +2396,7 @@ mod tests {
     fn it_sleep_method_notvalidated() {
         let config = Default::default();
         let rules = load_rules(&config).unwrap();
-        let rule = rules.get(36).unwrap();
+        let rule = rules.get(35).unwrap();
 
         let should_match = &["int var = EditText.getText  Thread.sleep(100 + var);",
                              "var = .getText  Thread.sleep(100 + var);"];
@@ -1525,4 +2415,295 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_smali_ecb_cipher() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.get(36).unwrap();
+        assert_eq!(rule.get_target(), RuleTarget::Smali);
+
+        let should_match = &["const-string v0, \"AES/ECB/PKCS5Padding\"\n\n    \
+                              invoke-static {v0}, \
+                              Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)\
+                              Ljavax/crypto/Cipher;",
+                             "const-string p1, \"AES\"\n    .line 42\n    invoke-static {p1}, \
+                              Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)\
+                              Ljavax/crypto/Cipher;"];
+        let should_not_match = &["const-string v0, \"AES/GCM/NoPadding\"\n    \
+                                  invoke-static {v0}, \
+                                  Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)\
+                                  Ljavax/crypto/Cipher;",
+                                 "Cipher.getInstance(\"AES/ECB/PKCS5Padding\");"];
+
+        for m in should_match {
+            assert!(check_match(m, rule));
+        }
+
+        for m in should_not_match {
+            assert!(!check_match(m, rule));
+        }
+    }
+
+    #[test]
+    fn it_smali_dex_class_loader() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.get(37).unwrap();
+        assert_eq!(rule.get_target(), RuleTarget::Smali);
+
+        let should_match = &["invoke-direct {v0, v1, v2, v3, p0}, \
+                              Ldalvik/system/DexClassLoader;-><init>(Ljava/lang/String;\
+                              Ljava/lang/String;Ljava/lang/String;Ljava/lang/ClassLoader;)V"];
+        let should_not_match = &["new DexClassLoader(path, dir, null, loader);"];
+
+        for m in should_match {
+            assert!(check_match(m, rule));
+        }
+
+        for m in should_not_match {
+            assert!(!check_match(m, rule));
+        }
+    }
+
+    #[test]
+    fn it_kotlin_rules() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.iter().find(|r| r.get_id() == "kotlin_accept_all_hostnames").unwrap();
+        assert_eq!(rule.get_target(), RuleTarget::Kotlin);
+        assert!(check_match("builder.hostnameVerifier { _, _ -> true }", rule));
+        assert!(check_match("connection.hostnameVerifier = HostnameVerifier { hostname, \
+                             session -> true }",
+                            rule));
+        assert!(!check_match("builder.hostnameVerifier { host, _ -> host == \"example.com\" }",
+                             rule));
+
+        let rule = rules.iter().find(|r| r.get_id() == "kotlin_webview_javascript").unwrap();
+        assert!(check_match("webView.settings.javaScriptEnabled = true\n\
+                             webView.addJavascriptInterface(Bridge(), \"bridge\")",
+                            rule));
+        assert!(!check_match("webView.settings.javaScriptEnabled = true", rule));
+
+        let rule = rules.iter().find(|r| r.get_id() == "kotlin_cleartext_url_read").unwrap();
+        assert!(check_match("val text = URL(\"http://example.com/data\").readText()", rule));
+        assert!(!check_match("val text = URL(\"https://example.com/data\").readText()", rule));
+
+        let rule = rules.iter().find(|r| r.get_id() == "kotlin_run_blocking").unwrap();
+        assert!(check_match("runBlocking { api.fetch() }", rule));
+        assert!(check_match("runBlocking(Dispatchers.IO) {", rule));
+        assert!(!check_match("lifecycleScope.launch { api.fetch() }", rule));
+    }
+
+    #[test]
+    fn it_rule_metadata() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.get(15).unwrap();
+        assert_eq!(rule.get_label(), "SQL injection");
+        assert_eq!(rule.get_metadata().get_cwe(), &[String::from("CWE-89")]);
+        assert_eq!(rule.get_metadata().get_owasp_mobile(), &[String::from("M4")]);
+        assert!(rule.get_metadata().get_remediation().is_some());
+        assert!(rules.get(7).unwrap().get_metadata().is_empty());
+
+        let mut rule = BTreeMap::new();
+        let _ = rule.insert(String::from("cwe"),
+                            Value::Array(vec![Value::U64(79), Value::String(String::from("89"))]));
+        let _ = rule.insert(String::from("masvs"), Value::String(String::from("MASVS-CODE-4")));
+        let metadata = get_rule_metadata(&rule).unwrap();
+        assert_eq!(metadata.get_cwe(),
+                   &[String::from("CWE-79"), String::from("CWE-89")]);
+        assert_eq!(metadata.get_masvs(), &[String::from("MASVS-CODE-4")]);
+        assert!(metadata.get_references().is_empty());
+
+        let _ = rule.insert(String::from("cwe"), Value::String(String::from("SQLi")));
+        assert!(get_rule_metadata(&rule).is_err());
+    }
+
+    #[test]
+    fn it_rule_translation() {
+        let mut config = Config::default();
+        config.set_language("es");
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.get(15).unwrap();
+        assert_eq!(rule.get_id(), "sql_injection");
+        assert_eq!(rule.get_label(), "Inyección SQL");
+        assert!(rule.get_metadata().get_remediation().unwrap().starts_with("Use consultas"));
+
+        let rule: Value = serde_json::from_str(r#"{"label": "Weak hash",
+            "translations": {"es": {"label": "Hash débil"}, "fr": "Hachage faible"}}"#)
+            .unwrap();
+        let rule = rule.as_object().unwrap();
+        let translation = get_rule_translation(rule, "es").unwrap().unwrap();
+        assert_eq!(translation.get("label").and_then(|l| l.as_str()), Some("Hash débil"));
+        assert!(get_rule_translation(rule, "en").unwrap().is_none());
+        assert!(get_rule_translation(rule, "fr").is_err());
+    }
+
+    #[test]
+    fn it_rule_ids() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        assert_eq!(rules.get(15).unwrap().get_id(), "sql_injection");
+
+        let mut ids = BTreeMap::new();
+        assert_eq!(get_free_rule_id("WebView ignores SSL errors", &ids),
+                   "webview_ignores_ssl_errors");
+        let _ = ids.insert(String::from("sleep_method"), PathBuf::from("rules.json"));
+        assert_eq!(get_free_rule_id("Sleep Method", &ids), "sleep_method_2");
+        assert_eq!(get_free_rule_id("Super user privileges.", &ids), "super_user_privileges");
+    }
+
+    #[test]
+    fn it_yaml_rules() {
+        let yaml = "- id: custom_log\n  \
+                    label: Custom logger\n  \
+                    description: Sensitive data could be logged.\n  \
+                    criticity: low\n  \
+                    regex: 'CustomLog\\.d\\('\n  \
+                    since_sdk: 16\n  \
+                    cwe: [532]\n";
+        let documents = YamlLoader::load_from_str(yaml).unwrap();
+        let rules = yaml_to_json(&documents[0]).unwrap();
+        let rule = rules.as_array().unwrap()[0].as_object().unwrap();
+        assert_eq!(rule.get("id"), Some(&Value::String(String::from("custom_log"))));
+        assert_eq!(rule.get("regex"), Some(&Value::String(String::from("CustomLog\\.d\\("))));
+        assert_eq!(rule.get("since_sdk"), Some(&Value::U64(16)));
+        assert_eq!(rule.get("cwe"), Some(&Value::Array(vec![Value::U64(532)])));
+
+        assert!(is_rule_file("rules/crypto.yml"));
+        assert!(is_rule_file("rules.json"));
+        assert!(!is_rule_file("rules/README.md"));
+    }
+
+    #[test]
+    fn it_command_injection() {
+        let config = Default::default();
+        let rules = load_rules(&config).unwrap();
+        let rule = rules.iter().find(|r| r.get_id() == "command_injection").unwrap();
+        let call = rule.get_call().unwrap();
+
+        let code = "Runtime.getRuntime().exec(\"sh -c \" + command);\n\
+                    Runtime.getRuntime().exec(new String[] { \"su\", \"-c\", cmd });\n\
+                    Runtime.getRuntime().exec(\"ls -l\");\n\
+                    // Runtime.getRuntime().exec(command);\n\
+                    String exec = \"Runtime.getRuntime().exec(command)\";";
+        assert!(rule.get_regex().is_match(code));
+        let calls = call.find_calls(&tokenize(code));
+        assert_eq!(calls.len(), 2);
+        assert_eq!(&code[calls[0].0..calls[0].1],
+                   "Runtime.getRuntime().exec(\"sh -c \" + command)");
+
+        let mut pattern = BTreeMap::new();
+        let _ = pattern.insert(String::from("method"), Value::String(String::from("exec")));
+        let _ = pattern.insert(String::from("arguments"),
+                               Value::Array(vec![Value::String(String::from("variable"))]));
+        assert!(get_call_pattern(&pattern).is_err());
+    }
+
+    #[test]
+    fn it_rules_extra() {
+        let path = "rules_extra_test.json";
+        let mut f = fs::File::create(path).unwrap();
+        f.write_all(b"[{\"id\": \"url_disclosure\", \"label\": \"Company URL\", \
+                      \"description\": \"A company URL was found.\", \
+                      \"criticity\": \"high\", \"regex\": \"example\\\\.com\"}, \
+                      {\"id\": \"company_token\", \"label\": \"Company token\", \
+                      \"description\": \"A company token was found.\", \
+                      \"criticity\": \"critical\", \"regex\": \"CT-[0-9]{8}\"}]")
+            .unwrap();
+
+        let mut config: Config = Default::default();
+        let base = load_rules(&config).unwrap();
+        config.add_rules_extra(path);
+        let rules = load_rules(&config).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(rules.len(), base.len() + 1);
+        assert_eq!(rules[0].get_id(), "url_disclosure");
+        assert_eq!(rules[0].get_label(), "Company URL");
+        assert_eq!(rules[0].get_criticity(), Criticity::High);
+        assert!(rules[0].get_regex().is_match("https://example.com"));
+        let token = rules.last().unwrap();
+        assert_eq!(token.get_id(), "company_token");
+        assert!(token.get_regex().is_match("CT-12345678"));
+    }
+
+    #[test]
+    fn it_rule_cvss() {
+        let config: Config = Default::default();
+        let rule = serde_json::from_str("{\"label\": \"Hardcoded key\", \
+                                         \"description\": \"A key is hardcoded.\", \
+                                         \"criticity\": \"low\", \"regex\": \"key\", \
+                                         \"cvss\": \"CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/\
+                                         I:H/A:N\"}")
+            .unwrap();
+        let rule = load_rule(&rule, &BTreeMap::new(), &config).unwrap();
+        assert_eq!(rule.get_criticity(), Criticity::High);
+        let cvss = rule.get_metadata().get_cvss().unwrap();
+        assert_eq!(cvss.get_score(), 9.1);
+        assert_eq!(cvss.get_vector(), "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:N");
+
+        let rule = serde_json::from_str("{\"label\": \"Hardcoded key\", \
+                                         \"description\": \"A key is hardcoded.\", \
+                                         \"regex\": \"key\", \"cvss\": \"CVSS:3.1/AV:L\"}")
+            .unwrap();
+        assert!(load_rule(&rule, &BTreeMap::new(), &config).is_err());
+    }
+
+    #[test]
+    fn it_path_filter() {
+        let filter = PathFilter::new(&[] as &[&str], &["com/google/**", "**/test/*.java"]);
+        assert!(filter.matches("classes/com/example/MainActivity.java"));
+        assert!(!filter.matches("classes/com/google/ads/AdView.java"));
+        assert!(!filter.matches("smali_classes2/com/google/ads/AdView.smali"));
+        assert!(!filter.matches("classes/com/example/test/MainTest.java"));
+        assert!(filter.matches("classes/com/example/test/data/Fixture.java"));
+        assert!(filter.excludes_folder("classes/com/google"));
+        assert!(!filter.excludes_folder("classes/com"));
+        assert!(!filter.excludes_folder(""));
+
+        let filter = PathFilter::new(&["/classes/**"], &[]);
+        assert!(filter.matches("classes/com/example/MainActivity.java"));
+        assert!(!filter.matches("res/layout/main.xml"));
+        assert!(!filter.matches("smali/classes/Foo.smali"));
+        assert!(!filter.excludes_folder("res"));
+
+        let filter = PathFilter::new(&["*.java"], &["R?.java"]);
+        assert!(filter.matches("classes/com/example/MainActivity.java"));
+        assert!(!filter.matches("classes/com/example/RS.java"));
+        assert!(!filter.matches("res/layout/main.xml"));
+    }
+
+    #[test]
+    fn it_add_files_to_vec() {
+        let mut config: Config = Default::default();
+        config.set_dist_folder("code_files_test");
+        config.set_app_id("com.example");
+        for &(folder, file) in &[("smali/android/support/v4/app", "Fragment.smali"),
+                                 ("smali_classes2/com/google/android/gms/ads", "AdView.smali"),
+                                 ("smali/com/example/android", "MainActivity.smali"),
+                                 ("smali_classes2/com/example/android/ui", "ListView.smali"),
+                                 ("classes/android/support/v4/app", "Fragment.java"),
+                                 ("classes/com/example/android", "MainActivity.java")] {
+            let folder = PathBuf::from("code_files_test/com.example").join(folder);
+            fs::create_dir_all(&folder).unwrap();
+            let _ = fs::File::create(folder.join(file)).unwrap();
+        }
+
+        let mut files = Vec::new();
+        add_files_to_vec("", &mut files, &PathFilter::default(), &config).unwrap();
+        fs::remove_dir_all("code_files_test").unwrap();
+
+        let mut files = files.iter()
+            .map(|f| f.path().to_string_lossy().replace('\\', "/"))
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files,
+                   vec!["code_files_test/com.example/classes/com/example/android/\
+                         MainActivity.java",
+                        "code_files_test/com.example/smali/com/example/android/\
+                         MainActivity.smali",
+                        "code_files_test/com.example/smali_classes2/com/example/android/ui/\
+                         ListView.smali"]);
+    }
 }
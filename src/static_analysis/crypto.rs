@@ -0,0 +1,562 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability};
+use super::code::get_line_for;
+use super::java::{get_java_files, get_class_name, get_enclosing_method, get_call_arguments,
+                  describe_location};
+use super::read_text_file;
+
+/// Minimum size of RSA keys
+const MIN_RSA_KEY_SIZE: i64 = 2048;
+
+/// Names that show that the file uses the cryptography APIs
+const CRYPTO_MARKERS: [&'static str; 4] = ["javax.crypto", "java.security", "Cipher",
+                                           "SecureRandom"];
+
+/// Block ciphers that use ECB mode when no mode is given
+const BLOCK_CIPHERS: [&'static str; 5] = ["AES", "DES", "DESEDE", "BLOWFISH", "TRIPLEDES"];
+
+/// Ciphers that should not be used, since they can be broken
+const WEAK_CIPHERS: [&'static str; 4] = ["DES", "RC2", "RC4", "ARCFOUR"];
+
+/// Digests that should not be used, since they are vulnerable to collisions
+const WEAK_DIGESTS: [&'static str; 4] = ["MD2", "MD4", "MD5", "SHA-1"];
+
+/// Value of a variable or an expression known at analysis time
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constant {
+    Str(String),
+    Int(i64),
+    /// Byte or char array, created from literals
+    Bytes,
+}
+
+/// Values assigned to the variables and fields of a class
+///
+/// Variables are tracked per method, so a name in one method does not get the value of another
+/// method's variable. Variables with any assignment that is not constant are not constant.
+struct Constants {
+    values: BTreeMap<(Option<String>, String), Option<Constant>>,
+}
+
+impl Constants {
+    fn new(code: &str, assignment: &Regex) -> Constants {
+        let mut constants = Constants { values: BTreeMap::new() };
+        for caps in assignment.captures_iter(code) {
+            let (s, _) = caps.pos(0).unwrap();
+            let name = caps.at(1).unwrap();
+            let method = get_enclosing_method(code, s).map(String::from);
+            let value = constants.parse(caps.at(2).unwrap(), method.as_ref().map(|m| m.as_str()));
+            let key = (method, String::from(name));
+            let entry = constants.values.entry(key).or_insert(value.clone());
+            if value.is_none() {
+                *entry = None;
+            }
+        }
+        constants
+    }
+
+    /// Gets the value of the variable in the given method, or of the field if there is no such
+    /// variable
+    fn get(&self, name: &str, method: Option<&str>) -> Option<&Constant> {
+        let name = String::from(name);
+        if let Some(value) = self.values.get(&(method.map(String::from), name.clone())) {
+            return value.as_ref();
+        }
+        match self.values.get(&(None, name)) {
+            Some(value) => value.as_ref(),
+            None => None,
+        }
+    }
+
+    /// Gets the constant value of the expression, if it has one
+    fn parse(&self, expression: &str, method: Option<&str>) -> Option<Constant> {
+        let expression = expression.trim();
+        // Casts, such as `(long) 1024`
+        let expression = match expression.find(')') {
+            Some(i) if expression.starts_with('(') && !expression.ends_with(')') => {
+                expression[i + 1..].trim()
+            }
+            _ => expression,
+        };
+
+        if let Some(s) = parse_string_literal(expression) {
+            return Some(Constant::Str(String::from(s)));
+        }
+        if let Ok(i) = expression.trim_right_matches(|c: char| c == 'L' || c == 'l').parse() {
+            return Some(Constant::Int(i));
+        }
+        if expression.starts_with("new byte") || expression.starts_with("new char") {
+            return match (expression.find('{'), expression.ends_with('}')) {
+                (Some(_), true) => Some(Constant::Bytes),
+                _ => None,
+            };
+        }
+        for method_name in &[".getBytes(", ".toCharArray("] {
+            if let Some(i) = expression.find(method_name) {
+                return match self.parse(&expression[..i], method) {
+                    Some(Constant::Str(_)) => Some(Constant::Bytes),
+                    _ => None,
+                };
+            }
+        }
+        if is_reference(expression) {
+            let name = expression.rsplit('.').next().unwrap();
+            return self.get(name, method).cloned();
+        }
+        None
+    }
+}
+
+/// Gets the content of a string literal, if the expression is a string literal
+fn parse_string_literal(expression: &str) -> Option<&str> {
+    if expression.len() >= 2 && expression.starts_with('"') && expression.ends_with('"') &&
+       !expression[1..expression.len() - 1].contains('"') {
+        Some(&expression[1..expression.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// Checks if the expression is a reference to a variable or a field, such as `this.key`
+fn is_reference(expression: &str) -> bool {
+    !expression.is_empty() &&
+    expression.split('.').all(|part| {
+        !part.is_empty() && !part.starts_with(|c: char| c.is_digit(10)) &&
+        part.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    })
+}
+
+/// Cryptography misuse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CryptoIssue {
+    EcbMode(String),
+    WeakCipher(String),
+    RsaWithoutPadding(String),
+    StaticIv,
+    HardcodedKey,
+    WeakSignatureDigest(String),
+    WeakDigest(String),
+    InsecureSeed,
+    ShortRsaKey(i64),
+}
+
+impl CryptoIssue {
+    pub fn get_criticity(&self) -> Criticity {
+        match *self {
+            CryptoIssue::StaticIv |
+            CryptoIssue::HardcodedKey |
+            CryptoIssue::WeakCipher(_) |
+            CryptoIssue::ShortRsaKey(_) => Criticity::High,
+            CryptoIssue::EcbMode(_) |
+            CryptoIssue::RsaWithoutPadding(_) |
+            CryptoIssue::WeakSignatureDigest(_) |
+            CryptoIssue::InsecureSeed => Criticity::Medium,
+            CryptoIssue::WeakDigest(_) => Criticity::Low,
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        match *self {
+            CryptoIssue::EcbMode(_) => "ECB cipher mode",
+            CryptoIssue::WeakCipher(_) => "Weak cipher",
+            CryptoIssue::RsaWithoutPadding(_) => "RSA without padding",
+            CryptoIssue::StaticIv => "Static initialization vector",
+            CryptoIssue::HardcodedKey => "Hardcoded cryptographic key",
+            CryptoIssue::WeakSignatureDigest(_) => "Weak signature digest",
+            CryptoIssue::WeakDigest(_) => "Weak digest",
+            CryptoIssue::InsecureSeed => "Insecure SecureRandom seed",
+            CryptoIssue::ShortRsaKey(_) => "Short RSA key",
+        }
+    }
+
+    /// Gets the description of the issue found in the given location
+    pub fn get_description(&self, location: &str) -> String {
+        match *self {
+            CryptoIssue::EcbMode(ref transformation) => {
+                format!("The cipher created with the {} transformation in {} uses ECB mode{}. \
+                         ECB mode encrypts equal blocks to equal ciphertexts, leaking patterns \
+                         of the plain text. Use an authenticated mode, such as GCM.",
+                        transformation,
+                        location,
+                        if transformation.contains('/') {
+                            ""
+                        } else {
+                            ", the default when no mode is given"
+                        })
+            }
+            CryptoIssue::WeakCipher(ref transformation) => {
+                format!("The {} cipher is used in {}. It's a weak cipher, and the encrypted \
+                         data could be decrypted by an attacker. Use AES instead.",
+                        transformation,
+                        location)
+            }
+            CryptoIssue::RsaWithoutPadding(ref transformation) => {
+                format!("RSA is used without padding, with the {} transformation, in {}. \
+                         Textbook RSA is deterministic and malleable. Use OAEP padding.",
+                        transformation,
+                        location)
+            }
+            CryptoIssue::StaticIv => {
+                format!("A constant initialization vector is used in {}. Reusing the IV with \
+                         the same key leaks information about the plain text, and breaks the \
+                         security of modes such as GCM. Generate a random IV for each \
+                         encryption.",
+                        location)
+            }
+            CryptoIssue::HardcodedKey => {
+                format!("A SecretKeySpec is created from a constant key in {}. Anybody with \
+                         the application can extract the key and decrypt the data. Generate \
+                         the keys at runtime and store them in the Android Keystore.",
+                        location)
+            }
+            CryptoIssue::WeakSignatureDigest(ref algorithm) => {
+                format!("Signatures are created or verified with {} in {}. The digest is \
+                         vulnerable to collisions, so signatures could be forged. Use SHA-256 \
+                         or stronger.",
+                        algorithm,
+                        location)
+            }
+            CryptoIssue::WeakDigest(ref algorithm) => {
+                format!("The {} digest is used in {}. It's vulnerable to collisions, and \
+                         should not be used for security purposes, such as integrity checks \
+                         or password hashing.",
+                        algorithm,
+                        location)
+            }
+            CryptoIssue::InsecureSeed => {
+                format!("A SecureRandom is seeded with a constant or the current time in {}. \
+                         In some Android versions, the seed replaces the system entropy, and \
+                         the generated values can be predicted.",
+                        location)
+            }
+            CryptoIssue::ShortRsaKey(size) => {
+                format!("An RSA key of {} bits is generated in {}. Keys shorter than {} bits \
+                         can be factored with enough resources.",
+                        size,
+                        location,
+                        MIN_RSA_KEY_SIZE)
+            }
+        }
+    }
+}
+
+/// Regular expressions of the cryptography API calls that are checked
+struct Detector {
+    assignment: Regex,
+    cipher: Regex,
+    iv: Regex,
+    secret_key: Regex,
+    signature: Regex,
+    digest: Regex,
+    seed: Regex,
+    rsa_generator: Regex,
+    key_size: Regex,
+}
+
+impl Detector {
+    fn new() -> Detector {
+        Detector {
+            assignment: Regex::new(r"\b(\w+)\s*=\s*([^=;][^;]*);").unwrap(),
+            cipher: Regex::new(r"\bCipher\s*\.\s*getInstance\s*\(").unwrap(),
+            iv: Regex::new(r"\bnew\s+(?:IvParameterSpec|GCMParameterSpec)\s*\(").unwrap(),
+            secret_key: Regex::new(r"\bnew\s+SecretKeySpec\s*\(").unwrap(),
+            signature: Regex::new(r"\bSignature\s*\.\s*getInstance\s*\(").unwrap(),
+            digest: Regex::new(r"\bMessageDigest\s*\.\s*getInstance\s*\(").unwrap(),
+            seed: Regex::new(r"(?:\bnew\s+SecureRandom|\.setSeed)\s*\(").unwrap(),
+            rsa_generator: Regex::new(r#"KeyPairGenerator\s*\.\s*getInstance\s*\(\s*"RSA""#)
+                .unwrap(),
+            key_size: Regex::new(r"(?:\.initialize|\bnew\s+RSAKeyGenParameterSpec)\s*\(")
+                .unwrap(),
+        }
+    }
+}
+
+/// Gets the constant value of the given argument of the call whose opening parenthesis is at the
+/// given index
+fn get_argument(code: &str, open: usize, index: usize, constants: &Constants) -> Option<Constant> {
+    let method = get_enclosing_method(code, open);
+    match get_call_arguments(code, open) {
+        Some(arguments) => arguments.get(index).and_then(|a| constants.parse(a, method)),
+        None => None,
+    }
+}
+
+/// Finds the cryptography misuses in the code, with their position in the code
+fn find_issues(code: &str, detector: &Detector) -> Vec<(CryptoIssue, usize, usize)> {
+    let constants = Constants::new(code, &detector.assignment);
+    let mut issues = Vec::new();
+
+    for (s, e) in detector.cipher.find_iter(code) {
+        if let Some(Constant::Str(transformation)) = get_argument(code, e - 1, 0, &constants) {
+            let upper = transformation.to_uppercase();
+            let mut parts = upper.split('/');
+            let algorithm = parts.next().unwrap();
+            let mode = parts.next();
+            let padding = parts.next();
+            if WEAK_CIPHERS.contains(&algorithm) {
+                issues.push((CryptoIssue::WeakCipher(transformation), s, e));
+            } else if algorithm == "RSA" && padding == Some("NOPADDING") {
+                issues.push((CryptoIssue::RsaWithoutPadding(transformation), s, e));
+            } else if BLOCK_CIPHERS.contains(&algorithm) &&
+                      (mode.is_none() || mode == Some("ECB")) {
+                issues.push((CryptoIssue::EcbMode(transformation), s, e));
+            }
+        }
+    }
+
+    for (s, e) in detector.iv.find_iter(code) {
+        // GCMParameterSpec receives the tag length before the IV
+        let index = if code[s..e].contains("GCMParameterSpec") { 1 } else { 0 };
+        if get_argument(code, e - 1, index, &constants) == Some(Constant::Bytes) {
+            issues.push((CryptoIssue::StaticIv, s, e));
+        }
+    }
+
+    for (s, e) in detector.secret_key.find_iter(code) {
+        if get_argument(code, e - 1, 0, &constants) == Some(Constant::Bytes) {
+            issues.push((CryptoIssue::HardcodedKey, s, e));
+        }
+    }
+
+    for (s, e) in detector.signature.find_iter(code) {
+        if let Some(Constant::Str(algorithm)) = get_argument(code, e - 1, 0, &constants) {
+            let upper = algorithm.to_uppercase();
+            if upper.starts_with("MD5") || upper.starts_with("SHA1") ||
+               upper.starts_with("SHA-1") {
+                issues.push((CryptoIssue::WeakSignatureDigest(algorithm), s, e));
+            }
+        }
+    }
+
+    for (s, e) in detector.digest.find_iter(code) {
+        if let Some(Constant::Str(algorithm)) = get_argument(code, e - 1, 0, &constants) {
+            let upper = algorithm.to_uppercase();
+            if WEAK_DIGESTS.contains(&upper.as_str()) || upper == "SHA1" {
+                issues.push((CryptoIssue::WeakDigest(algorithm), s, e));
+            }
+        }
+    }
+
+    for (s, e) in detector.seed.find_iter(code) {
+        let arguments = match get_call_arguments(code, e - 1) {
+            Some(a) => a,
+            None => continue,
+        };
+        let seed = match arguments.get(0) {
+            Some(seed) => *seed,
+            None => continue,
+        };
+        let method = get_enclosing_method(code, s);
+        if seed.contains("currentTimeMillis") || seed.contains("nanoTime") ||
+           constants.parse(seed, method).is_some() {
+            issues.push((CryptoIssue::InsecureSeed, s, e));
+        }
+    }
+
+    if detector.rsa_generator.is_match(code) {
+        for (s, e) in detector.key_size.find_iter(code) {
+            if let Some(Constant::Int(size)) = get_argument(code, e - 1, 0, &constants) {
+                if size < MIN_RSA_KEY_SIZE {
+                    issues.push((CryptoIssue::ShortRsaKey(size), s, e));
+                }
+            }
+        }
+    }
+
+    issues.sort_by_key(|&(_, s, _)| s);
+    issues
+}
+
+/// Analyzes the use of the cryptography APIs in the decompiled code of the application
+pub fn crypto_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Checking the use of cryptography in the code.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the cryptography \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let detector = Detector::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !CRYPTO_MARKERS.iter().any(|m| code.contains(m)) {
+            continue;
+        }
+
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        for (issue, s, e) in find_issues(&code, &detector) {
+            add_finding(&issue, &code, relative, s, e, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The use of cryptography was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Cryptography analyzed.");
+    }
+}
+
+/// Adds a finding for the issue, located in the class and method where it was found
+fn add_finding(issue: &CryptoIssue,
+               code: &str,
+               path: &Path,
+               start: usize,
+               end: usize,
+               config: &Config,
+               results: &mut Results) {
+    let class = get_class_name(path).unwrap_or_else(|| path.to_string_lossy().into_owned());
+    let location = describe_location(&class, get_enclosing_method(code, start));
+    let description = issue.get_description(&location);
+    let start_line = get_line_for(start, code);
+    let end_line = get_line_for(end, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), issue.get_criticity());
+    }
+
+    let vuln = Vulnerability::new(issue.get_criticity(),
+                                  String::from(issue.get_label()),
+                                  description,
+                                  Some(path),
+                                  Some(start_line),
+                                  Some(end_line),
+                                  Some(get_code(code, start_line, end_line)));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Constant, Constants, CryptoIssue, Detector, find_issues, is_reference};
+
+    fn issues(code: &str) -> Vec<CryptoIssue> {
+        find_issues(code, &Detector::new()).into_iter().map(|(issue, _, _)| issue).collect()
+    }
+
+    #[test]
+    fn it_constants() {
+        let code = "class Keys {
+    private static final String KEY = \"0123456789abcdef\";
+    private byte[] salt = new byte[] { 1, 2, 3 };
+
+    void a() {
+        String mode = \"AES/CBC/PKCS5Padding\";
+        int size = (int) 1024;
+        byte[] key = KEY.getBytes();
+    }
+
+    void b(String input) {
+        String mode = input;
+        String other = \"RC4\";
+        other = input.trim();
+    }
+}";
+        let constants = Constants::new(code, &Detector::new().assignment);
+        assert_eq!(constants.get("KEY", Some("a")),
+                   Some(&Constant::Str(String::from("0123456789abcdef"))));
+        assert_eq!(constants.get("salt", Some("b")), Some(&Constant::Bytes));
+        assert_eq!(constants.get("mode", Some("a")),
+                   Some(&Constant::Str(String::from("AES/CBC/PKCS5Padding"))));
+        assert_eq!(constants.get("size", Some("a")), Some(&Constant::Int(1024)));
+        assert_eq!(constants.get("key", Some("a")), Some(&Constant::Bytes));
+        assert_eq!(constants.get("mode", Some("b")), None);
+        assert_eq!(constants.get("other", Some("b")), None);
+        assert_eq!(constants.get("missing", Some("a")), None);
+    }
+
+    #[test]
+    fn it_is_reference() {
+        assert!(is_reference("key"));
+        assert!(is_reference("this.key"));
+        assert!(is_reference("Config.SECRET_KEY"));
+        assert!(!is_reference("getKey()"));
+        assert!(!is_reference("1024"));
+        assert!(!is_reference("a + b"));
+    }
+
+    #[test]
+    fn it_cipher_modes() {
+        let code = "void encrypt() {
+    String transformation = \"AES\";
+    Cipher a = Cipher.getInstance(transformation);
+    Cipher b = Cipher.getInstance(\"AES/ECB/PKCS5Padding\");
+    Cipher c = Cipher.getInstance(\"AES/GCM/NoPadding\");
+    Cipher d = Cipher.getInstance(\"DES/CBC/PKCS5Padding\");
+    Cipher e = Cipher.getInstance(\"RSA/ECB/NoPadding\");
+    Cipher f = Cipher.getInstance(\"RSA/ECB/OAEPWithSHA-256AndMGF1Padding\");
+    Cipher g = Cipher.getInstance(this.getTransformation());
+}";
+        assert_eq!(issues(code),
+                   vec![CryptoIssue::EcbMode(String::from("AES")),
+                        CryptoIssue::EcbMode(String::from("AES/ECB/PKCS5Padding")),
+                        CryptoIssue::WeakCipher(String::from("DES/CBC/PKCS5Padding")),
+                        CryptoIssue::RsaWithoutPadding(String::from("RSA/ECB/NoPadding"))]);
+    }
+
+    #[test]
+    fn it_keys_and_ivs() {
+        let code = "class Crypto {
+    private static final String KEY = \"0123456789abcdef\";
+
+    void encrypt(byte[] userKey, byte[] iv) {
+        byte[] staticIv = new byte[] { 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0 };
+        SecretKeySpec a = new SecretKeySpec(KEY.getBytes(\"UTF-8\"), \"AES\");
+        SecretKeySpec b = new SecretKeySpec(userKey, \"AES\");
+        IvParameterSpec c = new IvParameterSpec(staticIv);
+        IvParameterSpec d = new IvParameterSpec(iv);
+        GCMParameterSpec e = new GCMParameterSpec(128, staticIv);
+    }
+}";
+        assert_eq!(issues(code),
+                   vec![CryptoIssue::HardcodedKey, CryptoIssue::StaticIv, CryptoIssue::StaticIv]);
+    }
+
+    #[test]
+    fn it_digests_seeds_and_key_sizes() {
+        let code = "void sign() {
+    Signature a = Signature.getInstance(\"SHA1withRSA\");
+    Signature b = Signature.getInstance(\"SHA256withECDSA\");
+    MessageDigest c = MessageDigest.getInstance(\"MD5\");
+    MessageDigest d = MessageDigest.getInstance(\"SHA-256\");
+    SecureRandom e = new SecureRandom();
+    e.setSeed(System.currentTimeMillis());
+    SecureRandom f = new SecureRandom(\"seed\".getBytes());
+    KeyPairGenerator g = KeyPairGenerator.getInstance(\"RSA\");
+    g.initialize(1024);
+}";
+        assert_eq!(issues(code),
+                   vec![CryptoIssue::WeakSignatureDigest(String::from("SHA1withRSA")),
+                        CryptoIssue::WeakDigest(String::from("MD5")),
+                        CryptoIssue::InsecureSeed,
+                        CryptoIssue::InsecureSeed,
+                        CryptoIssue::ShortRsaKey(1024)]);
+    }
+}
@@ -0,0 +1,327 @@
+//! CVSS v3.1 vectors of the code rules, to give their findings numeric scores and criticities
+//! derived from them, instead of only the fixed criticity of the rule.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use {Criticity, Error, Result};
+
+/// Prefixes of the supported vectors
+const VECTOR_PREFIXES: [&'static str; 2] = ["CVSS:3.1/", "CVSS:3.0/"];
+
+/// Metrics required in every vector
+const BASE_METRICS: [&'static str; 8] = ["AV", "AC", "PR", "UI", "S", "C", "I", "A"];
+
+/// Metrics that can be overridden in the configuration, for the environment of the application
+const ENVIRONMENTAL_METRICS: [&'static str; 11] = ["CR", "IR", "AR", "MAV", "MAC", "MPR", "MUI",
+                                                   "MS", "MC", "MI", "MA"];
+
+const ATTACK_VECTOR: &'static [&'static str] = &["N", "A", "L", "P"];
+const ATTACK_COMPLEXITY: &'static [&'static str] = &["L", "H"];
+const PRIVILEGES: &'static [&'static str] = &["N", "L", "H"];
+const USER_INTERACTION: &'static [&'static str] = &["N", "R"];
+const SCOPE: &'static [&'static str] = &["U", "C"];
+const IMPACT: &'static [&'static str] = &["H", "L", "N"];
+const EXPLOIT_MATURITY: &'static [&'static str] = &["X", "H", "F", "P", "U"];
+const REMEDIATION_LEVEL: &'static [&'static str] = &["X", "U", "W", "T", "O"];
+const REPORT_CONFIDENCE: &'static [&'static str] = &["X", "C", "R", "U"];
+const REQUIREMENT: &'static [&'static str] = &["X", "H", "M", "L"];
+const MODIFIED_ATTACK_VECTOR: &'static [&'static str] = &["X", "N", "A", "L", "P"];
+const MODIFIED_ATTACK_COMPLEXITY: &'static [&'static str] = &["X", "L", "H"];
+const MODIFIED_PRIVILEGES: &'static [&'static str] = &["X", "N", "L", "H"];
+const MODIFIED_USER_INTERACTION: &'static [&'static str] = &["X", "N", "R"];
+const MODIFIED_SCOPE: &'static [&'static str] = &["X", "U", "C"];
+const MODIFIED_IMPACT: &'static [&'static str] = &["X", "H", "L", "N"];
+
+/// Gets the values a metric can have, if it's a CVSS v3.1 metric
+fn get_metric_values(metric: &str) -> Option<&'static [&'static str]> {
+    match metric {
+        "AV" => Some(ATTACK_VECTOR),
+        "AC" => Some(ATTACK_COMPLEXITY),
+        "PR" => Some(PRIVILEGES),
+        "UI" => Some(USER_INTERACTION),
+        "S" => Some(SCOPE),
+        "C" | "I" | "A" => Some(IMPACT),
+        "E" => Some(EXPLOIT_MATURITY),
+        "RL" => Some(REMEDIATION_LEVEL),
+        "RC" => Some(REPORT_CONFIDENCE),
+        "CR" | "IR" | "AR" => Some(REQUIREMENT),
+        "MAV" => Some(MODIFIED_ATTACK_VECTOR),
+        "MAC" => Some(MODIFIED_ATTACK_COMPLEXITY),
+        "MPR" => Some(MODIFIED_PRIVILEGES),
+        "MUI" => Some(MODIFIED_USER_INTERACTION),
+        "MS" => Some(MODIFIED_SCOPE),
+        "MC" | "MI" | "MA" => Some(MODIFIED_IMPACT),
+        _ => None,
+    }
+}
+
+/// Gets the weight of a metric value in the formulas of the specification
+///
+/// Modified metrics use the weights of their base metrics, and undefined (`X`) values weigh 1.
+fn get_weight(metric: &str, value: &str, scope_changed: bool) -> f64 {
+    match (metric, value) {
+        ("AV", "N") => 0.85,
+        ("AV", "A") => 0.62,
+        ("AV", "L") => 0.55,
+        ("AV", "P") => 0.2,
+        ("AC", "L") => 0.77,
+        ("AC", "H") => 0.44,
+        ("PR", "N") => 0.85,
+        ("PR", "L") => if scope_changed { 0.68 } else { 0.62 },
+        ("PR", "H") => if scope_changed { 0.5 } else { 0.27 },
+        ("UI", "N") => 0.85,
+        ("UI", "R") => 0.62,
+        ("C", "H") | ("I", "H") | ("A", "H") => 0.56,
+        ("C", "L") | ("I", "L") | ("A", "L") => 0.22,
+        ("C", "N") | ("I", "N") | ("A", "N") => 0.0,
+        ("E", "F") => 0.97,
+        ("E", "P") => 0.94,
+        ("E", "U") => 0.91,
+        ("RL", "W") => 0.97,
+        ("RL", "T") => 0.96,
+        ("RL", "O") => 0.95,
+        ("RC", "R") => 0.96,
+        ("RC", "U") => 0.92,
+        ("CR", "H") | ("IR", "H") | ("AR", "H") => 1.5,
+        ("CR", "L") | ("IR", "L") | ("AR", "L") => 0.5,
+        _ => 1.0,
+    }
+}
+
+/// Parses the `/` separated metrics of a vector, checking that they are valid and not repeated
+fn parse_metrics(metrics: &str) -> Result<BTreeMap<String, String>> {
+    let mut parsed = BTreeMap::new();
+    for metric in metrics.split('/') {
+        let mut parts = metric.splitn(2, ':');
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(n), Some(v)) => (n, v),
+            _ => return Err(Error::ParseError),
+        };
+        if !get_metric_values(name).map_or(false, |values| values.contains(&value)) ||
+           parsed.insert(String::from(name), String::from(value)).is_some() {
+            return Err(Error::ParseError);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Rounds up a score to one decimal, as defined in the CVSS v3.1 specification, so that
+/// floating point errors don't change the result
+fn round_up(value: f64) -> f64 {
+    let input = (value * 100_000f64).round() as u64;
+    if input % 10_000 == 0 {
+        input as f64 / 100_000f64
+    } else {
+        (input / 10_000 + 1) as f64 / 10f64
+    }
+}
+
+/// Environmental metrics of the configuration, applied to the vectors of all the rules
+///
+/// They are written as in a vector, such as `CR:H/IR:H/MAV:N`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CvssEnvironment {
+    metrics: BTreeMap<String, String>,
+}
+
+impl CvssEnvironment {
+    /// Returns if no environmental metric is set
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+}
+
+impl FromStr for CvssEnvironment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CvssEnvironment> {
+        let metrics = try!(parse_metrics(s));
+        if metrics.keys().all(|m| ENVIRONMENTAL_METRICS.contains(&m.as_str())) {
+            Ok(CvssEnvironment { metrics: metrics })
+        } else {
+            Err(Error::ParseError)
+        }
+    }
+}
+
+/// CVSS v3.1 vector of a rule, such as `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+///
+/// Besides the base metrics, the vector can have temporal and environmental metrics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvssVector {
+    vector: String,
+    metrics: BTreeMap<String, String>,
+}
+
+impl CvssVector {
+    pub fn get_vector(&self) -> &str {
+        self.vector.as_str()
+    }
+
+    /// Gets the value of a metric, from the environment if it's set in it, or `X` if it's not
+    /// defined
+    fn get_metric<'a>(&'a self, environment: &'a CvssEnvironment, metric: &str) -> &'a str {
+        environment.metrics
+            .get(metric)
+            .or_else(|| self.metrics.get(metric))
+            .map_or("X", |v| v.as_str())
+    }
+
+    /// Gets the value of a modified base metric, or the value of the base metric if it's not
+    /// defined
+    fn get_modified<'a>(&'a self, environment: &'a CvssEnvironment, metric: &str) -> &'a str {
+        match self.get_metric(environment, &format!("M{}", metric)) {
+            "X" => self.get_metric(environment, metric),
+            value => value,
+        }
+    }
+
+    /// Gets the score of the vector, from 0.0 to 10.0, in the given environment
+    ///
+    /// If neither the vector nor the environment have environmental metrics, it's the base
+    /// score adjusted with the temporal metrics. Otherwise, it's the environmental score.
+    pub fn get_score(&self, environment: &CvssEnvironment) -> f64 {
+        let temporal = ["E", "RL", "RC"]
+            .iter()
+            .map(|m| get_weight(m, self.get_metric(environment, m), false))
+            .fold(1f64, |product, w| product * w);
+
+        if ENVIRONMENTAL_METRICS.iter().all(|m| self.get_metric(environment, m) == "X") {
+            let scope_changed = self.get_metric(environment, "S") == "C";
+            let weight = |m: &str| get_weight(m, self.get_metric(environment, m), scope_changed);
+            let iss = 1f64 - (1f64 - weight("C")) * (1f64 - weight("I")) * (1f64 - weight("A"));
+            let impact = if scope_changed {
+                7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+            } else {
+                6.42 * iss
+            };
+            let exploitability = 8.22 * weight("AV") * weight("AC") * weight("PR") * weight("UI");
+            if impact <= 0f64 {
+                return 0f64;
+            }
+            let base = if scope_changed {
+                round_up((1.08 * (impact + exploitability)).min(10f64))
+            } else {
+                round_up((impact + exploitability).min(10f64))
+            };
+            round_up(base * temporal)
+        } else {
+            let scope_changed = self.get_modified(environment, "S") == "C";
+            let weight = |m: &str| get_weight(m, self.get_modified(environment, m), scope_changed);
+            let requirement = |m: &str| get_weight(m, self.get_metric(environment, m), false);
+            let miss = (1f64 - (1f64 - requirement("CR") * weight("C")) *
+                               (1f64 - requirement("IR") * weight("I")) *
+                               (1f64 - requirement("AR") * weight("A")))
+                .min(0.915);
+            let impact = if scope_changed {
+                7.52 * (miss - 0.029) - 3.25 * (miss * 0.9731 - 0.02).powi(13)
+            } else {
+                6.42 * miss
+            };
+            let exploitability = 8.22 * weight("AV") * weight("AC") * weight("PR") * weight("UI");
+            if impact <= 0f64 {
+                return 0f64;
+            }
+            let score = if scope_changed {
+                round_up((1.08 * (impact + exploitability)).min(10f64))
+            } else {
+                round_up((impact + exploitability).min(10f64))
+            };
+            round_up(score * temporal)
+        }
+    }
+}
+
+impl FromStr for CvssVector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CvssVector> {
+        let metrics = match VECTOR_PREFIXES.iter().find(|p| s.starts_with(*p)) {
+            Some(prefix) => try!(parse_metrics(&s[prefix.len()..])),
+            None => return Err(Error::ParseError),
+        };
+        if BASE_METRICS.iter().all(|m| metrics.contains_key(*m)) {
+            Ok(CvssVector {
+                vector: String::from(s),
+                metrics: metrics,
+            })
+        } else {
+            Err(Error::ParseError)
+        }
+    }
+}
+
+/// Gets the criticity of a score, following the qualitative severity rating scale of CVSS
+///
+/// Scores of 0.0, with no severity, are reported as warnings.
+pub fn get_criticity(score: f64) -> Criticity {
+    if score >= 9f64 {
+        Criticity::Critical
+    } else if score >= 7f64 {
+        Criticity::High
+    } else if score >= 4f64 {
+        Criticity::Medium
+    } else if score > 0f64 {
+        Criticity::Low
+    } else {
+        Criticity::Warning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use Criticity;
+    use super::{CvssVector, CvssEnvironment, get_criticity};
+
+    fn get_score(vector: &str, environment: &str) -> f64 {
+        let environment = if environment.is_empty() {
+            CvssEnvironment::default()
+        } else {
+            CvssEnvironment::from_str(environment).unwrap()
+        };
+        CvssVector::from_str(vector).unwrap().get_score(&environment)
+    }
+
+    #[test]
+    fn it_base_score() {
+        assert_eq!(get_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H", ""), 9.8);
+        assert_eq!(get_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H", ""), 10.0);
+        assert_eq!(get_score("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:N/A:N", ""), 5.5);
+        assert_eq!(get_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N", ""), 6.1);
+        assert_eq!(get_score("CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N", ""), 0.0);
+    }
+
+    #[test]
+    fn it_environmental_score() {
+        let vector = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H";
+        assert_eq!(get_score(vector, "CR:L/IR:L/AR:L"), 8.0);
+        assert_eq!(get_score(vector, "CR:H/MAV:L"), 8.4);
+        // The environment of the configuration overrides the one of the vector
+        assert_eq!(get_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/MAV:N/CR:H",
+                             "CR:H/MAV:L"),
+                   8.4);
+    }
+
+    #[test]
+    fn it_invalid_vector() {
+        assert!(CvssVector::from_str("AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H").is_err());
+        assert!(CvssVector::from_str("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H").is_err());
+        assert!(CvssVector::from_str("CVSS:3.1/AV:X/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H").is_err());
+        assert!(CvssVector::from_str("CVSS:3.1/AV:N/AV:L/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H")
+            .is_err());
+        assert!(CvssEnvironment::from_str("AV:N").is_err());
+        assert!(CvssEnvironment::from_str("CR:Q").is_err());
+    }
+
+    #[test]
+    fn it_get_criticity() {
+        assert_eq!(get_criticity(9.8), Criticity::Critical);
+        assert_eq!(get_criticity(7.0), Criticity::High);
+        assert_eq!(get_criticity(6.9), Criticity::Medium);
+        assert_eq!(get_criticity(0.1), Criticity::Low);
+        assert_eq!(get_criticity(0.0), Criticity::Warning);
+    }
+}
@@ -0,0 +1,260 @@
+use std::result;
+
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use {Config, Criticity, print_vulnerability, get_code};
+use results::{Results, Vulnerability, ReportSection};
+use super::manifest::{Manifest, Component, IntentFilter};
+
+/// Order of the deep links section in the report
+const SECTION_ORDER: u32 = 60;
+
+const VIEW_ACTION: &'static str = "android.intent.action.VIEW";
+const BROWSABLE_CATEGORY: &'static str = "android.intent.category.BROWSABLE";
+
+/// URI handled by a component of the application
+pub struct DeepLink {
+    component: String,
+    uri: String,
+    app_link: bool,
+    verified: bool,
+    browsable: bool,
+}
+
+impl DeepLink {
+    pub fn get_component(&self) -> &str {
+        self.component.as_str()
+    }
+
+    pub fn get_uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    /// Checks if the link is a web link, that can be an App Link
+    pub fn is_app_link(&self) -> bool {
+        self.app_link
+    }
+
+    /// Checks if the link is an App Link with `android:autoVerify="true"`
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    /// Checks if the link can be opened from a web browser
+    pub fn is_browsable(&self) -> bool {
+        self.browsable
+    }
+}
+
+impl Serialize for DeepLink {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("DeepLink", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "component", self.component.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "uri", self.uri.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "app_link", self.app_link));
+        try!(serializer.serialize_struct_elt(&mut state, "verified", self.verified));
+        try!(serializer.serialize_struct_elt(&mut state, "browsable", self.browsable));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Checks if the intent filter handles deep links
+fn is_deep_link_filter(filter: &IntentFilter) -> bool {
+    filter.has_action(VIEW_ACTION) && !filter.get_schemes().is_empty()
+}
+
+/// Checks if the scheme is a web scheme, that can be verified as an App Link
+fn is_web_scheme(scheme: &str) -> bool {
+    scheme == "http" || scheme == "https"
+}
+
+/// Gets the deep links of the intent filter
+fn get_deep_links(component: &Component, filter: &IntentFilter) -> Vec<DeepLink> {
+    let browsable = filter.has_category(BROWSABLE_CATEGORY);
+    filter.get_uris()
+        .into_iter()
+        .map(|uri| {
+            let app_link = is_web_scheme(&uri[..uri.find(':').unwrap()]);
+            DeepLink {
+                component: String::from(component.get_name()),
+                uri: uri,
+                app_link: app_link,
+                verified: app_link && filter.is_auto_verify(),
+                browsable: browsable,
+            }
+        })
+        .collect()
+}
+
+/// Audits the deep links and App Links of the application, and lists them in the report
+pub fn deep_link_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Checking the deep links of the application.");
+    }
+
+    let mut deep_links = Vec::new();
+    for component in manifest.get_components() {
+        if !component.is_enabled() || !component.is_exported() {
+            continue;
+        }
+        for filter in component.get_intent_filters().iter().filter(|f| is_deep_link_filter(f)) {
+            audit_filter(manifest, component, filter, config, results);
+            deep_links.extend(get_deep_links(component, filter));
+        }
+    }
+
+    results.add_section(get_report_section(&deep_links));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The deep links were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Deep links analyzed.");
+    }
+}
+
+/// Checks the deep links of an intent filter
+fn audit_filter(manifest: &Manifest,
+                component: &Component,
+                filter: &IntentFilter,
+                config: &Config,
+                results: &mut Results) {
+    let schemes = filter.get_schemes();
+    let web_schemes = schemes.iter().any(|s| is_web_scheme(s));
+    let custom_schemes: Vec<_> = schemes.iter().filter(|s| !is_web_scheme(s)).collect();
+    let hosts = filter.get_hosts();
+
+    if web_schemes && !filter.is_auto_verify() {
+        add_finding(Criticity::Low,
+                    "App Link not verified",
+                    format!("The component {} handles web links to {} without \
+                             android:autoVerify=\"true\" in its intent filter. The links are \
+                             not verified as App Links, so other applications can register \
+                             the same links, and the user will be asked which application \
+                             should open them.",
+                            component.get_name(),
+                            if hosts.is_empty() {
+                                String::from("any host")
+                            } else {
+                                hosts.join(", ")
+                            }),
+                    manifest,
+                    component,
+                    config,
+                    results);
+    }
+
+    if !custom_schemes.is_empty() {
+        add_finding(Criticity::Low,
+                    "Unverified deep link",
+                    format!("The component {} handles deep links with the custom schemes {}. \
+                             Custom schemes can't be verified, so any other application can \
+                             register them and intercept the links, and the data received in \
+                             them should be considered untrusted.",
+                            component.get_name(),
+                            custom_schemes.iter()
+                                .map(|s| format!("{}://", s))
+                                .collect::<Vec<_>>()
+                                .join(", ")),
+                    manifest,
+                    component,
+                    config,
+                    results);
+    }
+
+    for host in &hosts {
+        let (criticity, description) = if host == "*" || host.starts_with("*:") {
+            (Criticity::Medium,
+             format!("The component {} handles deep links to any host. Links to any web page \
+                      or domain, including those controlled by an attacker, will be opened by \
+                      the application.",
+                     component.get_name()))
+        } else if host.starts_with("*.") {
+            (Criticity::Low,
+             format!("The component {} handles deep links to all the subdomains of {}. If any \
+                      of the subdomains can be controlled by a third party, it could be used \
+                      to send crafted links to the application.",
+                     component.get_name(),
+                     &host[2..]))
+        } else {
+            continue;
+        };
+        add_finding(criticity,
+                    "Deep link with wildcard host",
+                    description,
+                    manifest,
+                    component,
+                    config,
+                    results);
+    }
+}
+
+/// Adds a finding for the given component
+fn add_finding(criticity: Criticity,
+               name: &str,
+               description: String,
+               manifest: &Manifest,
+               component: &Component,
+               config: &Config,
+               results: &mut Results) {
+    let line = component.get_line();
+    let code = match line {
+        Some(l) => Some(get_code(manifest.get_code(), l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(name),
+                                  description,
+                                  Some("AndroidManifest.xml"),
+                                  line,
+                                  line,
+                                  code);
+    results.add_vulnerability(vuln);
+}
+
+/// Generates the deep links section of the report
+fn get_report_section(deep_links: &[DeepLink]) -> ReportSection {
+    let mut section = ReportSection::new("deep_links", "Deep links", SECTION_ORDER);
+    section.set_data(&deep_links);
+    let rows: Vec<_> = deep_links.iter()
+        .map(|link| {
+            vec![Results::html_escape(link.get_uri()),
+                 Results::html_escape(link.get_component()),
+                 String::from(Results::html_yes_no(link.is_browsable())),
+                 String::from(if !link.is_app_link() {
+                     "Not verifiable"
+                 } else {
+                     Results::html_yes_no(link.is_verified())
+                 })]
+        })
+        .collect();
+    section.set_html_table(&["URI", "Component", "Browsable", "Verified"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_web_scheme;
+
+    #[test]
+    fn it_is_web_scheme() {
+        assert!(is_web_scheme("https"));
+        assert!(is_web_scheme("http"));
+        assert!(!is_web_scheme("myapp"));
+        assert!(!is_web_scheme("HTTPS"));
+    }
+}
@@ -0,0 +1,495 @@
+use std::{fs, result};
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, Serializer};
+use regex::Regex;
+use colored::Colorize;
+
+use Config;
+use dex::DexFile;
+use results::{Results, ReportSection};
+use super::read_text_file;
+
+/// Order of the dependencies section in the report
+const SECTION_ORDER: u32 = 80;
+
+/// Folders where apktool leaves the `META-INF` files of the application
+const META_INF_FOLDERS: [&'static str; 2] = ["original/META-INF", "unknown/META-INF"];
+
+/// String literals with a version number, optionally prefixed, as in `"okhttp/3.12.0"`
+const VERSION_REGEX: &'static str = r#""(?:[\w-]+/)?(\d+\.\d+(?:\.\d+)*(?:[-.][\w.]+)?)""#;
+
+/// Fingerprint of a third party library or SDK
+pub struct Library {
+    name: &'static str,
+    category: &'static str,
+    /// Packages of the library, as paths
+    packages: &'static [&'static str],
+    /// Prefixes of the `.version` files that Gradle adds to `META-INF` for the library
+    artifacts: &'static [&'static str],
+    /// Class that identifies the library, and that contains its version
+    fingerprint: Option<&'static str>,
+}
+
+/// Libraries and SDKs that can be detected in the applications
+const LIBRARIES: &'static [Library] = &[Library {
+                                             name: "Firebase",
+                                             category: "Backend",
+                                             packages: &["com/google/firebase"],
+                                             artifacts: &["com.google.firebase_firebase-common"],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Firebase Analytics",
+                                             category: "Analytics",
+                                             packages: &["com/google/firebase/analytics"],
+                                             artifacts: &["com.google.firebase_firebase-analytics"],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Firebase Crashlytics",
+                                             category: "Crash reporting",
+                                             packages: &["com/google/firebase/crashlytics"],
+                                             artifacts: &["com.google.firebase_firebase-\
+                                                           crashlytics"],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Google Play services",
+                                             category: "Backend",
+                                             packages: &["com/google/android/gms"],
+                                             artifacts: &["com.google.android.gms_play-services-\
+                                                           basement"],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Google Mobile Ads",
+                                             category: "Advertising",
+                                             packages: &["com/google/android/gms/ads"],
+                                             artifacts: &["com.google.android.gms_play-services-\
+                                                           ads"],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Facebook SDK",
+                                             category: "Social",
+                                             packages: &["com/facebook/login",
+                                                         "com/facebook/share"],
+                                             artifacts: &["com.facebook.android_facebook-core"],
+                                             fingerprint: Some("com/facebook/FacebookSdkVersion"),
+                                         },
+                                         Library {
+                                             name: "Facebook Audience Network",
+                                             category: "Advertising",
+                                             packages: &["com/facebook/ads"],
+                                             artifacts: &["com.facebook.android_audience-\
+                                                           network-sdk"],
+                                             fingerprint: Some("com/facebook/ads/BuildConfig"),
+                                         },
+                                         Library {
+                                             name: "Crashlytics",
+                                             category: "Crash reporting",
+                                             packages: &["com/crashlytics/android"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Sentry",
+                                             category: "Crash reporting",
+                                             packages: &["io/sentry"],
+                                             artifacts: &["io.sentry_sentry-android"],
+                                             fingerprint: Some("io/sentry/android/core/\
+                                                                BuildConfig"),
+                                         },
+                                         Library {
+                                             name: "Bugsnag",
+                                             category: "Crash reporting",
+                                             packages: &["com/bugsnag/android"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "ACRA",
+                                             category: "Crash reporting",
+                                             packages: &["org/acra"],
+                                             artifacts: &[],
+                                             fingerprint: Some("org/acra/BuildConfig"),
+                                         },
+                                         Library {
+                                             name: "Flurry",
+                                             category: "Analytics",
+                                             packages: &["com/flurry/android"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "AppsFlyer",
+                                             category: "Analytics",
+                                             packages: &["com/appsflyer"],
+                                             artifacts: &[],
+                                             fingerprint: Some("com/appsflyer/BuildConfig"),
+                                         },
+                                         Library {
+                                             name: "Adjust",
+                                             category: "Analytics",
+                                             packages: &["com/adjust/sdk"],
+                                             artifacts: &[],
+                                             fingerprint: Some("com/adjust/sdk/Constants"),
+                                         },
+                                         Library {
+                                             name: "Mixpanel",
+                                             category: "Analytics",
+                                             packages: &["com/mixpanel/android"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Unity Ads",
+                                             category: "Advertising",
+                                             packages: &["com/unity3d/ads"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "AppLovin",
+                                             category: "Advertising",
+                                             packages: &["com/applovin"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Chartboost",
+                                             category: "Advertising",
+                                             packages: &["com/chartboost/sdk"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "ironSource",
+                                             category: "Advertising",
+                                             packages: &["com/ironsource"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "MoPub",
+                                             category: "Advertising",
+                                             packages: &["com/mopub"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "InMobi",
+                                             category: "Advertising",
+                                             packages: &["com/inmobi"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Vungle",
+                                             category: "Advertising",
+                                             packages: &["com/vungle"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "OneSignal",
+                                             category: "Push notifications",
+                                             packages: &["com/onesignal"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "OkHttp",
+                                             category: "Networking",
+                                             packages: &["okhttp3"],
+                                             artifacts: &[],
+                                             fingerprint: Some("okhttp3/internal/Version"),
+                                         },
+                                         Library {
+                                             name: "Retrofit",
+                                             category: "Networking",
+                                             packages: &["retrofit2"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Gson",
+                                             category: "Utility",
+                                             packages: &["com/google/gson"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Glide",
+                                             category: "Utility",
+                                             packages: &["com/bumptech/glide"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Picasso",
+                                             category: "Utility",
+                                             packages: &["com/squareup/picasso"],
+                                             artifacts: &[],
+                                             fingerprint: None,
+                                         },
+                                         Library {
+                                             name: "Apache Cordova",
+                                             category: "Framework",
+                                             packages: &["org/apache/cordova"],
+                                             artifacts: &[],
+                                             fingerprint: Some("org/apache/cordova/\
+                                                                CordovaWebView"),
+                                         },
+                                         Library {
+                                             name: "React Native",
+                                             category: "Framework",
+                                             packages: &["com/facebook/react"],
+                                             artifacts: &[],
+                                             fingerprint: Some("com/facebook/react/modules/\
+                                                                systeminfo/\
+                                                                ReactNativeVersion"),
+                                         }];
+
+/// Third party library or SDK detected in the application
+pub struct Dependency {
+    name: String,
+    category: String,
+    version: Option<String>,
+    evidence: Vec<String>,
+}
+
+impl Dependency {
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn get_category(&self) -> &str {
+        self.category.as_str()
+    }
+
+    /// Gets the version of the library, if it could be found
+    pub fn get_version(&self) -> Option<&str> {
+        match self.version {
+            Some(ref v) => Some(v.as_str()),
+            None => None,
+        }
+    }
+
+    /// Gets the packages, classes and files that show that the library is bundled
+    pub fn get_evidence(&self) -> &[String] {
+        self.evidence.as_slice()
+    }
+}
+
+impl Serialize for Dependency {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Dependency", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "category", self.category.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "version", self.get_version()));
+        try!(serializer.serialize_struct_elt(&mut state, "evidence", &self.evidence));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Detects the library from the decompiled classes and the `.version` files of `META-INF`,
+/// indexed by file name. `exists` checks if a path exists in the classes folder.
+fn detect_library(library: &Library,
+                  exists: &Fn(&str) -> bool,
+                  artifacts: &BTreeMap<String, String>)
+                  -> Option<Dependency> {
+    let mut evidence = Vec::new();
+    let mut version = None;
+
+    for package in library.packages {
+        if exists(package) {
+            evidence.push(format!("package {}", package.replace('/', ".")));
+        }
+    }
+    if let Some(fingerprint) = library.fingerprint {
+        if exists(&format!("{}.java", fingerprint)) {
+            evidence.push(format!("class {}", fingerprint.replace('/', ".")));
+        }
+    }
+    for artifact in library.artifacts {
+        for (file, content) in artifacts {
+            if file.starts_with(artifact) && file.ends_with(".version") {
+                evidence.push(format!("META-INF/{}", file));
+                if version.is_none() && !content.trim().is_empty() {
+                    version = Some(String::from(content.trim()));
+                }
+            }
+        }
+    }
+
+    if evidence.is_empty() {
+        None
+    } else {
+        Some(Dependency {
+            name: String::from(library.name),
+            category: String::from(library.category),
+            version: version,
+            evidence: evidence,
+        })
+    }
+}
+
+/// Gets the first version number found in the code of a class
+fn get_class_version(code: &str, version_regex: &Regex) -> Option<String> {
+    version_regex.captures(code).and_then(|caps| caps.at(1).map(String::from))
+}
+
+/// Reads the `.version` files of the `META-INF` folders of the application
+fn get_artifacts(dist_folder: &Path) -> BTreeMap<String, String> {
+    let mut artifacts = BTreeMap::new();
+    for folder in &META_INF_FOLDERS {
+        let entries = match fs::read_dir(dist_folder.join(folder)) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".version") {
+                continue;
+            }
+            if let Ok(Some(content)) = read_text_file(&entry.path()) {
+                let _ = artifacts.insert(name, content);
+            }
+        }
+    }
+    artifacts
+}
+
+/// Identifies the third party libraries and SDKs bundled in the application, and lists them in
+/// the dependencies section of the report
+///
+/// If the code was not decompiled, the packages and classes are looked for in the DEX files.
+pub fn dependency_analysis(dex_files: &[DexFile],
+                           config: &Config,
+                           results: &mut Results)
+                           -> Vec<Dependency> {
+    if config.is_verbose() {
+        println!("Looking for the third party libraries bundled in the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let classes_folder = dist_folder.join("classes");
+    let exists = |path: &str| {
+        classes_folder.join(path).exists() ||
+        dex_files.iter().any(|dex| if path.ends_with(".java") {
+            dex.has_class(path.trim_right_matches(".java"))
+        } else {
+            dex.has_package(path)
+        })
+    };
+    let artifacts = get_artifacts(&dist_folder);
+    let version_regex = Regex::new(VERSION_REGEX).unwrap();
+
+    let mut dependencies = Vec::new();
+    for library in LIBRARIES {
+        let mut dependency = match detect_library(library, &exists, &artifacts) {
+            Some(d) => d,
+            None => continue,
+        };
+        if dependency.version.is_none() {
+            if let Some(fingerprint) = library.fingerprint {
+                let path = classes_folder.join(format!("{}.java", fingerprint));
+                if let Ok(Some(code)) = read_text_file(&path) {
+                    dependency.version = get_class_version(&code, &version_regex);
+                }
+            }
+        }
+        if config.is_verbose() {
+            println!("Found {} {}.",
+                     dependency.get_name(),
+                     dependency.get_version().unwrap_or("(unknown version)"));
+        }
+        dependencies.push(dependency);
+    }
+
+    results.add_section(get_report_section(&dependencies));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The dependencies were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Dependencies analyzed.");
+    }
+    dependencies
+}
+
+/// Generates the dependencies section of the report
+fn get_report_section(dependencies: &[Dependency]) -> ReportSection {
+    let mut section = ReportSection::new("dependencies", "Dependencies", SECTION_ORDER);
+    section.set_data(&dependencies);
+    let rows: Vec<_> = dependencies.iter()
+        .map(|dependency| {
+            vec![Results::html_escape(dependency.get_name()),
+                 Results::html_escape(dependency.get_category()),
+                 Results::html_escape(dependency.get_version().unwrap_or("Unknown")),
+                 Results::html_escape(&dependency.get_evidence().join("\n"))
+                     .replace("\n", "<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Library", "Category", "Version", "Evidence"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use regex::Regex;
+    use super::{LIBRARIES, VERSION_REGEX, detect_library, get_class_version};
+
+    fn find(name: &str) -> &'static super::Library {
+        LIBRARIES.iter().find(|l| l.name == name).unwrap()
+    }
+
+    #[test]
+    fn it_detect_library() {
+        let classes = ["com/google/firebase",
+                       "okhttp3",
+                       "okhttp3/internal/Version.java",
+                       "com/example/MainActivity.java"];
+        let exists = |p: &str| classes.contains(&p);
+        let mut artifacts = BTreeMap::new();
+        let _ = artifacts.insert(String::from("com.google.firebase_firebase-common.version"),
+                                 String::from("16.0.2\n"));
+
+        let firebase = detect_library(find("Firebase"), &exists, &artifacts).unwrap();
+        assert_eq!(firebase.get_version(), Some("16.0.2"));
+        assert_eq!(firebase.get_evidence(),
+                   &[String::from("package com.google.firebase"),
+                     String::from("META-INF/com.google.firebase_firebase-common.version")]);
+
+        let okhttp = detect_library(find("OkHttp"), &exists, &artifacts).unwrap();
+        assert_eq!(okhttp.get_version(), None);
+        assert_eq!(okhttp.get_evidence().len(), 2);
+
+        assert!(detect_library(find("Firebase Crashlytics"), &exists, &artifacts).is_none());
+        assert!(detect_library(find("Retrofit"), &exists, &artifacts).is_none());
+    }
+
+    #[test]
+    fn it_get_class_version() {
+        let regex = Regex::new(VERSION_REGEX).unwrap();
+        assert_eq!(get_class_version("public static String userAgent() {\n    return \
+                                      \"okhttp/3.12.1\";\n}",
+                                     &regex),
+                   Some(String::from("3.12.1")));
+        assert_eq!(get_class_version("public static final String VERSION_NAME = \
+                                      \"4.42.0-beta\";",
+                                     &regex),
+                   Some(String::from("4.42.0-beta")));
+        assert_eq!(get_class_version("String name = \"release\";", &regex), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{fs, result};
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use {Config, Result, print_warning, file_exists};
+use archive::{Archive, Extraction};
+use results::{Results, ReportSection};
+
+/// Order of the expansion files section in the report
+const SECTION_ORDER: u32 = 150;
+
+/// Folder of the decompression folder where the expansion files are extracted
+pub const EXPANSION_FOLDER: &'static str = "expansion";
+
+/// Kind of expansion file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionKind {
+    /// APK expansion file, named `{main|patch}.{version}.{package}.obb`
+    Obb,
+    /// Asset pack, named `{package}-{pack}.apk`
+    AssetPack,
+}
+
+impl ExpansionKind {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ExpansionKind::Obb => "obb",
+            ExpansionKind::AssetPack => "asset_pack",
+        }
+    }
+}
+
+/// Gets the kind of expansion file of the given application, or `None` if the file is not an
+/// expansion file of the application
+fn get_expansion_kind(file_name: &str, package: &str) -> Option<ExpansionKind> {
+    if file_name.ends_with(".obb") {
+        let name = &file_name[..file_name.len() - 4];
+        let mut parts = name.splitn(3, '.');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("main"), Some(version), Some(p)) |
+            (Some("patch"), Some(version), Some(p)) if p == package && !version.is_empty() &&
+                                                       version.chars()
+                                                           .all(|c| c.is_digit(10)) => {
+                Some(ExpansionKind::Obb)
+            }
+            _ => None,
+        }
+    } else if file_name.ends_with(".apk") && file_name.starts_with(package) &&
+              file_name[package.len()..].starts_with('-') &&
+              file_name.len() > package.len() + ".apk".len() + 1 {
+        Some(ExpansionKind::AssetPack)
+    } else {
+        None
+    }
+}
+
+/// Expansion file found alongside the application package
+pub struct ExpansionFile {
+    file_name: String,
+    kind: ExpansionKind,
+    extraction: Option<Extraction>,
+    error: Option<String>,
+}
+
+impl ExpansionFile {
+    /// Gets the status of the expansion file, to show in the report
+    pub fn get_status(&self) -> String {
+        match (&self.extraction, &self.error) {
+            (_, &Some(ref e)) => format!("Not analyzed: {}", e),
+            (&Some(ref extraction), &None) => {
+                match extraction.get_violation() {
+                    Some(v) => format!("Partially analyzed: {}", v),
+                    None => String::from("Analyzed"),
+                }
+            }
+            (&None, &None) => String::from("Analyzed in a previous run"),
+        }
+    }
+}
+
+impl Serialize for ExpansionFile {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("ExpansionFile", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "file", self.file_name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "kind", self.kind.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "entries",
+                                             self.extraction
+                                                 .as_ref()
+                                                 .map(|e| e.get_entries())));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "size",
+                                             self.extraction.as_ref().map(|e| e.get_size())));
+        try!(serializer.serialize_struct_elt(&mut state, "status", self.get_status()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Extracts the expansion files of the application so that the text based analyses of the
+/// assets, such as the secret detection and the endpoint extraction, also check them
+///
+/// The expansion files must be in the downloads folder, next to the application package. They
+/// are extracted to the `expansion/{file name}` folder of the decompression folder, so findings
+/// in them are attributed to the expansion file.
+pub fn expansion_analysis(config: &Config, results: &mut Results) {
+    let expansion_files = match get_expansion_files(config) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("There was an error looking for expansion files. They will \
+                                   not be analyzed. More info: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+    if expansion_files.is_empty() {
+        if config.is_verbose() {
+            println!("The application does not have expansion files.");
+        }
+        return;
+    }
+
+    if config.is_verbose() {
+        println!("Extracting the expansion files of the application, so that their assets are \
+                  also analyzed.");
+    }
+
+    let expansion_folder = PathBuf::from(format!("{}/{}/{}",
+                                                 config.get_dist_folder(),
+                                                 config.get_app_id(),
+                                                 EXPANSION_FOLDER));
+    let mut analyzed = Vec::with_capacity(expansion_files.len());
+    for (path, kind) in expansion_files {
+        let file_name = String::from(path.file_name().unwrap().to_string_lossy());
+        let to = expansion_folder.join(&file_name);
+        let mut expansion_file = ExpansionFile {
+            file_name: file_name.clone(),
+            kind: kind,
+            extraction: None,
+            error: None,
+        };
+
+        if !file_exists(&to) {
+            match extract_expansion_file(&path, &to, config) {
+                Ok(extraction) => {
+                    if let Some(violation) = extraction.get_violation() {
+                        print_warning(format!("The extraction of the expansion file {} was \
+                                               stopped, since {}. It will only be partially \
+                                               analyzed.",
+                                              file_name,
+                                              violation),
+                                      config.is_verbose());
+                    }
+                    expansion_file.extraction = Some(extraction);
+                }
+                Err(e) => {
+                    print_warning(format!("The expansion file {} could not be extracted, it \
+                                           might not be a ZIP archive. It will not be \
+                                           analyzed. More info: {}",
+                                          file_name,
+                                          e),
+                                  config.is_verbose());
+                    expansion_file.error = Some(format!("{}", e));
+                }
+            }
+        }
+        analyzed.push(expansion_file);
+    }
+
+    results.add_section(get_report_section(&analyzed));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The expansion files were extracted correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Expansion files extracted.");
+    }
+}
+
+/// Gets the expansion files of the application in the downloads folder
+fn get_expansion_files(config: &Config) -> Result<Vec<(PathBuf, ExpansionKind)>> {
+    let mut files = Vec::new();
+    for f in try!(fs::read_dir(config.get_downloads_folder())) {
+        let f = try!(f);
+        if !try!(f.file_type()).is_file() {
+            continue;
+        }
+        let kind = match f.file_name().to_str() {
+            Some(name) => get_expansion_kind(name, config.get_app_id()),
+            None => None,
+        };
+        if let Some(kind) = kind {
+            files.push((f.path(), kind));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Extracts the given expansion file in the given folder
+fn extract_expansion_file(path: &Path, to: &Path, config: &Config) -> Result<Extraction> {
+    let mut archive = try!(Archive::open(path, config.get_archive_config()));
+    try!(fs::create_dir_all(to));
+    archive.extract(to)
+}
+
+/// Generates the expansion files section of the report
+fn get_report_section(expansion_files: &[ExpansionFile]) -> ReportSection {
+    let mut section = ReportSection::new("expansion_files", "Expansion files", SECTION_ORDER);
+    section.set_data(&expansion_files);
+    let rows: Vec<_> = expansion_files.iter()
+        .map(|file| {
+            vec![Results::html_escape(&file.file_name),
+                 String::from(match file.kind {
+                     ExpansionKind::Obb => "OBB",
+                     ExpansionKind::AssetPack => "Asset pack",
+                 }),
+                 file.extraction
+                     .as_ref()
+                     .map(|e| format!("{}", e.get_entries()))
+                     .unwrap_or(String::new()),
+                 Results::html_escape(&file.get_status())]
+        })
+        .collect();
+    section.set_html_table(&["File", "Kind", "Entries", "Status"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpansionKind, get_expansion_kind};
+
+    #[test]
+    fn it_get_expansion_kind() {
+        assert_eq!(get_expansion_kind("main.314.com.example.game.obb", "com.example.game"),
+                   Some(ExpansionKind::Obb));
+        assert_eq!(get_expansion_kind("patch.315.com.example.game.obb", "com.example.game"),
+                   Some(ExpansionKind::Obb));
+        assert_eq!(get_expansion_kind("com.example.game-textures.apk", "com.example.game"),
+                   Some(ExpansionKind::AssetPack));
+
+        assert_eq!(get_expansion_kind("main.314.com.example.other.obb", "com.example.game"),
+                   None);
+        assert_eq!(get_expansion_kind("extra.314.com.example.game.obb", "com.example.game"),
+                   None);
+        assert_eq!(get_expansion_kind("main.v1.com.example.game.obb", "com.example.game"),
+                   None);
+        assert_eq!(get_expansion_kind("com.example.game.apk", "com.example.game"), None);
+        assert_eq!(get_expansion_kind("com.example.game.pro.apk", "com.example.game"), None);
+        assert_eq!(get_expansion_kind("com.example.game-.apk", "com.example.game"), None);
+    }
+}
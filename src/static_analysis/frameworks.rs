@@ -0,0 +1,465 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::result;
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Result, Criticity, print_warning, print_vulnerability, get_code, file_exists};
+use archive::Archive;
+use results::{Results, Vulnerability, ReportSection, RuleMetadata};
+use super::code::get_line_for;
+use super::native::{NativeLibrary, extract_strings, has_library};
+use super::{read_text_file, MAX_FILE_SIZE};
+
+/// Order of the frameworks section in the report
+const SECTION_ORDER: u32 = 98;
+
+/// Maximum size of the JavaScript bundles that will be scanned
+const MAX_BUNDLE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Minimum length of the strings extracted from the bundles and compiled code
+const MIN_STRING_LENGTH: usize = 6;
+
+/// Magic number of the Hermes bytecode bundles of React Native
+const HERMES_MAGIC: [u8; 8] = [0xc6, 0x1f, 0xbc, 0x03, 0xc1, 0x03, 0x19, 0x1f];
+
+/// Configuration file of Cordova applications
+const CORDOVA_CONFIG: &'static str = "res/xml/config.xml";
+
+/// Cross-platform framework the application was built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Framework {
+    ReactNative,
+    Flutter,
+    Cordova,
+}
+
+impl Framework {
+    pub fn get_id(&self) -> &'static str {
+        match *self {
+            Framework::ReactNative => "react_native",
+            Framework::Flutter => "flutter",
+            Framework::Cordova => "cordova",
+        }
+    }
+
+    pub fn get_label(&self) -> &'static str {
+        match *self {
+            Framework::ReactNative => "React Native",
+            Framework::Flutter => "Flutter",
+            Framework::Cordova => "Cordova",
+        }
+    }
+}
+
+/// Framework detected in the application, with the files that show it and the notes about its
+/// analysis
+struct FrameworkInfo {
+    framework: Framework,
+    evidence: Vec<String>,
+    notes: Vec<String>,
+}
+
+impl Serialize for FrameworkInfo {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("FrameworkInfo", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.framework.get_id()));
+        try!(serializer.serialize_struct_elt(&mut state, "label", self.framework.get_label()));
+        try!(serializer.serialize_struct_elt(&mut state, "evidence", &self.evidence));
+        try!(serializer.serialize_struct_elt(&mut state, "notes", &self.notes));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the JavaScript bundles of React Native in the assets folder
+fn get_bundles(dist_folder: &Path) -> Vec<PathBuf> {
+    let mut bundles: Vec<PathBuf> = match fs::read_dir(dist_folder.join("assets")) {
+        Ok(entries) => {
+            entries.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |e| e == "bundle"))
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    };
+    bundles.sort();
+    bundles
+}
+
+/// Detects the React Native, Flutter and Cordova frameworks, with the files that show them
+fn detect_frameworks(dist_folder: &Path,
+                     libraries: &[NativeLibrary])
+                     -> Vec<(Framework, Vec<String>)> {
+    let mut frameworks = Vec::new();
+
+    let mut react_native: Vec<String> = get_bundles(dist_folder)
+        .iter()
+        .map(|b| b.strip_prefix(dist_folder).unwrap().to_string_lossy().into_owned())
+        .collect();
+    for library in &["libreactnativejni.so", "libhermes.so"] {
+        if has_library(libraries, library) {
+            react_native.push(format!("lib/{}", library));
+        }
+    }
+    if file_exists(dist_folder.join("classes/com/facebook/react")) {
+        react_native.push(String::from("classes/com/facebook/react"));
+    }
+    if !react_native.is_empty() {
+        frameworks.push((Framework::ReactNative, react_native));
+    }
+
+    let mut flutter = Vec::new();
+    for library in &["libflutter.so", "libapp.so"] {
+        if has_library(libraries, library) {
+            flutter.push(format!("lib/{}", library));
+        }
+    }
+    if file_exists(dist_folder.join("assets/flutter_assets")) {
+        flutter.push(String::from("assets/flutter_assets"));
+    }
+    if !flutter.is_empty() {
+        frameworks.push((Framework::Flutter, flutter));
+    }
+
+    let cordova: Vec<String> = ["assets/www/cordova.js", "assets/www/index.html", CORDOVA_CONFIG]
+        .iter()
+        .filter(|f| file_exists(dist_folder.join(f)))
+        .map(|f| String::from(*f))
+        .collect();
+    if file_exists(dist_folder.join("assets/www/cordova.js")) {
+        frameworks.push((Framework::Cordova, cordova));
+    }
+
+    frameworks
+}
+
+/// Gets the strings as string literals, one per line, so that they can be scanned for secrets and
+/// endpoints as if they were code
+pub fn get_strings_code<I: IntoIterator<Item = String>>(strings: I) -> String {
+    let mut code = String::new();
+    for string in strings {
+        code.push('"');
+        code.push_str(&string.replace('"', "\\\""));
+        code.push_str("\"\n");
+    }
+    code
+}
+
+/// Extracts the string literals of minified JavaScript code
+fn extract_js_strings(code: &str) -> Vec<String> {
+    let regex = Regex::new(r#""((?:[^"\\\n]|\\.){6,})"|'((?:[^'\\\n]|\\.){6,})'"#).unwrap();
+    regex.captures_iter(code)
+        .filter_map(|caps| caps.at(1).or_else(|| caps.at(2)).map(String::from))
+        .collect()
+}
+
+/// Reads a React Native bundle, and gets its strings as code for the secret and endpoint
+/// analyses, and a note about it
+///
+/// Plain JavaScript bundles small enough to be scanned as assets are not returned, to avoid
+/// reporting their secrets and endpoints twice.
+fn read_bundle(path: &Path, relative: &str) -> Result<(Option<String>, String)> {
+    let mut f = try!(File::open(path));
+    let size = try!(f.metadata()).len();
+    if size > MAX_BUNDLE_SIZE {
+        return Ok((None,
+                   format!("The {} bundle is too big to be scanned ({} MB).",
+                           relative,
+                           size / 1024 / 1024)));
+    }
+    let mut bytes = Vec::with_capacity(size as usize);
+    try!(f.read_to_end(&mut bytes));
+    if bytes.starts_with(&HERMES_MAGIC) {
+        let strings = extract_strings(&bytes, MIN_STRING_LENGTH);
+        let note = format!("The {} bundle is compiled to Hermes bytecode. Its {} strings were \
+                            scanned for secrets and endpoints.",
+                           relative,
+                           strings.len());
+        Ok((Some(get_strings_code(strings)), note))
+    } else if size > MAX_FILE_SIZE || bytes.iter().take(8192).any(|b| *b == 0) {
+        let strings = extract_js_strings(&String::from_utf8_lossy(&bytes));
+        let note = format!("The {} JavaScript bundle is too big to be scanned as an asset. Its \
+                            {} string literals were scanned for secrets and endpoints.",
+                           relative,
+                           strings.len());
+        Ok((Some(get_strings_code(strings)), note))
+    } else {
+        Ok((None,
+            format!("The {} JavaScript bundle was scanned for secrets and endpoints as an \
+                     asset.",
+                    relative)))
+    }
+}
+
+/// Reads the strings of the first `libapp.so` library of the package, with the AOT-compiled
+/// Dart code of Flutter applications
+fn read_dart_strings(libraries: &[NativeLibrary], config: &Config) -> Result<Option<String>> {
+    let path = match libraries.iter()
+        .map(|l| l.get_path())
+        .find(|p| Path::new(p).file_name().map_or(false, |n| n == "libapp.so")) {
+        Some(p) => String::from(p),
+        None => return Ok(None),
+    };
+    let mut apk = try!(Archive::open(format!("{}/{}.apk",
+                                             config.get_downloads_folder(),
+                                             config.get_app_id()),
+                                     config.get_archive_config()));
+    let bytes = try!(apk.read(&path));
+    Ok(Some(get_strings_code(extract_strings(&bytes, MIN_STRING_LENGTH))))
+}
+
+/// Entry of the Cordova whitelist, in the `config.xml` file
+#[derive(Debug, PartialEq)]
+struct WhitelistEntry<'a> {
+    element: &'a str,
+    value: &'a str,
+    index: usize,
+}
+
+/// Gets the `access`, `allow-navigation` and `allow-intent` entries of the Cordova whitelist
+fn get_whitelist(config_xml: &str) -> Vec<WhitelistEntry> {
+    let regex = Regex::new(concat!(r"<(access|allow-navigation|allow-intent)\s",
+                                   r#"[^>]*?(?:origin|href)\s*=\s*"([^"]*)""#))
+        .unwrap();
+    regex.captures_iter(config_xml)
+        .map(|caps| {
+            WhitelistEntry {
+                element: caps.at(1).unwrap(),
+                value: caps.at(2).unwrap(),
+                index: caps.pos(0).unwrap().0,
+            }
+        })
+        .collect()
+}
+
+/// Checks if the whitelist value allows any host
+fn is_wildcard(value: &str) -> bool {
+    value == "*" || value.contains("://*/") || value.ends_with("://*") || value == "*:*"
+}
+
+/// Gets the finding of a whitelist entry, as its criticity, name and description, if it's
+/// insecure
+fn check_whitelist_entry(entry: &WhitelistEntry) -> Option<(Criticity, &'static str, String)> {
+    match entry.element {
+        "allow-navigation" if is_wildcard(entry.value) => {
+            Some((Criticity::High,
+                  "Cordova navigation to any site",
+                  format!("The Cordova whitelist lets the WebView of the application navigate \
+                           to any site with <allow-navigation href=\"{}\">. The pages loaded \
+                           in it can call the plugins of the application through the Cordova \
+                           bridge.",
+                          entry.value)))
+        }
+        "access" if is_wildcard(entry.value) => {
+            Some((Criticity::Medium,
+                  "Cordova network access to any origin",
+                  format!("The Cordova whitelist lets the application make network requests \
+                           to any origin with <access origin=\"{}\">, so injected scripts \
+                           can send data to any server.",
+                          entry.value)))
+        }
+        _ if entry.value.starts_with("http://") => {
+            Some((Criticity::Low,
+                  "Cordova cleartext whitelist entry",
+                  format!("The Cordova whitelist allows the HTTP URL {} in <{}>, so its \
+                           content can be read and modified by anyone in the network path.",
+                          entry.value,
+                          entry.element)))
+        }
+        _ => None,
+    }
+}
+
+/// Checks the whitelist of the `config.xml` file of a Cordova application, and gets a note with
+/// the number of entries
+fn check_cordova_whitelist(dist_folder: &Path, config: &Config, results: &mut Results) -> String {
+    let config_xml = match read_text_file(&dist_folder.join(CORDOVA_CONFIG)) {
+        Ok(Some(c)) => c,
+        _ => return format!("The {} file could not be read.", CORDOVA_CONFIG),
+    };
+    let whitelist = get_whitelist(&config_xml);
+    for entry in &whitelist {
+        if let Some((criticity, name, description)) = check_whitelist_entry(entry) {
+            if config.is_verbose() {
+                print_vulnerability(description.as_str(), criticity);
+            }
+            let line = get_line_for(entry.index, &config_xml);
+            let mut vuln = Vulnerability::new(criticity,
+                                              name,
+                                              description,
+                                              Some(CORDOVA_CONFIG),
+                                              Some(line),
+                                              Some(line),
+                                              Some(get_code(&config_xml, line, line)));
+            vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-942")],
+                                                vec![String::from("MASVS-PLATFORM-2")],
+                                                vec![String::from("M8")],
+                                                Vec::new(),
+                                                Some(String::from("List only the HTTPS \
+                                                                   origins the application \
+                                                                   needs in the whitelist, \
+                                                                   and add a Content \
+                                                                   Security Policy to the \
+                                                                   pages."))));
+            results.add_vulnerability(vuln);
+        }
+    }
+    format!("{} entries in the whitelist of {}.", whitelist.len(), CORDOVA_CONFIG)
+}
+
+/// Detects the cross-platform frameworks the application was built with, and runs the passes
+/// specific to each of them, returning the code extracted from their bundles and compiled code
+/// for the secret and endpoint analyses
+///
+/// The strings of the React Native bundles and of the AOT-compiled Dart code of Flutter are
+/// returned with their files, and the whitelist of Cordova applications is checked.
+pub fn framework_analysis(libraries: &[NativeLibrary],
+                          config: &Config,
+                          results: &mut Results)
+                          -> Vec<(PathBuf, String)> {
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let frameworks = detect_frameworks(&dist_folder, libraries);
+    if frameworks.is_empty() {
+        if config.is_verbose() {
+            println!("The application was not built with a cross-platform framework.");
+        }
+        return Vec::new();
+    }
+
+    let mut extracted = Vec::new();
+    let mut infos = Vec::with_capacity(frameworks.len());
+    for (framework, evidence) in frameworks {
+        if config.is_verbose() {
+            println!("The application was built with {}.", framework.get_label());
+        }
+        let mut notes = Vec::new();
+        match framework {
+            Framework::ReactNative => {
+                for bundle in get_bundles(&dist_folder) {
+                    let relative = bundle.strip_prefix(&dist_folder).unwrap().to_path_buf();
+                    match read_bundle(&bundle, &relative.to_string_lossy()) {
+                        Ok((code, note)) => {
+                            if let Some(code) = code {
+                                extracted.push((relative, code));
+                            }
+                            notes.push(note);
+                        }
+                        Err(e) => {
+                            print_warning(format!("The React Native bundle {} could not be \
+                                                   read: {}",
+                                                  relative.display(),
+                                                  e),
+                                          config.is_verbose())
+                        }
+                    }
+                }
+            }
+            Framework::Flutter => {
+                notes.push(String::from("The Dart code is AOT-compiled to native code in \
+                                         libapp.so, so it's not decompiled and the code rules \
+                                         don't cover it."));
+                match read_dart_strings(libraries, config) {
+                    Ok(Some(code)) => {
+                        notes.push(String::from("The strings of libapp.so were scanned for \
+                                                 secrets and endpoints."));
+                        extracted.push((PathBuf::from("libapp.so"), code));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        print_warning(format!("The libapp.so library could not be read: {}", e),
+                                      config.is_verbose())
+                    }
+                }
+            }
+            Framework::Cordova => {
+                notes.push(String::from("The application code is in the HTML and JavaScript \
+                                         files of assets/www, scanned as assets."));
+                notes.push(check_cordova_whitelist(&dist_folder, config, results));
+            }
+        }
+        infos.push(FrameworkInfo {
+            framework: framework,
+            evidence: evidence,
+            notes: notes,
+        });
+    }
+    results.add_section(get_report_section(&infos));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The cross-platform frameworks were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Cross-platform frameworks analyzed.");
+    }
+
+    extracted
+}
+
+/// Generates the frameworks section of the report
+fn get_report_section(infos: &[FrameworkInfo]) -> ReportSection {
+    let mut section = ReportSection::new("frameworks", "Cross-platform frameworks", SECTION_ORDER);
+    section.set_data(&infos);
+    let rows: Vec<_> = infos.iter()
+        .map(|info| {
+            vec![String::from(info.framework.get_label()),
+                 Results::html_escape(&info.evidence.join("\n")).replace("\n", "<br>"),
+                 Results::html_escape(&info.notes.join("\n")).replace("\n", "<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Framework", "Evidence", "Notes"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use Criticity;
+    use super::{WhitelistEntry, get_whitelist, check_whitelist_entry, extract_js_strings,
+                get_strings_code};
+
+    #[test]
+    fn it_cordova_whitelist() {
+        let config_xml = "<widget id=\"com.example\" xmlns=\"http://www.w3.org/ns/widgets\">\n\
+                          <access origin=\"*\" />\n\
+                          <allow-navigation href=\"https://*/*\" />\n\
+                          <allow-intent href=\"http://example.com/*\" />\n\
+                          <allow-intent href=\"tel:*\" />\n\
+                          </widget>";
+        let whitelist = get_whitelist(config_xml);
+        assert_eq!(whitelist.len(), 4);
+        assert_eq!(whitelist[0],
+                   WhitelistEntry {
+                       element: "access",
+                       value: "*",
+                       index: config_xml.find("<access").unwrap(),
+                   });
+
+        let findings: Vec<_> = whitelist.iter()
+            .map(|e| check_whitelist_entry(e).map(|(criticity, _, _)| criticity))
+            .collect();
+        assert_eq!(findings,
+                   vec![Some(Criticity::Medium),
+                        Some(Criticity::High),
+                        Some(Criticity::Low),
+                        None]);
+    }
+
+    #[test]
+    fn it_extract_js_strings() {
+        let code = "var a={url:\"https://api.example.com/v1\",k:'AIzaSyA1b2C3d4'},b=\"ok\";";
+        assert_eq!(extract_js_strings(code),
+                   vec![String::from("https://api.example.com/v1"),
+                        String::from("AIzaSyA1b2C3d4")]);
+        assert_eq!(get_strings_code(vec![String::from("say \"hi\"")]),
+                   "\"say \\\"hi\\\"\"\n");
+    }
+}
@@ -0,0 +1,278 @@
+use std::result;
+use std::path::PathBuf;
+
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use {Config, print_warning};
+use results::{Results, ReportSection};
+use super::java::{get_java_files, get_class_name};
+use super::read_text_file;
+
+/// Order of the hardening section in the report
+const SECTION_ORDER: u32 = 95;
+
+/// Maximum number of classes listed as evidence for each control
+const MAX_EVIDENCE: usize = 5;
+
+/// Protection of the application against a hostile environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HardeningControl {
+    RootDetection,
+    DebuggerDetection,
+    EmulatorDetection,
+    TamperDetection,
+    IntegrityAttestation,
+}
+
+/// All the controls, in the order of the scorecard
+const CONTROLS: [HardeningControl; 5] = [HardeningControl::RootDetection,
+                                         HardeningControl::DebuggerDetection,
+                                         HardeningControl::EmulatorDetection,
+                                         HardeningControl::TamperDetection,
+                                         HardeningControl::IntegrityAttestation];
+
+impl HardeningControl {
+    pub fn get_id(&self) -> &'static str {
+        match *self {
+            HardeningControl::RootDetection => "root_detection",
+            HardeningControl::DebuggerDetection => "debugger_detection",
+            HardeningControl::EmulatorDetection => "emulator_detection",
+            HardeningControl::TamperDetection => "tamper_detection",
+            HardeningControl::IntegrityAttestation => "integrity_attestation",
+        }
+    }
+
+    pub fn get_label(&self) -> &'static str {
+        match *self {
+            HardeningControl::RootDetection => "Root detection",
+            HardeningControl::DebuggerDetection => "Debugger detection",
+            HardeningControl::EmulatorDetection => "Emulator detection",
+            HardeningControl::TamperDetection => "Tampering detection",
+            HardeningControl::IntegrityAttestation => "SafetyNet / Play Integrity attestation",
+        }
+    }
+
+    /// Gets the code fragments that show that the control is implemented
+    fn get_markers(&self) -> &'static [&'static str] {
+        match *self {
+            HardeningControl::RootDetection => {
+                &["com.scottyab.rootbeer",
+                  "/system/xbin/su",
+                  "/system/bin/su",
+                  "/sbin/su",
+                  "Superuser.apk",
+                  "eu.chainfire.supersu",
+                  "com.topjohnwu.magisk",
+                  "test-keys"]
+            }
+            HardeningControl::DebuggerDetection => {
+                &["Debug.isDebuggerConnected",
+                  "Debug.waitingForDebugger",
+                  "TracerPid",
+                  "ApplicationInfo.FLAG_DEBUGGABLE"]
+            }
+            HardeningControl::EmulatorDetection => {
+                &["goldfish",
+                  "ranchu",
+                  "ro.kernel.qemu",
+                  "/dev/qemu_pipe",
+                  "google_sdk",
+                  "Genymotion",
+                  "sdk_gphone"]
+            }
+            HardeningControl::TamperDetection => {
+                &["PackageManager.GET_SIGNATURES",
+                  "PackageManager.GET_SIGNING_CERTIFICATES",
+                  "getInstallerPackageName",
+                  "getInstallSourceInfo"]
+            }
+            HardeningControl::IntegrityAttestation => {
+                &["com.google.android.play.core.integrity",
+                  "IntegrityManagerFactory",
+                  "com.google.android.gms.safetynet",
+                  "SafetyNet.getClient"]
+            }
+        }
+    }
+}
+
+/// Gets the controls implemented in the code, with the first marker found for each of them
+fn detect_controls(code: &str) -> Vec<(HardeningControl, &'static str)> {
+    CONTROLS.iter()
+        .filter_map(|control| {
+            control.get_markers()
+                .iter()
+                .find(|marker| code.contains(*marker))
+                .map(|marker| (*control, *marker))
+        })
+        .collect()
+}
+
+/// Result of a control of the scorecard
+struct ControlResult {
+    control: HardeningControl,
+    evidence: Vec<String>,
+}
+
+impl ControlResult {
+    fn is_present(&self) -> bool {
+        !self.evidence.is_empty()
+    }
+}
+
+impl Serialize for ControlResult {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("ControlResult", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.control.get_id()));
+        try!(serializer.serialize_struct_elt(&mut state, "label", self.control.get_label()));
+        try!(serializer.serialize_struct_elt(&mut state, "present", self.is_present()));
+        try!(serializer.serialize_struct_elt(&mut state, "evidence", &self.evidence));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Scorecard of the hardening of the application
+struct HardeningScorecard {
+    controls: Vec<ControlResult>,
+}
+
+impl HardeningScorecard {
+    /// Gets the number of controls implemented by the application
+    fn get_score(&self) -> usize {
+        self.controls.iter().filter(|c| c.is_present()).count()
+    }
+}
+
+impl Serialize for HardeningScorecard {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("HardeningScorecard", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "score", self.get_score()));
+        try!(serializer.serialize_struct_elt(&mut state, "total", self.controls.len()));
+        try!(serializer.serialize_struct_elt(&mut state, "controls", &self.controls));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Looks for root, debugger and emulator detection, tampering checks and integrity attestation
+/// in the code, and summarizes them in the hardening scorecard of the report
+///
+/// The scorecard is informational: the absence of these controls is not reported as a
+/// vulnerability.
+pub fn hardening_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for the hardening controls of the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the hardening \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut scorecard = HardeningScorecard {
+        controls: CONTROLS.iter()
+            .map(|control| {
+                ControlResult {
+                    control: *control,
+                    evidence: Vec::new(),
+                }
+            })
+            .collect(),
+    };
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for (control, marker) in detect_controls(&code) {
+            let result = scorecard.controls.iter_mut().find(|c| c.control == control).unwrap();
+            if result.evidence.len() < MAX_EVIDENCE {
+                result.evidence.push(format!("{} in {}", marker, class));
+            }
+        }
+    }
+
+    if config.is_verbose() {
+        for result in &scorecard.controls {
+            println!("{}: {}",
+                     result.control.get_label(),
+                     if result.is_present() { "present" } else { "absent" });
+        }
+    }
+
+    results.add_section(get_report_section(&scorecard));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The hardening controls were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Hardening controls analyzed.");
+    }
+}
+
+/// Generates the hardening section of the report
+fn get_report_section(scorecard: &HardeningScorecard) -> ReportSection {
+    let mut section = ReportSection::new("hardening",
+                                         format!("App hardening ({} of {} controls)",
+                                                 scorecard.get_score(),
+                                                 scorecard.controls.len())
+                                             .as_str(),
+                                         SECTION_ORDER);
+    section.set_data(scorecard);
+    let rows: Vec<_> = scorecard.controls
+        .iter()
+        .map(|result| {
+            vec![String::from(result.control.get_label()),
+                 String::from(Results::html_yes_no(result.is_present())),
+                 Results::html_escape(&result.evidence.join("\n")).replace("\n", "<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Control", "Present", "Evidence"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HardeningControl, detect_controls};
+
+    #[test]
+    fn it_detect_controls() {
+        let code = "if (new File(\"/system/xbin/su\").exists() || Debug.isDebuggerConnected()) \
+                    {\n    finish();\n}\n\
+                    IntegrityManager manager = IntegrityManagerFactory.create(context);";
+        assert_eq!(detect_controls(code),
+                   vec![(HardeningControl::RootDetection, "/system/xbin/su"),
+                        (HardeningControl::DebuggerDetection, "Debug.isDebuggerConnected"),
+                        (HardeningControl::IntegrityAttestation, "IntegrityManagerFactory")]);
+
+        assert!(detect_controls("String model = Build.MODEL;").is_empty());
+        assert_eq!(detect_controls("Build.HARDWARE.contains(\"goldfish\")"),
+                   vec![(HardeningControl::EmulatorDetection, "goldfish")]);
+    }
+}
@@ -0,0 +1,514 @@
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, Component, ComponentKind, get_base_protection_level};
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, get_assignment, get_assigned_variable,
+                  find_assignment, split_methods, parse_integer, get_java_files, get_class_name,
+                  get_enclosing_method, describe_location};
+use super::structural::{get_closing_index, split_arguments};
+use super::read_text_file;
+
+/// API level from which PendingIntents must declare if they are mutable
+const EXPLICIT_MUTABILITY_SINCE_SDK: i32 = 31;
+
+/// Value of `PendingIntent.FLAG_MUTABLE`
+const FLAG_MUTABLE: u64 = 0x0200_0000;
+
+/// Value of `PendingIntent.FLAG_IMMUTABLE`
+const FLAG_IMMUTABLE: u64 = 0x0400_0000;
+
+/// Calls returning a Parcelable extra of the received Intent
+const PARCELABLE_EXTRAS: [&'static str; 4] = ["getParcelableExtra",
+                                              "getParcelable",
+                                              "getParcelableArrayExtra",
+                                              "getParcelableArrayListExtra"];
+
+/// Calls that start a component or return a result with an Intent
+const INTENT_SINKS: [&'static str; 8] = ["startActivity",
+                                         "startActivityForResult",
+                                         "startActivities",
+                                         "startService",
+                                         "startForegroundService",
+                                         "bindService",
+                                         "sendBroadcast",
+                                         "setResult"];
+
+/// Calls that check the target of an Intent, or the caller of the component
+const VALIDATIONS: [&'static str; 6] = ["resolveActivity",
+                                        "getComponent",
+                                        "getPackage",
+                                        "getCallingActivity",
+                                        "getCallingPackage",
+                                        "checkCallingPermission"];
+
+/// Calls that set the target of an Intent, making it explicit
+const TARGET_SETTERS: [&'static str; 4] = ["setComponent", "setClass", "setClassName",
+                                           "setPackage"];
+
+/// Methods of `PendingIntent` that create them
+const PENDING_INTENT_FACTORIES: [&'static str; 5] = ["getActivity",
+                                                     "getActivities",
+                                                     "getService",
+                                                     "getForegroundService",
+                                                     "getBroadcast"];
+
+/// Intent received from another application and passed to a call that starts a component
+#[derive(Debug, PartialEq, Eq)]
+struct Redirection<'a> {
+    sink: &'a str,
+    /// Index of the call in the code
+    index: usize,
+}
+
+/// Finds the Intents received in Parcelable extras that are passed to calls starting
+/// components, in methods that don't validate them
+fn find_redirections(code: &str) -> Vec<Redirection> {
+    let tokens = tokenize(code);
+    let mut redirections = Vec::new();
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        let validated = statements.iter().any(|statement| {
+            statement.iter()
+                .enumerate()
+                .any(|(i, t)| VALIDATIONS.contains(&t.get_text()) && is_call(statement, i))
+        });
+        if validated {
+            continue;
+        }
+
+        let mut received = BTreeSet::new();
+        for statement in statements {
+            for (i, token) in statement.iter().enumerate() {
+                if !INTENT_SINKS.contains(&token.get_text()) || !is_call(statement, i) {
+                    continue;
+                }
+                let close = match get_closing_index(statement, i + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                if split_arguments(&statement[i + 2..close])
+                    .iter()
+                    .any(|argument| is_received_intent(argument, &received)) {
+                    redirections.push(Redirection {
+                        sink: token.get_text(),
+                        index: token.get_start(),
+                    });
+                }
+            }
+
+            if let Some((assignment, false)) = get_assignment(statement) {
+                let variable = match get_assigned_variable(&statement[..assignment]) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                // Other Parcelable extras can be received, so the Intent type must be named
+                if is_received_intent(&statement[assignment + 1..], &received) &&
+                   statement.iter().any(|t| t.get_text() == "Intent") {
+                    let _ = received.insert(variable);
+                } else {
+                    let _ = received.remove(variable);
+                }
+            }
+        }
+    }
+    redirections
+}
+
+/// Checks if an expression is an Intent received from another application
+fn is_received_intent(expression: &[Token], received: &BTreeSet<&str>) -> bool {
+    expression.iter().enumerate().any(|(i, token)| {
+        if token.get_kind() != TokenKind::Identifier {
+            return false;
+        }
+        if is_call(expression, i) {
+            PARCELABLE_EXTRAS.contains(&token.get_text())
+        } else {
+            received.contains(token.get_text()) &&
+            expression.get(i + 1).map_or(true, |t| t.get_text() != ".")
+        }
+    })
+}
+
+/// PendingIntent created mutable with an implicit base Intent
+#[derive(Debug, PartialEq, Eq)]
+struct MutablePendingIntent {
+    /// Index of the call creating it in the code
+    index: usize,
+    /// If it's mutable by default, instead of by using `FLAG_MUTABLE`
+    by_default: bool,
+}
+
+/// Finds the PendingIntents created mutable with an implicit base Intent
+///
+/// PendingIntents without `FLAG_MUTABLE` or `FLAG_IMMUTABLE` are only considered mutable if
+/// `mutable_by_default` is set.
+fn find_mutable_pending_intents(code: &str,
+                                mutable_by_default: bool)
+                                -> Vec<MutablePendingIntent> {
+    let tokens = tokenize(code);
+    let mut pending_intents = Vec::new();
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        for (i, statement) in statements.iter().enumerate() {
+            for (j, token) in statement.iter().enumerate() {
+                if !PENDING_INTENT_FACTORIES.contains(&token.get_text()) ||
+                   !is_call(statement, j) || j < 2 || statement[j - 1].get_text() != "." ||
+                   statement[j - 2].get_text() != "PendingIntent" {
+                    continue;
+                }
+                let close = match get_closing_index(statement, j + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let arguments = split_arguments(&statement[j + 2..close]);
+                if arguments.len() < 4 {
+                    continue;
+                }
+                let mutability = get_mutability(arguments[3]);
+                if mutability == Some(false) || (mutability.is_none() && !mutable_by_default) {
+                    continue;
+                }
+                if is_implicit_intent(arguments[2], statements, i) {
+                    pending_intents.push(MutablePendingIntent {
+                        index: token.get_start(),
+                        by_default: mutability.is_none(),
+                    });
+                }
+            }
+        }
+    }
+    pending_intents
+}
+
+/// Gets the mutability set by the flags of a PendingIntent, if any
+///
+/// Decompiled code usually has the values of the flags instead of their names.
+fn get_mutability(flags: &[Token]) -> Option<bool> {
+    let mut value = 0;
+    for token in flags {
+        match token.get_text() {
+            "FLAG_IMMUTABLE" => return Some(false),
+            "FLAG_MUTABLE" => return Some(true),
+            text if token.get_kind() == TokenKind::Literal => {
+                value |= parse_integer(text).unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+    if value & FLAG_IMMUTABLE != 0 {
+        Some(false)
+    } else if value & FLAG_MUTABLE != 0 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Checks if the Intent passed to a call in the statement at the given index is implicit
+///
+/// Intents created elsewhere, such as the ones received as parameters, are not considered
+/// implicit, since their target can't be known.
+fn is_implicit_intent(intent: &[Token], statements: &[&[Token]], index: usize) -> bool {
+    if sets_target(intent) {
+        return false;
+    }
+    if is_new_intent(intent) {
+        return is_implicit_constructor(intent);
+    }
+    if intent.len() != 1 || intent[0].get_kind() != TokenKind::Identifier {
+        return false;
+    }
+    let variable = intent[0].get_text();
+    let (assignment, value) = match find_assignment(variable, &statements[..index]) {
+        Some(a) => a,
+        None => return false,
+    };
+    if !is_new_intent(value) || !is_implicit_constructor(value) || sets_target(value) {
+        return false;
+    }
+    // Calls such as `intent.setPackage(...)` between the creation and the PendingIntent
+    !statements[assignment + 1..index + 1].iter().any(|statement| {
+        statement.len() > 2 && statement[0].get_text() == variable &&
+        statement[1].get_text() == "." && sets_target(statement)
+    })
+}
+
+/// Checks if the expression creates a new Intent
+fn is_new_intent(expression: &[Token]) -> bool {
+    expression.len() > 2 && expression[0].get_text() == "new" &&
+    expression[1].get_text() == "Intent" && expression[2].get_text() == "("
+}
+
+/// Checks if the `new Intent(...)` expression creates an implicit Intent
+fn is_implicit_constructor(expression: &[Token]) -> bool {
+    let close = match get_closing_index(expression, 2) {
+        Some(c) => c,
+        None => return false,
+    };
+    // `new Intent(context, Target.class)` and `new Intent(action, uri, context, Target.class)`
+    !split_arguments(&expression[3..close])
+        .iter()
+        .any(|argument| argument.iter().any(|t| t.get_text() == "class"))
+}
+
+/// Checks if the expression calls a method setting the target of an Intent
+fn sets_target(expression: &[Token]) -> bool {
+    expression.iter()
+        .enumerate()
+        .any(|(i, token)| TARGET_SETTERS.contains(&token.get_text()) && is_call(expression, i))
+}
+
+/// Checks if a component can be started by any application with an Intent
+pub fn is_unprotected(manifest: &Manifest, component: &Component) -> bool {
+    match manifest.get_component_protection(component) {
+        None => true,
+        Some(permission) => {
+            match manifest.get_declared_permission_level(permission)
+                .map(get_base_protection_level) {
+                Some("normal") | Some("dangerous") => true,
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Looks for Intents received by exported components that are forwarded to other components
+/// without validation, and for mutable PendingIntents with implicit base Intents
+///
+/// Intent redirections are only searched in the classes of the exported activities, services
+/// and receivers that are not protected by a permission, since other applications can't send
+/// Intents to the rest of the components.
+pub fn intent_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for Intent redirections and mutable PendingIntents.");
+    }
+
+    let mut exported = BTreeMap::new();
+    let mut mutable_by_default = false;
+    if let Some(manifest) = manifest {
+        for component in manifest.get_components() {
+            let kind = component.get_kind();
+            if component.is_enabled() && component.is_exported() &&
+               kind != ComponentKind::Provider && kind != ComponentKind::ActivityAlias &&
+               is_unprotected(manifest, component) {
+                let _ = exported.insert(String::from(component.get_name()), kind);
+            }
+        }
+        let target_sdk = manifest.get_target_sdk().unwrap_or_else(|| manifest.get_min_sdk());
+        mutable_by_default = target_sdk < EXPLICIT_MUTABILITY_SINCE_SDK;
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the Intent \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        let component = exported.get(&class).cloned();
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+
+        if let Some(kind) = component {
+            if PARCELABLE_EXTRAS.iter().any(|extra| code.contains(extra)) {
+                for redirection in find_redirections(&code) {
+                    add_redirection_finding(&redirection, kind, &code, &relative, &class, config,
+                                            results);
+                }
+            }
+        }
+        if code.contains("PendingIntent") {
+            for pending_intent in find_mutable_pending_intents(&code, mutable_by_default) {
+                add_pending_intent_finding(&pending_intent, &code, &relative, &class, config,
+                                           results);
+            }
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The Intents were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Intents analyzed.");
+    }
+}
+
+/// Adds a finding for an Intent redirection in an exported component
+fn add_redirection_finding(redirection: &Redirection,
+                           kind: ComponentKind,
+                           code: &str,
+                           path: &Path,
+                           class: &str,
+                           config: &Config,
+                           results: &mut Results) {
+    let description = format!("The exported {} {} passes an Intent received from other \
+                               applications to {}() in {}, without validating it. A malicious \
+                               application could use it to start the non-exported components \
+                               of this application, or to access its content providers with \
+                               its permissions.",
+                              kind.as_str(),
+                              class,
+                              redirection.sink,
+                              describe_location(class,
+                                                get_enclosing_method(code, redirection.index)));
+    let line = get_line_for(redirection.index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::High);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::High,
+                                      String::from("Intent redirection"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-926")],
+                                        vec![String::from("MASVS-PLATFORM-1")],
+                                        vec![String::from("M1")],
+                                        Vec::new(),
+                                        Some(String::from("Check the component the Intent \
+                                                           resolves to before starting it, or \
+                                                           don't forward Intents received from \
+                                                           other applications."))));
+    results.add_vulnerability(vuln);
+}
+
+/// Adds a finding for a mutable PendingIntent with an implicit base Intent
+fn add_pending_intent_finding(pending_intent: &MutablePendingIntent,
+                              code: &str,
+                              path: &Path,
+                              class: &str,
+                              config: &Config,
+                              results: &mut Results) {
+    let description = format!("A mutable PendingIntent is created with an implicit base Intent \
+                               in {}{}. The application receiving the PendingIntent can set its \
+                               target and send it with the identity and permissions of this \
+                               application. Use FLAG_IMMUTABLE, or an explicit base Intent.",
+                              describe_location(class,
+                                                get_enclosing_method(code, pending_intent.index)),
+                              if pending_intent.by_default {
+                                  ", as PendingIntents are mutable by default for applications \
+                                   targeting Android 11 or lower"
+                              } else {
+                                  ""
+                              });
+    let line = get_line_for(pending_intent.index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::High);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::High,
+                                      String::from("Mutable PendingIntent with implicit \
+                                                    Intent"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-927")],
+                                        vec![String::from("MASVS-PLATFORM-1")],
+                                        vec![String::from("M1")],
+                                        Vec::new(),
+                                        None));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Redirection, find_redirections, find_mutable_pending_intents, get_mutability};
+    use super::super::java::tokenize;
+
+    #[test]
+    fn it_find_redirections() {
+        let code = "public class ProxyActivity extends Activity {\n\
+                    protected void onCreate(Bundle state) {\n\
+                    Intent next = (Intent) getIntent().getParcelableExtra(\"next\");\n\
+                    Uri uri = getIntent().getParcelableExtra(\"uri\");\n\
+                    startActivity(next);\n\
+                    next = new Intent(this, MainActivity.class);\n\
+                    startActivity(next);\n\
+                    setResult(-1, (Intent) getIntent().getParcelableExtra(\"result\"));\n\
+                    }\n\
+                    protected void onNewIntent(Intent intent) {\n\
+                    Intent next = (Intent) intent.getParcelableExtra(\"next\");\n\
+                    if (next.resolveActivity(getPackageManager()) != null) {\n\
+                    startActivity(next);\n\
+                    }\n\
+                    }\n\
+                    }";
+        assert_eq!(find_redirections(code),
+                   vec![Redirection {
+                            sink: "startActivity",
+                            index: code.find("startActivity(next)").unwrap(),
+                        },
+                        Redirection {
+                            sink: "setResult",
+                            index: code.find("setResult").unwrap(),
+                        }]);
+    }
+
+    #[test]
+    fn it_find_mutable_pending_intents() {
+        let code = "void notify(Context context, Intent received) {\n\
+                    Intent intent = new Intent(\"com.example.OPEN\");\n\
+                    PendingIntent.getActivity(context, 0, intent, 33554432);\n\
+                    PendingIntent.getActivity(context, 0, intent, 201326592);\n\
+                    PendingIntent.getBroadcast(context, 0, new Intent(), 0);\n\
+                    PendingIntent.getService(context, 0, new Intent(context, Sync.class), \
+                    PendingIntent.FLAG_MUTABLE);\n\
+                    PendingIntent.getActivity(context, 0, received, PendingIntent.FLAG_MUTABLE);\n\
+                    intent.setPackage(\"com.example\");\n\
+                    PendingIntent.getActivity(context, 0, intent, 33554432);\n\
+                    }";
+        let pending_intents = find_mutable_pending_intents(code, false);
+        assert_eq!(pending_intents.len(), 1);
+        assert_eq!(pending_intents[0].index, code.find("getActivity").unwrap());
+        assert!(!pending_intents[0].by_default);
+
+        let pending_intents = find_mutable_pending_intents(code, true);
+        assert_eq!(pending_intents.len(), 2);
+        assert_eq!(pending_intents[1].index, code.find("getBroadcast").unwrap());
+        assert!(pending_intents[1].by_default);
+    }
+
+    #[test]
+    fn it_get_mutability() {
+        assert_eq!(get_mutability(&tokenize("PendingIntent.FLAG_MUTABLE")), Some(true));
+        assert_eq!(get_mutability(&tokenize("FLAG_IMMUTABLE | FLAG_UPDATE_CURRENT")),
+                   Some(false));
+        assert_eq!(get_mutability(&tokenize("0x4000000")), Some(false));
+        assert_eq!(get_mutability(&tokenize("134217728 | 33554432")), Some(true));
+        assert_eq!(get_mutability(&tokenize("0")), None);
+    }
+}
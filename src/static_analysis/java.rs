@@ -0,0 +1,717 @@
+use std::path::{Path, PathBuf};
+
+use Result;
+use super::add_files_to_vec;
+use super::structural::get_closing_index;
+
+/// Keywords that can be followed by parentheses and a block, but that are not methods
+const BLOCK_KEYWORDS: [&'static str; 7] = ["if", "for", "while", "switch", "catch",
+                                           "synchronized", "try"];
+
+/// Calls the closure with the index of each character of the code that is not in a literal or a
+/// comment, until it returns `false`
+///
+/// This is not a Java parser, but skipping literals and comments is enough to follow the braces
+/// of the decompiled code, and know in which method of the class a match was found.
+fn scan_code<F: FnMut(usize, char) -> bool>(code: &str, mut f: F) {
+    let mut chars = code.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                while let Some((_, n)) = chars.next() {
+                    if n == '\\' {
+                        let _ = chars.next();
+                    } else if n == c || n == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().map(|&(_, n)| n) == Some('/') => {
+                while let Some((_, n)) = chars.next() {
+                    if n == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().map(|&(_, n)| n) == Some('*') => {
+                let _ = chars.next();
+                let mut last = ' ';
+                while let Some((_, n)) = chars.next() {
+                    if last == '*' && n == '/' {
+                        break;
+                    }
+                    last = n;
+                }
+            }
+            _ => {
+                if !f(i, c) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Gets the name of the method declared by the header of a block, if it's a method
+pub fn get_method_name(header: &str) -> Option<&str> {
+    let header = match header.find(" throws ") {
+        Some(i) => &header[..i],
+        None => header,
+    };
+    let header = header.trim_right();
+    if !header.ends_with(')') {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut open = None;
+    for (i, c) in header.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    open = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let open = match open {
+        Some(o) => o,
+        None => return None,
+    };
+    let before = header[..open].trim_right();
+    let name_start = before.rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let name = &before[name_start..];
+    if name.is_empty() || BLOCK_KEYWORDS.contains(&name) {
+        return None;
+    }
+    // Anonymous classes, such as `new WebViewClient() {`
+    if before[..name_start].trim_right().ends_with("new") ||
+       before[..name_start].trim_right().ends_with('.') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Gets the name of the innermost method containing the given index of the code
+pub fn get_enclosing_method(code: &str, index: usize) -> Option<&str> {
+    let mut blocks = Vec::new();
+    let mut header_start = 0;
+    scan_code(code, |i, c| {
+        if i >= index {
+            return false;
+        }
+        match c {
+            '{' => {
+                blocks.push(get_method_name(&code[header_start..i]));
+                header_start = i + 1;
+            }
+            '}' => {
+                let _ = blocks.pop();
+                header_start = i + 1;
+            }
+            ';' => header_start = i + 1,
+            _ => {}
+        }
+        true
+    });
+    blocks.into_iter().rev().filter_map(|m| m).next()
+}
+
+/// Gets the index of the brace closing the block opened at the given index
+pub fn get_block_end(code: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut end = None;
+    scan_code(&code[open..], |i, c| {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(open + i);
+                    return false;
+                }
+            }
+            _ => {}
+        }
+        true
+    });
+    end
+}
+
+/// Gets the code of the block opened at the given index, without comments or whitespace
+///
+/// Literals are removed too, so it's only useful to check if the block is empty or trivial, such
+/// as `return true;`.
+pub fn get_compact_block(code: &str, open: usize) -> Option<String> {
+    let mut depth = 0;
+    let mut block = String::new();
+    let mut closed = false;
+    scan_code(&code[open..], |_, c| {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    closed = true;
+                    return false;
+                }
+            }
+            _ => {}
+        }
+        if depth > 1 || (depth == 1 && c != '{') {
+            if !c.is_whitespace() {
+                block.push(c);
+            }
+        }
+        true
+    });
+    if closed { Some(block) } else { None }
+}
+
+/// Gets the arguments of the call whose opening parenthesis is at the given index
+///
+/// Arguments are returned trimmed, as they are written in the code.
+pub fn get_call_arguments(code: &str, open: usize) -> Option<Vec<&str>> {
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut start = open + 1;
+    let mut end = None;
+    scan_code(&code[open..], |i, c| {
+        let i = open + i;
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(i);
+                    return false;
+                }
+            }
+            ',' if depth == 1 => {
+                arguments.push(code[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        true
+    });
+    let end = match end {
+        Some(e) => e,
+        None => return None,
+    };
+    let last = code[start..end].trim();
+    if !last.is_empty() || !arguments.is_empty() {
+        arguments.push(last);
+    }
+    Some(arguments)
+}
+
+/// Kind of a token of Java code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Identifiers and keywords
+    Identifier,
+    /// String, character and number literals
+    Literal,
+    /// Operators and punctuation, one character each
+    Symbol,
+}
+
+/// Token of Java code, with its position in the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+}
+
+impl<'a> Token<'a> {
+    pub fn get_kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn get_text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Gets the index of the first byte of the token in the code
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    /// Gets the index of the byte after the token in the code
+    pub fn get_end(&self) -> usize {
+        self.start + self.text.len()
+    }
+}
+
+/// Splits the code in tokens, skipping whitespace and comments
+///
+/// As `scan_code()`, this is not a full Java lexer, but it's enough to tell identifiers from
+/// literals, which is what structural rules need.
+pub fn tokenize(code: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = code.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek().map(|&(_, n)| n) == Some('/') => {
+                while let Some((_, n)) = chars.next() {
+                    if n == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '/' if chars.peek().map(|&(_, n)| n) == Some('*') => {
+                let _ = chars.next();
+                let mut last = ' ';
+                while let Some((_, n)) = chars.next() {
+                    if last == '*' && n == '/' {
+                        break;
+                    }
+                    last = n;
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                while let Some((_, n)) = chars.next() {
+                    if n == '\\' {
+                        let _ = chars.next();
+                    } else if n == c || n == '\n' {
+                        break;
+                    }
+                }
+                TokenKind::Literal
+            }
+            c if c.is_digit(10) => {
+                while let Some(&(_, n)) = chars.peek() {
+                    if n.is_alphanumeric() || n == '.' || n == '_' {
+                        let _ = chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                TokenKind::Literal
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                while let Some(&(_, n)) = chars.peek() {
+                    if n.is_alphanumeric() || n == '_' || n == '$' {
+                        let _ = chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                TokenKind::Identifier
+            }
+            _ => TokenKind::Symbol,
+        };
+        let end = chars.peek().map_or(code.len(), |&(i, _)| i);
+        tokens.push(Token {
+            kind: kind,
+            text: &code[start..end],
+            start: start,
+        });
+    }
+    tokens
+}
+
+/// Checks if the token at the given index is the name of a called method
+pub fn is_call(tokens: &[Token], index: usize) -> bool {
+    tokens[index].get_kind() == TokenKind::Identifier &&
+    tokens.get(index + 1).map(|t| t.get_text()) == Some("(")
+}
+
+/// Parses an integer literal, in decimal, hexadecimal, octal or binary notation
+pub fn parse_integer(literal: &str) -> Option<u64> {
+    let text: String = literal.trim_right_matches(|c: char| c == 'L' || c == 'l')
+        .chars()
+        .filter(|c| *c != '_')
+        .collect();
+    let result = if text.starts_with("0x") || text.starts_with("0X") {
+        u64::from_str_radix(&text[2..], 16)
+    } else if text.starts_with("0b") || text.starts_with("0B") {
+        u64::from_str_radix(&text[2..], 2)
+    } else if text.len() > 1 && text.starts_with('0') {
+        u64::from_str_radix(&text[1..], 8)
+    } else {
+        text.parse()
+    };
+    result.ok()
+}
+
+/// Splits an identifier or a string in lowercase words, at camel case and non alphanumeric
+/// boundaries
+pub fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut last_lowercase = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() || (c.is_uppercase() && last_lowercase) {
+            if !word.is_empty() {
+                words.push(word);
+                word = String::new();
+            }
+        }
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        }
+        last_lowercase = c.is_lowercase() || c.is_digit(10);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Gets the index of the assignment operator of the statement, if any, and whether it's a
+/// compound assignment, such as `+=`
+pub fn get_assignment(statement: &[Token]) -> Option<(usize, bool)> {
+    let mut depth = 0;
+    for (i, token) in statement.iter().enumerate() {
+        match token.get_text() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            "=" if depth == 0 => {
+                let previous = if i > 0 { statement[i - 1].get_text() } else { "" };
+                let next = statement.get(i + 1).map_or("", |t| t.get_text());
+                // Comparisons, such as `==`, `!=` or `<=`
+                if next == "=" || ["=", "!", "<", ">"].contains(&previous) {
+                    continue;
+                }
+                return Some((i, ["+", "-", "*", "/", "%", "&", "|", "^"].contains(&previous)));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Gets the variable assigned by the tokens before an assignment operator
+///
+/// Only the name is kept, so `this.query` and `values[i]` are `query` and `values`.
+pub fn get_assigned_variable<'a>(target: &[Token<'a>]) -> Option<&'a str> {
+    let mut depth = 0;
+    for token in target.iter().rev() {
+        match token.get_text() {
+            "]" => depth += 1,
+            "[" => depth -= 1,
+            _ if depth > 0 => {}
+            _ if token.get_kind() == TokenKind::Identifier => return Some(token.get_text()),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Finds the last assignment of the variable in the statements, returning the index of the
+/// statement and the assigned value
+pub fn find_assignment<'a, 't>(variable: &str,
+                               statements: &[&'t [Token<'a>]])
+                               -> Option<(usize, &'t [Token<'a>])> {
+    for (i, statement) in statements.iter().enumerate().rev() {
+        if let Some((assignment, false)) = get_assignment(statement) {
+            if get_assigned_variable(&statement[..assignment]) == Some(variable) {
+                return Some((i, &statement[assignment + 1..]));
+            }
+        }
+    }
+    None
+}
+
+/// Body of a method, split in statements
+///
+/// The headers of the blocks inside the method, such as `if (a == b)`, are statements too, and
+/// the methods of anonymous classes are split as separate methods.
+#[derive(Debug)]
+pub struct MethodBody<'a, 't> {
+    name: &'a str,
+    statements: Vec<&'t [Token<'a>]>,
+}
+
+impl<'a, 't> MethodBody<'a, 't> {
+    pub fn get_name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn get_statements(&self) -> &[&'t [Token<'a>]] {
+        self.statements.as_slice()
+    }
+}
+
+/// Splits the tokens of the code in the bodies of its methods
+///
+/// Methods are returned in the order they end, so methods of anonymous classes come before the
+/// method they are declared in.
+pub fn split_methods<'a, 't>(code: &'a str, tokens: &'t [Token<'a>]) -> Vec<MethodBody<'a, 't>> {
+    let mut methods = Vec::new();
+    // Methods being split, with the number of open blocks at the start of their body
+    let mut open: Vec<(usize, MethodBody)> = Vec::new();
+    // Open parentheses in each of the open blocks
+    let mut parens = vec![0];
+    let mut start = 0;
+    let mut skip_to = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if i < skip_to {
+            continue;
+        }
+        match token.get_text() {
+            // Array initializers, such as `new String[] { id }`, are part of the statement
+            "{" if i > 0 && ["]", "="].contains(&tokens[i - 1].get_text()) => {
+                skip_to = get_closing_index(tokens, i).map_or(tokens.len(), |c| c + 1);
+            }
+            "(" => *parens.last_mut().unwrap() += 1,
+            ")" => {
+                let count = parens.last_mut().unwrap();
+                if *count > 0 {
+                    *count -= 1;
+                }
+            }
+            ";" if *parens.last().unwrap() == 0 => {
+                if let Some(&mut (_, ref mut method)) = open.last_mut() {
+                    if start < i {
+                        method.statements.push(&tokens[start..i]);
+                    }
+                }
+                start = i + 1;
+            }
+            "{" => {
+                let header = &tokens[start..i];
+                let name = if header.is_empty() {
+                    None
+                } else {
+                    get_method_name(&code[header[0].get_start()..
+                                          header[header.len() - 1].get_end()])
+                };
+                parens.push(0);
+                match name {
+                    Some(name) => {
+                        open.push((parens.len(),
+                                   MethodBody {
+                                       name: name,
+                                       statements: Vec::new(),
+                                   }))
+                    }
+                    None => {
+                        if let Some(&mut (_, ref mut method)) = open.last_mut() {
+                            if !header.is_empty() {
+                                method.statements.push(header);
+                            }
+                        }
+                    }
+                }
+                start = i + 1;
+            }
+            "}" => {
+                if parens.len() > 1 {
+                    if open.last().map_or(false, |&(depth, _)| depth == parens.len()) {
+                        methods.push(open.pop().unwrap().1);
+                    }
+                    let _ = parens.pop();
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    methods
+}
+
+/// Gets the decompiled Java files of the application
+pub fn get_java_files(dist_folder: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    try!(add_files_to_vec(dist_folder, &dist_folder.join("classes"), &mut files));
+    Ok(files.into_iter()
+        .filter(|f| f.extension().map_or(false, |e| e == "java"))
+        .collect())
+}
+
+/// Gets the fully qualified class name of a decompiled Java file, from its path relative to the
+/// decompression folder
+pub fn get_class_name(relative: &Path) -> Option<String> {
+    match (relative.strip_prefix("classes"), relative.extension()) {
+        (Ok(class), Some(ext)) if ext == "java" => {
+            Some(class.with_extension("").to_string_lossy().replace('/', ".").replace('\\', "."))
+        }
+        _ => None,
+    }
+}
+
+/// Describes where the code was found, as `method()` in `class`
+pub fn describe_location(class: &str, method: Option<&str>) -> String {
+    match method {
+        Some(m) => format!("the {}() method of the {} class", m, class),
+        None => format!("the {} class", class),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{TokenKind, get_enclosing_method, get_block_end, get_compact_block,
+                get_call_arguments, get_class_name, describe_location, tokenize, get_assignment,
+                split_methods, parse_integer, split_words};
+
+    const CODE: &'static str = "package com.example;
+
+public class Browser extends Activity {
+    private String url = \"https://example.com/{id}\";
+
+    @Override
+    protected void onCreate(Bundle bundle) throws Exception {
+        if (bundle != null) {
+            this.webView.getSettings().setJavaScriptEnabled(true);
+        }
+        this.webView.setWebViewClient(new WebViewClient() {
+            public void onReceivedSslError(WebView v, SslErrorHandler h, SslError e) {
+                // Ignore errors {
+                h.proceed();
+            }
+        });
+        load();
+    }
+
+    private void load() {
+        this.webView.loadUrl(this.url);
+    }
+}
+";
+
+    #[test]
+    fn it_get_enclosing_method() {
+        let index = |needle: &str| CODE.find(needle).unwrap();
+
+        assert_eq!(get_enclosing_method(CODE, index("private String")), None);
+        assert_eq!(get_enclosing_method(CODE, index("setJavaScriptEnabled")),
+                   Some("onCreate"));
+        assert_eq!(get_enclosing_method(CODE, index("h.proceed")),
+                   Some("onReceivedSslError"));
+        assert_eq!(get_enclosing_method(CODE, index("load();")), Some("onCreate"));
+        assert_eq!(get_enclosing_method(CODE, index("loadUrl")), Some("load"));
+    }
+
+    #[test]
+    fn it_get_block_end() {
+        let open = CODE.find("SslError e) {").unwrap() + "SslError e) ".len();
+        let end = get_block_end(CODE, open).unwrap();
+        assert!(CODE[open..end].contains("h.proceed();"));
+        assert!(CODE[end + 1..].trim_left().starts_with("});"));
+
+        assert_eq!(get_block_end("{ {", 0), None);
+    }
+
+    #[test]
+    fn it_get_compact_block() {
+        let open = CODE.find("SslError e) {").unwrap() + "SslError e) ".len();
+        assert_eq!(get_compact_block(CODE, open), Some(String::from("h.proceed();")));
+
+        let code = "void check() { /* Trust { everything */ return; }";
+        assert_eq!(get_compact_block(code, code.find('{').unwrap()),
+                   Some(String::from("return;")));
+        assert_eq!(get_compact_block("{ if (a) { b(); } }", 0),
+                   Some(String::from("if(a){b();}")));
+        assert_eq!(get_compact_block("{ {", 0), None);
+    }
+
+    #[test]
+    fn it_get_call_arguments() {
+        let code = "new SecretKeySpec(\"a, (b)\".getBytes(UTF_8), \"AES\");";
+        assert_eq!(get_call_arguments(code, code.find('(').unwrap()),
+                   Some(vec!["\"a, (b)\".getBytes(UTF_8)", "\"AES\""]));
+
+        let code = "new IvParameterSpec(new byte[] { 1, 2 })";
+        assert_eq!(get_call_arguments(code, code.find('(').unwrap()),
+                   Some(vec!["new byte[] { 1, 2 }"]));
+
+        assert_eq!(get_call_arguments("random.nextInt()", 14), Some(Vec::new()));
+        assert_eq!(get_call_arguments("foo(a, b", 3), None);
+    }
+
+    #[test]
+    fn it_tokenize() {
+        let code = "// Runtime.exec(cmd)\n\
+                    rt.exec(\"ls \\\"-l\\\"\" + dir, 0x1F); /* exec */ c = 'x';";
+        let tokens = tokenize(code);
+        let texts: Vec<_> = tokens.iter().map(|t| t.get_text()).collect();
+        assert_eq!(texts,
+                   vec!["rt", ".", "exec", "(", "\"ls \\\"-l\\\"\"", "+", "dir", ",", "0x1F", ")",
+                        ";", "c", "=", "'x'", ";"]);
+        assert_eq!(tokens[2].get_kind(), TokenKind::Identifier);
+        assert_eq!(tokens[4].get_kind(), TokenKind::Literal);
+        assert_eq!(tokens[8].get_kind(), TokenKind::Literal);
+        assert_eq!(tokens[5].get_kind(), TokenKind::Symbol);
+        assert_eq!(&code[tokens[2].get_start()..tokens[2].get_end()], "exec");
+    }
+
+    #[test]
+    fn it_get_assignment() {
+        assert_eq!(get_assignment(&tokenize("String a = b")), Some((2, false)));
+        assert_eq!(get_assignment(&tokenize("query += id")), Some((2, true)));
+        assert_eq!(get_assignment(&tokenize("values[i == 0 ? 1 : 2] = id")), Some((11, false)));
+        assert_eq!(get_assignment(&tokenize("if (a == b)")), None);
+        assert_eq!(get_assignment(&tokenize("return a <= b")), None);
+    }
+
+    #[test]
+    fn it_parse_integer() {
+        assert_eq!(parse_integer("33554432"), Some(33554432));
+        assert_eq!(parse_integer("0x4000000"), Some(0x4000000));
+        assert_eq!(parse_integer("0777"), Some(0o777));
+        assert_eq!(parse_integer("0b101"), Some(5));
+        assert_eq!(parse_integer("1_000L"), Some(1000));
+        assert_eq!(parse_integer("0"), Some(0));
+        assert_eq!(parse_integer("\"0\""), None);
+    }
+
+    #[test]
+    fn it_split_words() {
+        assert_eq!(split_words("userPassword"), vec!["user", "password"]);
+        assert_eq!(split_words("API_KEY"), vec!["api", "key"]);
+        assert_eq!(split_words("\"credit card\""), vec!["credit", "card"]);
+        assert_eq!(split_words("sha256Digest"), vec!["sha256", "digest"]);
+    }
+
+    #[test]
+    fn it_split_methods() {
+        let tokens = tokenize(CODE);
+        let methods = split_methods(CODE, &tokens);
+        let names: Vec<_> = methods.iter().map(|m| m.get_name()).collect();
+        assert_eq!(names, vec!["onReceivedSslError", "onCreate", "load"]);
+
+        let statement = |s: &[super::Token]| {
+            s.iter().map(|t| t.get_text()).collect::<Vec<_>>().join(" ")
+        };
+        assert_eq!(methods[0].get_statements().len(), 1);
+        assert_eq!(statement(methods[0].get_statements()[0]), "h . proceed ( )");
+        let statements: Vec<_> = methods[1]
+            .get_statements()
+            .iter()
+            .map(|s| statement(s))
+            .collect();
+        assert_eq!(statements[0], "if ( bundle ! = null )");
+        assert_eq!(statements[2], "this . webView . setWebViewClient ( new WebViewClient ( )");
+        assert_eq!(statements.last().unwrap(), "load ( )");
+    }
+
+    #[test]
+    fn it_get_class_name() {
+        assert_eq!(get_class_name(Path::new("classes/com/example/Browser.java")),
+                   Some(String::from("com.example.Browser")));
+        assert_eq!(get_class_name(Path::new("res/layout/main.xml")), None);
+        assert_eq!(get_class_name(Path::new("classes/com/example/Browser.class")), None);
+    }
+
+    #[test]
+    fn it_describe_location() {
+        assert_eq!(describe_location("com.example.Browser", Some("load")),
+                   "the load() method of the com.example.Browser class");
+        assert_eq!(describe_location("com.example.Browser", None),
+                   "the com.example.Browser class");
+    }
+}
@@ -0,0 +1,364 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::{get_java_files, get_class_name, get_block_end, get_enclosing_method,
+                  describe_location};
+use super::native::NativeLibrary;
+use super::taint::find_untrusted_calls;
+use super::read_text_file;
+
+/// Order of the native interface section in the report
+const SECTION_ORDER: u32 = 105;
+
+/// Native method declared in the decompiled Java code
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NativeMethod {
+    /// Binary name of the class, with `$` separating nested classes
+    class: String,
+    name: String,
+    parameters: String,
+    path: PathBuf,
+    line: usize,
+    /// Untrusted data received by the method
+    untrusted: Option<String>,
+}
+
+impl NativeMethod {
+    /// Gets the name of the JNI function implementing the method, without the argument
+    /// signature used for overloaded methods
+    fn get_symbol(&self) -> String {
+        format!("Java_{}_{}", mangle_jni_name(&self.class), mangle_jni_name(&self.name))
+    }
+
+    /// Gets the paths of the libraries exporting the JNI function of the method
+    fn find_implementations<'l>(&self, libraries: &'l [NativeLibrary]) -> Vec<&'l str> {
+        let symbol = self.get_symbol();
+        let overloaded = format!("{}__", symbol);
+        libraries.iter()
+            .filter(|l| {
+                l.get_jni_symbols().iter().any(|s| *s == symbol || s.starts_with(&overloaded))
+            })
+            .map(|l| l.get_path())
+            .collect()
+    }
+}
+
+/// Mangles a class or method name as the JNI specification does for the names of native
+/// functions
+fn mangle_jni_name(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '.' | '/' => mangled.push('_'),
+            '_' => mangled.push_str("_1"),
+            ';' => mangled.push_str("_2"),
+            '[' => mangled.push_str("_3"),
+            c if (c as u32) < 0x80 && c.is_alphanumeric() => mangled.push(c),
+            c => {
+                for unit in c.to_string().encode_utf16() {
+                    mangled.push_str(&format!("_0{:04x}", unit));
+                }
+            }
+        }
+    }
+    mangled
+}
+
+/// Gets the binary name of the class declaring the code at the given index, adding the nested
+/// classes to the class of the file
+fn get_declaring_class(code: &str, index: usize, file_class: &str) -> String {
+    let regex = Regex::new(r"\b(?:class|interface|enum)\s+(\w+)[^{;]*\{").unwrap();
+    let mut class = String::from(file_class);
+    for (i, caps) in regex.captures_iter(&code[..index]).enumerate() {
+        let (_, end) = caps.pos(0).unwrap();
+        if i > 0 && get_block_end(code, end - 1).map_or(true, |e| e > index) {
+            class.push('$');
+            class.push_str(caps.at(1).unwrap());
+        }
+    }
+    class
+}
+
+/// Finds the native methods declared in the given code
+fn find_native_methods(code: &str, file_class: &str, path: &Path) -> Vec<NativeMethod> {
+    let regex = Regex::new(concat!(r"(?m)^[ \t]*((?:(?:public|protected|private|static|final|",
+                                   r"synchronized|native|strictfp)[ \t]+)+)[\w.$<>\[\], ?]+?",
+                                   r"[ \t]+(\w+)[ \t]*\(([^)]*)\)[^;{]*;"))
+        .unwrap();
+    regex.captures_iter(code)
+        .filter(|caps| caps.at(1).unwrap().split_whitespace().any(|m| m == "native"))
+        .map(|caps| {
+            let (start, _) = caps.pos(2).unwrap();
+            NativeMethod {
+                class: get_declaring_class(code, start, file_class),
+                name: String::from(caps.at(2).unwrap()),
+                parameters: caps.at(3).unwrap().split_whitespace().collect::<Vec<_>>().join(" "),
+                path: path.to_path_buf(),
+                line: get_line_for(start, code),
+                untrusted: None,
+            }
+        })
+        .collect()
+}
+
+/// Lists the native methods declared in the code, matches them with the JNI functions exported
+/// by the native libraries and reports the ones receiving untrusted data
+pub fn jni_analysis(libraries: &[NativeLibrary], config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Matching the native methods of the code with the native libraries.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the native \
+                                   interface analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut natives = Vec::new();
+    for path in &files {
+        let code = match read_text_file(path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !code.contains("native") {
+            continue;
+        }
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        if let Some(class) = get_class_name(relative) {
+            natives.extend(find_native_methods(&code, &class, relative));
+        }
+    }
+
+    if !natives.is_empty() {
+        find_untrusted_input(&mut natives, &files, &dist_folder, config, results);
+    }
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    let mut declared = Vec::new();
+    let registers_natives = libraries.iter()
+        .any(|l| l.get_jni_symbols().iter().any(|s| s == "JNI_OnLoad"));
+    for native in &natives {
+        let implementations = native.find_implementations(libraries);
+        let implementation = if !implementations.is_empty() {
+            implementations.join(", ")
+        } else if registers_natives {
+            String::from("Not exported, possibly registered in JNI_OnLoad")
+        } else {
+            String::from("Not found")
+        };
+        let symbol = native.get_symbol();
+        let untrusted = native.untrusted.clone().unwrap_or_else(String::new);
+        rows.push(vec![Results::html_escape(&native.class),
+                       format!("<code>{}({})</code>",
+                               Results::html_escape(&native.name),
+                               Results::html_escape(&native.parameters)),
+                       format!("<code>{}</code>", Results::html_escape(&symbol)),
+                       Results::html_escape(&implementation),
+                       Results::html_escape(&untrusted)]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("class", native.class.clone());
+        let _ = entry.insert("method", native.name.clone());
+        let _ = entry.insert("parameters", native.parameters.clone());
+        let _ = entry.insert("location",
+                             format!("{}:{}", native.path.display(), native.line + 1));
+        let _ = entry.insert("symbol", symbol.clone());
+        let _ = entry.insert("implementation", implementation);
+        let _ = entry.insert("untrusted_input", untrusted);
+        data.push(entry);
+        declared.push(symbol);
+    }
+
+    // JNI functions without a native method in the code, usually because of obfuscation or
+    // because the code calling them was not decompiled
+    for library in libraries {
+        for symbol in library.get_jni_symbols() {
+            let is_declared = declared.iter()
+                .any(|d| symbol == d || symbol.starts_with(&format!("{}__", d)));
+            if !symbol.starts_with("Java_") || is_declared {
+                continue;
+            }
+            rows.push(vec![String::new(),
+                           String::from("Not declared"),
+                           format!("<code>{}</code>", Results::html_escape(symbol)),
+                           Results::html_escape(library.get_path()),
+                           String::new()]);
+            let mut entry = BTreeMap::new();
+            let _ = entry.insert("symbol", symbol.clone());
+            let _ = entry.insert("implementation", String::from(library.get_path()));
+            data.push(entry);
+        }
+    }
+
+    if !data.is_empty() {
+        let mut section = ReportSection::new("native_interface", "Native interface", SECTION_ORDER);
+        section.set_data(&data);
+        section.set_html_table(&["Class", "Method", "JNI function", "Library", "Untrusted input"],
+                               &rows);
+        results.add_section(section);
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The native interface was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Native interface analyzed.");
+    }
+}
+
+/// Finds the calls to the native methods receiving untrusted data, reporting the first one of
+/// each method
+fn find_untrusted_input(natives: &mut [NativeMethod],
+                        files: &[PathBuf],
+                        dist_folder: &Path,
+                        config: &Config,
+                        results: &mut Results) {
+    let mut names: Vec<_> = natives.iter().map(|n| n.name.clone()).collect();
+    names.sort();
+    names.dedup();
+
+    for path in files {
+        let code = match read_text_file(path) {
+            Ok(Some(c)) => c,
+            _ => continue,
+        };
+        if !names.iter().any(|n| code.contains(n.as_str())) {
+            continue;
+        }
+        let relative = path.strip_prefix(dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for (method, index, source) in find_untrusted_calls(&code,
+                                                            &names,
+                                                            config.get_taint_config()) {
+            for native in natives.iter_mut() {
+                if native.name != method || native.untrusted.is_some() {
+                    continue;
+                }
+                native.untrusted = Some(source.clone());
+                add_untrusted_finding(native, relative, &class, &code, index, config, results);
+            }
+        }
+    }
+}
+
+/// Adds the finding for a native method receiving untrusted data in the call at the given index
+fn add_untrusted_finding(native: &NativeMethod,
+                         path: &Path,
+                         class: &str,
+                         code: &str,
+                         index: usize,
+                         config: &Config,
+                         results: &mut Results) {
+    let location = describe_location(class, get_enclosing_method(code, index));
+    let line = get_line_for(index, code);
+    let description = format!("Data from {} is passed to the {}() native method of the {} \
+                               class in {}. Native code is not memory safe, so a missing check \
+                               of the length or the format of the data in the JNI function {} \
+                               could lead to memory corruption that an attacker can exploit.",
+                              native.untrusted.as_ref().unwrap(),
+                              native.name,
+                              native.class,
+                              location,
+                              native.get_symbol());
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Medium);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Medium,
+                                      String::from("Untrusted data passed to native code"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-111")],
+                                        vec![String::from("MASVS-CODE-4")],
+                                        vec![String::from("M4")],
+                                        Vec::new(),
+                                        Some(String::from("Validate the length and the format \
+                                                           of the data before passing it to \
+                                                           native code, and check the bounds \
+                                                           of every buffer in the native \
+                                                           implementation."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{mangle_jni_name, get_declaring_class, find_native_methods};
+
+    #[test]
+    fn it_mangle_jni_name() {
+        assert_eq!(mangle_jni_name("com.example.Native"), "com_example_Native");
+        assert_eq!(mangle_jni_name("decode_frame"), "decode_1frame");
+        assert_eq!(mangle_jni_name("Outer$Inner"), "Outer_00024Inner");
+        assert_eq!(mangle_jni_name("[Ljava/lang/String;"), "_3Ljava_lang_String_2");
+        assert_eq!(mangle_jni_name("cañon"), "ca_000f1on");
+    }
+
+    #[test]
+    fn it_get_declaring_class() {
+        let code = "public class Codec {\n\
+                    static class Frame {\n\
+                    int size;\n\
+                    }\n\
+                    private static class Decoder {\n\
+                    native void decode();\n\
+                    }\n\
+                    native void encode();\n\
+                    }";
+        let decode = code.find("decode").unwrap();
+        let encode = code.find("encode").unwrap();
+        assert_eq!(get_declaring_class(code, decode, "com.example.Codec"),
+                   "com.example.Codec$Decoder");
+        assert_eq!(get_declaring_class(code, encode, "com.example.Codec"),
+                   "com.example.Codec");
+    }
+
+    #[test]
+    fn it_find_native_methods() {
+        let code = "package com.example;\n\
+                    \n\
+                    public class Codec {\n\
+                    // The native code is loaded in the constructor\n\
+                    public native byte[] decode_frame(byte[] data, int length);\n\
+                    private static native synchronized void init();\n\
+                    public String getNativeName() { return \"native\"; }\n\
+                    }";
+        let natives = find_native_methods(code,
+                                          "com.example.Codec",
+                                          Path::new("classes/com/example/Codec.java"));
+        assert_eq!(natives.len(), 2);
+        assert_eq!(natives[0].name, "decode_frame");
+        assert_eq!(natives[0].parameters, "byte[] data, int length");
+        assert_eq!(natives[0].line, 4);
+        assert_eq!(natives[0].get_symbol(), "Java_com_example_Codec_decode_1frame");
+        assert_eq!(natives[1].name, "init");
+        assert_eq!(natives[1].parameters, "");
+    }
+}
@@ -0,0 +1,379 @@
+use std::fs;
+use std::fs::{File, DirEntry};
+use std::io::Read;
+use std::result;
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use {Config, Result, Criticity, print_warning, print_vulnerability, get_code, file_exists};
+use results::{Results, Vulnerability, ReportSection, RuleMetadata};
+use super::code::{PathFilter, get_line_for};
+use super::java::{get_java_files, get_class_name, describe_location};
+use super::read_text_file;
+
+/// Order of the Kotlin section in the report
+const SECTION_ORDER: u32 = 97;
+
+/// Files and folders of the decompiled application that show that it was written in Kotlin
+const KOTLIN_MARKERS: [&'static str; 5] = ["unknown/kotlin",
+                                           "kotlin",
+                                           "classes/kotlin/Metadata.java",
+                                           "smali/kotlin/Metadata.smali",
+                                           "classes/kotlin/jvm/internal/Intrinsics.java"];
+
+/// Folders with the `META-INF` files of the libraries, where the `.kotlin_module` files are kept
+const META_INF_FOLDERS: [&'static str; 2] = ["unknown/META-INF", "original/META-INF"];
+
+/// Files and folders of the decompiled application that show that it uses coroutines
+const COROUTINE_MARKERS: [&'static str; 3] = ["unknown/META-INF/kotlinx_coroutines_core.version",
+                                              "classes/kotlinx/coroutines",
+                                              "smali/kotlinx/coroutines"];
+
+/// Null checks generated by the Kotlin compiler, that quote the source of the checked
+/// expressions in string literals
+const INTRINSICS_CHECKS: [&'static str; 6] = ["Intrinsics.checkNotNull",
+                                              "Intrinsics.checkParameterIsNotNull",
+                                              "Intrinsics.checkExpressionValueIsNotNull",
+                                              "Intrinsics.checkFieldIsNotNull",
+                                              "Intrinsics.checkReturnedValueIsNotNull",
+                                              "Intrinsics.throwUninitialized"];
+
+/// Decompiled coroutine scopes and builders that run their coroutines in the main thread
+const MAIN_THREAD_SCOPES: [&'static str; 4] = ["Dispatchers.getMain()",
+                                               "LifecycleOwnerKt.getLifecycleScope(",
+                                               "ViewModelKt.getViewModelScope(",
+                                               "BuildersKt.runBlocking"];
+
+/// Decompiled blocking network calls
+const NETWORK_CALLS: [&'static str; 5] = ["openConnection(",
+                                          "openStream()",
+                                          "TextStreamsKt.readBytes(",
+                                          "TextStreamsKt.readText(",
+                                          ").execute()"];
+
+/// Checks if the code at the given index is in a null check generated by the Kotlin compiler,
+/// such as `Intrinsics.checkNotNullExpressionValue(digest, "getInstance(\"MD5\")")`
+///
+/// The string literals of these checks repeat the code of the line before them, so rules
+/// matching them would report the same code twice.
+pub fn is_intrinsics_check(code: &str, index: usize) -> bool {
+    let start = code[..index].rfind('\n').map_or(0, |i| i + 1);
+    let line = code[start..].trim_left();
+    INTRINSICS_CHECKS.iter().any(|check| line.starts_with(check))
+}
+
+/// Adds to the vector the Kotlin source files of the source root that pass the filter, so that
+/// the rules with the `kotlin` target are checked in them
+///
+/// Build output and hidden folders, such as `.gradle`, are skipped.
+pub fn add_kotlin_files<P: AsRef<Path>>(root: P,
+                                        vec: &mut Vec<DirEntry>,
+                                        filter: &PathFilter)
+                                        -> Result<()> {
+    add_kotlin_files_in(root.as_ref(), root.as_ref(), vec, filter)
+}
+
+fn add_kotlin_files_in(root: &Path,
+                       folder: &Path,
+                       vec: &mut Vec<DirEntry>,
+                       filter: &PathFilter)
+                       -> Result<()> {
+    for f in try!(fs::read_dir(folder)) {
+        let f = try!(f);
+        let f_path = f.path();
+        let relative = f_path.strip_prefix(root).unwrap().to_string_lossy().into_owned();
+        if try!(f.file_type()).is_dir() {
+            let name = f.file_name().to_string_lossy().into_owned();
+            if name != "build" && !name.starts_with('.') && !filter.excludes_folder(&relative) {
+                try!(add_kotlin_files_in(root, &f_path, vec, filter));
+            }
+        } else if f_path.extension().map_or(false, |e| e == "kt") && filter.matches(&relative) {
+            vec.push(f);
+        }
+    }
+    Ok(())
+}
+
+/// Gets the files of the decompiled application that show that it was written in Kotlin
+fn detect_kotlin(dist_folder: &Path) -> Vec<String> {
+    let mut markers: Vec<String> = KOTLIN_MARKERS.iter()
+        .filter(|marker| file_exists(dist_folder.join(marker)))
+        .map(|marker| String::from(*marker))
+        .collect();
+    for folder in &META_INF_FOLDERS {
+        if let Ok(entries) = fs::read_dir(dist_folder.join(folder)) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.ends_with(".kotlin_module") {
+                    markers.push(format!("{}/{}", folder, name));
+                }
+            }
+        }
+    }
+    markers
+}
+
+/// Gets the version of the coroutines library, or an empty string if the version is not known,
+/// if the application uses coroutines
+fn detect_coroutines(dist_folder: &Path) -> Option<String> {
+    if !COROUTINE_MARKERS.iter().any(|marker| file_exists(dist_folder.join(marker))) {
+        return None;
+    }
+    let mut version = String::new();
+    let _ = File::open(dist_folder.join(COROUTINE_MARKERS[0]))
+        .and_then(|mut f| f.read_to_string(&mut version));
+    Some(String::from(version.trim()))
+}
+
+/// Gets the file of the outer class of a decompiled inner class, such as the one of the
+/// `MainActivity` class for the `MainActivity$onCreate$1` coroutine
+fn get_outer_class_file(path: &Path) -> Option<PathBuf> {
+    let name = match path.file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => return None,
+    };
+    name.find('$').map(|i| path.with_file_name(format!("{}.java", &name[..i])))
+}
+
+/// Finds the blocking network calls of the suspending lambdas of a decompiled class, if its
+/// coroutines are launched in the main thread and they don't switch to the IO dispatcher
+///
+/// The coroutines can be launched in the class itself or in its outer class, since the lambdas
+/// are decompiled as inner classes.
+fn find_main_thread_network_calls(code: &str, outer_code: Option<&str>) -> Vec<usize> {
+    let lambda = match code.find("invokeSuspend(") {
+        Some(i) => i,
+        None => return Vec::new(),
+    };
+    let in_main_thread = |c: &str| MAIN_THREAD_SCOPES.iter().any(|s| c.contains(s));
+    if code.contains("Dispatchers.getIO()") ||
+       !(in_main_thread(code) || outer_code.map_or(false, &in_main_thread)) {
+        return Vec::new();
+    }
+    let mut calls: Vec<usize> = NETWORK_CALLS.iter()
+        .flat_map(|call| code[lambda..].match_indices(call).map(|(i, _)| lambda + i))
+        .collect();
+    calls.sort();
+    calls
+}
+
+/// Kotlin information of the application
+struct KotlinInfo {
+    markers: Vec<String>,
+    coroutines: Option<String>,
+    classes: usize,
+    main_thread_calls: usize,
+}
+
+impl Serialize for KotlinInfo {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("KotlinInfo", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "markers", &self.markers));
+        try!(serializer.serialize_struct_elt(&mut state, "coroutines", self.coroutines.is_some()));
+        let version = match self.coroutines {
+            Some(ref v) if !v.is_empty() => Some(v.as_str()),
+            _ => None,
+        };
+        try!(serializer.serialize_struct_elt(&mut state, "coroutines_version", version));
+        try!(serializer.serialize_struct_elt(&mut state, "classes", self.classes));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "main_thread_network_calls",
+                                             self.main_thread_calls));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Detects if the application was written in Kotlin, and looks for the Kotlin specific issues
+/// of its decompiled code, such as blocking network calls in coroutines launched in the main
+/// thread
+///
+/// The Kotlin section of the report is only added for Kotlin applications.
+pub fn kotlin_analysis(config: &Config, results: &mut Results) {
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let markers = detect_kotlin(&dist_folder);
+    if markers.is_empty() {
+        if config.is_verbose() {
+            println!("The application does not seem to be written in Kotlin.");
+        }
+        return;
+    }
+    if config.is_verbose() {
+        println!("The application was written in Kotlin. Looking for Kotlin specific issues.");
+    }
+
+    let mut info = KotlinInfo {
+        markers: markers,
+        coroutines: detect_coroutines(&dist_folder),
+        classes: 0,
+        main_thread_calls: 0,
+    };
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the Kotlin \
+                                   analysis, the coroutines will not be checked. Error: {}",
+                                  e),
+                          config.is_verbose());
+            Vec::new()
+        }
+    };
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !code.contains("@Metadata(") {
+            continue;
+        }
+        info.classes += 1;
+        if info.coroutines.is_none() {
+            continue;
+        }
+
+        let outer_code = match get_outer_class_file(&path) {
+            Some(outer) => read_text_file(&outer).ok().and_then(|c| c),
+            None => None,
+        };
+        let calls = find_main_thread_network_calls(&code, outer_code.as_ref().map(|c| c.as_str()));
+        if calls.is_empty() {
+            continue;
+        }
+        info.main_thread_calls += calls.len();
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for index in calls {
+            add_network_call_finding(&code, index, relative, &class, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("{} Kotlin classes found.", info.classes);
+    }
+    results.add_section(get_report_section(&info));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The Kotlin code was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Kotlin code analyzed.");
+    }
+}
+
+/// Adds a finding for a blocking network call in a coroutine launched in the main thread
+fn add_network_call_finding(code: &str,
+                            index: usize,
+                            path: &Path,
+                            class: &str,
+                            config: &Config,
+                            results: &mut Results) {
+    let description = format!("A coroutine in {} makes a blocking network call, and it's \
+                               launched in the main thread without switching to the IO \
+                               dispatcher. The user interface is frozen until the server \
+                               responds, and the application can be reported as not \
+                               responding.",
+                              describe_location(class, None));
+    let line = get_line_for(index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Low);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Low,
+                                      String::from("Network call in main thread coroutine"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-400")],
+                                        vec![String::from("MASVS-CODE-4")],
+                                        Vec::new(),
+                                        Vec::new(),
+                                        Some(String::from("Make the network calls with \
+                                                           withContext(Dispatchers.IO), or \
+                                                           with the suspending functions of \
+                                                           the HTTP client."))));
+    results.add_vulnerability(vuln);
+}
+
+/// Generates the Kotlin section of the report
+fn get_report_section(info: &KotlinInfo) -> ReportSection {
+    let mut section = ReportSection::new("kotlin", "Kotlin", SECTION_ORDER);
+    section.set_data(info);
+    let coroutines = match info.coroutines {
+        Some(ref v) if !v.is_empty() => format!("Yes ({})", v),
+        Some(_) => String::from("Yes"),
+        None => String::from("No"),
+    };
+    let rows = vec![vec![String::from("Evidence"),
+                         Results::html_escape(&info.markers.join("\n")).replace("\n", "<br>")],
+                    vec![String::from("Kotlin classes"), format!("{}", info.classes)],
+                    vec![String::from("Coroutines"), Results::html_escape(&coroutines)],
+                    vec![String::from("Network calls in main thread coroutines"),
+                         format!("{}", info.main_thread_calls)]];
+    section.set_html_table(&["Property", "Value"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{is_intrinsics_check, get_outer_class_file, find_main_thread_network_calls};
+
+    #[test]
+    fn it_intrinsics_check() {
+        let code = "MessageDigest digest = MessageDigest.getInstance(\"MD5\");\n    \
+                    Intrinsics.checkNotNullExpressionValue(digest, \
+                    \"getInstance(\\\"MD5\\\")\");\n";
+        assert!(!is_intrinsics_check(code, code.find("\"MD5\"").unwrap()));
+        assert!(is_intrinsics_check(code, code.rfind("\"MD5").unwrap()));
+        assert!(!is_intrinsics_check("Intrinsics.areEqual(a, b);", 0));
+    }
+
+    #[test]
+    fn it_outer_class_file() {
+        assert_eq!(get_outer_class_file(Path::new("classes/com/example/MainActivity$onCreate$1.\
+                                                   java")),
+                   Some(PathBuf::from("classes/com/example/MainActivity.java")));
+        assert_eq!(get_outer_class_file(Path::new("classes/com/example/MainActivity.java")),
+                   None);
+    }
+
+    #[test]
+    fn it_main_thread_network_calls() {
+        let lambda = "final class MainActivity$onCreate$1 extends SuspendLambda {\n\
+                      public final Object invokeSuspend(Object obj) {\n\
+                      URLConnection c = new URL(this.$url).openConnection();\n\
+                      Response r = this.$client.newCall(request).execute();\n\
+                      }\n}";
+        let outer = "BuildersKt.launch$default(LifecycleOwnerKt.getLifecycleScope(this), null, \
+                     null, new MainActivity$onCreate$1(url, null), 3, null);";
+        let calls = find_main_thread_network_calls(lambda, Some(outer));
+        assert_eq!(calls.len(), 2);
+        assert!(lambda[calls[0]..].starts_with("openConnection("));
+        assert!(lambda[calls[1]..].starts_with(").execute()"));
+
+        assert!(find_main_thread_network_calls(lambda, None).is_empty());
+        let io = lambda.replace("invokeSuspend(Object obj) {",
+                                "invokeSuspend(Object obj) {\nBuildersKt.withContext(\
+                                 Dispatchers.getIO(), block);");
+        assert!(find_main_thread_network_calls(&io, Some(outer)).is_empty());
+    }
+}
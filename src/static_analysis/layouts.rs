@@ -0,0 +1,582 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Result, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::get_java_files;
+use super::logging::{get_names_regex, is_sensitive_name};
+use super::read_text_file;
+
+/// Order of the layouts section in the report, after the backups
+const SECTION_ORDER: u32 = 88;
+
+/// Declaration of a method with a single `View` parameter, that can handle clicks
+const CLICK_HANDLER: &'static str = concat!(r"\b(\w+)\s*\(\s*(?:final\s+)?",
+                                            r"(?:android\.view\.)?View\s+\w+\s*\)\s*",
+                                            r"(?:throws[^{;]*)?\{");
+
+/// Input types that keep the typed text out of the keyboard suggestions and caches
+const PRIVATE_INPUT_TYPES: [&'static str; 5] = ["textNoSuggestions",
+                                                "textPassword",
+                                                "textVisiblePassword",
+                                                "textWebPassword",
+                                                "numberPassword"];
+
+/// Element of a layout file
+///
+/// Attributes of the `android` namespace are stored without their prefix, and the rest, such as
+/// `app:hint` or `tools:text`, with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutElement {
+    name: String,
+    attributes: BTreeMap<String, String>,
+    line: usize,
+}
+
+impl LayoutElement {
+    /// Gets the name of the element, such as `EditText` or `android.webkit.WebView`
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Gets the value of an attribute, such as `inputType` for `android:inputType`
+    pub fn get_attribute(&self, attribute: &str) -> Option<&str> {
+        self.attributes.get(attribute).map(|v| v.as_str())
+    }
+
+    /// Gets the line of the layout file where the element starts, starting at 0
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    /// Gets the name of the ID of the element, without the `@+id/` prefix
+    pub fn get_id(&self) -> Option<&str> {
+        self.get_attribute("id").map(|id| match id.find('/') {
+            Some(i) => &id[i + 1..],
+            None => id,
+        })
+    }
+
+    /// Checks if the element is an editable text field
+    pub fn is_text_input(&self) -> bool {
+        let class = self.name.rsplit('.').next().unwrap();
+        class.ends_with("EditText") || class.ends_with("AutoCompleteTextView")
+    }
+
+    /// Checks if the element is a password field, by its input type or the deprecated
+    /// `android:password` attribute
+    pub fn is_password_input(&self) -> bool {
+        self.get_attribute("password") == Some("true") ||
+        self.get_attribute("inputType").map_or(false, |t| t.contains("assword"))
+    }
+
+    /// Checks if the text typed in the field is kept out of the keyboard suggestions
+    pub fn is_private_input(&self) -> bool {
+        self.get_attribute("password") == Some("true") ||
+        self.get_attribute("inputType").map_or(false, |types| {
+            types.split('|').any(|t| PRIVATE_INPUT_TYPES.contains(&t.trim()))
+        })
+    }
+}
+
+/// Layout file of the application
+#[derive(Debug)]
+pub struct Layout {
+    name: String,
+    path: PathBuf,
+    code: String,
+    elements: Vec<LayoutElement>,
+}
+
+impl Layout {
+    /// Gets the name of the layout resource, such as `activity_login`
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Gets the path of the layout file, relative to the dist folder
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the XML code of the layout
+    pub fn get_code(&self) -> &str {
+        self.code.as_str()
+    }
+
+    /// Gets the elements of the layout, in the order they appear in the file
+    pub fn get_elements(&self) -> &[LayoutElement] {
+        &self.elements
+    }
+}
+
+/// Checks if a text field of a layout has a sensitive ID or hint
+pub fn is_sensitive_field(element: &LayoutElement, names: &Regex) -> bool {
+    ["id", "hint", "app:hint", "contentDescription"]
+        .iter()
+        .filter_map(|attribute| element.get_attribute(attribute))
+        .any(|value| {
+            let name = match value.rfind('/') {
+                Some(i) => &value[i + 1..],
+                None => value,
+            };
+            is_sensitive_name(name, names)
+        })
+}
+
+/// Parses the elements of a layout file, with their attributes
+///
+/// A regular expression is used instead of an XML parser so that the lines of the elements are
+/// known, and so that layouts with errors are still analyzed.
+pub fn parse_layout(code: &str) -> Vec<LayoutElement> {
+    let element = Regex::new(r#"<([A-Za-z][\w.]*)((?:\s+[\w:.\-]+\s*=\s*"[^"]*")*)\s*/?>"#)
+        .unwrap();
+    let attribute = Regex::new(r#"([\w:.\-]+)\s*=\s*"([^"]*)""#).unwrap();
+    element.captures_iter(code)
+        .map(|caps| {
+            let (start, _) = caps.pos(0).unwrap();
+            let attributes = attribute.captures_iter(caps.at(2).unwrap_or(""))
+                .map(|attr| {
+                    let name = attr.at(1).unwrap();
+                    let name = if name.starts_with("android:") {
+                        &name[8..]
+                    } else {
+                        name
+                    };
+                    (String::from(name), String::from(attr.at(2).unwrap()))
+                })
+                .collect();
+            LayoutElement {
+                name: String::from(caps.at(1).unwrap()),
+                attributes: attributes,
+                line: get_line_for(start, code),
+            }
+        })
+        .collect()
+}
+
+/// Gets the layout files in the `res/layout*` folders, such as `res/layout-land`
+fn get_layout_files(dist_folder: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let res_folder = dist_folder.join("res");
+    if !res_folder.is_dir() {
+        return Ok(files);
+    }
+    for folder in try!(fs::read_dir(&res_folder)) {
+        let folder = try!(folder).path();
+        if !folder.is_dir() ||
+           !folder.file_name().map_or(false, |f| f.to_string_lossy().starts_with("layout")) {
+            continue;
+        }
+        for file in try!(fs::read_dir(&folder)) {
+            let file = try!(file).path();
+            if file.extension().map_or(false, |e| e == "xml") {
+                files.push(file);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Loads and parses the layout files of the application
+pub fn load_layouts(config: &Config) -> Vec<Layout> {
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_layout_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading the layout files, they will \
+                                   not be analyzed. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return Vec::new();
+        }
+    };
+
+    let mut layouts = Vec::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        layouts.push(Layout {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            path: path.strip_prefix(&dist_folder).unwrap().to_path_buf(),
+            elements: parse_layout(&code),
+            code: code,
+        });
+    }
+    layouts
+}
+
+/// Issue found in a layout file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayoutIssue {
+    /// WebView declared in the layout, whose settings must be checked in the code
+    WebView,
+    /// Password or sensitive field without `autofillHints`
+    MissingAutofillHints,
+    /// Deprecated `android:password` attribute
+    PasswordAttribute,
+    /// `android:onClick` handler that is not declared in the code
+    MissingClickHandler,
+}
+
+impl LayoutIssue {
+    fn get_label(&self) -> &'static str {
+        match *self {
+            LayoutIssue::WebView => "WebView declared in layout",
+            LayoutIssue::MissingAutofillHints => "Sensitive field without autofill hints",
+            LayoutIssue::PasswordAttribute => "Deprecated password attribute",
+            LayoutIssue::MissingClickHandler => "Missing onClick handler",
+        }
+    }
+
+    fn get_criticity(&self) -> Criticity {
+        match *self {
+            LayoutIssue::PasswordAttribute => Criticity::Low,
+            LayoutIssue::WebView |
+            LayoutIssue::MissingAutofillHints |
+            LayoutIssue::MissingClickHandler => Criticity::Warning,
+        }
+    }
+
+    fn get_cwe(&self) -> Option<&'static str> {
+        match *self {
+            LayoutIssue::WebView => None,
+            LayoutIssue::MissingAutofillHints => Some("CWE-200"),
+            LayoutIssue::PasswordAttribute => Some("CWE-549"),
+            LayoutIssue::MissingClickHandler => Some("CWE-248"),
+        }
+    }
+
+    fn get_masvs(&self) -> &'static str {
+        match *self {
+            LayoutIssue::WebView => "MASVS-PLATFORM-2",
+            LayoutIssue::MissingAutofillHints |
+            LayoutIssue::PasswordAttribute => "MASVS-PLATFORM-3",
+            LayoutIssue::MissingClickHandler => "MASVS-CODE-4",
+        }
+    }
+
+    fn get_description(&self) -> &'static str {
+        match *self {
+            LayoutIssue::WebView => {
+                "The settings of the WebView, such as JavaScript, file access and the JavaScript \
+                 interfaces, are set in the code of the screen that inflates the layout, and \
+                 should be reviewed there."
+            }
+            LayoutIssue::MissingAutofillHints => {
+                "Without autofill hints, autofill services and password managers must guess the \
+                 content of the field, so they can fail to fill it, or save the data it holds \
+                 as a different kind of data."
+            }
+            LayoutIssue::PasswordAttribute => {
+                "The android:password attribute is deprecated, and it's ignored when the field \
+                 has an input type that is not a password type, showing the typed text. The \
+                 textPassword input type should be used instead."
+            }
+            LayoutIssue::MissingClickHandler => {
+                "No method with that name and a View parameter was found in the code, so the \
+                 application will crash when the element is clicked, unless the method was \
+                 removed or renamed by the obfuscator."
+            }
+        }
+    }
+}
+
+/// Issue found in a layout file
+#[derive(Debug, PartialEq, Eq)]
+struct LayoutFinding {
+    issue: LayoutIssue,
+    /// Line of the element in the file
+    line: usize,
+    /// ID or name of the element, or name of the handler
+    detail: String,
+}
+
+/// Finds the names of the methods with a single `View` parameter declared in the code, that
+/// can be used as `android:onClick` handlers
+fn find_click_handlers(code: &str, handler: &Regex) -> Vec<String> {
+    handler.captures_iter(code).map(|caps| String::from(caps.at(1).unwrap())).collect()
+}
+
+/// Finds the issues of the elements of a layout
+///
+/// The `android:onClick` handlers are only checked if the handlers declared in the code are
+/// given, since they can't be checked without the decompiled code.
+fn find_layout_issues(elements: &[LayoutElement],
+                      names: &Regex,
+                      handlers: Option<&BTreeSet<String>>)
+                      -> Vec<LayoutFinding> {
+    let mut findings = Vec::new();
+    for element in elements {
+        let detail = String::from(element.get_id().unwrap_or(element.get_name()));
+        if element.get_name().ends_with("WebView") {
+            findings.push(LayoutFinding {
+                issue: LayoutIssue::WebView,
+                line: element.get_line(),
+                detail: detail.clone(),
+            });
+        }
+        let sensitive = element.is_text_input() &&
+                        (element.is_password_input() || is_sensitive_field(element, names));
+        let autofill_excluded = element.get_attribute("importantForAutofill")
+            .map_or(false, |i| i.starts_with("no"));
+        if sensitive && element.get_attribute("autofillHints").is_none() && !autofill_excluded {
+            findings.push(LayoutFinding {
+                issue: LayoutIssue::MissingAutofillHints,
+                line: element.get_line(),
+                detail: detail.clone(),
+            });
+        }
+        if element.get_attribute("password") == Some("true") {
+            let detail = match element.get_attribute("inputType") {
+                Some(t) if !t.contains("assword") => {
+                    format!("{}, with the {} input type", detail, t)
+                }
+                _ => detail.clone(),
+            };
+            findings.push(LayoutFinding {
+                issue: LayoutIssue::PasswordAttribute,
+                line: element.get_line(),
+                detail: detail,
+            });
+        }
+        if let (Some(handler), Some(handlers)) = (element.get_attribute("onClick"), handlers) {
+            // Data binding expressions, such as `@{() -> viewModel.submit()}`
+            if !handler.starts_with('@') && !handlers.contains(handler) {
+                findings.push(LayoutFinding {
+                    issue: LayoutIssue::MissingClickHandler,
+                    line: element.get_line(),
+                    detail: format!("{}(), in {}", handler, detail),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Gets the `android:onClick` handlers declared in the decompiled code, if it can be read
+fn get_click_handlers(config: &Config) -> Option<BTreeSet<String>> {
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(ref f) if f.is_empty() => return None,
+        Ok(f) => f,
+        Err(_) => return None,
+    };
+    let handler = Regex::new(CLICK_HANDLER).unwrap();
+    let mut handlers = BTreeSet::new();
+    for path in files {
+        if let Ok(Some(code)) = read_text_file(&path) {
+            handlers.extend(find_click_handlers(&code, &handler));
+        }
+    }
+    Some(handlers)
+}
+
+/// Analyzes the layout files of the application: WebViews declared in them, password and
+/// sensitive fields without autofill hints, the deprecated `android:password` attribute and
+/// `android:onClick` handlers that are not declared in the code
+///
+/// The findings are listed in the layouts section of the report too.
+pub fn layout_analysis(layouts: &[Layout], config: &Config, results: &mut Results) {
+    if layouts.is_empty() {
+        return;
+    }
+    if config.is_verbose() {
+        println!("Analyzing the layout files.");
+    }
+    let names = get_names_regex(config.get_logging_config().get_sensitive_names());
+    let handlers = if layouts.iter()
+        .any(|l| l.get_elements().iter().any(|e| e.get_attribute("onClick").is_some())) {
+        get_click_handlers(config)
+    } else {
+        None
+    };
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for layout in layouts {
+        for finding in find_layout_issues(layout.get_elements(), &names, handlers.as_ref()) {
+            add_layout_finding(&finding, layout, config, results);
+
+            let location = layout.get_path().to_string_lossy().into_owned();
+            rows.push(vec![Results::html_escape(&location),
+                           String::from(finding.issue.get_label()),
+                           format!("<code>{}</code>", Results::html_escape(&finding.detail))]);
+            let mut entry = BTreeMap::new();
+            let _ = entry.insert("file", location);
+            let _ = entry.insert("issue", String::from(finding.issue.get_label()));
+            let _ = entry.insert("detail", finding.detail);
+            data.push(entry);
+        }
+    }
+
+    let mut section = ReportSection::new("layouts", "Layouts", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["File", "Issue", "Detail"], &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The layout files were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Layout files analyzed.");
+    }
+}
+
+/// Adds a finding for an issue of a layout file
+fn add_layout_finding(finding: &LayoutFinding,
+                      layout: &Layout,
+                      config: &Config,
+                      results: &mut Results) {
+    let issue = finding.issue;
+    let description = format!("{} found in the {} layout: {}. {}",
+                              issue.get_label(),
+                              layout.get_name(),
+                              finding.detail,
+                              issue.get_description());
+    let criticity = issue.get_criticity();
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(issue.get_label()),
+                                      description,
+                                      Some(layout.get_path()),
+                                      Some(finding.line),
+                                      Some(finding.line),
+                                      Some(get_code(layout.get_code(),
+                                                    finding.line,
+                                                    finding.line)));
+    vuln.set_metadata(RuleMetadata::new(issue.get_cwe().into_iter().map(String::from).collect(),
+                                        vec![String::from(issue.get_masvs())],
+                                        Vec::new(),
+                                        Vec::new(),
+                                        None));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use regex::Regex;
+
+    use super::{LayoutIssue, CLICK_HANDLER, parse_layout, is_sensitive_field,
+                find_click_handlers, find_layout_issues};
+    use super::super::logging::get_names_regex;
+
+    #[test]
+    fn it_parse_layout() {
+        let code = r#"<?xml version="1.0" encoding="utf-8"?>
+<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android"
+    android:orientation="vertical">
+    <EditText android:id="@+id/password"
+        android:inputType="textPassword" />
+    <com.google.android.material.textfield.TextInputEditText
+        android:id="@+id/card_number"
+        app:hint="@string/card"
+        android:inputType="number|textNoSuggestions" />
+    <Button android:id="@id/submit" android:onClick="onSubmit" />
+</LinearLayout>"#;
+        let elements = parse_layout(code);
+        assert_eq!(elements.len(), 4);
+        assert_eq!(elements[0].get_name(), "LinearLayout");
+        assert_eq!(elements[0].get_attribute("orientation"), Some("vertical"));
+        assert!(!elements[0].is_text_input());
+
+        assert_eq!(elements[1].get_line(), 3);
+        assert_eq!(elements[1].get_id(), Some("password"));
+        assert!(elements[1].is_text_input());
+        assert!(elements[1].is_password_input());
+        assert!(elements[1].is_private_input());
+
+        assert_eq!(elements[2].get_id(), Some("card_number"));
+        assert_eq!(elements[2].get_attribute("app:hint"), Some("@string/card"));
+        assert!(elements[2].is_text_input());
+        assert!(!elements[2].is_password_input());
+        assert!(elements[2].is_private_input());
+
+        assert_eq!(elements[3].get_id(), Some("submit"));
+        assert_eq!(elements[3].get_attribute("onClick"), Some("onSubmit"));
+        assert!(!elements[3].is_text_input());
+    }
+
+    #[test]
+    fn it_is_sensitive_field() {
+        let names = get_names_regex(&[]);
+        let elements = parse_layout(r#"<LinearLayout>
+    <EditText android:id="@+id/card_number" />
+    <EditText android:id="@+id/name" android:hint="@string/password_hint" />
+    <EditText android:id="@+id/search" />
+</LinearLayout>"#);
+        assert!(is_sensitive_field(&elements[1], &names));
+        assert!(is_sensitive_field(&elements[2], &names));
+        assert!(!is_sensitive_field(&elements[3], &names));
+    }
+
+    #[test]
+    fn it_find_click_handlers() {
+        let handler = Regex::new(CLICK_HANDLER).unwrap();
+        let code = "public void onSubmit(View view) {\n\
+                    }\n\
+                    public void onCancel(final android.view.View v) throws IOException {\n\
+                    }\n\
+                    public void onSave(View view, int position) {\n\
+                    }\n\
+                    button.setOnClickListener(onClick(view));";
+        assert_eq!(find_click_handlers(code, &handler),
+                   vec![String::from("onSubmit"), String::from("onCancel")]);
+    }
+
+    #[test]
+    fn it_find_layout_issues() {
+        let names = get_names_regex(&[]);
+        let elements = parse_layout(r#"<LinearLayout>
+    <WebView android:id="@+id/browser" />
+    <EditText android:id="@+id/password" android:inputType="textPassword" />
+    <EditText android:id="@+id/pin" android:password="true" android:inputType="number"
+        android:autofillHints="password" />
+    <EditText android:id="@+id/user_password" android:autofillHints="password"
+        android:inputType="textPassword" />
+    <Button android:onClick="onSubmit" />
+    <Button android:id="@+id/cancel" android:onClick="onCancel" />
+</LinearLayout>"#);
+        let mut handlers = BTreeSet::new();
+        let _ = handlers.insert(String::from("onSubmit"));
+        let issues: Vec<_> = find_layout_issues(&elements, &names, Some(&handlers))
+            .into_iter()
+            .map(|f| (f.issue, f.line, f.detail))
+            .collect();
+        assert_eq!(issues,
+                   vec![(LayoutIssue::WebView, 1, String::from("browser")),
+                        (LayoutIssue::MissingAutofillHints, 2, String::from("password")),
+                        (LayoutIssue::PasswordAttribute,
+                         3,
+                         String::from("pin, with the number input type")),
+                        (LayoutIssue::MissingClickHandler,
+                         8,
+                         String::from("onCancel(), in cancel"))]);
+        assert!(find_layout_issues(&elements[6..], &names, None).is_empty());
+    }
+}
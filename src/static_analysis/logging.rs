@@ -0,0 +1,361 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, find_assignment, split_methods,
+                  split_words, get_java_files, get_class_name, get_enclosing_method,
+                  describe_location};
+use super::structural::{get_closing_index, split_arguments};
+use super::read_text_file;
+
+/// Sensitive variable, field and method names, matched in lowercase snake case
+const SENSITIVE_NAMES: [&'static str; 11] = [r"passw(?:or)?d",
+                                             r"(?:^|_)pwd(?:_|$)",
+                                             r"(?:^|_)tokens?(?:_|$)",
+                                             r"secret",
+                                             r"(?:^|_)ssn(?:_|$)",
+                                             r"credit_?card",
+                                             r"card_?number",
+                                             r"(?:^|_)cvv(?:_|$)",
+                                             r"api_?key",
+                                             r"private_?key",
+                                             r"credential"];
+
+/// Cryptographic and network calls whose result should not be logged, with their kind
+const SENSITIVE_CALLS: [(&'static str, &'static str); 12] = [("doFinal", "cryptographic"),
+                                                             ("getEncoded", "cryptographic"),
+                                                             ("digest", "cryptographic"),
+                                                             ("sign", "cryptographic"),
+                                                             ("generateKey", "cryptographic"),
+                                                             ("generateSecret", "cryptographic"),
+                                                             ("getHeaderField", "network"),
+                                                             ("getHeaderFields", "network"),
+                                                             ("getCookie", "network"),
+                                                             ("getAuthToken", "network"),
+                                                             ("blockingGetAuthToken", "network"),
+                                                             ("peekAuthToken", "network")];
+
+/// Methods of `Log` and `Timber` that write to the system log
+const LOG_METHODS: [&'static str; 7] = ["v", "d", "i", "w", "e", "wtf", "println"];
+
+/// Methods of `System.out` and `System.err` that write to the standard output
+const PRINT_METHODS: [&'static str; 4] = ["print", "println", "printf", "format"];
+
+/// Calls on a sensitive value that don't reveal it
+const HARMLESS_CALLS: [&'static str; 4] = ["length", "size", "isEmpty", "hashCode"];
+
+/// Sensitive data passed to a logging call
+#[derive(Debug, PartialEq, Eq)]
+enum LoggedData<'a> {
+    /// Variable, field or getter with a sensitive name
+    Name(&'a str),
+    /// Cryptographic or network call, with its kind
+    Call(&'a str, &'static str),
+    /// Variable assigned with the result of a cryptographic or network call
+    Result(&'a str, &'a str, &'static str),
+}
+
+impl<'a> LoggedData<'a> {
+    /// Describes the logged data, for the description of the finding
+    fn describe(&self) -> String {
+        match *self {
+            LoggedData::Name(name) => format!("the value of {}", name),
+            LoggedData::Call(call, kind) => format!("the result of the {} call {}()", kind, call),
+            LoggedData::Result(variable, call, kind) => {
+                format!("the {} variable, which holds the result of the {} call {}()",
+                        variable,
+                        kind,
+                        call)
+            }
+        }
+    }
+}
+
+/// Logging call with sensitive data in its arguments
+#[derive(Debug, PartialEq, Eq)]
+struct SensitiveLog<'a> {
+    /// Logging call, with its class, such as `Log.d`
+    call: String,
+    data: LoggedData<'a>,
+    /// Index of the call in the code
+    index: usize,
+}
+
+/// Builds the regular expression matching the built-in sensitive names and the ones of the
+/// configuration
+pub fn get_names_regex(extra_names: &[String]) -> Regex {
+    let patterns = SENSITIVE_NAMES.iter()
+        .map(|p| format!("(?:{})", p))
+        .chain(extra_names.iter().map(|p| format!("(?:{})", p)))
+        .collect::<Vec<_>>();
+    Regex::new(&patterns.join("|")).unwrap()
+}
+
+/// Checks if a variable, field or method name is sensitive
+///
+/// Constants, in upper case, are not considered sensitive, since they are usually the keys of
+/// the values, such as `PASSWORD_KEY`.
+pub fn is_sensitive_name(name: &str, names: &Regex) -> bool {
+    name.chars().any(|c| c.is_lowercase()) && names.is_match(&split_words(name).join("_"))
+}
+
+/// Gets the logging call whose method name is at the given index, with the number of leading
+/// arguments that are not logged, such as the tag
+fn get_logging_call(tokens: &[Token], index: usize) -> Option<(String, usize, usize)> {
+    if index < 2 || tokens[index - 1].get_text() != "." {
+        return None;
+    }
+    let method = tokens[index].get_text();
+    let receiver = tokens[index - 2].get_text();
+    match receiver {
+        "Log" if LOG_METHODS.contains(&method) => {
+            let skipped = if method == "println" { 2 } else { 1 };
+            Some((format!("Log.{}", method), tokens[index - 2].get_start(), skipped))
+        }
+        "Timber" if LOG_METHODS.contains(&method) && method != "println" => {
+            Some((format!("Timber.{}", method), tokens[index - 2].get_start(), 0))
+        }
+        "out" | "err" if PRINT_METHODS.contains(&method) && index >= 4 &&
+                         tokens[index - 3].get_text() == "." &&
+                         tokens[index - 4].get_text() == "System" => {
+            Some((format!("System.{}.{}", receiver, method), tokens[index - 4].get_start(), 0))
+        }
+        _ => None,
+    }
+}
+
+/// Gets the cryptographic or network call in the expression, if any, with its kind
+fn get_sensitive_call<'a>(expression: &[Token<'a>]) -> Option<(&'a str, &'static str)> {
+    expression.iter()
+        .enumerate()
+        .filter(|&(i, _)| is_call(expression, i))
+        .filter_map(|(_, token)| {
+            SENSITIVE_CALLS.iter()
+                .find(|&&(call, _)| call == token.get_text())
+                .map(|&(_, kind)| (token.get_text(), kind))
+        })
+        .next()
+}
+
+/// Finds the sensitive data in an argument of a logging call
+///
+/// Variables are followed back to their last assignment in the previous statements of the
+/// method, to find the results of cryptographic and network calls.
+fn find_logged_data<'a>(argument: &[Token<'a>],
+                        previous: &[&[Token<'a>]],
+                        names: &Regex)
+                        -> Option<LoggedData<'a>> {
+    for (i, token) in argument.iter().enumerate() {
+        if token.get_kind() != TokenKind::Identifier {
+            continue;
+        }
+        let text = token.get_text();
+        if is_call(argument, i) {
+            if let Some(&(_, kind)) = SENSITIVE_CALLS.iter().find(|&&(c, _)| c == text) {
+                return Some(LoggedData::Call(text, kind));
+            }
+            if is_sensitive_name(text, names) {
+                return Some(LoggedData::Name(text));
+            }
+            continue;
+        }
+        let harmless = argument.get(i + 1).map(|t| t.get_text()) == Some(".") &&
+                       argument.get(i + 2).map_or(false, |t| {
+            HARMLESS_CALLS.contains(&t.get_text())
+        });
+        if harmless {
+            continue;
+        }
+        if is_sensitive_name(text, names) {
+            return Some(LoggedData::Name(text));
+        }
+        if let Some((_, value)) = find_assignment(text, previous) {
+            if let Some((call, kind)) = get_sensitive_call(value) {
+                return Some(LoggedData::Result(text, call, kind));
+            }
+        }
+    }
+    None
+}
+
+/// Finds the logging calls with sensitive data in their arguments
+fn find_sensitive_logs<'a>(code: &'a str, names: &Regex) -> Vec<SensitiveLog<'a>> {
+    let tokens = tokenize(code);
+    let mut logs = Vec::new();
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        for (i, statement) in statements.iter().enumerate() {
+            for j in 0..statement.len() {
+                if !is_call(statement, j) {
+                    continue;
+                }
+                let (call, index, skipped) = match get_logging_call(statement, j) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let close = match get_closing_index(statement, j + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let data = split_arguments(&statement[j + 2..close])
+                    .into_iter()
+                    .skip(skipped)
+                    .filter_map(|argument| find_logged_data(argument, &statements[..i], names))
+                    .next();
+                if let Some(data) = data {
+                    logs.push(SensitiveLog {
+                        call: call,
+                        data: data,
+                        index: index,
+                    });
+                }
+            }
+        }
+    }
+    logs.sort_by_key(|log| log.index);
+    logs
+}
+
+/// Looks for logging calls that write sensitive data to the system log or the standard output
+///
+/// Unlike the generic logging rule, only calls with sensitive names in their arguments, or with
+/// the results of cryptographic and network calls, are reported.
+pub fn logging_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for sensitive data written to the logs.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the logging \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let names = get_names_regex(config.get_logging_config().get_sensitive_names());
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !code.contains("Log.") && !code.contains("System.") && !code.contains("Timber.") {
+            continue;
+        }
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for log in find_sensitive_logs(&code, &names) {
+            add_log_finding(&log, &code, relative, &class, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The logging calls were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Logging calls analyzed.");
+    }
+}
+
+/// Adds a finding for a logging call with sensitive data
+fn add_log_finding(log: &SensitiveLog,
+                   code: &str,
+                   path: &Path,
+                   class: &str,
+                   config: &Config,
+                   results: &mut Results) {
+    let description = format!("{}() writes {} to the logs in {}. Logs can be read with ADB, by \
+                               privileged applications and, on old versions of Android, by any \
+                               application with the READ_LOGS permission, and they are often \
+                               included in crash reports.",
+                              log.call,
+                              log.data.describe(),
+                              describe_location(class, get_enclosing_method(code, log.index)));
+    let line = get_line_for(log.index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Medium);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Medium,
+                                      String::from("Sensitive data in logs"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-532")],
+                                        vec![String::from("MASVS-STORAGE-2")],
+                                        vec![String::from("M9")],
+                                        Vec::new(),
+                                        Some(String::from("Don't log sensitive data, and \
+                                                           remove the logging calls from \
+                                                           release builds, for example with \
+                                                           ProGuard rules."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoggedData, find_sensitive_logs, get_names_regex, is_sensitive_name};
+
+    #[test]
+    fn it_find_sensitive_logs() {
+        let code = "void login(String user, String password) {\n\
+                    Log.d(\"LoginActivity\", \"Login: \" + user);\n\
+                    Log.d(TAG, \"Password: \" + password);\n\
+                    Log.i(TAG, \"Password length: \" + password.length());\n\
+                    Log.println(3, \"TOKEN_SERVICE\", \"Starting\");\n\
+                    byte[] encrypted = cipher.doFinal(data);\n\
+                    System.out.println(\"Encrypted: \" + encrypted);\n\
+                    Timber.w(\"Cookie %s\", CookieManager.getInstance().getCookie(url));\n\
+                    Log.e(TAG, \"Session: \" + session.getAuthToken());\n\
+                    Log.v(TAG, \"IBAN: \" + account.iban);\n\
+                    }";
+        let names = get_names_regex(&[]);
+        let logs = find_sensitive_logs(code, &names);
+        let found: Vec<_> = logs.iter().map(|l| (l.call.as_str(), &l.data)).collect();
+        assert_eq!(found,
+                   vec![("Log.d", &LoggedData::Name("password")),
+                        ("System.out.println",
+                         &LoggedData::Result("encrypted", "doFinal", "cryptographic")),
+                        ("Timber.w", &LoggedData::Call("getCookie", "network")),
+                        ("Log.e", &LoggedData::Call("getAuthToken", "network"))]);
+        assert_eq!(logs[0].index, code.find("Log.d(TAG").unwrap());
+
+        let names = get_names_regex(&[String::from("(?:^|_)iban(?:_|$)")]);
+        let logs = find_sensitive_logs(code, &names);
+        assert_eq!(logs.last().unwrap().data, LoggedData::Name("iban"));
+    }
+
+    #[test]
+    fn it_is_sensitive_name() {
+        let names = get_names_regex(&[]);
+        assert!(is_sensitive_name("userPassword", &names));
+        assert!(is_sensitive_name("accessToken", &names));
+        assert!(is_sensitive_name("getApiKey", &names));
+        assert!(is_sensitive_name("ssn", &names));
+        assert!(!is_sensitive_name("PASSWORD_KEY", &names));
+        assert!(!is_sensitive_name("className", &names));
+        assert!(!is_sensitive_name("tokenizer", &names));
+    }
+}
@@ -0,0 +1,495 @@
+use std::result;
+use std::path::PathBuf;
+
+use serde::ser::{Serialize, Serializer};
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability};
+use results::{Results, ReportSection, Vulnerability};
+use super::manifest::{Manifest, Permission};
+use super::native::NativeLibrary;
+use super::java::{get_java_files, get_class_name};
+use super::{add_files_to_vec, read_text_file};
+
+/// Order of the malware likelihood section in the report, so that it's shown first
+const SECTION_ORDER: u32 = 1;
+
+/// Maximum number of places listed as evidence for each signal
+const MAX_EVIDENCE: usize = 5;
+
+/// Number of sensitive permissions from which the application is considered over-privileged
+const EXCESSIVE_PERMISSIONS: usize = 10;
+
+/// Minimum scores of the medium and high malware likelihoods
+const MEDIUM_SCORE: u32 = 30;
+const HIGH_SCORE: u32 = 60;
+
+/// Permissions giving access to personal data or to paid or sensitive device features
+const SENSITIVE_PERMISSIONS: [Permission; 24] =
+    [Permission::AndroidPermissionSendSms,
+     Permission::AndroidPermissionReadSms,
+     Permission::AndroidPermissionReceiveSms,
+     Permission::AndroidPermissionReceiveMms,
+     Permission::AndroidPermissionReceiveWapPush,
+     Permission::AndroidPermissionWriteSms,
+     Permission::AndroidPermissionReadContacts,
+     Permission::AndroidPermissionWriteContacts,
+     Permission::AndroidPermissionReadCallLog,
+     Permission::AndroidPermissionWriteCallLog,
+     Permission::AndroidPermissionProcessOutgoingCalls,
+     Permission::AndroidPermissionCallPhone,
+     Permission::AndroidPermissionReadPhoneState,
+     Permission::AndroidPermissionRecordAudio,
+     Permission::AndroidPermissionCamera,
+     Permission::AndroidPermissionAccessFineLocation,
+     Permission::AndroidPermissionReadCalendar,
+     Permission::AndroidPermissionGetAccounts,
+     Permission::AndroidPermissionSystemAlertWindow,
+     Permission::AndroidPermissionWriteSettings,
+     Permission::AndroidPermissionRequestInstallPackages,
+     Permission::AndroidPermissionReceiveBootCompleted,
+     Permission::AndroidPermissionBodySensors,
+     Permission::AndroidPermissionReadExternalStorage];
+
+/// Files of the native libraries and assets added by known packers
+const PACKER_FILES: [&'static str; 16] = ["libjiagu.so",
+                                          "libjiagu_x86.so",
+                                          "libsecexe.so",
+                                          "libsecmain.so",
+                                          "libSecShell.so",
+                                          "libDexHelper.so",
+                                          "libshella.so",
+                                          "libshellx.so",
+                                          "libtup.so",
+                                          "libexec.so",
+                                          "libexecmain.so",
+                                          "ijiami.dat",
+                                          "libmobisec.so",
+                                          "libbaiduprotect.so",
+                                          "libnqshield.so",
+                                          "libprotectClass.so"];
+
+/// Signal that the application could be malware
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MalwareSignal {
+    DynamicCodeLoading,
+    SmsSending,
+    PremiumSms,
+    DeviceAdmin,
+    AccessibilityAbuse,
+    ExcessivePermissions,
+    Packer,
+}
+
+/// All the signals, in the order of the report
+const SIGNALS: [MalwareSignal; 7] = [MalwareSignal::DynamicCodeLoading,
+                                     MalwareSignal::SmsSending,
+                                     MalwareSignal::PremiumSms,
+                                     MalwareSignal::DeviceAdmin,
+                                     MalwareSignal::AccessibilityAbuse,
+                                     MalwareSignal::ExcessivePermissions,
+                                     MalwareSignal::Packer];
+
+impl MalwareSignal {
+    pub fn get_id(&self) -> &'static str {
+        match *self {
+            MalwareSignal::DynamicCodeLoading => "dynamic_code_loading",
+            MalwareSignal::SmsSending => "sms_sending",
+            MalwareSignal::PremiumSms => "premium_sms",
+            MalwareSignal::DeviceAdmin => "device_admin",
+            MalwareSignal::AccessibilityAbuse => "accessibility_abuse",
+            MalwareSignal::ExcessivePermissions => "excessive_permissions",
+            MalwareSignal::Packer => "packer",
+        }
+    }
+
+    pub fn get_label(&self) -> &'static str {
+        match *self {
+            MalwareSignal::DynamicCodeLoading => "Dynamic code loading",
+            MalwareSignal::SmsSending => "SMS sending",
+            MalwareSignal::PremiumSms => "SMS to short numbers",
+            MalwareSignal::DeviceAdmin => "Device administrator request",
+            MalwareSignal::AccessibilityAbuse => "Accessibility service automation",
+            MalwareSignal::ExcessivePermissions => "Excessive permissions",
+            MalwareSignal::Packer => "Packer",
+        }
+    }
+
+    /// Gets the points the signal adds to the score, out of 100
+    pub fn get_weight(&self) -> u32 {
+        match *self {
+            MalwareSignal::SmsSending | MalwareSignal::ExcessivePermissions => 10,
+            MalwareSignal::DynamicCodeLoading |
+            MalwareSignal::DeviceAdmin |
+            MalwareSignal::Packer => 15,
+            MalwareSignal::AccessibilityAbuse => 20,
+            MalwareSignal::PremiumSms => 25,
+        }
+    }
+
+    /// Gets the code fragments that show the signal
+    fn get_markers(&self) -> &'static [&'static str] {
+        match *self {
+            MalwareSignal::DynamicCodeLoading => {
+                &["DexClassLoader", "InMemoryDexClassLoader", "DexFile.loadDex"]
+            }
+            MalwareSignal::SmsSending => {
+                &["sendTextMessage", "sendMultipartTextMessage", "sendDataMessage"]
+            }
+            MalwareSignal::DeviceAdmin => {
+                &["ACTION_ADD_DEVICE_ADMIN", "android.app.action.ADD_DEVICE_ADMIN"]
+            }
+            MalwareSignal::AccessibilityAbuse => {
+                &["performGlobalAction", "dispatchGesture", "ACTION_SET_TEXT"]
+            }
+            MalwareSignal::Packer => {
+                &["com.secneo.apkwrapper",
+                  "com.qihoo.util.StubApp",
+                  "com.tencent.StubShell",
+                  "com.baidu.protect.StubApplication",
+                  "com.shell.SuperApplication"]
+            }
+            MalwareSignal::PremiumSms | MalwareSignal::ExcessivePermissions => &[],
+        }
+    }
+}
+
+/// Gets the signals shown in the code, with the first marker found for each of them
+fn detect_signals(code: &str) -> Vec<(MalwareSignal, &'static str)> {
+    SIGNALS.iter()
+        .filter_map(|signal| {
+            signal.get_markers()
+                .iter()
+                .find(|marker| code.contains(*marker))
+                .map(|marker| (*signal, *marker))
+        })
+        .collect()
+}
+
+/// Finds the short numbers, usually premium rate services, SMS messages are sent to
+fn find_short_numbers(code: &str) -> Vec<&str> {
+    let regex = Regex::new(r#"send(?:Text|MultipartText|Data)Message\(\s*"(\+?\d{3,6})""#)
+        .unwrap();
+    regex.captures_iter(code).map(|caps| caps.at(1).unwrap()).collect()
+}
+
+/// Result of a signal of the score
+struct SignalResult {
+    signal: MalwareSignal,
+    evidence: Vec<String>,
+}
+
+impl SignalResult {
+    fn is_present(&self) -> bool {
+        !self.evidence.is_empty()
+    }
+
+    fn add_evidence(&mut self, evidence: String) {
+        if self.evidence.len() < MAX_EVIDENCE && !self.evidence.contains(&evidence) {
+            self.evidence.push(evidence);
+        }
+    }
+}
+
+impl Serialize for SignalResult {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("SignalResult", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.signal.get_id()));
+        try!(serializer.serialize_struct_elt(&mut state, "label", self.signal.get_label()));
+        try!(serializer.serialize_struct_elt(&mut state, "weight", self.signal.get_weight()));
+        try!(serializer.serialize_struct_elt(&mut state, "present", self.is_present()));
+        try!(serializer.serialize_struct_elt(&mut state, "evidence", &self.evidence));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Malware likelihood score of the application
+struct MalwareScore {
+    signals: Vec<SignalResult>,
+}
+
+impl MalwareScore {
+    fn get_signal(&mut self, signal: MalwareSignal) -> &mut SignalResult {
+        self.signals.iter_mut().find(|s| s.signal == signal).unwrap()
+    }
+
+    /// Gets the score, adding the weights of the signals found, up to 100
+    fn get_score(&self) -> u32 {
+        let score = self.signals
+            .iter()
+            .filter(|s| s.is_present())
+            .fold(0, |score, s| score + s.signal.get_weight());
+        if score > 100 { 100 } else { score }
+    }
+
+    fn get_likelihood(&self) -> &'static str {
+        match self.get_score() {
+            s if s >= HIGH_SCORE => "High",
+            s if s >= MEDIUM_SCORE => "Medium",
+            _ => "Low",
+        }
+    }
+}
+
+impl Serialize for MalwareScore {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("MalwareScore", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "score", self.get_score()));
+        try!(serializer.serialize_struct_elt(&mut state, "likelihood", self.get_likelihood()));
+        try!(serializer.serialize_struct_elt(&mut state, "signals", &self.signals));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Combines dynamic code loading, SMS sending, device administrator requests, accessibility
+/// automation, excessive permissions and packers in a malware likelihood score, shown at the top
+/// of the report
+///
+/// The score is a triage aid for application vetting, and it's only calculated if malware
+/// heuristics are enabled in the configuration.
+pub fn malware_analysis(manifest: Option<&Manifest>,
+                        libraries: &[NativeLibrary],
+                        config: &Config,
+                        results: &mut Results) {
+    if !config.is_malware_heuristics() {
+        return;
+    }
+    if config.is_verbose() {
+        println!("Looking for signals of malicious behaviour.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the malware \
+                                   heuristics, they will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut score = MalwareScore {
+        signals: SIGNALS.iter()
+            .map(|signal| {
+                SignalResult {
+                    signal: *signal,
+                    evidence: Vec::new(),
+                }
+            })
+            .collect(),
+    };
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for (signal, marker) in detect_signals(&code) {
+            score.get_signal(signal).add_evidence(format!("{} in {}", marker, class));
+        }
+        for number in find_short_numbers(&code) {
+            score.get_signal(MalwareSignal::PremiumSms)
+                .add_evidence(format!("SMS to {} in {}", number, class));
+        }
+    }
+
+    add_manifest_signals(manifest, &mut score);
+
+    let mut packer_files: Vec<_> = libraries.iter().map(|l| PathBuf::from(l.get_path())).collect();
+    let assets = dist_folder.join("assets");
+    if assets.is_dir() {
+        let _ = add_files_to_vec(&dist_folder, &assets, &mut packer_files);
+    }
+    for file in packer_files {
+        let is_packer = file.file_name()
+            .map_or(false, |n| PACKER_FILES.iter().any(|p| n == *p));
+        if is_packer {
+            let relative = file.strip_prefix(&dist_folder).unwrap_or(file.as_path());
+            score.get_signal(MalwareSignal::Packer).add_evidence(relative.display().to_string());
+        }
+    }
+
+    if config.is_verbose() {
+        println!("Malware likelihood: {} ({} of 100)",
+                 score.get_likelihood(),
+                 score.get_score());
+    }
+    if score.get_score() >= HIGH_SCORE {
+        add_score_finding(&score, config, results);
+    }
+    results.add_section(get_report_section(&score));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The malware heuristics were calculated correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Malware heuristics calculated.");
+    }
+}
+
+/// Adds the signals declared in the manifest, and discards the code signals that need a
+/// permission or a component the manifest doesn't declare
+fn add_manifest_signals(manifest: Option<&Manifest>, score: &mut MalwareScore) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+
+    let permissions = manifest.get_permission_checklist();
+    if !permissions.needs_permission(Permission::AndroidPermissionSendSms) {
+        score.get_signal(MalwareSignal::SmsSending).evidence.clear();
+        score.get_signal(MalwareSignal::PremiumSms).evidence.clear();
+    }
+    let sensitive: Vec<_> = SENSITIVE_PERMISSIONS.iter()
+        .filter(|p| permissions.needs_permission(**p))
+        .map(|p| p.as_str())
+        .collect();
+    if sensitive.len() >= EXCESSIVE_PERMISSIONS {
+        score.get_signal(MalwareSignal::ExcessivePermissions)
+            .add_evidence(format!("{} sensitive permissions: {}",
+                                  sensitive.len(),
+                                  sensitive.join(", ")));
+    }
+
+    let mut accessibility_services = Vec::new();
+    for component in manifest.get_components() {
+        match component.get_permission() {
+            Some("android.permission.BIND_DEVICE_ADMIN") => {
+                score.get_signal(MalwareSignal::DeviceAdmin)
+                    .add_evidence(format!("Device administrator receiver {}",
+                                          component.get_name()));
+            }
+            Some("android.permission.BIND_ACCESSIBILITY_SERVICE") => {
+                accessibility_services.push(format!("Accessibility service {}",
+                                                    component.get_name()));
+            }
+            _ => {}
+        }
+    }
+    // Accessibility actions are only suspicious in the application's own service
+    let accessibility = score.get_signal(MalwareSignal::AccessibilityAbuse);
+    if accessibility_services.is_empty() {
+        accessibility.evidence.clear();
+    } else if accessibility.is_present() {
+        for service in accessibility_services {
+            accessibility.add_evidence(service);
+        }
+    }
+}
+
+/// Adds a finding summarizing the signals of an application with a high malware likelihood
+fn add_score_finding(score: &MalwareScore, config: &Config, results: &mut Results) {
+    let signals: Vec<_> = score.signals
+        .iter()
+        .filter(|s| s.is_present())
+        .map(|s| s.signal.get_label())
+        .collect();
+    let description = format!("The application has a high malware likelihood score of {} out \
+                               of 100, with the following signals: {}. The behaviour of the \
+                               application should be reviewed manually before it's \
+                               distributed.",
+                              score.get_score(),
+                              signals.join(", "));
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::High);
+    }
+
+    let vuln = Vulnerability::new(Criticity::High,
+                                  String::from("High malware likelihood"),
+                                  description,
+                                  None as Option<&str>,
+                                  None,
+                                  None,
+                                  None);
+    results.add_vulnerability(vuln);
+}
+
+/// Generates the malware likelihood section of the report
+fn get_report_section(score: &MalwareScore) -> ReportSection {
+    let mut section = ReportSection::new("malware",
+                                         format!("Malware likelihood: {} ({} of 100)",
+                                                 score.get_likelihood(),
+                                                 score.get_score())
+                                             .as_str(),
+                                         SECTION_ORDER);
+    section.set_data(score);
+    let rows: Vec<_> = score.signals
+        .iter()
+        .map(|result| {
+            vec![String::from(result.signal.get_label()),
+                 result.signal.get_weight().to_string(),
+                 String::from(Results::html_yes_no(result.is_present())),
+                 Results::html_escape(&result.evidence.join("\n")).replace("\n", "<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Signal", "Weight", "Present", "Evidence"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MalwareSignal, MalwareScore, SignalResult, SIGNALS, detect_signals,
+                find_short_numbers};
+
+    #[test]
+    fn it_detect_signals() {
+        let code = "DexClassLoader loader = new DexClassLoader(path, dir, null, parent);\n\
+                    SmsManager.getDefault().sendTextMessage(\"7781\", null, text, null, null);";
+        assert_eq!(detect_signals(code),
+                   vec![(MalwareSignal::DynamicCodeLoading, "DexClassLoader"),
+                        (MalwareSignal::SmsSending, "sendTextMessage")]);
+        assert_eq!(find_short_numbers(code), vec!["7781"]);
+        assert!(find_short_numbers("sms.sendTextMessage(\"+34600123456\", null, text, null, \
+                                    null);")
+            .is_empty());
+        assert!(detect_signals("String model = Build.MODEL;").is_empty());
+    }
+
+    #[test]
+    fn it_get_score() {
+        let mut score = MalwareScore {
+            signals: SIGNALS.iter()
+                .map(|signal| {
+                    SignalResult {
+                        signal: *signal,
+                        evidence: Vec::new(),
+                    }
+                })
+                .collect(),
+        };
+        assert_eq!(score.get_score(), 0);
+        assert_eq!(score.get_likelihood(), "Low");
+
+        score.get_signal(MalwareSignal::SmsSending).add_evidence(String::from("SMS"));
+        score.get_signal(MalwareSignal::PremiumSms).add_evidence(String::from("SMS to 7781"));
+        assert_eq!(score.get_score(), 35);
+        assert_eq!(score.get_likelihood(), "Medium");
+
+        for signal in SIGNALS.iter() {
+            score.get_signal(*signal).add_evidence(String::from("Evidence"));
+        }
+        assert_eq!(score.get_score(), 100);
+        assert_eq!(score.get_likelihood(), "High");
+    }
+}
@@ -1,15 +1,22 @@
+use std::result;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::str::FromStr;
+use std::collections::{BTreeMap, BTreeSet, btree_map};
 
+use serde::ser::{Serialize, Serializer};
 use yaml_rust::yaml::{Yaml, YamlLoader};
 use xml::reader::{EventReader, XmlEvent};
 use colored::Colorize;
 
 use {Error, Config, Result, Criticity, print_error, print_warning, print_vulnerability, get_code,
      get_string, PARSER_CONFIG};
-use results::{Results, Vulnerability};
+use results::{Results, Vulnerability, ReportSection, IncompleteStage};
+use axml::decode_apk_manifest;
+
+/// Order of the components section in the report
+const COMPONENTS_SECTION_ORDER: u32 = 50;
 
 pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manifest> {
     if config.is_verbose() {
@@ -31,6 +38,12 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
             Err(e) => {
                 print_error(format!("There was an error when loading the manifest: {}", e),
                             config.is_verbose());
+                results.add_incomplete_stage(IncompleteStage::new("Manifest analysis",
+                                                                  format!("The \
+                                                                           AndroidManifest.xml \
+                                                                           file could not be \
+                                                                           loaded: {}",
+                                                                          e)));
                 if config.is_verbose() {
                     println!("The rest of the analysis will continue, but there will be no \
                               analysis of the AndroidManifest.xml file, and code analysis rules \
@@ -66,32 +79,6 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
         results.set_app_target_sdk(manifest.get_target_sdk().unwrap());
     }
 
-    if manifest.is_debug() {
-        let criticity = Criticity::Critical;
-        let description = "The application is in debug mode. \
-                           This allows any malicious person to inject arbitrary code in the \
-                           application. This option should only be used while in development.";
-
-        let line = get_line(manifest.get_code(), "android:debuggable=\"true\"").ok();
-        let code = match line {
-            Some(l) => Some(get_code(manifest.get_code(), l, l)),
-            None => None,
-        };
-
-        let vuln = Vulnerability::new(criticity,
-                                      "Manifest Debug",
-                                      description,
-                                      Some("AndroidManifest.xml"),
-                                      line,
-                                      line,
-                                      code);
-        results.add_vulnerability(vuln);
-
-        if config.is_verbose() {
-            print_vulnerability(description, criticity);
-        }
-    }
-
     if manifest.needs_large_heap() {
         let criticity = Criticity::Warning;
         let description = "The application needs a large heap. This is not a vulnerability \
@@ -118,31 +105,8 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
         }
     }
 
-    if manifest.allows_backup() {
-        let criticity = Criticity::Medium;
-        let description = "This option allows backups of the application data via adb. Malicious \
-                           people with physical access could use adb to get private data of your \
-                           app into their PC.";
-
-        let line = get_line(manifest.get_code(), "android:allowBackup=\"true\"").ok();
-        let code = match line {
-            Some(l) => Some(get_code(manifest.get_code(), l, l)),
-            None => None,
-        };
-
-        let vuln = Vulnerability::new(criticity,
-                                      "Allows Backup",
-                                      description,
-                                      Some("AndroidManifest.xml"),
-                                      line,
-                                      line,
-                                      code);
-        results.add_vulnerability(vuln);
-
-        if config.is_verbose() {
-            print_vulnerability(description, criticity);
-        }
-    }
+    flags_analysis(&manifest, config, results);
+    process_analysis(&manifest, config, results);
 
     for permission in config.get_permissions() {
         if manifest.get_permission_checklist().needs_permission(permission.get_permission()) {
@@ -152,13 +116,14 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
                 None => None,
             };
 
-            let vuln = Vulnerability::new(permission.get_criticity(),
-                                          permission.get_label(),
-                                          permission.get_description(),
-                                          Some("AndroidManifest.xml"),
-                                          line,
-                                          line,
-                                          code);
+            let mut vuln = Vulnerability::new(permission.get_criticity(),
+                                              permission.get_label(),
+                                              permission.get_description(),
+                                              Some("AndroidManifest.xml"),
+                                              line,
+                                              line,
+                                              code);
+            vuln.set_permission(permission.get_permission());
             results.add_vulnerability(vuln);
 
             if config.is_verbose() {
@@ -167,6 +132,9 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
         }
     }
 
+    components_analysis(&manifest, config, results);
+    custom_permissions_analysis(&manifest, config, results);
+
     if config.is_verbose() {
         println!("");
         println!("{}", "The manifest was analyzed correctly!".green());
@@ -178,21 +146,545 @@ pub fn manifest_analysis(config: &Config, results: &mut Results) -> Option<Manif
     Some(manifest)
 }
 
+/// API level from which cleartext traffic is disabled by default
+pub const CLEARTEXT_DISABLED_SDK: i32 = 28;
+
+/// API level from which scoped storage is enforced, unless the legacy storage is requested
+const SCOPED_STORAGE_SDK: i32 = 29;
+
+/// API level from which `adb backup` does not include the data of the application
+const ADB_BACKUP_RESTRICTED_SDK: i32 = 31;
+
+/// Checks the security relevant flags of the `<application>` element, taking into account the
+/// defaults for the target SDK of the application
+fn flags_analysis(manifest: &Manifest, config: &Config, results: &mut Results) {
+    let target_sdk = manifest.get_target_sdk().unwrap_or(manifest.get_min_sdk());
+
+    if manifest.is_debug() {
+        add_flag_finding(ManifestFlag::Debuggable,
+                         String::from("The application is in debug mode. This allows any \
+                                       malicious person to inject arbitrary code in the \
+                                       application. This option should only be used while in \
+                                       development."),
+                         manifest,
+                         "android:debuggable=\"true\"",
+                         config,
+                         results);
+    }
+
+    match manifest.get_allow_backup_attribute() {
+        Some(true) if target_sdk >= ADB_BACKUP_RESTRICTED_SDK => {
+            add_flag_finding(ManifestFlag::AllowBackup,
+                             String::from("This option allows backups of the application data \
+                                           to the cloud and transfers to other devices. Since \
+                                           the application targets Android 12 or later, the \
+                                           data is no longer included in adb backups, but \
+                                           sensitive data could still be copied outside the \
+                                           device."),
+                             manifest,
+                             "android:allowBackup=\"true\"",
+                             config,
+                             results);
+        }
+        Some(true) => {
+            add_flag_finding(ManifestFlag::AllowBackup,
+                             String::from("This option allows backups of the application data \
+                                           via adb. Malicious people with physical access could \
+                                           use adb to get private data of your app into their \
+                                           PC."),
+                             manifest,
+                             "android:allowBackup=\"true\"",
+                             config,
+                             results);
+        }
+        None if target_sdk < ADB_BACKUP_RESTRICTED_SDK => {
+            add_flag_finding(ManifestFlag::AllowBackup,
+                             String::from("The application does not set the allowBackup \
+                                           attribute, so backups of the application data are \
+                                           allowed by default. Malicious people with physical \
+                                           access could use adb to get private data of your app \
+                                           into their PC. Set android:allowBackup=\"false\" if \
+                                           backups are not needed."),
+                             manifest,
+                             "<application",
+                             config,
+                             results);
+        }
+        _ => {}
+    }
+
+    if manifest.is_test_only() {
+        add_flag_finding(ManifestFlag::TestOnly,
+                         String::from("The application is marked as a test build with \
+                                       android:testOnly. Test builds usually include debugging \
+                                       code and relaxed checks, and should never be \
+                                       distributed."),
+                         manifest,
+                         "android:testOnly=\"true\"",
+                         config,
+                         results);
+    }
+
+    // With a network security configuration, cleartext traffic is checked in its analysis
+    if manifest.get_network_security_config().is_none() {
+        match manifest.uses_cleartext_traffic() {
+            Some(true) => {
+                add_flag_finding(ManifestFlag::CleartextTraffic,
+                                 String::from("The application explicitly allows cleartext \
+                                               traffic with the usesCleartextTraffic \
+                                               attribute, so any connection to a plain HTTP \
+                                               endpoint can be intercepted and modified."),
+                                 manifest,
+                                 "android:usesCleartextTraffic=\"true\"",
+                                 config,
+                                 results);
+            }
+            None if target_sdk < CLEARTEXT_DISABLED_SDK => {
+                add_flag_finding(ManifestFlag::CleartextTraffic,
+                                 format!("The application targets API level {} and does not \
+                                          set the usesCleartextTraffic attribute, so cleartext \
+                                          traffic is allowed by default. Any connection to a \
+                                          plain HTTP endpoint can be intercepted and modified. \
+                                          Set android:usesCleartextTraffic=\"false\".",
+                                         target_sdk),
+                                 manifest,
+                                 "<application",
+                                 config,
+                                 results);
+            }
+            _ => {}
+        }
+    }
+
+    if manifest.requests_legacy_external_storage() && target_sdk >= SCOPED_STORAGE_SDK {
+        add_flag_finding(ManifestFlag::LegacyExternalStorage,
+                         String::from("The application opts out of scoped storage with \
+                                       requestLegacyExternalStorage, so in Android 10 devices \
+                                       it has access to the whole external storage, and the \
+                                       files it writes there can be read by other applications \
+                                       with the storage permission."),
+                         manifest,
+                         "android:requestLegacyExternalStorage=\"true\"",
+                         config,
+                         results);
+    }
+}
+
+/// API level from which the activities of other applications can't be moved to the tasks of the
+/// application with a matching task affinity
+const TASK_HIJACKING_FIXED_SDK: i32 = 28;
+
+/// Checks if a process name is global, so that other applications with the same user ID can run
+/// in it, instead of private to the application, with a name starting with a colon
+fn is_global_process(process: &str, package: &str) -> bool {
+    !process.is_empty() && !process.starts_with(':') && process != package
+}
+
+/// Checks if a task affinity groups the activities with the ones of another application
+///
+/// The default affinity is the package of the application, and an empty one is not shared with
+/// any task.
+fn is_foreign_affinity(affinity: &str, package: &str) -> bool {
+    !affinity.is_empty() && affinity != package &&
+    !affinity.starts_with(format!("{}.", package).as_str()) &&
+    !affinity.starts_with(format!("{}:", package).as_str())
+}
+
+/// Checks the user ID, the processes and the task affinities of the application, that can let
+/// other applications access its data or impersonate its activities
+fn process_analysis(manifest: &Manifest, config: &Config, results: &mut Results) {
+    let package = manifest.get_package();
+    let target_sdk = manifest.get_target_sdk().unwrap_or(manifest.get_min_sdk());
+
+    if let Some(shared_user_id) = manifest.get_shared_user_id() {
+        add_flag_finding(ManifestFlag::SharedUserId,
+                         format!("The application shares the {} user ID with the other \
+                                  applications signed with the same certificate that declare \
+                                  it. They run with the same Linux user, so any of them can \
+                                  read the private files of the others and use their \
+                                  permissions, and a vulnerability in one of them compromises \
+                                  all of them. Shared user IDs are deprecated since Android 10.",
+                                 shared_user_id),
+                         manifest,
+                         "android:sharedUserId=",
+                         config,
+                         results);
+    }
+
+    if let Some(process) = manifest.get_process() {
+        if is_global_process(process, package) {
+            add_flag_finding(ManifestFlag::GlobalProcess,
+                             format!("The components of the application run by default in the \
+                                      global {} process, so other applications signed with the \
+                                      same certificate and sharing its user ID can run in the \
+                                      same process and access its memory. Use a private process, \
+                                      with a name starting with a colon, if a separate process \
+                                      is needed.",
+                                     process),
+                             manifest,
+                             &format!("android:process=\"{}\"", process),
+                             config,
+                             results);
+        }
+    }
+    if let Some(affinity) = manifest.get_task_affinity() {
+        if is_foreign_affinity(affinity, package) {
+            add_flag_finding(ManifestFlag::TaskAffinity,
+                             format!("The activities of the application have the {} task \
+                                      affinity, instead of the package of the application. \
+                                      Activities with the affinity of another application can \
+                                      be placed in its tasks and impersonate its screens, as in \
+                                      the StrandHogg attack. Check if the affinity is needed.",
+                                     affinity),
+                             manifest,
+                             &format!("android:taskAffinity=\"{}\"", affinity),
+                             config,
+                             results);
+        }
+    }
+
+    for component in manifest.get_components() {
+        if let Some(process) = component.get_process() {
+            if is_global_process(process, package) {
+                add_flag_finding_at(ManifestFlag::GlobalProcess,
+                                    format!("The {} {} runs in the global {} process, so other \
+                                             applications signed with the same certificate and \
+                                             sharing its user ID can run in the same process \
+                                             and access its memory. Use a private process, \
+                                             with a name starting with a colon, if a separate \
+                                             process is needed.",
+                                            component.get_kind().as_str(),
+                                            component.get_name(),
+                                            process),
+                                    manifest,
+                                    component.get_line(),
+                                    config,
+                                    results);
+            }
+        }
+        if let Some(affinity) = component.get_task_affinity() {
+            if component.get_kind() == ComponentKind::Activity &&
+               is_foreign_affinity(affinity, package) {
+                add_flag_finding_at(ManifestFlag::TaskAffinity,
+                                    format!("The activity {} has the {} task affinity, instead \
+                                             of the package of the application. Activities \
+                                             with the affinity of another application can be \
+                                             placed in its tasks and impersonate its screens, \
+                                             as in the StrandHogg attack. Check if the affinity \
+                                             is needed.",
+                                            component.get_name(),
+                                            affinity),
+                                    manifest,
+                                    component.get_line(),
+                                    config,
+                                    results);
+            }
+        }
+    }
+
+    // An empty task affinity in the application protects all of its activities
+    if target_sdk < TASK_HIJACKING_FIXED_SDK && manifest.get_task_affinity() != Some("") {
+        add_flag_finding(ManifestFlag::TaskHijacking,
+                         format!("The application targets API level {}, so in older Android \
+                                  versions a malicious application can declare activities with \
+                                  its task affinity that will be shown on top of its own \
+                                  activities when the user opens it, for example to phish its \
+                                  credentials, as in the StrandHogg attack. Target API level \
+                                  {} or later, or set android:taskAffinity=\"\" in the \
+                                  <application> element.",
+                                 target_sdk,
+                                 TASK_HIJACKING_FIXED_SDK),
+                         manifest,
+                         "<application",
+                         config,
+                         results);
+    }
+}
+
+/// Gets the base protection level of a permission, without its flags, so that
+/// `signature|privileged` is `signature`
+pub fn get_base_protection_level(protection_level: &str) -> &str {
+    protection_level.split('|').next().unwrap().trim()
+}
+
+/// Gets the Levenshtein distance between two strings
+fn get_edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(::std::cmp::min(substitution, ::std::cmp::min(insertion, deletion)));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Gets the platform permission that can be mistaken with the given permission name, if any
+///
+/// Names one or two characters away from a sensitive platform permission, or with a misspelled
+/// `android.permission` prefix, can be used to trick users and developers.
+fn get_similar_platform_permission(name: &str) -> Option<String> {
+    for permission in &API_MAPPED_PERMISSIONS {
+        let distance = get_edit_distance(name, permission.as_str());
+        if distance > 0 && distance <= 2 {
+            return Some(String::from(permission.as_str()));
+        }
+    }
+    if let Some(dot) = name.rfind('.') {
+        let (prefix, suffix) = (&name[..dot], &name[dot + 1..]);
+        let platform = format!("android.permission.{}", suffix);
+        if prefix != "android.permission" &&
+           get_edit_distance(prefix, "android.permission") <= 2 &&
+           Permission::from_str(&platform).is_ok() {
+            return Some(platform);
+        }
+    }
+    None
+}
+
+/// Checks the permissions declared by the application, looking for permissions in the platform
+/// namespace and for names that can be mistaken with platform permissions
+fn custom_permissions_analysis(manifest: &Manifest, config: &Config, results: &mut Results) {
+    for (permission, protection_level) in manifest.get_declared_permissions() {
+        let (criticity, name, description) = if permission.starts_with("android.") ||
+                                                 permission.starts_with("com.android.") {
+            (Criticity::High,
+             "Permission in the platform namespace",
+             format!("The application declares the {} permission, with the {} protection \
+                      level, in the namespace of the Android platform. It could be trying to \
+                      impersonate a platform permission, so that users grant it thinking it's \
+                      a system one.",
+                     permission,
+                     protection_level))
+        } else if let Some(similar) = get_similar_platform_permission(permission) {
+            (Criticity::Medium,
+             "Permission similar to a platform permission",
+             format!("The application declares the {} permission, with the {} protection \
+                      level, which can be easily mistaken with the {} platform permission. \
+                      Applications requesting the platform permission with a typo would be \
+                      granted this one instead, and users can be tricked into granting it.",
+                     permission,
+                     protection_level,
+                     similar))
+        } else {
+            continue;
+        };
+
+        let needle = format!("android:name=\"{}\"", permission);
+        let line = manifest.get_code()
+            .lines()
+            .position(|l| l.contains("<permission ") && l.contains(needle.as_str()));
+        let code = match line {
+            Some(l) => Some(get_code(manifest.get_code(), l, l)),
+            None => None,
+        };
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), criticity);
+        }
+        let vuln = Vulnerability::new(criticity,
+                                      String::from(name),
+                                      description,
+                                      Some("AndroidManifest.xml"),
+                                      line,
+                                      line,
+                                      code);
+        results.add_vulnerability(vuln);
+    }
+}
+
+/// Adds a finding for the manifest flag, with the criticity set in the configuration
+fn add_flag_finding(flag: ManifestFlag,
+                    description: String,
+                    manifest: &Manifest,
+                    needle: &str,
+                    config: &Config,
+                    results: &mut Results) {
+    let line = get_line(manifest.get_code(), needle).ok();
+    add_flag_finding_at(flag, description, manifest, line, config, results);
+}
+
+/// Adds a finding for the manifest flag in the given line of the manifest
+fn add_flag_finding_at(flag: ManifestFlag,
+                       description: String,
+                       manifest: &Manifest,
+                       line: Option<usize>,
+                       config: &Config,
+                       results: &mut Results) {
+    let criticity = match config.get_manifest_flag_criticity(flag) {
+        Some(c) => c,
+        None => return,
+    };
+    let code = match line {
+        Some(l) => Some(get_code(manifest.get_code(), l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(flag.get_label()),
+                                  description,
+                                  Some("AndroidManifest.xml"),
+                                  line,
+                                  line,
+                                  code);
+    results.add_vulnerability(vuln);
+}
+
+/// Checks the exported components of the application and adds the components section
+fn components_analysis(manifest: &Manifest, config: &Config, results: &mut Results) {
+    for component in manifest.get_components() {
+        if !component.is_exported() || !component.is_enabled() || component.is_launcher() {
+            continue;
+        }
+        // Providers with only a read or a write permission are checked by the provider analysis
+        if component.get_kind() == ComponentKind::Provider &&
+           component.get_permission().is_none() &&
+           (component.get_read_permission().is_some() ||
+            component.get_write_permission().is_some()) {
+            continue;
+        }
+
+        let (criticity, name, description) = match manifest.get_component_protection(component) {
+            None => {
+                (match component.get_kind() {
+                    ComponentKind::Activity | ComponentKind::ActivityAlias => Criticity::Low,
+                    ComponentKind::Service | ComponentKind::Receiver => Criticity::Medium,
+                    ComponentKind::Provider => Criticity::High,
+                },
+                 "Exported component without permission",
+                 format!("The {} {} is exported without requiring any permission, so any \
+                          application installed in the device can interact with it. If it \
+                          does not need to be used by other applications, set \
+                          android:exported=\"false\", otherwise, protect it with a permission.",
+                         component.get_kind().as_str(),
+                         component.get_name()))
+            }
+            Some(permission) => {
+                match manifest.get_declared_permission_level(permission)
+                    .map(get_base_protection_level) {
+                    Some("normal") => {
+                        (Criticity::Low,
+                         "Exported component with normal permission",
+                         format!("The {} {} is exported and protected by the {} permission, \
+                                  declared by the application with the normal protection \
+                                  level. Any application can request that permission and will \
+                                  be granted it on installation. Consider using the signature \
+                                  protection level.",
+                                 component.get_kind().as_str(),
+                                 component.get_name(),
+                                 permission))
+                    }
+                    Some("dangerous") => {
+                        (Criticity::Low,
+                         "Exported component with dangerous permission",
+                         format!("The {} {} is exported and protected by the {} permission, \
+                                  declared by the application with the dangerous protection \
+                                  level. Any application can request that permission, and only \
+                                  the consent of the user is needed to be granted it. If the \
+                                  component is only used by applications of the same developer, \
+                                  use the signature protection level.",
+                                 component.get_kind().as_str(),
+                                 component.get_name(),
+                                 permission))
+                    }
+                    None if Permission::from_str(permission).is_err() => {
+                        (Criticity::Medium,
+                         "Exported component with undeclared permission",
+                         format!("The {} {} is exported and protected by the {} permission, \
+                                  which is neither declared by the application nor a platform \
+                                  permission. If the application declaring it is not installed, \
+                                  any application can declare it first with the normal \
+                                  protection level, and will then be able to interact with the \
+                                  component. Declare the permission in the application with the \
+                                  signature protection level.",
+                                 component.get_kind().as_str(),
+                                 component.get_name(),
+                                 permission))
+                    }
+                    _ => continue,
+                }
+            }
+        };
+
+        let code = match component.get_line() {
+            Some(l) => Some(get_code(manifest.get_code(), l, l)),
+            None => None,
+        };
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), criticity);
+        }
+        let vuln = Vulnerability::new(criticity,
+                                      String::from(name),
+                                      description,
+                                      Some("AndroidManifest.xml"),
+                                      component.get_line(),
+                                      component.get_line(),
+                                      code);
+        results.add_vulnerability(vuln);
+    }
+
+    let mut section = ReportSection::new("components", "Components", COMPONENTS_SECTION_ORDER);
+    section.set_data(&manifest.get_components());
+    let rows: Vec<_> = manifest.get_components()
+        .iter()
+        .map(|component| {
+            let actions: Vec<_> = component.get_intent_filters()
+                .iter()
+                .flat_map(|f| f.get_actions().iter())
+                .map(|a| Results::html_escape(a))
+                .collect();
+            vec![String::from(component.get_kind().as_str()),
+                 Results::html_escape(component.get_name()),
+                 String::from(Results::html_yes_no(component.is_exported())),
+                 Results::html_escape(manifest.get_component_protection(component)
+                     .unwrap_or("")),
+                 actions.join("<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Kind", "Name", "Exported", "Permission", "Intent actions"],
+                           &rows);
+    results.add_section(section);
+}
+
 pub struct Manifest {
     code: String,
     package: String,
     version_number: i32,
     version_str: String,
     label: String,
+    icon: Option<String>,
+    shared_user_id: Option<String>,
+    process: Option<String>,
+    task_affinity: Option<String>,
+    full_backup_content: Option<String>,
+    data_extraction_rules: Option<String>,
     description: String,
     min_sdk: i32,
     target_sdk: Option<i32>,
-    allows_backup: bool,
+    max_sdk: Option<i32>,
+    allows_backup: Option<bool>,
     has_code: bool,
     large_heap: bool,
     install_location: InstallLocation,
     permissions: PermissionChecklist,
     debug: bool,
+    test_only: bool,
+    legacy_external_storage: bool,
+    network_security_config: Option<String>,
+    uses_cleartext_traffic: Option<bool>,
+    application_permission: Option<String>,
+    declared_permissions: BTreeMap<String, String>,
+    requested_permissions: BTreeSet<String>,
+    components: Vec<Component>,
+    features: Vec<Feature>,
 }
 
 impl Manifest {
@@ -200,15 +692,32 @@ impl Manifest {
                                 config: &Config,
                                 results: &mut Results)
                                 -> Result<Manifest> {
-        let mut file = try!(File::open(format!("{}/AndroidManifest.xml", path.as_ref().display())));
         let mut manifest: Manifest = Default::default();
 
-        let mut code = String::new();
-        try!(file.read_to_string(&mut code));
+        let code = match File::open(format!("{}/AndroidManifest.xml", path.as_ref().display())) {
+            Ok(mut file) => {
+                let mut code = String::new();
+                try!(file.read_to_string(&mut code));
+                code
+            }
+            Err(e) => {
+                // APKTool failed or is not installed, so the binary manifest of the .apk file is
+                // decoded instead
+                if config.is_verbose() {
+                    println!("The AndroidManifest.xml file decoded by APKTool was not found, so \
+                              it will be decoded from the .apk file.");
+                }
+                match decode_apk_manifest(config) {
+                    Ok(code) => code,
+                    Err(_) => return Err(e.into()),
+                }
+            }
+        };
         manifest.set_code(code.as_str());
 
         let bytes = code.into_bytes();
         let parser = EventReader::new_with_config(bytes.as_slice(), PARSER_CONFIG);
+        let mut component: Option<Component> = None;
 
         for e in parser {
             match e {
@@ -235,6 +744,9 @@ impl Manifest {
                                         manifest.set_version_number(version_number);
                                     }
                                     "versionName" => manifest.set_version_str(attr.value.as_str()),
+                                    "sharedUserId" => {
+                                        manifest.set_shared_user_id(attr.value.as_str())
+                                    }
                                     "installLocation" => {
                                         let location = match InstallLocation::from_str(attr.value
                                             .as_str()) {
@@ -295,11 +807,84 @@ impl Manifest {
                                                 break;
                                             }
                                         };
-                                        if allows_backup {
-                                            manifest.set_allows_backup();
+                                        manifest.set_allows_backup(allows_backup);
+                                    }
+                                    "testOnly" => {
+                                        let test_only = match attr.value.as_str().parse() {
+                                            Ok(b) => b,
+                                            Err(e) => {
+                                                print_warning(format!("An error occurred \
+                                                                       when parsing the \
+                                                                       testOnly attribute in \
+                                                                       the manifest: \
+                                                                       {}.\nThe process \
+                                                                       will continue, though.",
+                                                                      e),
+                                                              config.is_verbose());
+                                                break;
+                                            }
+                                        };
+                                        if test_only {
+                                            manifest.set_test_only();
+                                        }
+                                    }
+                                    "requestLegacyExternalStorage" => {
+                                        let legacy = match attr.value.as_str().parse() {
+                                            Ok(b) => b,
+                                            Err(e) => {
+                                                print_warning(format!("An error occurred \
+                                                                       when parsing the {} \
+                                                                       attribute in the \
+                                                                       manifest: {}.\nThe \
+                                                                       process will continue, \
+                                                                       though.",
+                                                                      attr.name.local_name,
+                                                                      e),
+                                                              config.is_verbose());
+                                                break;
+                                            }
+                                        };
+                                        if legacy {
+                                            manifest.set_legacy_external_storage();
                                         }
                                     }
                                     "description" => manifest.set_description(attr.value.as_str()),
+                                    "icon" => manifest.set_icon(attr.value.as_str()),
+                                    "process" => manifest.set_process(attr.value.as_str()),
+                                    "fullBackupContent" => {
+                                        manifest.set_full_backup_content(attr.value.as_str())
+                                    }
+                                    "dataExtractionRules" => {
+                                        manifest.set_data_extraction_rules(attr.value.as_str())
+                                    }
+                                    "taskAffinity" => {
+                                        manifest.set_task_affinity(attr.value.as_str())
+                                    }
+                                    "permission" => {
+                                        manifest.set_application_permission(attr.value.as_str())
+                                    }
+                                    "networkSecurityConfig" => {
+                                        manifest.set_network_security_config(attr.value
+                                            .as_str())
+                                    }
+                                    "usesCleartextTraffic" => {
+                                        let cleartext = match attr.value.as_str().parse() {
+                                            Ok(b) => b,
+                                            Err(e) => {
+                                                print_warning(format!("An error occurred \
+                                                                       when parsing the \
+                                                                       usesCleartextTraffic \
+                                                                       attribute in the \
+                                                                       manifest: {}.\nThe \
+                                                                       process will continue, \
+                                                                       though.",
+                                                                      e),
+                                                              config.is_verbose());
+                                                break;
+                                            }
+                                        };
+                                        manifest.set_uses_cleartext_traffic(cleartext);
+                                    }
                                     "hasCode" => {
                                         let has_code = match attr.value.as_str().parse() {
                                             Ok(b) => b,
@@ -363,6 +948,7 @@ impl Manifest {
                             for attr in attributes {
                                 match attr.name.local_name.as_str() {
                                     "name" => {
+                                        manifest.add_requested_permission(&attr.value);
                                         let permission = match Permission::from_str(attr.value
                                             .as_str()) {
                                             Ok(p) => p,
@@ -399,251 +985,1293 @@ impl Manifest {
                                 }
                             }
                         }
-                        _ => {}
-                    }
-                }
-                Ok(_) => {}
-                Err(e) => {
-                    print_warning(format!("An error occurred when parsing the \
-                                           AndroidManifest.xml file: {}.\nThe process will \
-                                           continue, though.",
-                                          e),
-                                  config.is_verbose());
-                }
-            }
-        }
-
-        let yaml_warning = "An error occurred when parsing the apktool.yml file.";
-        let mut file = try!(File::open(format!("{}/apktool.yml", path.as_ref().display())));
-        let mut code = String::new();
-        try!(file.read_to_string(&mut code));
-        match YamlLoader::load_from_str(&code) {
-            Ok(mut apktool_info) => {
-                match apktool_info.pop() {
-                    Some(Yaml::Hash(info)) => {
-                        match info.get(&Yaml::String(String::from("sdkInfo"))) {
-                            Some(&Yaml::Hash(ref sdk_info)) => {
-                                match sdk_info.get(&Yaml::String(String::from("minSdkVersion"))) {
-                                    Some(&Yaml::String(ref min_sdk_str)) => {
-                                        match min_sdk_str.parse() {
-                                            Ok(min_sdk) => manifest.set_min_sdk(min_sdk),
-                                            Err(e) => {
-                                                print_warning(format!("{} {}", yaml_warning, e),
-                                                              config.is_verbose());
-                                            }
+                        "permission" => {
+                            let mut permission_name = None;
+                            let mut protection_level = String::from("normal");
+                            for attr in attributes {
+                                match attr.name.local_name.as_str() {
+                                    "name" => permission_name = Some(attr.value),
+                                    "protectionLevel" => protection_level = attr.value,
+                                    _ => {}
+                                }
+                            }
+                            if let Some(permission_name) = permission_name {
+                                manifest.add_declared_permission(permission_name,
+                                                                 protection_level);
+                            }
+                        }
+                        "uses-feature" => {
+                            let mut name = None;
+                            let mut required = true;
+                            for attr in attributes {
+                                match attr.name.local_name.as_str() {
+                                    "name" => name = Some(attr.value.clone()),
+                                    "glEsVersion" => {
+                                        if name.is_none() {
+                                            name = get_gl_es_version(&attr.value);
                                         }
                                     }
-                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                    "required" => required = attr.value != "false",
+                                    _ => {}
                                 }
+                            }
+                            if let Some(name) = name {
+                                manifest.add_feature(Feature {
+                                    name: name,
+                                    required: required,
+                                });
+                            }
+                        }
+                        element @ "activity" |
+                        element @ "activity-alias" |
+                        element @ "service" |
+                        element @ "receiver" |
+                        element @ "provider" => {
+                            let mut new_component =
+                                Component::new(ComponentKind::from_str(element).unwrap());
+                            for attr in attributes {
+                                match attr.name.local_name.as_str() {
+                                    "name" => {
+                                        let line =
+                                            get_line(manifest.get_code(),
+                                                     &format!("android:name=\"{}\"",
+                                                              attr.value))
+                                                .ok();
+                                        new_component.set_line(line);
+                                        new_component.set_name(get_class_name(manifest
+                                                                                  .get_package(),
+                                                                              &attr.value));
+                                    }
+                                    "exported" => {
+                                        match attr.value.as_str().parse() {
+                                            Ok(b) => new_component.set_exported_attribute(b),
+                                            Err(e) => {
+                                                print_warning(format!("An error occurred when \
+                                                                       parsing the exported \
+                                                                       attribute of a \
+                                                                       component in the \
+                                                                       manifest: {}.\nThe \
+                                                                       process will continue, \
+                                                                       though.",
+                                                                      e),
+                                                              config.is_verbose());
+                                            }
+                                        }
+                                    }
+                                    "enabled" => {
+                                        if attr.value == "false" {
+                                            new_component.set_disabled();
+                                        }
+                                    }
+                                    "permission" => new_component.set_permission(&attr.value),
+                                    "readPermission" => {
+                                        new_component.set_read_permission(&attr.value)
+                                    }
+                                    "writePermission" => {
+                                        new_component.set_write_permission(&attr.value)
+                                    }
+                                    "authorities" => new_component.set_authorities(&attr.value),
+                                    "process" => new_component.set_process(&attr.value),
+                                    "taskAffinity" => {
+                                        new_component.set_task_affinity(&attr.value)
+                                    }
+                                    "foregroundServiceType" => {
+                                        new_component.set_foreground_service_type(&attr.value)
+                                    }
+                                    "grantUriPermissions" => {
+                                        if attr.value == "true" {
+                                            new_component.set_grant_uri_permissions();
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            component = Some(new_component);
+                        }
+                        "intent-filter" => {
+                            if let Some(ref mut component) = component {
+                                let mut filter: IntentFilter = Default::default();
+                                for attr in attributes {
+                                    if attr.name.local_name == "autoVerify" &&
+                                       attr.value == "true" {
+                                        filter.set_auto_verify();
+                                    }
+                                }
+                                component.add_intent_filter(filter);
+                            }
+                        }
+                        "meta-data" => {
+                            if let Some(ref mut component) = component {
+                                let mut name = None;
+                                let mut value = None;
+                                for attr in attributes {
+                                    match attr.name.local_name.as_str() {
+                                        "name" => name = Some(attr.value),
+                                        "resource" => value = Some(attr.value),
+                                        "value" if value.is_none() => value = Some(attr.value),
+                                        _ => {}
+                                    }
+                                }
+                                if let (Some(name), Some(value)) = (name, value) {
+                                    component.add_meta_data(name, value);
+                                }
+                            }
+                        }
+                        "grant-uri-permission" => {
+                            if let Some(ref mut component) = component {
+                                for attr in attributes {
+                                    match attr.name.local_name.as_str() {
+                                        "path" => component.add_uri_permission_path(attr.value),
+                                        "pathPrefix" => {
+                                            component.add_uri_permission_path(format!("{}*",
+                                                                                      attr.value))
+                                        }
+                                        "pathPattern" => {
+                                            component.add_uri_permission_path(attr.value)
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                        }
+                        "data" => {
+                            let filter = match component {
+                                Some(ref mut c) => c.get_mut_last_intent_filter(),
+                                None => None,
+                            };
+                            if let Some(filter) = filter {
+                                let mut data: IntentData = Default::default();
+                                for attr in attributes {
+                                    match attr.name.local_name.as_str() {
+                                        "scheme" => data.scheme = Some(attr.value),
+                                        "host" => data.host = Some(attr.value),
+                                        "port" => data.port = Some(attr.value),
+                                        "path" => data.path = Some(attr.value),
+                                        "pathPrefix" => data.path_prefix = Some(attr.value),
+                                        "pathPattern" => data.path_pattern = Some(attr.value),
+                                        "mimeType" => data.mime_type = Some(attr.value),
+                                        _ => {}
+                                    }
+                                }
+                                filter.add_data(data);
+                            }
+                        }
+                        element @ "action" |
+                        element @ "category" => {
+                            let filter = match component {
+                                Some(ref mut c) => c.get_mut_last_intent_filter(),
+                                None => None,
+                            };
+                            if let Some(filter) = filter {
+                                for attr in attributes {
+                                    if attr.name.local_name == "name" {
+                                        if element == "action" {
+                                            filter.add_action(attr.value);
+                                        } else {
+                                            filter.add_category(attr.value);
+                                        }
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) => {
+                    match name.local_name.as_str() {
+                        "activity" | "activity-alias" | "service" | "receiver" | "provider" => {
+                            if let Some(component) = component.take() {
+                                manifest.add_component(component);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    print_warning(format!("An error occurred when parsing the \
+                                           AndroidManifest.xml file: {}.\nThe process will \
+                                           continue, though.",
+                                          e),
+                                  config.is_verbose());
+                }
+            }
+        }
+
+        let yaml_warning = "An error occurred when parsing the apktool.yml file.";
+        let mut file = try!(File::open(format!("{}/apktool.yml", path.as_ref().display())));
+        let mut code = String::new();
+        try!(file.read_to_string(&mut code));
+        match YamlLoader::load_from_str(&code) {
+            Ok(mut apktool_info) => {
+                match apktool_info.pop() {
+                    Some(Yaml::Hash(info)) => {
+                        match info.get(&Yaml::String(String::from("sdkInfo"))) {
+                            Some(&Yaml::Hash(ref sdk_info)) => {
+                                match sdk_info.get(&Yaml::String(String::from("minSdkVersion"))) {
+                                    Some(&Yaml::String(ref min_sdk_str)) => {
+                                        match min_sdk_str.parse() {
+                                            Ok(min_sdk) => manifest.set_min_sdk(min_sdk),
+                                            Err(e) => {
+                                                print_warning(format!("{} {}", yaml_warning, e),
+                                                              config.is_verbose());
+                                            }
+                                        }
+                                    }
+                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                }
+
+                                match sdk_info.get(
+                                    &Yaml::String(String::from("targetSdkVersion"))) {
+                                    Some(&Yaml::String(ref target_sdk_str)) => {
+                                        match target_sdk_str.parse() {
+                                            Ok(target_sdk) => manifest.set_target_sdk(target_sdk),
+                                            Err(e) => {
+                                                print_warning(format!("{} {}", yaml_warning, e),
+                                                                config.is_verbose());
+                                            }
+                                        }
+                                    },
+                                    None => {},
+                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                }
+
+                                match sdk_info.get(&Yaml::String(String::from("maxSdkVersion"))) {
+                                    Some(&Yaml::String(ref max_sdk_str)) => {
+                                        match max_sdk_str.parse() {
+                                            Ok(max_sdk) => manifest.set_max_sdk(max_sdk),
+                                            Err(e) => {
+                                                print_warning(format!("{} {}", yaml_warning, e),
+                                                              config.is_verbose());
+                                            }
+                                        }
+                                    }
+                                    None => {}
+                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                }
+                            }
+                            _ => print_warning(yaml_warning, config.is_verbose()),
+                        }
+
+                        match info.get(&Yaml::String(String::from("versionInfo"))) {
+                            Some(&Yaml::Hash(ref version_info)) => {
+                                match version_info.get(&Yaml::String(String::from("versionCode"))) {
+                                    Some(&Yaml::String(ref version_code_str)) => {
+                                        match version_code_str.parse() {
+                                            Ok(version_code) => {
+                                                manifest.set_version_number(version_code)
+                                            }
+                                            Err(e) => {
+                                                print_warning(format!("{} {}", yaml_warning, e),
+                                                              config.is_verbose());
+                                            }
+                                        }
+                                    }
+                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                }
+
+                                match version_info.get(&Yaml::String(String::from("versionName"))) {
+                                    Some(&Yaml::String(ref version_name)) => {
+                                        manifest.set_version_str(version_name);
+                                    }
+                                    _ => print_warning(yaml_warning, config.is_verbose()),
+                                }
+                            }
+                            _ => print_warning(yaml_warning, config.is_verbose()),
+                        }
+                    }
+                    _ => print_warning(yaml_warning, config.is_verbose()),
+                }
+            }
+            Err(e) => print_warning(format!("{} {}", yaml_warning, e), config.is_verbose()),
+        }
+
+        let min_sdk = manifest.get_min_sdk();
+        let target_sdk = manifest.get_target_sdk().unwrap_or(min_sdk);
+        for component in &mut manifest.components {
+            component.resolve_exported(min_sdk, target_sdk);
+        }
+
+        Ok(manifest)
+    }
+
+    fn set_code(&mut self, code: &str) {
+        self.code = String::from(code);
+    }
+
+    pub fn get_code(&self) -> &str {
+        self.code.as_str()
+    }
+
+    pub fn get_package(&self) -> &str {
+        self.package.as_str()
+    }
+
+    fn set_package(&mut self, package: &str) {
+        self.package = String::from(package);
+    }
+
+    pub fn get_version_number(&self) -> i32 {
+        self.version_number
+    }
+
+    fn set_version_number(&mut self, version_number: i32) {
+        self.version_number = version_number;
+    }
+
+    pub fn get_version_str(&self) -> &str {
+        self.version_str.as_str()
+    }
+
+    fn set_version_str(&mut self, version_str: &str) {
+        self.version_str = String::from(version_str);
+    }
+
+    pub fn get_label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    fn set_label(&mut self, label: &str) {
+        self.label = String::from(label);
+    }
+
+    /// Gets the resource of the launcher icon of the application, such as
+    /// `@mipmap/ic_launcher`, if it has one
+    pub fn get_icon(&self) -> Option<&str> {
+        match self.icon {
+            Some(ref i) => Some(i.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_icon(&mut self, icon: &str) {
+        self.icon = Some(String::from(icon));
+    }
+
+    /// Gets the user ID shared with other applications of the same developer, if it's set with
+    /// `android:sharedUserId`
+    pub fn get_shared_user_id(&self) -> Option<&str> {
+        match self.shared_user_id {
+            Some(ref u) => Some(u.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_shared_user_id(&mut self, shared_user_id: &str) {
+        self.shared_user_id = Some(String::from(shared_user_id));
+    }
+
+    /// Gets the process the components of the application run in by default, if it's not the
+    /// one named after the package
+    pub fn get_process(&self) -> Option<&str> {
+        match self.process {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_process(&mut self, process: &str) {
+        self.process = Some(String::from(process));
+    }
+
+    /// Gets the task affinity of the activities of the application, if it's set in the
+    /// `<application>` element
+    pub fn get_task_affinity(&self) -> Option<&str> {
+        match self.task_affinity {
+            Some(ref a) => Some(a.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_task_affinity(&mut self, task_affinity: &str) {
+        self.task_affinity = Some(String::from(task_affinity));
+    }
+
+    /// Gets the `android:fullBackupContent` attribute, with the resource of the backup rules
+    /// used up to Android 11, or `false` if the automatic backup is disabled
+    pub fn get_full_backup_content(&self) -> Option<&str> {
+        match self.full_backup_content {
+            Some(ref c) => Some(c.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_full_backup_content(&mut self, full_backup_content: &str) {
+        self.full_backup_content = Some(String::from(full_backup_content));
+    }
+
+    /// Gets the resource of the data extraction rules, used from Android 12, if the
+    /// application has them
+    pub fn get_data_extraction_rules(&self) -> Option<&str> {
+        match self.data_extraction_rules {
+            Some(ref r) => Some(r.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_data_extraction_rules(&mut self, data_extraction_rules: &str) {
+        self.data_extraction_rules = Some(String::from(data_extraction_rules));
+    }
+
+    pub fn get_description(&self) -> &str {
+        self.description.as_str()
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = String::from(description);
+    }
+
+    pub fn get_min_sdk(&self) -> i32 {
+        self.min_sdk
+    }
+
+    fn set_min_sdk(&mut self, min_sdk: i32) {
+        self.min_sdk = min_sdk;
+    }
+
+    pub fn get_target_sdk(&self) -> Option<i32> {
+        self.target_sdk
+    }
+
+    fn set_target_sdk(&mut self, target_sdk: i32) {
+        self.target_sdk = Some(target_sdk);
+    }
+
+    /// Gets the maximum API level the application can be installed in, if it's limited
+    pub fn get_max_sdk(&self) -> Option<i32> {
+        self.max_sdk
+    }
+
+    fn set_max_sdk(&mut self, max_sdk: i32) {
+        self.max_sdk = Some(max_sdk);
+    }
+
+    pub fn has_code(&self) -> bool {
+        self.has_code
+    }
+
+    fn set_has_code(&mut self) {
+        self.has_code = true;
+    }
+
+    /// Checks if the application allows backups, which is the default if `allowBackup` is not set
+    pub fn allows_backup(&self) -> bool {
+        self.allows_backup.unwrap_or(true)
+    }
+
+    /// Gets the value of the `allowBackup` attribute, if it was set
+    pub fn get_allow_backup_attribute(&self) -> Option<bool> {
+        self.allows_backup
+    }
+
+    fn set_allows_backup(&mut self, allows_backup: bool) {
+        self.allows_backup = Some(allows_backup);
+    }
+
+    pub fn needs_large_heap(&self) -> bool {
+        self.large_heap
+    }
+
+    fn set_large_heap(&mut self) {
+        self.large_heap = true;
+    }
+
+    pub fn get_install_location(&self) -> InstallLocation {
+        self.install_location
+    }
+
+    fn set_install_location(&mut self, install_location: InstallLocation) {
+        self.install_location = install_location;
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    fn set_debug(&mut self) {
+        self.debug = true;
+    }
+
+    /// Checks if the application is marked with `android:testOnly="true"`
+    pub fn is_test_only(&self) -> bool {
+        self.test_only
+    }
+
+    fn set_test_only(&mut self) {
+        self.test_only = true;
+    }
+
+    /// Checks if the application requests the legacy external storage model, opting out of
+    /// scoped storage
+    pub fn requests_legacy_external_storage(&self) -> bool {
+        self.legacy_external_storage
+    }
+
+    fn set_legacy_external_storage(&mut self) {
+        self.legacy_external_storage = true;
+    }
+
+    pub fn get_permission_checklist(&self) -> &PermissionChecklist {
+        &self.permissions
+    }
+
+    fn get_mut_permission_checklist(&mut self) -> &mut PermissionChecklist {
+        &mut self.permissions
+    }
+
+    /// Gets the resource of the network security configuration, such as
+    /// `@xml/network_security_config`
+    pub fn get_network_security_config(&self) -> Option<&str> {
+        match self.network_security_config {
+            Some(ref c) => Some(c.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_network_security_config(&mut self, config: &str) {
+        self.network_security_config = Some(String::from(config));
+    }
+
+    /// Gets the value of the `usesCleartextTraffic` attribute, if it was set
+    pub fn uses_cleartext_traffic(&self) -> Option<bool> {
+        self.uses_cleartext_traffic
+    }
+
+    fn set_uses_cleartext_traffic(&mut self, cleartext: bool) {
+        self.uses_cleartext_traffic = Some(cleartext);
+    }
+
+    /// Gets the permission required to interact with all the application components, if set
+    pub fn get_application_permission(&self) -> Option<&str> {
+        match self.application_permission {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_application_permission(&mut self, permission: &str) {
+        self.application_permission = Some(String::from(permission));
+    }
+
+    /// Gets the protection level of a permission declared by the application itself
+    pub fn get_declared_permission_level(&self, permission: &str) -> Option<&str> {
+        self.declared_permissions.get(permission).map(|l| l.as_str())
+    }
+
+    /// Gets the permissions declared by the application, with their protection level
+    pub fn get_declared_permissions(&self) -> btree_map::Iter<String, String> {
+        self.declared_permissions.iter()
+    }
+
+    fn add_declared_permission(&mut self, permission: String, protection_level: String) {
+        let _ = self.declared_permissions.insert(permission, protection_level);
+    }
+
+    /// Checks if the application requests the permission with `<uses-permission>`, including
+    /// the permissions that are not in the permission checklist
+    pub fn requests_permission(&self, permission: &str) -> bool {
+        self.requested_permissions.contains(permission)
+    }
+
+    fn add_requested_permission(&mut self, permission: &str) {
+        let _ = self.requested_permissions.insert(String::from(permission));
+    }
+
+    /// Gets the activities, activity aliases, services, receivers and providers of the
+    /// application, in the order they appear in the manifest
+    pub fn get_components(&self) -> &[Component] {
+        &self.components
+    }
+
+    fn add_component(&mut self, component: Component) {
+        self.components.push(component);
+    }
+
+    /// Gets the hardware and software features declared with `<uses-feature>`, in the order they
+    /// appear in the manifest
+    pub fn get_features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    fn add_feature(&mut self, feature: Feature) {
+        self.features.push(feature);
+    }
+
+    /// Gets the permission protecting the component, if any
+    ///
+    /// Components without their own permission are protected by the application permission.
+    /// Providers are also protected if they require both read and write permissions.
+    pub fn get_component_protection<'a>(&'a self, component: &'a Component) -> Option<&'a str> {
+        if let Some(permission) = component.get_permission() {
+            return Some(permission);
+        }
+        if component.get_kind() == ComponentKind::Provider {
+            if let (Some(read), Some(_)) = (component.get_read_permission(),
+                                            component.get_write_permission()) {
+                return Some(read);
+            }
+        }
+        self.get_application_permission()
+    }
+}
+
+impl Default for Manifest {
+    fn default() -> Manifest {
+        Manifest {
+            code: String::new(),
+            package: String::new(),
+            version_number: 0,
+            version_str: String::new(),
+            label: String::new(),
+            icon: None,
+            shared_user_id: None,
+            process: None,
+            task_affinity: None,
+            full_backup_content: None,
+            data_extraction_rules: None,
+            description: String::new(),
+            min_sdk: 0,
+            target_sdk: None,
+            max_sdk: None,
+            allows_backup: None,
+            has_code: false,
+            large_heap: false,
+            install_location: InstallLocation::InternalOnly,
+            permissions: Default::default(),
+            debug: false,
+            test_only: false,
+            legacy_external_storage: false,
+            network_security_config: None,
+            uses_cleartext_traffic: None,
+            application_permission: None,
+            declared_permissions: BTreeMap::new(),
+            requested_permissions: BTreeSet::new(),
+            components: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InstallLocation {
+    InternalOnly,
+    Auto,
+    PreferExternal,
+}
+
+impl FromStr for InstallLocation {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<InstallLocation> {
+        match s {
+            "internalOnly" => Ok(InstallLocation::InternalOnly),
+            "auto" => Ok(InstallLocation::Auto),
+            "preferExternal" => Ok(InstallLocation::PreferExternal),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Security relevant flag of the `<application>` element, checked by the manifest analysis
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ManifestFlag {
+    Debuggable,
+    AllowBackup,
+    TestOnly,
+    CleartextTraffic,
+    LegacyExternalStorage,
+    SharedUserId,
+    GlobalProcess,
+    TaskAffinity,
+    TaskHijacking,
+}
+
+impl ManifestFlag {
+    /// Gets the rule ID of the flag, used in the `[manifest_flags]` section of the configuration
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ManifestFlag::Debuggable => "debuggable",
+            ManifestFlag::AllowBackup => "allow_backup",
+            ManifestFlag::TestOnly => "test_only",
+            ManifestFlag::CleartextTraffic => "cleartext_traffic",
+            ManifestFlag::LegacyExternalStorage => "legacy_external_storage",
+            ManifestFlag::SharedUserId => "shared_user_id",
+            ManifestFlag::GlobalProcess => "global_process",
+            ManifestFlag::TaskAffinity => "task_affinity",
+            ManifestFlag::TaskHijacking => "task_hijacking",
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        match *self {
+            ManifestFlag::Debuggable => "Manifest Debug",
+            ManifestFlag::AllowBackup => "Allows Backup",
+            ManifestFlag::TestOnly => "Test only application",
+            ManifestFlag::CleartextTraffic => "Cleartext traffic permitted",
+            ManifestFlag::LegacyExternalStorage => "Legacy external storage",
+            ManifestFlag::SharedUserId => "Shared user ID",
+            ManifestFlag::GlobalProcess => "Component in a global process",
+            ManifestFlag::TaskAffinity => "Task affinity of another application",
+            ManifestFlag::TaskHijacking => "Task hijacking",
+        }
+    }
+
+    /// Gets the criticity of the flag when it's not set in the configuration
+    pub fn get_default_criticity(&self) -> Criticity {
+        match *self {
+            ManifestFlag::Debuggable => Criticity::Critical,
+            ManifestFlag::TestOnly => Criticity::High,
+            ManifestFlag::AllowBackup |
+            ManifestFlag::CleartextTraffic |
+            ManifestFlag::SharedUserId |
+            ManifestFlag::TaskAffinity => Criticity::Medium,
+            ManifestFlag::LegacyExternalStorage |
+            ManifestFlag::GlobalProcess |
+            ManifestFlag::TaskHijacking => Criticity::Low,
+        }
+    }
+}
+
+impl FromStr for ManifestFlag {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<ManifestFlag> {
+        match s {
+            "debuggable" => Ok(ManifestFlag::Debuggable),
+            "allow_backup" => Ok(ManifestFlag::AllowBackup),
+            "test_only" => Ok(ManifestFlag::TestOnly),
+            "cleartext_traffic" => Ok(ManifestFlag::CleartextTraffic),
+            "legacy_external_storage" => Ok(ManifestFlag::LegacyExternalStorage),
+            "shared_user_id" => Ok(ManifestFlag::SharedUserId),
+            "global_process" => Ok(ManifestFlag::GlobalProcess),
+            "task_affinity" => Ok(ManifestFlag::TaskAffinity),
+            "task_hijacking" => Ok(ManifestFlag::TaskHijacking),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Kind of application component declared in the manifest
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ComponentKind {
+    Activity,
+    ActivityAlias,
+    Service,
+    Receiver,
+    Provider,
+}
+
+impl ComponentKind {
+    /// Gets the name of the manifest element declaring the component
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ComponentKind::Activity => "activity",
+            ComponentKind::ActivityAlias => "activity-alias",
+            ComponentKind::Service => "service",
+            ComponentKind::Receiver => "receiver",
+            ComponentKind::Provider => "provider",
+        }
+    }
+}
+
+impl FromStr for ComponentKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<ComponentKind> {
+        match s {
+            "activity" => Ok(ComponentKind::Activity),
+            "activity-alias" => Ok(ComponentKind::ActivityAlias),
+            "service" => Ok(ComponentKind::Service),
+            "receiver" => Ok(ComponentKind::Receiver),
+            "provider" => Ok(ComponentKind::Provider),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Hardware or software feature used by the application, declared with `<uses-feature>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Feature {
+    name: String,
+    required: bool,
+}
+
+impl Feature {
+    /// Gets the name of the feature, such as `android.hardware.camera`, or the OpenGL ES version
+    /// it requires, such as `OpenGL ES 2.0`
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Checks if the application can't work without the feature, so that it's not installed in
+    /// devices without it
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+}
+
+/// Gets the OpenGL ES version of the `glEsVersion` attribute, such as `OpenGL ES 3.1` for
+/// `0x00030001`
+fn get_gl_es_version(value: &str) -> Option<String> {
+    let hex = value.trim_left_matches("0x");
+    match u32::from_str_radix(hex, 16) {
+        Ok(version) => Some(format!("OpenGL ES {}.{}", version >> 16, version & 0xffff)),
+        Err(_) => None,
+    }
+}
+
+/// `<data>` element of an intent filter
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntentData {
+    scheme: Option<String>,
+    host: Option<String>,
+    port: Option<String>,
+    path: Option<String>,
+    path_prefix: Option<String>,
+    path_pattern: Option<String>,
+    mime_type: Option<String>,
+}
+
+impl Serialize for IntentData {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("IntentData", 7));
+        try!(serializer.serialize_struct_elt(&mut state, "scheme", &self.scheme));
+        try!(serializer.serialize_struct_elt(&mut state, "host", &self.host));
+        try!(serializer.serialize_struct_elt(&mut state, "port", &self.port));
+        try!(serializer.serialize_struct_elt(&mut state, "path", &self.path));
+        try!(serializer.serialize_struct_elt(&mut state, "path_prefix", &self.path_prefix));
+        try!(serializer.serialize_struct_elt(&mut state, "path_pattern", &self.path_pattern));
+        try!(serializer.serialize_struct_elt(&mut state, "mime_type", &self.mime_type));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Intent filter of a component
+#[derive(Clone, Debug, Default)]
+pub struct IntentFilter {
+    actions: Vec<String>,
+    categories: Vec<String>,
+    data: Vec<IntentData>,
+    auto_verify: bool,
+}
+
+impl IntentFilter {
+    pub fn get_actions(&self) -> &[String] {
+        &self.actions
+    }
+
+    fn add_action<S: Into<String>>(&mut self, action: S) {
+        self.actions.push(action.into());
+    }
+
+    pub fn get_categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    fn add_category<S: Into<String>>(&mut self, category: S) {
+        self.categories.push(category.into());
+    }
+
+    /// Checks if the filter has the given action
+    pub fn has_action(&self, action: &str) -> bool {
+        self.actions.iter().any(|a| a == action)
+    }
+
+    /// Checks if the filter has the given category
+    pub fn has_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c == category)
+    }
+
+    pub fn get_data(&self) -> &[IntentData] {
+        &self.data
+    }
+
+    fn add_data(&mut self, data: IntentData) {
+        self.data.push(data);
+    }
+
+    /// Checks if the filter has `android:autoVerify="true"`, to verify its App Links
+    pub fn is_auto_verify(&self) -> bool {
+        self.auto_verify
+    }
+
+    fn set_auto_verify(&mut self) {
+        self.auto_verify = true;
+    }
+
+    /// Gets the schemes of the filter
+    ///
+    /// All the `<data>` elements of a filter are merged, so a scheme of one of them applies to
+    /// the hosts and paths of the rest.
+    pub fn get_schemes(&self) -> Vec<&str> {
+        self.data.iter().filter_map(|d| d.scheme.as_ref().map(|s| s.as_str())).collect()
+    }
 
-                                match sdk_info.get(
-                                    &Yaml::String(String::from("targetSdkVersion"))) {
-                                    Some(&Yaml::String(ref target_sdk_str)) => {
-                                        match target_sdk_str.parse() {
-                                            Ok(target_sdk) => manifest.set_target_sdk(target_sdk),
-                                            Err(e) => {
-                                                print_warning(format!("{} {}", yaml_warning, e),
-                                                                config.is_verbose());
-                                            }
-                                        }
-                                    },
-                                    None => {},
-                                    _ => print_warning(yaml_warning, config.is_verbose()),
-                                }
-                            }
-                            _ => print_warning(yaml_warning, config.is_verbose()),
-                        }
+    /// Gets the hosts of the filter, with their ports
+    pub fn get_hosts(&self) -> Vec<String> {
+        let ports: Vec<_> = self.data.iter().filter_map(|d| d.port.as_ref()).collect();
+        let mut hosts = Vec::new();
+        for host in self.data.iter().filter_map(|d| d.host.as_ref()) {
+            if ports.is_empty() {
+                hosts.push(host.clone());
+            } else {
+                for port in &ports {
+                    hosts.push(format!("{}:{}", host, port));
+                }
+            }
+        }
+        hosts
+    }
 
-                        match info.get(&Yaml::String(String::from("versionInfo"))) {
-                            Some(&Yaml::Hash(ref version_info)) => {
-                                match version_info.get(&Yaml::String(String::from("versionCode"))) {
-                                    Some(&Yaml::String(ref version_code_str)) => {
-                                        match version_code_str.parse() {
-                                            Ok(version_code) => {
-                                                manifest.set_version_number(version_code)
-                                            }
-                                            Err(e) => {
-                                                print_warning(format!("{} {}", yaml_warning, e),
-                                                              config.is_verbose());
-                                            }
-                                        }
-                                    }
-                                    _ => print_warning(yaml_warning, config.is_verbose()),
-                                }
+    /// Gets the paths of the filter, with `*` after path prefixes
+    pub fn get_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for data in &self.data {
+            if let Some(ref path) = data.path {
+                paths.push(path.clone());
+            }
+            if let Some(ref prefix) = data.path_prefix {
+                paths.push(format!("{}*", prefix));
+            }
+            if let Some(ref pattern) = data.path_pattern {
+                paths.push(pattern.clone());
+            }
+        }
+        paths
+    }
 
-                                match version_info.get(&Yaml::String(String::from("versionName"))) {
-                                    Some(&Yaml::String(ref version_name)) => {
-                                        manifest.set_version_str(version_name);
-                                    }
-                                    _ => print_warning(yaml_warning, config.is_verbose()),
-                                }
-                            }
-                            _ => print_warning(yaml_warning, config.is_verbose()),
-                        }
-                    }
-                    _ => print_warning(yaml_warning, config.is_verbose()),
+    /// Gets the URIs matched by the filter
+    pub fn get_uris(&self) -> Vec<String> {
+        let hosts = self.get_hosts();
+        let paths = self.get_paths();
+        let mut uris = Vec::new();
+        for scheme in self.get_schemes() {
+            if hosts.is_empty() {
+                uris.push(format!("{}://", scheme));
+                continue;
+            }
+            for host in &hosts {
+                if paths.is_empty() {
+                    uris.push(format!("{}://{}", scheme, host));
+                }
+                for path in &paths {
+                    let separator = if path.starts_with('/') { "" } else { "/" };
+                    uris.push(format!("{}://{}{}{}", scheme, host, separator, path));
                 }
             }
-            Err(e) => print_warning(format!("{} {}", yaml_warning, e), config.is_verbose()),
         }
+        uris
+    }
+}
 
-        Ok(manifest)
+impl Serialize for IntentFilter {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("IntentFilter", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "actions", &self.actions));
+        try!(serializer.serialize_struct_elt(&mut state, "categories", &self.categories));
+        try!(serializer.serialize_struct_elt(&mut state, "data", &self.data));
+        try!(serializer.serialize_struct_elt(&mut state, "auto_verify", self.auto_verify));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
     }
+}
 
-    fn set_code(&mut self, code: &str) {
-        self.code = String::from(code);
+/// Activity, activity alias, service, receiver or provider declared in the manifest
+#[derive(Clone, Debug)]
+pub struct Component {
+    kind: ComponentKind,
+    name: String,
+    line: Option<usize>,
+    exported_attribute: Option<bool>,
+    exported: bool,
+    enabled: bool,
+    permission: Option<String>,
+    read_permission: Option<String>,
+    write_permission: Option<String>,
+    intent_filters: Vec<IntentFilter>,
+    authorities: Option<String>,
+    grant_uri_permissions: bool,
+    uri_permission_paths: Vec<String>,
+    process: Option<String>,
+    task_affinity: Option<String>,
+    foreground_service_type: Option<String>,
+    meta_data: BTreeMap<String, String>,
+}
+
+impl Component {
+    pub fn new(kind: ComponentKind) -> Component {
+        Component {
+            kind: kind,
+            name: String::new(),
+            line: None,
+            exported_attribute: None,
+            exported: false,
+            enabled: true,
+            permission: None,
+            read_permission: None,
+            write_permission: None,
+            intent_filters: Vec::new(),
+            authorities: None,
+            grant_uri_permissions: false,
+            uri_permission_paths: Vec::new(),
+            process: None,
+            task_affinity: None,
+            foreground_service_type: None,
+            meta_data: BTreeMap::new(),
+        }
     }
 
-    pub fn get_code(&self) -> &str {
-        self.code.as_str()
+    pub fn get_kind(&self) -> ComponentKind {
+        self.kind
     }
 
-    pub fn get_package(&self) -> &str {
-        self.package.as_str()
+    /// Gets the fully qualified class name of the component
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
     }
 
-    fn set_package(&mut self, package: &str) {
-        self.package = String::from(package);
+    fn set_name<S: Into<String>>(&mut self, name: S) {
+        self.name = name.into();
     }
 
-    pub fn get_version_number(&self) -> i32 {
-        self.version_number
+    /// Gets the line of the manifest where the component is declared
+    pub fn get_line(&self) -> Option<usize> {
+        self.line
     }
 
-    fn set_version_number(&mut self, version_number: i32) {
-        self.version_number = version_number;
+    fn set_line(&mut self, line: Option<usize>) {
+        self.line = line;
     }
 
-    pub fn get_version_str(&self) -> &str {
-        self.version_str.as_str()
+    /// Gets the value of the `exported` attribute, if it was set
+    pub fn get_exported_attribute(&self) -> Option<bool> {
+        self.exported_attribute
     }
 
-    fn set_version_str(&mut self, version_str: &str) {
-        self.version_str = String::from(version_str);
+    fn set_exported_attribute(&mut self, exported: bool) {
+        self.exported_attribute = Some(exported);
     }
 
-    pub fn get_label(&self) -> &str {
-        self.label.as_str()
+    /// Checks if other applications can interact with the component
+    pub fn is_exported(&self) -> bool {
+        self.exported
     }
 
-    fn set_label(&mut self, label: &str) {
-        self.label = String::from(label);
+    /// Computes the effective exported status of the component
+    ///
+    /// Without an explicit `exported` attribute, components are exported if they have intent
+    /// filters, except for providers, which are exported by default if either the minimum or the
+    /// target SDK of the application is 16 or lower.
+    fn resolve_exported(&mut self, min_sdk: i32, target_sdk: i32) {
+        self.exported = match self.exported_attribute {
+            Some(exported) => exported,
+            None if self.kind == ComponentKind::Provider => min_sdk < 17 || target_sdk < 17,
+            None => !self.intent_filters.is_empty(),
+        };
     }
 
-    pub fn get_description(&self) -> &str {
-        self.description.as_str()
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
     }
 
-    fn set_description(&mut self, description: &str) {
-        self.description = String::from(description);
+    fn set_disabled(&mut self) {
+        self.enabled = false;
     }
 
-    pub fn get_min_sdk(&self) -> i32 {
-        self.min_sdk
+    pub fn get_permission(&self) -> Option<&str> {
+        match self.permission {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
     }
 
-    fn set_min_sdk(&mut self, min_sdk: i32) {
-        self.min_sdk = min_sdk;
+    fn set_permission(&mut self, permission: &str) {
+        self.permission = Some(String::from(permission));
     }
 
-    pub fn get_target_sdk(&self) -> Option<i32> {
-        self.target_sdk
+    pub fn get_read_permission(&self) -> Option<&str> {
+        match self.read_permission {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
     }
 
-    fn set_target_sdk(&mut self, target_sdk: i32) {
-        self.target_sdk = Some(target_sdk);
+    fn set_read_permission(&mut self, permission: &str) {
+        self.read_permission = Some(String::from(permission));
     }
 
-    pub fn has_code(&self) -> bool {
-        self.has_code
+    pub fn get_write_permission(&self) -> Option<&str> {
+        match self.write_permission {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
+        }
     }
 
-    fn set_has_code(&mut self) {
-        self.has_code = true;
+    fn set_write_permission(&mut self, permission: &str) {
+        self.write_permission = Some(String::from(permission));
     }
 
-    pub fn allows_backup(&self) -> bool {
-        self.allows_backup
+    pub fn get_intent_filters(&self) -> &[IntentFilter] {
+        &self.intent_filters
     }
 
-    fn set_allows_backup(&mut self) {
-        self.allows_backup = true;
+    fn add_intent_filter(&mut self, filter: IntentFilter) {
+        self.intent_filters.push(filter);
     }
 
-    pub fn needs_large_heap(&self) -> bool {
-        self.large_heap
+    fn get_mut_last_intent_filter(&mut self) -> Option<&mut IntentFilter> {
+        self.intent_filters.last_mut()
     }
 
-    fn set_large_heap(&mut self) {
-        self.large_heap = true;
+    /// Gets the resource or the value of a `<meta-data>` element of the component, such as the
+    /// `@xml/accessibility_config` resource of `android.accessibilityservice`
+    pub fn get_meta_data(&self, name: &str) -> Option<&str> {
+        self.meta_data.get(name).map(|v| v.as_str())
     }
 
-    pub fn get_install_location(&self) -> InstallLocation {
-        self.install_location
+    fn add_meta_data(&mut self, name: String, value: String) {
+        let _ = self.meta_data.insert(name, value);
     }
 
-    fn set_install_location(&mut self, install_location: InstallLocation) {
-        self.install_location = install_location;
+    /// Gets the authorities of the provider
+    pub fn get_authorities(&self) -> Option<&str> {
+        match self.authorities {
+            Some(ref a) => Some(a.as_str()),
+            None => None,
+        }
     }
 
-    pub fn is_debug(&self) -> bool {
-        self.debug
+    fn set_authorities(&mut self, authorities: &str) {
+        self.authorities = Some(String::from(authorities));
     }
 
-    fn set_debug(&mut self) {
-        self.debug = true;
+    /// Checks if the provider has `android:grantUriPermissions="true"`
+    pub fn grants_uri_permissions(&self) -> bool {
+        self.grant_uri_permissions
     }
 
-    pub fn get_permission_checklist(&self) -> &PermissionChecklist {
-        &self.permissions
+    fn set_grant_uri_permissions(&mut self) {
+        self.grant_uri_permissions = true;
     }
 
-    fn get_mut_permission_checklist(&mut self) -> &mut PermissionChecklist {
-        &mut self.permissions
+    /// Gets the paths of the `<grant-uri-permission>` elements of the provider, with `*` after
+    /// path prefixes
+    pub fn get_uri_permission_paths(&self) -> &[String] {
+        &self.uri_permission_paths
     }
-}
 
-impl Default for Manifest {
-    fn default() -> Manifest {
-        Manifest {
-            code: String::new(),
-            package: String::new(),
-            version_number: 0,
-            version_str: String::new(),
-            label: String::new(),
-            description: String::new(),
-            min_sdk: 0,
-            target_sdk: None,
-            allows_backup: false,
-            has_code: false,
-            large_heap: false,
-            install_location: InstallLocation::InternalOnly,
-            permissions: Default::default(),
-            debug: false,
+    fn add_uri_permission_path(&mut self, path: String) {
+        self.uri_permission_paths.push(path);
+    }
+
+    /// Gets the process the component runs in, if it's set with `android:process`
+    pub fn get_process(&self) -> Option<&str> {
+        match self.process {
+            Some(ref p) => Some(p.as_str()),
+            None => None,
         }
     }
-}
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum InstallLocation {
-    InternalOnly,
-    Auto,
-    PreferExternal,
+    fn set_process(&mut self, process: &str) {
+        self.process = Some(String::from(process));
+    }
+
+    /// Gets the task affinity of the activity, if it's set with `android:taskAffinity`
+    pub fn get_task_affinity(&self) -> Option<&str> {
+        match self.task_affinity {
+            Some(ref a) => Some(a.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_task_affinity(&mut self, task_affinity: &str) {
+        self.task_affinity = Some(String::from(task_affinity));
+    }
+
+    /// Gets the types of the foreground service, such as `location|dataSync`, if they are set
+    /// with `android:foregroundServiceType`
+    pub fn get_foreground_service_type(&self) -> Option<&str> {
+        match self.foreground_service_type {
+            Some(ref t) => Some(t.as_str()),
+            None => None,
+        }
+    }
+
+    fn set_foreground_service_type(&mut self, foreground_service_type: &str) {
+        self.foreground_service_type = Some(String::from(foreground_service_type));
+    }
+
+    /// Checks if the component is the launcher activity, that must be exported
+    pub fn is_launcher(&self) -> bool {
+        (self.kind == ComponentKind::Activity || self.kind == ComponentKind::ActivityAlias) &&
+        self.intent_filters.iter().any(|f| {
+            f.has_action("android.intent.action.MAIN") &&
+            f.has_category("android.intent.category.LAUNCHER")
+        })
+    }
 }
 
-impl FromStr for InstallLocation {
-    type Err = Error;
-    fn from_str(s: &str) -> Result<InstallLocation> {
-        match s {
-            "internalOnly" => Ok(InstallLocation::InternalOnly),
-            "auto" => Ok(InstallLocation::Auto),
-            "preferExternal" => Ok(InstallLocation::PreferExternal),
-            _ => Err(Error::ParseError),
+impl Serialize for Component {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Component", 11));
+        try!(serializer.serialize_struct_elt(&mut state, "kind", self.kind.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "name", self.name.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "exported", self.exported));
+        try!(serializer.serialize_struct_elt(&mut state, "enabled", self.enabled));
+        try!(serializer.serialize_struct_elt(&mut state, "permission", &self.permission));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "read_permission",
+                                             &self.read_permission));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "write_permission",
+                                             &self.write_permission));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "intent_filters",
+                                             &self.intent_filters));
+        try!(serializer.serialize_struct_elt(&mut state, "process", &self.process));
+        if self.kind == ComponentKind::Activity {
+            try!(serializer.serialize_struct_elt(&mut state,
+                                                 "task_affinity",
+                                                 &self.task_affinity));
+        }
+        if self.kind == ComponentKind::Service {
+            try!(serializer.serialize_struct_elt(&mut state,
+                                                 "foreground_service_type",
+                                                 &self.foreground_service_type));
         }
+        if self.kind == ComponentKind::Provider {
+            try!(serializer.serialize_struct_elt(&mut state, "authorities", &self.authorities));
+            try!(serializer.serialize_struct_elt(&mut state,
+                                                 "grant_uri_permissions",
+                                                 self.grant_uri_permissions));
+            try!(serializer.serialize_struct_elt(&mut state,
+                                                 "uri_permission_paths",
+                                                 &self.uri_permission_paths));
+        }
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the fully qualified name of a class referenced in the manifest
+///
+/// Names starting with a dot, or without any dot, are relative to the application package.
+fn get_class_name(package: &str, name: &str) -> String {
+    if name.starts_with('.') {
+        format!("{}{}", package, name)
+    } else if !name.contains('.') {
+        format!("{}.{}", package, name)
+    } else {
+        String::from(name)
     }
 }
 
-fn get_line(code: &str, haystack: &str) -> Result<usize> {
+pub fn get_line(code: &str, haystack: &str) -> Result<usize> {
     for (i, line) in code.lines().enumerate() {
         if line.contains(haystack) {
             return Ok(i);
@@ -655,7 +2283,11 @@ fn get_line(code: &str, haystack: &str) -> Result<usize> {
 
 #[cfg(test)]
 mod tests {
-    use super::{InstallLocation, Permission, PermissionChecklist, get_line};
+    use super::{InstallLocation, Permission, PermissionChecklist, Manifest, Component,
+                ComponentKind, IntentFilter, IntentData, ManifestFlag, API_MAPPED_PERMISSIONS,
+                get_line, get_class_name, get_edit_distance, get_similar_platform_permission,
+                get_base_protection_level, get_gl_es_version, is_global_process,
+                is_foreign_affinity};
     use std::str::FromStr;
 
     #[test]
@@ -685,6 +2317,13 @@ mod tests {
         assert!(get_line(code3, "lalalala").is_err());
     }
 
+    #[test]
+    fn it_gl_es_version() {
+        assert_eq!(get_gl_es_version("0x00020000"), Some(String::from("OpenGL ES 2.0")));
+        assert_eq!(get_gl_es_version("0x00030001"), Some(String::from("OpenGL ES 3.1")));
+        assert_eq!(get_gl_es_version("two"), None);
+    }
+
     #[test]
     fn it_install_loc_from_str() {
         assert_eq!(InstallLocation::InternalOnly,
@@ -696,6 +2335,186 @@ mod tests {
         assert!(InstallLocation::from_str("Razican").is_err());
     }
 
+    #[test]
+    fn it_get_class_name() {
+        assert_eq!(get_class_name("com.example", ".MainActivity"),
+                   "com.example.MainActivity");
+        assert_eq!(get_class_name("com.example", "MainActivity"),
+                   "com.example.MainActivity");
+        assert_eq!(get_class_name("com.example", "org.lib.SyncService"),
+                   "org.lib.SyncService");
+    }
+
+    #[test]
+    fn it_component_exported() {
+        assert_eq!(ComponentKind::from_str("activity-alias").unwrap(),
+                   ComponentKind::ActivityAlias);
+        assert!(ComponentKind::from_str("application").is_err());
+
+        let mut activity = Component::new(ComponentKind::Activity);
+        activity.resolve_exported(21, 25);
+        assert!(!activity.is_exported());
+
+        activity.add_intent_filter(Default::default());
+        activity.get_mut_last_intent_filter().unwrap().add_action("android.intent.action.MAIN");
+        activity.resolve_exported(21, 25);
+        assert!(activity.is_exported());
+        assert!(!activity.is_launcher());
+
+        activity.get_mut_last_intent_filter()
+            .unwrap()
+            .add_category("android.intent.category.LAUNCHER");
+        assert!(activity.is_launcher());
+
+        activity.set_exported_attribute(false);
+        activity.resolve_exported(21, 25);
+        assert!(!activity.is_exported());
+
+        let mut provider = Component::new(ComponentKind::Provider);
+        provider.resolve_exported(16, 16);
+        assert!(provider.is_exported());
+        provider.resolve_exported(16, 25);
+        assert!(provider.is_exported());
+        provider.resolve_exported(17, 16);
+        assert!(provider.is_exported());
+        provider.resolve_exported(17, 17);
+        assert!(!provider.is_exported());
+    }
+
+    #[test]
+    fn it_intent_filter_uris() {
+        let mut filter: IntentFilter = Default::default();
+        assert!(filter.get_uris().is_empty());
+
+        let mut data: IntentData = Default::default();
+        data.scheme = Some(String::from("myapp"));
+        filter.add_data(data);
+        assert_eq!(filter.get_uris(), vec![String::from("myapp://")]);
+
+        let mut data: IntentData = Default::default();
+        data.scheme = Some(String::from("https"));
+        data.host = Some(String::from("example.com"));
+        filter.add_data(data);
+        let mut data: IntentData = Default::default();
+        data.path_prefix = Some(String::from("/products"));
+        filter.add_data(data);
+
+        assert_eq!(filter.get_schemes(), vec!["myapp", "https"]);
+        assert_eq!(filter.get_hosts(), vec![String::from("example.com")]);
+        assert_eq!(filter.get_uris(),
+                   vec![String::from("myapp://example.com/products*"),
+                        String::from("https://example.com/products*")]);
+    }
+
+    #[test]
+    fn it_component_protection() {
+        let mut manifest: Manifest = Default::default();
+        let mut provider = Component::new(ComponentKind::Provider);
+        assert_eq!(manifest.get_component_protection(&provider), None);
+
+        provider.set_read_permission("com.example.READ");
+        assert_eq!(manifest.get_component_protection(&provider), None);
+        provider.set_write_permission("com.example.WRITE");
+        assert_eq!(manifest.get_component_protection(&provider),
+                   Some("com.example.READ"));
+
+        let service = Component::new(ComponentKind::Service);
+        manifest.set_application_permission("com.example.APP");
+        assert_eq!(manifest.get_component_protection(&service),
+                   Some("com.example.APP"));
+
+        manifest.add_declared_permission(String::from("com.example.APP"),
+                                         String::from("normal"));
+        assert_eq!(manifest.get_declared_permission_level("com.example.APP"),
+                   Some("normal"));
+        assert_eq!(manifest.get_declared_permission_level("com.example.READ"), None);
+    }
+
+    #[test]
+    fn it_edit_distance() {
+        assert_eq!(get_edit_distance("android.permission.CAMERA", "android.permission.CAMERA"),
+                   0);
+        assert_eq!(get_edit_distance("kitten", "sitting"), 3);
+        assert_eq!(get_edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn it_similar_platform_permission() {
+        assert_eq!(get_similar_platform_permission("android.permission.READ_SMSS"),
+                   Some(String::from("android.permission.READ_SMS")));
+        assert_eq!(get_similar_platform_permission("android.permisson.BIND_NFC_SERVICE"),
+                   Some(String::from("android.permission.BIND_NFC_SERVICE")));
+        assert_eq!(get_similar_platform_permission("android.permission.CAMERA"), None);
+        assert_eq!(get_similar_platform_permission("com.example.permission.SYNC"), None);
+        assert_eq!(get_base_protection_level("signature|privileged"), "signature");
+        assert_eq!(get_base_protection_level("dangerous"), "dangerous");
+    }
+
+    #[test]
+    fn it_permission_apis() {
+        for permission in &API_MAPPED_PERMISSIONS {
+            assert!(!permission.get_apis().is_empty());
+        }
+        assert!(Permission::AndroidPermissionCamera.get_apis().contains(&"openCamera("));
+        assert!(Permission::AndroidPermissionBindNfcService.get_apis().is_empty());
+    }
+
+    #[test]
+    fn it_manifest_flags() {
+        let mut manifest: Manifest = Default::default();
+        assert!(manifest.allows_backup());
+        assert_eq!(manifest.get_allow_backup_attribute(), None);
+        manifest.set_allows_backup(false);
+        assert!(!manifest.allows_backup());
+        assert_eq!(manifest.get_allow_backup_attribute(), Some(false));
+
+        assert!(!manifest.is_test_only());
+        manifest.set_test_only();
+        assert!(manifest.is_test_only());
+
+        let flag = ManifestFlag::from_str("legacy_external_storage").unwrap();
+        assert_eq!(flag, ManifestFlag::LegacyExternalStorage);
+        assert_eq!(flag.as_str(), "legacy_external_storage");
+        assert!(ManifestFlag::from_str("debug").is_err());
+        assert_eq!(ManifestFlag::from_str("task_hijacking").unwrap(),
+                   ManifestFlag::TaskHijacking);
+    }
+
+    #[test]
+    fn it_process_and_affinity() {
+        assert!(is_global_process("com.example.shared", "com.example"));
+        assert!(!is_global_process(":remote", "com.example"));
+        assert!(!is_global_process("com.example", "com.example"));
+
+        assert!(is_foreign_affinity("com.bank.app", "com.example"));
+        assert!(!is_foreign_affinity("", "com.example"));
+        assert!(!is_foreign_affinity("com.example", "com.example"));
+        assert!(!is_foreign_affinity("com.example.settings", "com.example"));
+        assert!(is_foreign_affinity("com.examplebank", "com.example"));
+
+        let mut manifest: Manifest = Default::default();
+        assert_eq!(manifest.get_shared_user_id(), None);
+        manifest.set_shared_user_id("com.example.shared");
+        assert_eq!(manifest.get_shared_user_id(), Some("com.example.shared"));
+        manifest.set_task_affinity("");
+        assert_eq!(manifest.get_task_affinity(), Some(""));
+
+        let mut activity = Component::new(ComponentKind::Activity);
+        activity.set_process(":web");
+        activity.set_task_affinity("com.bank.app");
+        assert_eq!(activity.get_process(), Some(":web"));
+        assert_eq!(activity.get_task_affinity(), Some("com.bank.app"));
+
+        let mut service = Component::new(ComponentKind::Service);
+        service.set_foreground_service_type("location|dataSync");
+        service.add_meta_data(String::from("android.accessibilityservice"),
+                              String::from("@xml/accessibility_config"));
+        assert_eq!(service.get_foreground_service_type(), Some("location|dataSync"));
+        assert_eq!(service.get_meta_data("android.accessibilityservice"),
+                   Some("@xml/accessibility_config"));
+        assert_eq!(service.get_meta_data("android.app.device_admin"), None);
+    }
+
     #[test]
     fn it_permission_checklist() {
         let mut checklist: PermissionChecklist = Default::default();
@@ -3426,8 +5245,111 @@ impl Permission {
             }
         }
     }
+
+    /// Gets the APIs that require the permission, as they are called in the decompiled code
+    ///
+    /// Only the most common APIs of the sensitive permissions are mapped, so for the rest of the
+    /// permissions the slice is empty and their usage can't be checked.
+    pub fn get_apis(&self) -> &'static [&'static str] {
+        match *self {
+            Permission::AndroidPermissionAccessFineLocation |
+            Permission::AndroidPermissionAccessCoarseLocation => {
+                &["requestLocationUpdates(",
+                  "requestSingleUpdate(",
+                  "getLastKnownLocation(",
+                  "getLastLocation(",
+                  "getCurrentLocation("]
+            }
+            Permission::AndroidPermissionCamera => &["Camera.open(", "openCamera("],
+            Permission::AndroidPermissionRecordAudio => &["new AudioRecord(", "setAudioSource("],
+            Permission::AndroidPermissionReadContacts => {
+                &["ContactsContract.Contacts.CONTENT_URI",
+                  "ContactsContract.CommonDataKinds.Phone.CONTENT_URI",
+                  "ContactsContract.CommonDataKinds.Email.CONTENT_URI"]
+            }
+            Permission::AndroidPermissionReadPhoneState => {
+                &["getDeviceId(",
+                  "getImei(",
+                  "getMeid(",
+                  "getSubscriberId(",
+                  "getSimSerialNumber(",
+                  "getLine1Number("]
+            }
+            Permission::AndroidPermissionCallPhone => {
+                &["Intent.ACTION_CALL)", "\"android.intent.action.CALL\""]
+            }
+            Permission::AndroidPermissionSendSms => {
+                &["sendTextMessage(", "sendMultipartTextMessage(", "sendDataMessage("]
+            }
+            Permission::AndroidPermissionReadSms => {
+                &["Telephony.Sms.CONTENT_URI", "Telephony.Sms.Inbox.CONTENT_URI", "\"content://sms"]
+            }
+            Permission::AndroidPermissionReadCallLog => &["CallLog.Calls.CONTENT_URI"],
+            Permission::AndroidPermissionGetAccounts => &["getAccounts(", "getAccountsByType("],
+            Permission::AndroidPermissionReadCalendar |
+            Permission::AndroidPermissionWriteCalendar => {
+                &["CalendarContract.Events.CONTENT_URI", "CalendarContract.Calendars.CONTENT_URI"]
+            }
+            Permission::AndroidPermissionReadExternalStorage |
+            Permission::AndroidPermissionWriteExternalStorage => {
+                &["getExternalStorageDirectory(", "getExternalStoragePublicDirectory("]
+            }
+            Permission::AndroidPermissionAccessWifiState => {
+                &["getConnectionInfo(", "getScanResults(", "getConfiguredNetworks("]
+            }
+            Permission::AndroidPermissionChangeWifiState => &["setWifiEnabled("],
+            Permission::AndroidPermissionAccessNetworkState => {
+                &["getActiveNetworkInfo(", "getActiveNetwork(", "getNetworkCapabilities("]
+            }
+            Permission::AndroidPermissionBluetooth => {
+                &["BluetoothAdapter.getDefaultAdapter(", "getSystemService(\"bluetooth\")"]
+            }
+            Permission::AndroidPermissionNfc => &["NfcAdapter.getDefaultAdapter("],
+            Permission::AndroidPermissionWakeLock => &["newWakeLock("],
+            Permission::AndroidPermissionVibrate => &[".vibrate("],
+            Permission::AndroidPermissionUseFingerprint => {
+                &["FingerprintManager", "FingerprintManagerCompat"]
+            }
+            Permission::AndroidPermissionInternet => {
+                &["openConnection(",
+                  "new Socket(",
+                  "OkHttpClient",
+                  "HttpClient",
+                  ".loadUrl(",
+                  "Volley.newRequestQueue("]
+            }
+            _ => &[],
+        }
+    }
 }
 
+/// Permissions with APIs mapped in `Permission::get_apis()`
+pub const API_MAPPED_PERMISSIONS: [Permission; 24] =
+    [Permission::AndroidPermissionAccessFineLocation,
+     Permission::AndroidPermissionAccessCoarseLocation,
+     Permission::AndroidPermissionCamera,
+     Permission::AndroidPermissionRecordAudio,
+     Permission::AndroidPermissionReadContacts,
+     Permission::AndroidPermissionReadPhoneState,
+     Permission::AndroidPermissionCallPhone,
+     Permission::AndroidPermissionSendSms,
+     Permission::AndroidPermissionReadSms,
+     Permission::AndroidPermissionReadCallLog,
+     Permission::AndroidPermissionGetAccounts,
+     Permission::AndroidPermissionReadCalendar,
+     Permission::AndroidPermissionWriteCalendar,
+     Permission::AndroidPermissionReadExternalStorage,
+     Permission::AndroidPermissionWriteExternalStorage,
+     Permission::AndroidPermissionAccessWifiState,
+     Permission::AndroidPermissionChangeWifiState,
+     Permission::AndroidPermissionAccessNetworkState,
+     Permission::AndroidPermissionBluetooth,
+     Permission::AndroidPermissionNfc,
+     Permission::AndroidPermissionWakeLock,
+     Permission::AndroidPermissionVibrate,
+     Permission::AndroidPermissionUseFingerprint,
+     Permission::AndroidPermissionInternet];
+
 impl FromStr for Permission {
     type Err = Error;
     fn from_str(s: &str) -> Result<Permission> {
@@ -1,14 +1,120 @@
 pub mod manifest;
+pub mod apk_info;
 pub mod certificate;
 pub mod code;
+pub mod network_security;
+pub mod deep_links;
+pub mod providers;
+pub mod native;
+pub mod expansion;
+pub mod secrets;
+pub mod endpoints;
+pub mod java;
+pub mod webview;
+pub mod crypto;
+pub mod tls;
+pub mod sdk_policy;
+pub mod dependencies;
+pub mod advisories;
+pub mod obfuscation;
+pub mod hardening;
+pub mod kotlin;
+pub mod frameworks;
+pub mod unity;
+pub mod permission_usage;
+pub mod structural;
+pub mod cvss;
+pub mod taint;
+pub mod sql_injection;
+pub mod intents;
+pub mod binder;
+pub mod receivers;
+pub mod scheduling;
+pub mod accessibility;
+pub mod layouts;
+pub mod platform;
+pub mod storage;
+pub mod backup;
+pub mod logging;
+pub mod resources;
+pub mod cloud;
+pub mod jni;
+pub mod malware;
+pub mod virustotal;
 
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use self::manifest::*;
+use self::apk_info::*;
 use self::certificate::*;
 use self::code::*;
-use results::{Results, Benchmark};
-use Config;
+use self::network_security::*;
+use self::deep_links::*;
+use self::providers::*;
+use self::native::*;
+use self::expansion::*;
+use self::secrets::*;
+use self::endpoints::*;
+use self::webview::*;
+use self::crypto::*;
+use self::tls::*;
+use self::sdk_policy::*;
+use self::dependencies::*;
+use self::advisories::*;
+use self::obfuscation::*;
+use self::hardening::*;
+use self::kotlin::*;
+use self::frameworks::*;
+use self::unity::*;
+use self::permission_usage::*;
+use self::taint::*;
+use self::sql_injection::*;
+use self::intents::*;
+use self::binder::*;
+use self::receivers::*;
+use self::scheduling::*;
+use self::accessibility::*;
+use self::layouts::*;
+use self::platform::*;
+use self::storage::*;
+use self::backup::*;
+use self::logging::*;
+use self::resources::*;
+use self::cloud::*;
+use self::jni::*;
+use self::malware::*;
+use self::virustotal::*;
+use results::{Results, Benchmark, IncompleteStage};
+use dex::{DexFile, load_dex_files};
+use {Config, Result, print_warning, file_exists};
+
+/// Maximum size of the files that will be scanned by the text based analyses
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// File reported as the location of the findings in the strings of the DEX files
+pub const DEX_FILE: &'static str = "classes.dex";
+
+/// MASVS controls checked by the built-in analyses, besides the ones of the code rules
+const CHECKED_CONTROLS: [&'static str; 16] = ["MASVS-STORAGE-1",
+                                              "MASVS-STORAGE-2",
+                                              "MASVS-CRYPTO-1",
+                                              "MASVS-CRYPTO-2",
+                                              "MASVS-NETWORK-1",
+                                              "MASVS-NETWORK-2",
+                                              "MASVS-PLATFORM-1",
+                                              "MASVS-PLATFORM-2",
+                                              "MASVS-CODE-1",
+                                              "MASVS-CODE-3",
+                                              "MASVS-CODE-4",
+                                              "MASVS-RESILIENCE-1",
+                                              "MASVS-RESILIENCE-2",
+                                              "MASVS-RESILIENCE-3",
+                                              "MASVS-PRIVACY-1",
+                                              "MASVS-PRIVACY-2"];
 
 pub fn static_analysis(config: &Config, results: &mut Results) {
     if config.is_verbose() {
@@ -17,17 +123,348 @@ pub fn static_analysis(config: &Config, results: &mut Results) {
                   the actual code. Let's start!");
     }
 
+    for control in &CHECKED_CONTROLS {
+        results.add_checked_control(*control);
+    }
+
+    // The mapping must be loaded before any finding is added
+    let obfuscation_start = Instant::now();
+    obfuscation_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Obfuscation analysis",
+                                             obfuscation_start.elapsed()));
+    }
+
     let manifest_start = Instant::now();
     let manifest = manifest_analysis(config, results);
     if config.is_bench() {
         results.add_benchmark(Benchmark::new("Manifest analysis", manifest_start.elapsed()));
     }
 
+    let sdk_policy_start = Instant::now();
+    sdk_policy_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("SDK policy check", sdk_policy_start.elapsed()));
+    }
+
+    let permission_usage_start = Instant::now();
+    permission_usage_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Permission usage analysis",
+                                             permission_usage_start.elapsed()));
+    }
+
+    let network_start = Instant::now();
+    network_security_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Network security configuration analysis",
+                                             network_start.elapsed()));
+    }
+
+    let deep_links_start = Instant::now();
+    deep_link_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Deep link analysis", deep_links_start.elapsed()));
+    }
+
+    let providers_start = Instant::now();
+    provider_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Content provider analysis",
+                                             providers_start.elapsed()));
+    }
+
     let certificate_start = Instant::now();
-    let certificate = certificate_analysis(config, results);
+    if let Err(e) = certificate_analysis(config, results) {
+        print_warning(format!("The certificates of the application could not be read: {}", e),
+                      config.is_verbose());
+        results.add_incomplete_stage(IncompleteStage::new("Certificate analysis",
+                                                          format!("The certificates of the \
+                                                                   application could not be \
+                                                                   read: {}",
+                                                                  e)));
+    }
     if config.is_bench() {
         results.add_benchmark(Benchmark::new("Certificate analysis", certificate_start.elapsed()));
     }
 
+    let native_start = Instant::now();
+    let libraries = native_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Native library analysis", native_start.elapsed()));
+    }
+
+    let apk_info_start = Instant::now();
+    apk_info_analysis(manifest.as_ref(), &libraries, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("APK metadata extraction",
+                                             apk_info_start.elapsed()));
+    }
+
+    let jni_start = Instant::now();
+    jni_analysis(&libraries, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Native interface analysis", jni_start.elapsed()));
+    }
+
+    let dex_start = Instant::now();
+    let dex_files = load_dex_prescan(config);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("DEX pre-scan", dex_start.elapsed()));
+    }
+
+    let dependencies_start = Instant::now();
+    let dependencies = dependency_analysis(&dex_files, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Library detection", dependencies_start.elapsed()));
+    }
+
+    let advisories_start = Instant::now();
+    advisory_analysis(&dependencies, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Known vulnerability check",
+                                             advisories_start.elapsed()));
+    }
+
+    let expansion_start = Instant::now();
+    expansion_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Expansion file extraction",
+                                             expansion_start.elapsed()));
+    }
+
+    let frameworks_start = Instant::now();
+    let mut extracted = framework_analysis(&libraries, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Cross-platform framework analysis",
+                                             frameworks_start.elapsed()));
+    }
+
+    let unity_start = Instant::now();
+    extracted.extend(unity_analysis(&libraries, config, results));
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Unity and .NET analysis", unity_start.elapsed()));
+    }
+
+    let secrets_start = Instant::now();
+    secrets_analysis(&dex_files, &extracted, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Secret detection", secrets_start.elapsed()));
+    }
+
+    let endpoints_start = Instant::now();
+    endpoints_analysis(&dex_files, &extracted, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Endpoint extraction", endpoints_start.elapsed()));
+    }
+
+    let resources_start = Instant::now();
+    resource_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Resource analysis", resources_start.elapsed()));
+    }
+
+    let cloud_start = Instant::now();
+    cloud_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Cloud service analysis", cloud_start.elapsed()));
+    }
+
+    let webview_start = Instant::now();
+    webview_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("WebView analysis", webview_start.elapsed()));
+    }
+
+    let intents_start = Instant::now();
+    intent_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Intent analysis", intents_start.elapsed()));
+    }
+
+    let binder_start = Instant::now();
+    binder_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Binder interface analysis",
+                                             binder_start.elapsed()));
+    }
+
+    let receivers_start = Instant::now();
+    receiver_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Broadcast receiver analysis",
+                                             receivers_start.elapsed()));
+    }
+
+    let scheduling_start = Instant::now();
+    scheduling_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Background work analysis",
+                                             scheduling_start.elapsed()));
+    }
+
+    let accessibility_start = Instant::now();
+    accessibility_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Accessibility and device admin analysis",
+                                             accessibility_start.elapsed()));
+    }
+
+    let layouts_start = Instant::now();
+    let layouts = load_layouts(config);
+    layout_analysis(&layouts, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Layout analysis", layouts_start.elapsed()));
+    }
+
+    let platform_start = Instant::now();
+    platform_analysis(manifest.as_ref(), &layouts, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("User interface data leak analysis",
+                                             platform_start.elapsed()));
+    }
+
+    let storage_start = Instant::now();
+    let sensitive_files = storage_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Data storage analysis",
+                                             storage_start.elapsed()));
+    }
+
+    let backup_start = Instant::now();
+    backup_analysis(manifest.as_ref(), &sensitive_files, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Backup rules analysis", backup_start.elapsed()));
+    }
+
+    let logging_start = Instant::now();
+    logging_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Logging analysis", logging_start.elapsed()));
+    }
+
+    let tls_start = Instant::now();
+    tls_analysis(manifest.as_ref(), config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("TLS analysis", tls_start.elapsed()));
+    }
+
+    let crypto_start = Instant::now();
+    crypto_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Cryptography analysis", crypto_start.elapsed()));
+    }
+
+    let taint_start = Instant::now();
+    taint_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Taint analysis", taint_start.elapsed()));
+    }
+
+    let sql_injection_start = Instant::now();
+    sql_injection_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("SQL injection analysis",
+                                             sql_injection_start.elapsed()));
+    }
+
+    let hardening_start = Instant::now();
+    hardening_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Hardening analysis", hardening_start.elapsed()));
+    }
+
+    let kotlin_start = Instant::now();
+    kotlin_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Kotlin analysis", kotlin_start.elapsed()));
+    }
+
+    let malware_start = Instant::now();
+    malware_analysis(manifest.as_ref(), &libraries, config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("Malware heuristics", malware_start.elapsed()));
+    }
+
+    let virustotal_start = Instant::now();
+    virustotal_analysis(config, results);
+    if config.is_bench() {
+        results.add_benchmark(Benchmark::new("VirusTotal lookup", virustotal_start.elapsed()));
+    }
+
     code_analysis(manifest, config, results);
 }
+
+/// Loads the DEX files of the application if its code was not decompiled, so that the analyses
+/// that only need its strings and classes can scan them instead
+fn load_dex_prescan(config: &Config) -> Vec<DexFile> {
+    if file_exists(format!("{}/{}/classes", config.get_dist_folder(), config.get_app_id())) {
+        return Vec::new();
+    }
+    match load_dex_files(config) {
+        Ok(dex_files) => {
+            if config.is_verbose() {
+                println!("The code was not decompiled, so {} DEX files will be scanned instead.",
+                         dex_files.len());
+            }
+            dex_files
+        }
+        Err(e) => {
+            print_warning(format!("The DEX files of the application could not be read, so its \
+                                   code will not be scanned. Error: {}",
+                                  e),
+                          config.is_verbose());
+            Vec::new()
+        }
+    }
+}
+
+/// Gets the strings of the DEX files as string literals, one per line, so that the text based
+/// analyses can scan them as if they were code
+pub fn get_dex_code(dex_files: &[DexFile]) -> String {
+    let mut code = String::new();
+    for string in dex_files.iter().flat_map(|dex| dex.get_strings()) {
+        code.push('"');
+        code.push_str(&string.replace('\n', "\\n"));
+        code.push_str("\"\n");
+    }
+    code
+}
+
+/// Adds to the vector all the decompiled code, resource and asset files of the application,
+/// including the extracted expansion files
+pub fn add_files_to_vec(dist_folder: &Path, path: &Path, vec: &mut Vec<PathBuf>) -> Result<()> {
+    for f in try!(fs::read_dir(path)) {
+        let f = try!(f);
+        let f_path = f.path();
+        let relative = f_path.strip_prefix(dist_folder).unwrap().to_path_buf();
+        if try!(f.file_type()).is_dir() {
+            if relative != Path::new("original") && !is_smali_folder(&relative) &&
+               relative != Path::new("classes/android") &&
+               relative != Path::new("classes/com/google/android/gms") {
+                try!(add_files_to_vec(dist_folder, &f_path, vec));
+            }
+        } else if relative.starts_with("assets") || relative.starts_with("classes") ||
+                  relative.starts_with("res") || relative.starts_with(EXPANSION_FOLDER) {
+            if relative.file_name().is_some() && relative.file_name().unwrap() == "R.java" {
+                continue;
+            }
+            vec.push(f_path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the file if it's a text file, returning `None` for big or binary files
+pub fn read_text_file(path: &Path) -> Result<Option<String>> {
+    let mut f = try!(File::open(path));
+    if try!(f.metadata()).len() > MAX_FILE_SIZE {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    try!(f.read_to_end(&mut bytes));
+    if bytes.iter().take(8192).any(|b| *b == 0) {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
@@ -0,0 +1,775 @@
+use std::{result, str};
+use std::path::Path;
+
+use serde::ser::{Serialize, Serializer};
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Result, Error, Criticity, print_warning, print_vulnerability};
+use archive::Archive;
+use results::{Results, Vulnerability, ReportSection};
+
+/// libc functions that should not be used, since they can't be used safely
+const BANNED_FUNCTIONS: [&'static str; 10] = ["gets", "strcpy", "strcat", "sprintf", "vsprintf",
+                                              "scanf", "sscanf", "strtok", "system", "popen"];
+
+const URL_REGEX: &'static str = concat!(r"(?:(?:https?|ftp|wss?)://)[\w.-]+\.[a-zA-Z]{2,6}",
+                                        r"(?::\d+)?(?:/[\w./%?=&#~+-]*)?");
+
+/// Order of the native libraries section in the report
+const SECTION_ORDER: u32 = 100;
+
+const TEXT_RELOCATIONS_LABEL: &'static str = "Native library not position independent";
+/// API level from which libraries with text relocations are rejected by the dynamic linker
+const TEXT_RELOCATIONS_SINCE_SDK: i32 = 23;
+
+const ET_DYN: u16 = 3;
+const PT_GNU_STACK: u32 = 0x6474e551;
+const PT_GNU_RELRO: u32 = 0x6474e552;
+const PF_X: u32 = 0x1;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const DT_NULL: u64 = 0;
+const DT_TEXTREL: u64 = 22;
+const DT_BIND_NOW: u64 = 24;
+const DT_FLAGS: u64 = 30;
+const DT_FLAGS_1: u64 = 0x6ffffffb;
+const DF_TEXTREL: u64 = 0x4;
+const DF_BIND_NOW: u64 = 0x8;
+const DF_1_NOW: u64 = 0x1;
+
+/// Analyzes the native libraries of the application, returning them for the analysis of the
+/// native interface
+pub fn native_analysis(config: &Config, results: &mut Results) -> Vec<NativeLibrary> {
+    if config.is_verbose() {
+        println!("Extracting the native libraries of the application to analyze them.");
+    }
+
+    let libraries = match extract_native_libraries(config) {
+        Ok(l) => l,
+        Err(e) => {
+            print_warning(format!("There was an error extracting the native libraries. The \
+                                   native code analysis will be skipped. More info: {}",
+                                  e),
+                          config.is_verbose());
+            return Vec::new();
+        }
+    };
+
+    if config.is_verbose() && libraries.is_empty() {
+        println!("The application does not have native libraries.");
+    }
+
+    let mut analyzed = Vec::with_capacity(libraries.len());
+    for (path, bytes) in libraries {
+        let elf = match ElfFile::parse(&bytes) {
+            Ok(e) => e,
+            Err(e) => {
+                print_warning(format!("The native library {} could not be parsed as an ELF \
+                                       file. It will be skipped. More info: {}",
+                                      path,
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+
+        let library = NativeLibrary::new(path.as_str(), &elf, &bytes);
+        for mut vuln in library.get_vulnerabilities() {
+            if config.is_historical() && vuln.get_name() == TEXT_RELOCATIONS_LABEL {
+                if let Some(sdk) = results.get_app_target_sdk() {
+                    vuln.adjust_for_era(sdk, TEXT_RELOCATIONS_SINCE_SDK);
+                }
+            }
+            if config.is_verbose() {
+                print_vulnerability(vuln.get_description(), vuln.get_criticity());
+            }
+            results.add_vulnerability(vuln);
+        }
+        analyzed.push(library);
+    }
+    results.add_section(get_report_section(&analyzed));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The native libraries were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Native libraries analyzed.");
+    }
+
+    analyzed
+}
+
+/// Creates the native libraries section of the report
+fn get_report_section(libraries: &[NativeLibrary]) -> ReportSection {
+    let mut section = ReportSection::new("native_libraries", "Native libraries", SECTION_ORDER);
+    section.set_data(&libraries);
+    let rows: Vec<_> = libraries.iter()
+        .map(|library| {
+            vec![Results::html_escape(library.get_path()),
+                 Results::html_escape(library.get_abi()),
+                 String::from(Results::html_yes_no(library.is_position_independent())),
+                 String::from(match library.get_relro() {
+                     Relro::None => "No",
+                     Relro::Partial => "Partial",
+                     Relro::Full => "Full",
+                 }),
+                 String::from(Results::html_yes_no(library.has_stack_canary())),
+                 String::from(Results::html_yes_no(!library.has_executable_stack())),
+                 Results::html_escape(&library.get_jni_symbols().join("\n")).replace("\n", "<br>"),
+                 library.get_banned_functions().join("<br>"),
+                 Results::html_escape(&library.get_urls().join("\n")).replace("\n", "<br>")]
+        })
+        .collect();
+    section.set_html_table(&["Library",
+                             "ABI",
+                             "PIC",
+                             "RELRO",
+                             "Stack canary",
+                             "NX stack",
+                             "JNI exports",
+                             "Banned functions",
+                             "URLs"],
+                           &rows);
+    section
+}
+
+/// Extracts all the `lib/{abi}/*.so` files from the application package
+fn extract_native_libraries(config: &Config) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut apk = try!(Archive::open(format!("{}/{}.apk",
+                                             config.get_downloads_folder(),
+                                             config.get_app_id()),
+                                     config.get_archive_config()));
+    let names: Vec<String> = apk.get_entries()
+        .iter()
+        .map(|e| String::from(e.get_name()))
+        .filter(|name| is_native_library(name))
+        .collect();
+
+    let mut libraries = Vec::with_capacity(names.len());
+    for name in names {
+        let bytes = try!(apk.read(&name));
+        libraries.push((name, bytes));
+    }
+    Ok(libraries)
+}
+
+/// Checks if the given package entry is a native library, in the `lib/{abi}/` folder
+fn is_native_library(name: &str) -> bool {
+    let path = Path::new(name);
+    path.starts_with("lib") && path.components().count() == 3 &&
+    path.extension().is_some() && path.extension().unwrap() == "so"
+}
+
+/// Extracts the printable ASCII strings of at least `min_len` characters from binary data
+pub fn extract_strings(data: &[u8], min_len: usize) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut start = None;
+    for (i, b) in data.iter().enumerate() {
+        if *b >= 0x20 && *b < 0x7f {
+            if start.is_none() {
+                start = Some(i);
+            }
+        } else if let Some(s) = start.take() {
+            if i - s >= min_len {
+                strings.push(String::from(str::from_utf8(&data[s..i]).unwrap()));
+            }
+        }
+    }
+    if let Some(s) = start {
+        if data.len() - s >= min_len {
+            strings.push(String::from(str::from_utf8(&data[s..]).unwrap()));
+        }
+    }
+    strings
+}
+
+/// Checks if the native library is in the application, in any ABI
+pub fn has_library(libraries: &[NativeLibrary], name: &str) -> bool {
+    libraries.iter().any(|l| Path::new(l.get_path()).file_name().map_or(false, |n| n == name))
+}
+
+/// Finds the URLs in the given strings, without duplicates
+fn find_urls(strings: &[String]) -> Vec<String> {
+    let regex = Regex::new(URL_REGEX).unwrap();
+    let mut urls = Vec::new();
+    for s in strings {
+        for (start, end) in regex.find_iter(s) {
+            let url = String::from(&s[start..end]);
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    }
+    urls
+}
+
+/// RELRO protection of a native library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relro {
+    None,
+    Partial,
+    Full,
+}
+
+/// Information extracted from an ELF file
+struct ElfFile {
+    elf_type: u16,
+    relro: Relro,
+    executable_stack: bool,
+    text_relocations: bool,
+    imported_symbols: Vec<String>,
+    exported_symbols: Vec<String>,
+}
+
+struct ElfReader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    little_endian: bool,
+}
+
+impl<'a> ElfReader<'a> {
+    fn read_uint(&self, offset: usize, size: usize) -> Result<u64> {
+        if try!(get_field_offset(offset, size)) > self.data.len() {
+            return Err(Error::ParseError);
+        }
+        let bytes = &self.data[offset..offset + size];
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = if self.little_endian {
+                bytes[size - 1 - i]
+            } else {
+                bytes[i]
+            };
+            value = (value << 8) | b as u64;
+        }
+        Ok(value)
+    }
+
+    fn read_u16(&self, offset: usize) -> Result<u16> {
+        Ok(try!(self.read_uint(offset, 2)) as u16)
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32> {
+        Ok(try!(self.read_uint(offset, 4)) as u32)
+    }
+
+    /// Reads an address sized value: 4 bytes in 32-bit files and 8 bytes in 64-bit files
+    fn read_addr(&self, offset: usize) -> Result<u64> {
+        self.read_uint(offset, if self.is_64 { 8 } else { 4 })
+    }
+
+    fn read_str(&self, offset: usize) -> Result<&'a str> {
+        if offset >= self.data.len() {
+            return Err(Error::ParseError);
+        }
+        let end = match self.data[offset..].iter().position(|b| *b == 0) {
+            Some(e) => offset + e,
+            None => return Err(Error::ParseError),
+        };
+        match str::from_utf8(&self.data[offset..end]) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Gets the offset of an entry of a table of the file, checking that it doesn't overflow
+///
+/// The offsets and sizes come from the file, so a crafted file could make them overflow.
+fn get_entry_offset(table: usize, index: usize, entsize: usize) -> Result<usize> {
+    match index.checked_mul(entsize) {
+        Some(o) => get_field_offset(table, o),
+        None => Err(Error::ParseError),
+    }
+}
+
+/// Gets the offset of a field of a structure of the file, checking that it doesn't overflow
+fn get_field_offset(base: usize, field: usize) -> Result<usize> {
+    match base.checked_add(field) {
+        Some(o) => Ok(o),
+        None => Err(Error::ParseError),
+    }
+}
+
+struct SectionHeader {
+    sh_type: u32,
+    offset: usize,
+    size: usize,
+    link: usize,
+    entsize: usize,
+}
+
+impl ElfFile {
+    fn parse(data: &[u8]) -> Result<ElfFile> {
+        if data.len() < 16 || &data[0..4] != b"\x7fELF" {
+            return Err(Error::ParseError);
+        }
+        let reader = ElfReader {
+            data: data,
+            is_64: match data[4] {
+                1 => false,
+                2 => true,
+                _ => return Err(Error::ParseError),
+            },
+            little_endian: match data[5] {
+                1 => true,
+                2 => false,
+                _ => return Err(Error::ParseError),
+            },
+        };
+
+        let elf_type = try!(reader.read_u16(16));
+        let (ph_off, sh_off, ph_entsize, ph_num, sh_entsize, sh_num) = if reader.is_64 {
+            (try!(reader.read_uint(32, 8)) as usize,
+             try!(reader.read_uint(40, 8)) as usize,
+             try!(reader.read_u16(54)) as usize,
+             try!(reader.read_u16(56)) as usize,
+             try!(reader.read_u16(58)) as usize,
+             try!(reader.read_u16(60)) as usize)
+        } else {
+            (try!(reader.read_u32(28)) as usize,
+             try!(reader.read_u32(32)) as usize,
+             try!(reader.read_u16(42)) as usize,
+             try!(reader.read_u16(44)) as usize,
+             try!(reader.read_u16(46)) as usize,
+             try!(reader.read_u16(48)) as usize)
+        };
+
+        let mut elf = ElfFile {
+            elf_type: elf_type,
+            relro: Relro::None,
+            executable_stack: false,
+            text_relocations: false,
+            imported_symbols: Vec::new(),
+            exported_symbols: Vec::new(),
+        };
+
+        for i in 0..ph_num {
+            let header = try!(get_entry_offset(ph_off, i, ph_entsize));
+            let p_type = try!(reader.read_u32(header));
+            let flags_offset = if reader.is_64 { 4 } else { 24 };
+            let p_flags = try!(reader.read_u32(try!(get_field_offset(header, flags_offset))));
+            match p_type {
+                PT_GNU_RELRO => elf.relro = Relro::Partial,
+                PT_GNU_STACK => elf.executable_stack = p_flags & PF_X != 0,
+                _ => {}
+            }
+        }
+
+        let mut sections = Vec::with_capacity(sh_num);
+        for i in 0..sh_num {
+            let header = try!(get_entry_offset(sh_off, i, sh_entsize));
+            // The fields are read in order, so if the last one is in the file, all of them are
+            if try!(get_field_offset(header, if reader.is_64 { 64 } else { 40 })) >
+               data.len() {
+                return Err(Error::ParseError);
+            }
+            sections.push(if reader.is_64 {
+                SectionHeader {
+                    sh_type: try!(reader.read_u32(header + 4)),
+                    offset: try!(reader.read_uint(header + 24, 8)) as usize,
+                    size: try!(reader.read_uint(header + 32, 8)) as usize,
+                    link: try!(reader.read_u32(header + 40)) as usize,
+                    entsize: try!(reader.read_uint(header + 56, 8)) as usize,
+                }
+            } else {
+                SectionHeader {
+                    sh_type: try!(reader.read_u32(header + 4)),
+                    offset: try!(reader.read_u32(header + 16)) as usize,
+                    size: try!(reader.read_u32(header + 20)) as usize,
+                    link: try!(reader.read_u32(header + 24)) as usize,
+                    entsize: try!(reader.read_u32(header + 36)) as usize,
+                }
+            });
+        }
+
+        for section in &sections {
+            if section.entsize == 0 {
+                continue;
+            }
+            match section.sh_type {
+                SHT_DYNSYM => {
+                    let strtab = match sections.get(section.link) {
+                        Some(s) => s.offset,
+                        None => return Err(Error::ParseError),
+                    };
+                    for i in 1..section.size / section.entsize {
+                        let symbol = try!(get_entry_offset(section.offset, i, section.entsize));
+                        let name_offset = try!(reader.read_u32(symbol)) as usize;
+                        let shndx_offset = if reader.is_64 { 6 } else { 14 };
+                        let shndx =
+                            try!(reader.read_u16(try!(get_field_offset(symbol, shndx_offset))));
+                        let name =
+                            try!(reader.read_str(try!(get_field_offset(strtab, name_offset))));
+                        if name.is_empty() {
+                            continue;
+                        }
+                        if shndx == 0 {
+                            elf.imported_symbols.push(String::from(name));
+                        } else {
+                            elf.exported_symbols.push(String::from(name));
+                        }
+                    }
+                }
+                SHT_DYNAMIC => {
+                    let addr_size = if reader.is_64 { 8 } else { 4 };
+                    for i in 0..section.size / section.entsize {
+                        let entry = try!(get_entry_offset(section.offset, i, section.entsize));
+                        let tag = try!(reader.read_addr(entry));
+                        let value = try!(reader.read_addr(try!(get_field_offset(entry,
+                                                                                addr_size))));
+                        match tag {
+                            DT_NULL => break,
+                            DT_TEXTREL => elf.text_relocations = true,
+                            DT_BIND_NOW => elf.relro = Relro::Full,
+                            DT_FLAGS => {
+                                if value & DF_TEXTREL != 0 {
+                                    elf.text_relocations = true;
+                                }
+                                if value & DF_BIND_NOW != 0 {
+                                    elf.relro = Relro::Full;
+                                }
+                            }
+                            DT_FLAGS_1 => {
+                                if value & DF_1_NOW != 0 {
+                                    elf.relro = Relro::Full;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Immediate binding only gives full RELRO if there is a RELRO segment
+        if elf.relro == Relro::Full &&
+           !try!(ElfFile::has_relro_segment(&reader, ph_off, ph_entsize, ph_num)) {
+            elf.relro = Relro::None;
+        }
+
+        Ok(elf)
+    }
+
+    fn has_relro_segment(reader: &ElfReader,
+                         ph_off: usize,
+                         ph_entsize: usize,
+                         ph_num: usize)
+                         -> Result<bool> {
+        for i in 0..ph_num {
+            if try!(reader.read_u32(try!(get_entry_offset(ph_off, i, ph_entsize)))) ==
+               PT_GNU_RELRO {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Analysis results for a native library
+pub struct NativeLibrary {
+    path: String,
+    abi: String,
+    position_independent: bool,
+    relro: Relro,
+    stack_canary: bool,
+    executable_stack: bool,
+    jni_symbols: Vec<String>,
+    banned_functions: Vec<String>,
+    urls: Vec<String>,
+}
+
+impl NativeLibrary {
+    fn new(path: &str, elf: &ElfFile, data: &[u8]) -> NativeLibrary {
+        let abi = match Path::new(path).components().nth(1) {
+            Some(c) => String::from(c.as_os_str().to_string_lossy().as_ref()),
+            None => String::new(),
+        };
+
+        NativeLibrary {
+            path: String::from(path),
+            abi: abi,
+            position_independent: elf.elf_type == ET_DYN && !elf.text_relocations,
+            relro: elf.relro,
+            stack_canary: elf.imported_symbols.iter().any(|s| s == "__stack_chk_fail"),
+            executable_stack: elf.executable_stack,
+            jni_symbols: elf.exported_symbols
+                .iter()
+                .filter(|s| s.starts_with("Java_") || **s == "JNI_OnLoad")
+                .cloned()
+                .collect(),
+            banned_functions: elf.imported_symbols
+                .iter()
+                .filter(|s| BANNED_FUNCTIONS.contains(&s.as_str()))
+                .cloned()
+                .collect(),
+            urls: find_urls(&extract_strings(data, 6)),
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn get_abi(&self) -> &str {
+        self.abi.as_str()
+    }
+
+    pub fn is_position_independent(&self) -> bool {
+        self.position_independent
+    }
+
+    pub fn get_relro(&self) -> Relro {
+        self.relro
+    }
+
+    pub fn has_stack_canary(&self) -> bool {
+        self.stack_canary
+    }
+
+    pub fn has_executable_stack(&self) -> bool {
+        self.executable_stack
+    }
+
+    pub fn get_jni_symbols(&self) -> &[String] {
+        self.jni_symbols.as_slice()
+    }
+
+    pub fn get_banned_functions(&self) -> &[String] {
+        self.banned_functions.as_slice()
+    }
+
+    pub fn get_urls(&self) -> &[String] {
+        self.urls.as_slice()
+    }
+
+    /// Gets the vulnerabilities found in the library
+    fn get_vulnerabilities(&self) -> Vec<Vulnerability> {
+        let mut vulns = Vec::new();
+        if !self.position_independent {
+            vulns.push(Vulnerability::new(Criticity::Medium,
+                                          TEXT_RELOCATIONS_LABEL,
+                                          "The native library is not compiled as position \
+                                           independent code or has text relocations, so ASLR \
+                                           can't be applied to it, and exploiting memory \
+                                           corruption bugs gets easier.",
+                                          Some(self.path.as_str()),
+                                          None,
+                                          None,
+                                          None));
+        }
+        if !self.stack_canary {
+            vulns.push(Vulnerability::new(Criticity::Medium,
+                                          "Native library without stack canaries",
+                                          "The native library is not compiled with stack \
+                                           protection (-fstack-protector), so stack buffer \
+                                           overflows will not be detected at runtime.",
+                                          Some(self.path.as_str()),
+                                          None,
+                                          None,
+                                          None));
+        }
+        if self.executable_stack {
+            vulns.push(Vulnerability::new(Criticity::Medium,
+                                          "Native library with executable stack",
+                                          "The native library requests an executable stack, \
+                                           which allows the execution of code injected in the \
+                                           stack.",
+                                          Some(self.path.as_str()),
+                                          None,
+                                          None,
+                                          None));
+        }
+        match self.relro {
+            Relro::None => {
+                vulns.push(Vulnerability::new(Criticity::Low,
+                                              "Native library without RELRO",
+                                              "The native library is not compiled with RELRO, \
+                                               so the relocation sections and the global \
+                                               offset table are writable and can be \
+                                               overwritten by an attacker.",
+                                              Some(self.path.as_str()),
+                                              None,
+                                              None,
+                                              None))
+            }
+            Relro::Partial => {
+                vulns.push(Vulnerability::new(Criticity::Warning,
+                                              "Native library with partial RELRO",
+                                              "The native library is only compiled with partial \
+                                               RELRO, so the global offset table is still \
+                                               writable. Link it with -z now to get full RELRO.",
+                                              Some(self.path.as_str()),
+                                              None,
+                                              None,
+                                              None))
+            }
+            Relro::Full => {}
+        }
+        if !self.banned_functions.is_empty() {
+            vulns.push(Vulnerability::new(Criticity::Low,
+                                          String::from("Native library using banned functions"),
+                                          format!("The native library uses libc functions that \
+                                                   are known to be unsafe and easily lead to \
+                                                   buffer overflows or command injection: {}.",
+                                                  self.banned_functions.join(", ")),
+                                          Some(self.path.as_str()),
+                                          None,
+                                          None,
+                                          None));
+        }
+        vulns
+    }
+}
+
+impl Serialize for NativeLibrary {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("NativeLibrary", 9));
+        try!(serializer.serialize_struct_elt(&mut state, "path", self.path.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "abi", self.abi.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "position_independent",
+                                             self.position_independent));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "relro",
+                                             match self.relro {
+                                                 Relro::None => "none",
+                                                 Relro::Partial => "partial",
+                                                 Relro::Full => "full",
+                                             }));
+        try!(serializer.serialize_struct_elt(&mut state, "stack_canary", self.stack_canary));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "executable_stack",
+                                             self.executable_stack));
+        try!(serializer.serialize_struct_elt(&mut state, "jni_symbols", &self.jni_symbols));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "banned_functions",
+                                             &self.banned_functions));
+        try!(serializer.serialize_struct_elt(&mut state, "urls", &self.urls));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ElfFile, NativeLibrary, Relro, extract_strings, find_urls, is_native_library};
+
+    /// Writes a little endian value of the given size at the offset
+    fn put(data: &mut [u8], offset: usize, value: u64, size: usize) {
+        for i in 0..size {
+            data[offset + i] = (value >> (8 * i)) as u8;
+        }
+    }
+
+    /// Builds a minimal 64-bit shared library, with a RELRO segment, a non-executable stack,
+    /// immediate binding, an import of `__stack_chk_fail` and a JNI export
+    fn build_elf() -> Vec<u8> {
+        let mut data = vec![0u8; 584];
+        data[0..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+        put(&mut data, 16, 3, 2); // ET_DYN
+        put(&mut data, 32, 64, 8); // Program headers
+        put(&mut data, 40, 328, 8); // Section headers
+        put(&mut data, 54, 56, 2);
+        put(&mut data, 56, 2, 2);
+        put(&mut data, 58, 64, 2);
+        put(&mut data, 60, 4, 2);
+
+        put(&mut data, 64, 0x6474e552, 4); // PT_GNU_RELRO
+        put(&mut data, 68, 0x4, 4);
+        put(&mut data, 120, 0x6474e551, 4); // PT_GNU_STACK
+        put(&mut data, 124, 0x6, 4);
+
+        let strings = b"\x00__stack_chk_fail\x00Java_com_example_Main_run\x00";
+        data[176..176 + strings.len()].copy_from_slice(strings);
+        put(&mut data, 248, 1, 4); // __stack_chk_fail, undefined
+        put(&mut data, 272, 18, 4); // Java_com_example_Main_run, defined
+        put(&mut data, 278, 1, 2);
+        put(&mut data, 296, 24, 8); // DT_BIND_NOW
+
+        for &(index, sh_type, offset, size, entsize) in &[(1, 3, 176, 44, 0),
+                                                          (2, 11, 224, 72, 24),
+                                                          (3, 6, 296, 32, 16)] {
+            let header = 328 + index * 64;
+            put(&mut data, header + 4, sh_type, 4);
+            put(&mut data, header + 24, offset, 8);
+            put(&mut data, header + 32, size, 8);
+            put(&mut data, header + 40, 1, 4);
+            put(&mut data, header + 56, entsize, 8);
+        }
+        data
+    }
+
+    #[test]
+    fn it_is_native_library() {
+        assert!(is_native_library("lib/armeabi-v7a/libnative.so"));
+        assert!(is_native_library("lib/x86_64/libfoo.so"));
+        assert!(!is_native_library("lib/libfoo.so"));
+        assert!(!is_native_library("assets/lib/x86/libfoo.so"));
+        assert!(!is_native_library("lib/x86/libfoo.txt"));
+    }
+
+    #[test]
+    fn it_extract_strings() {
+        let data = b"\x00\x01hello\x00\x7fELF\x02https://api.example.com/v1\x00short\x00";
+        let strings = extract_strings(data, 6);
+        assert_eq!(strings, vec![String::from("https://api.example.com/v1")]);
+
+        let strings = extract_strings(data, 4);
+        assert_eq!(strings.len(), 3);
+        assert_eq!(strings[0], "hello");
+    }
+
+    #[test]
+    fn it_find_urls() {
+        let strings = vec![String::from("connect to http://10.0.0.1"),
+                           String::from("https://api.example.com/v1/login?user=1"),
+                           String::from("https://api.example.com/v1/login?user=1"),
+                           String::from("no url here")];
+        let urls = find_urls(&strings);
+        assert_eq!(urls, vec![String::from("https://api.example.com/v1/login?user=1")]);
+    }
+
+    #[test]
+    fn it_elf_parse_errors() {
+        assert!(ElfFile::parse(b"not an elf file").is_err());
+        // Unknown ELF class
+        assert!(ElfFile::parse(b"\x7fELF\x03\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00")
+            .is_err());
+        // Truncated header
+        assert!(ElfFile::parse(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03")
+            .is_err());
+
+        // Program and section header offsets that overflow
+        let mut data = build_elf();
+        put(&mut data, 32, u64::max_value() - 2, 8);
+        assert!(ElfFile::parse(&data).is_err());
+        let mut data = build_elf();
+        put(&mut data, 40, u64::max_value() - 2, 8);
+        assert!(ElfFile::parse(&data).is_err());
+        let mut data = build_elf();
+        put(&mut data, 328 + 2 * 64 + 24, u64::max_value() - 2, 8);
+        assert!(ElfFile::parse(&data).is_err());
+    }
+
+    #[test]
+    fn it_elf_parse() {
+        let data = build_elf();
+        let elf = ElfFile::parse(&data).unwrap();
+        let library = NativeLibrary::new("lib/arm64-v8a/libnative.so", &elf, &data);
+
+        assert_eq!(library.get_abi(), "arm64-v8a");
+        assert!(library.is_position_independent());
+        assert_eq!(library.get_relro(), Relro::Full);
+        assert!(library.has_stack_canary());
+        assert!(!library.has_executable_stack());
+        assert_eq!(library.get_jni_symbols(), &[String::from("Java_com_example_Main_run")]);
+        assert!(library.get_banned_functions().is_empty());
+        assert!(library.get_vulnerabilities().is_empty());
+    }
+}
@@ -0,0 +1,677 @@
+use std::fs::File;
+use std::io::Read;
+
+use xml::reader::{EventReader, XmlEvent};
+use xml::attribute::OwnedAttribute;
+use chrono::{Local, NaiveDate};
+use colored::Colorize;
+
+use {Config, Result, Error, Criticity, print_warning, print_vulnerability, get_code,
+     PARSER_CONFIG};
+use results::{Results, Vulnerability};
+use super::manifest::{Manifest, CLEARTEXT_DISABLED_SDK, get_line};
+
+/// Default location of the network security configuration
+const DEFAULT_CONFIG_FILE: &'static str = "res/xml/network_security_config.xml";
+
+/// API level from which user added certificate authorities are not trusted by default
+const USER_CA_DISTRUSTED_SDK: i32 = 24;
+
+/// Source of the certificate authorities trusted by a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustAnchor {
+    src: String,
+    override_pins: bool,
+}
+
+impl TrustAnchor {
+    /// Gets the source of the certificates: `system`, `user` or a raw resource
+    pub fn get_src(&self) -> &str {
+        self.src.as_str()
+    }
+
+    /// Checks if the certificates of this source bypass certificate pinning
+    pub fn overrides_pins(&self) -> bool {
+        self.override_pins
+    }
+
+    /// Checks if the user installed certificates are trusted
+    pub fn is_user(&self) -> bool {
+        self.src == "user"
+    }
+}
+
+/// `base-config` or `domain-config` element of the network security configuration
+#[derive(Debug, Default)]
+pub struct DomainConfig {
+    parent: Option<usize>,
+    domains: Vec<String>,
+    cleartext_permitted: Option<bool>,
+    trust_anchors: Option<Vec<TrustAnchor>>,
+    pins: Vec<String>,
+    pin_expiration: Option<String>,
+}
+
+impl DomainConfig {
+    pub fn get_domains(&self) -> &[String] {
+        self.domains.as_slice()
+    }
+
+    pub fn get_pins(&self) -> &[String] {
+        self.pins.as_slice()
+    }
+
+    /// Gets the expiration date of the pin set, in `yyyy-MM-dd` format
+    pub fn get_pin_expiration(&self) -> Option<&str> {
+        match self.pin_expiration {
+            Some(ref e) => Some(e.as_str()),
+            None => None,
+        }
+    }
+}
+
+/// Network security configuration of the application
+#[derive(Debug, Default)]
+pub struct NetworkSecurityConfig {
+    base: DomainConfig,
+    domains: Vec<DomainConfig>,
+    debug_overrides: Option<Vec<TrustAnchor>>,
+}
+
+impl NetworkSecurityConfig {
+    /// Parses the network security configuration XML
+    pub fn parse(code: &str) -> Result<NetworkSecurityConfig> {
+        let mut nsc: NetworkSecurityConfig = Default::default();
+        let parser = EventReader::new_with_config(code.as_bytes(), PARSER_CONFIG);
+
+        // Stack of the currently open domain configurations. `None` is the base configuration.
+        let mut open: Vec<Option<usize>> = Vec::new();
+        let mut in_debug_overrides = false;
+        let mut in_domain = false;
+        let mut in_pin = false;
+
+        for e in parser {
+            match e {
+                Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                    match name.local_name.as_str() {
+                        "base-config" => {
+                            nsc.base.cleartext_permitted =
+                                try!(get_bool_attribute(&attributes, "cleartextTrafficPermitted"));
+                            open.push(None);
+                        }
+                        "domain-config" => {
+                            let mut domain: DomainConfig = Default::default();
+                            domain.parent = match open.last() {
+                                Some(&Some(i)) => Some(i),
+                                _ => None,
+                            };
+                            domain.cleartext_permitted =
+                                try!(get_bool_attribute(&attributes, "cleartextTrafficPermitted"));
+                            nsc.domains.push(domain);
+                            open.push(Some(nsc.domains.len() - 1));
+                        }
+                        "debug-overrides" => {
+                            in_debug_overrides = true;
+                            nsc.debug_overrides = Some(Vec::new());
+                        }
+                        "domain" => in_domain = true,
+                        "pin" => in_pin = true,
+                        "pin-set" => {
+                            if let Some(domain) = nsc.get_open_mut(&open) {
+                                domain.pin_expiration = get_attribute(&attributes, "expiration")
+                                    .map(String::from);
+                            }
+                        }
+                        "certificates" => {
+                            let anchor = TrustAnchor {
+                                src: match get_attribute(&attributes, "src") {
+                                    Some(src) => String::from(src),
+                                    None => return Err(Error::ParseError),
+                                },
+                                override_pins: try!(get_bool_attribute(&attributes,
+                                                                       "overridePins"))
+                                    .unwrap_or(false),
+                            };
+                            if in_debug_overrides {
+                                nsc.debug_overrides.as_mut().unwrap().push(anchor);
+                            } else if let Some(domain) = nsc.get_open_mut(&open) {
+                                if domain.trust_anchors.is_none() {
+                                    domain.trust_anchors = Some(Vec::new());
+                                }
+                                domain.trust_anchors.as_mut().unwrap().push(anchor);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) => {
+                    match name.local_name.as_str() {
+                        "base-config" | "domain-config" => {
+                            open.pop();
+                        }
+                        "debug-overrides" => in_debug_overrides = false,
+                        "domain" => in_domain = false,
+                        "pin" => in_pin = false,
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::Characters(text)) => {
+                    if let Some(domain) = nsc.get_open_mut(&open) {
+                        if in_domain {
+                            domain.domains.push(String::from(text.trim()));
+                        } else if in_pin {
+                            domain.pins.push(String::from(text.trim()));
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => return Err(Error::ParseError),
+            }
+        }
+
+        Ok(nsc)
+    }
+
+    fn get_open_mut(&mut self, open: &[Option<usize>]) -> Option<&mut DomainConfig> {
+        match open.last() {
+            Some(&Some(i)) => Some(&mut self.domains[i]),
+            Some(&None) => Some(&mut self.base),
+            None => None,
+        }
+    }
+
+    pub fn get_base_config(&self) -> &DomainConfig {
+        &self.base
+    }
+
+    pub fn get_domain_configs(&self) -> &[DomainConfig] {
+        self.domains.as_slice()
+    }
+
+    /// Gets the trust anchors only used in debuggable builds
+    pub fn get_debug_overrides(&self) -> Option<&[TrustAnchor]> {
+        match self.debug_overrides {
+            Some(ref o) => Some(o.as_slice()),
+            None => None,
+        }
+    }
+
+    /// Gets if cleartext traffic is explicitly permitted for the given configuration
+    ///
+    /// Domain configurations inherit the value from their parents and from the base
+    /// configuration. It returns `None` if the platform default is used.
+    pub fn is_cleartext_permitted(&self, config: &DomainConfig) -> Option<bool> {
+        let mut current = config;
+        loop {
+            if current.cleartext_permitted.is_some() {
+                return current.cleartext_permitted;
+            }
+            match current.parent {
+                Some(i) => current = &self.domains[i],
+                None if current as *const _ != &self.base as *const _ => current = &self.base,
+                None => return None,
+            }
+        }
+    }
+
+    /// Gets the trust anchors of the given configuration, if they are not the platform defaults
+    pub fn get_trust_anchors(&self, config: &DomainConfig) -> Option<&[TrustAnchor]> {
+        let mut current = config;
+        loop {
+            if let Some(ref anchors) = current.trust_anchors {
+                return Some(anchors.as_slice());
+            }
+            match current.parent {
+                Some(i) => current = &self.domains[i],
+                None if current as *const _ != &self.base as *const _ => current = &self.base,
+                None => return None,
+            }
+        }
+    }
+
+    /// Checks if any of the configurations pins certificates
+    pub fn has_pins(&self) -> bool {
+        !self.base.pins.is_empty() || self.domains.iter().any(|d| !d.pins.is_empty())
+    }
+}
+
+fn get_attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes.iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+fn get_bool_attribute(attributes: &[OwnedAttribute], name: &str) -> Result<Option<bool>> {
+    match get_attribute(attributes, name) {
+        Some(v) => {
+            match v.parse() {
+                Ok(b) => Ok(Some(b)),
+                Err(_) => Err(Error::ParseError),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn network_security_analysis(manifest: Option<&Manifest>,
+                                 config: &Config,
+                                 results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Checking the network security configuration of the application.");
+    }
+
+    let target_sdk = manifest.get_target_sdk().unwrap_or(manifest.get_min_sdk());
+    let dist_folder = format!("{}/{}", config.get_dist_folder(), config.get_app_id());
+
+    let file = match manifest.get_network_security_config() {
+        Some(resource) => {
+            let file = get_config_file(resource);
+            if file.is_none() {
+                print_warning(format!("The network security configuration resource {} could \
+                                       not be resolved.",
+                                      resource),
+                              config.is_verbose());
+            }
+            file
+        }
+        None => None,
+    };
+
+    match file {
+        Some(file) => {
+            let code = match read_file(format!("{}/{}", dist_folder, file)) {
+                Ok(c) => c,
+                Err(e) => {
+                    print_warning(format!("There was an error reading the network security \
+                                           configuration: {}",
+                                          e),
+                                  config.is_verbose());
+                    return;
+                }
+            };
+            match NetworkSecurityConfig::parse(code.as_str()) {
+                Ok(nsc) => {
+                    check_network_security_config(&nsc,
+                                                  manifest,
+                                                  target_sdk,
+                                                  file.as_str(),
+                                                  code.as_str(),
+                                                  config,
+                                                  results)
+                }
+                Err(e) => {
+                    print_warning(format!("There was an error parsing the network security \
+                                           configuration: {}",
+                                          e),
+                                  config.is_verbose());
+                    return;
+                }
+            }
+        }
+        None => {
+            if target_sdk > 0 && target_sdk < USER_CA_DISTRUSTED_SDK {
+                add_finding(Criticity::Medium,
+                            "User certificate authorities trusted",
+                            format!("The application targets API level {} and does not have a \
+                                     network security configuration, so it trusts the \
+                                     certificate authorities installed by the user. Anyone \
+                                     able to install a certificate in the device can intercept \
+                                     the TLS connections of the application.",
+                                    target_sdk),
+                            "AndroidManifest.xml",
+                            "",
+                            "",
+                            config,
+                            results);
+            }
+            if read_file(format!("{}/{}", dist_folder, DEFAULT_CONFIG_FILE)).is_ok() {
+                add_finding(Criticity::Warning,
+                            "Unused network security configuration",
+                            String::from("The application contains a network security \
+                                          configuration file, but it's not referenced with the \
+                                          networkSecurityConfig attribute of the manifest, so \
+                                          the platform will not apply it."),
+                            DEFAULT_CONFIG_FILE,
+                            "",
+                            "",
+                            config,
+                            results);
+            }
+            add_finding(Criticity::Warning,
+                        "No certificate pinning",
+                        String::from("The application does not have a network security \
+                                      configuration pinning the certificates of its servers. \
+                                      Any certificate authority trusted by the device can issue \
+                                      certificates for them, unless pinning is implemented in \
+                                      the code."),
+                        "AndroidManifest.xml",
+                        "",
+                        "",
+                        config,
+                        results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}",
+                 "The network security configuration was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Network security configuration analyzed.");
+    }
+}
+
+/// Gets the path of the network security configuration file, or of any other XML resource, from
+/// its resource reference
+pub fn get_config_file(resource: &str) -> Option<String> {
+    if resource.starts_with("@xml/") {
+        Some(format!("res/xml/{}.xml", &resource[5..]))
+    } else {
+        None
+    }
+}
+
+/// Loads the network security configuration of the application, if it has one and it can be
+/// parsed
+///
+/// Errors are not reported, since they are already reported by the network security
+/// configuration analysis.
+pub fn load_network_security_config(manifest: &Manifest,
+                                    config: &Config)
+                                    -> Option<NetworkSecurityConfig> {
+    let file = match manifest.get_network_security_config().and_then(get_config_file) {
+        Some(f) => f,
+        None => return None,
+    };
+    match read_file(format!("{}/{}/{}", config.get_dist_folder(), config.get_app_id(), file)) {
+        Ok(code) => NetworkSecurityConfig::parse(code.as_str()).ok(),
+        Err(_) => None,
+    }
+}
+
+fn check_network_security_config(nsc: &NetworkSecurityConfig,
+                                 manifest: &Manifest,
+                                 target_sdk: i32,
+                                 file: &str,
+                                 code: &str,
+                                 config: &Config,
+                                 results: &mut Results) {
+    let base_cleartext = nsc.is_cleartext_permitted(nsc.get_base_config());
+    if base_cleartext == Some(true) {
+        add_finding(Criticity::Medium,
+                    "Cleartext traffic permitted",
+                    String::from("The base network security configuration permits cleartext \
+                                  traffic, so any connection to a plain HTTP endpoint can be \
+                                  intercepted and modified."),
+                    file,
+                    code,
+                    "cleartextTrafficPermitted=\"true\"",
+                    config,
+                    results);
+    } else if base_cleartext.is_none() && target_sdk < CLEARTEXT_DISABLED_SDK &&
+              manifest.uses_cleartext_traffic() != Some(false) {
+        add_finding(Criticity::Low,
+                    "Cleartext traffic permitted",
+                    format!("The application targets API level {}, so cleartext traffic is \
+                             permitted by default unless the base network security \
+                             configuration disables it.",
+                            target_sdk),
+                    file,
+                    code,
+                    "<base-config",
+                    config,
+                    results);
+    }
+
+    let mut cleartext_domains = Vec::new();
+    let mut user_ca_scopes = Vec::new();
+    let mut overrides_pins = false;
+    let mut expired_pins = Vec::new();
+    let today = Local::today().naive_local();
+
+    if let Some(anchors) = nsc.get_trust_anchors(nsc.get_base_config()) {
+        if anchors.iter().any(|a| a.is_user()) {
+            user_ca_scopes.push(String::from("all domains"));
+            overrides_pins |= anchors.iter().any(|a| a.is_user() && a.overrides_pins());
+        }
+    } else if target_sdk < USER_CA_DISTRUSTED_SDK {
+        user_ca_scopes.push(format!("all domains (default for API level {})", target_sdk));
+    }
+
+    for domain in nsc.get_domain_configs() {
+        if domain.get_domains().is_empty() {
+            continue;
+        }
+        let domains = domain.get_domains().join(", ");
+        if nsc.is_cleartext_permitted(domain) == Some(true) {
+            cleartext_domains.push(domains.clone());
+        }
+        if let Some(anchors) = nsc.get_trust_anchors(domain) {
+            if anchors.iter().any(|a| a.is_user()) {
+                user_ca_scopes.push(domains.clone());
+                overrides_pins |= anchors.iter().any(|a| a.is_user() && a.overrides_pins());
+            }
+        }
+        if let Some(expiration) = domain.get_pin_expiration() {
+            match NaiveDate::parse_from_str(expiration, "%Y-%m-%d") {
+                Ok(date) if date < today => expired_pins.push(domains),
+                Ok(_) => {}
+                Err(_) => {
+                    print_warning(format!("The pin set expiration date {} could not be parsed.",
+                                          expiration),
+                                  config.is_verbose())
+                }
+            }
+        }
+    }
+
+    if !cleartext_domains.is_empty() {
+        add_finding(Criticity::Low,
+                    "Cleartext traffic permitted for domains",
+                    format!("The network security configuration permits cleartext traffic to \
+                             the following domains, so the connections to them can be \
+                             intercepted and modified: {}.",
+                            cleartext_domains.join("; ")),
+                    file,
+                    code,
+                    "cleartextTrafficPermitted=\"true\"",
+                    config,
+                    results);
+    }
+
+    if !user_ca_scopes.is_empty() {
+        add_finding(if overrides_pins {
+                        Criticity::High
+                    } else {
+                        Criticity::Medium
+                    },
+                    "User certificate authorities trusted",
+                    format!("The application trusts certificate authorities installed by the \
+                             user for {}. Anyone able to install a certificate in the device \
+                             can intercept the TLS connections of the application.{}",
+                            user_ca_scopes.join("; "),
+                            if overrides_pins {
+                                " Those certificates also override the pinned certificates."
+                            } else {
+                                ""
+                            }),
+                    file,
+                    code,
+                    "src=\"user\"",
+                    config,
+                    results);
+    }
+
+    if let Some(anchors) = nsc.get_debug_overrides() {
+        if !manifest.is_debug() {
+            add_finding(Criticity::Low,
+                        "Debug overrides in release build",
+                        format!("The network security configuration contains debug overrides \
+                                 trusting additional certificate authorities ({}). They are \
+                                 only applied to debuggable builds, but they should not be \
+                                 shipped in release builds.",
+                                anchors.iter()
+                                    .map(|a| a.get_src())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")),
+                        file,
+                        code,
+                        "<debug-overrides",
+                        config,
+                        results);
+        }
+    }
+
+    if !nsc.has_pins() {
+        add_finding(Criticity::Warning,
+                    "No certificate pinning",
+                    String::from("The network security configuration does not pin the \
+                                  certificates of any domain. Any certificate authority trusted \
+                                  by the device can issue certificates for them, unless pinning \
+                                  is implemented in the code."),
+                    file,
+                    code,
+                    "",
+                    config,
+                    results);
+    }
+
+    if !expired_pins.is_empty() {
+        add_finding(Criticity::Low,
+                    "Expired certificate pins",
+                    format!("The certificate pins for the following domains have expired, so \
+                             they are no longer enforced: {}.",
+                            expired_pins.join("; ")),
+                    file,
+                    code,
+                    "expiration=",
+                    config,
+                    results);
+    }
+}
+
+fn add_finding(criticity: Criticity,
+               name: &str,
+               description: String,
+               file: &str,
+               code: &str,
+               needle: &str,
+               config: &Config,
+               results: &mut Results) {
+    let line = if needle.is_empty() {
+        None
+    } else {
+        get_line(code, needle).ok()
+    };
+    let vuln_code = match line {
+        Some(l) => Some(get_code(code, l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(name),
+                                  description,
+                                  Some(file),
+                                  line,
+                                  line,
+                                  vuln_code);
+    results.add_vulnerability(vuln);
+}
+
+fn read_file(path: String) -> Result<String> {
+    let mut f = try!(File::open(path));
+    let mut code = String::new();
+    try!(f.read_to_string(&mut code));
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkSecurityConfig;
+
+    #[test]
+    fn it_parse_network_security_config() {
+        let code = "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+            <network-security-config>
+                <base-config cleartextTrafficPermitted=\"false\">
+                    <trust-anchors>
+                        <certificates src=\"system\" />
+                    </trust-anchors>
+                </base-config>
+                <domain-config cleartextTrafficPermitted=\"true\">
+                    <domain includeSubdomains=\"true\">example.com</domain>
+                    <domain-config>
+                        <domain>secure.example.com</domain>
+                        <trust-anchors>
+                            <certificates src=\"user\" overridePins=\"true\" />
+                        </trust-anchors>
+                        <pin-set expiration=\"2018-01-01\">
+                            <pin digest=\"SHA-256\">
+                                7HIpactkIAq2Y49orFOOQKurWxmmSFZhBCoQYcRhJ3Y=
+                            </pin>
+                        </pin-set>
+                    </domain-config>
+                </domain-config>
+                <debug-overrides>
+                    <trust-anchors>
+                        <certificates src=\"@raw/debug_cas\" />
+                    </trust-anchors>
+                </debug-overrides>
+            </network-security-config>";
+
+        let nsc = NetworkSecurityConfig::parse(code).unwrap();
+        assert_eq!(nsc.is_cleartext_permitted(nsc.get_base_config()), Some(false));
+        assert_eq!(nsc.get_domain_configs().len(), 2);
+
+        let parent = &nsc.get_domain_configs()[0];
+        assert_eq!(parent.get_domains(), &[String::from("example.com")]);
+        assert_eq!(nsc.is_cleartext_permitted(parent), Some(true));
+        assert_eq!(nsc.get_trust_anchors(parent).unwrap()[0].get_src(), "system");
+
+        let child = &nsc.get_domain_configs()[1];
+        assert_eq!(child.get_domains(), &[String::from("secure.example.com")]);
+        assert_eq!(nsc.is_cleartext_permitted(child), Some(true));
+        let anchors = nsc.get_trust_anchors(child).unwrap();
+        assert!(anchors[0].is_user());
+        assert!(anchors[0].overrides_pins());
+        assert_eq!(child.get_pins().len(), 1);
+        assert_eq!(child.get_pin_expiration(), Some("2018-01-01"));
+
+        assert!(nsc.has_pins());
+        assert_eq!(nsc.get_debug_overrides().unwrap()[0].get_src(), "@raw/debug_cas");
+    }
+
+    #[test]
+    fn it_parse_network_security_config_defaults() {
+        let code = "<network-security-config>
+                <domain-config>
+                    <domain>example.com</domain>
+                </domain-config>
+            </network-security-config>";
+
+        let nsc = NetworkSecurityConfig::parse(code).unwrap();
+        let domain = &nsc.get_domain_configs()[0];
+        assert_eq!(nsc.is_cleartext_permitted(domain), None);
+        assert!(nsc.get_trust_anchors(domain).is_none());
+        assert!(!nsc.has_pins());
+        assert!(nsc.get_debug_overrides().is_none());
+    }
+
+    #[test]
+    fn it_parse_network_security_config_errors() {
+        assert!(NetworkSecurityConfig::parse("<base-config cleartextTrafficPermitted=\"yes\" />")
+            .is_err());
+        assert!(NetworkSecurityConfig::parse("<base-config><trust-anchors><certificates />\
+                                              </trust-anchors></base-config>")
+            .is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use std::result;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, Serializer};
+use regex::{Regex, Captures};
+use colored::Colorize;
+
+use {Config, Criticity, Result, Error, print_warning, print_vulnerability};
+use results::{Results, ReportSection, Vulnerability};
+use super::java::{get_java_files, get_class_name, get_compact_block};
+use super::read_text_file;
+
+/// Order of the obfuscation section in the report
+const SECTION_ORDER: u32 = 90;
+
+/// Ratio of classes with obfuscated names from which the code is considered obfuscated
+const OBFUSCATED_RATIO: f64 = 0.3;
+
+/// Minimum number of classes to report that the code is not obfuscated
+const MIN_CLASSES: usize = 10;
+
+/// Maximum length of the class names generated by ProGuard and R8
+const MAX_OBFUSCATED_NAME: usize = 3;
+
+/// Static methods returning a string from a string, a char or byte array or an integer, such as
+/// the decryption methods of encrypted strings
+const DECRYPTOR_REGEX: &'static str = concat!(r"static\s+(?:final\s+)?(?:synchronized\s+)?",
+                                              r"String\s+([\w$]+)\s*\(\s*(?:String|char\s*\[\]|",
+                                              r"byte\s*\[\]|int)\b[^)]*\)\s*(?:throws[^{]*)?\{");
+
+/// Fully qualified names, such as `a.b.c` or `a.b.c.a`
+const QUALIFIED_NAME_REGEX: &'static str = r"[A-Za-z_$][\w$]*(?:\.[A-Za-z_$][\w$]*)+";
+
+/// ProGuard or R8 mapping file, relating the obfuscated names with the original ones
+#[derive(Debug)]
+pub struct Mapping {
+    /// Original class and members for each obfuscated class
+    classes: BTreeMap<String, (String, BTreeMap<String, Vec<String>>)>,
+    name_regex: Regex,
+}
+
+impl Mapping {
+    /// Loads the mapping from the given file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mapping> {
+        let mut f = try!(File::open(path));
+        let mut mapping = String::new();
+        try!(f.read_to_string(&mut mapping));
+        Mapping::parse(mapping.as_str())
+    }
+
+    /// Parses a mapping file
+    ///
+    /// Classes are written as `original.Class -> a.b:`, followed by their fields and methods,
+    /// indented, as `type name -> a` or `1:2:type name(arguments):3:4 -> a`. Lines starting with
+    /// `#` are comments.
+    pub fn parse(mapping: &str) -> Result<Mapping> {
+        let mut classes = BTreeMap::new();
+        let mut current = None;
+        for line in mapping.lines() {
+            if line.trim().is_empty() || line.trim_left().starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, " -> ");
+            let (original, obfuscated) = match (parts.next(), parts.next()) {
+                (Some(o), Some(b)) => (o.trim(), b.trim()),
+                _ => return Err(Error::ParseError),
+            };
+
+            if !line.starts_with(char::is_whitespace) {
+                if !obfuscated.ends_with(':') {
+                    return Err(Error::ParseError);
+                }
+                let obfuscated = String::from(&obfuscated[..obfuscated.len() - 1]);
+                let _ = classes.insert(obfuscated.clone(),
+                                       (String::from(original), BTreeMap::new()));
+                current = Some(obfuscated);
+            } else {
+                let class = match current {
+                    Some(ref c) => classes.get_mut(c).unwrap(),
+                    None => return Err(Error::ParseError),
+                };
+                let member = match get_member_name(original) {
+                    Some(m) => String::from(m),
+                    None => return Err(Error::ParseError),
+                };
+                let originals = class.1.entry(String::from(obfuscated)).or_insert_with(Vec::new);
+                if !originals.contains(&member) {
+                    originals.push(member);
+                }
+            }
+        }
+        Ok(Mapping {
+            classes: classes,
+            name_regex: Regex::new(QUALIFIED_NAME_REGEX).unwrap(),
+        })
+    }
+
+    /// Gets the number of classes in the mapping
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// Gets the original name of an obfuscated class
+    pub fn get_original_class(&self, class: &str) -> Option<&str> {
+        self.classes.get(class).map(|c| c.0.as_str())
+    }
+
+    /// Gets the original name of a member of an obfuscated class, if it's not ambiguous
+    pub fn get_original_member(&self, class: &str, member: &str) -> Option<&str> {
+        match self.classes.get(class).and_then(|c| c.1.get(member)) {
+            Some(originals) if originals.len() == 1 => Some(originals[0].as_str()),
+            _ => None,
+        }
+    }
+
+    /// Replaces the obfuscated classes and members in the text with their original names
+    ///
+    /// Only fully qualified names are replaced, such as `a.b.c` or `a.b.c.a`, since short
+    /// names are too ambiguous.
+    pub fn deobfuscate(&self, text: &str) -> String {
+        self.name_regex.replace_all(text, |caps: &Captures| {
+            let name = caps.at(0).unwrap();
+            if let Some(class) = self.get_original_class(name) {
+                return String::from(class);
+            }
+            if let Some(dot) = name.rfind('.') {
+                let (class, member) = (&name[..dot], &name[dot + 1..]);
+                if let (Some(original_class), Some(original_member)) =
+                       (self.get_original_class(class),
+                        self.get_original_member(class, member)) {
+                    return format!("{}.{}", original_class, original_member);
+                }
+            }
+            String::from(name)
+        })
+    }
+}
+
+/// Gets the name of a member from its declaration in the mapping file, such as `run` from
+/// `12:14:void run(int):50:52`
+fn get_member_name(declaration: &str) -> Option<&str> {
+    let declaration = declaration.trim_left_matches(|c: char| c.is_digit(10) || c == ':');
+    let name = match declaration.split_whitespace().nth(1) {
+        Some(n) => n,
+        None => return None,
+    };
+    // Inlined methods keep the class they come from, as `com.example.Other.run`
+    let name = name.split('(').next().unwrap().rsplit('.').next().unwrap();
+    if name.is_empty() { None } else { Some(name) }
+}
+
+/// Checks if a class name looks generated by an obfuscator, such as `a` or `ab`
+fn is_obfuscated_name(name: &str) -> bool {
+    let name = name.split('$').next().unwrap();
+    !name.is_empty() && name.len() <= MAX_OBFUSCATED_NAME &&
+    name.chars().all(|c| c >= 'a' && c <= 'z')
+}
+
+/// Finds the string decryption methods of the code
+///
+/// They are static methods returning a string that transform the characters of the argument
+/// with XOR operations.
+fn find_string_decryptors<'c>(code: &'c str, regex: &Regex) -> Vec<&'c str> {
+    let mut decryptors = Vec::new();
+    for caps in regex.captures_iter(code) {
+        let (_, end) = caps.pos(0).unwrap();
+        let body = match get_compact_block(code, end - 1) {
+            Some(b) => b,
+            None => continue,
+        };
+        if body.contains('^') &&
+           (body.contains("charAt(") || body.contains("toCharArray()")) &&
+           (body.contains("newString(") || body.contains("String.valueOf(") ||
+            body.contains(".intern()")) {
+            decryptors.push(caps.at(1).unwrap());
+        }
+    }
+    decryptors
+}
+
+/// Summary of the obfuscation of the code
+pub struct ObfuscationSummary {
+    classes: usize,
+    obfuscated_classes: usize,
+    string_decryptors: Vec<String>,
+    mapping_classes: Option<usize>,
+}
+
+impl ObfuscationSummary {
+    /// Checks if the class names are obfuscated
+    pub fn is_obfuscated(&self) -> bool {
+        self.classes > 0 &&
+        self.obfuscated_classes as f64 / self.classes as f64 >= OBFUSCATED_RATIO
+    }
+
+    /// Checks if the strings of the code are encrypted
+    pub fn has_string_encryption(&self) -> bool {
+        !self.string_decryptors.is_empty()
+    }
+}
+
+impl Serialize for ObfuscationSummary {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("ObfuscationSummary", 6));
+        try!(serializer.serialize_struct_elt(&mut state, "obfuscated", self.is_obfuscated()));
+        try!(serializer.serialize_struct_elt(&mut state, "classes", self.classes));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "obfuscated_classes",
+                                             self.obfuscated_classes));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "string_encryption",
+                                             self.has_string_encryption()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "string_decryptors",
+                                             &self.string_decryptors));
+        try!(serializer.serialize_struct_elt(&mut state, "mapping_classes", self.mapping_classes));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Detects if the code is obfuscated, and loads the mapping file given with `--mapping` so that
+/// the names in the report are de-obfuscated
+///
+/// It must run before any other analysis, so that all the findings use the original names.
+pub fn obfuscation_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Checking if the code of the application is obfuscated.");
+    }
+
+    let mut mapping_classes = None;
+    if let Some(path) = config.get_mapping_file() {
+        match Mapping::load(path) {
+            Ok(mapping) => {
+                if config.is_verbose() {
+                    println!("Loaded the mapping of {} classes from `{}`.", mapping.len(), path);
+                }
+                mapping_classes = Some(mapping.len());
+                results.set_mapping(mapping);
+            }
+            Err(e) => {
+                print_warning(format!("The mapping file `{}` could not be loaded, so the names \
+                                       in the report will not be de-obfuscated. More info: {}",
+                                      path,
+                                      e),
+                              config.is_verbose());
+            }
+        }
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the obfuscation \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let regex = Regex::new(DECRYPTOR_REGEX).unwrap();
+    let mut summary = ObfuscationSummary {
+        classes: 0,
+        obfuscated_classes: 0,
+        string_decryptors: Vec::new(),
+        mapping_classes: mapping_classes,
+    };
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        summary.classes += 1;
+        if is_obfuscated_name(class.rsplit('.').next().unwrap()) {
+            summary.obfuscated_classes += 1;
+        }
+        match read_text_file(&path) {
+            Ok(Some(code)) => {
+                if code.contains('^') {
+                    for method in find_string_decryptors(&code, &regex) {
+                        summary.string_decryptors.push(format!("{}.{}()", class, method));
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+            }
+        }
+    }
+
+    if !summary.is_obfuscated() && summary.classes >= MIN_CLASSES {
+        let description = format!("Only {} of the {} classes of the application have \
+                                   obfuscated names. Without obfuscation, the decompiled code \
+                                   keeps the names of the classes, methods and fields, which \
+                                   makes reverse engineering and tampering much easier. \
+                                   ProGuard or R8 should be enabled for release builds.",
+                                  summary.obfuscated_classes,
+                                  summary.classes);
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), Criticity::Warning);
+        }
+        results.add_vulnerability(Vulnerability::new(Criticity::Warning,
+                                                     String::from("Code not obfuscated"),
+                                                     description,
+                                                     None as Option<&str>,
+                                                     None,
+                                                     None,
+                                                     None));
+    }
+
+    results.add_section(get_report_section(&summary));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The obfuscation was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Obfuscation analyzed.");
+    }
+}
+
+/// Generates the obfuscation section of the report
+fn get_report_section(summary: &ObfuscationSummary) -> ReportSection {
+    let mut section = ReportSection::new("obfuscation", "Obfuscation", SECTION_ORDER);
+    section.set_data(summary);
+    let rows = vec![vec![String::from("Obfuscated class names"),
+                         format!("{} ({} of {} classes)",
+                                 Results::html_yes_no(summary.is_obfuscated()),
+                                 summary.obfuscated_classes,
+                                 summary.classes)],
+                    vec![String::from("String encryption"),
+                         if summary.has_string_encryption() {
+                             format!("Yes ({})",
+                                     Results::html_escape(&summary.string_decryptors.join(", ")))
+                         } else {
+                             String::from("No")
+                         }],
+                    vec![String::from("Mapping file"),
+                         match summary.mapping_classes {
+                             Some(classes) => format!("Loaded, with {} classes", classes),
+                             None => String::from("Not provided"),
+                         }]];
+    section.set_html_table(&["Check", "Result"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use super::{Mapping, DECRYPTOR_REGEX, get_member_name, is_obfuscated_name,
+                find_string_decryptors};
+
+    const MAPPING: &'static str = "# compiler: R8\n\
+                                   com.example.Login -> a.b.c:\n    \
+                                       java.lang.String token -> a\n    \
+                                       1:4:void send(java.lang.String):20:23 -> a\n    \
+                                       5:9:boolean check():30:34 -> b\n\
+                                   com.example.Crypto -> a.b.d:\n    \
+                                       byte[] encrypt(byte[]) -> a\n    \
+                                       byte[] decrypt(byte[]) -> a\n";
+
+    #[test]
+    fn it_mapping() {
+        let mapping = Mapping::parse(MAPPING).unwrap();
+        assert_eq!(mapping.len(), 2);
+        assert_eq!(mapping.get_original_class("a.b.c"), Some("com.example.Login"));
+        assert_eq!(mapping.get_original_class("a.b.e"), None);
+        assert_eq!(mapping.get_original_member("a.b.c", "a"), Some("token"));
+        assert_eq!(mapping.get_original_member("a.b.c", "b"), Some("check"));
+        // Overloaded names can't be resolved
+        assert_eq!(mapping.get_original_member("a.b.d", "a"), None);
+
+        assert_eq!(mapping.deobfuscate("Weak key in a.b.d, called from a.b.c.b() and a.b.d.a()"),
+                   "Weak key in com.example.Crypto, called from com.example.Login.check() and \
+                    a.b.d.a()");
+
+        assert!(Mapping::parse("a -> b").is_err());
+        assert!(Mapping::parse("    void run() -> a").is_err());
+    }
+
+    #[test]
+    fn it_get_member_name() {
+        assert_eq!(get_member_name("12:14:void run(int):50:52"), Some("run"));
+        assert_eq!(get_member_name("java.lang.String token"), Some("token"));
+        assert_eq!(get_member_name("1:3:void com.example.Other.run():10:12"), Some("run"));
+        assert_eq!(get_member_name("token"), None);
+    }
+
+    #[test]
+    fn it_is_obfuscated_name() {
+        assert!(is_obfuscated_name("a"));
+        assert!(is_obfuscated_name("abc"));
+        assert!(is_obfuscated_name("ab$a"));
+        assert!(!is_obfuscated_name("abcd"));
+        assert!(!is_obfuscated_name("Login"));
+        assert!(!is_obfuscated_name("R"));
+    }
+
+    #[test]
+    fn it_find_string_decryptors() {
+        let regex = Regex::new(DECRYPTOR_REGEX).unwrap();
+        let code = "public final class a {\n    \
+                        public static String a(String str) {\n        \
+                            char[] chars = str.toCharArray();\n        \
+                            for (int i = 0; i < chars.length; i++) {\n            \
+                                chars[i] = (char) (chars[i] ^ 'K');\n        \
+                            }\n        \
+                            return new String(chars).intern();\n    \
+                        }\n\n    \
+                        public static String b(String str) {\n        \
+                            return str.toUpperCase();\n    \
+                        }\n\
+                    }\n";
+        assert_eq!(find_string_decryptors(code, &regex), vec!["a"]);
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::PathBuf;
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability};
+use super::manifest::{Manifest, Permission, API_MAPPED_PERMISSIONS, get_line};
+use super::code::get_line_for;
+use super::java::{get_java_files, get_class_name, get_enclosing_method, describe_location};
+use super::read_text_file;
+
+/// Order of the permission usage section in the report
+const SECTION_ORDER: u32 = 45;
+
+/// First call to an API found in the code
+struct ApiUsage {
+    path: PathBuf,
+    code: String,
+    index: usize,
+}
+
+/// Gets the mapped APIs called in the code, with the index of their first call
+fn find_api_calls(code: &str) -> Vec<(&'static str, usize)> {
+    let mut calls = Vec::new();
+    for permission in &API_MAPPED_PERMISSIONS {
+        for api in permission.get_apis() {
+            if calls.iter().any(|&(a, _)| a == *api) {
+                continue;
+            }
+            if let Some(index) = code.find(api) {
+                calls.push((*api, index));
+            }
+        }
+    }
+    calls
+}
+
+/// Gets the permissions that allow calling the API
+fn get_api_permissions(api: &str) -> Vec<Permission> {
+    API_MAPPED_PERMISSIONS.iter()
+        .filter(|p| p.get_apis().iter().any(|a| *a == api))
+        .cloned()
+        .collect()
+}
+
+/// Cross-references the permissions declared in the manifest with the APIs called in the code,
+/// reporting permissions that are never used and APIs used without declaring their permission
+pub fn permission_usage_analysis(manifest: Option<&Manifest>,
+                                 config: &Config,
+                                 results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    if config.is_verbose() {
+        println!("Checking the usage of the permissions of the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the permission \
+                                   usage analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut usages: BTreeMap<&'static str, ApiUsage> = BTreeMap::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let calls: Vec<_> = find_api_calls(&code)
+            .into_iter()
+            .filter(|&(api, _)| !usages.contains_key(api))
+            .collect();
+        if calls.is_empty() {
+            continue;
+        }
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        for (api, index) in calls {
+            let _ = usages.insert(api,
+                                  ApiUsage {
+                                      path: relative.clone(),
+                                      code: code.clone(),
+                                      index: index,
+                                  });
+        }
+    }
+
+    let checklist = manifest.get_permission_checklist();
+    let mut rows = Vec::new();
+    let mut data = BTreeMap::new();
+    for permission in API_MAPPED_PERMISSIONS.iter().filter(|p| checklist.needs_permission(**p)) {
+        let used: Vec<_> = permission.get_apis()
+            .iter()
+            .filter(|api| usages.contains_key(*api))
+            .cloned()
+            .collect();
+        if used.is_empty() {
+            add_unused_finding(*permission, manifest, config, results);
+            data.entry("unused")
+                .or_insert_with(Vec::new)
+                .push(String::from(permission.as_str()));
+        }
+        rows.push(vec![String::from(permission.as_str()),
+                       String::from(Results::html_yes_no(true)),
+                       String::from(Results::html_yes_no(!used.is_empty())),
+                       Results::html_escape(&used.join(", "))]);
+    }
+
+    // APIs allowed by the same permissions are reported once
+    let mut missing: BTreeMap<Vec<Permission>, Vec<&'static str>> = BTreeMap::new();
+    for api in usages.keys() {
+        let permissions = get_api_permissions(api);
+        if !permissions.iter().any(|p| checklist.needs_permission(*p)) {
+            missing.entry(permissions).or_insert_with(Vec::new).push(*api);
+        }
+    }
+    for (permissions, apis) in missing {
+        let usage = usages.get(apis[0]).unwrap();
+        add_missing_finding(&permissions, apis[0], usage, config, results);
+        for permission in permissions {
+            data.entry("undeclared")
+                .or_insert_with(Vec::new)
+                .push(String::from(permission.as_str()));
+            rows.push(vec![String::from(permission.as_str()),
+                           String::from(Results::html_yes_no(false)),
+                           String::from(Results::html_yes_no(true)),
+                           Results::html_escape(&apis.join(", "))]);
+        }
+    }
+
+    let mut section = ReportSection::new("permission_usage", "Permission usage", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["Permission", "Declared", "Used", "APIs found"], &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The permission usage was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Permission usage analyzed.");
+    }
+}
+
+/// Adds a finding for a permission declared in the manifest but never used in the code
+fn add_unused_finding(permission: Permission,
+                      manifest: &Manifest,
+                      config: &Config,
+                      results: &mut Results) {
+    let description = format!("The application requests the {} permission, but none of the \
+                               APIs that require it were found in the code ({}). Requesting \
+                               permissions that are not needed increases the impact of a \
+                               compromise of the application. Note that the permission could \
+                               still be used through reflection or native code.",
+                              permission.as_str(),
+                              permission.get_apis().join(", "));
+    let line = get_line(manifest.get_code(), permission.as_str()).ok();
+    let code = match line {
+        Some(l) => Some(get_code(manifest.get_code(), l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Low);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Low,
+                                      String::from("Unused permission"),
+                                      description,
+                                      Some("AndroidManifest.xml"),
+                                      line,
+                                      line,
+                                      code);
+    vuln.set_permission(permission);
+    results.add_vulnerability(vuln);
+}
+
+/// Adds a finding for an API called in the code without declaring any of its permissions
+fn add_missing_finding(permissions: &[Permission],
+                       api: &str,
+                       usage: &ApiUsage,
+                       config: &Config,
+                       results: &mut Results) {
+    let class = get_class_name(&usage.path)
+        .unwrap_or_else(|| usage.path.to_string_lossy().into_owned());
+    let location = describe_location(&class, get_enclosing_method(&usage.code, usage.index));
+    let description = format!("The code calls {} in {}, which requires the {} permission, but \
+                               the application doesn't declare it. The call will fail with a \
+                               SecurityException, unless the permission is granted through a \
+                               shared user ID.",
+                              api.trim_right_matches('('),
+                              location,
+                              permissions.iter()
+                                  .map(|p| p.as_str())
+                                  .collect::<Vec<_>>()
+                                  .join(" or "));
+    let line = get_line_for(usage.index, &usage.code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Warning);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Warning,
+                                      String::from("Undeclared permission"),
+                                      description,
+                                      Some(usage.path.as_path()),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(&usage.code, line, line)));
+    vuln.set_required_permissions(permissions.to_vec());
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_api_calls, get_api_permissions};
+    use super::super::manifest::Permission;
+
+    #[test]
+    fn it_find_api_calls() {
+        let code = "LocationManager manager = (LocationManager) getSystemService(\"location\");\n\
+                    Location last = manager.getLastKnownLocation(\"gps\");\n\
+                    manager.requestLocationUpdates(\"gps\", 0, 0, this);\n\
+                    SmsManager.getDefault().sendTextMessage(number, null, text, null, null);";
+        let calls = find_api_calls(code);
+        assert_eq!(calls.len(), 3);
+        let index = code.find("getLastKnownLocation").unwrap();
+        assert!(calls.contains(&("getLastKnownLocation(", index)));
+        assert!(calls.iter().any(|&(api, _)| api == "requestLocationUpdates("));
+        assert!(calls.iter().any(|&(api, _)| api == "sendTextMessage("));
+        assert!(find_api_calls("int a = 3;").is_empty());
+    }
+
+    #[test]
+    fn it_get_api_permissions() {
+        assert_eq!(get_api_permissions("getLastKnownLocation("),
+                   vec![Permission::AndroidPermissionAccessFineLocation,
+                        Permission::AndroidPermissionAccessCoarseLocation]);
+        assert_eq!(get_api_permissions("newWakeLock("),
+                   vec![Permission::AndroidPermissionWakeLock]);
+        assert!(get_api_permissions("toString(").is_empty());
+    }
+}
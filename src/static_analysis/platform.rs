@@ -0,0 +1,344 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, ComponentKind};
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, split_methods, get_java_files,
+                  get_class_name, get_enclosing_method, describe_location};
+use super::structural::{get_closing_index, split_arguments};
+use super::logging::{get_names_regex, is_sensitive_name};
+use super::layouts::{Layout, is_sensitive_field};
+use super::accessibility::read_component_code;
+use super::read_text_file;
+
+/// Factories of `ClipData` that copy text to the clipboard, whose first argument is the label
+const CLIP_DATA_FACTORIES: [&'static str; 2] = ["newPlainText", "newHtmlText"];
+
+/// Data leak through the user interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UiLeak {
+    /// Sensitive value copied to the clipboard, readable by other applications
+    Clipboard,
+    /// Activity with password fields that can be captured in screenshots and recent apps
+    Screenshot,
+    /// Sensitive text field whose content is kept by the keyboard
+    KeyboardCache,
+}
+
+impl UiLeak {
+    fn get_name(&self) -> &'static str {
+        match *self {
+            UiLeak::Clipboard => "Sensitive data copied to the clipboard",
+            UiLeak::Screenshot => "Sensitive screen without FLAG_SECURE",
+            UiLeak::KeyboardCache => "Keyboard cache for sensitive input",
+        }
+    }
+
+    fn get_criticity(&self) -> Criticity {
+        match *self {
+            UiLeak::Clipboard => Criticity::Medium,
+            UiLeak::Screenshot | UiLeak::KeyboardCache => Criticity::Low,
+        }
+    }
+
+    fn get_cwe(&self) -> &'static str {
+        match *self {
+            UiLeak::Clipboard | UiLeak::Screenshot => "CWE-200",
+            UiLeak::KeyboardCache => "CWE-524",
+        }
+    }
+
+    fn get_remediation(&self) -> &'static str {
+        match *self {
+            UiLeak::Clipboard => {
+                "Don't copy sensitive values to the clipboard, or mark the clip as sensitive \
+                 with the ClipDescription.EXTRA_IS_SENSITIVE extra."
+            }
+            UiLeak::Screenshot => {
+                "Set the FLAG_SECURE window flag in the activities that show sensitive data."
+            }
+            UiLeak::KeyboardCache => {
+                "Use the textNoSuggestions input type, or a password input type, for the fields \
+                 with sensitive data."
+            }
+        }
+    }
+}
+
+/// Finds the sensitive values copied to the clipboard, with the index of the call and the name
+/// of the value
+///
+/// Methods that mark the clip as sensitive with `EXTRA_IS_SENSITIVE` are skipped.
+fn find_clipboard_writes<'a>(code: &'a str, names: &Regex) -> Vec<(usize, &'a str)> {
+    let tokens = tokenize(code);
+    let mut writes = Vec::new();
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        if statements.iter().any(|s| s.iter().any(|t| t.get_text() == "EXTRA_IS_SENSITIVE")) {
+            continue;
+        }
+        for statement in statements {
+            for (i, token) in statement.iter().enumerate() {
+                if !is_call(statement, i) {
+                    continue;
+                }
+                let skipped = if CLIP_DATA_FACTORIES.contains(&token.get_text()) && i >= 2 &&
+                                 statement[i - 2].get_text() == "ClipData" {
+                    1
+                } else if token.get_text() == "setText" &&
+                          statement.iter().any(|t| t.get_text().contains("lipboard")) {
+                    0
+                } else {
+                    continue;
+                };
+                let close = match get_closing_index(statement, i + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let value = split_arguments(&statement[i + 2..close])
+                    .into_iter()
+                    .skip(skipped)
+                    .filter_map(|argument| get_sensitive_value(argument, names))
+                    .next();
+                if let Some(value) = value {
+                    writes.push((token.get_start(), value));
+                }
+            }
+        }
+    }
+    writes.sort();
+    writes
+}
+
+/// Gets the variable, field or getter with a sensitive name in the argument, if any
+fn get_sensitive_value<'a>(argument: &[Token<'a>], names: &Regex) -> Option<&'a str> {
+    argument.iter()
+        .find(|t| t.get_kind() == TokenKind::Identifier && is_sensitive_name(t.get_text(), names))
+        .map(|t| t.get_text())
+}
+
+/// Checks if the code protects the window from screenshots with `FLAG_SECURE`
+///
+/// Decompiled code usually has the value of the flag, `8192`, instead of its name.
+fn has_flag_secure(code: &str, flags: &Regex) -> bool {
+    code.contains("FLAG_SECURE") || flags.is_match(code)
+}
+
+/// Looks for data leaks through the user interface: sensitive values copied to the clipboard,
+/// activities with password fields that can be captured in screenshots, and sensitive text
+/// fields whose content is kept in the keyboard caches
+///
+/// The sensitive names are the same as in the logging analysis, so that the names added in the
+/// configuration are used too.
+pub fn platform_analysis(manifest: Option<&Manifest>,
+                         layouts: &[Layout],
+                         config: &Config,
+                         results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for data leaks through the clipboard, screenshots and keyboard caches.");
+    }
+    let names = get_names_regex(config.get_logging_config().get_sensitive_names());
+
+    for layout in layouts {
+        for element in layout.get_elements() {
+            if element.is_text_input() && !element.is_private_input() &&
+               is_sensitive_field(element, &names) {
+                let description = format!("The {} text field of the {} layout seems to hold \
+                                           sensitive data, but {}, so the keyboard can keep \
+                                           the typed text in its suggestions and caches, \
+                                           where other applications can read it.",
+                                          element.get_id().unwrap_or(element.get_name()),
+                                          layout.get_name(),
+                                          if element.get_attribute("inputType").is_none() {
+                                              "it has no input type"
+                                          } else {
+                                              "its input type does not include \
+                                               textNoSuggestions"
+                                          });
+                add_finding(UiLeak::KeyboardCache,
+                            description,
+                            layout.get_path(),
+                            layout.get_code(),
+                            element.get_line(),
+                            config,
+                            results);
+            }
+        }
+    }
+
+    // Layouts with password fields, whose activities should not be captured in screenshots
+    let sensitive_layouts: BTreeSet<&str> = layouts.iter()
+        .filter(|layout| layout.get_elements().iter().any(|e| e.is_password_input()))
+        .map(Layout::get_name)
+        .collect();
+    if let Some(manifest) = manifest {
+        let layout_reference = Regex::new(r"\bR\.layout\.(\w+)").unwrap();
+        let flags = Regex::new(r"\b(?:setFlags|addFlags)\s*\(\s*8192\b").unwrap();
+        for activity in manifest.get_components() {
+            if activity.get_kind() != ComponentKind::Activity || !activity.is_enabled() {
+                continue;
+            }
+            let (path, code) = match read_component_code(activity, config) {
+                Some(c) => c,
+                None => continue,
+            };
+            if has_flag_secure(&code, &flags) {
+                continue;
+            }
+            let reference = layout_reference.captures_iter(&code)
+                .find(|caps| sensitive_layouts.contains(caps.at(1).unwrap()));
+            if let Some(caps) = reference {
+                let (start, _) = caps.pos(0).unwrap();
+                let description = format!("The {} activity shows the {} layout, that has \
+                                           password fields, without setting the FLAG_SECURE \
+                                           window flag, so its content can be captured in \
+                                           screenshots, screen recordings and the recent apps \
+                                           screen.",
+                                          activity.get_name(),
+                                          caps.at(1).unwrap());
+                add_finding(UiLeak::Screenshot,
+                            description,
+                            &path,
+                            &code,
+                            get_line_for(start, &code),
+                            config,
+                            results);
+            }
+        }
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the clipboard \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !code.contains("ClipData") && !code.contains("lipboard") {
+            continue;
+        }
+        for (index, value) in find_clipboard_writes(&code, &names) {
+            let description = format!("The sensitive value {} is copied to the clipboard in \
+                                       {}. Other applications, and keyboards, can read the \
+                                       clipboard, and it's synchronized between devices in \
+                                       some of them.",
+                                      value,
+                                      describe_location(&class,
+                                                        get_enclosing_method(&code, index)));
+            add_finding(UiLeak::Clipboard,
+                        description,
+                        &relative,
+                        &code,
+                        get_line_for(index, &code),
+                        config,
+                        results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The user interface data leaks were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("User interface data leaks analyzed.");
+    }
+}
+
+/// Adds a finding for a data leak through the user interface
+fn add_finding(leak: UiLeak,
+               description: String,
+               path: &Path,
+               code: &str,
+               line: usize,
+               config: &Config,
+               results: &mut Results) {
+    let criticity = leak.get_criticity();
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(leak.get_name()),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(leak.get_cwe())],
+                                        vec![String::from("MASVS-PLATFORM-3")],
+                                        vec![String::from("M6")],
+                                        Vec::new(),
+                                        Some(String::from(leak.get_remediation()))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{find_clipboard_writes, has_flag_secure};
+    use super::super::logging::get_names_regex;
+
+    #[test]
+    fn it_find_clipboard_writes() {
+        let names = get_names_regex(&[]);
+        let code = "public class Account {\n\
+                    void copy(ClipboardManager clipboard) {\n\
+                    clipboard.setPrimaryClip(ClipData.newPlainText(\"password\", this.password));\n\
+                    }\n\
+                    void copyName(ClipboardManager clipboard) {\n\
+                    clipboard.setPrimaryClip(ClipData.newPlainText(\"token\", userName));\n\
+                    }\n\
+                    void copyToken(ClipboardManager clipboard) {\n\
+                    ClipData clip = ClipData.newPlainText(\"token\", accessToken);\n\
+                    clip.getDescription().setExtras(extras(ClipDescription.EXTRA_IS_SENSITIVE));\n\
+                    clipboard.setPrimaryClip(clip);\n\
+                    }\n\
+                    void copyKey(android.text.ClipboardManager clipboard) {\n\
+                    clipboard.setText(getApiKey());\n\
+                    }\n\
+                    }";
+        let writes: Vec<_> = find_clipboard_writes(code, &names)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(writes, vec!["password", "getApiKey"]);
+    }
+
+    #[test]
+    fn it_has_flag_secure() {
+        let flags = Regex::new(r"\b(?:setFlags|addFlags)\s*\(\s*8192\b").unwrap();
+        assert!(has_flag_secure("getWindow().setFlags(8192, 8192);", &flags));
+        assert!(has_flag_secure("getWindow().addFlags(WindowManager.LayoutParams.FLAG_SECURE);",
+                                &flags));
+        assert!(!has_flag_secure("getWindow().addFlags(128);", &flags));
+    }
+}
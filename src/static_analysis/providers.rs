@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability};
+use super::manifest::{Manifest, Component, ComponentKind};
+use super::code::get_line_for;
+use super::read_text_file;
+
+/// SQL calls built concatenating the selection or the URI received from the client
+const QUERY_SQL_REGEX: &'static str = concat!(r#"(?i)\b(?:rawQuery|execSQL|query|update|delete)"#,
+                                              r#"\s*\(.*(?:"\s*\+\s*[\w.()]*"#,
+                                              r#"(?:selection|uri|segment|sort|parameter|where)"#,
+                                              r#"|(?:selection|uri|segment|sort|parameter|where)"#,
+                                              r#"[\w.()]*\s*\+\s*")"#);
+
+/// Classes showing that the provider is backed by an SQLite database
+const SQL_MARKERS: [&'static str; 4] = ["SQLiteDatabase",
+                                        "SQLiteQueryBuilder",
+                                        "getReadableDatabase",
+                                        "getWritableDatabase"];
+
+/// Checks the content providers of the application
+///
+/// Exported providers are checked for missing read or write permissions, URI permissions
+/// granted for all their paths, and SQL queries built concatenating the selection or the URI
+/// received from the client. SQL injections in exported providers are reported as a single
+/// finding, with the manifest declaration and the vulnerable query.
+pub fn provider_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    let providers: Vec<_> = manifest.get_components()
+        .iter()
+        .filter(|c| c.get_kind() == ComponentKind::Provider && c.is_enabled())
+        .collect();
+    if providers.is_empty() {
+        return;
+    }
+    if config.is_verbose() {
+        println!("Checking the content providers of the application.");
+    }
+
+    let sql_regex = Regex::new(QUERY_SQL_REGEX).unwrap();
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+
+    for provider in providers {
+        let read_permission = provider.get_permission()
+            .or(provider.get_read_permission())
+            .or(manifest.get_application_permission());
+        let write_permission = provider.get_permission()
+            .or(provider.get_write_permission())
+            .or(manifest.get_application_permission());
+
+        if provider.is_exported() && (read_permission.is_some() || write_permission.is_some()) {
+            if read_permission.is_none() {
+                add_manifest_finding(Criticity::High,
+                                     "Exported provider without read permission",
+                                     format!("The content provider {} is exported and only \
+                                              protected for writing. Any application installed \
+                                              in the device can read its data. Set \
+                                              android:readPermission or android:permission.",
+                                             provider.get_name()),
+                                     manifest,
+                                     provider,
+                                     config,
+                                     results);
+            } else if write_permission.is_none() {
+                add_manifest_finding(Criticity::High,
+                                     "Exported provider without write permission",
+                                     format!("The content provider {} is exported and only \
+                                              protected for reading. Any application installed \
+                                              in the device can modify its data. Set \
+                                              android:writePermission or android:permission.",
+                                             provider.get_name()),
+                                     manifest,
+                                     provider,
+                                     config,
+                                     results);
+            }
+        }
+
+        if provider.grants_uri_permissions() {
+            let broad_paths: Vec<_> = provider.get_uri_permission_paths()
+                .iter()
+                .filter(|p| is_broad_path(p))
+                .cloned()
+                .collect();
+            if provider.get_uri_permission_paths().is_empty() || !broad_paths.is_empty() {
+                add_manifest_finding(Criticity::Medium,
+                                     "Broad URI permission grants",
+                                     format!("The content provider {} can grant temporary \
+                                              permissions {}. If a URI received from another \
+                                              application is granted back without validation, \
+                                              any data of the provider could be accessed. Limit \
+                                              the grants to specific paths with \
+                                              <grant-uri-permission> elements.",
+                                             provider.get_name(),
+                                             if broad_paths.is_empty() {
+                                                 String::from("for any of its URIs")
+                                             } else {
+                                                 format!("for the broad paths {}",
+                                                         broad_paths.join(", "))
+                                             }),
+                                     manifest,
+                                     provider,
+                                     config,
+                                     results);
+            }
+        }
+
+        if provider.is_exported() {
+            check_sql_injection(provider,
+                                read_permission.is_some() && write_permission.is_some(),
+                                &dist_folder,
+                                &sql_regex,
+                                config,
+                                results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The content providers were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Content providers analyzed.");
+    }
+}
+
+/// Checks if the URI permission path covers all, or almost all, the URIs of the provider
+fn is_broad_path(path: &str) -> bool {
+    match path {
+        "" | "*" | "/" | "/*" | ".*" | "/.*" => true,
+        _ => false,
+    }
+}
+
+/// Gets the path of the decompiled source file of the class
+fn get_class_file(dist_folder: &Path, class_name: &str) -> PathBuf {
+    dist_folder.join(format!("classes/{}.java", class_name.replace('.', "/")))
+}
+
+/// Finds the first SQL query built concatenating data received from the client, returning its
+/// position in the code
+fn find_sql_concatenation(code: &str, sql_regex: &Regex) -> Option<(usize, usize)> {
+    if !SQL_MARKERS.iter().any(|m| code.contains(m)) {
+        return None;
+    }
+    sql_regex.find(code)
+}
+
+/// Checks the decompiled code of the exported provider for SQL injections
+fn check_sql_injection(provider: &Component,
+                       protected: bool,
+                       dist_folder: &Path,
+                       sql_regex: &Regex,
+                       config: &Config,
+                       results: &mut Results) {
+    let path = get_class_file(dist_folder, provider.get_name());
+    let code = match read_text_file(&path) {
+        Ok(Some(c)) => c,
+        Ok(None) => return,
+        Err(e) => {
+            if config.is_verbose() {
+                print_warning(format!("The source code of the content provider {} could not \
+                                       be read. It will not be checked for SQL injections. \
+                                       More info: {}",
+                                      provider.get_name(),
+                                      e),
+                              config.is_verbose());
+            }
+            return;
+        }
+    };
+
+    if let Some((s, e)) = find_sql_concatenation(&code, sql_regex) {
+        let start_line = get_line_for(s, &code);
+        let end_line = get_line_for(e, &code);
+        let criticity = if protected {
+            Criticity::Medium
+        } else {
+            Criticity::High
+        };
+        let description = format!("The content provider {}, exported in the \
+                                   AndroidManifest.xml file{}, builds SQL queries \
+                                   concatenating the selection or the URI received from its \
+                                   clients. {} could read or modify any data in its database \
+                                   with an SQL injection. Use selection arguments or \
+                                   SQLiteQueryBuilder with strict mode instead.",
+                                  provider.get_name(),
+                                  match provider.get_line() {
+                                      Some(l) => format!(" at line {}", l + 1),
+                                      None => String::new(),
+                                  },
+                                  if protected {
+                                      "Applications holding the provider permissions"
+                                  } else {
+                                      "Any application installed in the device"
+                                  });
+
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), criticity);
+        }
+        let vuln = Vulnerability::new(criticity,
+                                      String::from("SQL injection in exported content provider"),
+                                      description,
+                                      Some(path.strip_prefix(dist_folder).unwrap()),
+                                      Some(start_line),
+                                      Some(end_line),
+                                      Some(get_code(&code, start_line, end_line)));
+        results.add_vulnerability(vuln);
+    }
+}
+
+/// Adds a finding for the manifest declaration of the provider
+fn add_manifest_finding(criticity: Criticity,
+                        name: &str,
+                        description: String,
+                        manifest: &Manifest,
+                        provider: &Component,
+                        config: &Config,
+                        results: &mut Results) {
+    let line = provider.get_line();
+    let code = match line {
+        Some(l) => Some(get_code(manifest.get_code(), l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(name),
+                                  description,
+                                  Some("AndroidManifest.xml"),
+                                  line,
+                                  line,
+                                  code);
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use regex::Regex;
+    use super::{QUERY_SQL_REGEX, is_broad_path, get_class_file, find_sql_concatenation};
+
+    #[test]
+    fn it_is_broad_path() {
+        assert!(is_broad_path("/"));
+        assert!(is_broad_path("/*"));
+        assert!(is_broad_path(".*"));
+        assert!(!is_broad_path("/shared/*"));
+        assert!(!is_broad_path("/images/avatar.png"));
+    }
+
+    #[test]
+    fn it_get_class_file() {
+        assert_eq!(get_class_file(Path::new("dist/com.example"), "com.example.NotesProvider"),
+                   PathBuf::from("dist/com.example/classes/com/example/NotesProvider.java"));
+    }
+
+    #[test]
+    fn it_find_sql_concatenation() {
+        let regex = Regex::new(QUERY_SQL_REGEX).unwrap();
+
+        let vulnerable = "SQLiteDatabase db = this.helper.getReadableDatabase();\n\
+                          return db.rawQuery(\"SELECT * FROM notes WHERE id = \" + \
+                          uri.getLastPathSegment(), null);";
+        assert!(find_sql_concatenation(vulnerable, &regex).is_some());
+
+        let selection = "SQLiteDatabase db = this.helper.getWritableDatabase();\n\
+                         db.delete(\"notes\", \"owner = 1 AND \" + selection, null);";
+        assert!(find_sql_concatenation(selection, &regex).is_some());
+
+        let safe = "SQLiteDatabase db = this.helper.getReadableDatabase();\n\
+                    return db.query(\"notes\", projection, \"id = ?\", new String[] { id }, \
+                    null, null, sortOrder);";
+        assert!(find_sql_concatenation(safe, &regex).is_none());
+
+        let no_sql = "return this.cache.query(\"key = \" + selection);";
+        assert!(find_sql_concatenation(no_sql, &regex).is_none());
+    }
+}
@@ -0,0 +1,495 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, Component, ComponentKind};
+use super::intents::is_unprotected;
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, split_methods, parse_integer,
+                  get_java_files, get_class_name, get_enclosing_method, describe_location};
+use super::structural::{get_closing_index, split_arguments};
+use super::read_text_file;
+
+/// Order of the broadcast receivers section in the report, after the components
+const SECTION_ORDER: u32 = 54;
+
+/// Value of `Context.RECEIVER_NOT_EXPORTED`
+const RECEIVER_NOT_EXPORTED: u64 = 0x4;
+
+/// Broadcast actions that only the system can send, declared as `<protected-broadcast>` by the
+/// platform
+const PROTECTED_BROADCASTS: [&'static str; 26] =
+    ["android.intent.action.BOOT_COMPLETED",
+     "android.intent.action.LOCKED_BOOT_COMPLETED",
+     "android.intent.action.REBOOT",
+     "android.intent.action.ACTION_SHUTDOWN",
+     "android.intent.action.PACKAGE_ADDED",
+     "android.intent.action.PACKAGE_REMOVED",
+     "android.intent.action.PACKAGE_REPLACED",
+     "android.intent.action.PACKAGE_CHANGED",
+     "android.intent.action.PACKAGE_FULLY_REMOVED",
+     "android.intent.action.MY_PACKAGE_REPLACED",
+     "android.intent.action.TIMEZONE_CHANGED",
+     "android.intent.action.TIME_SET",
+     "android.intent.action.DATE_CHANGED",
+     "android.intent.action.LOCALE_CHANGED",
+     "android.intent.action.CONFIGURATION_CHANGED",
+     "android.intent.action.BATTERY_LOW",
+     "android.intent.action.BATTERY_OKAY",
+     "android.intent.action.ACTION_POWER_CONNECTED",
+     "android.intent.action.ACTION_POWER_DISCONNECTED",
+     "android.intent.action.SCREEN_ON",
+     "android.intent.action.SCREEN_OFF",
+     "android.intent.action.USER_PRESENT",
+     "android.intent.action.AIRPLANE_MODE",
+     "android.intent.action.NEW_OUTGOING_CALL",
+     "android.intent.action.PHONE_STATE",
+     "android.provider.Telephony.SMS_RECEIVED"];
+
+/// Calls that read the extras of a received Intent
+const EXTRA_GETTERS: [&'static str; 13] = ["getExtras",
+                                           "getStringExtra",
+                                           "getCharSequenceExtra",
+                                           "getIntExtra",
+                                           "getLongExtra",
+                                           "getBooleanExtra",
+                                           "getByteArrayExtra",
+                                           "getStringArrayExtra",
+                                           "getStringArrayListExtra",
+                                           "getBundleExtra",
+                                           "getSerializableExtra",
+                                           "getParcelableExtra",
+                                           "getParcelableArrayListExtra"];
+
+/// Calls that check the action or the sender of a received Intent
+const SENDER_CHECKS: [&'static str; 3] = ["getAction", "getSentFromPackage", "getSentFromUid"];
+
+/// Calls that send or remove sticky broadcasts
+const STICKY_CALLS: [&'static str; 5] = ["sendStickyBroadcast",
+                                         "sendStickyBroadcastAsUser",
+                                         "sendStickyOrderedBroadcast",
+                                         "sendStickyOrderedBroadcastAsUser",
+                                         "removeStickyBroadcast"];
+
+/// Evidence found in the `onReceive()` method of a broadcast receiver
+#[derive(Debug, PartialEq, Eq)]
+struct ReceiverCode {
+    /// Index of the first read of an extra of the received Intent, if any
+    extra: Option<usize>,
+    /// If the action or the sender of the received Intent is checked
+    checks_sender: bool,
+}
+
+/// Looks for the reads of Intent extras and the checks of the action in the `onReceive()`
+/// method of a receiver, if the code has it
+fn analyze_on_receive(code: &str) -> Option<ReceiverCode> {
+    let tokens = tokenize(code);
+    let methods = split_methods(code, &tokens);
+    let method = match methods.iter().find(|m| m.get_name() == "onReceive") {
+        Some(m) => m,
+        None => return None,
+    };
+    let mut evidence = ReceiverCode {
+        extra: None,
+        checks_sender: false,
+    };
+    for statement in method.get_statements() {
+        for (i, token) in statement.iter().enumerate() {
+            if !is_call(statement, i) {
+                continue;
+            }
+            if evidence.extra.is_none() && EXTRA_GETTERS.contains(&token.get_text()) {
+                evidence.extra = Some(token.get_start());
+            } else if SENDER_CHECKS.contains(&token.get_text()) {
+                evidence.checks_sender = true;
+            }
+        }
+    }
+    Some(evidence)
+}
+
+/// Kind of broadcast call found in the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BroadcastCall {
+    /// Receiver registered without a permission and without `RECEIVER_NOT_EXPORTED`
+    UnprotectedRegistration,
+    /// Sticky broadcast sent or removed
+    Sticky,
+}
+
+/// Finds the receivers registered in the code that any application can send broadcasts to,
+/// and the sticky broadcasts, with the index of their calls
+fn find_broadcast_calls(code: &str) -> Vec<(BroadcastCall, usize)> {
+    let tokens = tokenize(code);
+    let mut calls = Vec::new();
+    for method in split_methods(code, &tokens) {
+        for statement in method.get_statements() {
+            for (i, token) in statement.iter().enumerate() {
+                if !is_call(statement, i) {
+                    continue;
+                }
+                if STICKY_CALLS.contains(&token.get_text()) {
+                    calls.push((BroadcastCall::Sticky, token.get_start()));
+                } else if token.get_text() == "registerReceiver" &&
+                          !statement.iter().any(|t| t.get_text() == "LocalBroadcastManager") {
+                    let close = match get_closing_index(statement, i + 1) {
+                        Some(c) => c,
+                        None => continue,
+                    };
+                    if is_unprotected_registration(&split_arguments(&statement[i + 2..close])) {
+                        calls.push((BroadcastCall::UnprotectedRegistration, token.get_start()));
+                    }
+                }
+            }
+        }
+    }
+    calls
+}
+
+/// Checks if the arguments of a `registerReceiver()` call allow any application to send
+/// broadcasts to the receiver
+///
+/// The permission is the third argument of the calls with a scheduler, and the flags the last
+/// argument of the calls with three or five arguments.
+fn is_unprotected_registration(arguments: &[&[Token]]) -> bool {
+    let permission = match arguments.len() {
+        2 | 3 => None,
+        4 | 5 => Some(arguments[2]),
+        _ => return false,
+    };
+    if let Some(permission) = permission {
+        if !(permission.len() == 1 && permission[0].get_text() == "null") {
+            return false;
+        }
+    }
+    if arguments.len() == 3 || arguments.len() == 5 {
+        let flags = arguments[arguments.len() - 1];
+        let mut value = 0;
+        for token in flags {
+            match token.get_text() {
+                "RECEIVER_NOT_EXPORTED" => return false,
+                text if token.get_kind() == TokenKind::Literal => {
+                    value |= parse_integer(text).unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+        if value & RECEIVER_NOT_EXPORTED != 0 {
+            return false;
+        }
+    }
+    true
+}
+
+/// Gets the actions of the intent filters of a receiver that any application can send
+fn get_unprotected_actions(receiver: &Component) -> Vec<&str> {
+    let mut actions: Vec<&str> = receiver.get_intent_filters()
+        .iter()
+        .flat_map(|filter| filter.get_actions().iter().map(|a| a.as_str()))
+        .filter(|action| !PROTECTED_BROADCASTS.contains(action))
+        .collect();
+    actions.sort();
+    actions.dedup();
+    actions
+}
+
+/// Analyzes the broadcast receivers declared in the manifest, with the code of their
+/// `onReceive()` methods, and the broadcasts registered and sent in the code
+///
+/// Exported receivers that are not protected by a signature permission and read the extras of
+/// the received Intents are reported if they listen to actions that are not protected
+/// broadcasts, or if they don't check the action, since any application can send them an
+/// explicit Intent. Receivers registered in the code without a permission, and sticky
+/// broadcasts, that any application can read and replace, are reported too.
+pub fn receiver_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Analyzing the broadcast receivers.");
+    }
+
+    let mut receivers = BTreeMap::new();
+    if let Some(manifest) = manifest {
+        for component in manifest.get_components() {
+            if component.get_kind() == ComponentKind::Receiver && component.is_exported() &&
+               component.is_enabled() {
+                let _ = receivers.insert(String::from(component.get_name()), component);
+            }
+        }
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the broadcast \
+                                   receiver analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut evidences = BTreeMap::new();
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+
+        if code.contains("registerReceiver") || code.contains("Sticky") {
+            for (call, index) in find_broadcast_calls(&code) {
+                add_call_finding(call, index, &code, &relative, &class, config, results);
+            }
+        }
+        if receivers.contains_key(&class) {
+            if let Some(evidence) = analyze_on_receive(&code) {
+                let _ = evidences.insert(class, (evidence, relative, code));
+            }
+        }
+    }
+
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for (name, receiver) in &receivers {
+        let protection = manifest.get_component_protection(receiver);
+        let unprotected = is_unprotected(manifest, receiver);
+        let actions = get_unprotected_actions(receiver);
+        let evidence = evidences.get(name);
+
+        if let Some(&(ref evidence, ref path, ref code)) = evidence {
+            if let Some(extra) = evidence.extra {
+                if unprotected && (!actions.is_empty() || !evidence.checks_sender) {
+                    add_receiver_finding(name,
+                                         &actions,
+                                         evidence.checks_sender,
+                                         extra,
+                                         code,
+                                         path,
+                                         config,
+                                         results);
+                }
+            }
+        }
+
+        let reads_extras = evidence.map(|&(ref e, _, _)| e.extra.is_some());
+        let checks_sender = evidence.map(|&(ref e, _, _)| e.checks_sender);
+        rows.push(vec![Results::html_escape(name),
+                       Results::html_escape(protection.unwrap_or("")),
+                       actions.iter()
+                           .map(|a| Results::html_escape(a))
+                           .collect::<Vec<_>>()
+                           .join("<br>"),
+                       reads_extras.map_or(String::new(),
+                                           |r| String::from(Results::html_yes_no(r))),
+                       checks_sender.map_or(String::new(),
+                                            |c| String::from(Results::html_yes_no(c)))]);
+        let mut entry = BTreeMap::new();
+        let _ = entry.insert("receiver", name.clone());
+        let _ = entry.insert("permission", String::from(protection.unwrap_or("")));
+        let _ = entry.insert("unprotected_actions", actions.join(", "));
+        let _ = entry.insert("reads_extras",
+                             reads_extras.map_or(String::new(), |r| r.to_string()));
+        let _ = entry.insert("checks_action",
+                             checks_sender.map_or(String::new(), |c| c.to_string()));
+        data.push(entry);
+    }
+
+    if !rows.is_empty() {
+        let mut section = ReportSection::new("receivers", "Broadcast receivers", SECTION_ORDER);
+        section.set_data(&data);
+        section.set_html_table(&["Receiver", "Permission", "Unprotected actions",
+                                 "Reads extras", "Checks action"],
+                               &rows);
+        results.add_section(section);
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The broadcast receivers were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Broadcast receivers analyzed.");
+    }
+}
+
+/// Adds a finding for an exported receiver that trusts the extras of Intents that any
+/// application can send
+fn add_receiver_finding(receiver: &str,
+                        actions: &[&str],
+                        checks_sender: bool,
+                        extra: usize,
+                        code: &str,
+                        path: &Path,
+                        config: &Config,
+                        results: &mut Results) {
+    let reason = if actions.is_empty() {
+        String::from("it does not check the action of the received Intent, so any \
+                      application can send it an explicit Intent")
+    } else if checks_sender {
+        format!("it listens to actions that are not protected broadcasts, so any application \
+                 can send them: {}",
+                actions.join(", "))
+    } else {
+        format!("it listens to actions that are not protected broadcasts, so any application \
+                 can send them, and it does not check the action of the received Intent: {}",
+                actions.join(", "))
+    };
+    let description = format!("The exported receiver {} is not protected by a signature \
+                               permission and reads the extras of the received Intent in {}, \
+                               but {}. A malicious application could send it spoofed \
+                               broadcasts with its own extras.",
+                              receiver,
+                              describe_location(receiver, get_enclosing_method(code, extra)),
+                              reason);
+    let line = get_line_for(extra, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::Medium);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::Medium,
+                                      String::from("Spoofable broadcast receiver"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-925")],
+                                        vec![String::from("MASVS-PLATFORM-1")],
+                                        vec![String::from("M1")],
+                                        Vec::new(),
+                                        Some(String::from("Protect the receiver with a \
+                                                           signature permission, or check the \
+                                                           action and validate the extras \
+                                                           before using them."))));
+    results.add_vulnerability(vuln);
+}
+
+/// Adds a finding for a receiver registered without a permission or a sticky broadcast
+fn add_call_finding(call: BroadcastCall,
+                    index: usize,
+                    code: &str,
+                    path: &Path,
+                    class: &str,
+                    config: &Config,
+                    results: &mut Results) {
+    let location = describe_location(class, get_enclosing_method(code, index));
+    let (criticity, name, description, cwe, remediation) = match call {
+        BroadcastCall::UnprotectedRegistration => {
+            (Criticity::Low,
+             "Receiver registered without permission",
+             format!("A broadcast receiver is registered in {} without a permission and \
+                      without the RECEIVER_NOT_EXPORTED flag, so any application can send \
+                      it broadcasts.",
+                     location),
+             "CWE-925",
+             "Register the receiver with the RECEIVER_NOT_EXPORTED flag, or with a signature \
+              permission that the senders must hold.")
+        }
+        BroadcastCall::Sticky => {
+            (Criticity::Low,
+             "Sticky broadcast",
+             format!("A sticky broadcast is used in {}. Sticky broadcasts are deprecated and \
+                      not protected: any application can read them, and replace or remove \
+                      them with its own data.",
+                     location),
+             "CWE-927",
+             "Use non-sticky broadcasts, or another mechanism to keep the state.")
+        }
+    };
+    let line = get_line_for(index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(name),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(cwe)],
+                                        vec![String::from("MASVS-PLATFORM-1")],
+                                        vec![String::from("M1")],
+                                        Vec::new(),
+                                        Some(String::from(remediation))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReceiverCode, BroadcastCall, analyze_on_receive, find_broadcast_calls};
+
+    #[test]
+    fn it_analyze_on_receive() {
+        let code = "public class TokenReceiver extends BroadcastReceiver {\n\
+                    public void onReceive(Context context, Intent intent) {\n\
+                    String token = intent.getStringExtra(\"token\");\n\
+                    save(context, token);\n\
+                    }\n\
+                    }";
+        let extra = code.find("getStringExtra").unwrap();
+        assert_eq!(analyze_on_receive(code),
+                   Some(ReceiverCode {
+                       extra: Some(extra),
+                       checks_sender: false,
+                   }));
+
+        let code = "public class BootReceiver extends BroadcastReceiver {\n\
+                    public void onReceive(Context context, Intent intent) {\n\
+                    if (\"android.intent.action.BOOT_COMPLETED\".equals(intent.getAction())) {\n\
+                    schedule(context);\n\
+                    }\n\
+                    }\n\
+                    }";
+        assert_eq!(analyze_on_receive(code),
+                   Some(ReceiverCode {
+                       extra: None,
+                       checks_sender: true,
+                   }));
+        assert_eq!(analyze_on_receive("public class Helper {\nvoid run() {\n}\n}"), None);
+    }
+
+    #[test]
+    fn it_find_broadcast_calls() {
+        let code = "public class Main {\n\
+                    void register() {\n\
+                    registerReceiver(receiver, filter);\n\
+                    registerReceiver(receiver, filter, 4);\n\
+                    registerReceiver(receiver, filter, Context.RECEIVER_EXPORTED);\n\
+                    registerReceiver(receiver, filter, \"com.example.SEND\", null);\n\
+                    registerReceiver(receiver, filter, null, handler);\n\
+                    LocalBroadcastManager.getInstance(this).registerReceiver(receiver, filter);\n\
+                    sendStickyBroadcast(intent);\n\
+                    }\n\
+                    }";
+        let calls: Vec<_> = find_broadcast_calls(code).into_iter().map(|(c, _)| c).collect();
+        assert_eq!(calls,
+                   vec![BroadcastCall::UnprotectedRegistration,
+                        BroadcastCall::UnprotectedRegistration,
+                        BroadcastCall::UnprotectedRegistration,
+                        BroadcastCall::Sticky]);
+    }
+}
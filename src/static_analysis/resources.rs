@@ -0,0 +1,838 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::split_words;
+use super::endpoints::{get_host, is_private_ip};
+use super::logging::get_names_regex;
+use super::apk_info::get_locale;
+use super::secrets::is_secret_candidate;
+use super::{add_files_to_vec, read_text_file};
+
+/// Order of the resources section in the report
+const SECTION_ORDER: u32 = 210;
+
+/// Maximum size of the SQLite databases read to find their tables
+const MAX_DATABASE_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Header of SQLite 3 database files
+const SQLITE_HEADER: &'static [u8] = b"SQLite format 3\0";
+
+/// Minimum length of a string resource with a sensitive name to be considered a credential
+const MIN_STRING_CREDENTIAL_LENGTH: usize = 8;
+
+/// Labels of the host names of development and testing servers
+const DEBUG_HOST_LABELS: [&'static str; 11] = ["dev", "develop", "development", "staging",
+                                               "stage", "test", "testing", "debug", "qa",
+                                               "uat", "sandbox"];
+
+/// Path segments and host name labels of administration and internal consoles
+const ADMIN_LABELS: [&'static str; 13] = ["admin", "administrator", "wp-admin", "phpmyadmin",
+                                          "cpanel", "backoffice", "backend", "console",
+                                          "internal", "manage", "management", "actuator",
+                                          "backdoor"];
+
+/// Configuration values that are placeholders or flags instead of credentials
+const PLACEHOLDER_VALUES: [&'static str; 5] = ["null", "none", "true", "false", "undefined"];
+
+/// First words of the table definitions that are constraints instead of columns
+const TABLE_CONSTRAINTS: [&'static str; 5] = ["primary", "unique", "foreign", "check",
+                                              "constraint"];
+
+/// Kind of resource or asset file, by its location and extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceKind {
+    /// XML files of the `res/values*` folders, such as `strings.xml`
+    Values,
+    /// SQLite databases shipped with the application
+    Database,
+    /// JavaScript and HTML files, usually loaded in WebViews
+    Web,
+    /// JSON, properties, YAML and INI configuration files
+    Config,
+}
+
+impl ResourceKind {
+    /// Gets the kind of a file, from its path relative to the dist folder
+    fn from_path(relative: &Path) -> Option<ResourceKind> {
+        if !relative.starts_with("res") && !relative.starts_with("assets") {
+            return None;
+        }
+        let extension = match relative.extension() {
+            Some(e) => e.to_string_lossy().to_lowercase(),
+            None => return None,
+        };
+        match extension.as_str() {
+            "xml" => {
+                let in_values = relative.parent()
+                    .and_then(|p| p.file_name())
+                    .map_or(false, |f| f.to_string_lossy().starts_with("values"));
+                if in_values && relative.starts_with("res") {
+                    Some(ResourceKind::Values)
+                } else {
+                    None
+                }
+            }
+            "db" | "db3" | "sqlite" | "sqlite3" => Some(ResourceKind::Database),
+            "js" | "html" | "htm" => Some(ResourceKind::Web),
+            "json" | "properties" | "yaml" | "yml" | "ini" | "cfg" | "conf" => {
+                Some(ResourceKind::Config)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Issue found in a resource or asset file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceIssue {
+    /// Password, token or key stored in a resource or configuration file
+    Credential,
+    /// URL of a development, testing or local server
+    DebugEndpoint,
+    /// URL of an administration or internal console
+    AdminEndpoint,
+    /// Table of a shipped database with sensitive columns
+    SensitiveTable,
+    /// JavaScript code evaluated from strings
+    CodeEvaluation,
+    /// HTML written to the document from JavaScript
+    HtmlInjection,
+    /// Script loaded over a connection without TLS
+    CleartextScript,
+}
+
+impl ResourceIssue {
+    fn get_label(&self) -> &'static str {
+        match *self {
+            ResourceIssue::Credential => "Credential in resource file",
+            ResourceIssue::DebugEndpoint => "Debug endpoint in resource file",
+            ResourceIssue::AdminEndpoint => "Administration URL in resource file",
+            ResourceIssue::SensitiveTable => "Sensitive table in shipped database",
+            ResourceIssue::CodeEvaluation => "Dynamic code evaluation in web asset",
+            ResourceIssue::HtmlInjection => "HTML injection sink in web asset",
+            ResourceIssue::CleartextScript => "Script loaded without TLS in web asset",
+        }
+    }
+
+    fn get_criticity(&self) -> Criticity {
+        match *self {
+            ResourceIssue::Credential => Criticity::High,
+            ResourceIssue::SensitiveTable |
+            ResourceIssue::CleartextScript |
+            ResourceIssue::AdminEndpoint => Criticity::Medium,
+            ResourceIssue::DebugEndpoint |
+            ResourceIssue::CodeEvaluation |
+            ResourceIssue::HtmlInjection => Criticity::Low,
+        }
+    }
+
+    fn get_cwe(&self) -> &'static str {
+        match *self {
+            ResourceIssue::Credential => "CWE-798",
+            ResourceIssue::DebugEndpoint => "CWE-489",
+            ResourceIssue::AdminEndpoint => "CWE-912",
+            ResourceIssue::SensitiveTable => "CWE-312",
+            ResourceIssue::CodeEvaluation => "CWE-95",
+            ResourceIssue::HtmlInjection => "CWE-79",
+            ResourceIssue::CleartextScript => "CWE-319",
+        }
+    }
+
+    fn get_masvs(&self) -> &'static str {
+        match *self {
+            ResourceIssue::Credential | ResourceIssue::SensitiveTable => "MASVS-STORAGE-1",
+            ResourceIssue::DebugEndpoint | ResourceIssue::AdminEndpoint => "MASVS-CODE-4",
+            ResourceIssue::CodeEvaluation | ResourceIssue::HtmlInjection => "MASVS-PLATFORM-2",
+            ResourceIssue::CleartextScript => "MASVS-NETWORK-1",
+        }
+    }
+
+    fn get_description(&self) -> &'static str {
+        match *self {
+            ResourceIssue::Credential => {
+                "Anyone with access to the application package can extract it, so it must be \
+                 considered compromised and revoked."
+            }
+            ResourceIssue::DebugEndpoint => {
+                "Development and testing servers are usually less protected than the production \
+                 ones, and they should not be referenced by release builds."
+            }
+            ResourceIssue::AdminEndpoint => {
+                "Administration and internal consoles give access to functionality that users \
+                 should not have, and their addresses are easily found in the application \
+                 package, so they must require authentication on the server and should not be \
+                 referenced by the application."
+            }
+            ResourceIssue::SensitiveTable => {
+                "Databases shipped in the package can be extracted by anyone, and once copied \
+                 to the device they are stored without encryption, unless a library such as \
+                 SQLCipher is used."
+            }
+            ResourceIssue::CodeEvaluation => {
+                "If any part of the evaluated string comes from a web page or from the URL, an \
+                 attacker can run JavaScript in the WebView, with access to its JavaScript \
+                 interfaces."
+            }
+            ResourceIssue::HtmlInjection => {
+                "If the HTML is built with data from a web page or from the URL, an attacker can \
+                 inject scripts in the WebView, with access to its JavaScript interfaces."
+            }
+            ResourceIssue::CleartextScript => {
+                "An attacker in the network can replace the script, and run JavaScript in the \
+                 WebView with access to its JavaScript interfaces."
+            }
+        }
+    }
+}
+
+/// Issue found in a resource or asset file
+#[derive(Debug, PartialEq, Eq)]
+struct ResourceFinding {
+    issue: ResourceIssue,
+    /// Line of the issue in the file
+    line: usize,
+    /// Key, URL, table or call of the issue
+    detail: String,
+    /// Affected code, when the file is not a text file
+    code: Option<String>,
+}
+
+impl ResourceFinding {
+    fn new(issue: ResourceIssue, line: usize, detail: String) -> ResourceFinding {
+        ResourceFinding {
+            issue: issue,
+            line: line,
+            detail: detail,
+            code: None,
+        }
+    }
+}
+
+/// Regular expressions to find the issues in the resources
+struct Scanner {
+    names: Regex,
+    string_resource: Regex,
+    json_entry: Regex,
+    config_entry: Regex,
+    url: Regex,
+    create_table: Regex,
+    web_patterns: Vec<(ResourceIssue, Regex)>,
+}
+
+impl Scanner {
+    fn new(config: &Config) -> Scanner {
+        let web_patterns = vec![(ResourceIssue::CodeEvaluation,
+                                 r"\b(?:eval|Function)\s*\("),
+                                (ResourceIssue::CodeEvaluation,
+                                 r#"\bset(?:Timeout|Interval)\s*\(\s*["']"#),
+                                (ResourceIssue::HtmlInjection,
+                                 r"\.(?:innerHTML|outerHTML)\s*=[^=]"),
+                                (ResourceIssue::HtmlInjection,
+                                 r"\b(?:document\s*\.\s*write(?:ln)?|insertAdjacentHTML)\s*\("),
+                                (ResourceIssue::CleartextScript,
+                                 r#"(?i)<script[^>]+src\s*=\s*["']?http://"#)];
+        Scanner {
+            names: get_names_regex(config.get_logging_config().get_sensitive_names()),
+            string_resource: Regex::new(r#"<string\s+name="([^"]+)"[^>]*>([^<]*)</string>"#)
+                .unwrap(),
+            json_entry: Regex::new(r#""([\w.\-]+)"\s*:\s*"([^"]*)""#).unwrap(),
+            config_entry: Regex::new(r"(?m)^[ \t]*([\w.\-]+)[ \t]*[=:][ \t]*([^\r\n]*)$")
+                .unwrap(),
+            url: Regex::new(r#"(?i)\bhttps?://[^\s"'<>\\]+"#).unwrap(),
+            create_table: Regex::new(concat!(r"(?i)CREATE\s+TABLE\s+(?:IF\s+NOT\s+EXISTS\s+)?",
+                                             r#"["`\[]?(\w+)["`\]]?\s*\("#))
+                .unwrap(),
+            web_patterns: web_patterns.into_iter()
+                .map(|(issue, pattern)| (issue, Regex::new(pattern).unwrap()))
+                .collect(),
+        }
+    }
+
+    /// Checks if the name of a key or a column is sensitive
+    fn is_sensitive(&self, name: &str) -> bool {
+        self.names.is_match(&split_words(name).join("_"))
+    }
+
+    /// Finds the credentials stored in the entries of a values or configuration file
+    ///
+    /// String resources are only considered credentials if they look like random keys, since
+    /// most of them are texts of the user interface, such as the label of a password field.
+    fn find_credentials(&self, code: &str, kind: ResourceKind) -> Vec<ResourceFinding> {
+        let regexes = match kind {
+            ResourceKind::Values => vec![&self.string_resource],
+            ResourceKind::Config => vec![&self.json_entry, &self.config_entry],
+            _ => return Vec::new(),
+        };
+        let mut findings: Vec<ResourceFinding> = Vec::new();
+        for regex in regexes {
+            for caps in regex.captures_iter(code) {
+                let key = caps.at(1).unwrap();
+                let value = caps.at(2)
+                    .unwrap()
+                    .trim()
+                    .trim_matches(|c: char| c == '"' || c == '\'');
+                let (start, _) = caps.pos(0).unwrap();
+                let line = get_line_for(start, code);
+                let credential = if kind == ResourceKind::Values {
+                    is_secret_candidate(value, MIN_STRING_CREDENTIAL_LENGTH)
+                } else {
+                    is_credential_value(value)
+                };
+                if !credential || !self.is_sensitive(key) ||
+                   findings.iter().any(|f| f.line == line) {
+                    continue;
+                }
+                findings.push(ResourceFinding::new(ResourceIssue::Credential,
+                                                   line,
+                                                   String::from(key)));
+            }
+        }
+        findings
+    }
+
+    /// Finds the URLs of development, testing and local servers
+    fn find_debug_endpoints(&self, code: &str) -> Vec<ResourceFinding> {
+        self.url
+            .find_iter(code)
+            .filter(|&(s, e)| is_debug_url(&code[s..e]))
+            .map(|(s, e)| {
+                ResourceFinding::new(ResourceIssue::DebugEndpoint,
+                                     get_line_for(s, code),
+                                     String::from(&code[s..e]))
+            })
+            .collect()
+    }
+
+    /// Finds the URLs of administration and internal consoles, that are not debug endpoints
+    fn find_admin_endpoints(&self, code: &str) -> Vec<ResourceFinding> {
+        self.url
+            .find_iter(code)
+            .filter(|&(s, e)| !is_debug_url(&code[s..e]) && is_admin_url(&code[s..e]))
+            .map(|(s, e)| {
+                ResourceFinding::new(ResourceIssue::AdminEndpoint,
+                                     get_line_for(s, code),
+                                     String::from(&code[s..e]))
+            })
+            .collect()
+    }
+
+    /// Gets the values of the string resources of a values file, by their names
+    fn get_strings(&self, code: &str) -> BTreeMap<String, String> {
+        self.string_resource
+            .captures_iter(code)
+            .map(|caps| (String::from(caps.at(1).unwrap()), String::from(caps.at(2).unwrap())))
+            .collect()
+    }
+
+    /// Checks if the string resource in the given line of a localized values file is missing
+    /// from the default resources or has a different value in them
+    fn is_locale_only(&self, code: &str, line: usize, defaults: &BTreeMap<String, String>) -> bool {
+        let line = match code.lines().nth(line) {
+            Some(l) => l,
+            None => return false,
+        };
+        match self.string_resource.captures(line) {
+            Some(caps) => {
+                defaults.get(caps.at(1).unwrap()).map(String::as_str) != caps.at(2)
+            }
+            None => false,
+        }
+    }
+
+    /// Finds the tables with sensitive names or columns in the schema of a SQLite database
+    ///
+    /// The schema is stored as text in the database, so the `CREATE TABLE` statements can be
+    /// found without opening it.
+    fn find_sensitive_tables(&self, database: &str) -> Vec<ResourceFinding> {
+        let mut findings: Vec<ResourceFinding> = Vec::new();
+        for caps in self.create_table.captures_iter(database) {
+            let table = caps.at(1).unwrap();
+            let (start, open) = caps.pos(0).unwrap();
+            let close = match get_statement_end(database, open - 1) {
+                Some(c) => c,
+                None => continue,
+            };
+            let columns = get_columns(&database[open..close]);
+            let sensitive: Vec<_> = columns.iter()
+                .filter(|c| self.is_sensitive(c))
+                .cloned()
+                .collect();
+            if sensitive.is_empty() && !self.is_sensitive(table) {
+                continue;
+            }
+            let detail = if sensitive.is_empty() {
+                String::from(table)
+            } else {
+                format!("{} ({})", table, sensitive.join(", "))
+            };
+            // Old versions of the schema can remain in the free pages of the database
+            if findings.iter().any(|f| f.detail == detail) {
+                continue;
+            }
+            let mut finding = ResourceFinding::new(ResourceIssue::SensitiveTable, 0, detail);
+            finding.code = Some(format!("{}\n", &database[start..close + 1]));
+            findings.push(finding);
+        }
+        findings
+    }
+
+    /// Finds the dangerous JavaScript patterns of a web asset
+    ///
+    /// Only the first match of each pattern is reported, since minified libraries can have
+    /// hundreds of them.
+    fn find_web_issues(&self, code: &str) -> Vec<ResourceFinding> {
+        let mut findings: Vec<ResourceFinding> = Vec::new();
+        for &(issue, ref regex) in &self.web_patterns {
+            if findings.iter().any(|f| f.issue == issue) {
+                continue;
+            }
+            if let Some((s, e)) = regex.find(code) {
+                findings.push(ResourceFinding::new(issue,
+                                                   get_line_for(s, code),
+                                                   String::from(code[s..e].trim())));
+            }
+        }
+        findings
+    }
+}
+
+/// Checks if a configuration value could be a credential, instead of a placeholder, a number,
+/// a URL or a flag
+fn is_credential_value(value: &str) -> bool {
+    value.len() >= 4 && !value.contains(char::is_whitespace) && !value.starts_with("${") &&
+    !value.starts_with('@') && !value.starts_with('{') && !value.starts_with('[') &&
+    !value.contains("://") && !value.chars().all(|c| c.is_digit(10) || c == '.') &&
+    !PLACEHOLDER_VALUES.contains(&value.to_lowercase().as_str())
+}
+
+/// Checks if the URL points to a development, testing or local server
+fn is_debug_url(url: &str) -> bool {
+    let host = match get_host(url) {
+        Some(h) => h.to_lowercase(),
+        None => return false,
+    };
+    is_private_ip(&host) ||
+    host.split(|c: char| c == '.' || c == '-').any(|label| DEBUG_HOST_LABELS.contains(&label))
+}
+
+/// Checks if the URL points to an administration or internal console, by its host name or path
+fn is_admin_url(url: &str) -> bool {
+    let host = match get_host(url) {
+        Some(h) => h,
+        None => return false,
+    };
+    let path = match url.find(host) {
+        Some(i) => &url[i + host.len()..],
+        None => "",
+    };
+    let path = &path[..path.find(|c: char| c == '?' || c == '#').unwrap_or(path.len())];
+    host.to_lowercase().split('.').any(|label| ADMIN_LABELS.contains(&label)) ||
+    path.to_lowercase().split('/').any(|segment| ADMIN_LABELS.contains(&segment))
+}
+
+/// Gets the locale of a file of a localized `res/values*` folder, such as `ru` for
+/// `res/values-ru/strings.xml`
+fn get_file_locale(relative: &Path) -> Option<String> {
+    relative.parent()
+        .and_then(|p| p.file_name())
+        .and_then(|f| get_locale(&f.to_string_lossy()))
+}
+
+/// Gets the index of the parenthesis closing the table definition opened at the given index
+fn get_statement_end(database: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in database[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            '\0' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Gets the names of the columns of a table definition, without the constraints
+fn get_columns(definition: &str) -> Vec<String> {
+    let mut columns = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in definition.char_indices().chain(Some((definition.len(), ','))) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                let column = definition[start..i]
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("")
+                    .trim_matches(|c: char| c == '"' || c == '`' || c == '[' || c == ']');
+                if !column.is_empty() &&
+                   !TABLE_CONSTRAINTS.contains(&column.to_lowercase().as_str()) {
+                    columns.push(String::from(column));
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    columns
+}
+
+/// Reads the schema of a SQLite database, as text, if the file is a database
+fn read_database(path: &Path) -> Option<String> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return None,
+    };
+    if f.metadata().map(|m| m.len() > MAX_DATABASE_SIZE).unwrap_or(true) {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    if f.read_to_end(&mut bytes).is_err() || !bytes.starts_with(SQLITE_HEADER) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Scans the resources and assets of the application for credentials, debug endpoints,
+/// sensitive tables in shipped databases and dangerous JavaScript in web assets
+///
+/// The code is analyzed by the rest of the analyses, and the secrets with a known format are
+/// found by the secret detection, so this analysis focuses on the structure of each kind of
+/// file: the keys of the configuration entries, the schema of the databases and the patterns of
+/// the web assets.
+pub fn resource_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Scanning the resources and assets of the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let mut files = Vec::new();
+    if let Err(e) = add_files_to_vec(&dist_folder, &dist_folder, &mut files) {
+        print_warning(format!("An error occurred when reading files for the resource \
+                               analysis, the results might be incomplete. Error: {}",
+                              e),
+                      config.is_verbose());
+    }
+
+    let scanner = Scanner::new(config);
+
+    // String resources of the default locale, to find the ones only present in other locales
+    let mut defaults = BTreeMap::new();
+    for path in &files {
+        if path.strip_prefix(&dist_folder).ok().and_then(|r| r.parent()) ==
+           Some(Path::new("res/values")) {
+            if let Ok(Some(code)) = read_text_file(path) {
+                defaults.extend(scanner.get_strings(&code));
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let kind = match ResourceKind::from_path(&relative) {
+            Some(k) => k,
+            None => continue,
+        };
+        let locale = if kind == ResourceKind::Values {
+            get_file_locale(&relative)
+        } else {
+            None
+        };
+        let code = if kind == ResourceKind::Database {
+            match read_database(&path) {
+                Some(d) => d,
+                None => continue,
+            }
+        } else {
+            match read_text_file(&path) {
+                Ok(Some(c)) => c,
+                Ok(None) => continue,
+                Err(e) => {
+                    print_warning(format!("There was an error reading the file {}: {}",
+                                          path.display(),
+                                          e),
+                                  config.is_verbose());
+                    continue;
+                }
+            }
+        };
+
+        let findings = match kind {
+            ResourceKind::Database => scanner.find_sensitive_tables(&code),
+            ResourceKind::Web => scanner.find_web_issues(&code),
+            ResourceKind::Values | ResourceKind::Config => {
+                let mut findings = scanner.find_credentials(&code, kind);
+                findings.extend(scanner.find_debug_endpoints(&code));
+                findings.extend(scanner.find_admin_endpoints(&code));
+                findings
+            }
+        };
+        for finding in findings {
+            let locale_only = locale.is_some() &&
+                              scanner.is_locale_only(&code, finding.line, &defaults);
+            add_resource_finding(&finding,
+                                 &relative,
+                                 &code,
+                                 locale.as_ref().map(String::as_str),
+                                 locale_only,
+                                 config,
+                                 results);
+            let location = relative.to_string_lossy().into_owned();
+            let locale_label = match locale {
+                Some(ref l) if locale_only => format!("{} (only)", l),
+                Some(ref l) => l.clone(),
+                None => String::new(),
+            };
+            rows.push(vec![Results::html_escape(&location),
+                           Results::html_escape(&locale_label),
+                           String::from(finding.issue.get_label()),
+                           format!("<code>{}</code>", Results::html_escape(&finding.detail))]);
+            let mut entry = BTreeMap::new();
+            let _ = entry.insert("file", location);
+            if let Some(ref l) = locale {
+                let _ = entry.insert("locale", l.clone());
+                let _ = entry.insert("locale_only", locale_only.to_string());
+            }
+            let _ = entry.insert("issue", String::from(finding.issue.get_label()));
+            let _ = entry.insert("detail", finding.detail);
+            data.push(entry);
+        }
+    }
+
+    let mut section = ReportSection::new("resources", "Resources and assets", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["File", "Locale", "Issue", "Detail"], &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The resources and assets were scanned correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Resources and assets scanned.");
+    }
+}
+
+/// Adds a finding for an issue of a resource or asset file
+///
+/// Findings in the resources of other locales than the default one say so, since they are easily
+/// missed when reviewing the resources.
+fn add_resource_finding(finding: &ResourceFinding,
+                        path: &Path,
+                        code: &str,
+                        locale: Option<&str>,
+                        locale_only: bool,
+                        config: &Config,
+                        results: &mut Results) {
+    let issue = finding.issue;
+    let location = match locale {
+        Some(l) if locale_only => {
+            format!("{}, only in the resources of the `{}` locale", path.display(), l)
+        }
+        Some(l) => format!("{}, of the `{}` locale", path.display(), l),
+        None => format!("{}", path.display()),
+    };
+    let description = format!("{} found in {}: {}. {}",
+                              issue.get_label(),
+                              location,
+                              finding.detail,
+                              issue.get_description());
+    let criticity = issue.get_criticity();
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let snippet = match finding.code {
+        Some(ref c) => c.clone(),
+        None => get_code(code, finding.line, finding.line),
+    };
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(issue.get_label()),
+                                      description,
+                                      Some(path),
+                                      Some(finding.line),
+                                      Some(finding.line),
+                                      Some(snippet));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(issue.get_cwe())],
+                                        vec![String::from(issue.get_masvs())],
+                                        Vec::new(),
+                                        Vec::new(),
+                                        None));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use Config;
+    use std::collections::BTreeMap;
+
+    use super::{Scanner, ResourceKind, ResourceIssue, is_credential_value, is_debug_url,
+                is_admin_url, get_file_locale, get_columns};
+
+    #[test]
+    fn it_resource_kind() {
+        assert_eq!(ResourceKind::from_path(Path::new("res/values/strings.xml")),
+                   Some(ResourceKind::Values));
+        assert_eq!(ResourceKind::from_path(Path::new("res/values-es/strings.xml")),
+                   Some(ResourceKind::Values));
+        assert_eq!(ResourceKind::from_path(Path::new("res/layout/main.xml")), None);
+        assert_eq!(ResourceKind::from_path(Path::new("assets/data.sqlite")),
+                   Some(ResourceKind::Database));
+        assert_eq!(ResourceKind::from_path(Path::new("assets/www/index.HTML")),
+                   Some(ResourceKind::Web));
+        assert_eq!(ResourceKind::from_path(Path::new("assets/config.properties")),
+                   Some(ResourceKind::Config));
+        assert_eq!(ResourceKind::from_path(Path::new("classes/config.json")), None);
+    }
+
+    #[test]
+    fn it_find_credentials() {
+        let scanner = Scanner::new(&Config::default());
+        let strings = "<resources>\n\
+                       <string name=\"password_hint\">Enter your password</string>\n\
+                       <string name=\"api_key\">a8F3kL92mQx7Zp</string>\n\
+                       <string name=\"app_name\">Example</string>\n\
+                       </resources>";
+        let findings = scanner.find_credentials(strings, ResourceKind::Values);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 2);
+        assert_eq!(findings[0].detail, "api_key");
+
+        let properties = "# Database\n\
+                          db.user=admin\n\
+                          db.password=hunter22\n\
+                          token.url=https://example.com/token\n\
+                          password.min_length=8\n";
+        let findings = scanner.find_credentials(properties, ResourceKind::Config);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].detail, "db.password");
+
+        let json = "{\"clientSecret\": \"s3cr3t-value\", \"secretHeader\": \"\"}";
+        let findings = scanner.find_credentials(json, ResourceKind::Config);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].detail, "clientSecret");
+    }
+
+    #[test]
+    fn it_is_credential_value() {
+        assert!(is_credential_value("hunter22"));
+        assert!(!is_credential_value("${DB_PASSWORD}"));
+        assert!(!is_credential_value("@string/password"));
+        assert!(!is_credential_value("false"));
+        assert!(!is_credential_value("Enter the password"));
+        assert!(!is_credential_value("3600"));
+    }
+
+    #[test]
+    fn it_find_debug_endpoints() {
+        assert!(is_debug_url("http://10.0.2.2:8080/api"));
+        assert!(is_debug_url("https://staging.example.com"));
+        assert!(is_debug_url("https://api-dev.example.com/v1"));
+        assert!(!is_debug_url("https://api.example.com"));
+        assert!(!is_debug_url("https://developer.android.com"));
+
+        let scanner = Scanner::new(&Config::default());
+        let code = "{\n\"url\": \"https://api.example.com\",\n\
+                    \"debug_url\": \"http://localhost:3000\"\n}";
+        let findings = scanner.find_debug_endpoints(code);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 2);
+        assert_eq!(findings[0].detail, "http://localhost:3000");
+    }
+
+    #[test]
+    fn it_find_admin_endpoints() {
+        assert!(is_admin_url("https://example.com/admin/login"));
+        assert!(is_admin_url("https://admin.example.com"));
+        assert!(is_admin_url("https://example.com/api/internal?token=1"));
+        assert!(!is_admin_url("https://example.com/administration-guide"));
+        assert!(!is_admin_url("https://example.com/help#admin"));
+
+        let scanner = Scanner::new(&Config::default());
+        let code = "<resources>\n\
+                    <string name=\"panel\">https://example.com/wp-admin/</string>\n\
+                    <string name=\"debug\">http://10.0.2.2/admin</string>\n\
+                    </resources>";
+        let findings = scanner.find_admin_endpoints(code);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].issue, ResourceIssue::AdminEndpoint);
+        assert_eq!(findings[0].line, 1);
+    }
+
+    #[test]
+    fn it_locale_only_strings() {
+        assert_eq!(get_file_locale(Path::new("res/values-ru/strings.xml")),
+                   Some(String::from("ru")));
+        assert_eq!(get_file_locale(Path::new("res/values-night/colors.xml")), None);
+        assert_eq!(get_file_locale(Path::new("res/values/strings.xml")), None);
+
+        let scanner = Scanner::new(&Config::default());
+        let mut defaults = BTreeMap::new();
+        let _ = defaults.insert(String::from("app_name"), String::from("Example"));
+        let _ = defaults.insert(String::from("api_url"), String::from("https://example.com"));
+        let code = "<resources>\n\
+                    <string name=\"app_name\">Example</string>\n\
+                    <string name=\"api_url\">https://dev.example.com</string>\n\
+                    <string name=\"admin_url\">https://example.com/admin</string>\n\
+                    </resources>";
+        assert_eq!(scanner.get_strings(code).len(), 3);
+        assert!(!scanner.is_locale_only(code, 1, &defaults));
+        assert!(scanner.is_locale_only(code, 2, &defaults));
+        assert!(scanner.is_locale_only(code, 3, &defaults));
+        assert!(!scanner.is_locale_only(code, 4, &defaults));
+    }
+
+    #[test]
+    fn it_find_sensitive_tables() {
+        let scanner = Scanner::new(&Config::default());
+        let database = "SQLite format 3\0\0\0tableusersusers\u{3}CREATE TABLE users (id INTEGER \
+                        PRIMARY KEY, name VARCHAR(64), password_hash TEXT, PRIMARY KEY (id))\0\
+                        \0tablenotesnotes\u{4}CREATE TABLE \"notes\" (id INTEGER, body TEXT)\0";
+        let findings = scanner.find_sensitive_tables(database);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].issue, ResourceIssue::SensitiveTable);
+        assert_eq!(findings[0].detail, "users (password_hash)");
+        assert!(findings[0].code.as_ref().unwrap().starts_with("CREATE TABLE users ("));
+        assert_eq!(get_columns("id INTEGER, `card_number` TEXT, UNIQUE (id)"),
+                   vec!["id", "card_number"]);
+    }
+
+    #[test]
+    fn it_find_web_issues() {
+        let scanner = Scanner::new(&Config::default());
+        let code = "<script src=\"http://cdn.example.com/lib.js\"></script>\n\
+                    <script>\n\
+                    var params = location.hash.substring(1);\n\
+                    document.getElementById(\"out\").innerHTML = params;\n\
+                    eval(params);\n\
+                    eval(other);\n\
+                    </script>";
+        let findings = scanner.find_web_issues(code);
+        let found: Vec<_> = findings.iter().map(|f| (f.issue, f.line)).collect();
+        assert_eq!(found,
+                   vec![(ResourceIssue::CodeEvaluation, 4),
+                        (ResourceIssue::HtmlInjection, 3),
+                        (ResourceIssue::CleartextScript, 0)]);
+    }
+}
@@ -0,0 +1,449 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, RuleMetadata};
+use super::manifest::{Manifest, ComponentKind};
+use super::code::get_line_for;
+use super::java::{tokenize, is_call, split_methods, get_java_files, get_class_name,
+                  get_enclosing_method, describe_location};
+use super::read_text_file;
+
+/// Permission that lets alarm clock and calendar applications schedule exact alarms, restricted
+/// by the store policies to those applications
+const USE_EXACT_ALARM: &'static str = "android.permission.USE_EXACT_ALARM";
+
+/// Permission that the user must grant to schedule exact alarms
+const SCHEDULE_EXACT_ALARM: &'static str = "android.permission.SCHEDULE_EXACT_ALARM";
+
+/// Calls of `AlarmManager` that schedule exact alarms
+const EXACT_ALARM_CALLS: [&'static str; 4] = ["setExact",
+                                              "setExactAndAllowWhileIdle",
+                                              "setAlarmClock",
+                                              "setWindow"];
+
+/// Foreground service types that give access to sensitive data while the application is not
+/// visible
+const SENSITIVE_SERVICE_TYPES: [&'static str; 3] = ["location", "camera", "microphone"];
+
+/// Foreground service types that the store policies only allow for specific use cases, that
+/// must be justified
+const RESTRICTED_SERVICE_TYPES: [&'static str; 3] = ["specialUse", "systemExempted", "dataSync"];
+
+/// Code markers of the sensitive data that background jobs could collect, with the permissions
+/// needed to read it
+const JOB_DATA_SOURCES: [(&'static str, &'static [&'static str]); 7] =
+    [("ContactsContract", &["android.permission.READ_CONTACTS"]),
+     ("content://sms", &["android.permission.READ_SMS"]),
+     ("Telephony.Sms", &["android.permission.READ_SMS"]),
+     ("CallLog", &["android.permission.READ_CALL_LOG"]),
+     ("getLastKnownLocation",
+      &["android.permission.ACCESS_FINE_LOCATION", "android.permission.ACCESS_COARSE_LOCATION"]),
+     ("getAccounts", &["android.permission.GET_ACCOUNTS"]),
+     ("getInstalledPackages", &["android.permission.QUERY_ALL_PACKAGES"])];
+
+/// Code markers of network uploads
+const NETWORK_SINKS: [&'static str; 6] = ["openConnection",
+                                          "getOutputStream",
+                                          "newCall",
+                                          "HttpPost",
+                                          "RequestBody",
+                                          "sendTextMessage"];
+
+/// Background work that could be abused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackgroundWork {
+    ExactAlarm,
+    ForegroundService,
+    DataCollectionJob,
+}
+
+impl BackgroundWork {
+    fn get_name(&self) -> &'static str {
+        match *self {
+            BackgroundWork::ExactAlarm => "Exact alarm",
+            BackgroundWork::ForegroundService => "Dubious foreground service type",
+            BackgroundWork::DataCollectionJob => "Data collection in background job",
+        }
+    }
+
+    fn get_cwe(&self) -> &'static str {
+        match *self {
+            BackgroundWork::ExactAlarm |
+            BackgroundWork::ForegroundService => "CWE-400",
+            BackgroundWork::DataCollectionJob => "CWE-359",
+        }
+    }
+
+    fn get_masvs(&self) -> &'static str {
+        match *self {
+            BackgroundWork::ExactAlarm |
+            BackgroundWork::ForegroundService => "MASVS-PLATFORM-1",
+            BackgroundWork::DataCollectionJob => "MASVS-PRIVACY-1",
+        }
+    }
+
+    fn get_owasp(&self) -> &'static str {
+        match *self {
+            BackgroundWork::ExactAlarm |
+            BackgroundWork::ForegroundService => "M8",
+            BackgroundWork::DataCollectionJob => "M6",
+        }
+    }
+
+    fn get_remediation(&self) -> &'static str {
+        match *self {
+            BackgroundWork::ExactAlarm => {
+                "Use inexact alarms or WorkManager unless the feature needs exact timing, and \
+                 check canScheduleExactAlarms() before scheduling them."
+            }
+            BackgroundWork::ForegroundService => {
+                "Declare the foreground service types that match the use case of the service, \
+                 and stop the service when its work is done."
+            }
+            BackgroundWork::DataCollectionJob => {
+                "Only collect the data needed by a visible feature, with the consent of the \
+                 user, and disclose it in the privacy policy."
+            }
+        }
+    }
+}
+
+/// Finds the calls scheduling exact alarms, with their index in the code
+fn find_exact_alarms(code: &str) -> Vec<usize> {
+    let tokens = tokenize(code);
+    tokens.iter()
+        .enumerate()
+        .filter(|&(i, token)| {
+            EXACT_ALARM_CALLS.contains(&token.get_text()) && is_call(&tokens, i) && i > 0 &&
+            tokens[i - 1].get_text() == "."
+        })
+        .map(|(_, token)| token.get_start())
+        .collect()
+}
+
+/// Checks if the service is restarted by the system when it's killed, by returning
+/// `START_STICKY` from `onStartCommand()`
+fn is_sticky_service(code: &str) -> bool {
+    let tokens = tokenize(code);
+    split_methods(code, &tokens)
+        .iter()
+        .filter(|method| method.get_name() == "onStartCommand")
+        .any(|method| {
+            method.get_statements().iter().any(|statement| {
+                statement.len() > 1 && statement[0].get_text() == "return" &&
+                ["START_STICKY", "1"].contains(&statement[statement.len() - 1].get_text())
+            })
+        })
+}
+
+/// Gets the sensitive and the restricted types of a foreground service
+fn get_dubious_types(service_types: &str) -> (Vec<&str>, Vec<&str>) {
+    let types: Vec<&str> = service_types.split('|').map(|t| t.trim()).collect();
+    (types.iter().cloned().filter(|t| SENSITIVE_SERVICE_TYPES.contains(t)).collect(),
+     types.iter().cloned().filter(|t| RESTRICTED_SERVICE_TYPES.contains(t)).collect())
+}
+
+/// Gets the data sources read by the code of a background job that also uploads data, with the
+/// permissions they need
+fn get_collected_sources(code: &str) -> Vec<(&'static str, &'static [&'static str])> {
+    if !NETWORK_SINKS.iter().any(|sink| code.contains(sink)) {
+        return Vec::new();
+    }
+    JOB_DATA_SOURCES.iter().cloned().filter(|&(marker, _)| code.contains(marker)).collect()
+}
+
+/// Looks for background work that could be abused: exact alarms, foreground services with
+/// sensitive or restricted types, and jobs that collect sensitive data and upload it
+///
+/// The criticity depends on the permissions requested by the application: exact alarms are
+/// more relevant with the `USE_EXACT_ALARM` permission, restricted by the store policies, and
+/// data collection in jobs when the application can read the collected data.
+pub fn scheduling_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for alarms, foreground services and jobs that could be abused.");
+    }
+
+    let mut services = BTreeMap::new();
+    if let Some(manifest) = manifest {
+        for component in manifest.get_components() {
+            if component.get_kind() != ComponentKind::Service || !component.is_enabled() {
+                continue;
+            }
+            if let Some(service_types) = component.get_foreground_service_type() {
+                let (sensitive, restricted) = get_dubious_types(service_types);
+                if !sensitive.is_empty() || !restricted.is_empty() {
+                    let _ = services.insert(String::from(component.get_name()),
+                                            (sensitive, restricted, component.get_line()));
+                }
+            }
+        }
+    }
+    let requests = |permission: &str| {
+        manifest.map_or(false, |m| m.requests_permission(permission))
+    };
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the background \
+                                   work analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let job_class = Regex::new(concat!(r"\bclass\s+\w+\s+extends\s+(?:[\w.]+\.)?",
+                                       r"(Worker|CoroutineWorker|ListenableWorker|RxWorker|",
+                                       r"JobService|JobIntentService)\b"))
+        .unwrap();
+    for path in files {
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = match get_class_name(&relative) {
+            Some(c) => c,
+            None => continue,
+        };
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+
+        if code.contains("AlarmManager") {
+            let (criticity, permission) = if requests(USE_EXACT_ALARM) {
+                (Criticity::Medium,
+                 "It requests the USE_EXACT_ALARM permission, that the store policies only \
+                  allow for alarm clock and calendar applications.")
+            } else if requests(SCHEDULE_EXACT_ALARM) {
+                (Criticity::Low,
+                 "It requests the SCHEDULE_EXACT_ALARM permission, that the user must grant.")
+            } else {
+                (Criticity::Warning,
+                 "It does not request a permission to schedule exact alarms, so they will \
+                  fail in recent Android versions.")
+            };
+            for index in find_exact_alarms(&code) {
+                let description = format!("An exact alarm is scheduled in {}. Exact alarms \
+                                           wake the device at a precise time, and are often \
+                                           used to keep the application running in the \
+                                           background. {}",
+                                          describe_location(&class,
+                                                            get_enclosing_method(&code, index)),
+                                          permission);
+                add_code_finding(BackgroundWork::ExactAlarm,
+                                 criticity,
+                                 description,
+                                 index,
+                                 &code,
+                                 &relative,
+                                 config,
+                                 results);
+            }
+        }
+
+        if let Some(&(ref sensitive, ref restricted, line)) = services.get(&class) {
+            let sticky = is_sticky_service(&code);
+            add_service_finding(&class,
+                                sensitive,
+                                restricted,
+                                sticky,
+                                line,
+                                manifest.unwrap(),
+                                config,
+                                results);
+        }
+
+        if let Some(caps) = job_class.captures(&code) {
+            let sources = get_collected_sources(&code);
+            if !sources.is_empty() {
+                let granted = sources.iter()
+                    .any(|&(_, permissions)| permissions.iter().any(|p| requests(p)));
+                let criticity = if granted {
+                    Criticity::Medium
+                } else {
+                    Criticity::Low
+                };
+                let markers: Vec<_> = sources.iter().map(|&(marker, _)| marker).collect();
+                let index = code.find(markers[0]).unwrap();
+                let description = format!("The background job {}, a {}, reads sensitive data \
+                                           ({}) and sends data over the network. Jobs run \
+                                           without the user being aware of them, and are \
+                                           used by malicious applications to exfiltrate \
+                                           data periodically. {}",
+                                          class,
+                                          caps.at(1).unwrap(),
+                                          markers.join(", "),
+                                          if granted {
+                                              "The application requests the permissions to \
+                                               read that data."
+                                          } else {
+                                              "The application does not request the \
+                                               permissions to read that data."
+                                          });
+                add_code_finding(BackgroundWork::DataCollectionJob,
+                                 criticity,
+                                 description,
+                                 index,
+                                 &code,
+                                 &relative,
+                                 config,
+                                 results);
+            }
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The background work was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Background work analyzed.");
+    }
+}
+
+/// Adds a finding for a foreground service with sensitive or restricted types
+///
+/// Services that the system restarts when they are killed are more critical, since they keep
+/// running in the background.
+fn add_service_finding(service: &str,
+                       sensitive: &[&str],
+                       restricted: &[&str],
+                       sticky: bool,
+                       line: Option<usize>,
+                       manifest: &Manifest,
+                       config: &Config,
+                       results: &mut Results) {
+    let criticity = match (sticky, sensitive.is_empty()) {
+        (true, false) => Criticity::Medium,
+        (false, true) => Criticity::Warning,
+        _ => Criticity::Low,
+    };
+    let mut reasons = Vec::new();
+    if !sensitive.is_empty() {
+        reasons.push(format!("the {} types give it access to sensitive data while the \
+                              application is not visible",
+                             sensitive.join(", ")));
+    }
+    if !restricted.is_empty() {
+        reasons.push(format!("the {} types are only allowed by the store policies for \
+                              specific use cases, that must be justified",
+                             restricted.join(", ")));
+    }
+    let description = format!("The foreground service {} is declared with dubious types: \
+                               {}.{}",
+                              service,
+                              reasons.join(", and "),
+                              if sticky {
+                                  " It returns START_STICKY from onStartCommand(), so the \
+                                   system restarts it when it's killed, keeping it running \
+                                   persistently."
+                              } else {
+                                  ""
+                              });
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let work = BackgroundWork::ForegroundService;
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(work.get_name()),
+                                      description,
+                                      Some("AndroidManifest.xml"),
+                                      line,
+                                      line,
+                                      line.map(|l| get_code(manifest.get_code(), l, l)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(work.get_cwe())],
+                                        vec![String::from(work.get_masvs())],
+                                        vec![String::from(work.get_owasp())],
+                                        Vec::new(),
+                                        Some(String::from(work.get_remediation()))));
+    results.add_vulnerability(vuln);
+}
+
+/// Adds a finding for background work found in the code
+fn add_code_finding(work: BackgroundWork,
+                    criticity: Criticity,
+                    description: String,
+                    index: usize,
+                    code: &str,
+                    path: &Path,
+                    config: &Config,
+                    results: &mut Results) {
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let line = get_line_for(index, code);
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(work.get_name()),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(work.get_cwe())],
+                                        vec![String::from(work.get_masvs())],
+                                        vec![String::from(work.get_owasp())],
+                                        Vec::new(),
+                                        Some(String::from(work.get_remediation()))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_exact_alarms, is_sticky_service, get_dubious_types, get_collected_sources};
+
+    #[test]
+    fn it_find_exact_alarms() {
+        let code = "AlarmManager manager = (AlarmManager) getSystemService(\"alarm\");\n\
+                    manager.setExactAndAllowWhileIdle(0, time, pendingIntent);\n\
+                    manager.set(0, time, pendingIntent);\n\
+                    setExact(time);";
+        assert_eq!(find_exact_alarms(code),
+                   vec![code.find("setExactAndAllowWhileIdle").unwrap()]);
+    }
+
+    #[test]
+    fn it_is_sticky_service() {
+        let code = "public class TrackerService extends Service {\n\
+                    public int onStartCommand(Intent intent, int flags, int startId) {\n\
+                    startForeground(1, notification);\n\
+                    return 1;\n\
+                    }\n\
+                    }";
+        assert!(is_sticky_service(code));
+        assert!(!is_sticky_service(&code.replace("return 1", "return START_NOT_STICKY")));
+        assert!(is_sticky_service(&code.replace("return 1", "return Service.START_STICKY")));
+    }
+
+    #[test]
+    fn it_get_dubious_types() {
+        assert_eq!(get_dubious_types("location|dataSync"),
+                   (vec!["location"], vec!["dataSync"]));
+        assert_eq!(get_dubious_types("mediaPlayback"), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn it_get_collected_sources() {
+        let code = "Cursor cursor = resolver.query(ContactsContract.Contacts.CONTENT_URI, \
+                    null, null, null, null);\n\
+                    HttpURLConnection connection = (HttpURLConnection) url.openConnection();";
+        let sources: Vec<_> = get_collected_sources(code).into_iter().map(|(m, _)| m).collect();
+        assert_eq!(sources, vec!["ContactsContract"]);
+        assert!(get_collected_sources("Cursor cursor = query(CallLog.Calls.CONTENT_URI);")
+            .is_empty());
+    }
+}
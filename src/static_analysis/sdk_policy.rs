@@ -0,0 +1,144 @@
+use colored::Colorize;
+
+use {Config, Criticity, print_vulnerability, get_code};
+use results::{Results, Vulnerability};
+use super::manifest::{Manifest, get_line};
+
+/// API level from which dangerous permissions are requested at runtime
+const RUNTIME_PERMISSIONS_SDK: i32 = 23;
+
+/// Security changes of the platform that only apply to applications targeting the given API
+/// level or later
+const TARGET_SDK_CHANGES: [(i32, &'static str); 4] =
+    [(RUNTIME_PERMISSIONS_SDK,
+      "dangerous permissions are granted on installation instead of at runtime, so the user \
+       can't deny them"),
+     (24, "certificate authorities installed by the user are trusted"),
+     (28, "cleartext traffic is allowed by default"),
+     (31, "components with intent filters are exported by default")];
+
+/// Gets the security protections of the platform that the application misses by targeting the
+/// given API level
+fn get_missing_protections(target_sdk: i32) -> Vec<&'static str> {
+    TARGET_SDK_CHANGES.iter()
+        .filter(|&&(sdk, _)| target_sdk < sdk)
+        .map(|&(_, change)| change)
+        .collect()
+}
+
+/// Checks the minimum and target SDKs of the application against the `[sdk_policy]` section of
+/// the configuration
+pub fn sdk_policy_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    let manifest = match manifest {
+        Some(m) => m,
+        None => return,
+    };
+    let policy = config.get_sdk_policy();
+    if policy.get_min_sdk().is_none() && policy.get_max_target_lag().is_none() {
+        return;
+    }
+    if config.is_verbose() {
+        println!("Checking the SDK versions of the application against the policy.");
+    }
+
+    let min_sdk = manifest.get_min_sdk();
+    let target_sdk = manifest.get_target_sdk().unwrap_or(min_sdk);
+
+    if let Some(policy_min_sdk) = policy.get_min_sdk() {
+        if min_sdk < policy_min_sdk {
+            add_finding(Criticity::Medium,
+                        "Minimum SDK below policy",
+                        format!("The application supports devices from API level {}, but the \
+                                 policy requires at least API level {}. Older Android versions \
+                                 no longer receive security updates, and lack protections of \
+                                 newer versions, so the application and its data are exposed \
+                                 to known vulnerabilities in those devices.",
+                                min_sdk,
+                                policy_min_sdk),
+                        manifest,
+                        "android:minSdkVersion",
+                        config,
+                        results);
+        }
+    }
+
+    if let Some(max_lag) = policy.get_max_target_lag() {
+        let latest_sdk = policy.get_latest_sdk();
+        if latest_sdk - target_sdk > max_lag {
+            let protections = get_missing_protections(target_sdk);
+            add_finding(if target_sdk < RUNTIME_PERMISSIONS_SDK {
+                            Criticity::High
+                        } else {
+                            Criticity::Medium
+                        },
+                        "Target SDK below policy",
+                        format!("The application targets API level {}, {} levels behind the \
+                                 latest API level {}, while the policy allows at most {}. The \
+                                 platform keeps the legacy behaviour for old target SDKs{}",
+                                target_sdk,
+                                latest_sdk - target_sdk,
+                                latest_sdk,
+                                max_lag,
+                                if protections.is_empty() {
+                                    String::from(".")
+                                } else {
+                                    format!(", so in this application {}.",
+                                            protections.join("; "))
+                                }),
+                        manifest,
+                        "android:targetSdkVersion",
+                        config,
+                        results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The SDK policy was checked correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("SDK policy checked.");
+    }
+}
+
+/// Adds a finding for the SDK versions of the application
+fn add_finding(criticity: Criticity,
+               name: &str,
+               description: String,
+               manifest: &Manifest,
+               needle: &str,
+               config: &Config,
+               results: &mut Results) {
+    let line = get_line(manifest.get_code(), needle).ok();
+    let code = match line {
+        Some(l) => Some(get_code(manifest.get_code(), l, l)),
+        None => None,
+    };
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from(name),
+                                  description,
+                                  Some("AndroidManifest.xml"),
+                                  line,
+                                  line,
+                                  code);
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_missing_protections;
+
+    #[test]
+    fn it_get_missing_protections() {
+        assert_eq!(get_missing_protections(22).len(), 4);
+        assert_eq!(get_missing_protections(27),
+                   vec!["cleartext traffic is allowed by default",
+                        "components with intent filters are exported by default"]);
+        assert!(get_missing_protections(31).is_empty());
+    }
+}
@@ -0,0 +1,392 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, get_assignment, find_assignment,
+                  split_methods, get_java_files, get_class_name, get_enclosing_method,
+                  describe_location};
+use super::structural::{get_closing_index, split_arguments, is_constant};
+use super::read_text_file;
+
+/// Maximum number of assignments followed back to find how a statement was built
+const MAX_DEPTH: usize = 5;
+
+/// SQL APIs, with the index of the argument with the SQL statement and the index of the bound
+/// arguments, if they can be passed to the call
+const QUERY_APIS: [(&'static str, usize, Option<usize>); 5] =
+    [("rawQuery", 0, Some(1)),
+     ("rawQueryWithFactory", 1, Some(2)),
+     ("execSQL", 0, Some(1)),
+     ("query", 2, Some(3)),
+     ("compileStatement", 0, None)];
+
+/// Way an SQL statement is built from non-constant values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Construction {
+    Concatenation,
+    Format,
+    Builder,
+}
+
+impl Construction {
+    fn get_description(&self) -> &'static str {
+        match *self {
+            Construction::Concatenation => "concatenating non-constant values",
+            Construction::Format => "formatting non-constant values with String.format()",
+            Construction::Builder => "appending non-constant values to a StringBuilder",
+        }
+    }
+}
+
+/// Call to an SQL API with a statement built from non-constant values
+#[derive(Debug)]
+struct DynamicQuery {
+    method: &'static str,
+    /// Index of the call in the code
+    index: usize,
+    construction: Construction,
+}
+
+/// Finds the calls to SQL APIs whose statement is built from non-constant values, without bound
+/// arguments
+fn find_dynamic_queries(code: &str) -> Vec<DynamicQuery> {
+    let tokens = tokenize(code);
+    let mut queries = Vec::new();
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        for (i, statement) in statements.iter().enumerate() {
+            for (j, token) in statement.iter().enumerate() {
+                let &(api, sql, bound) = match QUERY_APIS.iter()
+                    .find(|&&(api, _, _)| api == token.get_text()) {
+                    Some(a) if is_call(statement, j) => a,
+                    _ => continue,
+                };
+                let close = match get_closing_index(statement, j + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let arguments = split_arguments(&statement[j + 2..close]);
+                // `query(boolean distinct, String table, ...)`
+                let distinct = api == "query" && arguments.first().map_or(false, |a| is_boolean(a));
+                let shift = if distinct { 1 } else { 0 };
+                let sql = match arguments.get(sql + shift) {
+                    Some(s) => *s,
+                    None => continue,
+                };
+                let is_bound = bound.and_then(|b| arguments.get(b + shift))
+                    .map_or(false, |a| a.len() != 1 || a[0].get_text() != "null");
+                if is_bound {
+                    continue;
+                }
+                if let Some(construction) = get_construction(sql, &statements[..i], 0) {
+                    queries.push(DynamicQuery {
+                        method: api,
+                        index: token.get_start(),
+                        construction: construction,
+                    });
+                }
+            }
+        }
+    }
+    queries
+}
+
+/// Checks if the argument is a boolean literal
+fn is_boolean(argument: &[Token]) -> bool {
+    argument.len() == 1 && ["true", "false"].contains(&argument[0].get_text())
+}
+
+/// Gets how the expression is built from non-constant values, if it is
+///
+/// Variables are followed back to their last assignment in the previous statements of the
+/// method, and to the values appended to them afterwards.
+fn get_construction(expression: &[Token],
+                    previous: &[&[Token]],
+                    depth: usize)
+                    -> Option<Construction> {
+    if expression.is_empty() || depth > MAX_DEPTH {
+        return None;
+    }
+    for (i, token) in expression.iter().enumerate() {
+        let construction = match token.get_text() {
+            "format" => Construction::Format,
+            "append" | "StringBuilder" | "StringBuffer" => Construction::Builder,
+            _ => continue,
+        };
+        if !is_call(expression, i) {
+            continue;
+        }
+        let close = match get_closing_index(expression, i + 1) {
+            Some(c) => c,
+            None => continue,
+        };
+        // The format string is the first argument of `format()`
+        let skip = if construction == Construction::Format { 1 } else { 0 };
+        if split_arguments(&expression[i + 2..close])
+            .iter()
+            .skip(skip)
+            .any(|argument| is_dynamic(argument, previous, depth)) {
+            return Some(construction);
+        }
+    }
+
+    let operands = split_operands(expression);
+    if operands.len() > 1 {
+        return if operands.iter().any(|operand| is_dynamic(operand, previous, depth)) {
+            Some(Construction::Concatenation)
+        } else {
+            None
+        };
+    }
+
+    let variable = match get_variable(expression) {
+        Some(v) => v,
+        None => return None,
+    };
+    let (assignment, value) = match find_assignment(variable, previous) {
+        Some(a) => a,
+        None => return None,
+    };
+    get_update_construction(variable, previous, assignment + 1, depth + 1)
+        .or_else(|| get_construction(value, &previous[..assignment], depth + 1))
+}
+
+/// Gets how a variable is modified with non-constant values in the statements starting at the
+/// given index, with `+=` or by appending to it, if it is
+fn get_update_construction(variable: &str,
+                           statements: &[&[Token]],
+                           from: usize,
+                           depth: usize)
+                           -> Option<Construction> {
+    for i in from..statements.len() {
+        let statement = statements[i];
+        if statement.len() < 2 || statement[0].get_text() != variable {
+            continue;
+        }
+        match get_assignment(statement) {
+            Some((assignment, true)) => {
+                if is_dynamic(&statement[assignment + 1..], &statements[..i], depth) {
+                    return Some(Construction::Concatenation);
+                }
+            }
+            Some(_) => {}
+            None if statement[1].get_text() == "." => {
+                let construction = get_construction(statement, &statements[..i], depth);
+                if construction.is_some() {
+                    return construction;
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Checks if a value is not constant, following it back to its assignment if it's a variable
+fn is_dynamic(value: &[Token], previous: &[&[Token]], depth: usize) -> bool {
+    if is_constant(value) {
+        return false;
+    }
+    let variable = match get_variable(value) {
+        Some(v) => v,
+        None => return true,
+    };
+    // Parameters and fields could have any value
+    match find_assignment(variable, previous) {
+        Some((assignment, assigned)) if depth < MAX_DEPTH => {
+            is_dynamic(assigned, &previous[..assignment], depth + 1) ||
+            get_update_construction(variable, previous, assignment + 1, depth + 1).is_some()
+        }
+        _ => true,
+    }
+}
+
+/// Gets the variable an expression reads, if it's only a variable, optionally converted with
+/// `toString()`
+fn get_variable<'a>(expression: &[Token<'a>]) -> Option<&'a str> {
+    let expression = if expression.len() > 2 && expression[0].get_text() == "this" &&
+                        expression[1].get_text() == "." {
+        &expression[2..]
+    } else {
+        expression
+    };
+    if expression.is_empty() || expression[0].get_kind() != TokenKind::Identifier {
+        return None;
+    }
+    let texts: Vec<_> = expression[1..].iter().map(|t| t.get_text()).collect();
+    if texts.is_empty() || texts == [".", "toString", "(", ")"] {
+        Some(expression[0].get_text())
+    } else {
+        None
+    }
+}
+
+/// Splits an expression in the operands of its top level `+` operators
+fn split_operands<'a, 'b>(expression: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in expression.iter().enumerate() {
+        match token.get_text() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            "+" if depth == 0 => {
+                operands.push(&expression[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    operands.push(&expression[start..]);
+    operands
+}
+
+/// Looks for SQL statements built by concatenating, formatting or appending non-constant values,
+/// and executed without bound arguments
+///
+/// Unlike the SQL injection rule, this tells parameterized queries from the ones built with
+/// string operations, by following the variables passed to the SQL APIs to their assignments.
+pub fn sql_injection_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Looking for SQL statements built from non-constant values.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the SQL injection \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !QUERY_APIS.iter().any(|&(api, _, _)| code.contains(api)) {
+            continue;
+        }
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for query in find_dynamic_queries(&code) {
+            add_query_finding(&query, &code, relative, &class, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The SQL statements were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("SQL statements analyzed.");
+    }
+}
+
+/// Adds a finding for an SQL statement built from non-constant values
+fn add_query_finding(query: &DynamicQuery,
+                     code: &str,
+                     path: &Path,
+                     class: &str,
+                     config: &Config,
+                     results: &mut Results) {
+    let description = format!("The {}() call in {} runs an SQL statement built by {}, without \
+                               bound arguments. If any of the values can be controlled by an \
+                               attacker, they could read or modify the database of the \
+                               application through SQL injection.",
+                              query.method,
+                              describe_location(class, get_enclosing_method(code, query.index)),
+                              query.construction.get_description());
+    let line = get_line_for(query.index, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), Criticity::High);
+    }
+
+    let mut vuln = Vulnerability::new(Criticity::High,
+                                      String::from("SQL statement built from non-constant \
+                                                    values"),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from("CWE-89")],
+                                        vec![String::from("MASVS-CODE-4")],
+                                        vec![String::from("M4")],
+                                        Vec::new(),
+                                        Some(String::from("Use `?` placeholders in the SQL \
+                                                           statement and pass the values as \
+                                                           selection or bind arguments."))));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Construction, find_dynamic_queries};
+
+    #[test]
+    fn it_find_dynamic_queries() {
+        let code = "class UserDao {\n\
+                    void find(String id, String[] args) {\n\
+                    db.rawQuery(\"SELECT * FROM users WHERE id = \" + id, null);\n\
+                    db.rawQuery(\"SELECT * FROM users WHERE id = ?\", new String[] { id });\n\
+                    db.rawQuery(\"SELECT * FROM \" + TABLE + \" WHERE id = ?\", args);\n\
+                    String all = \"SELECT * FROM \" + TABLE;\n\
+                    db.rawQuery(all, null);\n\
+                    String sql = String.format(\"DELETE FROM users WHERE name = '%s'\", id);\n\
+                    db.execSQL(sql);\n\
+                    StringBuilder builder = new StringBuilder(\"UPDATE users SET name = '\");\n\
+                    builder.append(id).append(\"'\");\n\
+                    db.execSQL(builder.toString());\n\
+                    db.query(\"users\", null, \"id = \" + id, null, null, null, null);\n\
+                    db.query(true, \"users\", null, \"id = ?\", args, null, null, null, null);\n\
+                    db.execSQL(this.statement);\n\
+                    }\n\
+                    }";
+        let queries = find_dynamic_queries(code);
+        let found: Vec<_> = queries.iter().map(|q| (q.method, q.construction)).collect();
+        assert_eq!(found,
+                   vec![("rawQuery", Construction::Concatenation),
+                        ("execSQL", Construction::Format),
+                        ("execSQL", Construction::Builder),
+                        ("query", Construction::Concatenation)]);
+        assert_eq!(queries[0].index, code.find("rawQuery").unwrap());
+    }
+
+    #[test]
+    fn it_constant_queries() {
+        let code = "void create() {\n\
+                    String sql = \"CREATE TABLE \" + TABLE;\n\
+                    sql += \" (id INTEGER PRIMARY KEY)\";\n\
+                    db.execSQL(sql);\n\
+                    StringBuilder builder = new StringBuilder();\n\
+                    builder.append(\"DROP TABLE \").append(Contract.TABLE);\n\
+                    db.execSQL(builder.toString());\n\
+                    String name = \"users\";\n\
+                    db.execSQL(\"DELETE FROM \" + name);\n\
+                    name = getName();\n\
+                    db.execSQL(\"DELETE FROM \" + name);\n\
+                    }";
+        let queries = find_dynamic_queries(code);
+        assert_eq!(queries.len(), 1);
+        assert_eq!(code[..queries[0].index].matches('\n').count(), 10);
+    }
+}
@@ -0,0 +1,857 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use xml::reader::{EventReader, XmlEvent};
+use colored::Colorize;
+
+use {Config, Result, Error, Criticity, print_warning, print_vulnerability, get_code,
+     PARSER_CONFIG};
+use results::{Results, ReportSection, Vulnerability, RuleMetadata};
+use super::manifest::get_line;
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, parse_integer, split_methods,
+                  split_words, get_java_files, get_class_name, get_enclosing_method,
+                  describe_location};
+use super::structural::{get_closing_index, split_arguments, is_constant};
+use super::read_text_file;
+
+/// Order of the data storage section in the report
+const SECTION_ORDER: u32 = 85;
+
+/// Methods of `Context` receiving the creation mode of a file as their second argument
+const MODE_APIS: [&'static str; 4] = ["openFileOutput",
+                                      "getSharedPreferences",
+                                      "getDir",
+                                      "openOrCreateDatabase"];
+
+/// Value of `Context.MODE_WORLD_READABLE`
+const MODE_WORLD_READABLE: u64 = 0x1;
+
+/// Value of `Context.MODE_WORLD_WRITEABLE`
+const MODE_WORLD_WRITEABLE: u64 = 0x2;
+
+/// Calls returning a folder of the shared external storage
+const EXTERNAL_STORAGE_APIS: [&'static str; 6] = ["getExternalStorageDirectory",
+                                                  "getExternalStoragePublicDirectory",
+                                                  "getExternalFilesDir",
+                                                  "getExternalFilesDirs",
+                                                  "getExternalCacheDir",
+                                                  "getExternalMediaDirs"];
+
+/// Classes and methods that write to a file
+const FILE_WRITES: [&'static str; 6] = ["FileOutputStream",
+                                        "FileWriter",
+                                        "PrintWriter",
+                                        "write",
+                                        "writeBytes",
+                                        "writeText"];
+
+/// Terms in names and strings showing that the data is sensitive, as sequences of words
+const SENSITIVE_TERMS: [&'static str; 12] = ["password",
+                                             "passwd",
+                                             "token",
+                                             "secret",
+                                             "credential",
+                                             "social security",
+                                             "credit card",
+                                             "card number",
+                                             "private key",
+                                             "api key",
+                                             "cvv",
+                                             "pin code"];
+
+/// Elements of the FileProvider paths configuration, with the folder they share
+const PATH_ELEMENTS: [(&'static str, &'static str); 7] = [("root-path", "root"),
+                                                          ("files-path", "internal files"),
+                                                          ("cache-path", "internal cache"),
+                                                          ("external-path", "external storage"),
+                                                          ("external-files-path",
+                                                           "external files"),
+                                                          ("external-cache-path",
+                                                           "external cache"),
+                                                          ("external-media-path",
+                                                           "external media")];
+
+/// Calls opening a private file of the application by its name, with the backup domain of the
+/// file and the extension of its name
+const PRIVATE_FILE_APIS: [(&'static str, &'static str, &'static str); 4] =
+    [("getSharedPreferences", "sharedpref", ".xml"),
+     ("openOrCreateDatabase", "database", ""),
+     ("getDatabasePath", "database", ""),
+     ("openFileOutput", "file", "")];
+
+/// Private file of the application that stores sensitive data, found in the code, so that the
+/// backup analysis can check if it's copied outside the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensitiveFile {
+    domain: &'static str,
+    name: String,
+    term: &'static str,
+    location: String,
+}
+
+impl SensitiveFile {
+    /// Gets the domain of the file in the backup rules: `sharedpref`, `database` or `file`
+    pub fn get_domain(&self) -> &'static str {
+        self.domain
+    }
+
+    /// Gets the path of the file in its domain, such as `auth.xml` for the `auth` shared
+    /// preferences
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Gets the sensitive term found in the name of the file or in the code using it
+    pub fn get_term(&self) -> &'static str {
+        self.term
+    }
+
+    /// Gets the class and method where the file is opened
+    pub fn get_location(&self) -> &str {
+        self.location.as_str()
+    }
+}
+
+/// Data storage issue found by the analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StorageIssue {
+    /// File created, or changed, to be readable by any application
+    WorldReadable,
+    /// File created, or changed, to be writable by any application
+    WorldWritable,
+    /// Sensitive looking data written to the shared external storage
+    SensitiveExternalStorage,
+    /// FileProvider sharing the whole file system of the device
+    FileProviderRootPath,
+    /// FileProvider sharing a whole storage folder
+    FileProviderBroadPath,
+}
+
+impl StorageIssue {
+    /// Gets the name of the finding
+    ///
+    /// World accessible files use the labels of the equivalent code rules, so that a line
+    /// matched by both is only reported once.
+    fn get_label(&self) -> &'static str {
+        match *self {
+            StorageIssue::WorldReadable => "World readable permissions",
+            StorageIssue::WorldWritable => "World writable permissions",
+            StorageIssue::SensitiveExternalStorage => "Sensitive data in external storage",
+            StorageIssue::FileProviderRootPath => "FileProvider sharing the root path",
+            StorageIssue::FileProviderBroadPath => "FileProvider sharing a whole folder",
+        }
+    }
+
+    fn get_criticity(&self) -> Criticity {
+        match *self {
+            StorageIssue::WorldReadable |
+            StorageIssue::WorldWritable |
+            StorageIssue::FileProviderRootPath => Criticity::High,
+            StorageIssue::SensitiveExternalStorage |
+            StorageIssue::FileProviderBroadPath => Criticity::Medium,
+        }
+    }
+
+    fn get_cwe(&self) -> &'static str {
+        match *self {
+            StorageIssue::WorldReadable | StorageIssue::WorldWritable => "CWE-276",
+            StorageIssue::SensitiveExternalStorage => "CWE-922",
+            StorageIssue::FileProviderRootPath |
+            StorageIssue::FileProviderBroadPath => "CWE-552",
+        }
+    }
+
+    fn get_masvs(&self) -> &'static str {
+        match *self {
+            StorageIssue::SensitiveExternalStorage => "MASVS-STORAGE-1",
+            _ => "MASVS-STORAGE-2",
+        }
+    }
+
+    fn get_remediation(&self) -> &'static str {
+        match *self {
+            StorageIssue::WorldReadable | StorageIssue::WorldWritable => {
+                "Create files with Context.MODE_PRIVATE, and share them with other applications \
+                 through a FileProvider or a content provider with the appropriate permissions."
+            }
+            StorageIssue::SensitiveExternalStorage => {
+                "Store sensitive data in the internal storage of the application, encrypted if \
+                 possible, instead of the external storage."
+            }
+            StorageIssue::FileProviderRootPath | StorageIssue::FileProviderBroadPath => {
+                "Limit the paths of the FileProvider to the subfolders that contain the files \
+                 to share."
+            }
+        }
+    }
+}
+
+/// Storage issue found in the code
+#[derive(Debug, PartialEq, Eq)]
+struct CodeIssue<'a> {
+    issue: StorageIssue,
+    /// Method called, or `chmod` for shell commands
+    call: &'a str,
+    /// Sensitive term found, for data written to the external storage
+    term: Option<&'static str>,
+    /// Index of the call in the code
+    index: usize,
+}
+
+/// Finds the files made accessible to other applications, and the sensitive looking data
+/// written to the external storage, in the code
+///
+/// Data written to the external storage is only checked inside each method: the method must
+/// get an external storage folder, write to a file and mention a sensitive term.
+fn find_code_issues(code: &str) -> Vec<CodeIssue> {
+    let tokens = tokenize(code);
+    let mut issues = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.get_kind() == TokenKind::Literal {
+            if let Some((readable, writable)) = get_shell_chmod_access(token.get_text()) {
+                push_access_issues(&mut issues, "chmod", token.get_start(), readable, writable);
+            }
+            continue;
+        }
+        if !is_call(&tokens, i) {
+            continue;
+        }
+        let close = match get_closing_index(&tokens, i + 1) {
+            Some(c) => c,
+            None => continue,
+        };
+        let arguments = split_arguments(&tokens[i + 2..close]);
+        let access = match token.get_text() {
+            method if MODE_APIS.contains(&method) && arguments.len() >= 2 => {
+                get_mode_access(arguments[1])
+            }
+            "chmod" if arguments.len() == 2 && is_constant(arguments[1]) => {
+                get_permissions_access(get_literal_value(arguments[1]))
+            }
+            "setReadable" | "setWritable" if arguments.len() == 2 &&
+                                             arguments[0].len() == 1 &&
+                                             arguments[0][0].get_text() == "true" &&
+                                             arguments[1].len() == 1 &&
+                                             arguments[1][0].get_text() == "false" => {
+                (token.get_text() == "setReadable", token.get_text() == "setWritable")
+            }
+            _ => continue,
+        };
+        push_access_issues(&mut issues,
+                           token.get_text(),
+                           token.get_start(),
+                           access.0,
+                           access.1);
+    }
+
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        let external = statements.iter()
+            .flat_map(|s| s.iter().enumerate().map(move |(i, t)| (*s, i, t)))
+            .find(|&(s, i, t)| EXTERNAL_STORAGE_APIS.contains(&t.get_text()) && is_call(s, i));
+        let (_, _, external) = match external {
+            Some(e) => e,
+            None => continue,
+        };
+        if !statements.iter().any(|s| s.iter().any(|t| FILE_WRITES.contains(&t.get_text()))) {
+            continue;
+        }
+        let term = statements.iter()
+            .flat_map(|s| s.iter())
+            .filter_map(|t| find_sensitive_term(t.get_text()))
+            .next();
+        if let Some(term) = term {
+            issues.push(CodeIssue {
+                issue: StorageIssue::SensitiveExternalStorage,
+                call: external.get_text(),
+                term: Some(term),
+                index: external.get_start(),
+            });
+        }
+    }
+    issues.sort_by_key(|issue| issue.index);
+    issues
+}
+
+/// Finds the private files opened by name in the code that store sensitive data, with their
+/// backup domain, their name, the sensitive term and the index of the call
+///
+/// A file is sensitive if its name, or the method opening it, mentions a sensitive term. The
+/// schema of the databases of a `SQLiteOpenHelper` is usually in other methods, so the whole
+/// class is checked for the databases named in its constructor.
+fn find_sensitive_files(code: &str) -> Vec<(&'static str, String, &'static str, usize)> {
+    let tokens = tokenize(code);
+    let mut files = Vec::new();
+    let helper = code.contains("extends SQLiteOpenHelper");
+    let class_term = if helper {
+        tokens.iter().filter_map(|t| find_sensitive_term(t.get_text())).next()
+    } else {
+        None
+    };
+    for method in split_methods(code, &tokens) {
+        let statements = method.get_statements();
+        let method_term = statements.iter()
+            .flat_map(|s| s.iter())
+            .filter(|t| t.get_kind() == TokenKind::Identifier)
+            .filter_map(|t| find_sensitive_term(t.get_text()))
+            .next();
+        for statement in statements {
+            for (i, token) in statement.iter().enumerate() {
+                if !is_call(statement, i) {
+                    continue;
+                }
+                let close = match get_closing_index(statement, i + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let arguments = split_arguments(&statement[i + 2..close]);
+                let (domain, extension, argument, term) = match PRIVATE_FILE_APIS.iter()
+                    .find(|&&(api, _, _)| api == token.get_text()) {
+                    Some(&(_, domain, extension)) if !arguments.is_empty() => {
+                        (domain, extension, arguments[0], method_term)
+                    }
+                    None if token.get_text() == "super" && helper && arguments.len() >= 2 => {
+                        ("database", "", arguments[1], class_term)
+                    }
+                    _ => continue,
+                };
+                if argument.len() != 1 || argument[0].get_kind() != TokenKind::Literal ||
+                   !argument[0].get_text().starts_with('"') {
+                    continue;
+                }
+                let name = argument[0].get_text().trim_matches('"');
+                if name.is_empty() || name.contains('/') {
+                    continue;
+                }
+                if let Some(term) = find_sensitive_term(name).or(term) {
+                    files.push((domain,
+                                format!("{}{}", name, extension),
+                                term,
+                                token.get_start()));
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Adds the issues for the access given to other applications to a file
+fn push_access_issues<'a>(issues: &mut Vec<CodeIssue<'a>>,
+                          call: &'a str,
+                          index: usize,
+                          readable: bool,
+                          writable: bool) {
+    for &(issue, given) in &[(StorageIssue::WorldReadable, readable),
+                             (StorageIssue::WorldWritable, writable)] {
+        if given {
+            issues.push(CodeIssue {
+                issue: issue,
+                call: call,
+                term: None,
+                index: index,
+            });
+        }
+    }
+}
+
+/// Gets the value of the integer literals of a constant argument, combined as flags
+fn get_literal_value(argument: &[Token]) -> u64 {
+    argument.iter()
+        .filter(|t| t.get_kind() == TokenKind::Literal)
+        .filter_map(|t| parse_integer(t.get_text()))
+        .fold(0, |value, literal| value | literal)
+}
+
+/// Gets the read and write access given to other applications by the creation mode of a file
+///
+/// Decompiled code usually has the value of the mode instead of its name.
+fn get_mode_access(mode: &[Token]) -> (bool, bool) {
+    let names = mode.iter().map(|t| t.get_text()).collect::<Vec<_>>();
+    let value = if is_constant(mode) {
+        get_literal_value(mode)
+    } else {
+        0
+    };
+    (names.contains(&"MODE_WORLD_READABLE") || value & MODE_WORLD_READABLE != 0,
+     names.contains(&"MODE_WORLD_WRITEABLE") || names.contains(&"MODE_WORLD_WRITABLE") ||
+     value & MODE_WORLD_WRITEABLE != 0)
+}
+
+/// Gets the read and write access given to other users by Unix permissions
+fn get_permissions_access(permissions: u64) -> (bool, bool) {
+    (permissions & 0o4 != 0, permissions & 0o2 != 0)
+}
+
+/// Gets the read and write access given to other users by a `chmod` shell command in a string
+/// literal, if the literal has one
+fn get_shell_chmod_access(literal: &str) -> Option<(bool, bool)> {
+    if !literal.starts_with('"') || !literal.contains("chmod") {
+        return None;
+    }
+    let mut words = literal.trim_matches('"').split_whitespace();
+    if !words.next().map_or(false, |w| w == "chmod" || w.ends_with("/chmod")) {
+        return None;
+    }
+    let mode = match words.find(|w| !w.starts_with('-')) {
+        Some(m) => m,
+        None => return None,
+    };
+
+    let mut access = (false, false);
+    if mode.chars().all(|c| c.is_digit(8)) {
+        access = get_permissions_access(u64::from_str_radix(mode, 8).unwrap_or(0));
+    }
+    for clause in mode.split(',') {
+        let operator = match clause.find(|c: char| c == '+' || c == '=') {
+            Some(o) => o,
+            None => continue,
+        };
+        let who = &clause[..operator];
+        if who.is_empty() || who.contains('a') || who.contains('o') {
+            let permissions = &clause[operator + 1..];
+            access.0 |= permissions.contains('r');
+            access.1 |= permissions.contains('w');
+        }
+    }
+    if access.0 || access.1 {
+        Some(access)
+    } else {
+        None
+    }
+}
+
+/// Finds the first sensitive term in an identifier or a string, accepting plurals
+fn find_sensitive_term(text: &str) -> Option<&'static str> {
+    let words = split_words(text);
+    SENSITIVE_TERMS.iter()
+        .find(|term| {
+            let term_words = term.split(' ').collect::<Vec<_>>();
+            words.windows(term_words.len()).any(|window| {
+                window.iter()
+                    .zip(&term_words)
+                    .all(|(word, term_word)| {
+                        word == term_word || *word == format!("{}s", term_word)
+                    })
+            })
+        })
+        .cloned()
+}
+
+/// Path shared by a FileProvider that gives access to too many files
+#[derive(Debug, PartialEq, Eq)]
+struct ProviderPath {
+    issue: StorageIssue,
+    element: String,
+    name: Option<String>,
+    folder: &'static str,
+}
+
+/// Finds the root and broad paths of a FileProvider paths configuration
+///
+/// Files that are not a paths configuration return no paths.
+fn find_provider_paths(code: &str) -> Result<Vec<ProviderPath>> {
+    let parser = EventReader::new_with_config(code.as_bytes(), PARSER_CONFIG);
+    let mut paths = Vec::new();
+    let mut first = true;
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                if first {
+                    if name.local_name != "paths" {
+                        return Ok(Vec::new());
+                    }
+                    first = false;
+                    continue;
+                }
+                let folder = match PATH_ELEMENTS.iter()
+                    .find(|&&(element, _)| element == name.local_name) {
+                    Some(&(_, folder)) => folder,
+                    None => continue,
+                };
+                let attribute = |key: &str| {
+                    attributes.iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.clone())
+                };
+                let issue = if name.local_name == "root-path" {
+                    StorageIssue::FileProviderRootPath
+                } else if attribute("path")
+                    .map_or(true, |p| p.trim_matches(|c: char| c == '/' || c == '.').is_empty()) {
+                    StorageIssue::FileProviderBroadPath
+                } else {
+                    continue;
+                };
+                paths.push(ProviderPath {
+                    issue: issue,
+                    element: name.local_name.clone(),
+                    name: attribute("name"),
+                    folder: folder,
+                });
+            }
+            Ok(_) => {}
+            Err(_) => return Err(Error::ParseError),
+        }
+    }
+    Ok(paths)
+}
+
+/// Gets the XML resource files of the application, relative to the dist folder
+fn get_xml_resources(dist_folder: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for folder in try!(fs::read_dir(dist_folder.join("res"))) {
+        let folder = try!(folder);
+        if !folder.file_name().to_string_lossy().starts_with("xml") ||
+           !try!(folder.file_type()).is_dir() {
+            continue;
+        }
+        for f in try!(fs::read_dir(folder.path())) {
+            let path = try!(f).path();
+            if path.extension().map_or(false, |e| e == "xml") {
+                files.push(path.strip_prefix(dist_folder).unwrap().to_path_buf());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Looks for data stored insecurely: files accessible to other applications, sensitive data
+/// written to the external storage and FileProviders sharing too many files
+///
+/// The issues are reported as findings, and grouped in the data storage section of the report.
+/// The private files with sensitive data are returned, to check the backup rules.
+pub fn storage_analysis(config: &Config, results: &mut Results) -> Vec<SensitiveFile> {
+    if config.is_verbose() {
+        println!("Looking for insecure data storage.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the data storage \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return Vec::new();
+        }
+    };
+
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    let mut sensitive_files: Vec<SensitiveFile> = Vec::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+        let class = get_class_name(&relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for issue in find_code_issues(&code) {
+            let location = describe_location(&class, get_enclosing_method(&code, issue.index));
+            let description = get_code_description(&issue, &location);
+            let line = get_line_for(issue.index, &code);
+            add_finding(issue.issue, description, &relative, &code, line, config, results);
+            add_row(issue.issue, location, &mut rows, &mut data);
+        }
+        for (domain, name, term, index) in find_sensitive_files(&code) {
+            if sensitive_files.iter().any(|f| f.domain == domain && f.name == name) {
+                continue;
+            }
+            sensitive_files.push(SensitiveFile {
+                domain: domain,
+                name: name,
+                term: term,
+                location: describe_location(&class, get_enclosing_method(&code, index)),
+            });
+        }
+    }
+
+    // Applications without XML resources have nothing to check
+    let resources = get_xml_resources(&dist_folder).unwrap_or_else(|_| Vec::new());
+    for relative in resources {
+        let code = match read_text_file(&dist_folder.join(&relative)) {
+            Ok(Some(c)) => c,
+            _ => continue,
+        };
+        if !code.contains("<paths") {
+            continue;
+        }
+        let paths = match find_provider_paths(&code) {
+            Ok(p) => p,
+            Err(e) => {
+                print_warning(format!("The FileProvider paths in {} could not be parsed: {}",
+                                      relative.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        for path in paths {
+            let location = relative.to_string_lossy().into_owned();
+            let description = get_provider_path_description(&path, &location);
+            let needle = match path.name {
+                Some(ref name) => format!("name=\"{}\"", name),
+                None => format!("<{}", path.element),
+            };
+            let line = get_line(&code, &needle).unwrap_or(0);
+            add_finding(path.issue, description, &relative, &code, line, config, results);
+            add_row(path.issue, location, &mut rows, &mut data);
+        }
+    }
+
+    let mut section = ReportSection::new("data_storage", "Data storage", SECTION_ORDER);
+    section.set_data(&data);
+    section.set_html_table(&["Issue", "Location", "MASVS"], &rows);
+    results.add_section(section);
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The data storage was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Data storage analyzed.");
+    }
+    sensitive_files
+}
+
+/// Gets the description of an issue found in the code
+fn get_code_description(issue: &CodeIssue, location: &str) -> String {
+    match issue.issue {
+        StorageIssue::SensitiveExternalStorage => {
+            format!("Data that looks sensitive (\"{}\") is written to the external storage, \
+                     obtained with {}() in {}. Files in the shared external storage can be read, \
+                     and modified, by other applications with the storage permissions, and by \
+                     anyone with physical access to the device.",
+                    issue.term.unwrap_or(""),
+                    issue.call,
+                    location)
+        }
+        _ => {
+            format!("The call to {}() in {} gives {} access to a file to any application on \
+                     the device. Since Android 7.0, creating world accessible files with the \
+                     MODE_WORLD_* modes throws a SecurityException, but changing their \
+                     permissions afterwards still exposes them.",
+                    issue.call,
+                    location,
+                    if issue.issue == StorageIssue::WorldReadable {
+                        "read"
+                    } else {
+                        "write"
+                    })
+        }
+    }
+}
+
+/// Gets the description of a path shared by a FileProvider
+fn get_provider_path_description(path: &ProviderPath, location: &str) -> String {
+    let name = match path.name {
+        Some(ref name) => format!(" \"{}\"", name),
+        None => String::new(),
+    };
+    match path.issue {
+        StorageIssue::FileProviderRootPath => {
+            format!("The FileProvider paths in {} have the root-path element{}, so any file \
+                     the application can read, including its private files, can be shared \
+                     through a content URI. A flaw in how the application chooses the files to \
+                     share would expose them to other applications.",
+                    location,
+                    name)
+        }
+        _ => {
+            format!("The {} element{} of the FileProvider paths in {} shares the whole {} \
+                     folder of the application, instead of the subfolder with the files to \
+                     share. A flaw in how the application chooses the files to share would \
+                     expose the rest of them to other applications.",
+                    path.element,
+                    name,
+                    location,
+                    path.folder)
+        }
+    }
+}
+
+/// Adds a finding for a storage issue
+fn add_finding(issue: StorageIssue,
+               description: String,
+               path: &Path,
+               code: &str,
+               line: usize,
+               config: &Config,
+               results: &mut Results) {
+    let criticity = issue.get_criticity();
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(issue.get_label()),
+                                      description,
+                                      Some(path),
+                                      Some(line),
+                                      Some(line),
+                                      Some(get_code(code, line, line)));
+    vuln.set_metadata(RuleMetadata::new(vec![String::from(issue.get_cwe())],
+                                        vec![String::from(issue.get_masvs())],
+                                        vec![String::from("M9")],
+                                        Vec::new(),
+                                        Some(String::from(issue.get_remediation()))));
+    results.add_vulnerability(vuln);
+}
+
+/// Adds a storage issue to the rows and the data of the report section
+fn add_row(issue: StorageIssue,
+           location: String,
+           rows: &mut Vec<Vec<String>>,
+           data: &mut Vec<BTreeMap<&'static str, String>>) {
+    rows.push(vec![String::from(issue.get_label()),
+                   Results::html_escape(&location),
+                   String::from(issue.get_masvs())]);
+    let mut entry = BTreeMap::new();
+    let _ = entry.insert("issue", String::from(issue.get_label()));
+    let _ = entry.insert("location", location);
+    let _ = entry.insert("masvs", String::from(issue.get_masvs()));
+    data.push(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StorageIssue, CodeIssue, ProviderPath, find_code_issues, get_shell_chmod_access,
+                find_sensitive_term, find_provider_paths, find_sensitive_files};
+
+    #[test]
+    fn it_find_code_issues() {
+        let code = "void save(Context context, String token) {\n\
+                    context.openFileOutput(\"config\", 1);\n\
+                    context.getSharedPreferences(\"prefs\", Context.MODE_WORLD_WRITEABLE);\n\
+                    context.getDir(\"cache\", 0);\n\
+                    Os.chmod(path, 511);\n\
+                    Runtime.getRuntime().exec(\"chmod 600 \" + path);\n\
+                    Runtime.getRuntime().exec(\"chmod o+r \" + path);\n\
+                    file.setReadable(true, false);\n\
+                    file.setWritable(true);\n\
+                    File out = new File(Environment.getExternalStorageDirectory(), \"t.txt\");\n\
+                    new FileOutputStream(out).write(token.getBytes());\n\
+                    }\n\
+                    void export(Context context, String report) {\n\
+                    File out = new File(context.getExternalFilesDir(null), \"report.txt\");\n\
+                    new FileWriter(out).write(report);\n\
+                    }";
+        let issues = find_code_issues(code);
+        let found: Vec<_> = issues.iter().map(|i| (i.issue, i.call)).collect();
+        assert_eq!(found,
+                   vec![(StorageIssue::WorldReadable, "openFileOutput"),
+                        (StorageIssue::WorldWritable, "getSharedPreferences"),
+                        (StorageIssue::WorldReadable, "chmod"),
+                        (StorageIssue::WorldWritable, "chmod"),
+                        (StorageIssue::WorldReadable, "chmod"),
+                        (StorageIssue::WorldReadable, "setReadable"),
+                        (StorageIssue::SensitiveExternalStorage, "getExternalStorageDirectory")]);
+        assert_eq!(issues[6],
+                   CodeIssue {
+                       issue: StorageIssue::SensitiveExternalStorage,
+                       call: "getExternalStorageDirectory",
+                       term: Some("token"),
+                       index: code.find("getExternalStorageDirectory").unwrap(),
+                   });
+    }
+
+    #[test]
+    fn it_find_sensitive_files() {
+        let code = "class Session {\n\
+                    void saveToken(Context context, String token) {\n\
+                    context.getSharedPreferences(\"session\", 0).edit()\n\
+                    .putString(\"token\", token).apply();\n\
+                    }\n\
+                    void saveTheme(Context context, String theme) {\n\
+                    context.getSharedPreferences(\"ui\", 0).edit().putString(\"t\", theme);\n\
+                    context.getSharedPreferences(\"api_keys\", 0);\n\
+                    }\n\
+                    }";
+        let found: Vec<_> = find_sensitive_files(code)
+            .into_iter()
+            .map(|(domain, name, term, _)| (domain, name, term))
+            .collect();
+        assert_eq!(found,
+                   vec![("sharedpref", String::from("session.xml"), "token"),
+                        ("sharedpref", String::from("api_keys.xml"), "api key")]);
+
+        let code = "public class Db extends SQLiteOpenHelper {\n\
+                    public Db(Context context) {\n\
+                    super(context, \"accounts.db\", null, 1);\n\
+                    }\n\
+                    public void onCreate(SQLiteDatabase db) {\n\
+                    db.execSQL(\"CREATE TABLE users (name TEXT, password TEXT)\");\n\
+                    }\n\
+                    }";
+        let found: Vec<_> = find_sensitive_files(code)
+            .into_iter()
+            .map(|(domain, name, term, _)| (domain, name, term))
+            .collect();
+        assert_eq!(found, vec![("database", String::from("accounts.db"), "password")]);
+    }
+
+    #[test]
+    fn it_get_shell_chmod_access() {
+        assert_eq!(get_shell_chmod_access("\"chmod 777 \""), Some((true, true)));
+        assert_eq!(get_shell_chmod_access("\"/system/bin/chmod -R 0604 /data\""),
+                   Some((true, false)));
+        assert_eq!(get_shell_chmod_access("\"chmod a+w \""), Some((false, true)));
+        assert_eq!(get_shell_chmod_access("\"chmod u+x,g+r \""), None);
+        assert_eq!(get_shell_chmod_access("\"chmod 750 \""), None);
+        assert_eq!(get_shell_chmod_access("\"echo chmod 777\""), None);
+    }
+
+    #[test]
+    fn it_find_sensitive_term() {
+        assert_eq!(find_sensitive_term("userPassword"), Some("password"));
+        assert_eq!(find_sensitive_term("ACCESS_TOKENS"), Some("token"));
+        assert_eq!(find_sensitive_term("\"credit_card_number\""), Some("credit card"));
+        assert_eq!(find_sensitive_term("StringTokenizer"), None);
+        assert_eq!(find_sensitive_term("keyboard"), None);
+    }
+
+    #[test]
+    fn it_find_provider_paths() {
+        let code = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <paths xmlns:android=\"http://schemas.android.com/apk/res/android\">\n\
+                    <root-path name=\"root\" path=\"\" />\n\
+                    <external-path name=\"external\" path=\".\" />\n\
+                    <cache-path name=\"images\" path=\"images/\" />\n\
+                    <files-path name=\"files\" />\n\
+                    </paths>";
+        assert_eq!(find_provider_paths(code).unwrap(),
+                   vec![ProviderPath {
+                            issue: StorageIssue::FileProviderRootPath,
+                            element: String::from("root-path"),
+                            name: Some(String::from("root")),
+                            folder: "root",
+                        },
+                        ProviderPath {
+                            issue: StorageIssue::FileProviderBroadPath,
+                            element: String::from("external-path"),
+                            name: Some(String::from("external")),
+                            folder: "external storage",
+                        },
+                        ProviderPath {
+                            issue: StorageIssue::FileProviderBroadPath,
+                            element: String::from("files-path"),
+                            name: Some(String::from("files")),
+                            folder: "internal files",
+                        }]);
+        let code = "<network-security-config><root-path /></network-security-config>";
+        assert!(find_provider_paths(code).unwrap().is_empty());
+    }
+}
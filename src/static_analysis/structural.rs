@@ -0,0 +1,320 @@
+use std::str::FromStr;
+
+use {Result, Error};
+use super::java::{Token, TokenKind};
+
+/// Constraint on an argument of a call matched by a structural rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentPattern {
+    /// Any argument
+    Any,
+    /// An argument made only of literals and constants
+    Constant,
+    /// An argument with at least a variable, field or call
+    NonConstant,
+    /// Any number of remaining arguments, only allowed as the last pattern
+    Rest,
+}
+
+impl ArgumentPattern {
+    /// Checks if the tokens of an argument match the pattern
+    fn matches(&self, argument: &[Token]) -> bool {
+        match *self {
+            ArgumentPattern::Any | ArgumentPattern::Rest => true,
+            ArgumentPattern::Constant => is_constant(argument),
+            ArgumentPattern::NonConstant => !is_constant(argument),
+        }
+    }
+}
+
+impl FromStr for ArgumentPattern {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<ArgumentPattern> {
+        match s {
+            "any" => Ok(ArgumentPattern::Any),
+            "constant" => Ok(ArgumentPattern::Constant),
+            "non_constant" => Ok(ArgumentPattern::NonConstant),
+            "..." => Ok(ArgumentPattern::Rest),
+            _ => Err(Error::ParseError),
+        }
+    }
+}
+
+/// Checks if the argument is only made of literals and constants
+///
+/// Decompiled code usually inlines constants, but `UPPER_CASE` identifiers, optionally qualified
+/// by their class, are considered constants too.
+pub fn is_constant(argument: &[Token]) -> bool {
+    !argument.is_empty() &&
+    argument.iter().enumerate().all(|(i, token)| {
+        match token.get_kind() {
+            TokenKind::Literal => true,
+            TokenKind::Symbol => ["+", "(", ")", "."].contains(&token.get_text()),
+            TokenKind::Identifier => {
+                let text = token.get_text();
+                ["true", "false", "null"].contains(&text) ||
+                (text.chars().any(|c| c.is_uppercase()) &&
+                 !text.chars().any(|c| c.is_lowercase())) ||
+                argument.get(i + 1).map(|t| t.get_text()) == Some(".")
+            }
+        }
+    })
+}
+
+/// Identifiers that can precede a call without it being a method declaration
+const EXPRESSION_KEYWORDS: [&'static str; 6] = ["return", "new", "throw", "else", "case",
+                                                "assert"];
+
+/// Pattern of a method call, matched against the tokens of the code instead of its text
+///
+/// As it works with tokens, calls in comments are not matched, and literals can be told apart
+/// from variables in the arguments.
+#[derive(Debug)]
+pub struct CallPattern {
+    method: String,
+    receiver: Option<String>,
+    arguments: Option<Vec<ArgumentPattern>>,
+}
+
+impl CallPattern {
+    /// Creates a new call pattern
+    ///
+    /// The receiver, if set, is the expression the method is called on, without whitespace,
+    /// such as `Runtime.getRuntime()`, and it's matched with the end of the actual receiver. If
+    /// the argument patterns are set, the call must have as many arguments as patterns, unless
+    /// the last one is `ArgumentPattern::Rest`.
+    pub fn new(method: String,
+               receiver: Option<String>,
+               arguments: Option<Vec<ArgumentPattern>>)
+               -> Result<CallPattern> {
+        let valid_method = !method.is_empty() &&
+                           method.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+        let valid_arguments = arguments.as_ref().map_or(true, |a| {
+            a.iter()
+                .position(|p| *p == ArgumentPattern::Rest)
+                .map_or(true, |i| i == a.len() - 1)
+        });
+        if !valid_method || !valid_arguments {
+            return Err(Error::ParseError);
+        }
+        Ok(CallPattern {
+            method: method,
+            receiver: receiver.map(|r| r.chars().filter(|c| !c.is_whitespace()).collect()),
+            arguments: arguments,
+        })
+    }
+
+    /// Gets the name of the called method
+    pub fn get_method(&self) -> &str {
+        self.method.as_str()
+    }
+
+    /// Finds the calls matching the pattern, returning the start and end indexes of each of them
+    /// in the code
+    pub fn find_calls(&self, tokens: &[Token]) -> Vec<(usize, usize)> {
+        let mut calls = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if token.get_kind() != TokenKind::Identifier || token.get_text() != self.method ||
+               tokens.get(i + 1).map(|t| t.get_text()) != Some("(") {
+                continue;
+            }
+            // Method declarations, such as `public Process exec(String command)`
+            if i > 0 &&
+               ((tokens[i - 1].get_kind() == TokenKind::Identifier &&
+                 !EXPRESSION_KEYWORDS.contains(&tokens[i - 1].get_text())) ||
+                tokens[i - 1].get_text() == "]") {
+                continue;
+            }
+            let close = match get_closing_index(tokens, i + 1) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let start = match self.receiver {
+                Some(ref receiver) => {
+                    if i == 0 || tokens[i - 1].get_text() != "." {
+                        continue;
+                    }
+                    let receiver_start = get_receiver_start(tokens, i - 1);
+                    let actual: String = tokens[receiver_start..i - 1]
+                        .iter()
+                        .map(|t| t.get_text())
+                        .collect();
+                    if actual != *receiver && !actual.ends_with(&format!(".{}", receiver)) {
+                        continue;
+                    }
+                    tokens[receiver_start].get_start()
+                }
+                None => token.get_start(),
+            };
+
+            if let Some(ref patterns) = self.arguments {
+                if !matches_arguments(patterns, &split_arguments(&tokens[i + 2..close])) {
+                    continue;
+                }
+            }
+            calls.push((start, tokens[close].get_end()));
+        }
+        calls
+    }
+}
+
+/// Gets the index of the token closing the group opened at the given index
+pub fn get_closing_index(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.get_text() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Gets the index of the token opening the group closed at the given index
+fn get_opening_index(tokens: &[Token], close: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..close + 1).rev() {
+        match tokens[i].get_text() {
+            ")" | "]" | "}" => depth += 1,
+            "(" | "[" | "{" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Gets the index of the first token of the receiver of a call, given the index of the dot
+/// before the method name
+fn get_receiver_start(tokens: &[Token], dot: usize) -> usize {
+    let mut start = dot;
+    while start > 0 {
+        let last = &tokens[start - 1];
+        match last.get_text() {
+            ")" | "]" => {
+                start = match get_opening_index(tokens, start - 1) {
+                    Some(o) => o,
+                    None => break,
+                };
+                if start > 0 && tokens[start - 1].get_kind() == TokenKind::Identifier {
+                    start -= 1;
+                }
+            }
+            _ if last.get_kind() != TokenKind::Symbol => start -= 1,
+            _ => break,
+        }
+        if start > 0 && tokens[start - 1].get_text() == "." {
+            start -= 1;
+        } else {
+            break;
+        }
+    }
+    start
+}
+
+/// Splits the tokens between the parentheses of a call in its arguments
+pub fn split_arguments<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.get_text() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            "," if depth == 0 => {
+                arguments.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(&tokens[start..]);
+    arguments
+}
+
+/// Checks if the arguments of a call match the argument patterns
+fn matches_arguments(patterns: &[ArgumentPattern], arguments: &[&[Token]]) -> bool {
+    for (i, pattern) in patterns.iter().enumerate() {
+        if *pattern == ArgumentPattern::Rest {
+            return true;
+        }
+        match arguments.get(i) {
+            Some(argument) if pattern.matches(argument) => {}
+            _ => return false,
+        }
+    }
+    patterns.len() == arguments.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallPattern, ArgumentPattern};
+    use super::super::java::tokenize;
+
+    #[test]
+    fn it_find_calls() {
+        let code = "// Runtime.getRuntime().exec(command);\n\
+                    Runtime.getRuntime().exec(\"ls -l\");\n\
+                    Runtime.getRuntime().exec(\"sh -c \" + command);\n\
+                    this.shell.exec(command, null);\n\
+                    public Process exec(String command) {";
+        let tokens = tokenize(code);
+
+        let pattern = CallPattern::new(String::from("exec"),
+                                       Some(String::from("Runtime.getRuntime()")),
+                                       Some(vec![ArgumentPattern::NonConstant,
+                                                 ArgumentPattern::Rest]))
+            .unwrap();
+        let calls = pattern.find_calls(&tokens);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(&code[calls[0].0..calls[0].1],
+                   "Runtime.getRuntime().exec(\"sh -c \" + command)");
+
+        let pattern = CallPattern::new(String::from("exec"),
+                                       None,
+                                       Some(vec![ArgumentPattern::Any]))
+            .unwrap();
+        let calls = pattern.find_calls(&tokens);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(&code[calls[0].0..calls[0].1], "exec(\"ls -l\")");
+
+        let pattern = CallPattern::new(String::from("exec"),
+                                       Some(String::from("shell")),
+                                       Some(vec![ArgumentPattern::NonConstant,
+                                                 ArgumentPattern::Constant]))
+            .unwrap();
+        let calls = pattern.find_calls(&tokens);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(&code[calls[0].0..calls[0].1], "this.shell.exec(command, null)");
+    }
+
+    #[test]
+    fn it_call_pattern() {
+        assert!(CallPattern::new(String::from("exec("), None, None).is_err());
+        assert!(CallPattern::new(String::from("exec"),
+                                 None,
+                                 Some(vec![ArgumentPattern::Rest, ArgumentPattern::Any]))
+            .is_err());
+        let pattern = CallPattern::new(String::from("rawQuery"),
+                                       Some(String::from("db . getReadableDatabase ( )")),
+                                       None)
+            .unwrap();
+        let tokens = tokenize("helper.db.getReadableDatabase().rawQuery(query, null)");
+        assert_eq!(pattern.find_calls(&tokens).len(), 1);
+        assert_eq!(pattern.get_method(), "rawQuery");
+    }
+}
@@ -0,0 +1,595 @@
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use config::TaintConfig;
+use results::{Results, Vulnerability, RuleMetadata};
+use super::code::get_line_for;
+use super::java::{Token, TokenKind, tokenize, is_call, get_assignment, get_assigned_variable,
+                  split_methods, get_java_files, get_class_name, get_enclosing_method,
+                  describe_location};
+use super::structural::get_closing_index;
+use super::read_text_file;
+
+/// Maximum number of statements kept in the path of a flow
+const MAX_STEPS: usize = 10;
+
+/// Maximum number of characters of each statement shown in the path of a flow
+const MAX_STATEMENT_LENGTH: usize = 80;
+
+/// Maximum number of lines between the source and the sink for the code of the finding to span
+/// both of them
+const MAX_SNIPPET_LINES: usize = 20;
+
+/// Calls returning data that can be controlled by an attacker, with a description of the data
+const SOURCES: [(&'static str, &'static str); 13] = [("getStringExtra", "an Intent extra"),
+                                                     ("getStringArrayExtra", "an Intent extra"),
+                                                     ("getCharSequenceExtra", "an Intent extra"),
+                                                     ("getBundleExtra", "an Intent extra"),
+                                                     ("getExtras", "an Intent extra"),
+                                                     ("getData", "a deep link URI"),
+                                                     ("getDataString", "a deep link URI"),
+                                                     ("getQueryParameter", "a deep link URI"),
+                                                     ("getPathSegments", "a deep link URI"),
+                                                     ("getLastPathSegment", "a deep link URI"),
+                                                     ("getInputStream", "network input"),
+                                                     ("getEntity", "network input"),
+                                                     ("getResponseMessage", "network input")];
+
+/// Calls that must not receive untrusted data as arguments
+const SINKS: [(&'static str, SinkKind); 13] = [("execSQL", SinkKind::Sql),
+                                               ("rawQuery", SinkKind::Sql),
+                                               ("exec", SinkKind::Command),
+                                               ("ProcessBuilder", SinkKind::Command),
+                                               ("loadUrl", SinkKind::WebView),
+                                               ("loadData", SinkKind::WebView),
+                                               ("loadDataWithBaseURL", SinkKind::WebView),
+                                               ("evaluateJavascript", SinkKind::WebView),
+                                               ("File", SinkKind::File),
+                                               ("FileInputStream", SinkKind::File),
+                                               ("FileOutputStream", SinkKind::File),
+                                               ("FileWriter", SinkKind::File),
+                                               ("openFileOutput", SinkKind::File)];
+
+/// Calls whose return value is safe to use in the sinks, even with untrusted arguments
+const SANITIZERS: [&'static str; 9] = ["parseInt",
+                                       "parseLong",
+                                       "parseDouble",
+                                       "parseFloat",
+                                       "parseBoolean",
+                                       "sqlEscapeString",
+                                       "encode",
+                                       "quote",
+                                       "escapeHtml"];
+
+/// Calls that add their arguments to the object they are called on, such as
+/// `builder.append(data)`
+const PROPAGATORS: [&'static str; 5] = ["append", "add", "addAll", "put", "insert"];
+
+/// Kind of sensitive operation performed by a sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SinkKind {
+    Sql,
+    Command,
+    WebView,
+    File,
+    /// Sinks added in the configuration
+    Custom,
+}
+
+impl SinkKind {
+    fn get_name(&self) -> &'static str {
+        match *self {
+            SinkKind::Sql => "Untrusted data in SQL query",
+            SinkKind::Command => "Untrusted data in command execution",
+            SinkKind::WebView => "Untrusted data loaded in WebView",
+            SinkKind::File => "Untrusted data in file path",
+            SinkKind::Custom => "Untrusted data in sensitive call",
+        }
+    }
+
+    fn get_criticity(&self) -> Criticity {
+        match *self {
+            SinkKind::Sql | SinkKind::Command => Criticity::High,
+            SinkKind::WebView | SinkKind::File | SinkKind::Custom => Criticity::Medium,
+        }
+    }
+
+    fn get_description(&self) -> &'static str {
+        match *self {
+            SinkKind::Sql => {
+                "an SQL query, which could allow an attacker to read or modify the database of \
+                 the application. Selection arguments should be used instead of concatenating \
+                 the data in the query."
+            }
+            SinkKind::Command => {
+                "a command execution, which could allow an attacker to run arbitrary commands \
+                 with the permissions of the application."
+            }
+            SinkKind::WebView => {
+                "a WebView, which could allow an attacker to load arbitrary content or run \
+                 JavaScript in it."
+            }
+            SinkKind::File => {
+                "a file path, which could allow an attacker to read or write files outside of \
+                 the expected directory through path traversal."
+            }
+            SinkKind::Custom => {
+                "a method configured as a sink in the [taint] section of the configuration."
+            }
+        }
+    }
+
+    fn get_cwe(&self) -> Option<&'static str> {
+        match *self {
+            SinkKind::Sql => Some("CWE-89"),
+            SinkKind::Command => Some("CWE-78"),
+            SinkKind::WebView => Some("CWE-79"),
+            SinkKind::File => Some("CWE-22"),
+            SinkKind::Custom => None,
+        }
+    }
+}
+
+/// Sources, sinks and sanitizers of the analysis, built-in and configured
+struct TaintSpec {
+    sources: BTreeMap<String, String>,
+    sinks: BTreeMap<String, SinkKind>,
+    sanitizers: BTreeSet<String>,
+}
+
+impl TaintSpec {
+    fn new(config: &TaintConfig) -> TaintSpec {
+        let mut sources: BTreeMap<_, _> = SOURCES.iter()
+            .map(|&(method, data)| (String::from(method), String::from(data)))
+            .collect();
+        for method in config.get_sources() {
+            let _ = sources.insert(method.clone(), format!("the {}() method", method));
+        }
+        let mut sinks: BTreeMap<_, _> = SINKS.iter()
+            .map(|&(method, kind)| (String::from(method), kind))
+            .collect();
+        for method in config.get_sinks() {
+            let _ = sinks.insert(method.clone(), SinkKind::Custom);
+        }
+        let sanitizers = SANITIZERS.iter()
+            .map(|method| String::from(*method))
+            .chain(config.get_sanitizers().iter().cloned())
+            .collect();
+
+        TaintSpec {
+            sources: sources,
+            sinks: sinks,
+            sanitizers: sanitizers,
+        }
+    }
+}
+
+/// Statement in the path of a flow
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    line: usize,
+    statement: String,
+}
+
+impl Step {
+    fn new(code: &str, statement: &[Token]) -> Step {
+        let start = statement[0].get_start();
+        let text = code[start..statement[statement.len() - 1].get_end()]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        Step {
+            line: get_line_for(start, code),
+            statement: if text.chars().count() > MAX_STATEMENT_LENGTH {
+                format!("{}...", text.chars().take(MAX_STATEMENT_LENGTH).collect::<String>())
+            } else {
+                text
+            },
+        }
+    }
+}
+
+/// Untrusted data held by a variable, with the statements it went through
+#[derive(Debug, Clone)]
+struct Taint {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl Taint {
+    /// Adds a statement to the path, dropping the oldest ones after the source if it's too long
+    fn push(&mut self, step: Step) {
+        if self.steps.len() == MAX_STEPS {
+            let _ = self.steps.remove(1);
+        }
+        self.steps.push(step);
+    }
+}
+
+/// Flow of untrusted data from a source to a sink
+#[derive(Debug)]
+struct Flow {
+    source: String,
+    sink: SinkKind,
+    method: String,
+    /// Index of the sink call in the code
+    index: usize,
+    steps: Vec<Step>,
+}
+
+/// Method being analyzed, with its tainted variables
+struct MethodState<'a> {
+    name: &'a str,
+    tainted: BTreeMap<&'a str, Taint>,
+}
+
+/// Flows found in the code, and methods returning untrusted data
+struct Analysis<'a, 's> {
+    spec: &'s TaintSpec,
+    returns: &'s BTreeMap<&'a str, Taint>,
+    flows: Vec<Flow>,
+    tainted_returns: BTreeMap<&'a str, Taint>,
+}
+
+impl<'a, 's> Analysis<'a, 's> {
+    /// Follows the untrusted data through a statement of a method
+    fn analyze_statement(&mut self,
+                         code: &'a str,
+                         statement: &[Token<'a>],
+                         method: &mut MethodState<'a>) {
+        if statement.is_empty() {
+            return;
+        }
+        let step = Step::new(code, statement);
+
+        for (i, token) in statement.iter().enumerate() {
+            let kind = match self.spec.sinks.get(token.get_text()) {
+                Some(k) if is_call(statement, i) => *k,
+                _ => continue,
+            };
+            let close = match get_closing_index(statement, i + 1) {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some(mut taint) = self.get_taint(&statement[i + 2..close], method) {
+                taint.push(step.clone());
+                self.flows.push(Flow {
+                    source: taint.source,
+                    sink: kind,
+                    method: String::from(token.get_text()),
+                    index: token.get_start(),
+                    steps: taint.steps,
+                });
+            }
+        }
+
+        if statement[0].get_text() == "return" {
+            if let Some(mut taint) = self.get_taint(&statement[1..], method) {
+                taint.push(step);
+                let _ = self.tainted_returns.entry(method.name).or_insert(taint);
+            }
+            return;
+        }
+
+        if let Some((assignment, compound)) = get_assignment(statement) {
+            let variable = match get_assigned_variable(&statement[..assignment]) {
+                Some(v) => v,
+                None => return,
+            };
+            match self.get_taint(&statement[assignment + 1..], method) {
+                Some(mut taint) => {
+                    taint.push(step);
+                    let _ = method.tainted.insert(variable, taint);
+                }
+                None if !compound => {
+                    let _ = method.tainted.remove(variable);
+                }
+                None => {}
+            }
+            return;
+        }
+
+        // Calls such as `builder.append(data).append(more)` taint the builder
+        if statement.len() > 1 && statement[0].get_kind() == TokenKind::Identifier &&
+           statement[1].get_text() == "." {
+            for (i, token) in statement.iter().enumerate() {
+                if !PROPAGATORS.contains(&token.get_text()) || !is_call(statement, i) {
+                    continue;
+                }
+                let close = match get_closing_index(statement, i + 1) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                if let Some(mut taint) = self.get_taint(&statement[i + 2..close], method) {
+                    taint.push(step);
+                    let _ = method.tainted.insert(statement[0].get_text(), taint);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Gets the untrusted data in an expression, if any
+    fn get_taint(&self, expression: &[Token], method: &MethodState) -> Option<Taint> {
+        if expression.iter()
+            .enumerate()
+            .any(|(i, t)| self.spec.sanitizers.contains(t.get_text()) && is_call(expression, i)) {
+            return None;
+        }
+        for (i, token) in expression.iter().enumerate() {
+            if token.get_kind() != TokenKind::Identifier {
+                continue;
+            }
+            if is_call(expression, i) {
+                if let Some(data) = self.spec.sources.get(token.get_text()) {
+                    return Some(Taint {
+                        source: data.clone(),
+                        steps: Vec::new(),
+                    });
+                }
+                if let Some(taint) = self.returns.get(token.get_text()) {
+                    return Some(taint.clone());
+                }
+            } else if let Some(taint) = method.tainted.get(token.get_text()) {
+                return Some(taint.clone());
+            }
+        }
+        None
+    }
+}
+
+/// Analyzes the methods of the code, returning the flows found and the methods returning
+/// untrusted data
+///
+/// Each method is analyzed on its own, and the taint of its variables is only propagated
+/// statement by statement, without following branches or loops.
+fn analyze_methods<'a>(code: &'a str,
+                       tokens: &[Token<'a>],
+                       spec: &TaintSpec,
+                       returns: &BTreeMap<&'a str, Taint>)
+                       -> (Vec<Flow>, BTreeMap<&'a str, Taint>) {
+    let mut analysis = Analysis {
+        spec: spec,
+        returns: returns,
+        flows: Vec::new(),
+        tainted_returns: BTreeMap::new(),
+    };
+    for body in split_methods(code, tokens) {
+        let mut method = MethodState {
+            name: body.get_name(),
+            tainted: BTreeMap::new(),
+        };
+        for statement in body.get_statements() {
+            analysis.analyze_statement(code, statement, &mut method);
+        }
+    }
+    (analysis.flows, analysis.tainted_returns)
+}
+
+/// Finds the flows of untrusted data from the sources to the sinks of the code
+///
+/// If `inter_procedural` is set, the methods of the class returning untrusted data are
+/// considered sources too.
+fn find_flows(code: &str, spec: &TaintSpec, inter_procedural: bool) -> Vec<Flow> {
+    let tokens = tokenize(code);
+    let returns = if inter_procedural {
+        analyze_methods(code, &tokens, spec, &BTreeMap::new()).1
+    } else {
+        BTreeMap::new()
+    };
+    analyze_methods(code, &tokens, spec, &returns).0
+}
+
+/// Finds the calls to the given methods receiving untrusted data, returning the called method,
+/// the index of the call in the code and a description of the data
+///
+/// The sources and sanitizers of the configuration are used, but the given methods replace the
+/// sinks.
+pub fn find_untrusted_calls(code: &str,
+                            methods: &[String],
+                            config: &TaintConfig)
+                            -> Vec<(String, usize, String)> {
+    let mut spec = TaintSpec::new(config);
+    spec.sinks = methods.iter().map(|m| (m.clone(), SinkKind::Custom)).collect();
+    find_flows(code, &spec, config.is_inter_procedural())
+        .into_iter()
+        .map(|flow| (flow.method, flow.index, flow.source))
+        .collect()
+}
+
+/// Looks for flows of untrusted data, such as Intent extras, deep link URIs or network input,
+/// to sensitive calls, such as SQL queries, command executions, WebViews or file accesses
+pub fn taint_analysis(config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Following untrusted data through the code of the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the taint \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let spec = TaintSpec::new(config.get_taint_config());
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        let flows = find_flows(&code,
+                               &spec,
+                               config.get_taint_config().is_inter_procedural());
+        if flows.is_empty() {
+            continue;
+        }
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        let class = get_class_name(relative)
+            .unwrap_or_else(|| relative.to_string_lossy().into_owned());
+        for flow in flows {
+            add_flow_finding(&flow, &code, relative, &class, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The taint flows were analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Taint flows analyzed.");
+    }
+}
+
+/// Adds a finding for a flow of untrusted data to a sink
+fn add_flow_finding(flow: &Flow,
+                    code: &str,
+                    path: &Path,
+                    class: &str,
+                    config: &Config,
+                    results: &mut Results) {
+    let location = describe_location(class, get_enclosing_method(code, flow.index));
+    let statements = flow.steps
+        .iter()
+        .map(|step| format!("line {}: {}", step.line + 1, step.statement))
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    let description = format!("Data from {} reaches the {}() call in {} without being \
+                               sanitized, through the following statements: {}. The data is \
+                               used in {}",
+                              flow.source,
+                              flow.method,
+                              location,
+                              statements,
+                              flow.sink.get_description());
+
+    let end = get_line_for(flow.index, code);
+    let start = flow.steps.first().map_or(end, |step| step.line);
+    let start = if start <= end && end - start <= MAX_SNIPPET_LINES {
+        start
+    } else {
+        end
+    };
+
+    let criticity = flow.sink.get_criticity();
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let mut vuln = Vulnerability::new(criticity,
+                                      String::from(flow.sink.get_name()),
+                                      description,
+                                      Some(path),
+                                      Some(start),
+                                      Some(end),
+                                      Some(get_code(code, start, end)));
+    if let Some(cwe) = flow.sink.get_cwe() {
+        vuln.set_metadata(RuleMetadata::new(vec![String::from(cwe)],
+                                            vec![String::from("MASVS-CODE-4")],
+                                            vec![String::from("M4")],
+                                            Vec::new(),
+                                            None));
+    }
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+    use super::{TaintSpec, SinkKind, find_flows};
+
+    #[test]
+    fn it_find_flows() {
+        let config: Config = Default::default();
+        let spec = TaintSpec::new(config.get_taint_config());
+        let code = "public class Store {\n\
+                    public void onCreate(Bundle state) {\n\
+                    String id = getIntent().getStringExtra(\"id\");\n\
+                    String query = \"SELECT * FROM users WHERE id = \" + id;\n\
+                    // db.execSQL(id);\n\
+                    this.db.rawQuery(query, null);\n\
+                    int number = Integer.parseInt(id);\n\
+                    this.db.rawQuery(\"SELECT * FROM users WHERE id = \" + number, null);\n\
+                    query = \"SELECT * FROM users\";\n\
+                    this.db.rawQuery(query, null);\n\
+                    StringBuilder builder = new StringBuilder(\"file://\");\n\
+                    builder.append(\"/sdcard/\").append(id);\n\
+                    webView.loadUrl(builder.toString());\n\
+                    }\n\
+                    public void other() {\n\
+                    this.db.execSQL(query);\n\
+                    }\n\
+                    }";
+        let flows = find_flows(code, &spec, false);
+        assert_eq!(flows.len(), 2);
+
+        assert_eq!(flows[0].source, "an Intent extra");
+        assert_eq!(flows[0].sink, SinkKind::Sql);
+        assert_eq!(flows[0].method, "rawQuery");
+        assert_eq!(flows[0].index, code.find("rawQuery").unwrap());
+        assert_eq!(flows[0].steps.iter().map(|s| s.line).collect::<Vec<_>>(),
+                   vec![2, 3, 5]);
+        assert_eq!(flows[0].steps[1].statement,
+                   "String query = \"SELECT * FROM users WHERE id = \" + id");
+
+        assert_eq!(flows[1].sink, SinkKind::WebView);
+        assert_eq!(flows[1].steps.iter().map(|s| s.line).collect::<Vec<_>>(),
+                   vec![2, 11, 12]);
+    }
+
+    #[test]
+    fn it_inter_procedural() {
+        let config: Config = Default::default();
+        let spec = TaintSpec::new(config.get_taint_config());
+        let code = "class Runner {\n\
+                    private String getCommand() {\n\
+                    Uri uri = getIntent().getData();\n\
+                    return uri.getQueryParameter(\"cmd\");\n\
+                    }\n\
+                    void run() {\n\
+                    Runtime.getRuntime().exec(\"sh -c \" + getCommand());\n\
+                    new Thread(new Runnable() {\n\
+                    public void run() {\n\
+                    String path = getCommand();\n\
+                    }\n\
+                    });\n\
+                    }\n\
+                    }";
+        assert!(find_flows(code, &spec, false).is_empty());
+
+        let flows = find_flows(code, &spec, true);
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source, "a deep link URI");
+        assert_eq!(flows[0].sink, SinkKind::Command);
+        assert_eq!(flows[0].steps.iter().map(|s| s.line).collect::<Vec<_>>(),
+                   vec![2, 3, 6]);
+
+        let spec = TaintSpec {
+            sources: vec![(String::from("getCommand"), String::from("a command"))]
+                .into_iter()
+                .collect(),
+            sinks: vec![(String::from("exec"), SinkKind::Custom)].into_iter().collect(),
+            sanitizers: Default::default(),
+        };
+        let flows = find_flows(code, &spec, false);
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source, "a command");
+        assert_eq!(flows[0].sink, SinkKind::Custom);
+    }
+}
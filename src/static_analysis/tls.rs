@@ -0,0 +1,575 @@
+use std::result;
+use std::path::PathBuf;
+
+use regex::Regex;
+use serde::ser::{Serialize, Serializer};
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability, ReportSection};
+use super::manifest::Manifest;
+use super::network_security::load_network_security_config;
+use super::code::get_line_for;
+use super::java::{get_java_files, get_class_name, get_enclosing_method, get_block_end,
+                  get_compact_block, get_call_arguments, describe_location};
+use super::read_text_file;
+
+/// Order of the TLS posture section in the report
+const SECTION_ORDER: u32 = 70;
+
+/// Names that show that the file configures TLS connections
+const TLS_MARKERS: [&'static str; 5] = ["javax.net.ssl",
+                                        "TrustManager",
+                                        "HostnameVerifier",
+                                        "SSLContext",
+                                        "CertificatePinner"];
+
+/// TLS validation disabled in the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsIssue {
+    TrustAllCertificates,
+    PermissiveHostnameVerifier,
+    InsecureSslContext,
+}
+
+impl TlsIssue {
+    pub fn get_criticity(&self) -> Criticity {
+        match *self {
+            TlsIssue::TrustAllCertificates |
+            TlsIssue::InsecureSslContext => Criticity::Critical,
+            TlsIssue::PermissiveHostnameVerifier => Criticity::High,
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        match *self {
+            TlsIssue::TrustAllCertificates => "Trust manager accepts all certificates",
+            TlsIssue::PermissiveHostnameVerifier => "Hostname verifier accepts all hosts",
+            TlsIssue::InsecureSslContext => "SSL context without certificate validation",
+        }
+    }
+
+    /// Gets the description of the issue found in the given location
+    pub fn get_description(&self, location: &str) -> String {
+        match *self {
+            TlsIssue::TrustAllCertificates => {
+                format!("The checkServerTrusted() method of a trust manager does not check the \
+                         server certificates in {}. Connections using it accept any \
+                         certificate, so they can be intercepted with a Man in the Middle \
+                         attack.",
+                        location)
+            }
+            TlsIssue::PermissiveHostnameVerifier => {
+                format!("A hostname verifier that accepts any host is used in {}. Connections \
+                         using it accept certificates issued for any other domain, so they can \
+                         be intercepted with a Man in the Middle attack.",
+                        location)
+            }
+            TlsIssue::InsecureSslContext => {
+                format!("An SSL context is initialized with a trust manager that accepts all \
+                         certificates in {}. All the sockets and connections created with it \
+                         can be intercepted with a Man in the Middle attack.",
+                        location)
+            }
+        }
+    }
+}
+
+/// TLS issue found in the code, as listed in the report
+pub struct TlsFinding {
+    issue: TlsIssue,
+    file: String,
+    line: usize,
+    location: String,
+}
+
+impl Serialize for TlsFinding {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("TlsFinding", 5));
+        try!(serializer.serialize_struct_elt(&mut state, "issue", self.issue.get_label()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "criticity",
+                                             self.issue.get_criticity()));
+        try!(serializer.serialize_struct_elt(&mut state, "file", self.file.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "line", self.line));
+        try!(serializer.serialize_struct_elt(&mut state, "location", self.location.as_str()));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Certificate pinning configured in the application
+pub struct CertificatePin {
+    source: &'static str,
+    location: String,
+    hosts: Vec<String>,
+}
+
+impl CertificatePin {
+    pub fn get_source(&self) -> &str {
+        self.source
+    }
+
+    pub fn get_location(&self) -> &str {
+        self.location.as_str()
+    }
+
+    /// Gets the pinned hosts, if they could be found
+    pub fn get_hosts(&self) -> &[String] {
+        self.hosts.as_slice()
+    }
+}
+
+impl Serialize for CertificatePin {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("CertificatePin", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "source", self.source));
+        try!(serializer.serialize_struct_elt(&mut state, "location", self.location.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "hosts", &self.hosts));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// TLS posture of the application: disabled validations and certificate pinning
+pub struct TlsPosture {
+    findings: Vec<TlsFinding>,
+    pins: Vec<CertificatePin>,
+}
+
+impl TlsPosture {
+    /// Checks if the validation of the server certificates is disabled anywhere
+    pub fn is_validation_disabled(&self) -> bool {
+        self.findings.iter().any(|f| f.issue != TlsIssue::PermissiveHostnameVerifier)
+    }
+
+    /// Checks if the hostname verification is disabled anywhere
+    pub fn is_hostname_verification_disabled(&self) -> bool {
+        self.findings.iter().any(|f| f.issue == TlsIssue::PermissiveHostnameVerifier)
+    }
+
+    /// Checks if certificates are pinned anywhere
+    pub fn has_pinning(&self) -> bool {
+        !self.pins.is_empty()
+    }
+}
+
+impl Serialize for TlsPosture {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("TlsPosture", 5));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "validation_disabled",
+                                             self.is_validation_disabled()));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "hostname_verification_disabled",
+                                             self.is_hostname_verification_disabled()));
+        try!(serializer.serialize_struct_elt(&mut state, "pinning", self.has_pinning()));
+        try!(serializer.serialize_struct_elt(&mut state, "findings", &self.findings));
+        try!(serializer.serialize_struct_elt(&mut state, "pins", &self.pins));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Regular expressions used to find TLS trust managers, hostname verifiers and pinning
+struct Detector {
+    check_server_trusted: Regex,
+    anonymous_trust_manager: Regex,
+    class: Regex,
+    verify: Regex,
+    allow_all_verifier: Regex,
+    context_init: Regex,
+    certificate_pinner: Regex,
+    pin: Regex,
+}
+
+impl Detector {
+    fn new() -> Detector {
+        Detector {
+            check_server_trusted: Regex::new(r"\bcheckServerTrusted\s*\([^)]*\)[^{;]*\{").unwrap(),
+            anonymous_trust_manager:
+                Regex::new(r"\bnew\s+(?:X509(?:Extended)?)?TrustManager\s*\(\s*\)\s*\{").unwrap(),
+            class: Regex::new(r"\bclass\s+(\w+)").unwrap(),
+            verify: Regex::new(concat!(r"\bboolean\s+verify\s*\(\s*String\s+\w+\s*,",
+                                       r"\s*SSLSession\s+\w+\s*\)[^{;]*\{"))
+                .unwrap(),
+            allow_all_verifier: Regex::new(concat!(r"\bnew\s+(?:AllowAllHostnameVerifier|",
+                                                   r"NoopHostnameVerifier)\s*\(|",
+                                                   r"\bNoopHostnameVerifier\s*\.\s*INSTANCE\b"))
+                .unwrap(),
+            context_init: Regex::new(r"\.init\s*\(").unwrap(),
+            certificate_pinner: Regex::new(r"\bCertificatePinner\s*\.\s*Builder\s*\(").unwrap(),
+            pin: Regex::new(r#"\.add\s*\(\s*"([^"]+)"\s*,\s*"sha(?:1|256)/"#).unwrap(),
+        }
+    }
+}
+
+/// Finds the trust managers that accept all certificates, with their position in the code and
+/// the name of their class, or `None` if they are anonymous classes
+fn find_trust_all_managers(code: &str,
+                           detector: &Detector)
+                           -> Vec<(usize, usize, Option<String>)> {
+    let mut managers = Vec::new();
+    for (s, e) in detector.check_server_trusted.find_iter(code) {
+        match get_compact_block(code, e - 1) {
+            Some(ref body) if body.is_empty() || body == "return;" => {}
+            _ => continue,
+        }
+        let anonymous = detector.anonymous_trust_manager
+            .find_iter(&code[..s])
+            .any(|(_, ae)| get_block_end(code, ae - 1).map_or(false, |end| end > s));
+        let class = if anonymous {
+            None
+        } else {
+            detector.class
+                .captures_iter(&code[..s])
+                .last()
+                .and_then(|caps| caps.at(1).map(String::from))
+        };
+        managers.push((s, e, class));
+    }
+    managers
+}
+
+/// Finds the hostname verifiers that accept all hosts, with their position in the code
+fn find_permissive_verifiers(code: &str, detector: &Detector) -> Vec<(usize, usize)> {
+    let mut verifiers: Vec<_> = detector.verify
+        .find_iter(code)
+        .filter(|&(_, e)| get_compact_block(code, e - 1).map_or(false, |b| b == "returntrue;"))
+        .collect();
+    verifiers.extend(detector.allow_all_verifier.find_iter(code));
+    verifiers.sort();
+    verifiers
+}
+
+/// Finds the SSL contexts initialized with trust managers that accept all certificates, with
+/// their position in the code
+fn find_insecure_contexts(code: &str,
+                          detector: &Detector,
+                          trust_all_classes: &[String],
+                          anonymous_trust_manager: bool)
+                          -> Vec<(usize, usize)> {
+    if !code.contains("SSLContext") {
+        return Vec::new();
+    }
+    let uses_trust_all = anonymous_trust_manager ||
+                         trust_all_classes.iter().any(|c| code.contains(&format!("new {}(", c)));
+    if !uses_trust_all {
+        return Vec::new();
+    }
+    detector.context_init
+        .find_iter(code)
+        .filter(|&(_, e)| {
+            match get_call_arguments(code, e - 1) {
+                Some(ref arguments) if arguments.len() == 3 => arguments[1] != "null",
+                _ => false,
+            }
+        })
+        .collect()
+}
+
+/// Finds the OkHttp certificate pinner built in the code, with its position and the pinned hosts
+fn find_certificate_pinner(code: &str, detector: &Detector) -> Option<(usize, Vec<String>)> {
+    detector.certificate_pinner.find(code).map(|(s, _)| {
+        let mut hosts = Vec::new();
+        for caps in detector.pin.captures_iter(code) {
+            let host = String::from(caps.at(1).unwrap());
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        (s, hosts)
+    })
+}
+
+/// Audits the TLS configuration of the application: trust managers, hostname verifiers and SSL
+/// contexts that disable the validation of the connections, correlated with the certificate
+/// pinning configured in the network security configuration or in the code
+pub fn tls_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Checking the TLS configuration of the application.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the TLS analysis, \
+                                   it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+
+    let mut sources = Vec::new();
+    for path in files {
+        match read_text_file(&path) {
+            Ok(Some(code)) => {
+                if TLS_MARKERS.iter().any(|m| code.contains(m)) {
+                    let relative = path.strip_prefix(&dist_folder).unwrap().to_path_buf();
+                    sources.push((relative, code));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+            }
+        }
+    }
+
+    let detector = Detector::new();
+    let mut pins = Vec::new();
+    if let Some(nsc) = manifest.and_then(|m| load_network_security_config(m, config)) {
+        let mut hosts = Vec::new();
+        if !nsc.get_base_config().get_pins().is_empty() {
+            hosts.push(String::from("*"));
+        }
+        for domain in nsc.get_domain_configs().iter().filter(|d| !d.get_pins().is_empty()) {
+            hosts.extend(domain.get_domains().iter().cloned());
+        }
+        if nsc.has_pins() {
+            pins.push(CertificatePin {
+                source: "Network security configuration",
+                location: String::from("the network security configuration"),
+                hosts: hosts,
+            });
+        }
+    }
+
+    let mut issues = Vec::new();
+    let mut trust_all_classes = Vec::new();
+    let mut anonymous_files = Vec::new();
+    for (i, &(ref path, ref code)) in sources.iter().enumerate() {
+        let class = get_class_name(path).unwrap_or_else(|| path.to_string_lossy().into_owned());
+        for (s, e, trust_all_class) in find_trust_all_managers(code, &detector) {
+            match trust_all_class {
+                Some(c) => trust_all_classes.push(c),
+                None => anonymous_files.push(i),
+            }
+            issues.push((TlsIssue::TrustAllCertificates, i, s, e));
+        }
+        for (s, e) in find_permissive_verifiers(code, &detector) {
+            issues.push((TlsIssue::PermissiveHostnameVerifier, i, s, e));
+        }
+        if let Some((s, hosts)) = find_certificate_pinner(code, &detector) {
+            pins.push(CertificatePin {
+                source: "OkHttp CertificatePinner",
+                location: describe_location(&class, get_enclosing_method(code, s)),
+                hosts: hosts,
+            });
+        }
+    }
+    for (i, &(_, ref code)) in sources.iter().enumerate() {
+        for (s, e) in find_insecure_contexts(code,
+                                             &detector,
+                                             &trust_all_classes,
+                                             anonymous_files.contains(&i)) {
+            issues.push((TlsIssue::InsecureSslContext, i, s, e));
+        }
+    }
+    issues.sort_by_key(|&(_, i, s, _)| (i, s));
+
+    let mitigation = get_pinning_note(&pins);
+    let mut findings = Vec::new();
+    for (issue, i, s, e) in issues {
+        let (ref path, ref code) = sources[i];
+        let class = get_class_name(path).unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let location = describe_location(&class, get_enclosing_method(code, s));
+        let description = format!("{} {}", issue.get_description(&location), mitigation);
+        let start_line = get_line_for(s, code);
+        let end_line = get_line_for(e, code);
+
+        if config.is_verbose() {
+            print_vulnerability(description.as_str(), issue.get_criticity());
+        }
+        let vuln = Vulnerability::new(issue.get_criticity(),
+                                      String::from(issue.get_label()),
+                                      description,
+                                      Some(path),
+                                      Some(start_line),
+                                      Some(end_line),
+                                      Some(get_code(code, start_line, end_line)));
+        results.add_vulnerability(vuln);
+
+        findings.push(TlsFinding {
+            issue: issue,
+            file: path.to_string_lossy().into_owned(),
+            line: start_line + 1,
+            location: location,
+        });
+    }
+
+    let posture = TlsPosture {
+        findings: findings,
+        pins: pins,
+    };
+    results.add_section(get_report_section(&posture));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The TLS configuration was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("TLS configuration analyzed.");
+    }
+}
+
+/// Gets the note added to the TLS findings, explaining if certificate pinning mitigates them
+fn get_pinning_note(pins: &[CertificatePin]) -> String {
+    if pins.is_empty() {
+        return String::from("No certificate pinning was found in the application to mitigate \
+                             it.");
+    }
+    let mut hosts: Vec<_> = pins.iter().flat_map(|p| p.get_hosts().iter().cloned()).collect();
+    hosts.sort();
+    hosts.dedup();
+    format!("Certificate pinning is configured for {}, so the connections to {} could still be \
+             protected if the pins are enforced on them, but any other connection is exposed.",
+            if hosts.is_empty() {
+                String::from("some hosts")
+            } else {
+                hosts.join(", ")
+            },
+            if hosts.len() == 1 {
+                "that host"
+            } else {
+                "those hosts"
+            })
+}
+
+/// Generates the TLS posture section of the report
+fn get_report_section(posture: &TlsPosture) -> ReportSection {
+    let mut section = ReportSection::new("tls_posture", "TLS posture", SECTION_ORDER);
+    section.set_data(posture);
+    let mut rows = vec![vec![String::from("Certificate validation"),
+                             String::new(),
+                             String::from(if posture.is_validation_disabled() {
+                                 "Disabled"
+                             } else {
+                                 "Not modified"
+                             })],
+                        vec![String::from("Hostname verification"),
+                             String::new(),
+                             String::from(if posture.is_hostname_verification_disabled() {
+                                 "Disabled"
+                             } else {
+                                 "Not modified"
+                             })],
+                        vec![String::from("Certificate pinning"),
+                             String::new(),
+                             String::from(if posture.has_pinning() {
+                                 "Found"
+                             } else {
+                                 "Not found"
+                             })]];
+    for finding in &posture.findings {
+        rows.push(vec![Results::html_escape(finding.issue.get_label()),
+                       Results::html_escape(&format!("{} ({}:{})",
+                                                     finding.location,
+                                                     finding.file,
+                                                     finding.line)),
+                       format!("{}", finding.issue.get_criticity())]);
+    }
+    for pin in &posture.pins {
+        rows.push(vec![Results::html_escape(&format!("Pinning ({})", pin.get_source())),
+                       Results::html_escape(pin.get_location()),
+                       Results::html_escape(&pin.get_hosts().join(", "))]);
+    }
+    section.set_html_table(&["Check", "Location", "Result"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Detector, find_trust_all_managers, find_permissive_verifiers,
+                find_insecure_contexts, find_certificate_pinner};
+
+    #[test]
+    fn it_find_trust_all_managers() {
+        let code = "public class TrustAll implements X509TrustManager {
+    public void checkClientTrusted(X509Certificate[] chain, String type) {
+    }
+
+    public void checkServerTrusted(X509Certificate[] chain, String type)
+        throws CertificateException {
+        // Trust everything
+    }
+}
+
+class Strict implements X509TrustManager {
+    public void checkServerTrusted(X509Certificate[] chain, String type)
+        throws CertificateException {
+        this.delegate.checkServerTrusted(chain, type);
+    }
+}
+
+class Client {
+    TrustManager[] managers = new TrustManager[] { new X509TrustManager() {
+        public void checkServerTrusted(X509Certificate[] chain, String type) {
+            return;
+        }
+    } };
+}";
+        let managers: Vec<_> = find_trust_all_managers(code, &Detector::new())
+            .into_iter()
+            .map(|(_, _, class)| class)
+            .collect();
+        assert_eq!(managers, vec![Some(String::from("TrustAll")), None]);
+    }
+
+    #[test]
+    fn it_find_permissive_verifiers() {
+        let code = "HostnameVerifier a = new HostnameVerifier() {
+    public boolean verify(String hostname, SSLSession session) {
+        return true;
+    }
+};
+HostnameVerifier b = new HostnameVerifier() {
+    public boolean verify(String hostname, SSLSession session) {
+        return hostname.equals(\"example.com\");
+    }
+};
+connection.setHostnameVerifier(new AllowAllHostnameVerifier());";
+        assert_eq!(find_permissive_verifiers(code, &Detector::new()).len(), 2);
+    }
+
+    #[test]
+    fn it_find_insecure_contexts() {
+        let detector = Detector::new();
+        let code = "SSLContext context = SSLContext.getInstance(\"TLS\");
+context.init(null, new TrustManager[] { new TrustAll() }, new SecureRandom());
+SSLContext other = SSLContext.getInstance(\"TLS\");
+other.init(null, null, null);";
+        let classes = vec![String::from("TrustAll")];
+        assert_eq!(find_insecure_contexts(code, &detector, &classes, false).len(), 1);
+        assert!(find_insecure_contexts(code, &detector, &[], false).is_empty());
+        assert_eq!(find_insecure_contexts(code, &detector, &[], true).len(), 1);
+    }
+
+    #[test]
+    fn it_find_certificate_pinner() {
+        let detector = Detector::new();
+        let code = "CertificatePinner pinner = new CertificatePinner.Builder()
+    .add(\"api.example.com\", \"sha256/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\")
+    .add(\"api.example.com\", \"sha256/BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB=\")
+    .add(\"*.example.org\", \"sha1/CCCCCCCCCCCCCCCCCCCCCCCCCCC=\")
+    .build();";
+        let (_, hosts) = find_certificate_pinner(code, &detector).unwrap();
+        assert_eq!(hosts,
+                   vec![String::from("api.example.com"), String::from("*.example.org")]);
+        assert!(find_certificate_pinner("OkHttpClient client = new OkHttpClient();", &detector)
+            .is_none());
+    }
+}
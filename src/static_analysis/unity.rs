@@ -0,0 +1,381 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::result;
+use std::path::{Path, PathBuf};
+
+use serde::ser::{Serialize, Serializer};
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Result, print_warning, file_exists};
+use results::{Results, ReportSection};
+use super::native::{NativeLibrary, extract_strings, has_library};
+use super::frameworks::get_strings_code;
+use super::advisories::{load_advisory_database, add_advisory_findings};
+
+/// Order of the Unity and .NET section in the report
+const SECTION_ORDER: u32 = 99;
+
+/// Data folder of Unity games
+const UNITY_DATA: &'static str = "assets/bin/Data";
+
+/// IL2CPP metadata, with the names and string literals of the C# code compiled to native code
+const IL2CPP_METADATA: &'static str = "assets/bin/Data/Managed/Metadata/global-metadata.dat";
+
+/// Magic number of the IL2CPP metadata files
+const IL2CPP_METADATA_MAGIC: [u8; 4] = [0xaf, 0x1b, 0xb1, 0xfa];
+
+/// Files of the Unity data folder that have the version of the engine in their header
+const VERSION_FILES: [&'static str; 3] = ["assets/bin/Data/globalgamemanagers",
+                                          "assets/bin/Data/data.unity3d",
+                                          "assets/bin/Data/unity default resources"];
+
+/// Maximum number of bytes read from the header of the files with the engine version
+const VERSION_HEADER_SIZE: u64 = 64 * 1024;
+
+/// Folders with the .NET assemblies of Unity games using Mono and of Xamarin applications
+const ASSEMBLY_FOLDERS: [&'static str; 2] = ["assets/bin/Data/Managed", "assets/assemblies"];
+
+/// Prefixes of the assemblies of the .NET runtime and frameworks, that are not scanned
+const FRAMEWORK_ASSEMBLIES: [&'static str; 8] = ["System",
+                                                 "Mono.",
+                                                 "mscorlib",
+                                                 "netstandard",
+                                                 "Microsoft.",
+                                                 "Xamarin.",
+                                                 "UnityEngine",
+                                                 "Unity."];
+
+/// Maximum size of the metadata and assembly files that will be scanned
+const MAX_ASSEMBLY_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Minimum length of the strings extracted from the metadata and the assemblies
+const MIN_STRING_LENGTH: usize = 6;
+
+/// Scripting backend of a Unity game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptingBackend {
+    /// C# code compiled to native code in `libil2cpp.so`
+    Il2cpp,
+    /// C# code in .NET assemblies run by Mono
+    Mono,
+}
+
+impl ScriptingBackend {
+    fn get_label(&self) -> &'static str {
+        match *self {
+            ScriptingBackend::Il2cpp => "IL2CPP",
+            ScriptingBackend::Mono => "Mono",
+        }
+    }
+}
+
+/// Unity and .NET information of the application
+struct UnityInfo {
+    unity: bool,
+    xamarin: bool,
+    version: Option<String>,
+    backend: Option<ScriptingBackend>,
+    metadata_version: Option<u32>,
+    assemblies: Vec<String>,
+    advisories: usize,
+    notes: Vec<String>,
+}
+
+impl Serialize for UnityInfo {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("UnityInfo", 8));
+        try!(serializer.serialize_struct_elt(&mut state, "unity", self.unity));
+        try!(serializer.serialize_struct_elt(&mut state, "xamarin", self.xamarin));
+        try!(serializer.serialize_struct_elt(&mut state, "version", &self.version));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "scripting_backend",
+                                             self.backend.map(|b| b.get_label())));
+        try!(serializer.serialize_struct_elt(&mut state,
+                                             "metadata_version",
+                                             self.metadata_version));
+        try!(serializer.serialize_struct_elt(&mut state, "assemblies", &self.assemblies));
+        try!(serializer.serialize_struct_elt(&mut state, "advisories", self.advisories));
+        try!(serializer.serialize_struct_elt(&mut state, "notes", &self.notes));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Finds the Unity version, such as `2021.3.16f1`, in the header of a file of the data folder
+fn find_unity_version(header: &[u8]) -> Option<String> {
+    let regex = Regex::new(r"\b(?:[45]|20\d\d|6000)\.\d+\.\d+[abfp]\d+\b").unwrap();
+    let header = String::from_utf8_lossy(header);
+    regex.find(&header).map(|(s, e)| String::from(&header[s..e]))
+}
+
+/// Reads the Unity version from the headers of the files of the data folder
+fn read_unity_version(dist_folder: &Path) -> Option<String> {
+    for file in &VERSION_FILES {
+        let mut header = Vec::new();
+        let read = File::open(dist_folder.join(file))
+            .and_then(|f| f.take(VERSION_HEADER_SIZE).read_to_end(&mut header));
+        if read.is_ok() {
+            if let Some(version) = find_unity_version(&header) {
+                return Some(version);
+            }
+        }
+    }
+    None
+}
+
+/// Gets the version of an IL2CPP metadata file, or `None` if it doesn't start with the magic
+/// number, because it's encrypted or obfuscated
+fn get_metadata_version(data: &[u8]) -> Option<u32> {
+    if data.len() < 8 || !data.starts_with(&IL2CPP_METADATA_MAGIC) {
+        return None;
+    }
+    Some(data[4] as u32 | (data[5] as u32) << 8 | (data[6] as u32) << 16 |
+         (data[7] as u32) << 24)
+}
+
+/// Extracts the UTF-16 strings of at least `min_len` printable ASCII characters, such as the
+/// user strings of .NET assemblies
+fn extract_utf16_strings(data: &[u8], min_len: usize) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut i = 0;
+    // The strings are not aligned, so a byte is skipped after each non-printable character
+    while i + 1 < data.len() {
+        if data[i + 1] == 0 && data[i] >= 0x20 && data[i] < 0x7f {
+            current.push(data[i] as char);
+            i += 2;
+        } else {
+            if current.len() >= min_len {
+                strings.push(current.clone());
+            }
+            current.clear();
+            i += 1;
+        }
+    }
+    if current.len() >= min_len {
+        strings.push(current);
+    }
+    strings
+}
+
+/// Reads a metadata or assembly file to scan its strings, if it's not too big
+fn read_binary(path: &Path) -> Result<Option<Vec<u8>>> {
+    let mut f = try!(File::open(path));
+    if try!(f.metadata()).len() > MAX_ASSEMBLY_SIZE {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    try!(f.read_to_end(&mut bytes));
+    Ok(Some(bytes))
+}
+
+/// Checks if the .NET assembly has the code of the application, and not of the runtime or the
+/// frameworks
+fn is_app_assembly(name: &str) -> bool {
+    name.ends_with(".dll") && !FRAMEWORK_ASSEMBLIES.iter().any(|p| name.starts_with(p))
+}
+
+/// Gets the .NET assemblies with the code of the application, relative to the distribution
+/// folder
+fn get_app_assemblies(dist_folder: &Path) -> Vec<PathBuf> {
+    let mut assemblies = Vec::new();
+    for folder in &ASSEMBLY_FOLDERS {
+        if let Ok(entries) = fs::read_dir(dist_folder.join(folder)) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if is_app_assembly(&name) {
+                    assemblies.push(Path::new(folder).join(name));
+                }
+            }
+        }
+    }
+    assemblies.sort();
+    assemblies
+}
+
+/// Detects Unity games and Xamarin applications, reporting the Unity version, its scripting
+/// backend and its known vulnerabilities, and returns the strings of the IL2CPP metadata and of
+/// the .NET assemblies of the application for the secret and endpoint analyses
+///
+/// The C# code is not decompiled, so the code rules don't cover it.
+pub fn unity_analysis(libraries: &[NativeLibrary],
+                      config: &Config,
+                      results: &mut Results)
+                      -> Vec<(PathBuf, String)> {
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let unity = has_library(libraries, "libunity.so") ||
+                file_exists(dist_folder.join(UNITY_DATA));
+    let xamarin = has_library(libraries, "libmonodroid.so");
+    if !unity && !xamarin {
+        if config.is_verbose() {
+            println!("The application is not a Unity game nor a .NET application.");
+        }
+        return Vec::new();
+    }
+
+    let mut info = UnityInfo {
+        unity: unity,
+        xamarin: xamarin,
+        version: None,
+        backend: None,
+        metadata_version: None,
+        assemblies: Vec::new(),
+        advisories: 0,
+        notes: Vec::new(),
+    };
+    let mut extracted = Vec::new();
+
+    if unity {
+        info.version = read_unity_version(&dist_folder);
+        if config.is_verbose() {
+            match info.version {
+                Some(ref v) => println!("The application is a Unity game, built with Unity {}.", v),
+                None => println!("The application is a Unity game of an unknown Unity version."),
+            }
+        }
+        if let Some(ref version) = info.version {
+            if let Some(database) = load_advisory_database(config) {
+                info.advisories =
+                    add_advisory_findings(&database, "Unity", version, config, results);
+            }
+        }
+
+        if has_library(libraries, "libil2cpp.so") ||
+           file_exists(dist_folder.join(IL2CPP_METADATA)) {
+            info.backend = Some(ScriptingBackend::Il2cpp);
+            match read_binary(&dist_folder.join(IL2CPP_METADATA)) {
+                Ok(Some(metadata)) => {
+                    info.metadata_version = get_metadata_version(&metadata);
+                    if info.metadata_version.is_none() {
+                        info.notes.push(String::from("The global-metadata.dat file does not \
+                                                      start with the IL2CPP magic number, so \
+                                                      it's probably encrypted."));
+                    }
+                    let strings = extract_strings(&metadata, MIN_STRING_LENGTH);
+                    info.notes.push(format!("The {} strings of global-metadata.dat were scanned \
+                                             for secrets and endpoints.",
+                                            strings.len()));
+                    extracted.push((PathBuf::from(IL2CPP_METADATA), get_strings_code(strings)));
+                }
+                Ok(None) => {
+                    info.notes.push(String::from("The global-metadata.dat file is too big to \
+                                                  be scanned."))
+                }
+                Err(e) => {
+                    info.notes.push(String::from("The global-metadata.dat file could not be \
+                                                  read, it may be stored elsewhere."));
+                    if config.is_verbose() {
+                        println!("The IL2CPP metadata could not be read: {}", e);
+                    }
+                }
+            }
+        } else {
+            info.backend = Some(ScriptingBackend::Mono);
+        }
+    }
+    if xamarin && !file_exists(dist_folder.join(ASSEMBLY_FOLDERS[1])) {
+        info.notes.push(String::from("The .NET assemblies of the application are packed in an \
+                                      assembly store, which is not supported, so they were not \
+                                      scanned."));
+    }
+
+    for assembly in get_app_assemblies(&dist_folder) {
+        match read_binary(&dist_folder.join(&assembly)) {
+            Ok(Some(bytes)) => {
+                let mut strings = extract_utf16_strings(&bytes, MIN_STRING_LENGTH);
+                strings.extend(extract_strings(&bytes, MIN_STRING_LENGTH));
+                info.assemblies.push(assembly.to_string_lossy().into_owned());
+                extracted.push((assembly, get_strings_code(strings)));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                print_warning(format!("The assembly {} could not be read: {}",
+                                      assembly.display(),
+                                      e),
+                              config.is_verbose())
+            }
+        }
+    }
+    results.add_section(get_report_section(&info));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The Unity and .NET code was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("Unity and .NET code analyzed.");
+    }
+
+    extracted
+}
+
+/// Generates the Unity and .NET section of the report
+fn get_report_section(info: &UnityInfo) -> ReportSection {
+    let title = if info.unity { "Unity" } else { ".NET (Xamarin)" };
+    let mut section = ReportSection::new("unity", title, SECTION_ORDER);
+    section.set_data(info);
+    let mut rows = Vec::new();
+    if info.unity {
+        rows.push(vec![String::from("Unity version"),
+                       Results::html_escape(info.version
+                           .as_ref()
+                           .map_or("Unknown", |v| v.as_str()))]);
+        rows.push(vec![String::from("Scripting backend"),
+                       String::from(info.backend.map_or("Unknown", |b| b.get_label()))]);
+        if let Some(version) = info.metadata_version {
+            rows.push(vec![String::from("IL2CPP metadata version"), format!("{}", version)]);
+        }
+        rows.push(vec![String::from("Known vulnerabilities"), format!("{}", info.advisories)]);
+    }
+    rows.push(vec![String::from("Scanned assemblies"),
+                   Results::html_escape(&info.assemblies.join("\n")).replace("\n", "<br>")]);
+    rows.push(vec![String::from("Notes"),
+                   Results::html_escape(&info.notes.join("\n")).replace("\n", "<br>")]);
+    section.set_html_table(&["Property", "Value"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_unity_version, get_metadata_version, extract_utf16_strings,
+                is_app_assembly};
+
+    #[test]
+    fn it_find_unity_version() {
+        let header = b"\x00\x00\x00\x16\x00\x00\x00\x11\x00\x00\x00\x002021.3.16f1\x00\x0d\x00";
+        assert_eq!(find_unity_version(header), Some(String::from("2021.3.16f1")));
+        assert_eq!(find_unity_version(b"\x00\x006000.0.23f1\x00"),
+                   Some(String::from("6000.0.23f1")));
+        assert_eq!(find_unity_version(b"version 1.2.3"), None);
+    }
+
+    #[test]
+    fn it_metadata_version() {
+        assert_eq!(get_metadata_version(&[0xaf, 0x1b, 0xb1, 0xfa, 0x1d, 0x00, 0x00, 0x00]),
+                   Some(29));
+        assert_eq!(get_metadata_version(&[0x12, 0x34, 0x56, 0x78, 0x1d, 0x00, 0x00, 0x00]),
+                   None);
+    }
+
+    #[test]
+    fn it_extract_utf16_strings() {
+        let data = b"\x01h\x00t\x00t\x00p\x00s\x00:\x00/\x00/\x00a\x00.\x00i\x00o\x00\x00\x00\
+                     a\x00b\x00";
+        assert_eq!(extract_utf16_strings(data, 6), vec![String::from("https://a.io")]);
+    }
+
+    #[test]
+    fn it_app_assembly() {
+        assert!(is_app_assembly("Assembly-CSharp.dll"));
+        assert!(is_app_assembly("MyGame.Core.dll"));
+        assert!(!is_app_assembly("UnityEngine.CoreModule.dll"));
+        assert!(!is_app_assembly("System.Net.Http.dll"));
+        assert!(!is_app_assembly("Assembly-CSharp.pdb"));
+    }
+}
@@ -0,0 +1,310 @@
+use std::result;
+use std::path::PathBuf;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use serde_json::value::Value;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability};
+use results::{Results, ReportSection, Vulnerability};
+use download::{request_url, get_sha256};
+
+/// Order of the VirusTotal section in the report, right after the malware likelihood
+const SECTION_ORDER: u32 = 2;
+
+/// Endpoint of the VirusTotal API for files
+const API_URL: &'static str = "https://www.virustotal.com/api/v3/files";
+
+/// Web page of the VirusTotal report of a file
+const REPORT_URL: &'static str = "https://www.virustotal.com/gui/file";
+
+/// Maximum time in seconds for the hash lookup and for the upload of the file
+const LOOKUP_TIMEOUT: u32 = 30;
+const UPLOAD_TIMEOUT: u32 = 300;
+
+/// Maximum size of the files that can be uploaded directly to the API
+const MAX_UPLOAD_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Number of engines detecting the application from which the finding is high
+const HIGH_DETECTIONS: u64 = 3;
+
+/// Detection report of a file in VirusTotal
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileReport {
+    malicious: u64,
+    suspicious: u64,
+    /// Engines that analyzed the file and gave a verdict
+    total: u64,
+    /// Engines detecting the file, with their detection name
+    detections: Vec<(String, String)>,
+}
+
+impl Serialize for FileReport {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("FileReport", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "malicious", self.malicious));
+        try!(serializer.serialize_struct_elt(&mut state, "suspicious", self.suspicious));
+        try!(serializer.serialize_struct_elt(&mut state, "total", self.total));
+        try!(serializer.serialize_struct_elt(&mut state, "detections", &self.detections));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Result of the VirusTotal lookup of the application
+struct Lookup {
+    sha256: String,
+    status: &'static str,
+    report: Option<FileReport>,
+}
+
+impl Lookup {
+    fn get_link(&self) -> String {
+        format!("{}/{}", REPORT_URL, self.sha256)
+    }
+}
+
+impl Serialize for Lookup {
+    fn serialize<S>(&self, serializer: &mut S) -> result::Result<(), S::Error>
+        where S: Serializer
+    {
+        let mut state = try!(serializer.serialize_struct("Lookup", 4));
+        try!(serializer.serialize_struct_elt(&mut state, "sha256", self.sha256.as_str()));
+        try!(serializer.serialize_struct_elt(&mut state, "status", self.status));
+        try!(serializer.serialize_struct_elt(&mut state, "link", self.get_link()));
+        try!(serializer.serialize_struct_elt(&mut state, "report", &self.report));
+        try!(serializer.serialize_struct_end(state));
+        Ok(())
+    }
+}
+
+/// Gets the value at the given path of keys of a JSON object
+fn get_path<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().fold(Some(value), |value, key| {
+        value.and_then(|v| v.as_object()).and_then(|o| o.get(*key))
+    })
+}
+
+/// Parses the detection report of a file from the response of the API
+fn parse_file_report(json: &str) -> Option<FileReport> {
+    let value: Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return None,
+    };
+    let stats = match get_path(&value, &["data", "attributes", "last_analysis_stats"]) {
+        Some(s) => s,
+        None => return None,
+    };
+    let get_stat = |key: &str| get_path(stats, &[key]).and_then(|v| v.as_u64()).unwrap_or(0);
+
+    let mut detections = Vec::new();
+    if let Some(results) = get_path(&value, &["data", "attributes", "last_analysis_results"])
+        .and_then(|r| r.as_object()) {
+        for (engine, result) in results {
+            let category = get_path(result, &["category"]).and_then(|c| c.as_str());
+            if category == Some("malicious") || category == Some("suspicious") {
+                let name = get_path(result, &["result"]).and_then(|r| r.as_str()).unwrap_or("");
+                detections.push((engine.clone(), String::from(name)));
+            }
+        }
+    }
+
+    Some(FileReport {
+        malicious: get_stat("malicious"),
+        suspicious: get_stat("suspicious"),
+        total: get_stat("malicious") + get_stat("suspicious") + get_stat("undetected") +
+               get_stat("harmless"),
+        detections: detections,
+    })
+}
+
+/// Looks up the SHA-256 hash of the application in VirusTotal, and embeds the detections in the
+/// report
+///
+/// Nothing is sent unless the lookup is enabled, and the application file is only uploaded if
+/// VirusTotal doesn't know it and uploads are explicitly allowed in the configuration.
+pub fn virustotal_analysis(config: &Config, results: &mut Results) {
+    let vt_config = config.get_virustotal_config();
+    if !vt_config.is_enabled() {
+        return;
+    }
+    let api_key = match vt_config.get_api_key() {
+        Some(k) => k,
+        None => {
+            print_warning("The VirusTotal lookup is enabled, but there is no API key in the \
+                           [virustotal] section of the configuration. It will be skipped.",
+                          config.is_verbose());
+            return;
+        }
+    };
+    if config.is_verbose() {
+        println!("Looking up the application in VirusTotal.");
+    }
+
+    let apk = PathBuf::from(format!("{}/{}.apk",
+                                    config.get_downloads_folder(),
+                                    config.get_app_id()));
+    let (size, sha256) = match get_sha256(&apk) {
+        Ok(h) => h,
+        Err(e) => {
+            print_warning(format!("The hash of the application could not be calculated, so it \
+                                   will not be looked up in VirusTotal. More info: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+    let headers = [format!("x-apikey: {}", api_key)];
+    let response = request_url(&format!("{}/{}", API_URL, sha256),
+                               &headers,
+                               None,
+                               LOOKUP_TIMEOUT);
+    let mut lookup = Lookup {
+        sha256: sha256,
+        status: "Not found",
+        report: None,
+    };
+    match response {
+        Ok((200, body)) => {
+            lookup.report = parse_file_report(&body);
+            lookup.status = if lookup.report.is_some() {
+                "Found"
+            } else {
+                "Invalid response"
+            };
+        }
+        Ok((404, _)) if vt_config.allows_upload() && size <= MAX_UPLOAD_SIZE => {
+            if config.is_verbose() {
+                println!("VirusTotal doesn't know the application, so it will be uploaded.");
+            }
+            lookup.status = match request_url(API_URL, &headers, Some(&apk), UPLOAD_TIMEOUT) {
+                Ok((200, _)) => "Uploaded for analysis",
+                _ => "Not found, upload failed",
+            };
+        }
+        Ok((404, _)) => {}
+        Ok((401, _)) | Ok((403, _)) => {
+            print_warning("The VirusTotal API key was rejected.", config.is_verbose());
+            return;
+        }
+        Ok((429, _)) => {
+            print_warning("The VirusTotal API quota has been exceeded, so the application was \
+                           not looked up.",
+                          config.is_verbose());
+            return;
+        }
+        Ok((status, _)) => {
+            print_warning(format!("VirusTotal returned an unexpected status code: {}", status),
+                          config.is_verbose());
+            return;
+        }
+        Err(e) => {
+            print_warning(format!("There was an error looking up the application in \
+                                   VirusTotal: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    }
+
+    if let Some(ref report) = lookup.report {
+        if report.malicious > 0 {
+            add_detection_finding(report, &lookup.get_link(), config, results);
+        }
+    }
+    results.add_section(get_report_section(&lookup));
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The application was looked up in VirusTotal correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("VirusTotal lookup done.");
+    }
+}
+
+/// Adds the finding for an application detected as malicious by antivirus engines
+fn add_detection_finding(report: &FileReport, link: &str, config: &Config, results: &mut Results) {
+    let criticity = if report.malicious >= HIGH_DETECTIONS {
+        Criticity::High
+    } else {
+        Criticity::Medium
+    };
+    let description = format!("{} of {} antivirus engines in VirusTotal detect the application \
+                               as malicious. The detections should be reviewed, since they can \
+                               come from bundled adware or riskware libraries too. Full report: \
+                               {}",
+                              report.malicious,
+                              report.total,
+                              link);
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), criticity);
+    }
+
+    let vuln = Vulnerability::new(criticity,
+                                  String::from("Detected by antivirus engines"),
+                                  description,
+                                  None as Option<&str>,
+                                  None,
+                                  None,
+                                  None);
+    results.add_vulnerability(vuln);
+}
+
+/// Generates the VirusTotal section of the report
+fn get_report_section(lookup: &Lookup) -> ReportSection {
+    let title = match lookup.report {
+        Some(ref r) => format!("VirusTotal ({} of {} detections)", r.malicious, r.total),
+        None => format!("VirusTotal ({})", lookup.status.to_lowercase()),
+    };
+    let mut section = ReportSection::new("virustotal", title.as_str(), SECTION_ORDER);
+    section.set_data(lookup);
+
+    let link = lookup.get_link();
+    let mut rows = vec![vec![String::from("SHA-256"), format!("<code>{}</code>", lookup.sha256)],
+                        vec![String::from("Status"), String::from(lookup.status)],
+                        vec![String::from("Report"),
+                             format!("<a href=\"{0}\">{0}</a>", Results::html_escape(&link))]];
+    if let Some(ref report) = lookup.report {
+        rows.push(vec![String::from("Detections"),
+                       format!("{} malicious and {} suspicious of {} engines",
+                               report.malicious,
+                               report.suspicious,
+                               report.total)]);
+        for &(ref engine, ref name) in &report.detections {
+            rows.push(vec![Results::html_escape(engine), Results::html_escape(name)]);
+        }
+    }
+    section.set_html_table(&["Engine", "Result"], &rows);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_file_report;
+
+    #[test]
+    fn it_parse_file_report() {
+        let json = r#"{"data": {"id": "abc", "attributes": {
+            "last_analysis_stats": {"malicious": 2, "suspicious": 1, "undetected": 60,
+                                    "harmless": 0, "type-unsupported": 10, "timeout": 0},
+            "last_analysis_results": {
+                "EngineA": {"category": "malicious", "result": "Android.Trojan.SmsSend"},
+                "EngineB": {"category": "undetected", "result": null},
+                "EngineC": {"category": "suspicious", "result": "Riskware"}
+            }}}}"#;
+        let report = parse_file_report(json).unwrap();
+        assert_eq!(report.malicious, 2);
+        assert_eq!(report.suspicious, 1);
+        assert_eq!(report.total, 63);
+        assert_eq!(report.detections,
+                   vec![(String::from("EngineA"), String::from("Android.Trojan.SmsSend")),
+                        (String::from("EngineC"), String::from("Riskware"))]);
+
+        assert!(parse_file_report("{\"error\": {\"code\": \"NotFoundError\"}}").is_none());
+        assert!(parse_file_report("not json").is_none());
+    }
+}
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use colored::Colorize;
+
+use {Config, Criticity, print_warning, print_vulnerability, get_code};
+use results::{Results, Vulnerability};
+use super::manifest::Manifest;
+use super::code::get_line_for;
+use super::java::{get_java_files, get_class_name, get_enclosing_method, get_block_end,
+                  describe_location};
+use super::read_text_file;
+
+/// API level from which only methods annotated with `@JavascriptInterface` are exposed to
+/// JavaScript
+const JAVASCRIPT_INTERFACE_SINCE_SDK: i32 = 17;
+
+/// WebView misconfiguration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebViewIssue {
+    JavaScriptEnabled,
+    JavascriptInterface,
+    FileAccessFromFileUrls,
+    UniversalAccessFromFileUrls,
+    IgnoredSslErrors,
+    CleartextContent,
+}
+
+impl WebViewIssue {
+    pub fn get_criticity(&self) -> Criticity {
+        match *self {
+            WebViewIssue::JavaScriptEnabled => Criticity::Warning,
+            WebViewIssue::JavascriptInterface |
+            WebViewIssue::UniversalAccessFromFileUrls |
+            WebViewIssue::IgnoredSslErrors => Criticity::High,
+            WebViewIssue::FileAccessFromFileUrls |
+            WebViewIssue::CleartextContent => Criticity::Medium,
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        match *self {
+            WebViewIssue::JavaScriptEnabled => "WebView with JavaScript enabled",
+            WebViewIssue::JavascriptInterface => "WebView JavaScript interface",
+            WebViewIssue::FileAccessFromFileUrls => "WebView file access from file URLs",
+            WebViewIssue::UniversalAccessFromFileUrls => {
+                "WebView universal access from file URLs"
+            }
+            WebViewIssue::IgnoredSslErrors => "WebView ignores SSL errors",
+            WebViewIssue::CleartextContent => "WebView loads content over HTTP",
+        }
+    }
+
+    /// Gets the description of the issue found in the given location
+    pub fn get_description(&self, location: &str) -> String {
+        match *self {
+            WebViewIssue::JavaScriptEnabled => {
+                format!("JavaScript is enabled for a WebView in {}. If the WebView loads \
+                         content that is not fully trusted, it could be used for Cross Site \
+                         Scripting attacks. Check that JavaScript is actually needed.",
+                        location)
+            }
+            WebViewIssue::JavascriptInterface => {
+                format!("A JavaScript interface is added to a WebView in {}, and the \
+                         application supports devices older than Android 4.2, or targets \
+                         them. In those devices, any JavaScript code loaded by the WebView can \
+                         use reflection through the interface to execute arbitrary code in the \
+                         application.",
+                        location)
+            }
+            WebViewIssue::FileAccessFromFileUrls => {
+                format!("A WebView allows JavaScript in file URLs to read other local files in \
+                         {}. A malicious HTML file could read the private files of the \
+                         application.",
+                        location)
+            }
+            WebViewIssue::UniversalAccessFromFileUrls => {
+                format!("A WebView allows JavaScript in file URLs to access content from any \
+                         origin in {}. A malicious HTML file could read the private files of \
+                         the application and send them to a remote server.",
+                        location)
+            }
+            WebViewIssue::IgnoredSslErrors => {
+                format!("The SSL errors of a WebView are ignored in {}, so it accepts any \
+                         certificate. The content loaded in the WebView can be modified with a \
+                         Man in the Middle attack.",
+                        location)
+            }
+            WebViewIssue::CleartextContent => {
+                format!("A WebView loads a page over HTTP in {}. The content can be read and \
+                         modified with a Man in the Middle attack. Use HTTPS instead.",
+                        location)
+            }
+        }
+    }
+}
+
+/// Regular expressions used to find WebView misconfigurations
+struct Detector {
+    checks: Vec<(WebViewIssue, Regex)>,
+    ssl_error_handler: Regex,
+    proceed: Regex,
+}
+
+impl Detector {
+    fn new() -> Detector {
+        Detector {
+            checks: vec![(WebViewIssue::JavaScriptEnabled,
+                          Regex::new(r"\.setJavaScriptEnabled\(\s*true\s*\)").unwrap()),
+                         (WebViewIssue::JavascriptInterface,
+                          Regex::new(r"\.addJavascriptInterface\s*\(").unwrap()),
+                         (WebViewIssue::FileAccessFromFileUrls,
+                          Regex::new(r"\.setAllowFileAccessFromFileURLs\(\s*true\s*\)").unwrap()),
+                         (WebViewIssue::UniversalAccessFromFileUrls,
+                          Regex::new(r"\.setAllowUniversalAccessFromFileURLs\(\s*true\s*\)")
+                              .unwrap()),
+                         (WebViewIssue::CleartextContent,
+                          Regex::new(r#"\.(?:loadUrl|postUrl)\s*\(\s*"http://"#).unwrap())],
+            ssl_error_handler: Regex::new(r"\bonReceivedSslError\s*\([^)]*\)[^{;]*\{").unwrap(),
+            proceed: Regex::new(r"\.proceed\s*\(\s*\)").unwrap(),
+        }
+    }
+}
+
+/// Finds the WebView misconfigurations in the code, with their position in the code
+fn find_issues(code: &str, detector: &Detector) -> Vec<(WebViewIssue, usize, usize)> {
+    let mut issues = Vec::new();
+    for &(issue, ref regex) in &detector.checks {
+        for (s, e) in regex.find_iter(code) {
+            issues.push((issue, s, e));
+        }
+    }
+
+    for (_, e) in detector.ssl_error_handler.find_iter(code) {
+        let end = match get_block_end(code, e - 1) {
+            Some(end) => end,
+            None => continue,
+        };
+        if let Some((s, pe)) = detector.proceed.find(&code[e..end]) {
+            issues.push((WebViewIssue::IgnoredSslErrors, e + s, e + pe));
+        }
+    }
+    issues.sort_by_key(|&(_, s, _)| s);
+    issues
+}
+
+/// Analyzes the WebView configuration in the decompiled code of the application
+pub fn webview_analysis(manifest: Option<&Manifest>, config: &Config, results: &mut Results) {
+    if config.is_verbose() {
+        println!("Checking the WebView configuration in the code.");
+    }
+
+    let dist_folder = PathBuf::from(format!("{}/{}",
+                                            config.get_dist_folder(),
+                                            config.get_app_id()));
+    let files = match get_java_files(&dist_folder) {
+        Ok(f) => f,
+        Err(e) => {
+            print_warning(format!("An error occurred when reading files for the WebView \
+                                   analysis, it will be skipped. Error: {}",
+                                  e),
+                          config.is_verbose());
+            return;
+        }
+    };
+    // Without the manifest, the interfaces are reported, since the SDK can't be checked
+    let interface_exploitable = match manifest {
+        Some(m) => {
+            m.get_min_sdk() < JAVASCRIPT_INTERFACE_SINCE_SDK ||
+            m.get_target_sdk().unwrap_or(m.get_min_sdk()) < JAVASCRIPT_INTERFACE_SINCE_SDK
+        }
+        None => true,
+    };
+
+    let detector = Detector::new();
+    for path in files {
+        let code = match read_text_file(&path) {
+            Ok(Some(c)) => c,
+            Ok(None) => continue,
+            Err(e) => {
+                print_warning(format!("There was an error reading the file {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose());
+                continue;
+            }
+        };
+        if !code.contains("WebView") && !code.contains("WebSettings") {
+            continue;
+        }
+
+        let relative = path.strip_prefix(&dist_folder).unwrap();
+        for (issue, s, e) in find_issues(&code, &detector) {
+            if issue == WebViewIssue::JavascriptInterface && !interface_exploitable {
+                continue;
+            }
+            add_finding(issue, &code, relative, s, e, config, results);
+        }
+    }
+
+    if config.is_verbose() {
+        println!("");
+        println!("{}", "The WebView configuration was analyzed correctly!".green());
+        println!("");
+    } else if !config.is_quiet() {
+        println!("WebView configuration analyzed.");
+    }
+}
+
+/// Adds a finding for the issue, located in the class and method where it was found
+fn add_finding(issue: WebViewIssue,
+               code: &str,
+               path: &Path,
+               start: usize,
+               end: usize,
+               config: &Config,
+               results: &mut Results) {
+    let class = get_class_name(path).unwrap_or_else(|| path.to_string_lossy().into_owned());
+    let location = describe_location(&class, get_enclosing_method(code, start));
+    let description = issue.get_description(&location);
+    let start_line = get_line_for(start, code);
+    let end_line = get_line_for(end, code);
+
+    if config.is_verbose() {
+        print_vulnerability(description.as_str(), issue.get_criticity());
+    }
+
+    let vuln = Vulnerability::new(issue.get_criticity(),
+                                  String::from(issue.get_label()),
+                                  description,
+                                  Some(path),
+                                  Some(start_line),
+                                  Some(end_line),
+                                  Some(get_code(code, start_line, end_line)));
+    results.add_vulnerability(vuln);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WebViewIssue, Detector, find_issues};
+
+    #[test]
+    fn it_find_issues() {
+        let code = "public class Browser extends Activity {
+    protected void onCreate(Bundle bundle) {
+        WebSettings settings = this.webView.getSettings();
+        settings.setJavaScriptEnabled(true);
+        settings.setAllowFileAccessFromFileURLs(false);
+        settings.setAllowUniversalAccessFromFileURLs(true);
+        this.webView.addJavascriptInterface(new Bridge(), \"bridge\");
+        this.webView.setWebViewClient(new WebViewClient() {
+            public void onReceivedSslError(WebView v, SslErrorHandler h, SslError e) {
+                h.proceed();
+            }
+        });
+        this.webView.loadUrl(\"http://example.com/\");
+        this.webView.loadUrl(\"https://example.com/\");
+    }
+}";
+        let issues: Vec<_> = find_issues(code, &Detector::new())
+            .into_iter()
+            .map(|(issue, _, _)| issue)
+            .collect();
+        assert_eq!(issues,
+                   vec![WebViewIssue::JavaScriptEnabled,
+                        WebViewIssue::UniversalAccessFromFileUrls,
+                        WebViewIssue::JavascriptInterface,
+                        WebViewIssue::IgnoredSslErrors,
+                        WebViewIssue::CleartextContent]);
+    }
+
+    #[test]
+    fn it_ssl_errors_handled() {
+        let code = "public void onReceivedSslError(WebView v, SslErrorHandler h, SslError e) {
+    h.cancel();
+}
+
+public void retry() {
+    this.handler.proceed();
+}";
+        assert!(find_issues(code, &Detector::new()).is_empty());
+    }
+}
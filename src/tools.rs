@@ -0,0 +1,292 @@
+use std::process::Command;
+use std::time::Duration;
+use std::collections::BTreeMap;
+
+use {Config, file_exists, run_command};
+
+/// Minimum Java version supported by APKTool, Dex2Jar and JD-CMD
+const MIN_JAVA_VERSION: u32 = 7;
+
+/// Major versions of APKTool and Dex2Jar supported by SUPER
+const APKTOOL_MAJOR_VERSION: u32 = 2;
+const DEX2JAR_MAJOR_VERSION: u32 = 2;
+
+/// Minimum JD-CMD version supported by SUPER
+const MIN_JD_CMD_VERSION: [u32; 2] = [0, 9];
+
+/// Maximum time in seconds for a tool to print its version
+const VERSION_TIMEOUT: u64 = 60;
+
+/// Runs Java and the external tools to detect their versions, and gets an error for each of them
+/// that can't be run or whose version is not supported
+///
+/// Tools whose files don't exist are skipped, since they are already reported as missing, and so
+/// are Dex2Jar and JD-CMD if the code won't be decompiled.
+pub fn check_tools(config: &Config) -> Vec<String> {
+    let mut errors = Vec::new();
+    let java = match get_command_output(Command::new("java").arg("-version")) {
+        Ok(output) => parse_java_version(&output),
+        Err(e) => {
+            errors.push(format!("Java could not be executed: {}. Java 1.{} or newer must be \
+                                 installed and in the PATH",
+                                e,
+                                MIN_JAVA_VERSION));
+            return errors;
+        }
+    };
+    match java {
+        Some(ref version) if get_java_major_version(version)
+            .map_or(false, |v| v >= MIN_JAVA_VERSION) => {}
+        Some(version) => {
+            errors.push(format!("the detected Java version {} is not supported, Java 1.{} or \
+                                 newer is needed",
+                                version,
+                                MIN_JAVA_VERSION));
+            return errors;
+        }
+        None => {
+            errors.push(format!("the Java version could not be detected, Java 1.{} or newer \
+                                 is needed",
+                                MIN_JAVA_VERSION));
+            return errors;
+        }
+    }
+
+    if file_exists(config.get_apktool_file()) {
+        let output = get_command_output(&mut get_apktool_command(config));
+        if let Some(error) = check_version("APKTool",
+                                           config.get_apktool_file(),
+                                           output.map(|o| parse_apktool_version(&o)),
+                                           |v| v.first() == Some(&APKTOOL_MAJOR_VERSION),
+                                           &format!("{}.x", APKTOOL_MAJOR_VERSION)) {
+            errors.push(error);
+        }
+    }
+
+    let dex2jar = get_dex2jar_script(config);
+    if config.is_decompile() && file_exists(&dex2jar) {
+        // Without arguments, Dex2Jar prints its usage, which ends with its version
+        let output = get_command_output(&mut Command::new(&dex2jar));
+        if let Some(error) = check_version("Dex2Jar",
+                                           &dex2jar,
+                                           output.map(|o| parse_dex2jar_version(&o)),
+                                           |v| v.first() == Some(&DEX2JAR_MAJOR_VERSION),
+                                           &format!("{}.x", DEX2JAR_MAJOR_VERSION)) {
+            errors.push(error);
+        }
+    }
+
+    if config.is_decompile() && file_exists(config.get_jd_cmd_file()) {
+        let output = get_command_output(&mut get_jd_cmd_command(config));
+        if let Some(error) = check_version("JD-CMD",
+                                           config.get_jd_cmd_file(),
+                                           output.map(|o| parse_jd_cmd_version(&o)),
+                                           |v| v >= &MIN_JD_CMD_VERSION[..],
+                                           &format!("{}.{} or newer",
+                                                    MIN_JD_CMD_VERSION[0],
+                                                    MIN_JD_CMD_VERSION[1])) {
+            errors.push(error);
+        }
+    }
+    errors
+}
+
+/// Gets the versions of Java and of the external tools used in the analysis, by their names,
+/// to record them in the report
+///
+/// Tools that can't be run or whose versions can't be detected are left out, and so are Dex2Jar
+/// and JD-CMD if the code won't be decompiled.
+pub fn get_tool_versions(config: &Config) -> BTreeMap<String, String> {
+    let mut versions = BTreeMap::new();
+    let java = get_command_output(Command::new("java").arg("-version"));
+    if let Some(version) = java.ok().and_then(|o| parse_java_version(&o)) {
+        let _ = versions.insert(String::from("java"), version);
+    } else {
+        return versions;
+    }
+
+    if file_exists(config.get_apktool_file()) {
+        let output = get_command_output(&mut get_apktool_command(config));
+        if let Some(version) = output.ok().and_then(|o| parse_apktool_version(&o)) {
+            let _ = versions.insert(String::from("apktool"), version);
+        }
+    }
+    let dex2jar = get_dex2jar_script(config);
+    if config.is_decompile() && file_exists(&dex2jar) {
+        let output = get_command_output(&mut Command::new(&dex2jar));
+        if let Some(version) = output.ok().and_then(|o| parse_dex2jar_version(&o)) {
+            let _ = versions.insert(String::from("dex2jar"), version);
+        }
+    }
+    if config.is_decompile() && file_exists(config.get_jd_cmd_file()) {
+        let output = get_command_output(&mut get_jd_cmd_command(config));
+        if let Some(version) = output.ok().and_then(|o| parse_jd_cmd_version(&o)) {
+            let _ = versions.insert(String::from("jd_cmd"), version);
+        }
+    }
+    versions
+}
+
+/// Gets the command printing the version of APKTool
+fn get_apktool_command(config: &Config) -> Command {
+    let mut command = Command::new("java");
+    command.arg("-jar").arg(config.get_apktool_file()).arg("--version");
+    command
+}
+
+/// Gets the command printing the help of JD-CMD, which starts with its version
+fn get_jd_cmd_command(config: &Config) -> Command {
+    let mut command = Command::new("java");
+    command.arg("-jar").arg(config.get_jd_cmd_file()).arg("--help");
+    command
+}
+
+/// Gets the path of the Dex2Jar script for the current platform
+fn get_dex2jar_script(config: &Config) -> String {
+    if cfg!(target_family = "windows") {
+        format!("{}\\d2j-dex2jar.bat", config.get_dex2jar_folder())
+    } else {
+        format!("{}/d2j-dex2jar.sh", config.get_dex2jar_folder())
+    }
+}
+
+/// Checks the detected version of a tool, and gets the error to report, if any
+fn check_version<F>(tool: &str,
+                    path: &str,
+                    version: Result<Option<String>, String>,
+                    is_supported: F,
+                    supported: &str)
+                    -> Option<String>
+    where F: Fn(&[u32]) -> bool
+{
+    match version {
+        Ok(Some(ref version)) if is_supported(&get_version_numbers(version)) => None,
+        Ok(Some(version)) => {
+            Some(format!("the {} version {} of `{}` is not supported, {} {} is needed. It can be \
+                          installed with `super setup`",
+                         tool,
+                         version,
+                         path,
+                         tool,
+                         supported))
+        }
+        Ok(None) => {
+            Some(format!("the {} version of `{}` could not be detected, {} {} is needed. It \
+                          can be installed with `super setup`",
+                         tool,
+                         path,
+                         tool,
+                         supported))
+        }
+        Err(e) => Some(format!("{} could not be executed from `{}`: {}", tool, path, e)),
+    }
+}
+
+/// Runs the command, and gets both its standard output and error
+fn get_command_output(command: &mut Command) -> Result<String, String> {
+    match run_command(command, Some(Duration::from_secs(VERSION_TIMEOUT)), false) {
+        Ok(output) => {
+            Ok(format!("{}\n{}",
+                       String::from_utf8_lossy(&output.stdout),
+                       String::from_utf8_lossy(&output.stderr)))
+        }
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+/// Parses the output of `java -version`, such as `java version "1.8.0_101"`
+fn parse_java_version(output: &str) -> Option<String> {
+    output.lines()
+        .filter_map(|line| line.find("version \"").map(|i| &line[i + 9..]))
+        .filter_map(|rest| rest.find('"').map(|end| String::from(&rest[..end])))
+        .next()
+}
+
+/// Parses the output of `apktool --version`, which is just the version
+fn parse_apktool_version(output: &str) -> Option<String> {
+    output.lines()
+        .map(|line| line.trim())
+        .find(|line| line.chars().next().map_or(false, |c| c.is_digit(10)))
+        .map(String::from)
+}
+
+/// Parses the usage of Dex2Jar, ending with a line such as `version: reader-2.0, ...`
+fn parse_dex2jar_version(output: &str) -> Option<String> {
+    output.find("reader-").map(|i| {
+        output[i + 7..]
+            .chars()
+            .take_while(|c| *c != ',' && !c.is_whitespace())
+            .collect()
+    })
+}
+
+/// Parses the help of JD-CMD, starting with a line such as `jd-cli version 0.9.1.Final`
+fn parse_jd_cmd_version(output: &str) -> Option<String> {
+    output.find("jd-cli version ")
+        .and_then(|i| output[i + 15..].split_whitespace().next())
+        .map(String::from)
+}
+
+/// Gets the numeric components of a version, such as `[0, 9, 1]` for `0.9.1.Final`
+fn get_version_numbers(version: &str) -> Vec<u32> {
+    let mut numbers = Vec::new();
+    for part in version.split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_digit(10)).collect();
+        match digits.parse() {
+            Ok(n) => numbers.push(n),
+            Err(_) => break,
+        }
+    }
+    numbers
+}
+
+/// Gets the major version of Java, such as 8 for `1.8.0_101` or 11 for `11.0.2`
+fn get_java_major_version(version: &str) -> Option<u32> {
+    let numbers = get_version_numbers(version);
+    match numbers.first() {
+        Some(&1) => numbers.get(1).cloned(),
+        Some(&major) => Some(major),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_java_version, parse_apktool_version, parse_dex2jar_version,
+                parse_jd_cmd_version, get_version_numbers, get_java_major_version};
+
+    #[test]
+    fn it_parse_tool_versions() {
+        let java = "java version \"1.8.0_101\"\nJava(TM) SE Runtime Environment (build \
+                    1.8.0_101-b13)\n";
+        assert_eq!(parse_java_version(java), Some(String::from("1.8.0_101")));
+        let openjdk = "openjdk version \"17.0.15\" 2025-04-15\nOpenJDK Runtime Environment\n";
+        assert_eq!(parse_java_version(openjdk), Some(String::from("17.0.15")));
+        assert_eq!(parse_java_version("java: command not found"), None);
+
+        assert_eq!(parse_apktool_version("2.2.0\n\n"), Some(String::from("2.2.0")));
+        assert_eq!(parse_apktool_version("Exception in thread \"main\""), None);
+
+        let dex2jar = "d2j-dex2jar -- convert dex to jar\n -h,--help  Print this help message\n\
+                       version: reader-2.0, translator-2.0, ir-2.0\n";
+        assert_eq!(parse_dex2jar_version(dex2jar), Some(String::from("2.0")));
+        assert_eq!(parse_dex2jar_version("usage: d2j-dex2jar"), None);
+
+        let jd_cmd = "jd-cli version 0.9.1.Final - Copyright (C) 2015 Josef Cacek\n";
+        assert_eq!(parse_jd_cmd_version(jd_cmd), Some(String::from("0.9.1.Final")));
+        assert_eq!(parse_jd_cmd_version("Unknown option"), None);
+    }
+
+    #[test]
+    fn it_get_version_numbers() {
+        assert_eq!(get_version_numbers("0.9.1.Final"), vec![0, 9, 1]);
+        assert_eq!(get_version_numbers("2.2.0"), vec![2, 2, 0]);
+        assert_eq!(get_version_numbers("Final"), Vec::<u32>::new());
+
+        assert_eq!(get_java_major_version("1.7.0_79"), Some(7));
+        assert_eq!(get_java_major_version("1.8.0_101"), Some(8));
+        assert_eq!(get_java_major_version("11.0.2"), Some(11));
+        assert_eq!(get_java_major_version("9-ea"), Some(9));
+        assert_eq!(get_java_major_version("unknown"), None);
+    }
+}
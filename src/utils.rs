@@ -1,14 +1,20 @@
-use std::{fs, io};
+use std::{fs, io, thread};
 use std::path::Path;
 use std::io::{Read, Write};
-use std::time::Duration;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{channel, TryRecvError};
+use std::time::{Duration, Instant};
 use std::thread::sleep;
 
 use xml::reader::{EventReader, XmlEvent};
 use xml::ParserConfig;
 use colored::Colorize;
 
-use super::{Criticity, Result, Config};
+use super::{Criticity, Result, Config, Error};
+use arsc::ResourceTable;
+
+/// Interval between the checks of the commands run with a timeout, in milliseconds
+const COMMAND_POLL_INTERVAL: u64 = 100;
 
 pub const PARSER_CONFIG: ParserConfig = ParserConfig {
     trim_whitespace: true,
@@ -73,23 +79,137 @@ pub fn get_code(code: &str, s_line: usize, e_line: usize) -> String {
     result
 }
 
+/// Runs the command, collecting its output, and kills it with all its child processes if it
+/// runs for longer than the timeout
+///
+/// Returns `Error::TimedOut` with the output generated until then if the command was killed.
+/// External tools such as dex2jar can hang forever with malformed applications, and they start
+/// their own Java processes, so killing only the command would leave them running.
+///
+/// The output is also echoed to the standard output and error of SUPER as it's generated if
+/// `echo` is set.
+pub fn run_command(command: &mut Command, timeout: Option<Duration>, echo: bool) -> Result<Output> {
+    let mut child = try!(command.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn());
+    // Both outputs are read in their own threads, so that the command never blocks on them
+    let stdout = child.stdout.take().unwrap();
+    let stdout = thread::spawn(move || read_pipe(stdout, if echo { Some(false) } else { None }));
+    let stderr = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || read_pipe(stderr, if echo { Some(true) } else { None }));
+    // The command is also waited for in its own thread, since the supported Rust versions can't
+    // check if a child process exited without blocking
+    let pid = child.id();
+    let (sender, receiver) = channel();
+    let _ = thread::spawn(move || sender.send(child.wait()));
+
+    let start = Instant::now();
+    loop {
+        match receiver.try_recv() {
+            Ok(status) => {
+                return Ok(Output {
+                    status: try!(status),
+                    stdout: stdout.join().unwrap_or_else(|_| Vec::new()),
+                    stderr: stderr.join().unwrap_or_else(|_| Vec::new()),
+                });
+            }
+            Err(TryRecvError::Disconnected) => return Err(Error::Unknown),
+            Err(TryRecvError::Empty) => {}
+        }
+        if timeout.map_or(false, |t| start.elapsed() > t) {
+            kill_process_tree(pid);
+            let _ = receiver.recv();
+            // The pipes are closed once the whole tree is killed, so the output generated until
+            // the timeout can be read for the logs
+            return Err(Error::TimedOut(stdout.join().unwrap_or_else(|_| Vec::new()),
+                                       stderr.join().unwrap_or_else(|_| Vec::new())));
+        }
+        sleep(Duration::from_millis(COMMAND_POLL_INTERVAL));
+    }
+}
+
+/// Reads the pipe until it's closed, echoing what's read to the standard error if `echo` is
+/// `Some(true)`, or to the standard output if it's `Some(false)`
+fn read_pipe<R: Read>(mut pipe: R, echo: Option<bool>) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        match pipe.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                output.extend_from_slice(&buffer[..read]);
+                let _ = match echo {
+                    Some(true) => io::stderr().write_all(&buffer[..read]),
+                    Some(false) => io::stdout().write_all(&buffer[..read]),
+                    None => Ok(()),
+                };
+            }
+        }
+    }
+    output
+}
+
+/// Kills the process with the given ID and all its descendants
+#[cfg(target_family = "unix")]
+pub fn kill_process_tree(pid: u32) {
+    // Descendants are collected before killing anything, since they are reparented once their
+    // parent dies
+    let mut pids = vec![pid.to_string()];
+    let mut i = 0;
+    while i < pids.len() {
+        if let Ok(output) = Command::new("pgrep").arg("-P").arg(&pids[i]).output() {
+            pids.extend(String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .map(String::from));
+        }
+        i += 1;
+    }
+    let _ = Command::new("kill").arg("-KILL").args(&pids).output();
+}
+
+/// Kills the process with the given ID and all its descendants
+#[cfg(target_family = "windows")]
+pub fn kill_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .arg("/T")
+        .arg("/F")
+        .arg("/PID")
+        .arg(pid.to_string())
+        .output();
+}
+
+/// Escapes a field of a CSV file, quoting it if it contains commas, quotes or line breaks
+pub fn csv_escape(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
 pub fn file_exists<P: AsRef<Path>>(path: P) -> bool {
     path.as_ref().exists()
 }
 
 pub fn get_string(label: &str, config: &Config) -> Result<String> {
-    let mut file = try!(fs::File::open({
-        let path = format!("{}/{}/res/values-en/strings.xml",
-                           config.get_dist_folder(),
-                           config.get_app_id());
-        if file_exists(&path) {
-            path
-        } else {
-            format!("{}/{}/res/values/strings.xml",
-                    config.get_dist_folder(),
-                    config.get_app_id())
-        }
-    }));
+    let path = format!("{}/{}/res/values-en/strings.xml",
+                       config.get_dist_folder(),
+                       config.get_app_id());
+    let path = if file_exists(&path) {
+        path
+    } else {
+        format!("{}/{}/res/values/strings.xml",
+                config.get_dist_folder(),
+                config.get_app_id())
+    };
+    if !file_exists(&path) {
+        // APKTool failed or is not installed, so the string is taken from the resource table of
+        // the .apk file
+        let resources = try!(ResourceTable::load(config));
+        return Ok(String::from(resources.get_string(label).unwrap_or("")));
+    }
+    let mut file = try!(fs::File::open(path));
 
     let mut code = String::new();
     try!(file.read_to_string(&mut code));
@@ -125,9 +245,11 @@ pub fn get_string(label: &str, config: &Config) -> Result<String> {
 
 #[cfg(test)]
 mod test {
-    use {get_code, file_exists};
+    use {Error, get_code, file_exists, run_command};
     use std::fs;
     use std::fs::File;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn it_get_code() {
@@ -197,4 +319,26 @@ mod test {
         fs::remove_file("test.txt").unwrap();
         assert!(!file_exists("test.txt"));
     }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn it_run_command() {
+        let output = run_command(Command::new("echo").arg("hello"),
+                                 Some(Duration::from_secs(10)),
+                                 false)
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+
+        let start = Instant::now();
+        match run_command(Command::new("sh").arg("-c").arg("echo started; sleep 30; echo done"),
+                          Some(Duration::from_millis(500)),
+                          false) {
+            Err(Error::TimedOut(stdout, _)) => {
+                assert_eq!(String::from_utf8_lossy(&stdout), "started\n");
+            }
+            r => panic!("the command did not time out: {:?}", r),
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
 }
@@ -0,0 +1,229 @@
+//! Temporary workspaces for the applications given as a stream or an in-memory buffer, instead
+//! of as a file in the downloads folder.
+//!
+//! Each workspace has its own downloads and dist folders, and it's removed when it's dropped, so
+//! scanning services don't need to manage the layout of the downloads folder. This module also
+//! applies the cleanup policies of the dist folder, so that large-scale use doesn't fill the disk
+//! with decompiled trees.
+
+use std::{env, fs, process};
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::Local;
+
+use archive::Archive;
+use axml::decode_xml;
+use config::ArchiveConfig;
+use server::is_package_name;
+use {Config, Result, Error, print_warning};
+
+/// Temporary folder where an application is analyzed
+pub struct Workspace {
+    folder: PathBuf,
+    package: String,
+}
+
+impl Workspace {
+    /// Creates a temporary workspace with the given contents of an APK file, in the workspace
+    /// folder of the configuration
+    ///
+    /// The package name of the application is read from its manifest if it's not given.
+    pub fn new(apk: &[u8], package: Option<&str>, config: &Config) -> Result<Workspace> {
+        let package = match package {
+            Some(p) => String::from(p),
+            None => try!(get_package_name(apk)),
+        };
+        if !is_package_name(&package) {
+            return Err(Error::Config);
+        }
+
+        let folder = get_workspace_folder(config).join(format!("super-{}-{}",
+                                                               process::id(),
+                                                               Local::now()
+                                                                   .format("%Y%m%d%H%M%S%f")));
+        let workspace = Workspace {
+            folder: folder,
+            package: package,
+        };
+        try!(fs::create_dir_all(workspace.get_downloads_folder()));
+        try!(fs::create_dir_all(workspace.get_dist_folder()));
+        let mut f = try!(File::create(workspace.get_downloads_folder()
+            .join(format!("{}.apk", workspace.package))));
+        try!(f.write_all(apk));
+        Ok(workspace)
+    }
+
+    /// Gets the downloads folder of the workspace, with the APK file of the application
+    pub fn get_downloads_folder(&self) -> PathBuf {
+        self.folder.join("downloads")
+    }
+
+    /// Gets the folder of the workspace where the application is decompressed
+    pub fn get_dist_folder(&self) -> PathBuf {
+        self.folder.join("dist")
+    }
+
+    /// Sets the downloads and dist folders of the workspace, and the package of the application,
+    /// in the configuration
+    pub fn configure(&self, config: &mut Config) {
+        config.set_app_id(&self.package);
+        config.set_downloads_folder(self.get_downloads_folder().to_string_lossy().into_owned());
+        config.set_dist_folder(self.get_dist_folder().to_string_lossy().into_owned());
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.folder);
+    }
+}
+
+/// Reads the package name of the application from the manifest in its APK file
+pub fn get_package_name(apk: &[u8]) -> Result<String> {
+    let mut archive = try!(Archive::new(Cursor::new(apk), &ArchiveConfig::default()));
+    let manifest = try!(decode_xml(&try!(archive.read("AndroidManifest.xml")), None));
+    match get_manifest_package(&manifest) {
+        Some(package) => Ok(package),
+        None => Err(Error::ParseError),
+    }
+}
+
+/// Gets the `package` attribute of the decoded manifest
+fn get_manifest_package(manifest: &str) -> Option<String> {
+    manifest.find(" package=\"").and_then(|start| {
+        let value = &manifest[start + 10..];
+        value.find('"').map(|end| String::from(&value[..end]))
+    })
+}
+
+/// Gets the folder where the temporary workspaces are created
+fn get_workspace_folder(config: &Config) -> PathBuf {
+    match config.get_workspace_folder() {
+        Some(folder) => PathBuf::from(folder),
+        None => env::temp_dir(),
+    }
+}
+
+/// Removes the dist folder of the analyzed application, unless the `keep_dist` policy of the
+/// configuration keeps it
+pub fn clean_dist_folder(config: &Config, failed: bool) {
+    if config.get_keep_dist().should_keep(failed) {
+        return;
+    }
+
+    let path = Path::new(config.get_dist_folder()).join(config.get_app_id());
+    if path.exists() {
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                if config.is_verbose() {
+                    println!("The dist folder of the application has been removed, since the \
+                              `keep_dist` policy is `{}`.",
+                             config.get_keep_dist().as_str());
+                }
+            }
+            Err(e) => {
+                print_warning(format!("There was an error when removing the dist folder \
+                                       {}: {}",
+                                      path.display(),
+                                      e),
+                              config.is_verbose())
+            }
+        }
+    }
+}
+
+/// Removes the folders of the dist folder, and the leftover temporary workspaces, older than the
+/// `dist_max_age` of the configuration
+///
+/// The dist folder of the application being analyzed is never removed.
+pub fn remove_old_artifacts(config: &Config) {
+    let max_age = match config.get_dist_max_age() {
+        0 => return,
+        days => Duration::from_secs(days * 24 * 60 * 60),
+    };
+
+    let removed = remove_old_folders(Path::new(config.get_dist_folder()),
+                                     max_age,
+                                     |name| name != config.get_app_id()) +
+                  remove_old_folders(&get_workspace_folder(config),
+                                     max_age,
+                                     |name| name.starts_with("super-"));
+    if removed > 0 && config.is_verbose() {
+        println!("{} folders older than {} days have been removed.",
+                 removed,
+                 config.get_dist_max_age());
+    }
+}
+
+/// Removes the subfolders of the given folder accepted by the filter that were last modified
+/// before the given age, and returns how many of them were removed
+fn remove_old_folders<F>(folder: &Path, max_age: Duration, filter: F) -> usize
+    where F: Fn(&str) -> bool
+{
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_old = match entry.metadata() {
+            Ok(ref m) if m.is_dir() => {
+                m.modified()
+                    .ok()
+                    .and_then(|modified| now.duration_since(modified).ok())
+                    .map_or(false, |age| age >= max_age)
+            }
+            _ => false,
+        };
+        if is_old && filter(&name) && fs::remove_dir_all(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{get_manifest_package, remove_old_folders};
+
+    #[test]
+    fn it_get_manifest_package() {
+        let manifest = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest \
+                        xmlns:android=\"http://schemas.android.com/apk/res/android\" \
+                        android:versionCode=\"1\" package=\"com.example.app\">\n</manifest>";
+        assert_eq!(get_manifest_package(manifest), Some(String::from("com.example.app")));
+        assert_eq!(get_manifest_package("<manifest>\n</manifest>"), None);
+    }
+
+    #[test]
+    fn it_remove_old_folders() {
+        let folder = Path::new("remove_old_folders_test");
+        fs::create_dir_all(folder.join("super-1234-20170101")).unwrap();
+        fs::create_dir_all(folder.join("com.example.app")).unwrap();
+
+        assert_eq!(remove_old_folders(folder, Duration::from_secs(24 * 60 * 60), |_| true),
+                   0);
+        assert_eq!(remove_old_folders(folder,
+                                      Duration::from_secs(0),
+                                      |name| name.starts_with("super-")),
+                   1);
+        assert!(!folder.join("super-1234-20170101").exists());
+        assert!(folder.join("com.example.app").exists());
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}